@@ -0,0 +1,374 @@
+// AVIF decoder using dav1d. Like libjxl, dav1d's Rust bindings aren't vendored, so this
+// declares the few functions of the libdav1d 1.0+ API it needs and links the system
+// library, and reads the AV1 items out of the AVIF container itself.
+
+use crate::{ BenchmarkDecoder, ImageData };
+use std::ffi::{ c_int, c_void };
+use std::ptr;
+
+// The settings passed to dav1d_open. Only the leading fields are set; the rest is left as
+// dav1d_default_settings fills it, with room to spare for the reserved bytes.
+#[repr(C, align(8))]
+struct Dav1dSettings {
+    n_threads: c_int,
+    max_frame_delay: c_int,
+    rest: [u8; 248],
+}
+
+#[repr(C)]
+struct Dav1dData {
+    data: *const u8,
+    sz: usize,
+    reference: *mut c_void,
+    // Dav1dDataProps
+    props: [u64; 6],
+}
+
+// The leading fields of Dav1dSequenceHeader, up to the color range
+#[repr(C)]
+struct Dav1dSequenceHeader {
+    profile: c_int,
+    max_width: c_int,
+    max_height: c_int,
+    layout: c_int,
+    pri: c_int,
+    trc: c_int,
+    mtrx: c_int,
+    chr: c_int,
+    hbd: c_int,
+    color_range: c_int,
+}
+
+// Dav1dPicture up to its parameters, with room to spare for the fields after them
+#[repr(C)]
+struct Dav1dPicture {
+    seq_hdr: *const Dav1dSequenceHeader,
+    frame_hdr: *const c_void,
+    data: [*const u8; 3],
+    stride: [isize; 2],
+    w: c_int,
+    h: c_int,
+    layout: c_int,
+    bpc: c_int,
+    rest: [u64; 64],
+}
+
+const DAV1D_PIXEL_LAYOUT_I400: c_int = 0;
+const DAV1D_PIXEL_LAYOUT_I420: c_int = 1;
+const DAV1D_PIXEL_LAYOUT_I422: c_int = 2;
+// DAV1D_ERR(EAGAIN)
+const DAV1D_EAGAIN: c_int = -11;
+
+#[link(name = "dav1d")]
+extern "C" {
+    fn dav1d_default_settings(s: *mut Dav1dSettings);
+    fn dav1d_open(c_out: *mut *mut c_void, s: *const Dav1dSettings) -> c_int;
+    fn dav1d_close(c_out: *mut *mut c_void);
+    fn dav1d_data_wrap(
+        data: *mut Dav1dData,
+        buf: *const u8,
+        sz: usize,
+        free_callback: unsafe extern "C" fn(buf: *const u8, cookie: *mut c_void),
+        cookie: *mut c_void
+    ) -> c_int;
+    fn dav1d_data_unref(data: *mut Dav1dData);
+    fn dav1d_send_data(c: *mut c_void, data: *mut Dav1dData) -> c_int;
+    fn dav1d_get_picture(c: *mut c_void, out: *mut Dav1dPicture) -> c_int;
+    fn dav1d_picture_unref(p: *mut Dav1dPicture);
+}
+
+// The AV1 data is borrowed for the whole decode, so there is nothing to free
+unsafe extern "C" fn borrowed(_buf: *const u8, _cookie: *mut c_void) {}
+
+// Closes a dav1d decoder when dropped
+struct Decoder(*mut c_void);
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        unsafe { dav1d_close(&mut self.0) }
+    }
+}
+
+// Releases a picture when dropped
+struct Picture(Dav1dPicture);
+
+impl Drop for Picture {
+    fn drop(&mut self) {
+        unsafe { dav1d_picture_unref(&mut self.0) }
+    }
+}
+
+impl Picture {
+    // The sample of a plane at (x, y), at the picture's bit depth
+    fn sample(&self, plane: usize, x: usize, y: usize) -> u16 {
+        let stride = self.0.stride[plane.min(1)];
+        unsafe {
+            let row = self.0.data[plane].offset((y as isize) * stride);
+            if self.0.bpc == 8 {
+                *row.add(x) as u16
+            } else {
+                *row.cast::<u16>().add(x)
+            }
+        }
+    }
+
+    fn sequence_header(&self) -> &Dav1dSequenceHeader {
+        unsafe { &*self.0.seq_hdr }
+    }
+}
+
+// Decodes one AV1 image on a single thread, like every other codec here
+fn decode_av1(data: &[u8]) -> Result<Picture, Box<dyn std::error::Error>> {
+    let mut settings = Dav1dSettings { n_threads: 0, max_frame_delay: 0, rest: [0; 248] };
+    unsafe { dav1d_default_settings(&mut settings) };
+    settings.n_threads = 1;
+    settings.max_frame_delay = 1;
+
+    let mut decoder = Decoder(ptr::null_mut());
+    if unsafe { dav1d_open(&mut decoder.0, &settings) } < 0 {
+        return Err("dav1d failed to create a decoder".into());
+    }
+    let mut av1 = Dav1dData {
+        data: ptr::null(),
+        sz: 0,
+        reference: ptr::null_mut(),
+        props: [0; 6],
+    };
+    let status = unsafe {
+        dav1d_data_wrap(&mut av1, data.as_ptr(), data.len(), borrowed, ptr::null_mut())
+    };
+    if status < 0 {
+        return Err("dav1d failed to wrap the AV1 data".into());
+    }
+
+    let mut picture = Picture(unsafe { std::mem::zeroed() });
+    let result = loop {
+        let sent = unsafe { dav1d_send_data(decoder.0, &mut av1) };
+        if sent < 0 && sent != DAV1D_EAGAIN {
+            break Err("dav1d failed to decode the AV1 data".into());
+        }
+        match unsafe { dav1d_get_picture(decoder.0, &mut picture.0) } {
+            0 => {
+                break Ok(());
+            }
+            DAV1D_EAGAIN if av1.sz > 0 => {}
+            _ => {
+                break Err("dav1d returned no picture".into());
+            }
+        }
+    };
+    unsafe { dav1d_data_unref(&mut av1) };
+    result.map(|()| picture)
+}
+
+// The ISO base media boxes in `data`, as type and payload
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let size = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+        let kind: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let (header, size) = match size {
+            0 => (8, data.len()),
+            1 => (16, u64::from_be_bytes(data.get(8..16)?.try_into().ok()?) as usize),
+            _ => (8, size),
+        };
+        let payload = data.get(header..size)?;
+        data = &data[size..];
+        Some((kind, payload))
+    })
+}
+
+// Reads big-endian integers of any size up to 8 bytes
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn uint(&mut self, size: usize) -> Result<u64, Box<dyn std::error::Error>> {
+        if size > 8 || self.0.len() < size {
+            return Err("Truncated AVIF box".into());
+        }
+        let (bytes, rest) = self.0.split_at(size);
+        self.0 = rest;
+        Ok(bytes.iter().fold(0, |value, &byte| (value << 8) | u64::from(byte)))
+    }
+
+    // The version of a full box, skipping its flags
+    fn version(&mut self) -> Result<u8, Box<dyn std::error::Error>> {
+        Ok((self.uint(4)? >> 24) as u8)
+    }
+}
+
+// The AV1 data of the primary item of an AVIF file and of its auxiliary image, which AVIF
+// files from ravif use for alpha
+fn items(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), Box<dyn std::error::Error>> {
+    let (_, meta) = boxes(data)
+        .find(|(kind, _)| kind == b"meta")
+        .ok_or("No meta box")?;
+    let meta = meta.get(4..).ok_or("Truncated meta box")?;
+    let child = |name: &[u8; 4]| {
+        boxes(meta)
+            .find(|(kind, _)| kind == name)
+            .map(|(_, payload)| payload)
+    };
+
+    let mut pitm = Reader(child(b"pitm").ok_or("No primary item")?);
+    let primary = match pitm.version()? {
+        0 => pitm.uint(2)?,
+        _ => pitm.uint(4)?,
+    };
+
+    // The auxiliary images of the primary item, from its `auxl` references
+    let mut auxiliary = Vec::new();
+    if let Some(iref) = child(b"iref") {
+        let mut reader = Reader(iref);
+        let id_size = if reader.version()? == 0 { 2 } else { 4 };
+        for (_, payload) in boxes(reader.0).filter(|(kind, _)| kind == b"auxl") {
+            let mut reference = Reader(payload);
+            let from = reference.uint(id_size)?;
+            for _ in 0..reference.uint(2)? {
+                if reference.uint(id_size)? == primary {
+                    auxiliary.push(from);
+                }
+            }
+        }
+    }
+
+    // The locations of the items, as a single extent of the file each
+    let mut iloc = Reader(child(b"iloc").ok_or("No item locations")?);
+    let version = iloc.version()?;
+    let sizes = iloc.uint(2)?;
+    let (offset_size, length_size) = ((sizes >> 12) as usize, ((sizes >> 8) & 15) as usize);
+    let (base_offset_size, index_size) = (((sizes >> 4) & 15) as usize, (sizes & 15) as usize);
+    let count = iloc.uint(if version < 2 { 2 } else { 4 })?;
+    let mut color = None;
+    let mut alpha = None;
+    for _ in 0..count {
+        let id = iloc.uint(if version < 2 { 2 } else { 4 })?;
+        let construction_method = if version == 0 { 0 } else { iloc.uint(2)? & 15 };
+        // The data reference index, 0 for this file
+        iloc.uint(2)?;
+        let base_offset = iloc.uint(base_offset_size)?;
+        let extents = iloc.uint(2)?;
+        let mut extent = None;
+        for _ in 0..extents {
+            if version > 0 {
+                iloc.uint(index_size)?;
+            }
+            let offset = base_offset + iloc.uint(offset_size)?;
+            let length = iloc.uint(length_size)?;
+            extent = Some((offset as usize, length as usize));
+        }
+        if id != primary && !auxiliary.contains(&id) {
+            continue;
+        }
+        let Some((offset, length)) = extent.filter(|_| extents == 1 && construction_method == 0) else {
+            return Err("Only AVIF items stored as one extent of the file are supported".into());
+        };
+        let item = data.get(offset..offset + length).ok_or("AVIF item outside the file")?;
+        if id == primary {
+            color = Some(item);
+        } else {
+            alpha = Some(item);
+        }
+    }
+    Ok((color.ok_or("No primary item location")?, alpha))
+}
+
+// Converts a sample at the picture's bit depth to 8 bits
+fn to_8_bit(value: f32, bpc: c_int) -> u8 {
+    let max = ((1 << bpc) - 1) as f32;
+    ((value * 255.0) / max).round().clamp(0.0, 255.0) as u8
+}
+
+// Converts the YUV (or monochrome) picture of the primary item to RGB, scaled to 8 bits,
+// taking the matrix coefficients and range from its sequence header
+fn to_rgb(picture: &Picture, bytes_per_pixel: usize, pixels: &mut [u8]) {
+    let header = picture.sequence_header();
+    let (width, height) = (picture.0.w as usize, picture.0.h as usize);
+    let bpc = picture.0.bpc;
+    let scale = (1 << (bpc - 8)) as f32;
+    let max = ((1 << bpc) - 1) as f32;
+    let (ss_x, ss_y) = match picture.0.layout {
+        DAV1D_PIXEL_LAYOUT_I420 => (1, 1),
+        DAV1D_PIXEL_LAYOUT_I422 => (1, 0),
+        _ => (0, 0),
+    };
+    // Limited range stretches 16..235 (16..240 for chroma) at 8 bits to the full range
+    let (y_offset, y_scale, c_scale) = if header.color_range != 0 {
+        (0.0, 1.0, 1.0)
+    } else {
+        (16.0 * scale, max / (219.0 * scale), max / (224.0 * scale))
+    };
+    // Kr and Kb of BT.709, BT.2020 and, for the rest, BT.601
+    let (kr, kb) = match header.mtrx {
+        1 => (0.2126, 0.0722),
+        9 | 10 => (0.2627, 0.0593),
+        _ => (0.299, 0.114),
+    };
+    let half = (1 << (bpc - 1)) as f32;
+
+    for y in 0..height {
+        let row = &mut pixels[y * width * bytes_per_pixel..(y + 1) * width * bytes_per_pixel];
+        for (x, pixel) in row.chunks_exact_mut(bytes_per_pixel).enumerate() {
+            let luma = ((picture.sample(0, x, y) as f32) - y_offset) * y_scale;
+            let rgb = if picture.0.layout == DAV1D_PIXEL_LAYOUT_I400 {
+                [luma; 3]
+            } else {
+                let (cx, cy) = (x >> ss_x, y >> ss_y);
+                let cb = ((picture.sample(1, cx, cy) as f32) - half) * c_scale;
+                let cr = ((picture.sample(2, cx, cy) as f32) - half) * c_scale;
+                if header.mtrx == 0 {
+                    // Identity: the planes hold G, B and R
+                    [cr + half, luma, cb + half]
+                } else {
+                    let r = luma + (2.0 - 2.0 * kr) * cr;
+                    let b = luma + (2.0 - 2.0 * kb) * cb;
+                    [r, (luma - kr * r - kb * b) / (1.0 - kr - kb), b]
+                }
+            };
+            for (channel, value) in pixel.iter_mut().zip(rgb) {
+                *channel = to_8_bit(value, bpc);
+            }
+        }
+    }
+}
+
+// Implementation for AVIF decoder
+pub struct AvifDecoder;
+
+impl BenchmarkDecoder for AvifDecoder {
+    fn name(&self) -> &str {
+        "AVIF"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<ImageData, Box<dyn std::error::Error>> {
+        let (color, alpha) = items(data)?;
+        let picture = decode_av1(color)?;
+        let alpha = alpha.map(decode_av1).transpose()?;
+        let (width, height) = (picture.0.w as usize, picture.0.h as usize);
+        let bytes_per_pixel = if alpha.is_some() { 4 } else { 3 };
+        let mut pixels = vec![0; width * height * bytes_per_pixel];
+        to_rgb(&picture, bytes_per_pixel, &mut pixels);
+
+        if let Some(alpha) = &alpha {
+            if (alpha.0.w as usize, alpha.0.h as usize) != (width, height) {
+                return Err("The AVIF alpha has another size than the image".into());
+            }
+            let limited = alpha.sequence_header().color_range == 0;
+            let scale = (1 << (alpha.0.bpc - 8)) as f32;
+            let max = ((1 << alpha.0.bpc) - 1) as f32;
+            for (i, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+                let mut value = alpha.sample(0, i % width, i / width) as f32;
+                if limited {
+                    value = ((value - 16.0 * scale) * max) / (219.0 * scale);
+                }
+                pixel[3] = to_8_bit(value, alpha.0.bpc);
+            }
+        }
+
+        Ok(ImageData {
+            pixels,
+            width: width as u32,
+            height: height as u32,
+            bytes_per_pixel,
+        })
+    }
+}
@@ -0,0 +1,99 @@
+// Saved results of an earlier run, and the comparison of a run with them.
+//
+// A baseline is the JSON report, so any `--format json` output can serve as one.
+// Only the speed of each codec is compared.
+
+use serde::Deserialize;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::{ BenchmarkReport, BenchmarkResults };
+
+#[derive(Deserialize)]
+struct Baseline {
+    encode: Vec<BaselineResults>,
+    decode: Vec<BaselineResults>,
+}
+
+#[derive(Deserialize)]
+struct BaselineResults {
+    format: String,
+    speed_images_s: f64,
+}
+
+pub fn save(path: &Path, report: &BenchmarkReport) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+    eprintln!("Saved the baseline to {}", path.display());
+    Ok(())
+}
+
+// Prints the change in speed of every codec in the baseline, and fails if any slowed
+// down by more than `threshold` percent
+pub fn compare(
+    path: &Path,
+    report: &BenchmarkReport,
+    threshold: f64
+) -> Result<(), Box<dyn std::error::Error>> {
+    let baseline: Baseline = serde_json
+        ::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|e| format!("Invalid baseline {}: {}", path.display(), e))?;
+
+    let rule = "|-----------+------------+------------+------------+----------+------------|";
+    let mut table = format!("\nCOMPARISON WITH {}\n{}\n", path.display(), rule);
+    table.push_str("| Operation | Format     | Baseline   | Current    | Change   | Result     |\n");
+    table.push_str("|           |            | (imgs/s)   | (imgs/s)   | (%)      |            |\n");
+    table.push_str(rule);
+    table.push('\n');
+
+    let mut regressions = Vec::new();
+    let operations = [
+        ("encode", &baseline.encode, report.encode),
+        ("decode", &baseline.decode, report.decode),
+    ];
+    for (operation, base_results, results) in operations {
+        for base in base_results {
+            let current = results.iter().find(|r| r.encoder_name == base.format);
+            let (current_speed, change, result) = match current {
+                Some(BenchmarkResults { speed_images_s, .. }) if base.speed_images_s > 0.0 => {
+                    let change = (speed_images_s / base.speed_images_s - 1.0) * 100.0;
+                    let result = if change < -threshold {
+                        regressions.push(format!("{} {} ({:+.1}%)", operation, base.format, change));
+                        "REGRESSED"
+                    } else if change > threshold {
+                        "improved"
+                    } else {
+                        "ok"
+                    };
+                    (format!("{:.2}", speed_images_s), format!("{:+.1}", change), result)
+                }
+                Some(r) => (format!("{:.2}", r.speed_images_s), String::new(), "n/a"),
+                None => (String::new(), String::new(), "not run"),
+            };
+            writeln!(
+                table,
+                "| {:<9} | {:<10} | {:<10.2} | {:<10} | {:<8} | {:<10} |",
+                operation,
+                base.format,
+                base.speed_images_s,
+                current_speed,
+                change,
+                result
+            )?;
+        }
+    }
+    table.push_str(rule);
+    table.push('\n');
+    print!("{}", table);
+
+    if regressions.is_empty() {
+        Ok(())
+    } else {
+        Err(
+            format!(
+                "Speed regressed by more than {}% for {}",
+                threshold,
+                regressions.join(", ")
+            ).into()
+        )
+    }
+}
@@ -0,0 +1,84 @@
+// HEIC codecs using libheif, built with the `heif` feature. libheif needs its HEVC
+// plugins (libde265 to decode, x265 to encode); without them the codecs fail and are
+// skipped.
+
+use crate::{ BenchmarkDecoder, BenchmarkEncoder, ImageData };
+use libheif_rs::{
+    Channel,
+    ColorSpace,
+    CompressionFormat,
+    EncoderQuality,
+    HeifContext,
+    Image,
+    LibHeif,
+    RgbChroma,
+};
+
+// Implementation for HEIC encoder. Lossless HEVC needs 4:4:4 chroma, which few
+// decoders support, so like JPEG it is benchmarked at a fixed quality.
+pub struct HeifEncoder {
+    pub quality: u8,
+}
+
+impl BenchmarkEncoder for HeifEncoder {
+    fn name(&self) -> &str {
+        "HEIC"
+    }
+
+    fn encode(&self, image: &ImageData) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let chroma = if image.bytes_per_pixel == 4 { RgbChroma::Rgba } else { RgbChroma::Rgb };
+        let mut heif_image = Image::new(image.width, image.height, ColorSpace::Rgb(chroma))?;
+        heif_image.create_plane(Channel::Interleaved, image.width, image.height, 8)?;
+
+        // libheif pads its rows, so copy the pixels over one row at a time
+        let plane = heif_image.planes_mut().interleaved.ok_or("No interleaved HEIC plane")?;
+        let row_len = (image.width as usize) * image.bytes_per_pixel;
+        for (src, dst) in image.pixels.chunks_exact(row_len).zip(plane.data.chunks_mut(plane.stride)) {
+            dst[..row_len].copy_from_slice(src);
+        }
+
+        let lib_heif = LibHeif::new();
+        let mut encoder = lib_heif.encoder_for_format(CompressionFormat::Hevc)?;
+        encoder.set_quality(EncoderQuality::Lossy(self.quality))?;
+        let mut context = HeifContext::new()?;
+        context.encode_image(&heif_image, &mut encoder, None)?;
+        Ok(context.write_to_bytes()?)
+    }
+}
+
+// Implementation for HEIC decoder
+pub struct HeifDecoder;
+
+impl BenchmarkDecoder for HeifDecoder {
+    fn name(&self) -> &str {
+        "HEIC"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<ImageData, Box<dyn std::error::Error>> {
+        let context = HeifContext::read_from_bytes(data)?;
+        let handle = context.primary_image_handle()?;
+        let (chroma, bytes_per_pixel) = if handle.has_alpha_channel() {
+            (RgbChroma::Rgba, 4)
+        } else {
+            (RgbChroma::Rgb, 3)
+        };
+        let decoded = LibHeif::new().decode(&handle, ColorSpace::Rgb(chroma), None)?;
+
+        let planes = decoded.planes();
+        let plane = planes.interleaved.ok_or("No interleaved HEIC plane")?;
+        let row_len = (plane.width as usize) * bytes_per_pixel;
+        let pixels = plane.data
+            .chunks(plane.stride)
+            .take(plane.height as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect();
+
+        Ok(ImageData {
+            pixels,
+            width: plane.width,
+            height: plane.height,
+            bytes_per_pixel,
+        })
+    }
+}
@@ -0,0 +1,281 @@
+// JPEG XL codecs using libjxl, built with the `jxl` feature. Neither libjxl nor its
+// Rust bindings are vendored, so this declares the few functions of the libjxl 0.7+
+// API it needs and links the system library.
+
+use crate::{ BenchmarkDecoder, BenchmarkEncoder, ImageData };
+use std::ffi::c_void;
+use std::ptr;
+
+#[repr(C)]
+#[derive(Default)]
+struct JxlBasicInfo {
+    have_container: i32,
+    xsize: u32,
+    ysize: u32,
+    bits_per_sample: u32,
+    exponent_bits_per_sample: u32,
+    intensity_target: f32,
+    min_nits: f32,
+    relative_to_max_display: i32,
+    linear_below: f32,
+    uses_original_profile: i32,
+    have_preview: i32,
+    have_animation: i32,
+    orientation: u32,
+    num_color_channels: u32,
+    num_extra_channels: u32,
+    alpha_bits: u32,
+    alpha_exponent_bits: u32,
+    alpha_premultiplied: i32,
+    preview: [u32; 2],
+    animation: [u32; 4],
+    intrinsic_xsize: u32,
+    intrinsic_ysize: u32,
+    padding: Padding,
+}
+
+// The reserved bytes at the end of JxlBasicInfo
+#[repr(C)]
+struct Padding([u8; 100]);
+
+impl Default for Padding {
+    fn default() -> Self {
+        Padding([0; 100])
+    }
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct JxlColorEncoding {
+    color_space: u32,
+    white_point: u32,
+    white_point_xy: [f64; 2],
+    primaries: u32,
+    primaries_red_xy: [f64; 2],
+    primaries_green_xy: [f64; 2],
+    primaries_blue_xy: [f64; 2],
+    transfer_function: u32,
+    gamma: f64,
+    rendering_intent: u32,
+}
+
+#[repr(C)]
+struct JxlPixelFormat {
+    num_channels: u32,
+    data_type: u32,
+    endianness: u32,
+    align: usize,
+}
+
+impl JxlPixelFormat {
+    fn rgb8(bytes_per_pixel: usize) -> Self {
+        JxlPixelFormat {
+            num_channels: bytes_per_pixel as u32,
+            data_type: JXL_TYPE_UINT8,
+            endianness: JXL_NATIVE_ENDIAN,
+            align: 0,
+        }
+    }
+}
+
+const JXL_TYPE_UINT8: u32 = 2;
+const JXL_NATIVE_ENDIAN: u32 = 0;
+const JXL_ENC_SUCCESS: i32 = 0;
+const JXL_ENC_NEED_MORE_OUTPUT: i32 = 2;
+const JXL_ENC_FRAME_SETTING_EFFORT: i32 = 0;
+const JXL_DEC_SUCCESS: i32 = 0;
+const JXL_DEC_NEED_IMAGE_OUT_BUFFER: i32 = 5;
+const JXL_DEC_BASIC_INFO: i32 = 0x40;
+const JXL_DEC_FULL_IMAGE: i32 = 0x1000;
+
+#[link(name = "jxl")]
+extern "C" {
+    fn JxlEncoderCreate(memory_manager: *const c_void) -> *mut c_void;
+    fn JxlEncoderDestroy(enc: *mut c_void);
+    fn JxlEncoderInitBasicInfo(info: *mut JxlBasicInfo);
+    fn JxlEncoderSetBasicInfo(enc: *mut c_void, info: *const JxlBasicInfo) -> i32;
+    fn JxlColorEncodingSetToSRGB(color_encoding: *mut JxlColorEncoding, is_gray: i32);
+    fn JxlEncoderSetColorEncoding(enc: *mut c_void, color: *const JxlColorEncoding) -> i32;
+    fn JxlEncoderFrameSettingsCreate(enc: *mut c_void, source: *const c_void) -> *mut c_void;
+    fn JxlEncoderSetFrameLossless(frame_settings: *mut c_void, lossless: i32) -> i32;
+    fn JxlEncoderFrameSettingsSetOption(frame_settings: *mut c_void, option: i32, value: i64) -> i32;
+    fn JxlEncoderAddImageFrame(
+        frame_settings: *const c_void,
+        pixel_format: *const JxlPixelFormat,
+        buffer: *const c_void,
+        size: usize
+    ) -> i32;
+    fn JxlEncoderCloseInput(enc: *mut c_void);
+    fn JxlEncoderProcessOutput(enc: *mut c_void, next_out: *mut *mut u8, avail_out: *mut usize) -> i32;
+
+    fn JxlDecoderCreate(memory_manager: *const c_void) -> *mut c_void;
+    fn JxlDecoderDestroy(dec: *mut c_void);
+    fn JxlDecoderSubscribeEvents(dec: *mut c_void, events_wanted: i32) -> i32;
+    fn JxlDecoderSetInput(dec: *mut c_void, data: *const u8, size: usize) -> i32;
+    fn JxlDecoderCloseInput(dec: *mut c_void);
+    fn JxlDecoderProcessInput(dec: *mut c_void) -> i32;
+    fn JxlDecoderGetBasicInfo(dec: *const c_void, info: *mut JxlBasicInfo) -> i32;
+    fn JxlDecoderImageOutBufferSize(
+        dec: *const c_void,
+        format: *const JxlPixelFormat,
+        size: *mut usize
+    ) -> i32;
+    fn JxlDecoderSetImageOutBuffer(
+        dec: *mut c_void,
+        format: *const JxlPixelFormat,
+        buffer: *mut c_void,
+        size: usize
+    ) -> i32;
+}
+
+// Destroys a libjxl encoder or decoder when dropped
+struct Owned(*mut c_void, unsafe extern "C" fn(*mut c_void));
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        unsafe { (self.1)(self.0) }
+    }
+}
+
+fn check(status: i32, what: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if status == 0 { Ok(()) } else { Err(format!("libjxl failed to {}", what).into()) }
+}
+
+// Implementation for lossless JPEG XL encoder. Effort goes from 1 (fastest) to 9.
+pub struct JxlEncoder {
+    pub effort: i64,
+}
+
+impl BenchmarkEncoder for JxlEncoder {
+    fn name(&self) -> &str {
+        "JPEG XL"
+    }
+
+    fn encode(&self, image: &ImageData) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let has_alpha = image.bytes_per_pixel == 4;
+        unsafe {
+            let enc = JxlEncoderCreate(ptr::null());
+            if enc.is_null() {
+                return Err("libjxl failed to create an encoder".into());
+            }
+            let enc = Owned(enc, JxlEncoderDestroy);
+
+            let mut info = JxlBasicInfo::default();
+            JxlEncoderInitBasicInfo(&mut info);
+            info.xsize = image.width;
+            info.ysize = image.height;
+            info.bits_per_sample = 8;
+            info.num_color_channels = 3;
+            // Lossless needs the original color space instead of XYB
+            info.uses_original_profile = 1;
+            if has_alpha {
+                info.num_extra_channels = 1;
+                info.alpha_bits = 8;
+            }
+            check(JxlEncoderSetBasicInfo(enc.0, &info), "set the basic info")?;
+
+            let mut color = JxlColorEncoding::default();
+            JxlColorEncodingSetToSRGB(&mut color, 0);
+            check(JxlEncoderSetColorEncoding(enc.0, &color), "set the color encoding")?;
+
+            let settings = JxlEncoderFrameSettingsCreate(enc.0, ptr::null());
+            check(JxlEncoderSetFrameLossless(settings, 1), "enable lossless")?;
+            check(
+                JxlEncoderFrameSettingsSetOption(settings, JXL_ENC_FRAME_SETTING_EFFORT, self.effort),
+                "set the effort"
+            )?;
+            let format = JxlPixelFormat::rgb8(image.bytes_per_pixel);
+            check(
+                JxlEncoderAddImageFrame(
+                    settings,
+                    &format,
+                    image.pixels.as_ptr().cast(),
+                    image.pixels.len()
+                ),
+                "add the image"
+            )?;
+            JxlEncoderCloseInput(enc.0);
+
+            // Grow the output until libjxl has written all of it
+            let mut output = vec![0u8; 64 * 1024];
+            let mut written = 0;
+            loop {
+                let mut next_out = output.as_mut_ptr().add(written);
+                let mut avail_out = output.len() - written;
+                let status = JxlEncoderProcessOutput(enc.0, &mut next_out, &mut avail_out);
+                written = output.len() - avail_out;
+                match status {
+                    JXL_ENC_SUCCESS => break,
+                    JXL_ENC_NEED_MORE_OUTPUT => output.resize(output.len() * 2, 0),
+                    _ => return Err("libjxl failed to encode the image".into()),
+                }
+            }
+            output.truncate(written);
+            Ok(output)
+        }
+    }
+}
+
+// Implementation for JPEG XL decoder
+pub struct JxlDecoder;
+
+impl BenchmarkDecoder for JxlDecoder {
+    fn name(&self) -> &str {
+        "JPEG XL"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<ImageData, Box<dyn std::error::Error>> {
+        unsafe {
+            let dec = JxlDecoderCreate(ptr::null());
+            if dec.is_null() {
+                return Err("libjxl failed to create a decoder".into());
+            }
+            let dec = Owned(dec, JxlDecoderDestroy);
+            check(
+                JxlDecoderSubscribeEvents(dec.0, JXL_DEC_BASIC_INFO | JXL_DEC_FULL_IMAGE),
+                "subscribe to events"
+            )?;
+            check(JxlDecoderSetInput(dec.0, data.as_ptr(), data.len()), "set the input")?;
+            JxlDecoderCloseInput(dec.0);
+
+            let mut info = JxlBasicInfo::default();
+            let mut format = JxlPixelFormat::rgb8(3);
+            let mut pixels = Vec::new();
+            loop {
+                match JxlDecoderProcessInput(dec.0) {
+                    JXL_DEC_BASIC_INFO => {
+                        check(JxlDecoderGetBasicInfo(dec.0, &mut info), "read the basic info")?;
+                        format = JxlPixelFormat::rgb8(if info.alpha_bits > 0 { 4 } else { 3 });
+                    }
+                    JXL_DEC_NEED_IMAGE_OUT_BUFFER => {
+                        let mut size = 0;
+                        check(
+                            JxlDecoderImageOutBufferSize(dec.0, &format, &mut size),
+                            "size the output"
+                        )?;
+                        pixels.resize(size, 0);
+                        check(
+                            JxlDecoderSetImageOutBuffer(
+                                dec.0,
+                                &format,
+                                pixels.as_mut_ptr().cast(),
+                                pixels.len()
+                            ),
+                            "set the output"
+                        )?;
+                    }
+                    JXL_DEC_FULL_IMAGE => {}
+                    JXL_DEC_SUCCESS => break,
+                    _ => return Err("Invalid JPEG XL data".into()),
+                }
+            }
+
+            Ok(ImageData {
+                pixels,
+                width: info.xsize,
+                height: info.ysize,
+                bytes_per_pixel: format.num_channels as usize,
+            })
+        }
+    }
+}
@@ -1,18 +1,38 @@
 #![allow(clippy::type_complexity)]
 
-use clap::Parser;
+use clap::{ Parser, ValueEnum };
 use image::{ ColorType, ImageEncoder, ImageFormat };
 use qoir_rs::{
+    decode_basic_metadata,
     decode_from_memory,
-    encode_to_memory,
+    encode_to_vec,
     DecodeOptions,
     EncodeOptions,
     Image as QoirImage,
     PixelFormat,
+    Rectangle,
 };
-use std::{ fs, path::{ Path, PathBuf }, time::{ Duration, Instant } };
+use memory::{ CountingAllocator, HeapPeak, RssPeak };
+use quality::{ QualityMetric, compare_lossy, format_quality_report };
+use rayon::prelude::*;
+use serde::Serialize;
+use std::{ fmt::Write as _, fs, path::{ Path, PathBuf }, time::{ Duration, Instant } };
 use tempfile::TempDir;
 
+mod avif;
+mod baseline;
+#[cfg(feature = "heif")]
+mod heif;
+#[cfg(feature = "jxl")]
+mod jxl;
+mod memory;
+mod quality;
+mod raw;
+mod report;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Benchmark image format performance")]
 struct Args {
@@ -27,6 +47,114 @@ struct Args {
     /// Frequency of progress updates
     #[arg(short, long, default_value = "10")]
     freq: usize,
+
+    /// Untimed runs over every image before each codec's timed iterations
+    #[arg(short, long, default_value = "1")]
+    warmup: usize,
+
+    /// Leave times outside 1.5 interquartile ranges of the quartiles out of the
+    /// average and percentiles
+    #[arg(long)]
+    reject_outliers: bool,
+
+    /// Also run each codec on this many threads at once, and report the aggregate speed
+    #[arg(short, long, default_value = "1", value_parser = clap::value_parser!(u16).range(1..))]
+    threads: u16,
+
+    /// Also time QOIR decoding into RGB, BGRA and premultiplied RGBA, and decoding
+    /// clipped and offset regions
+    #[arg(long)]
+    decode_variants: bool,
+
+    /// Also report the results of every image
+    #[arg(long)]
+    per_image: bool,
+
+    /// Also report averages over groups of similar images
+    #[arg(long, value_enum)]
+    group_by: Option<GroupBy>,
+
+    /// Benchmark on the camera RAW files in the input directory, developed once to RGB,
+    /// instead of on JPEG and PNG sources
+    #[arg(long)]
+    raw: bool,
+
+    /// Instead of timing, compare the sizes of lossy QOIR, JPEG and WebP at matched quality
+    #[arg(long)]
+    lossy: bool,
+
+    /// The metric qualities are matched with in --lossy mode
+    #[arg(long, value_enum, default_value = "psnr", requires = "lossy")]
+    metric: QualityMetric,
+
+    /// Save the results as a baseline to compare later runs with
+    #[arg(long, value_name = "FILE")]
+    save_baseline: Option<PathBuf>,
+
+    /// Compare the speed of every codec with a saved baseline (or any JSON results), and
+    /// fail if one regressed
+    #[arg(long, value_name = "FILE")]
+    compare_baseline: Option<PathBuf>,
+
+    /// How many percent slower than the baseline a codec may get before failing
+    #[arg(long, default_value = "10", requires = "compare_baseline")]
+    regression_threshold: f64,
+
+    /// Also write a standalone HTML page with charts of the results
+    #[arg(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+
+    /// Write the results to this file instead of printing them
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Format of the results [default: from the --output extension, else table]
+    #[arg(long, value_enum)]
+    format: Option<OutputFormat>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum GroupBy {
+    /// Under 1, 1 to 4, 4 to 12 and 12 or more megapixels
+    Megapixels,
+    /// Images with and without transparent pixels
+    Alpha,
+    /// Photographic or synthetic (drawings, screenshots, renders)
+    Content,
+}
+
+// How each codec is run, from the command line
+#[derive(Clone, Copy, Debug)]
+struct RunSettings {
+    iterations: usize,
+    freq: usize,
+    warmup: usize,
+    reject_outliers: bool,
+    threads: usize,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Fixed-width ASCII tables
+    Table,
+    /// One JSON document with the run settings and every result
+    Json,
+    /// One row per codec and operation
+    Csv,
+    /// Markdown tables
+    Md,
+}
+
+impl OutputFormat {
+    fn from_path(path: &Path) -> Option<OutputFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(OutputFormat::Json),
+            "csv" => Some(OutputFormat::Csv),
+            "md" | "markdown" => Some(OutputFormat::Md),
+            "txt" => Some(OutputFormat::Table),
+            _ => None,
+        }
+    }
 }
 
 // Common image data structure that works across different libraries
@@ -68,23 +196,56 @@ impl BenchmarkEncoder for QoirEncoder {
             stride_in_bytes: (image.width as usize) * image.bytes_per_pixel,
         };
 
-        let encoded = encode_to_memory(qoir_image, self.options.clone())?;
-        Ok(encoded.data.to_vec())
+        Ok(encode_to_vec(qoir_image, self.options.clone())?)
     }
 }
 
 // Implementation for QOIR decoder
 struct QoirDecoder {
+    name: &'static str,
     options: DecodeOptions,
+    region: DecodeRegion,
+}
+
+// The part of the image a QOIR decoder decodes. The destination is always the size of
+// the whole image.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum DecodeRegion {
+    Whole,
+    // The middle half of the width and height, through src_clip_rect
+    Crop,
+    // The whole image moved right and down by half its size, so that only its top-left
+    // quarter lands in the destination
+    Offset,
 }
 
 impl BenchmarkDecoder for QoirDecoder {
     fn name(&self) -> &str {
-        "QOIR"
+        self.name
     }
 
     fn decode(&self, data: &[u8]) -> Result<ImageData, Box<dyn std::error::Error>> {
-        let decoded = decode_from_memory(data, self.options.clone())?;
+        let mut options = self.options.clone();
+        if self.region != DecodeRegion::Whole {
+            let (width, height, _) = decode_basic_metadata(data)?;
+            let (width, height) = (width as i32, height as i32);
+            match self.region {
+                DecodeRegion::Crop => {
+                    options.src_clip_rect = Some(Rectangle {
+                        x0: width / 4,
+                        y0: height / 4,
+                        x1: width - width / 4,
+                        y1: height - height / 4,
+                    });
+                }
+                DecodeRegion::Offset => {
+                    options.offset_x = width / 2;
+                    options.offset_y = height / 2;
+                }
+                DecodeRegion::Whole => {}
+            }
+        }
+        let decoded = decode_from_memory(data, options)?;
 
         Ok(ImageData {
             pixels: decoded.image.pixels.to_vec(),
@@ -179,11 +340,113 @@ impl BenchmarkDecoder for PngDecoder {
     }
 }
 
-#[derive(Debug)]
+// Implementation for QOI encoder using the qoi crate
+struct QoiEncoder;
+
+impl BenchmarkEncoder for QoiEncoder {
+    fn name(&self) -> &str {
+        "QOI"
+    }
+
+    fn encode(&self, image: &ImageData) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        Ok(qoi::encode_to_vec(&image.pixels, image.width, image.height)?)
+    }
+}
+
+// Implementation for QOI decoder using the qoi crate
+struct QoiDecoder;
+
+impl BenchmarkDecoder for QoiDecoder {
+    fn name(&self) -> &str {
+        "QOI"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<ImageData, Box<dyn std::error::Error>> {
+        let (header, pixels) = qoi::decode_to_vec(data)?;
+
+        Ok(ImageData {
+            pixels,
+            width: header.width,
+            height: header.height,
+            bytes_per_pixel: header.channels.as_u8() as usize,
+        })
+    }
+}
+
+// Implementation for WebP encoder using libwebp; lossless unless a quality is given
+struct WebpEncoder {
+    quality: Option<f32>,
+}
+
+impl BenchmarkEncoder for WebpEncoder {
+    fn name(&self) -> &str {
+        "WebP"
+    }
+
+    fn encode(&self, image: &ImageData) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let encoder = webp::Encoder::from_rgba(&image.pixels, image.width, image.height);
+        let output = match self.quality {
+            Some(quality) => encoder.encode(quality),
+            None => encoder.encode_lossless(),
+        };
+        Ok(output.to_vec())
+    }
+}
+
+// Implementation for WebP decoder using libwebp
+struct WebpDecoder;
+
+impl BenchmarkDecoder for WebpDecoder {
+    fn name(&self) -> &str {
+        "WebP"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<ImageData, Box<dyn std::error::Error>> {
+        let decoded = webp::Decoder::new(data).decode().ok_or("Invalid WebP data")?;
+
+        Ok(ImageData {
+            pixels: decoded.to_vec(),
+            width: decoded.width(),
+            height: decoded.height(),
+            bytes_per_pixel: if decoded.is_alpha() { 4 } else { 3 },
+        })
+    }
+}
+
+// Implementation for AVIF encoder using ravif (rav1e). AVIF is decoded with dav1d, in
+// the avif module.
+struct AvifEncoder {
+    quality: f32,
+    speed: u8,
+}
+
+impl BenchmarkEncoder for AvifEncoder {
+    fn name(&self) -> &str {
+        "AVIF"
+    }
+
+    fn encode(&self, image: &ImageData) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let pixels: Vec<rgb::RGBA8> = image.pixels
+            .chunks_exact(4)
+            .map(|p| rgb::RGBA8::new(p[0], p[1], p[2], p[3]))
+            .collect();
+        let buffer = imgref::Img::new(&pixels[..], image.width as usize, image.height as usize);
+        let encoded = ravif::Encoder
+            ::new()
+            .with_quality(self.quality)
+            .with_speed(self.speed)
+            // Benchmark on one thread, like every other codec here.
+            .with_num_threads(Some(1))
+            .encode_rgba(buffer)?;
+        Ok(encoded.avif_file)
+    }
+}
+
+#[derive(Debug, Serialize)]
 struct BenchmarkResults {
+    #[serde(rename = "format")]
     encoder_name: String,
     num_images_tested: usize,
-    #[allow(unused)]
     num_iterations_per_image: usize,
     avg_time_per_image_ms: f64,
     total_time_s: f64,
@@ -192,13 +455,245 @@ struct BenchmarkResults {
     avg_size_change_percentage: f64,
     throughput_mb_s: f64,
     speed_images_s: f64,
+    // Largest heap growth during one operation, Rust allocations only
+    peak_heap_kb: f64,
+    // Growth of the peak RSS over the whole run, on Linux
+    peak_rss_kb: Option<f64>,
+    latency: LatencyStats,
+    // In the order of ConvertedImages::info; left out of the report without --per-image
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    per_image: Vec<ImageResults>,
+    // Filled in by --group-by
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<GroupResults>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parallel: Option<ParallelResults>,
+}
+
+// Running totals of the operations on one image
+#[derive(Default)]
+struct ImageTotals {
+    time: Duration,
+    operations: usize,
+    input_bytes: usize,
+    output_bytes: usize,
+}
+
+impl ImageTotals {
+    fn add(&mut self, duration: Duration, input_bytes: usize, output_bytes: usize) {
+        self.time += duration;
+        self.operations += 1;
+        self.input_bytes += input_bytes;
+        self.output_bytes += output_bytes;
+    }
+
+    fn results(&self) -> ImageResults {
+        let operations = self.operations.max(1) as f64;
+        let time_s = self.time.as_secs_f64();
+        ImageResults {
+            image: String::new(),
+            avg_time_ms: (time_s * 1000.0) / operations,
+            size_original_kb: (self.input_bytes as f64) / operations / 1024.0,
+            size_processed_kb: (self.output_bytes as f64) / operations / 1024.0,
+            throughput_mb_s: if time_s > 0.0 {
+                (self.input_bytes as f64) / (1024.0 * 1024.0) / time_s
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ImageResults {
+    image: String,
+    avg_time_ms: f64,
+    size_original_kb: f64,
+    size_processed_kb: f64,
+    throughput_mb_s: f64,
+}
+
+impl ImageResults {
+    fn size_change_percentage(&self) -> f64 {
+        if self.size_original_kb > 0.0 {
+            (self.size_processed_kb / self.size_original_kb) * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+// Averages over the images sharing one characteristic
+#[derive(Debug, Serialize)]
+struct GroupResults {
+    group: String,
+    num_images: usize,
+    avg_time_per_image_ms: f64,
+    avg_size_original_kb: f64,
+    avg_size_processed_kb: f64,
+    avg_size_change_percentage: f64,
+}
+
+// Names the per-image results, groups them if asked to, and drops them if not asked for
+fn break_down(
+    results: &mut [BenchmarkResults],
+    info: &[ImageInfo],
+    per_image: bool,
+    group_by: Option<GroupBy>
+) {
+    for result in results {
+        for (image, info) in result.per_image.iter_mut().zip(info) {
+            image.image = info.name.clone();
+        }
+        if let Some(group_by) = group_by {
+            result.groups = group_results(&result.per_image, info, group_by);
+        }
+        if !per_image {
+            result.per_image.clear();
+        }
+    }
+}
+
+fn group_results(per_image: &[ImageResults], info: &[ImageInfo], group_by: GroupBy) -> Vec<GroupResults> {
+    let mut groups: Vec<(String, Vec<&ImageResults>)> = Vec::new();
+    for (result, info) in per_image.iter().zip(info) {
+        let label = info.group(group_by);
+        match groups.iter_mut().find(|(group, _)| *group == label) {
+            Some((_, members)) => members.push(result),
+            None => groups.push((label, vec![result])),
+        }
+    }
+    groups.sort_by(|a, b| a.0.cmp(&b.0));
+    groups
+        .into_iter()
+        .map(|(group, members)| {
+            let count = members.len() as f64;
+            let mean = |value: fn(&ImageResults) -> f64| members.iter().map(|r| value(r)).sum::<f64>() / count;
+            let avg_size_original_kb = mean(|r| r.size_original_kb);
+            let avg_size_processed_kb = mean(|r| r.size_processed_kb);
+            GroupResults {
+                group,
+                num_images: members.len(),
+                avg_time_per_image_ms: mean(|r| r.avg_time_ms),
+                avg_size_original_kb,
+                avg_size_processed_kb,
+                avg_size_change_percentage: if avg_size_original_kb > 0.0 {
+                    (avg_size_processed_kb / avg_size_original_kb) * 100.0
+                } else {
+                    0.0
+                },
+            }
+        })
+        .collect()
+}
+
+// Distribution of the times of single operations
+#[derive(Debug, Serialize)]
+struct LatencyStats {
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    std_dev_ms: f64,
+    outliers_rejected: usize,
+}
+
+// Returns the mean and distribution of `times_ms`, without the outliers if asked to
+fn latency_stats(mut times_ms: Vec<f64>, reject_outliers: bool) -> (f64, LatencyStats) {
+    times_ms.sort_by(f64::total_cmp);
+    let total = times_ms.len();
+    if reject_outliers && total >= 4 {
+        // Tukey's fences
+        let q1 = percentile(&times_ms, 25.0);
+        let q3 = percentile(&times_ms, 75.0);
+        let fence = 1.5 * (q3 - q1);
+        times_ms.retain(|&time| time >= q1 - fence && time <= q3 + fence);
+    }
+
+    let count = times_ms.len() as f64;
+    let mean = if count > 0.0 { times_ms.iter().sum::<f64>() / count } else { 0.0 };
+    let variance = if count > 1.0 {
+        times_ms.iter().map(|time| (time - mean).powi(2)).sum::<f64>() / (count - 1.0)
+    } else {
+        0.0
+    };
+    let stats = LatencyStats {
+        p50_ms: percentile(&times_ms, 50.0),
+        p90_ms: percentile(&times_ms, 90.0),
+        p99_ms: percentile(&times_ms, 99.0),
+        std_dev_ms: variance.sqrt(),
+        outliers_rejected: total - times_ms.len(),
+    };
+    (mean, stats)
+}
+
+// Nearest-rank percentile of sorted times
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = ((percent / 100.0) * (sorted.len() as f64)).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+// Results of running a codec on several threads at once
+#[derive(Debug, Serialize)]
+struct ParallelResults {
+    threads: usize,
+    total_time_s: f64,
+    speed_images_s: f64,
+    // The parallel speed as a percentage of `threads` times the single-threaded speed
+    scaling_efficiency_percentage: f64,
+}
+
+// Runs `operation` on every job `iterations` times, spread over `threads` threads, and
+// returns how long that took
+fn run_parallel<T: Sync>(
+    jobs: &[T],
+    iterations: usize,
+    threads: usize,
+    operation: impl Fn(&T) -> Result<(), String> + Sync + Send
+) -> Result<Duration, Box<dyn std::error::Error>> {
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build()?;
+    let start_time = Instant::now();
+    pool.install(|| {
+        (0..iterations)
+            .into_par_iter()
+            .flat_map_iter(|_| jobs.iter())
+            .try_for_each(operation)
+    })?;
+    Ok(start_time.elapsed())
+}
+
+fn parallel_results(
+    threads: usize,
+    total_operations: usize,
+    duration: Duration,
+    single_thread_speed_images_s: f64
+) -> ParallelResults {
+    let total_time_s = duration.as_secs_f64();
+    let speed_images_s = if total_time_s > 0.0 {
+        (total_operations as f64) / total_time_s
+    } else {
+        0.0
+    };
+    let scaling_efficiency_percentage = if single_thread_speed_images_s > 0.0 {
+        (speed_images_s / (single_thread_speed_images_s * (threads as f64))) * 100.0
+    } else {
+        0.0
+    };
+    ParallelResults {
+        threads,
+        total_time_s,
+        speed_images_s,
+        scaling_efficiency_percentage,
+    }
 }
 
 impl std::fmt::Display for BenchmarkResults {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "| {:<10} | {:<6} | {:<8.2} | {:<8.2} | {:<10.2} | {:<10.2} | {:<6.2} | {:<8.2} | {:<8.2} |",
+            "| {:<10} | {:<6} | {:<8.2} | {:<8.2} | {:<10.2} | {:<10.2} | {:<6.2} | {:<8.2} | {:<10.2} | {:<10.0} | {:<10} |",
             self.encoder_name,
             self.num_images_tested,
             self.avg_time_per_image_ms,
@@ -207,7 +702,9 @@ impl std::fmt::Display for BenchmarkResults {
             self.avg_size_processed_kb,
             self.avg_size_change_percentage,
             self.throughput_mb_s,
-            self.speed_images_s
+            self.speed_images_s,
+            self.peak_heap_kb,
+            self.peak_rss_kb.map_or("n/a".to_string(), |kb| format!("{:.0}", kb))
         )
     }
 }
@@ -219,15 +716,65 @@ struct ConvertedImages {
     png_files: Vec<(Vec<u8>, usize)>,
     jpeg_files: Vec<(Vec<u8>, usize)>,
     qoir_files: Vec<(Vec<u8>, usize)>,
+    qoi_files: Vec<(Vec<u8>, usize)>,
+    webp_files: Vec<(Vec<u8>, usize)>,
+    avif_files: Vec<(Vec<u8>, usize)>,
     rgba_images: Vec<ImageData>,
+    info: Vec<ImageInfo>,
+}
+
+// Characteristics of a source image, for --per-image and --group-by
+struct ImageInfo {
+    name: String,
+    megapixels: f64,
+    has_alpha: bool,
+    synthetic: bool,
+}
+
+impl ImageInfo {
+    fn new(name: String, image: &ImageData) -> ImageInfo {
+        let pixels: Vec<&[u8]> = image.pixels.chunks_exact(4).collect();
+        let has_alpha = pixels.iter().any(|pixel| pixel[3] != 0xff);
+        // Drawings, screenshots and renders repeat exact colors in flat areas, while
+        // the noise in photos makes neighbours differ. Count pixels equal to the one on
+        // their left.
+        let repeats = pixels
+            .chunks(image.width.max(1) as usize)
+            .flat_map(|row| row.windows(2))
+            .filter(|pair| pair[0] == pair[1])
+            .count();
+        ImageInfo {
+            name,
+            megapixels: ((image.width as f64) * (image.height as f64)) / 1_000_000.0,
+            has_alpha,
+            synthetic: (repeats as f64) > 0.25 * (pixels.len() as f64),
+        }
+    }
+
+    fn group(&self, group_by: GroupBy) -> String {
+        match group_by {
+            GroupBy::Megapixels =>
+                (match self.megapixels {
+                    mp if mp < 1.0 => "< 1 MP",
+                    mp if mp < 4.0 => "1-4 MP",
+                    mp if mp < 12.0 => "4-12 MP",
+                    _ => ">= 12 MP",
+                }).to_string(),
+            GroupBy::Alpha => (if self.has_alpha { "alpha" } else { "opaque" }).to_string(),
+            GroupBy::Content => (if self.synthetic { "synthetic" } else { "photo" }).to_string(),
+        }
+    }
 }
 
-fn prepare_images(input_dir: &Path) -> Result<ConvertedImages, Box<dyn std::error::Error>> {
-    println!("Scanning for images in: {}", input_dir.display());
+fn prepare_images(
+    input_dir: &Path,
+    raw: bool
+) -> Result<ConvertedImages, Box<dyn std::error::Error>> {
+    eprintln!("Scanning for images in: {}", input_dir.display());
 
     // Create temporary directory
     let temp_dir = TempDir::new()?;
-    println!("Created temporary directory at: {}", temp_dir.path().display());
+    eprintln!("Created temporary directory at: {}", temp_dir.path().display());
 
     // Scan the input directory for image files
     let mut source_images = Vec::new();
@@ -239,10 +786,25 @@ fn prepare_images(input_dir: &Path) -> Result<ConvertedImages, Box<dyn std::erro
         if path.is_file() {
             if let Some(ext) = path.extension() {
                 let ext = ext.to_string_lossy().to_lowercase();
-                if ["jpg", "jpeg", "png", "gif", "bmp"].contains(&ext.as_str()) {
+                if raw {
+                    if raw::RAW_EXTENSIONS.contains(&ext.as_str()) {
+                        match raw::develop_file(&path) {
+                            Ok(rgb) => {
+                                eprintln!("Developed RAW image: {}", path.display());
+                                source_images.push((
+                                    path.file_name().unwrap().to_string_lossy().to_string(),
+                                    image::DynamicImage::ImageRgb8(rgb),
+                                ));
+                            }
+                            Err(e) => {
+                                eprintln!("Warning: Failed to develop {}: {}", path.display(), e);
+                            }
+                        }
+                    }
+                } else if ["jpg", "jpeg", "png", "gif", "bmp"].contains(&ext.as_str()) {
                     match image::open(&path) {
                         Ok(img) => {
-                            println!("Found image: {}", path.display());
+                            eprintln!("Found image: {}", path.display());
                             source_images.push((
                                 path.file_name().unwrap().to_string_lossy().to_string(),
                                 img,
@@ -261,13 +823,17 @@ fn prepare_images(input_dir: &Path) -> Result<ConvertedImages, Box<dyn std::erro
         return Err("No valid images found in the input directory".into());
     }
 
-    println!("Found {} images to convert", source_images.len());
+    eprintln!("Found {} images to convert", source_images.len());
 
     // Generate test images in all formats
     let mut png_files = Vec::new();
     let mut jpeg_files = Vec::new();
     let mut qoir_files = Vec::new();
+    let mut qoi_files = Vec::new();
+    let mut webp_files = Vec::new();
+    let mut avif_files = Vec::new();
     let mut rgba_images = Vec::new();
+    let mut info = Vec::new();
 
     for (filename, img) in source_images {
         // Save as RGBA for memory testing
@@ -278,6 +844,7 @@ fn prepare_images(input_dir: &Path) -> Result<ConvertedImages, Box<dyn std::erro
             height: rgba.height(),
             bytes_per_pixel: 4,
         });
+        info.push(ImageInfo::new(filename.clone(), rgba_images.last().unwrap()));
 
         // Save as PNG
         let png_path = temp_dir.path().join(format!("{}.png", filename));
@@ -311,63 +878,92 @@ fn prepare_images(input_dir: &Path) -> Result<ConvertedImages, Box<dyn std::erro
             ..Default::default()
         };
 
-        let encoded_qoir = encode_to_memory(qoir_image, qoir_options)?;
-        let qoir_buffer = encoded_qoir.data.to_vec();
+        let qoir_buffer = encode_to_vec(qoir_image, qoir_options)?;
         let qoir_size = qoir_buffer.len();
         fs::write(&qoir_path, &qoir_buffer)?;
         qoir_files.push((qoir_buffer, qoir_size));
+
+        // Save as QOI and lossless WebP
+        let rgba_image = rgba_images.last().unwrap();
+        let qoi_buffer = QoiEncoder.encode(rgba_image)?;
+        let qoi_size = qoi_buffer.len();
+        fs::write(temp_dir.path().join(format!("{}.qoi", filename)), &qoi_buffer)?;
+        qoi_files.push((qoi_buffer, qoi_size));
+
+        let webp_buffer = (WebpEncoder { quality: None }).encode(rgba_image)?;
+        let webp_size = webp_buffer.len();
+        fs::write(temp_dir.path().join(format!("{}.webp", filename)), &webp_buffer)?;
+        webp_files.push((webp_buffer, webp_size));
+
+        // Save as AVIF, with the settings of the encoding benchmark
+        let avif_buffer = (AvifEncoder { quality: 80.0, speed: 8 }).encode(rgba_image)?;
+        let avif_size = avif_buffer.len();
+        fs::write(temp_dir.path().join(format!("{}.avif", filename)), &avif_buffer)?;
+        avif_files.push((avif_buffer, avif_size));
     }
 
-    println!("Converted all images to PNG, JPEG, and QOIR formats");
+    eprintln!("Converted all images to PNG, JPEG, QOIR, QOI, WebP and AVIF formats");
 
     Ok(ConvertedImages {
         temp_dir,
         png_files,
         jpeg_files,
         qoir_files,
+        qoi_files,
+        webp_files,
+        avif_files,
         rgba_images,
+        info,
     })
 }
 
-fn benchmark_encode<E: BenchmarkEncoder>(
+fn benchmark_encode<E: BenchmarkEncoder + Sync>(
     encoder: &E,
     images: &[ImageData],
-    iterations: usize,
-    freq: usize,
+    settings: &RunSettings,
 ) -> Result<BenchmarkResults, Box<dyn std::error::Error>> {
-    println!("Running {} Encode Benchmark...", encoder.name());
+    let RunSettings { iterations, freq, warmup, reject_outliers, threads } = *settings;
+    eprintln!("Running {} Encode Benchmark...", encoder.name());
 
     let mut total_encoding_time = Duration::new(0, 0);
     let mut total_input_pixel_bytes_processed: usize = 0;
     let mut total_output_bytes_processed: usize = 0;
     let mut encoding_times_ms: Vec<f64> = Vec::new();
+    let mut per_image: Vec<ImageTotals> = images.iter().map(|_| ImageTotals::default()).collect();
+    let mut peak_heap_bytes: usize = 0;
+    let rss_peak = RssPeak::start();
+
+    for _ in 0..warmup {
+        for image in images {
+            encoder.encode(image)?;
+        }
+    }
 
     for iter in 0..iterations {
         if iter % freq == 0 {
-            println!("Processing batch {}/{}", iter + 1, iterations);
+            eprintln!("Processing batch {}/{}", iter + 1, iterations);
         }
-        for image in images {
+        for (index, image) in images.iter().enumerate() {
             let input_size = image.pixels.len();
             total_input_pixel_bytes_processed += input_size;
 
+            let heap_peak = HeapPeak::start();
             let start_time = Instant::now();
             let encoded_data = encoder.encode(image)?;
             let duration = start_time.elapsed();
+            peak_heap_bytes = peak_heap_bytes.max(heap_peak.bytes());
 
             total_encoding_time += duration;
             encoding_times_ms.push(duration.as_secs_f64() * 1000.0);
             total_output_bytes_processed += encoded_data.len();
+            per_image[index].add(duration, input_size, encoded_data.len());
         }
     }
 
     let num_images_tested = images.len();
     let total_operations = num_images_tested * iterations;
 
-    let avg_time_per_image_ms = if !encoding_times_ms.is_empty() {
-        encoding_times_ms.iter().sum::<f64>() / (encoding_times_ms.len() as f64)
-    } else {
-        0.0
-    };
+    let (avg_time_per_image_ms, latency) = latency_stats(encoding_times_ms, reject_outliers);
     let total_time_s = total_encoding_time.as_secs_f64();
 
     let avg_size_original_kb = if num_images_tested > 0 {
@@ -403,6 +999,16 @@ fn benchmark_encode<E: BenchmarkEncoder>(
         0.0
     };
 
+    let parallel = if threads > 1 {
+        eprintln!("Running {} Encode Benchmark on {} threads...", encoder.name(), threads);
+        let duration = run_parallel(images, iterations, threads, |image| {
+            encoder.encode(image).map(drop).map_err(|e| e.to_string())
+        })?;
+        Some(parallel_results(threads, total_operations, duration, speed_images_s))
+    } else {
+        None
+    };
+
     Ok(BenchmarkResults {
         encoder_name: encoder.name().to_string(),
         num_images_tested,
@@ -414,47 +1020,61 @@ fn benchmark_encode<E: BenchmarkEncoder>(
         avg_size_change_percentage,
         throughput_mb_s,
         speed_images_s,
+        peak_heap_kb: (peak_heap_bytes as f64) / 1024.0,
+        peak_rss_kb: rss_peak.bytes().map(|bytes| (bytes as f64) / 1024.0),
+        latency,
+        per_image: per_image.iter().map(ImageTotals::results).collect(),
+        groups: Vec::new(),
+        parallel,
     })
 }
 
-fn benchmark_decode<D: BenchmarkDecoder>(
+fn benchmark_decode<D: BenchmarkDecoder + Sync>(
     decoder: &D,
     files: &[(Vec<u8>, usize)],
-    iterations: usize,
-    freq: usize,
+    settings: &RunSettings,
 ) -> Result<BenchmarkResults, Box<dyn std::error::Error>> {
-    println!("Running {} Decode Benchmark...", decoder.name());
+    let RunSettings { iterations, freq, warmup, reject_outliers, threads } = *settings;
+    eprintln!("Running {} Decode Benchmark...", decoder.name());
 
     let mut total_decoding_time = Duration::new(0, 0);
     let mut total_input_bytes_processed: usize = 0;
     let mut total_output_pixel_bytes_processed: usize = 0;
     let mut decoding_times_ms: Vec<f64> = Vec::new();
+    let mut per_image: Vec<ImageTotals> = files.iter().map(|_| ImageTotals::default()).collect();
+    let mut peak_heap_bytes: usize = 0;
+    let rss_peak = RssPeak::start();
+
+    for _ in 0..warmup {
+        for (buffer, _) in files {
+            decoder.decode(buffer)?;
+        }
+    }
 
     for iter in 0..iterations {
         if iter % freq == 0 {
-            println!("Processing batch {}/{}", iter + 1, iterations);
+            eprintln!("Processing batch {}/{}", iter + 1, iterations);
         }
-        for (buffer, original_size) in files {
+        for (index, (buffer, original_size)) in files.iter().enumerate() {
             total_input_bytes_processed += original_size;
 
+            let heap_peak = HeapPeak::start();
             let start_time = Instant::now();
             let decoded_image = decoder.decode(buffer)?;
             let duration = start_time.elapsed();
+            peak_heap_bytes = peak_heap_bytes.max(heap_peak.bytes());
 
             total_decoding_time += duration;
             decoding_times_ms.push(duration.as_secs_f64() * 1000.0);
             total_output_pixel_bytes_processed += decoded_image.pixels.len();
+            per_image[index].add(duration, *original_size, decoded_image.pixels.len());
         }
     }
 
     let num_files_tested = files.len();
     let total_operations = num_files_tested * iterations;
 
-    let avg_time_per_file_ms = if !decoding_times_ms.is_empty() {
-        decoding_times_ms.iter().sum::<f64>() / (decoding_times_ms.len() as f64)
-    } else {
-        0.0
-    };
+    let (avg_time_per_file_ms, latency) = latency_stats(decoding_times_ms, reject_outliers);
     let total_time_s = total_decoding_time.as_secs_f64();
 
     let avg_size_original_kb = if num_files_tested > 0 {
@@ -490,6 +1110,16 @@ fn benchmark_decode<D: BenchmarkDecoder>(
         0.0
     };
 
+    let parallel = if threads > 1 {
+        eprintln!("Running {} Decode Benchmark on {} threads...", decoder.name(), threads);
+        let duration = run_parallel(files, iterations, threads, |(buffer, _)| {
+            decoder.decode(buffer).map(drop).map_err(|e| e.to_string())
+        })?;
+        Some(parallel_results(threads, total_operations, duration, speed_images_s))
+    } else {
+        None
+    };
+
     Ok(BenchmarkResults {
         encoder_name: decoder.name().to_string(),
         num_images_tested: num_files_tested,
@@ -501,29 +1131,377 @@ fn benchmark_decode<D: BenchmarkDecoder>(
         avg_size_change_percentage,
         throughput_mb_s,
         speed_images_s,
+        peak_heap_kb: (peak_heap_bytes as f64) / 1024.0,
+        peak_rss_kb: rss_peak.bytes().map(|bytes| (bytes as f64) / 1024.0),
+        latency,
+        per_image: per_image.iter().map(ImageTotals::results).collect(),
+        groups: Vec::new(),
+        parallel,
     })
 }
 
-fn print_benchmark_table_header(title: &str) {
-    println!("\n{}", title);
-    println!(
-        "|-----------+--------+----------+----------+------------+------------+--------+----------+------------|"
-    );
-    println!(
-        "| Format    | Images | Avg Time | Total    | Orig Size  | Proc Size  | Size   | Thrghpt  | Speed      |"
-    );
-    println!(
-        "|           |        | (ms)     | Time (s) | (KB)       | (KB)       | (%)    | (MB/s)   | (imgs/s)   |"
+// Benchmarks the decoder of a codec that isn't always available. Its test files aren't
+// prepared up front, so they are encoded here, and the codec is skipped if its library
+// fails.
+#[cfg(any(feature = "jxl", feature = "heif"))]
+fn benchmark_optional_decode<E: BenchmarkEncoder, D: BenchmarkDecoder + Sync>(
+    encoder: &E,
+    decoder: &D,
+    images: &[ImageData],
+    settings: &RunSettings,
+) -> Result<BenchmarkResults, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    for image in images {
+        let buffer = encoder.encode(image)?;
+        let size = buffer.len();
+        files.push((buffer, size));
+    }
+    benchmark_decode(decoder, &files, settings)
+}
+
+const TABLE_RULE: &str =
+    "|------------+--------+----------+----------+------------+------------+--------+----------+------------+------------+------------|";
+
+fn benchmark_table(title: &str, results: &[BenchmarkResults]) -> String {
+    let mut table = format!("\n{}\n{}\n", title, TABLE_RULE);
+    table.push_str(
+        "| Format     | Images | Avg Time | Total    | Orig Size  | Proc Size  | Size   | Thrghpt  | Speed      | Peak Heap  | Peak RSS   |\n"
     );
-    println!(
-        "|-----------+--------+----------+----------+------------+------------+--------+----------+------------|"
+    table.push_str(
+        "|            |        | (ms)     | Time (s) | (KB)       | (KB)       | (%)    | (MB/s)   | (imgs/s)   | (KB)       | (KB)       |\n"
     );
+    table.push_str(TABLE_RULE);
+    table.push('\n');
+    for result in results {
+        let _ = writeln!(table, "{}", result);
+    }
+    table.push_str(TABLE_RULE);
+    table.push('\n');
+
+    table.push('\n');
+    table.push_str(LATENCY_TABLE_RULE);
+    table.push('\n');
+    table.push_str("| Format     | p50      | p90      | p99      | Std Dev  | Outliers |\n");
+    table.push_str("|            | (ms)     | (ms)     | (ms)     | (ms)     |          |\n");
+    table.push_str(LATENCY_TABLE_RULE);
+    table.push('\n');
+    for result in results {
+        let l = &result.latency;
+        let _ = writeln!(
+            table,
+            "| {:<10} | {:<8.2} | {:<8.2} | {:<8.2} | {:<8.2} | {:<8} |",
+            result.encoder_name,
+            l.p50_ms,
+            l.p90_ms,
+            l.p99_ms,
+            l.std_dev_ms,
+            l.outliers_rejected
+        );
+    }
+    table.push_str(LATENCY_TABLE_RULE);
+    table.push('\n');
+
+    // With --threads, the aggregate speed of each codec follows in a table of its own
+    let parallel: Vec<_> = results
+        .iter()
+        .filter_map(|result| Some((&result.encoder_name, result.parallel.as_ref()?)))
+        .collect();
+    if !parallel.is_empty() {
+        table.push('\n');
+        table.push_str(PARALLEL_TABLE_RULE);
+        table.push('\n');
+        table.push_str("| Format     | Threads | Total    | Speed      | Scaling    |\n");
+        table.push_str("|            |         | Time (s) | (imgs/s)   | (%)        |\n");
+        table.push_str(PARALLEL_TABLE_RULE);
+        table.push('\n');
+        for (name, p) in parallel {
+            let _ = writeln!(
+                table,
+                "| {:<10} | {:<7} | {:<8.2} | {:<10.2} | {:<10.1} |",
+                name,
+                p.threads,
+                p.total_time_s,
+                p.speed_images_s,
+                p.scaling_efficiency_percentage
+            );
+        }
+        table.push_str(PARALLEL_TABLE_RULE);
+        table.push('\n');
+    }
+
+    // With --per-image and --group-by, the details follow
+    for (column, rows) in [("Image", image_rows(results)), ("Group", group_rows(results))] {
+        if rows.is_empty() {
+            continue;
+        }
+        table.push('\n');
+        table.push_str(DETAIL_TABLE_RULE);
+        table.push('\n');
+        let _ = writeln!(
+            table,
+            "| {:<24} | Format     | Images | Avg Time | Orig Size  | Proc Size  | Size   |",
+            column
+        );
+        table.push_str(
+            "|                          |            |        | (ms)     | (KB)       | (KB)       | (%)    |\n"
+        );
+        table.push_str(DETAIL_TABLE_RULE);
+        table.push('\n');
+        for row in rows {
+            let _ = writeln!(
+                table,
+                "| {:<24.24} | {:<10} | {:<6} | {:<8.2} | {:<10.2} | {:<10.2} | {:<6.2} |",
+                row.label,
+                row.format,
+                row.images,
+                row.avg_time_ms,
+                row.size_original_kb,
+                row.size_processed_kb,
+                row.size_change_percentage
+            );
+        }
+        table.push_str(DETAIL_TABLE_RULE);
+        table.push('\n');
+    }
+    table
 }
 
-fn print_benchmark_table_footer() {
-    println!(
-        "|-----------+--------+----------+----------+------------+------------+--------+----------+------------|"
-    );
+// One line of the --per-image or --group-by details
+struct DetailRow<'a> {
+    format: &'a str,
+    label: &'a str,
+    images: usize,
+    avg_time_ms: f64,
+    size_original_kb: f64,
+    size_processed_kb: f64,
+    size_change_percentage: f64,
+}
+
+fn image_rows(results: &[BenchmarkResults]) -> Vec<DetailRow<'_>> {
+    results
+        .iter()
+        .flat_map(|r| {
+            r.per_image.iter().map(|i| DetailRow {
+                format: &r.encoder_name,
+                label: &i.image,
+                images: 1,
+                avg_time_ms: i.avg_time_ms,
+                size_original_kb: i.size_original_kb,
+                size_processed_kb: i.size_processed_kb,
+                size_change_percentage: i.size_change_percentage(),
+            })
+        })
+        .collect()
+}
+
+fn group_rows(results: &[BenchmarkResults]) -> Vec<DetailRow<'_>> {
+    results
+        .iter()
+        .flat_map(|r| {
+            r.groups.iter().map(|g| DetailRow {
+                format: &r.encoder_name,
+                label: &g.group,
+                images: g.num_images,
+                avg_time_ms: g.avg_time_per_image_ms,
+                size_original_kb: g.avg_size_original_kb,
+                size_processed_kb: g.avg_size_processed_kb,
+                size_change_percentage: g.avg_size_change_percentage,
+            })
+        })
+        .collect()
+}
+
+const DETAIL_TABLE_RULE: &str =
+    "|--------------------------+------------+--------+----------+------------+------------+--------|";
+
+const LATENCY_TABLE_RULE: &str = "|------------+----------+----------+----------+----------+----------|";
+
+const PARALLEL_TABLE_RULE: &str = "|------------+---------+----------+------------+------------|";
+
+#[derive(Serialize)]
+struct BenchmarkReport<'a> {
+    iterations: usize,
+    input_dir: &'a Path,
+    encode: &'a [BenchmarkResults],
+    decode: &'a [BenchmarkResults],
+}
+
+fn format_report(
+    format: OutputFormat,
+    report: &BenchmarkReport
+) -> Result<String, Box<dyn std::error::Error>> {
+    let operations = [
+        ("encode", "ENCODING BENCHMARK RESULTS", report.encode),
+        ("decode", "DECODING BENCHMARK RESULTS", report.decode),
+    ];
+    let mut output = String::new();
+    match format {
+        OutputFormat::Table => {
+            for (_, title, results) in operations {
+                output.push_str(&benchmark_table(title, results));
+            }
+        }
+        OutputFormat::Json => {
+            output = serde_json::to_string_pretty(report)?;
+            output.push('\n');
+        }
+        OutputFormat::Csv => {
+            output.push_str(
+                "operation,format,images,iterations,avg_time_ms,total_time_s,avg_original_kb,avg_processed_kb,size_percent,throughput_mb_s,images_per_s,peak_heap_kb,peak_rss_kb,p50_ms,p90_ms,p99_ms,std_dev_ms,outliers_rejected,threads,parallel_time_s,parallel_images_per_s,scaling_percent,image,group\n"
+            );
+            for (operation, _, results) in operations {
+                for r in results {
+                    writeln!(
+                        output,
+                        "{},{},{},{},{:.4},{:.4},{:.2},{:.2},{:.2},{:.2},{:.2},{:.0},{},{:.4},{:.4},{:.4},{:.4},{},{},,",
+                        operation,
+                        r.encoder_name,
+                        r.num_images_tested,
+                        r.num_iterations_per_image,
+                        r.avg_time_per_image_ms,
+                        r.total_time_s,
+                        r.avg_size_original_kb,
+                        r.avg_size_processed_kb,
+                        r.avg_size_change_percentage,
+                        r.throughput_mb_s,
+                        r.speed_images_s,
+                        r.peak_heap_kb,
+                        r.peak_rss_kb.map_or(String::new(), |kb| format!("{:.0}", kb)),
+                        r.latency.p50_ms,
+                        r.latency.p90_ms,
+                        r.latency.p99_ms,
+                        r.latency.std_dev_ms,
+                        r.latency.outliers_rejected,
+                        r.parallel.as_ref().map_or("1,,,".to_string(), |p| {
+                            format!(
+                                "{},{:.4},{:.2},{:.1}",
+                                p.threads,
+                                p.total_time_s,
+                                p.speed_images_s,
+                                p.scaling_efficiency_percentage
+                            )
+                        })
+                    )?;
+                }
+
+                // Details fill in the columns they have, and the image or group
+                for (rows, is_image) in [(image_rows(results), true), (group_rows(results), false)] {
+                    for row in rows {
+                        writeln!(
+                            output,
+                            "{},{},{},{},{:.4},,{:.2},{:.2},{:.2},,,,,,,,,,,,,,{},{}",
+                            operation,
+                            row.format,
+                            row.images,
+                            report.iterations,
+                            row.avg_time_ms,
+                            row.size_original_kb,
+                            row.size_processed_kb,
+                            row.size_change_percentage,
+                            if is_image { row.label } else { "" },
+                            if is_image { "" } else { row.label }
+                        )?;
+                    }
+                }
+            }
+        }
+        OutputFormat::Md => {
+            for (operation, _, results) in operations {
+                writeln!(output, "### {}\n", if operation == "encode" { "Encoding" } else { "Decoding" })?;
+                output.push_str(
+                    "| Format | Images | Avg time (ms) | Total time (s) | Orig size (KB) | Proc size (KB) | Size (%) | Throughput (MB/s) | Speed (imgs/s) | Peak heap (KB) | Peak RSS (KB) |\n"
+                );
+                output.push_str("|---|--:|--:|--:|--:|--:|--:|--:|--:|--:|--:|\n");
+                for r in results {
+                    writeln!(
+                        output,
+                        "| {} | {} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.0} | {} |",
+                        r.encoder_name,
+                        r.num_images_tested,
+                        r.avg_time_per_image_ms,
+                        r.total_time_s,
+                        r.avg_size_original_kb,
+                        r.avg_size_processed_kb,
+                        r.avg_size_change_percentage,
+                        r.throughput_mb_s,
+                        r.speed_images_s,
+                        r.peak_heap_kb,
+                        r.peak_rss_kb.map_or("n/a".to_string(), |kb| format!("{:.0}", kb))
+                    )?;
+                }
+                output.push('\n');
+
+                output.push_str(
+                    "| Format | p50 (ms) | p90 (ms) | p99 (ms) | Std dev (ms) | Outliers |\n|---|--:|--:|--:|--:|--:|\n"
+                );
+                for r in results {
+                    writeln!(
+                        output,
+                        "| {} | {:.2} | {:.2} | {:.2} | {:.2} | {} |",
+                        r.encoder_name,
+                        r.latency.p50_ms,
+                        r.latency.p90_ms,
+                        r.latency.p99_ms,
+                        r.latency.std_dev_ms,
+                        r.latency.outliers_rejected
+                    )?;
+                }
+                output.push('\n');
+
+                if results.iter().any(|r| r.parallel.is_some()) {
+                    output.push_str(
+                        "| Format | Threads | Total time (s) | Speed (imgs/s) | Scaling (%) |\n|---|--:|--:|--:|--:|\n"
+                    );
+                    for r in results {
+                        if let Some(p) = &r.parallel {
+                            writeln!(
+                                output,
+                                "| {} | {} | {:.2} | {:.2} | {:.1} |",
+                                r.encoder_name,
+                                p.threads,
+                                p.total_time_s,
+                                p.speed_images_s,
+                                p.scaling_efficiency_percentage
+                            )?;
+                        }
+                    }
+                    output.push('\n');
+                }
+
+                for (column, rows) in [("Image", image_rows(results)), ("Group", group_rows(results))] {
+                    if rows.is_empty() {
+                        continue;
+                    }
+                    writeln!(
+                        output,
+                        "| {} | Format | Images | Avg time (ms) | Orig size (KB) | Proc size (KB) | Size (%) |\n|---|---|--:|--:|--:|--:|--:|",
+                        column
+                    )?;
+                    for row in rows {
+                        writeln!(
+                            output,
+                            "| {} | {} | {} | {:.2} | {:.2} | {:.2} | {:.2} |",
+                            row.label,
+                            row.format,
+                            row.images,
+                            row.avg_time_ms,
+                            row.size_original_kb,
+                            row.size_processed_kb,
+                            row.size_change_percentage
+                        )?;
+                    }
+                    output.push('\n');
+                }
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn write_html_report(path: &Path, html: String) -> Result<(), Box<dyn std::error::Error>> {
+    fs::write(path, html)?;
+    eprintln!("Wrote the report to {}", path.display());
+    Ok(())
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -531,12 +1509,29 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
     let iterations = args.iterations;
     let freq = args.freq;
+    let settings = RunSettings {
+        iterations,
+        freq,
+        warmup: args.warmup,
+        reject_outliers: args.reject_outliers,
+        threads: args.threads as usize,
+    };
+    let format = match (args.format, &args.output) {
+        (Some(format), _) => format,
+        (None, None) => OutputFormat::Table,
+        (None, Some(path)) =>
+            OutputFormat::from_path(path).ok_or_else(|| {
+                format!("Can't tell the result format from {}; pass --format", path.display())
+            })?,
+    };
+    // Print each table as soon as it's ready, unless the results are written elsewhere.
+    let print_tables = format == OutputFormat::Table && args.output.is_none();
 
-    println!("Starting Image Format Benchmark ({} iterations per image)...", iterations);
-    println!("Using images from: {}", args.input_dir.display());
+    eprintln!("Starting Image Format Benchmark ({} iterations per image)...", iterations);
+    eprintln!("Using images from: {}", args.input_dir.display());
 
     // Prepare test images
-    let converted_images = match prepare_images(&args.input_dir) {
+    let converted_images = match prepare_images(&args.input_dir, args.raw) {
         Ok(images) => images,
         Err(e) => {
             eprintln!("Failed to prepare test images: {}", e);
@@ -544,6 +1539,22 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    if args.lossy {
+        let results = compare_lossy(&converted_images.rgba_images, args.metric)?;
+        let output = format_quality_report(format, &results)?;
+        match &args.output {
+            Some(path) => {
+                fs::write(path, output)?;
+                eprintln!("Wrote results to {}", path.display());
+            }
+            None => print!("{}", output),
+        }
+        if let Some(path) = &args.report {
+            write_html_report(path, report::quality_html(&results))?;
+        }
+        return Ok(());
+    }
+
     // Create encoders
     let qoir_encoder = QoirEncoder {
         options: EncodeOptions {
@@ -555,36 +1566,71 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let jpeg_encoder = JpegEncoder { quality: 90 };
     let png_encoder = PngEncoder;
+    let qoi_encoder = QoiEncoder;
+    let webp_encoder = WebpEncoder { quality: None };
+    let avif_encoder = AvifEncoder { quality: 80.0, speed: 8 };
+    #[cfg(feature = "jxl")]
+    let jxl_encoder = jxl::JxlEncoder { effort: 3 };
+    #[cfg(feature = "heif")]
+    let heif_encoder = heif::HeifEncoder { quality: 90 };
 
     // Create decoders
     let qoir_decoder = QoirDecoder {
+        name: "QOIR",
         options: DecodeOptions::default(),
+        region: DecodeRegion::Whole,
     };
 
     let jpeg_decoder = JpegDecoder;
     let png_decoder = PngDecoder;
+    let qoi_decoder = QoiDecoder;
+    let webp_decoder = WebpDecoder;
 
     // Run encoding benchmarks
     let mut encode_results = Vec::new();
 
-    if let Ok(results) = benchmark_encode(&qoir_encoder, &converted_images.rgba_images, iterations, freq) {
+    if let Ok(results) = benchmark_encode(&qoir_encoder, &converted_images.rgba_images, &settings) {
         encode_results.push(results);
     }
 
-    if let Ok(results) = benchmark_encode(&jpeg_encoder, &converted_images.rgba_images, iterations, freq) {
+    if let Ok(results) = benchmark_encode(&jpeg_encoder, &converted_images.rgba_images, &settings) {
         encode_results.push(results);
     }
 
-    if let Ok(results) = benchmark_encode(&png_encoder, &converted_images.rgba_images, iterations, freq) {
+    if let Ok(results) = benchmark_encode(&png_encoder, &converted_images.rgba_images, &settings) {
         encode_results.push(results);
     }
 
+    if let Ok(results) = benchmark_encode(&qoi_encoder, &converted_images.rgba_images, &settings) {
+        encode_results.push(results);
+    }
+
+    if let Ok(results) = benchmark_encode(&webp_encoder, &converted_images.rgba_images, &settings) {
+        encode_results.push(results);
+    }
+
+    if let Ok(results) = benchmark_encode(&avif_encoder, &converted_images.rgba_images, &settings) {
+        encode_results.push(results);
+    }
+
+    #[cfg(feature = "jxl")]
+    match benchmark_encode(&jxl_encoder, &converted_images.rgba_images, &settings) {
+        Ok(results) => encode_results.push(results),
+        Err(e) => eprintln!("Skipping JPEG XL: {}", e),
+    }
+
+    #[cfg(feature = "heif")]
+    match benchmark_encode(&heif_encoder, &converted_images.rgba_images, &settings) {
+        Ok(results) => encode_results.push(results),
+        Err(e) => eprintln!("Skipping HEIC: {}", e),
+    }
+
+    break_down(&mut encode_results, &converted_images.info, args.per_image, args.group_by);
+
     // Display encoding results
-    print_benchmark_table_header("ENCODING BENCHMARK RESULTS");
-    for result in &encode_results {
-        println!("{}", result);
+    if print_tables {
+        print!("{}", benchmark_table("ENCODING BENCHMARK RESULTS", &encode_results));
     }
-    print_benchmark_table_footer();
 
     // Run decoding benchmarks
     let mut decode_results = Vec::new();
@@ -595,8 +1641,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let Ok(results) = benchmark_decode(
                 &qoir_decoder,
                 &converted_images.qoir_files,
-                iterations,
-                freq
+                &settings
             )
         {
             decode_results.push(results);
@@ -605,14 +1650,37 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         eprintln!("Warning: No QOIR files available for decoding benchmark");
     }
 
+    // QOIR decoding into other pixel formats and regions
+    if args.decode_variants && !converted_images.qoir_files.is_empty() {
+        let variants = [
+            ("QOIR RGB", PixelFormat::RGB, DecodeRegion::Whole),
+            ("QOIR BGRA", PixelFormat::BGRANonPremul, DecodeRegion::Whole),
+            ("QOIR PMA", PixelFormat::RGBAPremul, DecodeRegion::Whole),
+            ("QOIR crop", PixelFormat::RGBANonPremul, DecodeRegion::Crop),
+            ("QOIR shift", PixelFormat::RGBANonPremul, DecodeRegion::Offset),
+        ];
+        for (name, pixel_format, region) in variants {
+            let decoder = QoirDecoder {
+                name,
+                options: DecodeOptions {
+                    pixel_format,
+                    ..Default::default()
+                },
+                region,
+            };
+            if let Ok(results) = benchmark_decode(&decoder, &converted_images.qoir_files, &settings) {
+                decode_results.push(results);
+            }
+        }
+    }
+
     // JPEG decoding benchmark
     if !converted_images.jpeg_files.is_empty() {
         if
             let Ok(results) = benchmark_decode(
                 &jpeg_decoder,
                 &converted_images.jpeg_files,
-                iterations,
-                freq
+                &settings
             )
         {
             decode_results.push(results);
@@ -627,8 +1695,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             let Ok(results) = benchmark_decode(
                 &png_decoder,
                 &converted_images.png_files,
-                iterations,
-                freq
+                &settings
             )
         {
             decode_results.push(results);
@@ -637,14 +1704,110 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         eprintln!("Warning: No PNG files available for decoding benchmark");
     }
 
+    // QOI decoding benchmark
+    if !converted_images.qoi_files.is_empty() {
+        if
+            let Ok(results) = benchmark_decode(
+                &qoi_decoder,
+                &converted_images.qoi_files,
+                &settings
+            )
+        {
+            decode_results.push(results);
+        }
+    } else {
+        eprintln!("Warning: No QOI files available for decoding benchmark");
+    }
+
+    // WebP decoding benchmark
+    if !converted_images.webp_files.is_empty() {
+        if
+            let Ok(results) = benchmark_decode(
+                &webp_decoder,
+                &converted_images.webp_files,
+                &settings
+            )
+        {
+            decode_results.push(results);
+        }
+    } else {
+        eprintln!("Warning: No WebP files available for decoding benchmark");
+    }
+
+    // AVIF decoding benchmark
+    if !converted_images.avif_files.is_empty() {
+        match benchmark_decode(&avif::AvifDecoder, &converted_images.avif_files, &settings) {
+            Ok(results) => decode_results.push(results),
+            Err(e) => eprintln!("Skipping AVIF decoding: {}", e),
+        }
+    } else {
+        eprintln!("Warning: No AVIF files available for decoding benchmark");
+    }
+
+    // JPEG XL and HEIC decoding benchmarks, on files their encoders write
+    #[cfg(feature = "jxl")]
+    match
+        benchmark_optional_decode(
+            &jxl_encoder,
+            &jxl::JxlDecoder,
+            &converted_images.rgba_images,
+            &settings
+        )
+    {
+        Ok(results) => decode_results.push(results),
+        Err(e) => eprintln!("Skipping JPEG XL: {}", e),
+    }
+
+    #[cfg(feature = "heif")]
+    match
+        benchmark_optional_decode(
+            &heif_encoder,
+            &heif::HeifDecoder,
+            &converted_images.rgba_images,
+            &settings
+        )
+    {
+        Ok(results) => decode_results.push(results),
+        Err(e) => eprintln!("Skipping HEIC: {}", e),
+    }
+
+    #[cfg(not(feature = "jxl"))]
+    eprintln!("Note: JPEG XL is not benchmarked; build with --features jxl");
+    #[cfg(not(feature = "heif"))]
+    eprintln!("Note: HEIC is not benchmarked; build with --features heif");
+
+    break_down(&mut decode_results, &converted_images.info, args.per_image, args.group_by);
+
     // Display decoding results
-    print_benchmark_table_header("DECODING BENCHMARK RESULTS");
-    for result in &decode_results {
-        println!("{}", result);
+    let report = BenchmarkReport {
+        iterations,
+        input_dir: &args.input_dir,
+        encode: &encode_results,
+        decode: &decode_results,
+    };
+    if print_tables {
+        print!("{}", benchmark_table("DECODING BENCHMARK RESULTS", &decode_results));
+    } else {
+        let output = format_report(format, &report)?;
+        match &args.output {
+            Some(path) => {
+                fs::write(path, output)?;
+                eprintln!("Wrote results to {}", path.display());
+            }
+            None => print!("{}", output),
+        }
     }
-    print_benchmark_table_footer();
 
-    println!("\nBenchmarks finished.");
+    if let Some(path) = &args.save_baseline {
+        baseline::save(path, &report)?;
+    }
+    if let Some(path) = &args.report {
+        write_html_report(path, report::benchmark_html(&report))?;
+    }
+    eprintln!("\nBenchmarks finished.");
+    if let Some(path) = &args.compare_baseline {
+        baseline::compare(path, &report, args.regression_threshold)?;
+    }
     // Temp dir will be automatically cleaned up when the ConvertedImages struct goes out of scope
 
     Ok(())
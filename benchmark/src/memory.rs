@@ -0,0 +1,101 @@
+// Memory measurement for the benchmarks.
+//
+// Two numbers are tracked, because neither covers everything:
+// - The heap peak counts what goes through Rust's global allocator, for each
+//   encode or decode on its own. It misses the C libraries (libqoir, libwebp),
+//   which call malloc directly.
+// - The peak RSS (Linux only) sees every allocation, but only per codec: the
+//   high-water mark is reset before a codec's run and read after it. Memory
+//   that the allocator keeps after an earlier run isn't counted again.
+
+use std::alloc::{ GlobalAlloc, Layout, System };
+use std::sync::atomic::{ AtomicUsize, Ordering };
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+// The system allocator, counting the bytes currently allocated and the peak
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc(layout) };
+        if !ptr.is_null() {
+            add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = unsafe { System.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            add(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) };
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = unsafe { System.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+            add(new_size);
+        }
+        new_ptr
+    }
+}
+
+fn add(size: usize) {
+    let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+// Tracks the heap peak of one operation: start it right before, read it right after
+pub struct HeapPeak {
+    baseline: usize,
+}
+
+impl HeapPeak {
+    pub fn start() -> HeapPeak {
+        let baseline = CURRENT.load(Ordering::Relaxed);
+        PEAK.store(baseline, Ordering::Relaxed);
+        HeapPeak { baseline }
+    }
+
+    // Bytes allocated at the peak, on top of what was allocated at the start
+    pub fn bytes(&self) -> usize {
+        PEAK.load(Ordering::Relaxed).saturating_sub(self.baseline)
+    }
+}
+
+// Tracks the growth of the peak resident set size over a run of a codec
+pub struct RssPeak {
+    baseline: Option<usize>,
+}
+
+impl RssPeak {
+    pub fn start() -> RssPeak {
+        // Writing 5 to clear_refs resets VmHWM to the current RSS
+        let reset = std::fs::write("/proc/self/clear_refs", "5").is_ok();
+        RssPeak {
+            baseline: if reset { proc_status_bytes("VmRSS:") } else { None },
+        }
+    }
+
+    // Bytes the RSS grew by at its peak, or None where it can't be measured
+    pub fn bytes(&self) -> Option<usize> {
+        let baseline = self.baseline?;
+        Some(proc_status_bytes("VmHWM:")?.saturating_sub(baseline))
+    }
+}
+
+fn proc_status_bytes(field: &str) -> Option<usize> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with(field))?;
+    let kb: usize = line[field.len()..].trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
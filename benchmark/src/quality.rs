@@ -0,0 +1,368 @@
+// Quality-matched comparison of the lossy codecs.
+//
+// Every lossy QOIR level is compared with the JPEG and WebP files of the same
+// quality: for each image, the lowest JPEG and WebP quality settings whose
+// output scores at least as well as QOIR's is found by binary search, and the
+// sizes are compared at those settings. Transparent images are flattened onto
+// black, since JPEG can't store alpha.
+
+use clap::ValueEnum;
+use qoir_rs::EncodeOptions;
+use serde::Serialize;
+use std::fmt::Write as _;
+
+use crate::{
+    BenchmarkDecoder,
+    BenchmarkEncoder,
+    DecodeRegion,
+    ImageData,
+    JpegDecoder,
+    JpegEncoder,
+    OutputFormat,
+    QoirDecoder,
+    QoirEncoder,
+    WebpDecoder,
+    WebpEncoder,
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityMetric {
+    /// Peak signal-to-noise ratio of the color channels, in dB
+    Psnr,
+    /// Mean structural similarity of the luma
+    Ssim,
+}
+
+impl QualityMetric {
+    pub fn name(self) -> &'static str {
+        match self {
+            QualityMetric::Psnr => "PSNR",
+            QualityMetric::Ssim => "SSIM",
+        }
+    }
+
+    fn measure(self, original: &ImageData, decoded: &ImageData) -> f64 {
+        match self {
+            QualityMetric::Psnr => psnr(original, decoded),
+            QualityMetric::Ssim => ssim(original, decoded),
+        }
+    }
+}
+
+// The sizes of every codec at the quality of one QOIR lossiness level
+#[derive(Debug, Serialize)]
+pub struct QualityResults {
+    pub lossiness: u8,
+    pub metric: QualityMetric,
+    // Mean score of the QOIR images, which the other codecs are matched to
+    pub score: f64,
+    pub qoir_kb: f64,
+    pub jpeg: MatchedCodec,
+    pub webp: MatchedCodec,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchedCodec {
+    pub size_kb: f64,
+    // Size relative to QOIR at the same quality
+    pub size_percentage: f64,
+    pub avg_quality: f64,
+    // Images that didn't reach QOIR's score even at quality 100
+    pub unmatched: usize,
+}
+
+pub fn compare_lossy(
+    images: &[ImageData],
+    metric: QualityMetric
+) -> Result<Vec<QualityResults>, Box<dyn std::error::Error>> {
+    let images: Vec<ImageData> = images.iter().map(flatten).collect();
+    let mut results = Vec::new();
+    for lossiness in 1..=7 {
+        eprintln!("Matching JPEG and WebP to QOIR lossiness {}...", lossiness);
+        let qoir_encoder = QoirEncoder {
+            options: EncodeOptions {
+                lossiness,
+                ..Default::default()
+            },
+        };
+        let qoir_decoder = QoirDecoder {
+            name: "QOIR",
+            options: Default::default(),
+            region: DecodeRegion::Whole,
+        };
+
+        let mut qoir_bytes = 0;
+        let mut scores = Vec::new();
+        let mut jpeg = Matcher::default();
+        let mut webp = Matcher::default();
+        for image in &images {
+            let encoded = qoir_encoder.encode(image)?;
+            let score = metric.measure(image, &qoir_decoder.decode(&encoded)?);
+            qoir_bytes += encoded.len();
+            scores.push(score);
+
+            jpeg.match_score(image, metric, score, |quality| {
+                let encoded = (JpegEncoder { quality: quality as u8 }).encode(image)?;
+                let decoded = JpegDecoder.decode(&encoded)?;
+                Ok((encoded.len(), decoded))
+            })?;
+            webp.match_score(image, metric, score, |quality| {
+                let encoded = (WebpEncoder { quality: Some(quality as f32) }).encode(image)?;
+                let decoded = WebpDecoder.decode(&encoded)?;
+                Ok((encoded.len(), decoded))
+            })?;
+        }
+
+        let qoir_kb = (qoir_bytes as f64) / 1024.0;
+        results.push(QualityResults {
+            lossiness,
+            metric,
+            score: scores.iter().sum::<f64>() / (scores.len().max(1) as f64),
+            qoir_kb,
+            jpeg: jpeg.finish(qoir_kb, images.len()),
+            webp: webp.finish(qoir_kb, images.len()),
+        });
+    }
+    Ok(results)
+}
+
+#[derive(Default)]
+struct Matcher {
+    bytes: usize,
+    qualities: u32,
+    unmatched: usize,
+}
+
+impl Matcher {
+    // Finds the lowest quality from 1 to 100 scoring at least `target`, assuming
+    // the score grows with the quality, and adds its size
+    fn match_score(
+        &mut self,
+        original: &ImageData,
+        metric: QualityMetric,
+        target: f64,
+        encode: impl Fn(u32) -> Result<(usize, ImageData), Box<dyn std::error::Error>>
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut low, mut high) = (1, 100);
+        let mut best = None;
+        while low <= high {
+            let quality = (low + high) / 2;
+            let (size, decoded) = encode(quality)?;
+            if metric.measure(original, &decoded) >= target {
+                best = Some((quality, size));
+                high = quality - 1;
+            } else {
+                low = quality + 1;
+            }
+        }
+        let (quality, size) = match best {
+            Some(best) => best,
+            None => {
+                self.unmatched += 1;
+                (100, encode(100)?.0)
+            }
+        };
+        self.bytes += size;
+        self.qualities += quality;
+        Ok(())
+    }
+
+    fn finish(self, qoir_kb: f64, images: usize) -> MatchedCodec {
+        let size_kb = (self.bytes as f64) / 1024.0;
+        MatchedCodec {
+            size_kb,
+            size_percentage: if qoir_kb > 0.0 { (size_kb / qoir_kb) * 100.0 } else { 0.0 },
+            avg_quality: (self.qualities as f64) / (images.max(1) as f64),
+            unmatched: self.unmatched,
+        }
+    }
+}
+
+// JPEG has no alpha, so the images are composited onto black first, and every codec
+// compresses the same opaque pixels
+fn flatten(image: &ImageData) -> ImageData {
+    let pixels = image.pixels
+        .chunks_exact(image.bytes_per_pixel)
+        .flat_map(|pixel| {
+            let alpha = if image.bytes_per_pixel == 4 { pixel[3] as u32 } else { 255 };
+            let [r, g, b] = [0, 1, 2].map(|c| (((pixel[c] as u32) * alpha + 127) / 255) as u8);
+            [r, g, b, 0xff]
+        })
+        .collect();
+    ImageData {
+        pixels,
+        width: image.width,
+        height: image.height,
+        bytes_per_pixel: 4,
+    }
+}
+
+// The color channels of each pixel
+fn rgb(image: &ImageData) -> impl Iterator<Item = [f64; 3]> + '_ {
+    image.pixels
+        .chunks_exact(image.bytes_per_pixel)
+        .map(|pixel| [0, 1, 2].map(|c| pixel[c] as f64))
+}
+
+fn psnr(a: &ImageData, b: &ImageData) -> f64 {
+    let mut squared_error = 0.0;
+    let mut samples = 0;
+    for (a, b) in rgb(a).zip(rgb(b)) {
+        for (a, b) in a.into_iter().zip(b) {
+            squared_error += (a - b).powi(2);
+            samples += 1;
+        }
+    }
+    let mse = squared_error / (samples.max(1) as f64);
+    // Identical images would score infinity, which no mean survives
+    (10.0 * ((255.0 * 255.0) / mse).log10()).min(100.0)
+}
+
+// Mean structural similarity of the luma, over 8x8 windows placed every 4 pixels
+fn ssim(a: &ImageData, b: &ImageData) -> f64 {
+    const WINDOW: u32 = 8;
+    const STEP: usize = 4;
+    const C1: f64 = 0.01 * 255.0 * (0.01 * 255.0);
+    const C2: f64 = 0.03 * 255.0 * (0.03 * 255.0);
+
+    let luma = |image: &ImageData| -> Vec<f64> {
+        rgb(image)
+            .map(|[r, g, b]| 0.299 * r + 0.587 * g + 0.114 * b)
+            .collect()
+    };
+    let (la, lb) = (luma(a), luma(b));
+    let (width, height) = (a.width, a.height);
+    let window_width = WINDOW.min(width);
+    let window_height = WINDOW.min(height);
+    if window_width == 0 || window_height == 0 {
+        return 1.0;
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y0 in (0..=height - window_height).step_by(STEP) {
+        for x0 in (0..=width - window_width).step_by(STEP) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (
+                0.0, 0.0, 0.0, 0.0, 0.0,
+            );
+            for y in y0..y0 + window_height {
+                for x in x0..x0 + window_width {
+                    let i = (y * width + x) as usize;
+                    let (va, vb) = (la[i], lb[i]);
+                    sum_a += va;
+                    sum_b += vb;
+                    sum_aa += va * va;
+                    sum_bb += vb * vb;
+                    sum_ab += va * vb;
+                }
+            }
+            let n = (window_width * window_height) as f64;
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+            total +=
+                ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2)) /
+                ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / (windows as f64)
+}
+
+pub fn format_quality_report(
+    format: OutputFormat,
+    results: &[QualityResults]
+) -> Result<String, Box<dyn std::error::Error>> {
+    let mut output = String::new();
+    match format {
+        OutputFormat::Table => {
+            let rule =
+                "|-----------+----------+------------+------------+---------+-----------+------------+---------+-----------|";
+            let metric = results.first().map_or(QualityMetric::Psnr, |r| r.metric);
+            writeln!(output, "\nQUALITY-MATCHED SIZES ({})\n{}", metric.name(), rule)?;
+            output.push_str(
+                "| QOIR      | Score    | QOIR Size  | JPEG Size  | JPEG    | JPEG      | WebP Size  | WebP    | WebP      |\n"
+            );
+            output.push_str(
+                "| Lossiness |          | (KB)       | (KB)       | (%)     | Quality   | (KB)       | (%)     | Quality   |\n"
+            );
+            writeln!(output, "{}", rule)?;
+            for r in results {
+                writeln!(
+                    output,
+                    "| {:<9} | {:<8.3} | {:<10.2} | {:<10.2} | {:<7.1} | {:<9} | {:<10.2} | {:<7.1} | {:<9} |",
+                    r.lossiness,
+                    r.score,
+                    r.qoir_kb,
+                    r.jpeg.size_kb,
+                    r.jpeg.size_percentage,
+                    matched_quality(&r.jpeg),
+                    r.webp.size_kb,
+                    r.webp.size_percentage,
+                    matched_quality(&r.webp)
+                )?;
+            }
+            writeln!(output, "{}", rule)?;
+        }
+        OutputFormat::Json => {
+            output = serde_json::to_string_pretty(results)?;
+            output.push('\n');
+        }
+        OutputFormat::Csv => {
+            output.push_str(
+                "lossiness,metric,score,qoir_kb,jpeg_kb,jpeg_percent,jpeg_avg_quality,jpeg_unmatched,webp_kb,webp_percent,webp_avg_quality,webp_unmatched\n"
+            );
+            for r in results {
+                writeln!(
+                    output,
+                    "{},{},{:.4},{:.2},{:.2},{:.1},{:.1},{},{:.2},{:.1},{:.1},{}",
+                    r.lossiness,
+                    r.metric.name(),
+                    r.score,
+                    r.qoir_kb,
+                    r.jpeg.size_kb,
+                    r.jpeg.size_percentage,
+                    r.jpeg.avg_quality,
+                    r.jpeg.unmatched,
+                    r.webp.size_kb,
+                    r.webp.size_percentage,
+                    r.webp.avg_quality,
+                    r.webp.unmatched
+                )?;
+            }
+        }
+        OutputFormat::Md => {
+            output.push_str(
+                "| QOIR lossiness | Score | QOIR size (KB) | JPEG size (KB) | JPEG (%) | JPEG quality | WebP size (KB) | WebP (%) | WebP quality |\n"
+            );
+            output.push_str("|--:|--:|--:|--:|--:|--:|--:|--:|--:|\n");
+            for r in results {
+                writeln!(
+                    output,
+                    "| {} | {:.3} | {:.2} | {:.2} | {:.1} | {} | {:.2} | {:.1} | {} |",
+                    r.lossiness,
+                    r.score,
+                    r.qoir_kb,
+                    r.jpeg.size_kb,
+                    r.jpeg.size_percentage,
+                    matched_quality(&r.jpeg),
+                    r.webp.size_kb,
+                    r.webp.size_percentage,
+                    matched_quality(&r.webp)
+                )?;
+            }
+        }
+    }
+    Ok(output)
+}
+
+// The average quality, with the number of images that fell short of QOIR's score
+fn matched_quality(codec: &MatchedCodec) -> String {
+    if codec.unmatched > 0 {
+        format!("{:.0} ({}!)", codec.avg_quality, codec.unmatched)
+    } else {
+        format!("{:.0}", codec.avg_quality)
+    }
+}
@@ -0,0 +1,186 @@
+// A standalone HTML page of the results, with the charts drawn as inline SVG so the
+// file can be mailed or attached without any scripts or other files.
+
+use std::fmt::Write as _;
+
+use crate::quality::QualityResults;
+use crate::{ BenchmarkReport, BenchmarkResults };
+
+const COLORS: [&str; 6] = ["#4e79a7", "#f28e2b", "#59a14f", "#e15759", "#76b7b2", "#b07aa1"];
+
+// One bar of a chart; bars of the same series share a color
+struct Bar {
+    label: String,
+    value: f64,
+    series: usize,
+}
+
+pub fn benchmark_html(report: &BenchmarkReport) -> String {
+    let mut body = format!(
+        "<p>{} iterations per image, images from <code>{}</code>. Higher speed and lower size are better.</p>\n",
+        report.iterations,
+        escape(&report.input_dir.display().to_string())
+    );
+    for (title, results) in [("Encoding", report.encode), ("Decoding", report.decode)] {
+        if results.is_empty() {
+            continue;
+        }
+        let _ = writeln!(body, "<h2>{}</h2>", title);
+        body.push_str(&bar_chart("Speed", "images/s", &bars(results, |r| r.speed_images_s)));
+        body.push_str(&bar_chart("Average time per image", "ms", &bars(results, |r| r.avg_time_per_image_ms)));
+        if title == "Encoding" {
+            body.push_str(&bar_chart("Size of the RGBA pixels", "%", &bars(results, |r| r.avg_size_change_percentage)));
+        }
+        body.push_str(&bar_chart("Peak heap", "KB", &bars(results, |r| r.peak_heap_kb)));
+        body.push_str(&results_table(results));
+    }
+    page("Image format benchmark", &body)
+}
+
+pub fn quality_html(results: &[QualityResults]) -> String {
+    let metric = results.first().map_or("PSNR", |r| r.metric.name());
+    let mut body = format!(
+        "<p>For each QOIR lossiness level, JPEG and WebP use the lowest quality that scores at least QOIR's {}. Lower is better.</p>\n",
+        metric
+    );
+    let mut sizes = Vec::new();
+    for r in results {
+        for (series, (codec, size_kb)) in [
+            ("QOIR", r.qoir_kb),
+            ("JPEG", r.jpeg.size_kb),
+            ("WebP", r.webp.size_kb),
+        ]
+            .into_iter()
+            .enumerate() {
+            sizes.push(Bar {
+                label: format!("Level {} {}", r.lossiness, codec),
+                value: size_kb,
+                series,
+            });
+        }
+    }
+    body.push_str(&bar_chart("Total size at matched quality", "KB", &sizes));
+
+    body.push_str(
+        "<table>\n<tr><th>QOIR lossiness</th><th>Score</th><th>QOIR (KB)</th><th>JPEG (KB)</th><th>JPEG quality</th><th>WebP (KB)</th><th>WebP quality</th></tr>\n"
+    );
+    for r in results {
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{:.3}</td><td>{:.2}</td><td>{:.2}</td><td>{:.0}</td><td>{:.2}</td><td>{:.0}</td></tr>",
+            r.lossiness,
+            r.score,
+            r.qoir_kb,
+            r.jpeg.size_kb,
+            r.jpeg.avg_quality,
+            r.webp.size_kb,
+            r.webp.avg_quality
+        );
+    }
+    body.push_str("</table>\n");
+    page("Lossy image formats at matched quality", &body)
+}
+
+fn bars(results: &[BenchmarkResults], value: fn(&BenchmarkResults) -> f64) -> Vec<Bar> {
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, r)| Bar {
+            label: r.encoder_name.clone(),
+            value: value(r),
+            series: i,
+        })
+        .collect()
+}
+
+// A horizontal bar chart, scaled to the largest value
+fn bar_chart(title: &str, unit: &str, bars: &[Bar]) -> String {
+    const LABEL_WIDTH: f64 = 140.0;
+    const BAR_WIDTH: f64 = 480.0;
+    const ROW_HEIGHT: f64 = 24.0;
+
+    let max = bars.iter().map(|bar| bar.value).fold(0.0, f64::max);
+    let height = ROW_HEIGHT * (bars.len() as f64) + 8.0;
+    let mut svg = format!(
+        "<figure>\n<figcaption>{} ({})</figcaption>\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">\n",
+        escape(title),
+        escape(unit),
+        LABEL_WIDTH + BAR_WIDTH + 100.0,
+        height
+    );
+    for (i, bar) in bars.iter().enumerate() {
+        let y = (i as f64) * ROW_HEIGHT + 4.0;
+        let width = if max > 0.0 { (bar.value / max) * BAR_WIDTH } else { 0.0 };
+        let _ = writeln!(
+            svg,
+            "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text><rect x=\"{}\" y=\"{}\" width=\"{:.1}\" height=\"{}\" fill=\"{}\"/><text x=\"{:.1}\" y=\"{}\">{:.2}</text>",
+            LABEL_WIDTH - 8.0,
+            y + 15.0,
+            escape(&bar.label),
+            LABEL_WIDTH,
+            y,
+            width,
+            ROW_HEIGHT - 6.0,
+            COLORS[bar.series % COLORS.len()],
+            LABEL_WIDTH + width + 6.0,
+            y + 15.0,
+            bar.value
+        );
+    }
+    svg.push_str("</svg>\n</figure>\n");
+    svg
+}
+
+fn results_table(results: &[BenchmarkResults]) -> String {
+    let mut table = String::from(
+        "<table>\n<tr><th>Format</th><th>Images</th><th>Avg time (ms)</th><th>p90 (ms)</th><th>Orig size (KB)</th><th>Proc size (KB)</th><th>Size (%)</th><th>Throughput (MB/s)</th><th>Speed (imgs/s)</th></tr>\n"
+    );
+    for r in results {
+        let _ = writeln!(
+            table,
+            "<tr><td>{}</td><td>{}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.2}</td></tr>",
+            escape(&r.encoder_name),
+            r.num_images_tested,
+            r.avg_time_per_image_ms,
+            r.latency.p90_ms,
+            r.avg_size_original_kb,
+            r.avg_size_processed_kb,
+            r.avg_size_change_percentage,
+            r.throughput_mb_s,
+            r.speed_images_s
+        );
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2em auto; max-width: 60em; color: #222; }}
+figure {{ margin: 1.5em 0; }}
+figcaption {{ font-weight: bold; margin-bottom: 0.5em; }}
+svg text {{ font-size: 13px; }}
+table {{ border-collapse: collapse; margin: 1em 0 2em; }}
+th, td {{ border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: right; }}
+th:first-child, td:first-child {{ text-align: left; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{body}</body>
+</html>
+",
+        title = escape(title),
+        body = body
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,151 @@
+//! Micro-benchmarks of the library entry points, run with `cargo bench -p qoir-rs`.
+//!
+//! The images are generated rather than read from `data/`, so every size is
+//! available and the results don't depend on which test files are present.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Image, PixelFormat, decode_basic_metadata, decode_from_memory,
+    encode_to_memory, read_metadata,
+};
+use std::hint::black_box;
+
+const SIZES: [u32; 4] = [64, 256, 1024, 2048];
+
+/// An RGBA image with smooth gradients and some noise, which compresses
+/// roughly like a photo rather than like a flat fill.
+fn test_pixels(size: u32) -> Vec<u8> {
+    let mut state = 0x2545_f491u32;
+    let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let noise = (state & 0x0F) as u8;
+            let r = (x * 255 / size) as u8;
+            let g = (y * 255 / size) as u8;
+            let b = ((x + y) * 127 / size) as u8;
+            pixels.extend_from_slice(&[
+                r.wrapping_add(noise),
+                g.wrapping_add(noise),
+                b.wrapping_add(noise),
+                0xFF,
+            ]);
+        }
+    }
+    pixels
+}
+
+fn test_image(pixels: &[u8], size: u32) -> Image<'_> {
+    Image {
+        pixels,
+        width: size,
+        height: size,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: size as usize * 4,
+    }
+}
+
+fn encoded(size: u32, options: EncodeOptions) -> Vec<u8> {
+    let pixels = test_pixels(size);
+    encode_to_memory(test_image(&pixels, size), options)
+        .expect("Failed to encode")
+        .data
+        .to_vec()
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode_to_memory");
+    for size in SIZES {
+        let pixels = test_pixels(size);
+        group.throughput(Throughput::Bytes(pixels.len() as u64));
+        for lossiness in [0, 2] {
+            let options = EncodeOptions {
+                lossiness,
+                ..Default::default()
+            };
+            let id = BenchmarkId::new(format!("lossiness {lossiness}"), size);
+            group.bench_with_input(id, &pixels, |b, pixels| {
+                b.iter(|| encode_to_memory(test_image(black_box(pixels), size), options.clone()))
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_from_memory");
+    for size in SIZES {
+        let data = encoded(size, EncodeOptions::default());
+        group.throughput(Throughput::Bytes(size as u64 * size as u64 * 4));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| decode_from_memory(black_box(data), DecodeOptions::default()))
+        });
+    }
+    group.finish();
+}
+
+fn bench_metadata(c: &mut Criterion) {
+    let mut group = c.benchmark_group("metadata");
+    for size in SIZES {
+        let data = encoded(
+            size,
+            EncodeOptions {
+                icc_profile: Some(vec![0; 3144]),
+                exif: Some(vec![0; 512]),
+                ..Default::default()
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("decode_basic_metadata", size),
+            &data,
+            |b, data| b.iter(|| decode_basic_metadata(black_box(data))),
+        );
+        group.bench_with_input(BenchmarkId::new("read_metadata", size), &data, |b, data| {
+            b.iter(|| read_metadata(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_conversion(c: &mut Criterion) {
+    let formats = [
+        PixelFormat::BGRANonPremul,
+        PixelFormat::RGBAPremul,
+        PixelFormat::RGB,
+    ];
+    let mut group = c.benchmark_group("conversion");
+    for size in SIZES {
+        let pixels = test_pixels(size);
+        let data = encoded(size, EncodeOptions::default());
+        group.throughput(Throughput::Bytes(pixels.len() as u64));
+        for pixel_format in formats {
+            // Converting while decoding, against decoding then converting.
+            let options = DecodeOptions {
+                pixel_format,
+                ..Default::default()
+            };
+            let id = BenchmarkId::new(format!("decode to {pixel_format:?}"), size);
+            group.bench_with_input(id, &data, |b, data| {
+                b.iter(|| decode_from_memory(black_box(data), options.clone()))
+            });
+
+            let id = BenchmarkId::new(format!("to_pixel_format {pixel_format:?}"), size);
+            group.bench_with_input(id, &pixels, |b, pixels| {
+                b.iter(|| test_image(black_box(pixels), size).to_pixel_format(pixel_format))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_encode,
+    bench_decode,
+    bench_metadata,
+    bench_conversion
+);
+criterion_main!(benches);
@@ -1,20 +1,60 @@
-use std::{env, path::PathBuf};
-
 fn main() {
-    let mut build = cc::Build::new();
-    #[cfg(not(feature = "simd"))]
-    build.define("QOIR_CONFIG__DISABLE_SIMD", None);
+    println!("cargo::rustc-check-cfg=cfg(qoir_avx2)");
+    println!("cargo::rustc-check-cfg=cfg(qoir_simd)");
+    println!("cargo::rustc-check-cfg=cfg(qoir_large_luts)");
 
-    #[cfg(feature = "large_luts")]
-    build.define("QOIR_CONFIG__DISABLE_LARGE_LOOK_UP_TABLES", None);
+    #[cfg(feature = "c-backend")]
+    build_qoir();
+}
 
-    build
-        .file("src/qoir.c")
-        .include("../vendor/qoir/src")
-        .compile("qoir");
+/// The range of system `qoir` versions, as reported by pkg-config, whose ABI
+/// matches the bindings this crate is written against. The upper bound is
+/// exclusive.
+#[cfg(feature = "system-qoir")]
+const SYSTEM_QOIR_VERSIONS: (&str, &str) = ("0.1.0", "0.2.0");
 
-    let bindings = bindgen::Builder::default()
-        .header("../vendor/qoir/src/qoir.h")
+#[cfg(feature = "c-backend")]
+fn build_qoir() {
+    use std::{env, path::PathBuf};
+
+    #[cfg(feature = "system-qoir")]
+    let bindings = {
+        let (min_version, max_version) = SYSTEM_QOIR_VERSIONS;
+        let library = pkg_config::Config::new()
+            .range_version(min_version..max_version)
+            .probe("qoir")
+            .unwrap_or_else(|e| {
+                panic!(
+                    "The `system-qoir` feature needs qoir >= {min_version}, < {max_version} \
+                     registered with pkg-config: {e}"
+                )
+            });
+        println!("cargo::rustc-env=QOIR_RS_C_LIBRARY_REV={}", library.version);
+
+        // Generate the bindings from the installed header, so they describe
+        // the library that is actually linked.
+        bindgen::Builder::default()
+            .header_contents("qoir_system.h", "#include <qoir.h>\n")
+            .clang_args(
+                library
+                    .include_paths
+                    .iter()
+                    .map(|path| format!("-I{}", path.display())),
+            )
+    };
+
+    let target = TargetConfig::from_env();
+
+    #[cfg(not(feature = "system-qoir"))]
+    let bindings = {
+        compile_vendored_qoir(&target);
+        bindgen::Builder::default().header("../vendor/qoir/src/qoir.h")
+    };
+
+    let bindings = bindings
+        .clang_args(&target.clang_args)
+        // Keep the generated code usable without `std`.
+        .use_core()
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()))
         .generate()
         .expect("Unable to generate bindings");
@@ -24,3 +64,235 @@ fn main() {
         .write_to_file(out_path.join("qoir_bindings.rs"))
         .expect("Couldn't write bindings!");
 }
+
+#[cfg(all(feature = "c-backend", not(feature = "system-qoir")))]
+fn compile_vendored_qoir(target: &TargetConfig) {
+    use std::env;
+
+    // `cfg!(target_arch = ...)` describes the host running this script, so the
+    // target has to be read from the environment cargo sets for build scripts.
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let is_wasm = target_arch == "wasm32";
+
+    let mut build = cc::Build::new();
+    target.configure(&mut build);
+
+    // The SIMD code paths rely on x86 intrinsics headers, which clang does not
+    // provide when targeting wasm.
+    if cfg!(feature = "simd") && !is_wasm {
+        println!("cargo::rustc-cfg=qoir_simd");
+    } else {
+        build.define("QOIR_CONFIG__DISABLE_SIMD", None);
+    }
+
+    if cfg!(feature = "large_luts") {
+        println!("cargo::rustc-cfg=qoir_large_luts");
+    } else {
+        build.define("QOIR_CONFIG__DISABLE_LARGE_LOOK_UP_TABLES", None);
+    }
+
+    // Reported by `build_info`. Crates unpacked from the registry have no git
+    // checkout, so the revision is only known when building from the repository.
+    if let Some(rev) = vendored_qoir_rev() {
+        println!("cargo::rustc-env=QOIR_RS_C_LIBRARY_REV={rev}");
+    }
+
+    build
+        .file("src/qoir.c")
+        .include("../vendor/qoir/src")
+        .compile("qoir");
+
+    // With `runtime-simd`, a second copy of qoir built for AVX2 is linked in
+    // and picked at run time on CPUs that support it.
+    if cfg!(feature = "runtime-simd") && matches!(target_arch.as_str(), "x86" | "x86_64") {
+        let mut avx2 = cc::Build::new();
+        target.configure(&mut avx2);
+        if !cfg!(feature = "large_luts") {
+            avx2.define("QOIR_CONFIG__DISABLE_LARGE_LOOK_UP_TABLES", None);
+        }
+
+        avx2.file("src/qoir_avx2.c")
+            .include("../vendor/qoir/src")
+            .flag_if_supported("-mavx2")
+            .flag_if_supported("/arch:AVX2")
+            // The static copy has functions that the shim never calls.
+            .flag_if_supported("-Wno-unused-function")
+            .compile("qoir_avx2");
+        println!("cargo::rustc-cfg=qoir_avx2");
+    }
+}
+
+/// The git revision of the vendored `qoir` sources, if they are a checkout.
+#[cfg(all(feature = "c-backend", not(feature = "system-qoir")))]
+fn vendored_qoir_rev() -> Option<String> {
+    // Without this check, git would report the revision of an enclosing
+    // repository.
+    if !std::path::Path::new("../vendor/qoir/.git").exists() {
+        return None;
+    }
+    let output = std::process::Command::new("git")
+        .args(["-C", "../vendor/qoir", "rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()?;
+    let rev = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !rev.trim().is_empty()).then(|| rev.trim().to_string())
+}
+
+/// Settings for cross-compiling to targets that `cc` and bindgen do not set up
+/// on their own, mainly Android (through the NDK) and iOS.
+///
+/// Every setting can be overridden through the environment: `CC_<target>` (or
+/// `CC`) picks the compiler, `QOIR_RS_SYSROOT` the sysroot, `ANDROID_PLATFORM`
+/// the Android API level and `QOIR_RS_EMBED_BITCODE=1` embeds bitcode in iOS
+/// builds.
+#[cfg(feature = "c-backend")]
+#[derive(Default)]
+struct TargetConfig {
+    /// The C compiler to use, when the one `cc` would guess does not exist.
+    compiler: Option<std::path::PathBuf>,
+    /// Flags for the C compiler.
+    cflags: Vec<String>,
+    /// Arguments for the clang that bindgen parses `qoir.h` with.
+    clang_args: Vec<String>,
+}
+
+/// The Android API level to build for when `ANDROID_PLATFORM` is not set. The
+/// NDK does not support anything older.
+#[cfg(feature = "c-backend")]
+const DEFAULT_ANDROID_API_LEVEL: &str = "21";
+
+#[cfg(feature = "c-backend")]
+impl TargetConfig {
+    fn from_env() -> Self {
+        use std::env;
+
+        for var in [
+            "QOIR_RS_SYSROOT",
+            "QOIR_RS_EMBED_BITCODE",
+            "ANDROID_PLATFORM",
+            "ANDROID_NDK_HOME",
+            "ANDROID_NDK_ROOT",
+            "SDKROOT",
+        ] {
+            println!("cargo::rerun-if-env-changed={var}");
+        }
+
+        let target = env::var("TARGET").unwrap_or_default();
+        let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+
+        let mut config = match target_os.as_str() {
+            "android" => Self::android(&target),
+            "ios" => Self::ios(&target),
+            _ => Self::default(),
+        };
+
+        // Cross toolchains for musl (and other targets) often keep their
+        // headers and libraries outside the default search paths.
+        if let Ok(sysroot) = env::var("QOIR_RS_SYSROOT") {
+            config.cflags.push(format!("--sysroot={sysroot}"));
+            config.clang_args.push(format!("--sysroot={sysroot}"));
+        }
+        config
+    }
+
+    fn android(target: &str) -> Self {
+        use std::{env, path::PathBuf};
+
+        let api_level = env::var("ANDROID_PLATFORM")
+            .map(|platform| platform.trim_start_matches("android-").to_string())
+            .unwrap_or_else(|_| DEFAULT_ANDROID_API_LEVEL.to_string());
+        // The NDK's clang names 32-bit ARM differently from Rust.
+        let clang_target = match target {
+            "armv7-linux-androideabi" | "thumbv7neon-linux-androideabi" => {
+                "armv7a-linux-androideabi".to_string()
+            }
+            _ => target.to_string(),
+        };
+
+        let mut config = Self::default();
+        // Versioned triples make clang define `__ANDROID_API__`, which the
+        // NDK headers need.
+        config
+            .clang_args
+            .push(format!("--target={clang_target}{api_level}"));
+
+        let Some(ndk) = env::var_os("ANDROID_NDK_HOME").or_else(|| env::var_os("ANDROID_NDK_ROOT"))
+        else {
+            return config;
+        };
+        let host_tag = match env::consts::OS {
+            "macos" => "darwin-x86_64",
+            "windows" => "windows-x86_64",
+            _ => "linux-x86_64",
+        };
+        let toolchain = PathBuf::from(ndk).join(format!("toolchains/llvm/prebuilt/{host_tag}"));
+        config
+            .clang_args
+            .push(format!("--sysroot={}", toolchain.join("sysroot").display()));
+
+        // Recent NDKs only ship compilers named after the API level, so `cc`'s
+        // guess of `<target>-clang` is not found.
+        if !has_compiler_override(target) {
+            let suffix = if env::consts::OS == "windows" {
+                ".cmd"
+            } else {
+                ""
+            };
+            config.compiler =
+                Some(toolchain.join(format!("bin/{clang_target}{api_level}-clang{suffix}")));
+        }
+        config
+    }
+
+    fn ios(target: &str) -> Self {
+        use std::{env, process::Command};
+
+        let sdk = if target.ends_with("-sim") || target.starts_with("x86_64") {
+            "iphonesimulator"
+        } else {
+            "iphoneos"
+        };
+        let sdk_path = env::var("SDKROOT").ok().or_else(|| {
+            let output = Command::new("xcrun")
+                .args(["--sdk", sdk, "--show-sdk-path"])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        });
+
+        let mut config = Self::default();
+        if let Some(sdk_path) = sdk_path {
+            config.clang_args.push("-isysroot".to_string());
+            config.clang_args.push(sdk_path);
+        }
+        // Bitcode is deprecated since Xcode 14, so only embed it on request.
+        if env::var("QOIR_RS_EMBED_BITCODE").is_ok_and(|value| value == "1") {
+            config.cflags.push("-fembed-bitcode".to_string());
+        }
+        config
+    }
+
+    /// Applies the compiler and flags to a `cc` build.
+    #[cfg(not(feature = "system-qoir"))]
+    fn configure(&self, build: &mut cc::Build) {
+        if let Some(compiler) = &self.compiler {
+            build.compiler(compiler);
+        }
+        for flag in &self.cflags {
+            build.flag(flag);
+        }
+    }
+}
+
+/// Whether the user picked a C compiler for `target` through the environment
+/// variables `cc` reads.
+#[cfg(feature = "c-backend")]
+fn has_compiler_override(target: &str) -> bool {
+    let target_var = format!("CC_{}", target.replace('-', "_"));
+    ["CC", "TARGET_CC", &format!("CC_{target}"), &target_var]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some())
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qoir_rs::{DecodeOptions, decode_basic_metadata, decode_from_memory};
+
+fuzz_target!(|data: &[u8]| {
+    // Whatever the metadata claims must agree with a full decode.
+    if let Ok((width, height, _)) = decode_basic_metadata(data)
+        && let Ok(decoded) = decode_from_memory(data, DecodeOptions::default())
+    {
+        assert_eq!((decoded.image.width, decoded.image.height), (width, height));
+    }
+});
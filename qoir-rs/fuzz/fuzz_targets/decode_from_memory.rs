@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qoir_rs::{DecodeOptions, decode_from_memory};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = decode_from_memory(data, DecodeOptions::default());
+});
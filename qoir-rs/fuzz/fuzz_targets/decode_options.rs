@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qoir_rs::{DecodeOptions, decode_from_memory};
+
+fuzz_target!(|input: (DecodeOptions, &[u8])| {
+    let (options, data) = input;
+    let pixel_format = options.pixel_format;
+    if let Ok(decoded) = decode_from_memory(data, options) {
+        let image = decoded.image;
+        assert_eq!(image.pixel_format, pixel_format);
+        assert!(image.pixels.len() >= image.stride_in_bytes * image.height as usize);
+    }
+});
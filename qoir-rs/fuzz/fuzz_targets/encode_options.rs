@@ -0,0 +1,69 @@
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Image, PixelFormat, decode_from_memory, encode_to_memory,
+};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    options: EncodeOptions,
+    width: u8,
+    height: u8,
+    pixel_format: PixelFormat,
+    padding: u8,
+    pixels: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let Input {
+        options,
+        width,
+        height,
+        pixel_format,
+        padding,
+        pixels,
+    } = input;
+    let lossless = options.lossiness == 0;
+    let image = Image {
+        pixels: &pixels,
+        width: width as u32,
+        height: height as u32,
+        pixel_format,
+        stride_in_bytes: width as usize * pixel_format.bytes_per_pixel() + padding as usize,
+    };
+
+    // Anything the encoder accepts must decode again, to the same pixels when
+    // the encoding is lossless.
+    let Ok(encoded) = encode_to_memory(image.clone(), options) else {
+        return;
+    };
+    let options = DecodeOptions {
+        pixel_format,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(encoded.data, options).expect("encoded data must decode");
+    assert_eq!(
+        (decoded.image.width, decoded.image.height),
+        (image.width, image.height)
+    );
+
+    // Premultiplied colors are rounded and the padding byte of `BGRX` and
+    // `RGBX` is not stored, so only the other formats round trip exactly.
+    let exact = matches!(
+        pixel_format,
+        PixelFormat::BGR
+            | PixelFormat::RGB
+            | PixelFormat::BGRANonPremul
+            | PixelFormat::RGBANonPremul
+    );
+    let row_len = image.width as usize * pixel_format.bytes_per_pixel();
+    if lossless && exact && row_len > 0 {
+        let src_rows = image.pixels.chunks(image.stride_in_bytes);
+        let dst_rows = decoded.image.pixels.chunks(decoded.image.stride_in_bytes);
+        for (src, dst) in src_rows.zip(dst_rows).take(image.height as usize) {
+            assert_eq!(&src[..row_len], &dst[..row_len]);
+        }
+    }
+});
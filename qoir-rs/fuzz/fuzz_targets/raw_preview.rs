@@ -0,0 +1,17 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qoir_rs::raw::{PreviewOptions, encode_raw_preview_from_memory, extract_raw_preview};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(jpeg) = extract_raw_preview(data) {
+        assert!(jpeg.starts_with(&[0xFF, 0xD8]));
+    }
+    // Small enough that a large preview or developed image is shrunk, which
+    // exercises the whole path without spending the run on encoding.
+    let options = PreviewOptions {
+        max_size: Some(64),
+        ..Default::default()
+    };
+    let _ = encode_raw_preview_from_memory(data, options);
+});
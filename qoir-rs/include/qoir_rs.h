@@ -0,0 +1,96 @@
+/* C interface to qoir-rs, available when the crate is built with the `capi`
+ * feature. See `src/capi.rs` for details. */
+
+#ifndef QOIR_RS_H
+#define QOIR_RS_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Status codes. Every function returns one of these, zero meaning success. */
+#define QOIR_RS_STATUS_OK 0
+#define QOIR_RS_STATUS_INVALID_PARAMETER 1
+#define QOIR_RS_STATUS_DECODING_FAILED 2
+#define QOIR_RS_STATUS_ENCODING_FAILED 3
+#define QOIR_RS_STATUS_FILE_NOT_FOUND 4
+#define QOIR_RS_STATUS_IO_ERROR 5
+#define QOIR_RS_STATUS_INTERNAL_ERROR 6
+
+/* Pixel formats, matching QOIR_PIXEL_FORMAT__ETC in qoir.h. */
+#define QOIR_RS_PIXEL_FORMAT_BGRX 0x01
+#define QOIR_RS_PIXEL_FORMAT_BGRA_NONPREMUL 0x02
+#define QOIR_RS_PIXEL_FORMAT_BGRA_PREMUL 0x03
+#define QOIR_RS_PIXEL_FORMAT_BGR 0x11
+#define QOIR_RS_PIXEL_FORMAT_RGBX 0x21
+#define QOIR_RS_PIXEL_FORMAT_RGBA_NONPREMUL 0x22
+#define QOIR_RS_PIXEL_FORMAT_RGBA_PREMUL 0x23
+#define QOIR_RS_PIXEL_FORMAT_RGB 0x31
+
+/* Owns the memory behind an image or buffer. Release with qoir_rs_free. */
+typedef struct qoir_rs_handle qoir_rs_handle;
+
+typedef struct qoir_rs_metadata {
+  uint32_t width;
+  uint32_t height;
+  uint32_t pixel_format;
+} qoir_rs_metadata;
+
+typedef struct qoir_rs_image {
+  qoir_rs_handle* handle;
+  const uint8_t* pixels;
+  size_t pixels_len;
+  uint32_t width;
+  uint32_t height;
+  uint32_t pixel_format;
+  size_t stride_in_bytes;
+} qoir_rs_image;
+
+typedef struct qoir_rs_buffer {
+  qoir_rs_handle* handle;
+  const uint8_t* data;
+  size_t len;
+} qoir_rs_buffer;
+
+typedef struct qoir_rs_encode_options {
+  uint8_t lossiness;
+  bool dither;
+} qoir_rs_encode_options;
+
+int32_t qoir_rs_decode_metadata(const uint8_t* data, size_t len,
+                                qoir_rs_metadata* out);
+
+int32_t qoir_rs_decode(const uint8_t* data, size_t len, uint32_t pixel_format,
+                       qoir_rs_image* out);
+
+int32_t qoir_rs_decode_file(const char* path, uint32_t pixel_format,
+                            qoir_rs_image* out);
+
+/* options may be NULL for lossless encoding. */
+int32_t qoir_rs_encode(const uint8_t* pixels, size_t pixels_len,
+                       uint32_t width, uint32_t height, uint32_t pixel_format,
+                       size_t stride_in_bytes,
+                       const qoir_rs_encode_options* options,
+                       qoir_rs_buffer* out);
+
+int32_t qoir_rs_encode_file(const uint8_t* pixels, size_t pixels_len,
+                            uint32_t width, uint32_t height,
+                            uint32_t pixel_format, size_t stride_in_bytes,
+                            const qoir_rs_encode_options* options,
+                            const char* path);
+
+/* Passing NULL does nothing. */
+void qoir_rs_free(qoir_rs_handle* handle);
+
+/* Returns a static, NUL-terminated description of a status code. */
+const char* qoir_rs_status_message(int32_t status);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif  // QOIR_RS_H
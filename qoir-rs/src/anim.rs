@@ -0,0 +1,296 @@
+//! Sequences of QOIR frames, such as bursts or short animations, in one file.
+//!
+//! An animation file uses the chunk layout of a QOIR file. It starts with a
+//! `QANM` header chunk holding the width, height and frame count as
+//! little-endian `u32`s. The metadata shared by every frame follows in
+//! optional `CICP`, `ICCP`, `EXIF` and `XMP ` chunks, then one `QFRM` chunk
+//! per frame and finally an empty `QEND` chunk. A `QFRM` payload is the
+//! frame's duration in milliseconds, as a little-endian `u32`, followed by a
+//! complete QOIR image, so frames can be copied out without being decoded.
+//!
+//! All frames have the same width and height.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::anim::{AnimationDecoder, AnimationEncoder};
+//! use qoir_rs::{DecodeOptions, EncodeOptions};
+//!
+//! let mut encoder = AnimationEncoder::new(EncodeOptions::default());
+//! for path in ["burst-1.qoir", "burst-2.qoir", "burst-3.qoir"] {
+//!     let frame = std::fs::read(path).expect("Failed to read QOIR file");
+//!     encoder.add_encoded_frame(&frame, 100).expect("Failed to add frame");
+//! }
+//! let data = encoder.finish().expect("Failed to finish");
+//!
+//! let decoder = AnimationDecoder::new(&data).expect("Failed to parse");
+//! for index in 0..decoder.frame_count() {
+//!     let frame = decoder
+//!         .decode_frame(index, DecodeOptions::default())
+//!         .expect("Failed to decode");
+//!     println!("Frame {}: {}x{}", index, frame.image.width, frame.image.height);
+//! }
+//! ```
+
+use alloc::{string::ToString, vec::Vec};
+
+use crate::container::{Container, next_chunk, write_chunk};
+use crate::{
+    DecodeOptions, DecodedImage, EncodeOptions, Error, Image, QoirMetadata, decode_from_memory,
+    encode_to_memory,
+};
+
+const HEADER_PAYLOAD_LEN: usize = 12;
+const DURATION_LEN: usize = 4;
+
+fn invalid_data() -> Error {
+    Error::DecodingFailed("#qoir-anim: invalid data".to_string())
+}
+
+/// One frame of an animation, borrowed from the encoded data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFrame<'a> {
+    /// How long the frame is shown, in milliseconds.
+    pub duration_ms: u32,
+    /// The frame as a complete QOIR image.
+    pub data: &'a [u8],
+}
+
+/// Builds an animation file from frames added one at a time.
+#[derive(Debug, Clone)]
+pub struct AnimationEncoder {
+    options: EncodeOptions,
+    size: Option<(u32, u32)>,
+    frame_count: u32,
+    frames: Vec<u8>,
+}
+
+impl AnimationEncoder {
+    /// Creates an encoder with no frames.
+    ///
+    /// The metadata in `options` is written once, shared by every frame. The
+    /// lossiness and dithering apply to the frames added with
+    /// [`add_frame`](Self::add_frame).
+    pub fn new(options: EncodeOptions) -> Self {
+        AnimationEncoder {
+            options,
+            size: None,
+            frame_count: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Encodes `image` and appends it as the next frame.
+    ///
+    /// # Returns
+    ///
+    /// `Error::InvalidParameter` if the image's size differs from the first
+    /// frame's, or the error of encoding it.
+    pub fn add_frame(&mut self, image: Image<'_>, duration_ms: u32) -> Result<(), Error> {
+        let options = EncodeOptions {
+            lossiness: self.options.lossiness,
+            dither: self.options.dither,
+            ..Default::default()
+        };
+        let encoded = encode_to_memory(image, options)?;
+        self.add_encoded_frame(encoded.data, duration_ms)
+    }
+
+    /// Appends an already encoded QOIR image as the next frame, without
+    /// decoding it.
+    ///
+    /// The frame is stored as it is, including any metadata of its own, which
+    /// takes precedence over the shared metadata when it is decoded.
+    ///
+    /// # Returns
+    ///
+    /// `Error::InvalidParameter` if the image's size differs from the first
+    /// frame's, or an error if `data` is not a valid QOIR image.
+    pub fn add_encoded_frame(&mut self, data: &[u8], duration_ms: u32) -> Result<(), Error> {
+        let header = Container::parse(data)?.header;
+        let size = (header.width, header.height);
+        if *self.size.get_or_insert(size) != size {
+            return Err(Error::InvalidParameter);
+        }
+
+        let mut payload = Vec::with_capacity(DURATION_LEN + data.len());
+        payload.extend_from_slice(&duration_ms.to_le_bytes());
+        payload.extend_from_slice(data);
+        write_chunk(&mut self.frames, *b"QFRM", &payload);
+        self.frame_count += 1;
+        Ok(())
+    }
+
+    /// The number of frames added so far.
+    pub fn frame_count(&self) -> usize {
+        self.frame_count as usize
+    }
+
+    /// Writes the animation file.
+    ///
+    /// # Returns
+    ///
+    /// The encoded animation, or `Error::InvalidParameter` if no frame was
+    /// added.
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        let Some((width, height)) = self.size else {
+            return Err(Error::InvalidParameter);
+        };
+
+        let mut header = [0; HEADER_PAYLOAD_LEN];
+        header[..4].copy_from_slice(&width.to_le_bytes());
+        header[4..8].copy_from_slice(&height.to_le_bytes());
+        header[8..].copy_from_slice(&self.frame_count.to_le_bytes());
+
+        let mut dst = Vec::with_capacity(self.frames.len() + 64);
+        write_chunk(&mut dst, *b"QANM", &header);
+        let metadata = [
+            (*b"CICP", &self.options.cicp_profile),
+            (*b"ICCP", &self.options.icc_profile),
+            (*b"EXIF", &self.options.exif),
+            (*b"XMP ", &self.options.xmp),
+        ];
+        for (tag, payload) in metadata {
+            if let Some(payload) = payload {
+                write_chunk(&mut dst, tag, payload);
+            }
+        }
+        dst.extend_from_slice(&self.frames);
+        write_chunk(&mut dst, *b"QEND", &[]);
+        Ok(dst)
+    }
+}
+
+/// Reads the frames of an animation file.
+///
+/// Parsing only walks the chunk headers; each frame is decoded on request.
+#[derive(Debug, Clone)]
+pub struct AnimationDecoder<'a> {
+    width: u32,
+    height: u32,
+    metadata: QoirMetadata<'a>,
+    frames: Vec<AnimationFrame<'a>>,
+}
+
+impl<'a> AnimationDecoder<'a> {
+    /// Parses an animation file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `AnimationDecoder` or an `Error` if the data
+    /// is not a valid animation.
+    pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+        let (tag, header, mut rest) = next_chunk(data)?;
+        if tag != *b"QANM" || header.len() != HEADER_PAYLOAD_LEN {
+            return Err(invalid_data());
+        }
+        let word =
+            |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let (width, height, frame_count) = (word(0), word(4), word(8));
+
+        let mut metadata = QoirMetadata::default();
+        let mut frames = Vec::new();
+        loop {
+            let (tag, payload, remaining) = next_chunk(rest)?;
+            rest = remaining;
+
+            match &tag {
+                b"CICP" => metadata.cic_profile = Some(payload),
+                b"ICCP" => metadata.icc_profile = Some(payload),
+                b"EXIF" => metadata.exif = Some(payload),
+                b"XMP " => metadata.xmp = Some(payload),
+                b"QFRM" => {
+                    if payload.len() < DURATION_LEN {
+                        return Err(invalid_data());
+                    }
+                    let (duration, data) = payload.split_at(DURATION_LEN);
+                    frames.push(AnimationFrame {
+                        duration_ms: u32::from_le_bytes([
+                            duration[0],
+                            duration[1],
+                            duration[2],
+                            duration[3],
+                        ]),
+                        data,
+                    });
+                }
+                b"QEND" => break,
+                // Unknown chunks are skipped.
+                _ => {}
+            }
+        }
+
+        if frames.len() != frame_count as usize {
+            return Err(invalid_data());
+        }
+        Ok(AnimationDecoder {
+            width,
+            height,
+            metadata,
+            frames,
+        })
+    }
+
+    /// Width of every frame in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of every frame in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of frames.
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// The metadata shared by every frame.
+    pub fn metadata(&self) -> QoirMetadata<'a> {
+        self.metadata
+    }
+
+    /// The frames, in order, without decoding them.
+    pub fn frames(&self) -> &[AnimationFrame<'a>] {
+        &self.frames
+    }
+
+    /// The sum of the frame durations, in milliseconds.
+    pub fn total_duration_ms(&self) -> u64 {
+        self.frames
+            .iter()
+            .map(|frame| frame.duration_ms as u64)
+            .sum()
+    }
+
+    /// Decodes one frame.
+    ///
+    /// Metadata the frame lacks is filled in from the shared metadata.
+    ///
+    /// # Arguments
+    ///
+    /// * `index`: The frame to decode, counting from 0.
+    /// * `options`: `DecodeOptions` to control the decoding process.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DecodedImage`, `Error::InvalidParameter` if
+    /// there is no such frame, or an `Error` if decoding fails.
+    pub fn decode_frame(
+        &self,
+        index: usize,
+        options: DecodeOptions,
+    ) -> Result<DecodedImage<'a>, Error> {
+        let frame = self.frames.get(index).ok_or(Error::InvalidParameter)?;
+        let mut decoded = decode_from_memory(frame.data, options)?;
+        if (decoded.image.width, decoded.image.height) != (self.width, self.height) {
+            return Err(invalid_data());
+        }
+        decoded.cic_profile = decoded.cic_profile.or(self.metadata.cic_profile);
+        decoded.icc_profile = decoded.icc_profile.or(self.metadata.icc_profile);
+        decoded.exif = decoded.exif.or(self.metadata.exif);
+        decoded.xmp = decoded.xmp.or(self.metadata.xmp);
+        Ok(decoded)
+    }
+}
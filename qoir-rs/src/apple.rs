@@ -0,0 +1,154 @@
+//! Helpers for showing QOIR images with Core Graphics on macOS and iOS,
+//! enabled with the `apple` feature.
+//!
+//! Core Graphics draws 32-bit pixels with premultiplied alpha in the host's
+//! byte order without converting them first, and Core Animation copies any
+//! image whose rows are not aligned to 64 bytes before uploading it.
+//! [`CgBitmap`] holds the pixels in that layout along with the values
+//! `CGImageCreate` needs to describe them.
+//!
+//! `CgBitmap` builds on every platform. On Apple platforms,
+//! `DecodedImage::to_cgimage` and [`CgBitmap::to_cgimage`] wrap the pixels in
+//! a `CGImage` from the `core-graphics` crate, which can be shown with
+//! `NSImage(cgImage:size:)` or `UIImage(cgImage:)` without another copy.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::{decode, DecodeOptions};
+//!
+//! let decoded = decode("input.qoir", DecodeOptions::default()).expect("Failed to decode");
+//! # #[cfg(target_vendor = "apple")]
+//! let image = decoded.to_cgimage().expect("Failed to create CGImage");
+//! ```
+
+use alloc::{vec, vec::Vec};
+
+use crate::pixel::{convert, to_bgra};
+use crate::{Error, Image, PixelFormat};
+
+/// The alignment of each row of a [`CgBitmap`], in bytes.
+pub const BYTES_PER_ROW_ALIGNMENT: usize = 64;
+
+/// `kCGImageAlphaPremultipliedFirst`: premultiplied alpha before the color
+/// channels.
+pub const ALPHA_PREMULTIPLIED_FIRST: u32 = 2;
+/// `kCGImageAlphaNoneSkipFirst`: an unused byte before the color channels.
+pub const ALPHA_NONE_SKIP_FIRST: u32 = 6;
+/// `kCGBitmapByteOrder32Little`: 32-bit pixels stored little-endian, so
+/// `ARGB` is `BGRA` in memory.
+pub const BYTE_ORDER_32_LITTLE: u32 = 2 << 12;
+
+/// Pixels laid out for Core Graphics: BGRA with premultiplied alpha, or BGRX
+/// for opaque images, with rows aligned to [`BYTES_PER_ROW_ALIGNMENT`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgBitmap {
+    /// The pixels, top row first.
+    pub pixels: Vec<u8>,
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// The size of each row in bytes, including padding.
+    pub bytes_per_row: usize,
+    /// The `CGBitmapInfo`: the byte order combined with the alpha info.
+    pub bitmap_info: u32,
+}
+
+impl CgBitmap {
+    /// Bits per color channel, the `bitsPerComponent` of `CGImageCreate`.
+    pub const BITS_PER_COMPONENT: usize = 8;
+    /// Bits per pixel, the `bitsPerPixel` of `CGImageCreate`.
+    pub const BITS_PER_PIXEL: usize = 32;
+
+    /// Copies `image` into the layout Core Graphics expects. Images with an
+    /// alpha channel are premultiplied; the others are stored as BGRX.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CgBitmap`, or `Error::InvalidParameter` if
+    /// the pixel format is `Invalid` or the pixel buffer is too small.
+    pub fn from_image(image: &Image) -> Result<Self, Error> {
+        if image.pixel_format == PixelFormat::Invalid {
+            return Err(Error::InvalidParameter);
+        }
+        image.check_buffer()?;
+
+        let (dst_format, alpha_info) = if image.pixel_format.has_alpha() {
+            (PixelFormat::BGRAPremul, ALPHA_PREMULTIPLIED_FIRST)
+        } else {
+            (PixelFormat::BGRX, ALPHA_NONE_SKIP_FIRST)
+        };
+        let width = image.width as usize;
+        let bytes_per_row = (width * 4).next_multiple_of(BYTES_PER_ROW_ALIGNMENT);
+        let mut pixels = vec![0; bytes_per_row * image.height as usize];
+
+        let bpp = image.pixel_format.bytes_per_pixel();
+        for y in 0..image.height as usize {
+            let src_row = &image.pixels[y * image.stride_in_bytes..][..width * bpp];
+            let dst_row = &mut pixels[y * bytes_per_row..][..width * 4];
+            if image.pixel_format == dst_format {
+                dst_row.copy_from_slice(src_row);
+                continue;
+            }
+            for (src, dst) in src_row.chunks_exact(bpp).zip(dst_row.chunks_exact_mut(4)) {
+                let bgra = to_bgra(src, image.pixel_format);
+                convert(bgra, image.pixel_format, dst_format, dst);
+            }
+        }
+
+        Ok(CgBitmap {
+            pixels,
+            width: image.width,
+            height: image.height,
+            bytes_per_row,
+            bitmap_info: BYTE_ORDER_32_LITTLE | alpha_info,
+        })
+    }
+
+    /// Wraps the pixels in an sRGB `CGImage`, which takes ownership of them.
+    #[cfg(target_vendor = "apple")]
+    pub fn to_cgimage(self) -> core_graphics::image::CGImage {
+        use core_graphics::base::kCGRenderingIntentDefault;
+        use core_graphics::color_space::{CGColorSpace, kCGColorSpaceSRGB};
+        use core_graphics::data_provider::CGDataProvider;
+        use std::sync::Arc;
+
+        // SAFETY: `kCGColorSpaceSRGB` is a constant exported by Core Graphics.
+        let color_space = CGColorSpace::create_with_name(unsafe { kCGColorSpaceSRGB })
+            .unwrap_or_else(CGColorSpace::create_device_rgb);
+        let provider = CGDataProvider::from_buffer(Arc::new(self.pixels));
+        core_graphics::image::CGImage::new(
+            self.width as usize,
+            self.height as usize,
+            Self::BITS_PER_COMPONENT,
+            Self::BITS_PER_PIXEL,
+            self.bytes_per_row,
+            &color_space,
+            self.bitmap_info,
+            &provider,
+            true,
+            kCGRenderingIntentDefault,
+        )
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+impl crate::DecodedImage<'_> {
+    /// Creates a `CGImage` from the decoded pixels, for AppKit and UIKit
+    /// previewers. The pixels are copied into a [`CgBitmap`] first, so any
+    /// pixel format works, though `PixelFormat::BGRAPremul` needs the least
+    /// conversion.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `CGImage`, or `Error::InvalidParameter` if
+    /// the image is empty or its pixel format is `Invalid`.
+    pub fn to_cgimage(&self) -> Result<core_graphics::image::CGImage, Error> {
+        // CGImageCreate fails for empty images.
+        if self.image.width == 0 || self.image.height == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        Ok(CgBitmap::from_image(&self.image)?.to_cgimage())
+    }
+}
@@ -0,0 +1,66 @@
+//! Decoding batches of images into an arena, enabled with the `bumpalo`
+//! feature.
+//!
+//! A worker that decodes thousands of large images, each freed soon after,
+//! fragments its heap until it holds far more memory than it uses.
+//! [`decode_batch_into_arena`] places every pixel buffer of a batch in one
+//! `bumpalo::Bump`, which is reset or dropped as a whole once the batch has
+//! been processed, so the buffers never reach the global allocator.
+//!
+//! ```no_run
+//! use bumpalo::Bump;
+//! use qoir_rs::{DecodeOptions, decode_batch_into_arena};
+//!
+//! let mut arena = Bump::new();
+//! for batch in [["a.qoir", "b.qoir"], ["c.qoir", "d.qoir"]] {
+//!     let inputs: Vec<Vec<u8>> = batch.iter().map(|path| std::fs::read(path).unwrap()).collect();
+//!     for image in decode_batch_into_arena(&inputs, DecodeOptions::default(), &arena) {
+//!         let image = image.expect("Failed to decode");
+//!         println!("{}x{}", image.width, image.height);
+//!     }
+//!     arena.reset();
+//! }
+//! ```
+
+use alloc::vec::Vec;
+
+use bumpalo::Bump;
+
+use crate::{DecodeOptions, Error, Image, rust_backend};
+
+/// Decodes a batch of QOIR images with the Rust backend, placing their pixels
+/// in `arena`.
+///
+/// The images borrow the arena, so they must be dropped before it is reset.
+/// Only the pixels are returned: the metadata, `DecodeOptions::fit_within`
+/// and `DecodeOptions::collect_timings` are ignored. An image whose header
+/// is invalid takes no space in the arena, but one with corrupt tiles keeps
+/// its pixel buffer until the arena is reset.
+///
+/// # Arguments
+///
+/// * `inputs`: The QOIR encoded images.
+/// * `options`: `DecodeOptions` to control the decoding process, used for
+///   every image.
+/// * `arena`: The arena to allocate the pixel buffers in.
+///
+/// # Returns
+///
+/// A `Vec` with, for each input in order, a `Result` containing the decoded
+/// `Image` or an `Error` if decoding it failed.
+pub fn decode_batch_into_arena<'b, D: AsRef<[u8]>>(
+    inputs: &[D],
+    options: DecodeOptions,
+    arena: &'b Bump,
+) -> Vec<Result<Image<'b>, Error>> {
+    inputs
+        .iter()
+        .map(|data| {
+            rust_backend::decode_into_buffer(data.as_ref(), &options, |len| {
+                arena
+                    .try_alloc_slice_fill_copy(len, 0)
+                    .map_err(|_| Error::DecodingFailed("#qoir: out of memory".into()))
+            })
+        })
+        .collect()
+}
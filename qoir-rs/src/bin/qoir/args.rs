@@ -0,0 +1,336 @@
+use clap::{Args, ValueEnum};
+use image::DynamicImage;
+use qoir_rs::{DecodeLimits, Image, ImageBuf, PixelFormat, Rectangle, ResizeFilter, ResizeMode};
+use std::path::Path;
+use crate::io::{rgba_image, to_dynamic_image};
+use crate::batch::is_up_to_date;
+
+/// Options for resizing images while they are encoded or converted.
+#[derive(Args)]
+pub struct ResizeArgs {
+    /// Resize the image to fit, fill or exactly match this size
+    #[arg(long, value_name = "WxH", value_parser = parse_size)]
+    resize: Option<(u32, u32)>,
+
+    /// Shrink the image, keeping its aspect ratio, so neither side exceeds this
+    #[arg(long, value_name = "N", conflicts_with = "resize", value_parser = clap::value_parser!(u32).range(1..))]
+    max_dimension: Option<u32>,
+
+    /// How --resize fits the image into the requested size
+    #[arg(long, value_enum, default_value = "fit")]
+    resize_mode: ResizeModeArg,
+
+    /// Resampling filter used when resizing
+    #[arg(long, value_enum, default_value = "lanczos3")]
+    filter: FilterArg,
+
+    /// Resize in linear light, which keeps fine detail from darkening
+    #[arg(long, default_value = "false")]
+    linear: bool,
+
+    /// Rotate the image clockwise by this many degrees before resizing
+    #[arg(long, value_enum, value_name = "DEGREES")]
+    rotate: Option<RotateArg>,
+
+    /// Mirror the image after rotating it
+    #[arg(long, value_enum)]
+    flip: Option<FlipArg>,
+}
+
+impl ResizeArgs {
+    pub fn requested(&self) -> bool {
+        self.resize.is_some() || self.max_dimension.is_some() || self.rotate.is_some() || self.flip.is_some()
+    }
+
+    /// Rotates, flips and resizes `image` as requested, or returns `None` if
+    /// it is left as it is.
+    pub fn apply(&self, image: &Image) -> Result<Option<ImageBuf>, qoir_rs::Error> {
+        let mut transformed = match self.rotate {
+            Some(RotateArg::Quarter) => Some(image.rotate90()?),
+            Some(RotateArg::Half) => Some(image.rotate180()?),
+            Some(RotateArg::ThreeQuarters) => Some(image.rotate270()?),
+            None => None,
+        };
+        if let Some(flip) = self.flip {
+            let source = transformed.as_ref().map_or(image.clone(), ImageBuf::as_image);
+            transformed = Some(match flip {
+                FlipArg::Horizontal => source.flip_h()?,
+                FlipArg::Vertical => source.flip_v()?,
+            });
+        }
+
+        let image = transformed.as_ref().map_or(image.clone(), ImageBuf::as_image);
+        let (width, height, mode) = match (self.resize, self.max_dimension) {
+            (Some((width, height)), _) => (width, height, self.resize_mode.into()),
+            (None, Some(max)) if image.width > max || image.height > max => (max, max, ResizeMode::Fit),
+            _ => return Ok(transformed),
+        };
+        if self.linear {
+            let linear = image.to_linear_f32()?.resize(width, height, mode, self.filter.into())?;
+            return linear.to_srgb_u8(image.pixel_format).map(Some);
+        }
+        image.resize(width, height, mode, self.filter.into()).map(Some)
+    }
+
+    /// Resizes an image loaded with the image crate as requested.
+    pub fn apply_to(&self, img: DynamicImage) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+        if !self.requested() {
+            return Ok(img);
+        }
+        let rgba_img = img.to_rgba8();
+        let image = rgba_image(&rgba_img);
+        match self.apply(&image)? {
+            Some(resized) => to_dynamic_image(&resized.as_image()),
+            None => Ok(img),
+        }
+    }
+}
+
+/// Limits on the images a command decodes, for pointing it at untrusted files.
+/// Files read from standard input get default limits, since nothing is known
+/// about where they came from, and so do tolerant decodes, which can't tell a
+/// truncated file from a forged header.
+#[derive(Args)]
+pub struct LimitArgs {
+    /// Refuse images with more pixels than this [default: none, 268435456 for standard input and --tolerant]
+    #[arg(long, value_name = "N")]
+    max_pixels: Option<u64>,
+
+    /// Refuse images wider or taller than this [default: none, 65535 for standard input and --tolerant]
+    #[arg(long, value_name = "N")]
+    max_dimension: Option<u32>,
+
+    /// Refuse images whose decoded pixels would take more memory than this, such as 512M
+    /// [default: none, 1G for standard input and --tolerant]
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    max_memory: Option<u64>,
+}
+
+impl LimitArgs {
+    /// The limits for decoding `input`.
+    pub fn limits(&self, input: &Path) -> DecodeLimits {
+        self.limits_or_defaults(input == Path::new("-"))
+    }
+
+    /// The limits that were given, with the defaults for the others if
+    /// `defaults` is set.
+    pub fn limits_or_defaults(&self, defaults: bool) -> DecodeLimits {
+        DecodeLimits {
+            max_pixels: self.max_pixels.or(defaults.then_some(16384 * 16384)),
+            max_dimension: self.max_dimension.or(defaults.then_some(65535)),
+            max_memory: self.max_memory.or(defaults.then_some(1 << 30)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ResizeModeArg {
+    /// Stretch to exactly the requested size
+    Exact,
+    /// Keep the aspect ratio and fit within the requested size
+    Fit,
+    /// Keep the aspect ratio, cover the requested size and crop the overflow
+    Fill,
+}
+
+impl From<ResizeModeArg> for ResizeMode {
+    fn from(mode: ResizeModeArg) -> Self {
+        match mode {
+            ResizeModeArg::Exact => ResizeMode::Exact,
+            ResizeModeArg::Fit => ResizeMode::Fit,
+            ResizeModeArg::Fill => ResizeMode::Fill,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RotateArg {
+    #[value(name = "90")]
+    Quarter,
+    #[value(name = "180")]
+    Half,
+    #[value(name = "270")]
+    ThreeQuarters,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FlipArg {
+    /// Left to right
+    Horizontal,
+    /// Top to bottom
+    Vertical,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FilterArg {
+    Nearest,
+    Box,
+    Bilinear,
+    CatmullRom,
+    Lanczos3,
+}
+
+impl From<FilterArg> for ResizeFilter {
+    fn from(filter: FilterArg) -> Self {
+        match filter {
+            FilterArg::Nearest => ResizeFilter::Nearest,
+            FilterArg::Box => ResizeFilter::Box,
+            FilterArg::Bilinear => ResizeFilter::Bilinear,
+            FilterArg::CatmullRom => ResizeFilter::CatmullRom,
+            FilterArg::Lanczos3 => ResizeFilter::Lanczos3,
+        }
+    }
+}
+
+/// When a command replaces an output file that already exists.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Overwrite {
+    /// Never replace existing files
+    Never,
+    /// Always replace existing files
+    Always,
+    /// Replace files older than their input
+    IfNewer,
+}
+
+impl Overwrite {
+    /// Whether `output` may be written from `input`.
+    pub fn allows(self, input: &Path, output: &Path) -> bool {
+        match self {
+            Overwrite::Never => !output.exists(),
+            Overwrite::Always => true,
+            Overwrite::IfNewer => !is_up_to_date(input, output),
+        }
+    }
+}
+
+/// What `dedupe` compares.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DedupeBy {
+    /// The decoded pixels, so lossless re-encodes and copies with different metadata still match
+    Pixels,
+    /// The encoded bytes of the files
+    Bytes,
+}
+
+/// What `dedupe` does with the duplicates it finds.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DedupeAction {
+    /// Only list them
+    Report,
+    /// Replace each duplicate with a hard link to the file that is kept
+    Hardlink,
+    /// Delete them
+    Delete,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InfoFormat {
+    Text,
+    Json,
+    Yaml,
+}
+
+/// How `view` draws an image.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ViewProtocol {
+    Auto,
+    /// The kitty graphics protocol, also supported by WezTerm, Ghostty and Konsole
+    Kitty,
+    /// iTerm2 inline images, also supported by WezTerm
+    Iterm,
+    /// Sixel graphics, supported by foot, mlterm, xterm -ti vt340 and others
+    Sixel,
+    /// Colored Unicode half blocks, which work in any true-color terminal
+    Blocks,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Metric {
+    Psnr,
+    Ssim,
+    All,
+}
+
+pub fn parse_pixel_format(value: &str) -> Result<PixelFormat, String> {
+    match value.to_lowercase().as_str() {
+        "rgba" => Ok(PixelFormat::RGBANonPremul),
+        "rgba-premul" => Ok(PixelFormat::RGBAPremul),
+        "rgbx" => Ok(PixelFormat::RGBX),
+        "rgb" => Ok(PixelFormat::RGB),
+        "bgra" => Ok(PixelFormat::BGRANonPremul),
+        "bgra-premul" => Ok(PixelFormat::BGRAPremul),
+        "bgrx" => Ok(PixelFormat::BGRX),
+        "bgr" => Ok(PixelFormat::BGR),
+        _ => Err(format!("unsupported pixel format {value:?}")),
+    }
+}
+
+/// Parses `--crop x,y,w,h` into a source clip rectangle.
+pub fn parse_crop(value: &str) -> Result<Rectangle, String> {
+    let parts = parse_ints(value)?;
+    let [x, y, w, h] = parts[..] else {
+        return Err("expected X,Y,W,H".into());
+    };
+    if w < 0 || h < 0 {
+        return Err("width and height must not be negative".into());
+    }
+    Ok(Rectangle {
+        x0: x,
+        y0: y,
+        x1: x.saturating_add(w),
+        y1: y.saturating_add(h),
+    })
+}
+
+/// Parses `--resize WxH`.
+pub fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WxH, got {value:?}"))?;
+    let parse = |side: &str| match side.trim().parse() {
+        Ok(0) | Err(_) => Err(format!("invalid size {value:?}: sides must be positive integers")),
+        Ok(side) => Ok(side),
+    };
+    Ok((parse(width)?, parse(height)?))
+}
+
+pub fn parse_psnr(value: &str) -> Result<f64, String> {
+    let lower = value.trim().to_ascii_lowercase();
+    let number = lower.strip_suffix("db").unwrap_or(&lower);
+    match number.trim().parse::<f64>() {
+        Ok(psnr) if psnr.is_finite() && psnr > 0.0 => Ok(psnr),
+        _ => Err(format!("expected a PSNR in decibels such as 45db, got {value:?}")),
+    }
+}
+
+/// Parses a size in bytes with an optional K, M or G suffix (powers of 1024).
+pub fn parse_byte_size(value: &str) -> Result<u64, String> {
+    let upper = value.trim().to_ascii_uppercase();
+    let number = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, unit) = match number.char_indices().last() {
+        Some((i, 'K')) => (&number[..i], 1u64 << 10),
+        Some((i, 'M')) => (&number[..i], 1 << 20),
+        Some((i, 'G')) => (&number[..i], 1 << 30),
+        _ => (number, 1),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(size) if size.is_finite() && size > 0.0 => Ok((size * unit as f64) as u64),
+        _ => Err(format!("expected a size such as 500K or 2M, got {value:?}")),
+    }
+}
+
+/// Parses `--offset dx,dy`.
+pub fn parse_offset(value: &str) -> Result<(i32, i32), String> {
+    match parse_ints(value)?[..] {
+        [dx, dy] => Ok((dx, dy)),
+        _ => Err("expected DX,DY".into()),
+    }
+}
+
+pub fn parse_ints(value: &str) -> Result<Vec<i32>, String> {
+    value
+        .split(',')
+        .map(|part| part.trim().parse().map_err(|e| format!("{:?}: {}", part, e)))
+        .collect()
+}
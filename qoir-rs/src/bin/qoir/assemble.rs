@@ -0,0 +1,70 @@
+use qoir_rs::{encode_to_vec, read_metadata, rewrite_metadata, EncodeOptions, MetadataChange, MetadataEdit};
+use qoir_rs::anim::AnimationEncoder;
+use std::path::{Path, PathBuf};
+use crate::errors::{CliError, ErrorKind};
+use crate::io::{embed_source_metadata, format_bytes, load_image, rgba_image};
+
+pub fn assemble_command(
+    frames: &[PathBuf],
+    output: &Path,
+    duration_ms: u32,
+    mut options: EncodeOptions,
+    strip_metadata: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut encoded_frames = Vec::with_capacity(frames.len());
+    for (index, path) in frames.iter().enumerate() {
+        let data = std::fs::read(path)?;
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let frame = if ext.eq_ignore_ascii_case("qoir") {
+            // The first frame's metadata is shared by the whole animation, so
+            // the frames don't each carry a copy.
+            if index == 0 && !strip_metadata {
+                let metadata = read_metadata(&data)?;
+                options.cicp_profile = metadata.cic_profile.map(<[u8]>::to_vec);
+                options.icc_profile = metadata.icc_profile.map(<[u8]>::to_vec);
+                options.exif = metadata.exif.map(<[u8]>::to_vec);
+                options.xmp = metadata.xmp.map(<[u8]>::to_vec);
+            }
+            let strip = MetadataEdit {
+                cic_profile: MetadataChange::Remove,
+                icc_profile: MetadataChange::Remove,
+                exif: MetadataChange::Remove,
+                xmp: MetadataChange::Remove,
+            };
+            rewrite_metadata(&data, &strip)?
+        } else {
+            if index == 0 && !strip_metadata {
+                embed_source_metadata(&mut options, &data);
+            }
+            let rgba_img = load_image(path, &data)?.to_rgba8();
+            let frame_options = EncodeOptions {
+                lossiness: options.lossiness,
+                dither: options.dither,
+                ..Default::default()
+            };
+            encode_to_vec(rgba_image(&rgba_img), frame_options)?
+        };
+        encoded_frames.push(frame);
+    }
+
+    let mut encoder = AnimationEncoder::new(options);
+    for (path, frame) in frames.iter().zip(&encoded_frames) {
+        encoder.add_encoded_frame(frame, duration_ms).map_err(|e| match e {
+            qoir_rs::Error::InvalidParameter => {
+                let message = format!("{} differs in size from the first frame", path.display());
+                CliError::new(ErrorKind::Arguments, message).into()
+            }
+            e => Box::<dyn std::error::Error>::from(e),
+        })?;
+    }
+    let data = encoder.finish()?;
+    std::fs::write(output, &data)?;
+
+    println!(
+        "Assembled {} frames into: {} ({})",
+        frames.len(),
+        output.display(),
+        format_bytes(data.len())
+    );
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use qoir_rs::{encode_to_memory, EncodeOptions};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use crate::args::Overwrite;
+use crate::errors::{BatchError, CliError, ErrorKind, json_errors, print_error_record};
+use crate::io::{embed_source_metadata, format_bytes, load_image, rgba_image};
+
+/// What happened to one file of a batch.
+pub enum BatchOutcome {
+    Converted { input_len: u64, output_len: u64 },
+    /// The file would have been converted, but this is a dry run.
+    Planned,
+    Skipped,
+    Failed(ErrorKind, String),
+}
+
+/// Which files a batch skips, and what happens to the inputs it converts.
+#[derive(Clone, Copy)]
+pub struct BatchPolicy {
+    pub overwrite: Overwrite,
+    /// Delete each input once its output has been written.
+    pub delete_source: bool,
+    /// Only print what would be converted.
+    pub dry_run: bool,
+    /// Don't copy the metadata of the inputs into the outputs.
+    pub strip_metadata: bool,
+}
+
+/// Checks whether a single-file command may write `output`, printing why not.
+/// With `dry_run` it only prints what would be written.
+pub fn should_write(input: &Path, output: &Path, overwrite: Overwrite, dry_run: bool) -> bool {
+    if !overwrite.allows(input, output) {
+        println!("Skipped {}: not replacing {}", input.display(), output.display());
+        return false;
+    }
+    if dry_run {
+        println!("Would write {} from {}", output.display(), input.display());
+        return false;
+    }
+    true
+}
+
+/// Runs `f` on the pool sized by `--jobs`, so its parallel iterators use it.
+pub fn in_thread_pool<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    match qoir_rs::thread_pool() {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+/// Converts `inputs` to QOIR files under `output_dir`, at the same paths
+/// relative to it as the inputs have relative to `base`.
+pub fn batch_command(
+    inputs: &[PathBuf],
+    base: &Path,
+    output_dir: &Path,
+    options: EncodeOptions,
+    policy: BatchPolicy,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let start = Instant::now();
+    let progress = progress_bar(inputs.len() as u64);
+    let outcomes: Vec<BatchOutcome> = in_thread_pool(|| {
+        inputs
+            .par_iter()
+            .map(|input| {
+                let outcome = batch_convert(input, base, output_dir, &options, policy, &progress);
+                progress.inc(1);
+                outcome
+            })
+            .collect()
+    });
+    progress.finish_and_clear();
+
+    let (mut converted, mut planned, mut skipped) = (0, 0, 0);
+    let (mut total_in, mut total_out) = (0, 0);
+    let mut failures = Vec::new();
+    for (input, outcome) in inputs.iter().zip(outcomes) {
+        match outcome {
+            BatchOutcome::Converted { input_len, output_len } => {
+                converted += 1;
+                total_in += input_len;
+                total_out += output_len;
+            }
+            BatchOutcome::Planned => planned += 1,
+            BatchOutcome::Skipped => skipped += 1,
+            BatchOutcome::Failed(kind, error) => failures.push((input, kind, error)),
+        }
+    }
+
+    if policy.dry_run {
+        println!("Would convert {} files, skip {}", planned, skipped);
+        return Ok(());
+    }
+
+    println!(
+        "Converted {} files ({} -> {}), skipped {}, failed {} in {:.2?}",
+        converted,
+        format_bytes(total_in as usize),
+        format_bytes(total_out as usize),
+        skipped,
+        failures.len(),
+        start.elapsed()
+    );
+    if let Some(&(_, first_kind, _)) = failures.first() {
+        if !json_errors() {
+            eprintln!("Failed files:");
+            for (input, _, error) in &failures {
+                eprintln!("  {}: {}", input.display(), error);
+            }
+        }
+        // When every file failed, they most likely failed for the same reason.
+        let kind = if failures.len() < inputs.len() { ErrorKind::PartialFailure } else { first_kind };
+        return Err(CliError::new(kind, format!("{} files failed to convert", failures.len())).into());
+    }
+    Ok(())
+}
+
+/// Reports a file that failed to convert above the progress bar.
+pub fn conversion_failed(input: &Path, error: &BatchError, progress: &ProgressBar) -> BatchOutcome {
+    let kind = ErrorKind::of(error.as_ref());
+    let message = error.to_string();
+    progress.suspend(|| {
+        if json_errors() {
+            print_error_record(Some(input), kind, &message);
+        } else {
+            eprintln!("Failed to convert {}: {}", input.display(), message);
+        }
+    });
+    BatchOutcome::Failed(kind, message)
+}
+
+/// Converts one file of a batch, printing its status above the progress bar.
+pub fn batch_convert(
+    input: &Path,
+    base: &Path,
+    output_dir: &Path,
+    options: &EncodeOptions,
+    policy: BatchPolicy,
+    progress: &ProgressBar,
+) -> BatchOutcome {
+    let output = qoir_output_path(input, base, output_dir);
+
+    if !policy.overwrite.allows(input, &output) {
+        return BatchOutcome::Skipped;
+    }
+    if policy.dry_run {
+        let delete = if policy.delete_source { " and delete the source" } else { "" };
+        progress.suspend(|| {
+            println!("Would convert {} -> {}{}", input.display(), output.display(), delete)
+        });
+        return BatchOutcome::Planned;
+    }
+    let result = convert_to_qoir(input, &output, options, policy.strip_metadata).and_then(|lens| {
+        if policy.delete_source {
+            std::fs::remove_file(input)?;
+        }
+        Ok(lens)
+    });
+    match result {
+        Ok((input_len, output_len)) => {
+            progress.suspend(|| println!("{} -> {}", input.display(), output.display()));
+            BatchOutcome::Converted { input_len, output_len }
+        }
+        Err(e) => conversion_failed(input, &e, progress),
+    }
+}
+
+/// A bar counting converted files, with their rate and the time left. Like
+/// all indicatif bars it is drawn on stderr, and only when that is a terminal.
+pub fn progress_bar(len: u64) -> ProgressBar {
+    let style = ProgressStyle::with_template(
+        "{elapsed_precise} [{wide_bar}] {pos}/{len} files, {rate} files/s, ETA {eta}",
+    )
+    .expect("Invalid progress bar template")
+    .with_key("rate", |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+        let _ = write!(w, "{:.1}", state.per_sec());
+    })
+    .progress_chars("=> ");
+    ProgressBar::new(len).with_style(style)
+}
+
+/// Whether `output` exists and was written after `input` was last modified.
+pub fn is_up_to_date(input: &Path, output: &Path) -> bool {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified());
+    match (modified(input), modified(output)) {
+        (Ok(input), Ok(output)) => output >= input,
+        _ => false,
+    }
+}
+
+/// The QOIR file that `input` is converted to: its path relative to `base`,
+/// or just its file name if it is not under `base`, in `output_dir`.
+pub fn qoir_output_path(input: &Path, base: &Path, output_dir: &Path) -> PathBuf {
+    let relative = input
+        .strip_prefix(base)
+        .ok()
+        .filter(|relative| relative.file_name().is_some())
+        .unwrap_or_else(|| Path::new(input.file_name().unwrap_or_default()));
+    output_dir.join(relative).with_extension("qoir")
+}
+
+/// Encodes an image file to a QOIR file, returning the input and output sizes.
+pub fn convert_to_qoir(
+    input: &Path,
+    output: &Path,
+    options: &EncodeOptions,
+    strip_metadata: bool,
+) -> Result<(u64, u64), BatchError> {
+    let data = std::fs::read(input)?;
+    let rgba_img = load_image(input, &data)?.to_rgba8();
+    let image = rgba_image(&rgba_img);
+    let mut options = options.clone();
+    if !strip_metadata {
+        embed_source_metadata(&mut options, &data);
+    }
+    let encoded = encode_to_memory(image, options)?;
+
+    // Write to a temporary file first, so that an interrupted batch never
+    // leaves a truncated output that a later run would skip.
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = output.with_extension("qoir.partial");
+    std::fs::write(&partial, encoded.data)?;
+    std::fs::rename(&partial, output)?;
+    Ok((data.len() as u64, encoded.data.len() as u64))
+}
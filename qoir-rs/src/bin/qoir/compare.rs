@@ -0,0 +1,122 @@
+use image::RgbaImage;
+use std::path::Path;
+use crate::args::Metric;
+use crate::errors::{CliError, ErrorKind};
+use crate::io::load_rgba;
+use crate::diff::diff_heat_map;
+
+pub fn compare_command(
+    a: &Path,
+    b: &Path,
+    metric: Metric,
+    diff_output: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let a = load_rgba(a)?;
+    let b = load_rgba(b)?;
+    check_same_size(&a, &b)?;
+
+    if metric != Metric::Ssim {
+        let psnr = psnr(&a, &b);
+        if psnr.is_infinite() {
+            println!("PSNR: inf (identical)");
+        } else {
+            println!("PSNR: {:.3} dB", psnr);
+        }
+    }
+    if metric != Metric::Psnr {
+        println!("SSIM: {:.5}", ssim(&a, &b));
+    }
+
+    if let Some(diff_output) = diff_output {
+        let (diff, max) = diff_heat_map(&a, &b);
+        diff.save(diff_output)?;
+        println!(
+            "Diff saved to: {} (max channel difference {})",
+            diff_output.display(),
+            max
+        );
+    }
+
+    Ok(())
+}
+
+/// Peak signal-to-noise ratio over all four channels, in decibels.
+pub fn psnr(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    let squared_error: f64 = a
+        .as_raw()
+        .iter()
+        .zip(b.as_raw())
+        .map(|(&a, &b)| (a as f64 - b as f64).powi(2))
+        .sum();
+    let mse = squared_error / a.as_raw().len().max(1) as f64;
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+/// Mean structural similarity of the luma, over 8x8 windows placed every 4
+/// pixels.
+pub fn ssim(a: &RgbaImage, b: &RgbaImage) -> f64 {
+    const WINDOW: u32 = 8;
+    const STEP: usize = 4;
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+    let luma = |image: &RgbaImage| -> Vec<f64> {
+        image
+            .pixels()
+            .map(|p| 0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64)
+            .collect()
+    };
+    let (la, lb) = (luma(a), luma(b));
+    let (width, height) = a.dimensions();
+    let window_width = WINDOW.min(width);
+    let window_height = WINDOW.min(height);
+    if window_width == 0 || window_height == 0 {
+        return 1.0;
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y0 in (0..=height - window_height).step_by(STEP) {
+        for x0 in (0..=width - window_width).step_by(STEP) {
+            let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) =
+                (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in y0..y0 + window_height {
+                for x in x0..x0 + window_width {
+                    let i = (y * width + x) as usize;
+                    let (va, vb) = (la[i], lb[i]);
+                    sum_a += va;
+                    sum_b += vb;
+                    sum_aa += va * va;
+                    sum_bb += vb * vb;
+                    sum_ab += va * vb;
+                }
+            }
+            let n = (window_width * window_height) as f64;
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+            let var_a = sum_aa / n - mean_a * mean_a;
+            let var_b = sum_bb / n - mean_b * mean_b;
+            let covariance = sum_ab / n - mean_a * mean_b;
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covariance + C2))
+                / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+            windows += 1;
+        }
+    }
+    total / windows as f64
+}
+
+/// Colors each pixel by its largest channel difference, from black through
+/// red and yellow to white for the largest difference in the image. Returns
+/// the map and that largest difference.
+pub fn check_same_size(a: &RgbaImage, b: &RgbaImage) -> Result<(), CliError> {
+    if a.dimensions() != b.dimensions() {
+        let message = format!(
+            "Dimensions differ: {}x{} and {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        );
+        return Err(CliError::new(ErrorKind::Arguments, message));
+    }
+    Ok(())
+}
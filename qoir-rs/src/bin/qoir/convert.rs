@@ -0,0 +1,50 @@
+use qoir_rs::{decode, encode, DecodeOptions, EncodeOptions, ImageBuf};
+use std::path::PathBuf;
+use crate::args::ResizeArgs;
+use crate::io::{embed_source_metadata, load_image, open_image, rgba_image, save_image, to_dynamic_image};
+
+pub fn convert_command(
+    input: PathBuf,
+    output: PathBuf, 
+    quality: u8,
+    lossiness: u8,
+    strip_metadata: bool,
+    resize: &ResizeArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let in_ext = input.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let out_ext = output.extension().and_then(|e| e.to_str()).unwrap_or("");
+    
+    if in_ext.eq_ignore_ascii_case("qoir") {
+        // QOIR to other format
+        let decoded = decode(&input, DecodeOptions::default())?;
+        let resized = resize.apply(&decoded.image)?;
+        let image = resized.as_ref().map_or(decoded.image, ImageBuf::as_image);
+        
+        let img = to_dynamic_image(&image)?;
+        save_image(&img, &output, quality)?;
+    } else if out_ext.eq_ignore_ascii_case("qoir") {
+        // Other format to QOIR
+        let data = std::fs::read(&input)?;
+        let img = load_image(&input, &data)?;
+        let rgba_img = img.to_rgba8();
+        let image = rgba_image(&rgba_img);
+        let resized = resize.apply(&image)?;
+        let image = resized.as_ref().map_or(image, ImageBuf::as_image);
+        
+        let mut options = EncodeOptions {
+            lossiness,
+            ..Default::default()
+        };
+        if !strip_metadata {
+            embed_source_metadata(&mut options, &data);
+        }
+        encode(image, options, &output)?;
+    } else {
+        // Convert between non-QOIR formats using the image crate
+        let img = resize.apply_to(open_image(&input)?)?;
+        save_image(&img, &output, quality)?;
+    }
+    
+    println!("Converted {} to {}", input.display(), output.display());
+    Ok(())
+}
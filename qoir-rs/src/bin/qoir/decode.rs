@@ -0,0 +1,54 @@
+use qoir_rs::{decode_from_memory, DecodeOptions, PixelFormat};
+use std::path::PathBuf;
+use std::io::Write;
+use crate::args::parse_pixel_format;
+use crate::io::{IMAGE_EXTENSIONS, format_bytes, read_input, save_image, to_dynamic_image};
+
+pub fn decode_command(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    format: &str,
+    mut options: DecodeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    options.pixel_format = parse_pixel_format(format).unwrap_or_else(|_| {
+        println!("Unsupported format: {}. Using RGBA.", format);
+        PixelFormat::RGBANonPremul
+    });
+
+    let decoded = decode_from_memory(&read_input(&input)?, options)?;
+    
+    println!(
+        "Decoded image: {}x{} ({})",
+        decoded.image.width, decoded.image.height, format_bytes(decoded.image.pixels.len())
+    );
+    for region in &decoded.missing_regions {
+        println!(
+            "Missing region: {},{} {}x{}",
+            region.x0, region.y0, region.x1 - region.x0, region.y1 - region.y0
+        );
+    }
+    
+    if let Some(output_path) = output {
+        let ext = output_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        match ext.as_str() {
+            ext if IMAGE_EXTENSIONS.contains(&ext) => {
+                let img = to_dynamic_image(&decoded.image)?;
+                save_image(&img, &output_path, 90)?;
+                println!("Image saved to: {}", output_path.display());
+            }
+            _ => {
+                // Save raw pixel data
+                let mut file = std::fs::File::create(&output_path)?;
+                file.write_all(decoded.image.pixels)?;
+                println!("Raw pixel data saved to: {}", output_path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
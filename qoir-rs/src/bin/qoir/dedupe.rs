@@ -0,0 +1,157 @@
+use qoir_rs::{decode_from_memory, DecodeOptions};
+use rayon::prelude::*;
+use std::path::Path;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+use crate::args::{DedupeAction, DedupeBy};
+use crate::errors::{BatchError, CliError, ErrorKind};
+use crate::io::{collect_files, format_bytes};
+use crate::batch::in_thread_pool;
+
+pub fn dedupe_command(dir: &Path, by: DedupeBy, action: DedupeAction) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.retain(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("qoir")));
+    files.sort();
+
+    let results: Vec<Result<(u64, u64), BatchError>> = in_thread_pool(|| {
+        files
+            .par_iter()
+            .map(|path| {
+                let content = dedupe_content(path, by)?;
+                let mut hasher = DefaultHasher::new();
+                content.hash(&mut hasher);
+                Ok((hasher.finish(), std::fs::metadata(path)?.len()))
+            })
+            .collect()
+    });
+
+    // Files are grouped by the hash of their content, in path order, so the
+    // first file of each group is the one kept.
+    let mut groups: BTreeMap<u64, Vec<(&Path, u64)>> = BTreeMap::new();
+    let mut failed = Vec::new();
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok((hash, len)) => groups.entry(hash).or_default().push((path, len)),
+            Err(e) => {
+                eprintln!("Error: {}: {}", path.display(), e);
+                failed.push(ErrorKind::of(e.as_ref()));
+            }
+        }
+    }
+    let mut groups: Vec<_> = groups.into_values().filter(|group| group.len() > 1).collect();
+    groups.sort_by(|a, b| a[0].0.cmp(b[0].0));
+
+    let mut duplicates = 0;
+    let mut reclaimed = 0;
+    for group in &groups {
+        let (keep, _) = group[0];
+        println!("{}", keep.display());
+        for &(duplicate, len) in &group[1..] {
+            match remove_duplicate(keep, duplicate, by, action) {
+                Ok(Duplicate::Matched) => {
+                    println!("  {}", duplicate.display());
+                    duplicates += 1;
+                    reclaimed += len;
+                }
+                Ok(Duplicate::HardLinked) => println!("  {} (already a hard link, kept)", duplicate.display()),
+                Ok(Duplicate::HashCollision) => println!("  {} (hash collision, kept)", duplicate.display()),
+                Err(e) => {
+                    eprintln!("Error: {}: {}", duplicate.display(), e);
+                    failed.push(ErrorKind::of(e.as_ref()));
+                }
+            }
+        }
+    }
+    let verb = match action {
+        DedupeAction::Report => "could be reclaimed",
+        DedupeAction::Hardlink => "reclaimed by hard links",
+        DedupeAction::Delete => "reclaimed by deleting",
+    };
+    println!(
+        "{} duplicates in {} groups among {} files, {} {}",
+        duplicates,
+        groups.len(),
+        files.len(),
+        format_bytes(reclaimed as usize),
+        verb
+    );
+
+    if let Some(&first_kind) = failed.first() {
+        let kind = if failed.len() < files.len() { ErrorKind::PartialFailure } else { first_kind };
+        return Err(CliError::new(kind, format!("{} files could not be deduplicated", failed.len())).into());
+    }
+    Ok(())
+}
+
+/// What a file with the same hash as the kept one turned out to be.
+pub enum Duplicate {
+    /// A copy of the kept file, to which the action was applied
+    Matched,
+    /// Another hard link to the kept file, which takes no extra space
+    HardLinked,
+    /// A different file, left alone
+    HashCollision,
+}
+
+/// Applies `action` to `duplicate` if it really is a copy of `keep`.
+pub fn remove_duplicate(keep: &Path, duplicate: &Path, by: DedupeBy, action: DedupeAction) -> Result<Duplicate, BatchError> {
+    if is_same_file(keep, duplicate)? {
+        return Ok(Duplicate::HardLinked);
+    }
+    // The hashes only say the files are probably identical, so they are
+    // compared in full before one is reported or replaced.
+    if dedupe_content(keep, by)? != dedupe_content(duplicate, by)? {
+        return Ok(Duplicate::HashCollision);
+    }
+    match action {
+        DedupeAction::Report => {}
+        DedupeAction::Hardlink => replace_with_hard_link(keep, duplicate)?,
+        DedupeAction::Delete => std::fs::remove_file(duplicate)?,
+    }
+    Ok(Duplicate::Matched)
+}
+
+/// Whether both paths are hard links to the same file.
+#[cfg(unix)]
+pub fn is_same_file(a: &Path, b: &Path) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let (a, b) = (std::fs::metadata(a)?, std::fs::metadata(b)?);
+    Ok(a.dev() == b.dev() && a.ino() == b.ino())
+}
+
+#[cfg(not(unix))]
+pub fn is_same_file(_a: &Path, _b: &Path) -> std::io::Result<bool> {
+    Ok(false)
+}
+
+/// The part of a file `dedupe` compares: its bytes, or its width, height and
+/// RGBA pixels.
+pub fn dedupe_content(path: &Path, by: DedupeBy) -> Result<Vec<u8>, BatchError> {
+    let data = std::fs::read(path)?;
+    match by {
+        DedupeBy::Bytes => Ok(data),
+        DedupeBy::Pixels => {
+            let decoded = decode_from_memory(&data, DecodeOptions::default())?;
+            let image = &decoded.image;
+            let mut content = Vec::with_capacity(8 + image.pixels.len());
+            content.extend_from_slice(&image.width.to_le_bytes());
+            content.extend_from_slice(&image.height.to_le_bytes());
+            content.extend_from_slice(image.pixels);
+            Ok(content)
+        }
+    }
+}
+
+/// Replaces `duplicate` with a hard link to `keep`, through a temporary link
+/// so that `duplicate` is never missing.
+pub fn replace_with_hard_link(keep: &Path, duplicate: &Path) -> std::io::Result<()> {
+    let mut temp = duplicate.as_os_str().to_owned();
+    temp.push(".dedupe-tmp");
+    std::fs::hard_link(keep, &temp)?;
+    std::fs::rename(&temp, duplicate).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temp);
+    })
+}
@@ -0,0 +1,80 @@
+use image::{DynamicImage, RgbImage, Rgba, RgbaImage};
+use std::path::Path;
+use crate::io::{load_rgba, save_image};
+use crate::compare::{check_same_size, psnr};
+
+pub fn diff_command(
+    a: &Path,
+    b: &Path,
+    output: Option<&Path>,
+    amplify: u32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let a = load_rgba(a)?;
+    let b = load_rgba(b)?;
+    check_same_size(&a, &b)?;
+
+    let mut diff = RgbImage::new(a.width(), a.height());
+    let mut differing = 0u64;
+    let mut max = [0u8; 4];
+    let mut total = [0u64; 4];
+    for ((pa, pb), pixel) in a.pixels().zip(b.pixels()).zip(diff.pixels_mut()) {
+        let d: [u8; 4] = std::array::from_fn(|c| pa[c].abs_diff(pb[c]));
+        if d != [0; 4] {
+            differing += 1;
+        }
+        for c in 0..4 {
+            max[c] = max[c].max(d[c]);
+            total[c] += d[c] as u64;
+        }
+        // A change in alpha shows up in every color channel.
+        let amplified = |c: usize| (d[c].max(d[3]) as u32 * amplify).min(255) as u8;
+        *pixel = image::Rgb([amplified(0), amplified(1), amplified(2)]);
+    }
+
+    let pixel_count = (a.width() as u64 * a.height() as u64).max(1);
+    println!(
+        "Differing pixels: {} of {} ({:.2}%)",
+        differing,
+        pixel_count,
+        100.0 * differing as f64 / pixel_count as f64
+    );
+    println!("{:>8}  {:>4}  {:>7}", "Channel", "Max", "Mean");
+    for (c, name) in ["R", "G", "B", "A"].iter().enumerate() {
+        println!(
+            "{:>8}  {:>4}  {:>7.3}",
+            name,
+            max[c],
+            total[c] as f64 / pixel_count as f64
+        );
+    }
+    let psnr = psnr(&a, &b);
+    if psnr.is_infinite() {
+        println!("PSNR: inf (identical)");
+    } else {
+        println!("PSNR: {:.3} dB", psnr);
+    }
+
+    if let Some(output) = output {
+        save_image(&DynamicImage::ImageRgb8(diff), output, 100)?;
+        println!("Diff saved to: {} (amplified {}x)", output.display(), amplify);
+    }
+
+    Ok(())
+}
+
+pub fn diff_heat_map(a: &RgbaImage, b: &RgbaImage) -> (RgbaImage, u8) {
+    let diffs: Vec<u8> = a
+        .pixels()
+        .zip(b.pixels())
+        .map(|(pa, pb)| (0..4).map(|c| pa[c].abs_diff(pb[c])).max().unwrap_or(0))
+        .collect();
+    let max = diffs.iter().copied().max().unwrap_or(0);
+
+    let mut map = RgbaImage::new(a.width(), a.height());
+    for (pixel, &diff) in map.pixels_mut().zip(&diffs) {
+        let t = if max == 0 { 0.0 } else { diff as f64 / max as f64 * 3.0 };
+        let channel = |start: f64| ((t - start).clamp(0.0, 1.0) * 255.0).round() as u8;
+        *pixel = Rgba([channel(0.0), channel(1.0), channel(2.0), 255]);
+    }
+    (map, max)
+}
@@ -0,0 +1,118 @@
+use qoir_rs::{build_info, decode_from_memory, encode_to_vec, DecodeOptions, EncodeOptions, Image, PixelFormat};
+use crate::errors::{CliError, ErrorKind};
+
+/// The pixel formats the doctor command checks.
+pub const PIXEL_FORMATS: [PixelFormat; 8] = [
+    PixelFormat::BGRX,
+    PixelFormat::BGRANonPremul,
+    PixelFormat::BGRAPremul,
+    PixelFormat::BGR,
+    PixelFormat::RGBX,
+    PixelFormat::RGBANonPremul,
+    PixelFormat::RGBAPremul,
+    PixelFormat::RGB,
+];
+
+pub fn doctor_command() -> Result<(), Box<dyn std::error::Error>> {
+    let info = build_info();
+    let on_off = |enabled: bool| if enabled { "on" } else { "off" };
+    println!("qoir {}", info.crate_version);
+    let mut backends = Vec::new();
+    if info.c_backend {
+        backends.push(format!("C (revision {})", info.c_library_rev.unwrap_or("unknown")));
+    }
+    if info.rust_backend {
+        backends.push("Rust".to_string());
+    }
+    println!("Backends:    {}", backends.join(", "));
+    if info.c_backend {
+        println!("C library:   SIMD {}, large look-up tables {}, AVX2 build {}", on_off(info.simd_enabled), on_off(info.large_luts), if info.avx2 { "in use" } else { "not in use" });
+    }
+    println!("CPU:         {}", cpu_description());
+    println!();
+
+    // Odd sizes, so that the tiles at the right and bottom edges are partial.
+    let (width, height) = (67, 45);
+    let source: Vec<u8> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| [(x * 255 / width) as u8, (y * 255 / height) as u8, ((x + y) * 7 % 256) as u8, (128 + (x * y) % 128) as u8]))
+        .flatten()
+        .collect();
+    let source = Image::from_raw(&source, width, height, PixelFormat::RGBANonPremul)?;
+
+    let mut failed = 0;
+    for pixel_format in PIXEL_FORMATS {
+        match doctor_round_trip(&source, pixel_format) {
+            Ok(()) => println!("PASS  {:?} round trip at lossiness 0 to 7", pixel_format),
+            Err(e) => {
+                println!("FAIL  {:?} round trip: {}", pixel_format, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} of {} checks passed", PIXEL_FORMATS.len() - failed, PIXEL_FORMATS.len());
+    if failed > 0 {
+        return Err(CliError::new(ErrorKind::Other, format!("{} checks failed", failed)).into());
+    }
+    Ok(())
+}
+
+/// Encodes `source` in `pixel_format` at every lossiness level and checks
+/// that it decodes back, and that both backends agree when both are built in.
+pub fn doctor_round_trip(source: &Image, pixel_format: PixelFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let pixels = source.to_pixel_format(pixel_format)?;
+    let image = Image::from_raw(&pixels, source.width, source.height, pixel_format)?;
+    // Compare in one format, where the X bytes and premultiplication no longer
+    // make a difference.
+    let expected = image.to_pixel_format(PixelFormat::RGBANonPremul)?;
+    let options = DecodeOptions {
+        pixel_format,
+        ..Default::default()
+    };
+
+    for lossiness in 0..=7 {
+        let encoded = encode_to_vec(image.clone(), EncodeOptions { lossiness, ..Default::default() })?;
+        let decoded = decode_from_memory(&encoded, options.clone())?;
+        if (decoded.image.width, decoded.image.height) != (image.width, image.height) {
+            return Err(format!("lossiness {}: decoded as {}x{}", lossiness, decoded.image.width, decoded.image.height).into());
+        }
+        if build_info().c_backend {
+            let rust = qoir_rs::rust_backend::decode_from_memory(&encoded, options.clone())?;
+            if rust.image.to_pixel_format(pixel_format)? != decoded.image.to_pixel_format(pixel_format)? {
+                return Err(format!("lossiness {}: the C and Rust backends decode differently", lossiness).into());
+            }
+        }
+
+        let actual = decoded.image.to_pixel_format(PixelFormat::RGBANonPremul)?;
+        let total_error: u64 = expected.iter().zip(&actual).map(|(&a, &b)| a.abs_diff(b) as u64).sum();
+        let mean_error = total_error as f64 / expected.len() as f64;
+        // Lossless encoding is exact. Lossy encoding drops `lossiness` low bits
+        // from each color channel.
+        let max_error = if lossiness == 0 { 0.0 } else { (1u32 << lossiness) as f64 };
+        if mean_error > max_error {
+            return Err(format!("lossiness {}: mean error {:.2}, expected at most {}", lossiness, mean_error, max_error).into());
+        }
+    }
+    Ok(())
+}
+
+/// The CPU architecture and the SIMD instruction sets it supports.
+pub fn cpu_description() -> String {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    let features = [
+        ("SSE2", std::arch::is_x86_feature_detected!("sse2")),
+        ("SSE4.2", std::arch::is_x86_feature_detected!("sse4.2")),
+        ("AVX2", std::arch::is_x86_feature_detected!("avx2")),
+    ];
+    #[cfg(target_arch = "aarch64")]
+    let features = [("NEON", std::arch::is_aarch64_feature_detected!("neon"))];
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    let features: [(&str, bool); 0] = [];
+
+    let supported: Vec<&str> = features.iter().filter(|(_, detected)| *detected).map(|(name, _)| *name).collect();
+    if supported.is_empty() {
+        std::env::consts::ARCH.to_string()
+    } else {
+        format!("{} with {}", std::env::consts::ARCH, supported.join(", "))
+    }
+}
@@ -0,0 +1,34 @@
+use qoir_rs::{encode, EncodeOptions, ImageBuf};
+use std::path::PathBuf;
+use crate::args::ResizeArgs;
+use crate::io::{embed_source_metadata, format_bytes, load_image, rgba_image};
+
+pub fn encode_command(
+    input: PathBuf, 
+    output: PathBuf, 
+    mut options: EncodeOptions,
+    strip_metadata: bool,
+    resize: &ResizeArgs,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Convert input image to a format suitable for QOIR encoding
+    let data = std::fs::read(&input)?;
+    let img = load_image(&input, &data)?;
+    let rgba_img = img.to_rgba8();
+    let image = rgba_image(&rgba_img);
+    let resized = resize.apply(&image)?;
+    let image = resized.as_ref().map_or(image, ImageBuf::as_image);
+    
+    if !strip_metadata {
+        embed_source_metadata(&mut options, &data);
+    }
+    
+    let encoded = encode(image, options, &output)?;
+    
+    println!(
+        "Image encoded to QOIR: {} ({})", 
+        output.display(),
+        format_bytes(encoded.data.len())
+    );
+    
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use qoir_rs::{encode_to_memory, EncodeOptions, Image, PixelFormat};
+use std::path::Path;
+use std::io::{Read, Write};
+use crate::errors::{CliError, ErrorKind};
+use crate::io::format_bytes;
+
+pub fn encode_raw_command(
+    input: &Path,
+    output: &Path,
+    (width, height): (u32, u32),
+    pixel_format: PixelFormat,
+    stride: usize,
+    options: EncodeOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pixels = if input == Path::new("-") {
+        let mut pixels = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut pixels)?;
+        pixels
+    } else {
+        std::fs::read(input)?
+    };
+    let image = Image::from_raw_with_stride(&pixels, width, height, pixel_format, stride).map_err(|_| {
+        let message = format!(
+            "{} bytes of input is too little for {}x{} {:?} pixels with a stride of {}",
+            pixels.len(),
+            width,
+            height,
+            pixel_format,
+            stride
+        );
+        CliError::new(ErrorKind::Arguments, message)
+    })?;
+    let encoded = encode_to_memory(image, options)?;
+
+    if output == Path::new("-") {
+        std::io::stdout().lock().write_all(encoded.data)?;
+        // Keep standard output for the image.
+        eprintln!("Raw pixels encoded to QOIR ({})", format_bytes(encoded.data.len()));
+    } else {
+        std::fs::write(output, encoded.data)?;
+        println!(
+            "Raw pixels encoded to QOIR: {} ({})",
+            output.display(),
+            format_bytes(encoded.data.len())
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+use image::error::ImageError;
+use qoir_rs::VerifyError;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// How failures are reported on stderr.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// Messages for people
+    Text,
+    /// One JSON record per line, with the file, kind, exit code and message
+    Json,
+}
+
+pub static ERROR_FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+/// Whether failures are reported as JSON records rather than messages.
+pub fn json_errors() -> bool {
+    ERROR_FORMAT.get() == Some(&ErrorFormat::Json)
+}
+
+/// What kind of failure ended a command, which decides its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Other,
+    Decode,
+    Io,
+    Arguments,
+    PartialFailure,
+}
+
+impl ErrorKind {
+    /// The exit code for this kind of failure. These are stable, so that
+    /// scripts can rely on them.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Decode => 2,
+            ErrorKind::Io => 3,
+            ErrorKind::Arguments => 4,
+            ErrorKind::PartialFailure => 5,
+        }
+    }
+
+    pub fn of(error: &(dyn std::error::Error + 'static)) -> Self {
+        if let Some(error) = error.downcast_ref::<CliError>() {
+            return error.kind;
+        }
+        if let Some(error) = error.downcast_ref::<VerifyError>() {
+            return ErrorKind::of(&error.error);
+        }
+        if let Some(error) = error.downcast_ref::<qoir_rs::Error>() {
+            return match error {
+                qoir_rs::Error::DecodingFailed(_) => ErrorKind::Decode,
+                qoir_rs::Error::FileNotFound | qoir_rs::Error::IoError { .. } => ErrorKind::Io,
+                qoir_rs::Error::InvalidParameter => ErrorKind::Arguments,
+                qoir_rs::Error::EncodingFailed(_) | qoir_rs::Error::Internal(_) => ErrorKind::Other,
+            };
+        }
+        if let Some(error) = error.downcast_ref::<ImageError>() {
+            return match error {
+                ImageError::Decoding(_) | ImageError::Unsupported(_) | ImageError::Limits(_) => ErrorKind::Decode,
+                ImageError::IoError(_) => ErrorKind::Io,
+                ImageError::Parameter(_) => ErrorKind::Arguments,
+                ImageError::Encoding(_) => ErrorKind::Other,
+            };
+        }
+        if error.is::<std::io::Error>() {
+            ErrorKind::Io
+        } else if error.is::<clap::Error>() {
+            ErrorKind::Arguments
+        } else {
+            ErrorKind::Other
+        }
+    }
+}
+
+/// A failure detected by the CLI itself rather than by a library it calls.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub struct CliError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl CliError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CliError {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+/// A failure as `--errors json` prints it.
+#[derive(Serialize)]
+pub struct ErrorRecord<'a> {
+    file: Option<&'a Path>,
+    kind: ErrorKind,
+    exit_code: u8,
+    message: &'a str,
+}
+
+pub fn print_error_record(file: Option<&Path>, kind: ErrorKind, message: &str) {
+    let record = ErrorRecord {
+        file,
+        kind,
+        exit_code: kind.exit_code(),
+        message,
+    };
+    if let Ok(json) = serde_json::to_string(&record) {
+        eprintln!("{}", json);
+    }
+}
+
+pub type BatchError = Box<dyn std::error::Error + Send + Sync>;
@@ -0,0 +1,51 @@
+use qoir_rs::{read_metadata, rewrite_metadata, DecodeOptions, MetadataChange, MetadataEdit};
+use qoir_rs::anim::AnimationDecoder;
+use std::path::Path;
+use crate::errors::{CliError, ErrorKind};
+use crate::io::{IMAGE_EXTENSIONS, save_image, to_dynamic_image};
+
+pub fn explode_command(input: &Path, output_dir: &Path, extension: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let extension = extension.trim_start_matches('.').to_lowercase();
+    if extension != "qoir" && !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(CliError::new(ErrorKind::Arguments, format!("Unsupported output format: {}", extension)).into());
+    }
+
+    let data = std::fs::read(input)?;
+    let decoder = AnimationDecoder::new(&data)?;
+    let shared = decoder.metadata();
+    std::fs::create_dir_all(output_dir)?;
+
+    for (index, frame) in decoder.frames().iter().enumerate() {
+        let path = output_dir.join(format!("frame-{:04}.{}", index, extension));
+        if extension == "qoir" {
+            // Give each file the shared metadata it lacks, as decoding would.
+            let own = read_metadata(frame.data)?;
+            fn fill<'a>(own: Option<&[u8]>, shared: Option<&'a [u8]>) -> MetadataChange<'a> {
+                match (own, shared) {
+                    (None, Some(payload)) => MetadataChange::Set(payload),
+                    _ => MetadataChange::Keep,
+                }
+            }
+            let edit = MetadataEdit {
+                cic_profile: fill(own.cic_profile, shared.cic_profile),
+                icc_profile: fill(own.icc_profile, shared.icc_profile),
+                exif: fill(own.exif, shared.exif),
+                xmp: fill(own.xmp, shared.xmp),
+            };
+            std::fs::write(&path, rewrite_metadata(frame.data, &edit)?)?;
+        } else {
+            let decoded = decoder.decode_frame(index, DecodeOptions::default())?;
+            save_image(&to_dynamic_image(&decoded.image)?, &path, 90)?;
+        }
+    }
+
+    println!(
+        "Wrote {} frames of {}x{} ({} ms) to: {}",
+        decoder.frame_count(),
+        decoder.width(),
+        decoder.height(),
+        decoder.total_duration_ms(),
+        output_dir.display()
+    );
+    Ok(())
+}
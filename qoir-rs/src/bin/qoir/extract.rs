@@ -0,0 +1,33 @@
+use qoir_rs::read_metadata;
+use std::path::{Path, PathBuf};
+use std::io::Write;
+use crate::io::format_bytes;
+
+pub fn extract_command(input: &Path, paths: [Option<PathBuf>; 4]) -> Result<(), Box<dyn std::error::Error>> {
+    const NAMES: [&str; 4] = ["ICC profile", "EXIF data", "XMP data", "CICP profile"];
+
+    let data = std::fs::read(input)?;
+    let metadata = read_metadata(&data)?;
+    let payloads = [metadata.icc_profile, metadata.exif, metadata.xmp, metadata.cic_profile];
+
+    let mut missing = Vec::new();
+    for ((name, payload), path) in NAMES.iter().zip(payloads).zip(paths) {
+        let Some(path) = path else { continue };
+        let Some(payload) = payload else {
+            missing.push(*name);
+            continue;
+        };
+        if path == Path::new("-") {
+            std::io::stdout().lock().write_all(payload)?;
+        } else {
+            std::fs::write(&path, payload)?;
+            // Keep standard output clean when a payload is written there.
+            eprintln!("Extracted {} to: {} ({})", name, path.display(), format_bytes(payload.len()));
+        }
+    }
+
+    if !missing.is_empty() {
+        return Err(format!("{} has no {}", input.display(), missing.join(", ")).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,65 @@
+use qoir_rs::{decode_from_memory, phash, hamming_distance, DecodeOptions};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::errors::{BatchError, CliError, ErrorKind, json_errors, print_error_record};
+use crate::io::{open_image, rgba_image};
+use crate::batch::in_thread_pool;
+
+pub fn hash_command(files: &[PathBuf], duplicates: Option<u32>) -> Result<(), Box<dyn std::error::Error>> {
+    let results: Vec<Result<u64, BatchError>> = in_thread_pool(|| files.par_iter().map(|path| hash_file(path)).collect());
+
+    let mut hashes = Vec::new();
+    let mut failed = Vec::new();
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(hash) => {
+                if duplicates.is_none() {
+                    println!("{:016x}  {}", hash, path.display());
+                }
+                hashes.push((path, hash));
+            }
+            Err(e) => {
+                eprintln!("Error: {}: {}", path.display(), e);
+                let kind = ErrorKind::of(e.as_ref());
+                if json_errors() {
+                    print_error_record(Some(path), kind, &e.to_string());
+                }
+                failed.push(kind);
+            }
+        }
+    }
+
+    if let Some(max_distance) = duplicates {
+        let mut pairs = Vec::new();
+        for (i, &(a, hash_a)) in hashes.iter().enumerate() {
+            for &(b, hash_b) in &hashes[i + 1..] {
+                let distance = hamming_distance(hash_a, hash_b);
+                if distance <= max_distance {
+                    pairs.push((distance, a, b));
+                }
+            }
+        }
+        pairs.sort_by_key(|&(distance, _, _)| distance);
+        for (distance, a, b) in &pairs {
+            println!("{:2}  {}  {}", distance, a.display(), b.display());
+        }
+        println!("{} pairs within {} bits among {} images", pairs.len(), max_distance, hashes.len());
+    }
+
+    if let Some(&first_kind) = failed.first() {
+        let kind = if failed.len() < files.len() { ErrorKind::PartialFailure } else { first_kind };
+        return Err(CliError::new(kind, format!("{} files could not be hashed", failed.len())).into());
+    }
+    Ok(())
+}
+
+/// Computes the perceptual hash of an image file, reading QOIR pixels in place.
+pub fn hash_file(path: &Path) -> Result<u64, BatchError> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if ext.eq_ignore_ascii_case("qoir") {
+        let decoded = decode_from_memory(&std::fs::read(path)?, DecodeOptions::default())?;
+        return Ok(phash(&decoded.image)?);
+    }
+    let image = open_image(path)?.to_rgba8();
+    Ok(phash(&rgba_image(&image))?)
+}
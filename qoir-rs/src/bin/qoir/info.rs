@@ -0,0 +1,137 @@
+use qoir_rs::{
+    decode_basic_metadata, decode_from_memory, read_metadata, inspect, DecodeLimits, DecodeOptions, TileCodec
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use crate::io::{format_bytes, read_input};
+
+pub fn info_command(input: &Path, limits: DecodeLimits) -> Result<(), Box<dyn std::error::Error>> {
+    // Read QOIR file into memory
+    let data = read_input(input)?;
+    
+    // Get basic metadata
+    let (width, height, pixel_format) = decode_basic_metadata(&data)?;
+    
+    println!("QOIR File: {}", input.display());
+    println!("Dimensions: {}x{}", width, height);
+    println!("Pixel Format: {:?}", pixel_format);
+    println!("File Size: {}", format_bytes(data.len()));
+    
+    // Get more detailed information if possible
+    let options = DecodeOptions {
+        limits,
+        ..Default::default()
+    };
+    match decode_from_memory(&data, options) {
+        Ok(decoded) => {
+            println!("Decoded Image Size: {}", format_bytes(decoded.image.pixels.len()));
+            
+            if decoded.cic_profile.is_some() {
+                println!("Has CIC Profile: Yes");
+            }
+            if decoded.icc_profile.is_some() {
+                println!("Has ICC Profile: Yes");
+            }
+            if decoded.exif.is_some() {
+                println!("Has EXIF Data: Yes");
+            }
+            if decoded.xmp.is_some() {
+                println!("Has XMP Data: Yes");
+            }
+            if !decoded.custom_metadata.is_empty() {
+                let keys: Vec<&str> = decoded.custom_metadata.iter().map(|(key, _)| key.as_str()).collect();
+                println!("Custom Metadata: {}", keys.join(", "));
+            }
+        }
+        Err(e) => {
+            println!("Warning: Could not fully decode image: {:?}", e);
+        }
+    }
+    
+    Ok(())
+}
+
+/// The output of `info --format json` and `info --format yaml`.
+#[derive(Serialize)]
+pub struct InfoReport {
+    path: PathBuf,
+    file_size: usize,
+    width: u32,
+    height: u32,
+    pixel_format: String,
+    /// The number of low bits dropped from each color channel.
+    lossiness: u8,
+    lossless: bool,
+    /// Compressed bits per pixel, including metadata.
+    bits_per_pixel: f64,
+    /// Size of the pixels as 8-bit BGRA divided by the file size.
+    compression_ratio: f64,
+    metadata: MetadataReport,
+    tile_size: u32,
+    tiles: Vec<TileReport>,
+}
+
+/// The payload size of each metadata chunk, or `None` if it is absent.
+#[derive(Serialize)]
+pub struct MetadataReport {
+    cicp: Option<usize>,
+    icc: Option<usize>,
+    exif: Option<usize>,
+    xmp: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct TileReport {
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    offset: usize,
+    compressed_len: usize,
+    codec: &'static str,
+}
+
+pub fn info_report(input: &Path) -> Result<InfoReport, Box<dyn std::error::Error>> {
+    let data = read_input(input)?;
+    let layout = inspect(&data)?;
+    let metadata = read_metadata(&data)?;
+
+    let pixels = layout.width as f64 * layout.height as f64;
+    let len = |payload: Option<&[u8]>| payload.map(<[u8]>::len);
+    Ok(InfoReport {
+        path: input.to_path_buf(),
+        file_size: data.len(),
+        width: layout.width,
+        height: layout.height,
+        pixel_format: format!("{:?}", layout.pixel_format),
+        lossiness: layout.lossiness,
+        lossless: layout.lossiness == 0,
+        bits_per_pixel: if pixels > 0.0 { data.len() as f64 * 8.0 / pixels } else { 0.0 },
+        compression_ratio: pixels * 4.0 / data.len() as f64,
+        metadata: MetadataReport {
+            cicp: len(metadata.cic_profile),
+            icc: len(metadata.icc_profile),
+            exif: len(metadata.exif),
+            xmp: len(metadata.xmp),
+        },
+        tile_size: layout.tile_size,
+        tiles: layout
+            .tiles
+            .iter()
+            .map(|tile| TileReport {
+                x: tile.rect.x0,
+                y: tile.rect.y0,
+                width: tile.rect.x1 - tile.rect.x0,
+                height: tile.rect.y1 - tile.rect.y0,
+                offset: tile.offset,
+                compressed_len: tile.compressed_len,
+                codec: match tile.codec {
+                    TileCodec::Literals => "literals",
+                    TileCodec::Opcodes => "opcodes",
+                    TileCodec::Lz4Literals => "lz4_literals",
+                    TileCodec::Lz4Opcodes => "lz4_opcodes",
+                },
+            })
+            .collect(),
+    })
+}
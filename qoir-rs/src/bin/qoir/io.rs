@@ -0,0 +1,342 @@
+#[cfg(not(feature = "turbojpeg"))]
+use image::codecs::jpeg::JpegEncoder;
+use exif::experimental::Writer as ExifWriter;
+use exif::{Context, In, Tag, Value};
+use img_parts::jpeg::markers as jpeg_markers;
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::error::{DecodingError, ImageError};
+use image::{DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use qoir_rs::{decode, DecodeOptions, EncodeOptions, Image, PixelFormat, qoi};
+use std::path::{Component, Path, PathBuf};
+use std::fs::File;
+use std::io::Read;
+use std::sync::Mutex;
+use crate::errors::{CliError, ErrorKind};
+
+/// The output extensions, besides `.qoir`, that `save_image` writes.
+pub const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp", "tif", "tiff", "bmp", "ppm", "qoi"];
+
+/// Copies the ICC profile, EXIF and XMP of a JPEG, PNG, WebP or TIFF file
+/// into `options`. Metadata that is missing or cannot be parsed is skipped.
+pub fn embed_source_metadata(options: &mut EncodeOptions, data: &[u8]) {
+    let (icc_profile, exif, xmp) = if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+        tiff_metadata(data)
+    } else {
+        container_metadata(data)
+    };
+    options.icc_profile = options.icc_profile.take().or(icc_profile);
+    options.exif = options.exif.take().or(exif);
+    options.xmp = options.xmp.take().or(xmp);
+}
+
+/// The ICC profile, EXIF and XMP of a file.
+pub type SourceMetadata = (Option<Vec<u8>>, Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Reads the metadata of a JPEG, PNG or WebP file.
+pub fn container_metadata(data: &[u8]) -> SourceMetadata {
+    let Ok(Some(image)) = DynImage::from_bytes(Bytes::copy_from_slice(data)) else {
+        return (None, None, None);
+    };
+    let xmp = match &image {
+        DynImage::Jpeg(jpeg) => jpeg.segments_by_marker(jpeg_markers::APP1).find_map(|segment| {
+            segment.contents().strip_prefix(b"http://ns.adobe.com/xap/1.0/\0").map(<[u8]>::to_vec)
+        }),
+        DynImage::Png(png) => png.chunks_by_type(*b"iTXt").find_map(|chunk| png_xmp(chunk.contents())),
+        DynImage::WebP(webp) => webp
+            .chunk_by_id(*b"XMP ")
+            .and_then(|chunk| chunk.content().data())
+            .map(|data| data.to_vec()),
+    };
+    let icc_profile = image.icc_profile().map(|icc| icc.to_vec());
+    (icc_profile, image.exif().map(|exif| exif.to_vec()), xmp)
+}
+
+/// The XMP packet in a PNG `iTXt` chunk, if the chunk holds an uncompressed one.
+pub fn png_xmp(chunk: &[u8]) -> Option<Vec<u8>> {
+    // keyword NUL, compression flag, compression method, language tag NUL,
+    // translated keyword NUL, text
+    let text = chunk.strip_prefix(b"XML:com.adobe.xmp\0")?;
+    let (&[0, _], text) = text.split_at_checked(2)? else {
+        return None;
+    };
+    let mut parts = text.splitn(3, |&b| b == 0);
+    let (_language, _translated, text) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(text.to_vec())
+}
+
+/// Reads the metadata of a TIFF file from the tags of its first image. The
+/// EXIF is rebuilt from the descriptive tags and the EXIF and GPS
+/// directories, leaving out the tags that describe the TIFF's own pixels.
+pub fn tiff_metadata(data: &[u8]) -> SourceMetadata {
+    const ICC_PROFILE: Tag = Tag(Context::Tiff, 34675);
+    const XMP: Tag = Tag(Context::Tiff, 700);
+    const DESCRIPTIVE_TAGS: &[Tag] = &[
+        Tag::ImageDescription,
+        Tag::Make,
+        Tag::Model,
+        Tag::Orientation,
+        Tag::XResolution,
+        Tag::YResolution,
+        Tag::ResolutionUnit,
+        Tag::Software,
+        Tag::DateTime,
+        Tag::Artist,
+        Tag::Copyright,
+    ];
+
+    let Ok(exif) = exif::Reader::new().read_raw(data.to_vec()) else {
+        return (None, None, None);
+    };
+    let bytes = |tag| match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+        Some(Value::Byte(bytes) | Value::Undefined(bytes, _)) => Some(bytes.clone()),
+        _ => None,
+    };
+
+    let mut writer = ExifWriter::new();
+    let mut has_exif = false;
+    for field in exif.fields().filter(|field| field.ifd_num == In::PRIMARY) {
+        if field.tag.context() != Context::Tiff || DESCRIPTIVE_TAGS.contains(&field.tag) {
+            writer.push_field(field);
+            has_exif = true;
+        }
+    }
+    let mut rebuilt = std::io::Cursor::new(Vec::new());
+    let exif_data = (has_exif && writer.write(&mut rebuilt, exif.little_endian()).is_ok())
+        .then(|| rebuilt.into_inner());
+
+    (bytes(ICC_PROFILE), exif_data, bytes(XMP))
+}
+
+/// Reads an image in any format the image crate reads, decoding QOI with the
+/// library's own decoder.
+/// Reads a whole file, or standard input if `path` is `-`.
+pub fn read_input(path: &Path) -> std::io::Result<Vec<u8>> {
+    if path != Path::new("-") {
+        return std::fs::read(path);
+    }
+    let mut data = Vec::new();
+    std::io::stdin().lock().read_to_end(&mut data)?;
+    Ok(data)
+}
+
+pub fn open_image(path: &Path) -> image::ImageResult<DynamicImage> {
+    load_image(path, &std::fs::read(path)?)
+}
+
+/// Decodes the contents of the image file at `path`, whose extension picks
+/// the format.
+pub fn load_image(path: &Path, data: &[u8]) -> image::ImageResult<DynamicImage> {
+    // libjpeg-turbo is much faster than the image crate at JPEG, but cannot
+    // read every JPEG, such as CMYK ones, which fall back to the image crate.
+    #[cfg(feature = "turbojpeg")]
+    {
+        let jpeg = data.starts_with(&[0xFF, 0xD8, 0xFF]);
+        if let Some(Ok(decoded)) = jpeg.then(|| qoir_rs::turbojpeg::decode_jpeg(data, PixelFormat::RGB)) {
+            let img = RgbImage::from_raw(decoded.width, decoded.height, decoded.pixels);
+            return Ok(DynamicImage::ImageRgb8(img.expect("JPEG pixels are tightly packed")));
+        }
+    }
+
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("qoi") {
+        return match ImageFormat::from_path(path) {
+            Ok(format) => image::load_from_memory_with_format(data, format),
+            Err(_) => image::load_from_memory(data),
+        };
+    }
+
+    let qoi_error = |e: qoir_rs::Error| ImageError::Decoding(DecodingError::new(ImageFormat::Qoi.into(), e));
+    let header = qoi::decode_header(data).map_err(qoi_error)?;
+    let decoded = qoi::decode_from_memory(data, header.pixel_format()).map_err(qoi_error)?;
+    let (width, height) = (decoded.width, decoded.height);
+    let img = if header.channels == 4 {
+        RgbaImage::from_raw(width, height, decoded.pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        RgbImage::from_raw(width, height, decoded.pixels).map(DynamicImage::ImageRgb8)
+    };
+    Ok(img.expect("QOI pixels are tightly packed"))
+}
+
+/// Writes `img` in the format named by the extension of `path`: PNG, JPEG,
+/// lossless WebP, TIFF, BMP, binary PPM or QOI. `jpeg_quality` only applies to
+/// JPEG.
+pub fn save_image(img: &DynamicImage, path: &Path, jpeg_quality: u8) -> Result<(), Box<dyn std::error::Error>> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+    match ext.as_str() {
+        "jpg" | "jpeg" => save_jpeg(img, path, jpeg_quality)?,
+        "ppm" => {
+            // The image crate writes PAM for `.ppm` unless asked for a pixmap,
+            // which has no alpha channel.
+            let file = std::io::BufWriter::new(File::create(path)?);
+            let encoder = PnmEncoder::new(file).with_subtype(PnmSubtype::Pixmap(SampleEncoding::Binary));
+            img.to_rgb8().write_with_encoder(encoder)?;
+        }
+        "qoi" => {
+            let rgba;
+            let image = match img {
+                DynamicImage::ImageRgb8(rgb) => Image {
+                    pixels: rgb.as_raw(),
+                    width: rgb.width(),
+                    height: rgb.height(),
+                    pixel_format: PixelFormat::RGB,
+                    stride_in_bytes: rgb.width() as usize * 3,
+                },
+                _ => {
+                    rgba = img.to_rgba8();
+                    Image {
+                        pixels: rgba.as_raw(),
+                        width: rgba.width(),
+                        height: rgba.height(),
+                        pixel_format: PixelFormat::RGBANonPremul,
+                        stride_in_bytes: rgba.width() as usize * 4,
+                    }
+                }
+            };
+            std::fs::write(path, qoi::encode_to_memory(image)?)?;
+        }
+        ext if IMAGE_EXTENSIONS.contains(&ext) => img.save(path)?,
+        _ => return Err(CliError::new(ErrorKind::Arguments, format!("Unsupported output format: {}", ext)).into()),
+    }
+    Ok(())
+}
+
+/// Writes `img` as a JPEG of the given quality, dropping any alpha channel.
+pub fn save_jpeg(img: &DynamicImage, path: &Path, quality: u8) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(path, jpeg_data(img, quality)?)?;
+    Ok(())
+}
+
+/// Encodes `img` as a JPEG of the given quality, dropping any alpha channel.
+pub fn jpeg_data(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let rgb = img.to_rgb8();
+    #[cfg(feature = "turbojpeg")]
+    {
+        let image = Image::from_raw(rgb.as_raw(), rgb.width(), rgb.height(), PixelFormat::RGB)?;
+        Ok(qoir_rs::turbojpeg::encode_jpeg(&image, quality.clamp(1, 100))?)
+    }
+    #[cfg(not(feature = "turbojpeg"))]
+    {
+        let mut jpeg = Vec::new();
+        rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, quality))?;
+        Ok(jpeg)
+    }
+}
+
+/// The files matching a glob pattern.
+pub fn glob_inputs(pattern: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut inputs = Vec::new();
+    for entry in glob::glob(pattern)? {
+        match entry {
+            Ok(path) if path.is_file() => inputs.push(path),
+            Ok(_) => {}
+            Err(e) => eprintln!("Warning: {}", e),
+        }
+    }
+    if inputs.is_empty() {
+        return Err(CliError::new(ErrorKind::Arguments, format!("No files match {}", pattern)).into());
+    }
+    Ok(inputs)
+}
+
+/// The files under `dir`, at any depth, that the image crate can read.
+pub fn find_images(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    if !dir.is_dir() {
+        return Err(CliError::new(ErrorKind::Arguments, format!("{} is not a directory", dir.display())).into());
+    }
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.retain(|path| image::ImageFormat::from_path(path).is_ok());
+    if files.is_empty() {
+        return Err(CliError::new(ErrorKind::Arguments, format!("No images found in {}", dir.display())).into());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns the leading directories of a glob pattern that contain no
+/// wildcards, which the output paths are made relative to. A leading `./` is
+/// dropped, as `glob` drops it from the paths it returns.
+pub fn glob_base(pattern: &str) -> PathBuf {
+    Path::new(pattern)
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+/// Locks `mutex`, carrying on if another thread panicked while holding it.
+pub fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Appends the paths of all files under `dir` to `files`.
+pub fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Reads a QOIR file with the library, and any other file with the image
+/// crate, as non-premultiplied RGBA.
+pub fn load_rgba(path: &Path) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("qoir") {
+        return Ok(open_image(path)?.to_rgba8());
+    }
+
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGBANonPremul,
+        ..Default::default()
+    };
+    let decoded = decode(path, options)?;
+    let image = &decoded.image;
+    let pixels = image.to_pixel_format(PixelFormat::RGBANonPremul)?;
+    RgbaImage::from_raw(image.width, image.height, pixels).ok_or_else(|| "Invalid pixel buffer".into())
+}
+
+/// Borrows an image crate buffer as non-premultiplied RGBA pixels.
+pub fn rgba_image(rgba_img: &RgbaImage) -> Image<'_> {
+    let (width, height) = rgba_img.dimensions();
+    Image {
+        pixels: rgba_img.as_raw(),
+        width,
+        height,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: (width * 4) as usize,
+    }
+}
+
+/// Converts decoded pixels of any format to an image crate buffer: RGBA for
+/// formats with alpha, RGB otherwise.
+pub fn to_dynamic_image(image: &Image) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let (width, height) = (image.width, image.height);
+    let img = if image.pixel_format.has_alpha() {
+        let pixels = image.to_pixel_format(PixelFormat::RGBANonPremul)?;
+        RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        let pixels = image.to_pixel_format(PixelFormat::RGB)?;
+        RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+    };
+    img.ok_or_else(|| "Invalid pixel buffer".into())
+}
+
+// Helper function to format byte sizes in a human-readable way
+pub fn format_bytes(bytes: usize) -> String {
+    const KB: usize = 1024;
+    const MB: usize = KB * 1024;
+    
+    if bytes >= MB {
+        format!("{:.2} MB", bytes as f64 / MB as f64)
+    } else if bytes >= KB {
+        format!("{:.2} KB", bytes as f64 / KB as f64)
+    } else {
+        format!("{} bytes", bytes)
+    }
+}
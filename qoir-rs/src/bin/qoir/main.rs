@@ -0,0 +1,1029 @@
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand};
+use clap_complete::Shell;
+use qoir_rs::{DecodeOptions, EncodeOptions, PixelFormat, QuantizeOptions, Rectangle, ThreadPoolBuilder};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use crate::args::{DedupeAction, DedupeBy, FilterArg, InfoFormat, LimitArgs, Metric, Overwrite, ResizeArgs, ViewProtocol, parse_byte_size, parse_crop, parse_offset, parse_pixel_format, parse_psnr};
+use crate::errors::{CliError, ERROR_FORMAT, ErrorFormat, ErrorKind, json_errors, print_error_record};
+use crate::io::{find_images, glob_base, glob_inputs};
+use crate::decode::decode_command;
+use crate::encode::encode_command;
+use crate::encode_raw::encode_raw_command;
+use crate::info::{info_command, info_report};
+use crate::convert::convert_command;
+use crate::batch::{BatchPolicy, batch_command, should_write};
+use crate::watch::watch_command;
+use crate::thumb::thumb_command;
+use crate::verify::verify_command;
+use crate::compare::compare_command;
+use crate::hash::hash_command;
+use crate::stats::stats_command;
+use crate::dedupe::dedupe_command;
+use crate::diff::diff_command;
+use crate::sweep::sweep_command;
+use crate::optimize::{OptimizeTarget, optimize_command};
+use crate::extract::extract_command;
+use crate::assemble::assemble_command;
+use crate::explode::explode_command;
+use crate::pack::pack_command;
+use crate::unpack::unpack_command;
+use crate::man::man_command;
+use crate::view::view_command;
+use crate::metadata::metadata_command;
+use crate::serve::serve_command;
+use crate::doctor::doctor_command;
+
+mod args;
+mod assemble;
+mod batch;
+mod compare;
+mod convert;
+mod decode;
+mod dedupe;
+mod diff;
+mod doctor;
+mod encode;
+mod encode_raw;
+mod errors;
+mod explode;
+mod extract;
+mod hash;
+mod info;
+mod io;
+mod man;
+mod metadata;
+mod optimize;
+mod pack;
+mod serve;
+mod stats;
+mod sweep;
+mod thumb;
+mod unpack;
+mod verify;
+mod view;
+mod watch;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// Number of threads for converting, verifying and encoding (0 uses one per logical CPU)
+    #[arg(short, long, global = true, default_value = "0")]
+    jobs: usize,
+
+    /// How to report failures on stderr
+    #[arg(long, value_enum, global = true, default_value = "text")]
+    errors: ErrorFormat,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Decode a QOIR file to raw pixels or another format
+    Decode {
+        /// Input QOIR file, or - to read standard input
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output file (.png, .jpg, .webp, .tiff, .bmp, .ppm or .qoi to convert, anything else for raw pixels)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Pixel format for decoding: rgba, rgba-premul, rgbx, rgb, bgra,
+        /// bgra-premul, bgrx or bgr
+        #[arg(short, long, default_value = "rgba")]
+        format: String,
+
+        /// Only decode this region of the source image; other pixels stay zeroed
+        #[arg(long, value_name = "X,Y,W,H", value_parser = parse_crop)]
+        crop: Option<Rectangle>,
+
+        /// Move the decoded pixels by this many pixels in the output
+        #[arg(long, value_name = "DX,DY", value_parser = parse_offset, allow_hyphen_values = true)]
+        offset: Option<(i32, i32)>,
+
+        /// Recover what can be decoded from a truncated or corrupt file,
+        /// leaving the rest transparent
+        #[arg(long)]
+        tolerant: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+
+    /// Encode an image to QOIR format
+    Encode {
+        /// Input image file (supported: jpg, png, etc.)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output QOIR file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Lossiness level (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Apply dithering during lossy compression or color reduction
+        #[arg(short, long, default_value = "false")]
+        dither: bool,
+
+        /// Reduce the image to at most this many colors before encoding, which shrinks screenshots and UI captures
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+        colors: Option<u16>,
+
+        /// Store a checksum of the pixel data, which `verify` checks
+        #[arg(long, default_value = "false")]
+        checksum: bool,
+
+        /// When to replace an existing output file
+        #[arg(long, value_enum, default_value = "always")]
+        overwrite: Overwrite,
+
+        /// Print what would be written without writing anything
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+
+        /// Don't copy the ICC profile, EXIF and XMP of the input into the QOIR file
+        #[arg(long, default_value = "false")]
+        strip_metadata: bool,
+
+        #[command(flatten)]
+        resize: ResizeArgs,
+    },
+
+    /// Encode raw pixels, such as a framebuffer or camera dump, to QOIR format
+    EncodeRaw {
+        /// Raw pixel file, or - to read standard input
+        #[arg(short, long, default_value = "-")]
+        input: PathBuf,
+
+        /// Output QOIR file, or - to write standard output
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Width of the image in pixels
+        #[arg(long)]
+        width: u32,
+
+        /// Height of the image in pixels
+        #[arg(long)]
+        height: u32,
+
+        /// Pixel format of the input: rgba, rgba-premul, rgbx, rgb, bgra,
+        /// bgra-premul, bgrx or bgr
+        #[arg(short, long, default_value = "rgba", value_parser = parse_pixel_format)]
+        format: PixelFormat,
+
+        /// Bytes from the start of one row to the next; defaults to tightly packed rows
+        #[arg(long)]
+        stride: Option<usize>,
+
+        /// Lossiness level (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Apply dithering during lossy compression
+        #[arg(short, long, default_value = "false")]
+        dither: bool,
+    },
+
+    /// Display information about a QOIR file
+    Info {
+        /// QOIR file to inspect, or - to read standard input
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output format; json and yaml include the tile layout
+        #[arg(short, long, value_enum, default_value = "text")]
+        format: InfoFormat,
+
+        /// Shorthand for --format json
+        #[arg(long, default_value = "false", conflicts_with = "format")]
+        json: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+
+    /// Convert between image formats
+    Convert {
+        /// Input image file, or a directory with --recursive
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output image file (use appropriate extension), or a directory with --recursive
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Quality level for JPEG output (1-100)
+        #[arg(short, long, default_value = "90")]
+        quality: u8,
+
+        /// Lossiness level for QOIR output (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Convert every image under the input directory to QOIR, mirroring its
+        /// subdirectories under the output directory
+        #[arg(short, long, default_value = "false")]
+        recursive: bool,
+
+        /// When to replace an existing output file
+        #[arg(long, value_enum, default_value = "always")]
+        overwrite: Overwrite,
+
+        /// Leave outputs that already exist alone; same as --overwrite never
+        #[arg(long, default_value = "false", conflicts_with = "overwrite")]
+        skip_existing: bool,
+
+        /// Delete each source image once its QOIR output has been written
+        #[arg(long, default_value = "false", requires = "recursive")]
+        delete_source: bool,
+
+        /// Print what would be converted without writing or deleting anything
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+
+        /// Don't copy the ICC profile, EXIF and XMP of the input into the QOIR file
+        #[arg(long, default_value = "false")]
+        strip_metadata: bool,
+
+        #[command(flatten)]
+        resize: ResizeArgs,
+    },
+
+    /// Convert many images to QOIR concurrently
+    Batch {
+        /// Glob pattern matching the input images, such as "photos/**/*.jpg", or a
+        /// directory with --recursive
+        #[arg(short, long)]
+        input: String,
+
+        /// Directory for the QOIR files, mirroring the directories matched by the pattern
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Lossiness level (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Apply dithering during lossy compression
+        #[arg(short, long, default_value = "false")]
+        dither: bool,
+
+        /// When to replace an existing output file
+        #[arg(long, value_enum, default_value = "if-newer")]
+        overwrite: Overwrite,
+
+        /// Convert files whose output is already newer than the input; same as
+        /// --overwrite always
+        #[arg(short, long, default_value = "false", conflicts_with = "overwrite")]
+        force: bool,
+
+        /// Convert every image under the input directory instead of matching a pattern
+        #[arg(short, long, default_value = "false")]
+        recursive: bool,
+
+        /// Leave files whose output already exists alone, however old it is; same
+        /// as --overwrite never
+        #[arg(long, default_value = "false", conflicts_with_all = ["overwrite", "force"])]
+        skip_existing: bool,
+
+        /// Delete each source image once its QOIR output has been written
+        #[arg(long, default_value = "false")]
+        delete_source: bool,
+
+        /// Print what would be converted without writing or deleting anything
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+
+        /// Don't copy the ICC profile, EXIF and XMP of the input into the QOIR file
+        #[arg(long, default_value = "false")]
+        strip_metadata: bool,
+    },
+
+    /// Watch a directory and convert images to QOIR as they appear
+    Watch {
+        /// Directory to watch, including its subdirectories
+        #[arg(short, long)]
+        input_dir: PathBuf,
+
+        /// Directory for the QOIR files, mirroring the watched directories
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Lossiness level (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Apply dithering during lossy compression
+        #[arg(short, long, default_value = "false")]
+        dither: bool,
+
+        /// Milliseconds a file must go unchanged before it is converted
+        #[arg(long, default_value = "500")]
+        debounce_ms: u64,
+
+        /// Don't copy the ICC profile, EXIF and XMP of the input into the QOIR file
+        #[arg(long, default_value = "false")]
+        strip_metadata: bool,
+    },
+
+    /// Make a QOIR thumbnail of an image
+    Thumb {
+        /// Input image file (QOIR or any format the image crate reads)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output QOIR file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Largest width or height of the thumbnail, keeping the aspect ratio
+        #[arg(short, long, default_value = "512")]
+        max: u32,
+
+        /// Resampling filter used to shrink the image
+        #[arg(long, value_enum, default_value = "lanczos3")]
+        filter: FilterArg,
+
+        /// Lossiness level (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Apply dithering during lossy compression
+        #[arg(short, long, default_value = "false")]
+        dither: bool,
+
+        /// Also save the thumbnail to this file (use extensions .jpg, .png); can be repeated
+        #[arg(short, long)]
+        also: Vec<PathBuf>,
+    },
+
+    /// Check QOIR files for corruption, exiting with an error if any fail
+    Verify {
+        /// QOIR files to check; - reads standard input
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Only check the chunk and tile headers, without decoding the pixels
+        #[arg(long, default_value = "false")]
+        fast: bool,
+
+        #[command(flatten)]
+        limits: LimitArgs,
+    },
+
+    /// Compare two images (QOIR or any format the image crate reads)
+    Compare {
+        /// First image
+        a: PathBuf,
+
+        /// Second image
+        b: PathBuf,
+
+        /// Metric to report
+        #[arg(short, long, value_enum, default_value = "all")]
+        metric: Metric,
+
+        /// Write a heat map of the per-pixel differences to this file
+        #[arg(short, long)]
+        diff_output: Option<PathBuf>,
+    },
+
+    /// Print a perceptual hash of each image, or list pairs of near-duplicates
+    Hash {
+        /// Images to hash (QOIR or any format the image crate reads)
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Instead of the hashes, list the pairs of images whose hashes differ in at most this many bits
+        #[arg(short, long, value_name = "BITS")]
+        duplicates: Option<u32>,
+    },
+
+    /// Summarize a directory of QOIR files: sizes, resolutions, lossiness and metadata
+    Stats {
+        /// Directory to scan, including its subdirectories
+        dir: PathBuf,
+
+        /// Print the summary as JSON
+        #[arg(long, default_value = "false")]
+        json: bool,
+    },
+
+    /// Find QOIR files in a directory with identical contents, and optionally link or delete the copies
+    Dedupe {
+        /// Directory to scan, including its subdirectories
+        dir: PathBuf,
+
+        /// What makes two files duplicates
+        #[arg(long, value_enum, default_value = "pixels")]
+        by: DedupeBy,
+
+        /// What to do with each duplicate; the first file of each group, by path, is kept
+        #[arg(long, value_enum, default_value = "report")]
+        action: DedupeAction,
+    },
+
+    /// Write an amplified per-pixel difference image and summarize the differences
+    Diff {
+        /// First image
+        a: PathBuf,
+
+        /// Second image
+        b: PathBuf,
+
+        /// Difference image to write (.png, .jpg, .webp, .tiff, .bmp, .ppm or .qoi)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Multiply the differences by this factor, so small errors become visible
+        #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+        amplify: u32,
+    },
+
+    /// Encode an image at every lossiness level and tabulate size, quality and speed
+    Sweep {
+        /// Input image (QOIR or any format the image crate reads)
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+
+    /// Re-encode a QOIR file with the smallest settings that meet a target,
+    /// replacing it only if the result is smaller
+    Optimize {
+        /// QOIR file to re-compress
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Write the result here instead of replacing the input
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Lowest PSNR, compared to the current pixels, to accept, such as 45db
+        #[arg(long, value_name = "DB", value_parser = parse_psnr)]
+        target_quality: Option<f64>,
+
+        /// Largest file size to aim for, such as 500K or 2M
+        #[arg(long, value_name = "SIZE", value_parser = parse_byte_size, conflicts_with = "target_quality")]
+        target_size: Option<u64>,
+
+        /// Print the chosen settings without writing anything
+        #[arg(long, default_value = "false")]
+        dry_run: bool,
+    },
+
+    /// Write embedded profiles and metadata to files without decoding any pixels
+    #[command(group(ArgGroup::new("chunks").required(true).multiple(true)))]
+    Extract {
+        /// Input QOIR file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Write the ICC profile to this file, or - for standard output
+        #[arg(long, group = "chunks")]
+        icc: Option<PathBuf>,
+
+        /// Write the EXIF data to this file, or - for standard output
+        #[arg(long, group = "chunks")]
+        exif: Option<PathBuf>,
+
+        /// Write the XMP data to this file, or - for standard output
+        #[arg(long, group = "chunks")]
+        xmp: Option<PathBuf>,
+
+        /// Write the CICP profile to this file, or - for standard output
+        #[arg(long, group = "chunks")]
+        cicp: Option<PathBuf>,
+    },
+
+    /// Combine images into one multi-frame QOIR animation file
+    Assemble {
+        /// Frames in order: QOIR files, stored without re-encoding, or any
+        /// format the image crate reads
+        #[arg(required = true)]
+        frames: Vec<PathBuf>,
+
+        /// Output animation file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// How long each frame is shown, in milliseconds
+        #[arg(long, default_value = "100")]
+        duration_ms: u32,
+
+        /// Lossiness level for frames that aren't QOIR already (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Apply dithering during lossy compression
+        #[arg(short, long, default_value = "false")]
+        dither: bool,
+
+        /// Don't copy the ICC profile, EXIF and XMP of the first frame into the animation
+        #[arg(long, default_value = "false")]
+        strip_metadata: bool,
+    },
+
+    /// Write each frame of a QOIR animation file to its own image
+    Explode {
+        /// Input animation file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Directory for the frames, named frame-0000, frame-0001 and so on
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Extension of the frame files: qoir copies the frames without decoding
+        /// them; png, jpg, webp, tiff, bmp, ppm or qoi convert them
+        #[arg(short, long, default_value = "qoir")]
+        extension: String,
+    },
+
+    /// Pack many images into one indexed QOIR bundle file
+    Pack {
+        /// Images to pack: QOIR files, stored without re-encoding, or any format
+        /// the image crate reads. Each is named after its file name without the
+        /// extension
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output bundle file
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Lossiness level for images that aren't QOIR already (0-7, where 0 is lossless)
+        #[arg(short, long, default_value = "0")]
+        lossiness: u8,
+
+        /// Apply dithering during lossy compression
+        #[arg(short, long, default_value = "false")]
+        dither: bool,
+    },
+
+    /// Extract images from a QOIR bundle file
+    Unpack {
+        /// Input bundle file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Directory for the images
+        #[arg(short, long)]
+        output_dir: PathBuf,
+
+        /// Only extract the images with these names; all of them by default
+        names: Vec<String>,
+
+        /// Extension of the image files: qoir copies the images without decoding
+        /// them; png, jpg, webp, tiff, bmp, ppm or qoi convert them
+        #[arg(short, long, default_value = "qoir")]
+        extension: String,
+    },
+
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate the script for
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Print the man page, or write one page per subcommand into a directory
+    Man {
+        /// Directory to write qoir-rs.1 and a page for each subcommand into
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+    },
+
+    /// Show an image in the terminal
+    View {
+        /// Input image (QOIR or any format the image crate reads)
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Terminal graphics protocol; auto picks one from the environment
+        #[arg(short, long, value_enum, default_value = "auto")]
+        protocol: ViewProtocol,
+
+        /// Maximum width in terminal columns (defaults to the terminal width)
+        #[arg(short, long)]
+        columns: Option<u32>,
+    },
+
+    /// List, extract, strip or replace the EXIF, ICC and XMP metadata of a QOIR file
+    Metadata {
+        /// Input QOIR file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output QOIR file, required when stripping or setting metadata
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        /// Write the EXIF data to this file
+        #[arg(long)]
+        extract_exif: Option<PathBuf>,
+
+        /// Write the ICC profile to this file
+        #[arg(long)]
+        extract_icc: Option<PathBuf>,
+
+        /// Write the XMP data to this file
+        #[arg(long)]
+        extract_xmp: Option<PathBuf>,
+
+        /// Remove the EXIF data
+        #[arg(long, conflicts_with = "set_exif")]
+        strip_exif: bool,
+
+        /// Remove the ICC profile
+        #[arg(long, conflicts_with = "set_icc")]
+        strip_icc: bool,
+
+        /// Remove the XMP data
+        #[arg(long, conflicts_with = "set_xmp")]
+        strip_xmp: bool,
+
+        /// Replace the EXIF data with the contents of this file
+        #[arg(long)]
+        set_exif: Option<PathBuf>,
+
+        /// Replace the ICC profile with the contents of this file
+        #[arg(long)]
+        set_icc: Option<PathBuf>,
+
+        /// Replace the XMP data with the contents of this file
+        #[arg(long)]
+        set_xmp: Option<PathBuf>,
+    },
+
+    /// Serve a directory of QOIR files over HTTP, with an index page and PNG or
+    /// JPEG previews for browsers (?w=512, ?h=512, ?format=png|jpeg|qoir)
+    Serve {
+        /// Directory to serve, including its subdirectories
+        #[arg(short, long, default_value = ".")]
+        input_dir: PathBuf,
+
+        /// Address and port to listen on; 0.0.0.0 serves other machines too
+        #[arg(short, long, default_value = "127.0.0.1:8080")]
+        listen: String,
+
+        /// Quality level for JPEG previews (1-100)
+        #[arg(short, long, default_value = "85")]
+        quality: u8,
+    },
+
+    /// Print how qoir was built and check that encoding and decoding work on this machine
+    Doctor,
+}
+
+/// Builds the command line definition, for parsing as well as for generating
+/// completions and man pages.
+fn command() -> clap::Command {
+    Cli::command()
+}
+
+fn main() -> ExitCode {
+    let cli = match command().try_get_matches().and_then(|matches| Cli::from_arg_matches(&matches)) {
+        Ok(cli) => cli,
+        // --help and --version
+        Err(e) if !e.use_stderr() => {
+            let _ = e.print();
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            // The arguments didn't parse, so look for --errors json by hand.
+            let args: Vec<String> = std::env::args().collect();
+            let json = args.windows(2).any(|pair| pair[0] == "--errors" && pair[1] == "json")
+                || args.iter().any(|arg| arg == "--errors=json");
+            if json {
+                // Leave out the usage that follows the message.
+                let message = e.to_string();
+                let message: Vec<&str> = message.lines().take_while(|line| !line.is_empty()).map(str::trim).collect();
+                print_error_record(None, ErrorKind::Arguments, message.join(" ").trim_start_matches("error: "));
+            } else {
+                let _ = e.print();
+            }
+            return ExitCode::from(ErrorKind::Arguments.exit_code());
+        }
+    };
+    let _ = ERROR_FORMAT.set(cli.errors);
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let kind = ErrorKind::of(e.as_ref());
+            if json_errors() {
+                print_error_record(None, kind, &e.to_string());
+            } else {
+                eprintln!("Error: {}", e);
+            }
+            ExitCode::from(kind.exit_code())
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    // The library encodes and decodes bands of tiles on this pool too.
+    ThreadPoolBuilder::new()
+        .num_threads(cli.jobs)
+        .thread_name_prefix("qoir")
+        .build_global()?;
+
+    match cli.command {
+        Commands::Decode {
+            input,
+            output,
+            format,
+            crop,
+            offset,
+            tolerant,
+            limits,
+        } => {
+            let (offset_x, offset_y) = offset.unwrap_or_default();
+            let options = DecodeOptions {
+                src_clip_rect: crop,
+                offset_x,
+                offset_y,
+                tolerant,
+                limits: limits.limits_or_defaults(tolerant || input == Path::new("-")),
+                ..Default::default()
+            };
+            decode_command(input, output, &format, options)?
+        }
+        Commands::Encode {
+            input,
+            output,
+            lossiness,
+            dither,
+            colors,
+            checksum,
+            overwrite,
+            dry_run,
+            strip_metadata,
+            resize,
+        } => {
+            if should_write(&input, &output, overwrite, dry_run) {
+                let options = EncodeOptions {
+                    lossiness,
+                    dither,
+                    embed_checksum: checksum,
+                    quantize: colors.map(|max_colors| QuantizeOptions { max_colors, dither }),
+                    ..Default::default()
+                };
+                encode_command(input, output, options, strip_metadata, &resize)?
+            }
+        }
+        Commands::EncodeRaw {
+            input,
+            output,
+            width,
+            height,
+            format,
+            stride,
+            lossiness,
+            dither,
+        } => {
+            let options = EncodeOptions {
+                lossiness,
+                dither,
+                ..Default::default()
+            };
+            let stride = stride.unwrap_or(width as usize * format.bytes_per_pixel());
+            encode_raw_command(&input, &output, (width, height), format, stride, options)?
+        }
+        Commands::Info {
+            input,
+            format,
+            json,
+            limits,
+        } => match if json { InfoFormat::Json } else { format } {
+            InfoFormat::Text => info_command(&input, limits.limits(&input))?,
+            InfoFormat::Json => println!("{}", serde_json::to_string_pretty(&info_report(&input)?)?),
+            InfoFormat::Yaml => print!("{}", serde_yaml_ng::to_string(&info_report(&input)?)?),
+        },
+        Commands::Convert {
+            input,
+            output,
+            quality,
+            lossiness,
+            recursive,
+            overwrite,
+            skip_existing,
+            delete_source,
+            dry_run,
+            strip_metadata,
+            resize,
+        } => {
+            let overwrite = if skip_existing { Overwrite::Never } else { overwrite };
+            if recursive && resize.requested() {
+                let message = "--resize, --max-dimension, --rotate and --flip can't be combined with --recursive";
+                return Err(CliError::new(ErrorKind::Arguments, message).into());
+            } else if recursive {
+                let options = EncodeOptions {
+                    lossiness,
+                    ..Default::default()
+                };
+                let policy = BatchPolicy {
+                    overwrite,
+                    delete_source,
+                    dry_run,
+                    strip_metadata,
+                };
+                let inputs = find_images(&input)?;
+                batch_command(&inputs, &input, &output, options, policy)?
+            } else if should_write(&input, &output, overwrite, dry_run) {
+                convert_command(input, output, quality, lossiness, strip_metadata, &resize)?
+            }
+        }
+        Commands::Batch {
+            input,
+            output_dir,
+            lossiness,
+            dither,
+            overwrite,
+            force,
+            recursive,
+            skip_existing,
+            delete_source,
+            dry_run,
+            strip_metadata,
+        } => {
+            let options = EncodeOptions {
+                lossiness,
+                dither,
+                ..Default::default()
+            };
+            let overwrite = match (force, skip_existing) {
+                (true, _) => Overwrite::Always,
+                (_, true) => Overwrite::Never,
+                _ => overwrite,
+            };
+            let policy = BatchPolicy {
+                overwrite,
+                delete_source,
+                dry_run,
+                strip_metadata,
+            };
+            let (inputs, base) = if recursive {
+                let base = PathBuf::from(input);
+                (find_images(&base)?, base)
+            } else {
+                (glob_inputs(&input)?, glob_base(&input))
+            };
+            batch_command(&inputs, &base, &output_dir, options, policy)?
+        }
+        Commands::Watch {
+            input_dir,
+            output_dir,
+            lossiness,
+            dither,
+            debounce_ms,
+            strip_metadata,
+        } => {
+            let options = EncodeOptions {
+                lossiness,
+                dither,
+                ..Default::default()
+            };
+            let debounce = Duration::from_millis(debounce_ms);
+            watch_command(&input_dir, &output_dir, options, debounce, strip_metadata)?
+        }
+        Commands::Thumb {
+            input,
+            output,
+            max,
+            filter,
+            lossiness,
+            dither,
+            also,
+        } => {
+            let options = EncodeOptions {
+                lossiness,
+                dither,
+                ..Default::default()
+            };
+            thumb_command(&input, &output, max, filter.into(), options, &also)?
+        }
+        Commands::Verify { files, fast, limits } => verify_command(&files, fast, &limits)?,
+        Commands::Compare {
+            a,
+            b,
+            metric,
+            diff_output,
+        } => compare_command(&a, &b, metric, diff_output.as_deref())?,
+        Commands::Hash { files, duplicates } => hash_command(&files, duplicates)?,
+        Commands::Stats { dir, json } => stats_command(&dir, json)?,
+        Commands::Dedupe { dir, by, action } => dedupe_command(&dir, by, action)?,
+        Commands::Diff {
+            a,
+            b,
+            output,
+            amplify,
+        } => diff_command(&a, &b, output.as_deref(), amplify)?,
+        Commands::Sweep { input } => sweep_command(&input)?,
+        Commands::Optimize {
+            input,
+            output,
+            target_quality,
+            target_size,
+            dry_run,
+        } => {
+            let target = match (target_quality, target_size) {
+                (Some(psnr), _) => OptimizeTarget::Quality(psnr),
+                (_, Some(size)) => OptimizeTarget::Size(size),
+                _ => OptimizeTarget::Lossless,
+            };
+            optimize_command(&input, output.as_deref().unwrap_or(&input), target, dry_run)?
+        }
+        Commands::Extract {
+            input,
+            icc,
+            exif,
+            xmp,
+            cicp,
+        } => extract_command(&input, [icc, exif, xmp, cicp])?,
+        Commands::Assemble {
+            frames,
+            output,
+            duration_ms,
+            lossiness,
+            dither,
+            strip_metadata,
+        } => {
+            let options = EncodeOptions {
+                lossiness,
+                dither,
+                ..Default::default()
+            };
+            assemble_command(&frames, &output, duration_ms, options, strip_metadata)?
+        }
+        Commands::Explode {
+            input,
+            output_dir,
+            extension,
+        } => explode_command(&input, &output_dir, &extension)?,
+        Commands::Pack {
+            inputs,
+            output,
+            lossiness,
+            dither,
+        } => {
+            let options = EncodeOptions {
+                lossiness,
+                dither,
+                ..Default::default()
+            };
+            pack_command(&inputs, &output, options)?
+        }
+        Commands::Unpack {
+            input,
+            output_dir,
+            names,
+            extension,
+        } => unpack_command(&input, &output_dir, &names, &extension)?,
+        Commands::Completions { shell } => {
+            let mut command = command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+        }
+        Commands::Man { output_dir } => man_command(output_dir.as_deref())?,
+        Commands::View {
+            input,
+            protocol,
+            columns,
+        } => view_command(&input, protocol, columns)?,
+        Commands::Metadata {
+            input,
+            output,
+            extract_exif,
+            extract_icc,
+            extract_xmp,
+            strip_exif,
+            strip_icc,
+            strip_xmp,
+            set_exif,
+            set_icc,
+            set_xmp,
+        } => metadata_command(
+            &input,
+            output.as_deref(),
+            [extract_exif, extract_icc, extract_xmp],
+            [strip_exif, strip_icc, strip_xmp],
+            [set_exif, set_icc, set_xmp],
+        )?,
+        Commands::Serve {
+            input_dir,
+            listen,
+            quality,
+        } => serve_command(&input_dir, &listen, quality)?,
+        Commands::Doctor => doctor_command()?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use std::path::Path;
+use std::fs::File;
+use crate::command;
+
+pub fn man_command(output_dir: Option<&Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let command = command();
+    let Some(output_dir) = output_dir else {
+        clap_mangen::Man::new(command).render(&mut std::io::stdout())?;
+        return Ok(());
+    };
+
+    std::fs::create_dir_all(output_dir)?;
+    let name = command.get_name().to_string();
+    let mut pages = vec![(name.clone(), command.clone())];
+    pages.extend(
+        command
+            .get_subcommands()
+            .filter(|subcommand| subcommand.get_name() != "help")
+            .map(|subcommand| {
+                // Name each page after how the subcommand is invoked.
+                let page_name = format!("{}-{}", name, subcommand.get_name());
+                let subcommand = subcommand
+                    .clone()
+                    .display_name(page_name.clone())
+                    .version(env!("CARGO_PKG_VERSION"))
+                    .bin_name(format!("{} {}", name, subcommand.get_name()));
+                (page_name, subcommand)
+            }),
+    );
+    for (page_name, page) in pages {
+        let path = output_dir.join(format!("{page_name}.1"));
+        clap_mangen::Man::new(page).render(&mut File::create(&path)?)?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use qoir_rs::{read_metadata, rewrite_metadata, MetadataChange, MetadataEdit};
+use std::path::{Path, PathBuf};
+use crate::errors::{CliError, ErrorKind};
+use crate::io::format_bytes;
+
+/// Runs the `metadata` command. The extract, strip and set arguments are
+/// given for EXIF, ICC and XMP, in that order.
+pub fn metadata_command(
+    input: &Path,
+    output: Option<&Path>,
+    extract: [Option<PathBuf>; 3],
+    strip: [bool; 3],
+    set: [Option<PathBuf>; 3],
+) -> Result<(), Box<dyn std::error::Error>> {
+    const NAMES: [&str; 3] = ["EXIF data", "ICC profile", "XMP data"];
+
+    let data = std::fs::read(input)?;
+    let metadata = read_metadata(&data)?;
+    let current = [metadata.exif, metadata.icc_profile, metadata.xmp];
+
+    println!("QOIR File: {}", input.display());
+    if let Some(cicp) = metadata.cic_profile {
+        println!("CICP profile: {}", format_bytes(cicp.len()));
+    }
+    for (name, payload) in NAMES.iter().zip(current) {
+        match payload {
+            Some(payload) => println!("{}: {}", name, format_bytes(payload.len())),
+            None => println!("{}: none", name),
+        }
+    }
+
+    for ((name, payload), path) in NAMES.iter().zip(current).zip(extract) {
+        if let Some(path) = path {
+            let payload = payload.ok_or_else(|| format!("The file has no {}", name))?;
+            std::fs::write(&path, payload)?;
+            println!("Extracted {} to: {}", name, path.display());
+        }
+    }
+
+    let set = set
+        .map(|path| path.map(std::fs::read).transpose())
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    let changes: Vec<MetadataChange> = strip
+        .iter()
+        .zip(&set)
+        .map(|(&strip, payload)| match payload {
+            Some(payload) => MetadataChange::Set(payload),
+            None if strip => MetadataChange::Remove,
+            None => MetadataChange::Keep,
+        })
+        .collect();
+    if changes.iter().all(|change| *change == MetadataChange::Keep) {
+        return Ok(());
+    }
+
+    let output =
+        output.ok_or_else(|| CliError::new(ErrorKind::Arguments, "--output is required to strip or set metadata"))?;
+    let edit = MetadataEdit {
+        exif: changes[0],
+        icc_profile: changes[1],
+        xmp: changes[2],
+        ..Default::default()
+    };
+    let rewritten = rewrite_metadata(&data, &edit)?;
+    std::fs::write(output, &rewritten)?;
+    println!(
+        "Metadata rewritten to: {} ({})",
+        output.display(),
+        format_bytes(rewritten.len())
+    );
+    Ok(())
+}
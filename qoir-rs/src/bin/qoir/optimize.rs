@@ -0,0 +1,132 @@
+use image::RgbaImage;
+use qoir_rs::{
+    decode_basic_metadata, decode_from_memory, encode_to_memory, verify_integrity, DecodeOptions, EncodeOptions, PixelFormat
+};
+use std::path::Path;
+use crate::io::format_bytes;
+use crate::compare::psnr;
+use crate::sweep::encode_settings;
+
+/// What `optimize` looks for among the encode settings.
+#[derive(Clone, Copy)]
+pub enum OptimizeTarget {
+    /// The smallest lossless encoding
+    Lossless,
+    /// The smallest encoding with at least this PSNR
+    Quality(f64),
+    /// The highest PSNR encoding no larger than this many bytes
+    Size(u64),
+}
+
+pub fn optimize_command(
+    input: &Path,
+    output: &Path,
+    target: OptimizeTarget,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let data = std::fs::read(input)?;
+    // Re-encode in the stored pixel format, so opaque images stay opaque.
+    let (_, _, pixel_format) = decode_basic_metadata(&data)?;
+    let options = DecodeOptions {
+        pixel_format,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&data, options)?;
+    let image = &decoded.image;
+    let original = RgbaImage::from_raw(
+        image.width,
+        image.height,
+        image.to_pixel_format(PixelFormat::RGBANonPremul)?,
+    )
+    .ok_or("Invalid pixel buffer")?;
+
+    // A checksummed file stays checksummed.
+    let embed_checksum = verify_integrity(&data).is_ok();
+
+    // Keep the best encoding that meets the target: (data, lossiness, dither, PSNR).
+    let mut best: Option<(Vec<u8>, u8, bool, f64)> = None;
+    for (lossiness, dither) in encode_settings() {
+        if matches!(target, OptimizeTarget::Lossless) && lossiness > 0 {
+            break;
+        }
+        let options = EncodeOptions {
+            cicp_profile: decoded.cic_profile.map(<[u8]>::to_vec),
+            icc_profile: decoded.icc_profile.map(<[u8]>::to_vec),
+            exif: decoded.exif.map(<[u8]>::to_vec),
+            xmp: decoded.xmp.map(<[u8]>::to_vec),
+            custom_metadata: decoded.custom_metadata.clone(),
+            lossiness,
+            dither,
+            embed_checksum,
+            quantize: None,
+            collect_timings: false,
+            embed_thumbnail: None,
+        };
+        let encoded = encode_to_memory(image.clone(), options)?;
+        let psnr = if lossiness == 0 {
+            f64::INFINITY
+        } else {
+            let roundtrip = decode_from_memory(encoded.data, DecodeOptions::default())?;
+            let pixels = roundtrip.image.to_pixel_format(PixelFormat::RGBANonPremul)?;
+            let roundtrip = RgbaImage::from_raw(image.width, image.height, pixels).ok_or("Invalid pixel buffer")?;
+            psnr(&original, &roundtrip)
+        };
+        let size = encoded.data.len();
+
+        let better = match (target, &best) {
+            (OptimizeTarget::Quality(min_psnr), _) if psnr < min_psnr => false,
+            (OptimizeTarget::Size(max_size), _) if size as u64 > max_size => false,
+            (_, None) => true,
+            (OptimizeTarget::Size(_), Some((data, _, _, best_psnr))) => {
+                psnr > *best_psnr || (psnr == *best_psnr && size < data.len())
+            }
+            (_, Some((data, ..))) => size < data.len(),
+        };
+        if better {
+            best = Some((encoded.data.to_vec(), lossiness, dither, psnr));
+        }
+    }
+
+    let Some((encoded, lossiness, dither, psnr)) = best else {
+        let target = match target {
+            OptimizeTarget::Size(max_size) => format!("{} or smaller", format_bytes(max_size as usize)),
+            _ => "the target".to_string(),
+        };
+        return Err(format!("No lossiness level makes {} {}", input.display(), target).into());
+    };
+    let settings = format!(
+        "lossiness {}{}, PSNR {}",
+        lossiness,
+        if dither { " with dither" } else { "" },
+        if psnr.is_infinite() { "inf".to_string() } else { format!("{:.2} dB", psnr) }
+    );
+    if encoded.len() >= data.len() {
+        println!(
+            "Kept {}: {} already, {} with {}",
+            input.display(),
+            format_bytes(data.len()),
+            format_bytes(encoded.len()),
+            settings
+        );
+        return Ok(());
+    }
+
+    let summary = format!(
+        "{} -> {} ({}, {:.1}% smaller)",
+        format_bytes(data.len()),
+        format_bytes(encoded.len()),
+        settings,
+        100.0 * (1.0 - encoded.len() as f64 / data.len() as f64)
+    );
+    if dry_run {
+        println!("Would write {}: {}", output.display(), summary);
+        return Ok(());
+    }
+    // Write to a temporary file first, so that a failure never leaves the
+    // original truncated.
+    let partial = output.with_extension("qoir.partial");
+    std::fs::write(&partial, &encoded)?;
+    std::fs::rename(&partial, output)?;
+    println!("Optimized {}: {}", output.display(), summary);
+    Ok(())
+}
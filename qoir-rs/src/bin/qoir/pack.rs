@@ -0,0 +1,36 @@
+use qoir_rs::{encode_to_memory, EncodeOptions};
+use qoir_rs::bundle::BundleWriter;
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use crate::errors::{CliError, ErrorKind};
+use crate::io::{format_bytes, load_image, rgba_image};
+
+pub fn pack_command(inputs: &[PathBuf], output: &Path, options: EncodeOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let file = File::create(output)?;
+    let mut writer = BundleWriter::new(std::io::BufWriter::new(file))?;
+    for path in inputs {
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+        if name.is_empty() || writer.contains(name) {
+            let message = format!("{} has an empty or duplicate name", path.display());
+            return Err(CliError::new(ErrorKind::Arguments, message).into());
+        }
+
+        let data = std::fs::read(path)?;
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if ext.eq_ignore_ascii_case("qoir") {
+            writer.add(name, &data)?;
+        } else {
+            let rgba_img = load_image(path, &data)?.to_rgba8();
+            writer.add(name, encode_to_memory(rgba_image(&rgba_img), options.clone())?.data)?;
+        }
+    }
+    writer.finish()?;
+
+    println!(
+        "Packed {} images into: {} ({})",
+        inputs.len(),
+        output.display(),
+        format_bytes(std::fs::metadata(output)?.len() as usize)
+    );
+    Ok(())
+}
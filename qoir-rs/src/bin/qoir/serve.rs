@@ -0,0 +1,262 @@
+use image::ImageFormat;
+use qoir_rs::{decode_from_memory, encode_to_vec, DecodeOptions, EncodeOptions, PixelFormat, ResizeFilter, ResizeMode};
+use std::path::{Component, Path, PathBuf};
+use crate::errors::{CliError, ErrorKind};
+use crate::io::{jpeg_data, to_dynamic_image};
+
+/// What `serve` sends a QOIR file as, from the `format` query parameter.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PreviewFormat {
+    Png,
+    Jpeg,
+    Qoir,
+}
+
+/// The query of a `serve` request for a QOIR file: `w` and `h` bound the size
+/// of the preview, and `format` picks what it is sent as.
+pub struct PreviewQuery {
+    max_width: Option<u32>,
+    max_height: Option<u32>,
+    format: PreviewFormat,
+}
+
+impl PreviewQuery {
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut parsed = PreviewQuery {
+            max_width: None,
+            max_height: None,
+            format: PreviewFormat::Png,
+        };
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let size = || match value.parse() {
+                Ok(0) | Err(_) => Err(format!("{} must be a positive integer, got {:?}", key, value)),
+                Ok(size) => Ok(Some(size)),
+            };
+            match key {
+                "w" => parsed.max_width = size()?,
+                "h" => parsed.max_height = size()?,
+                "format" => {
+                    parsed.format = match value {
+                        "png" => PreviewFormat::Png,
+                        "jpg" | "jpeg" => PreviewFormat::Jpeg,
+                        "qoir" => PreviewFormat::Qoir,
+                        _ => return Err(format!("Unsupported format: {}", value)),
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// The status code, content type and body of a `serve` response.
+pub type ServeResponse = (u16, &'static str, Vec<u8>);
+
+pub fn serve_command(input_dir: &Path, listen: &str, quality: u8) -> Result<(), Box<dyn std::error::Error>> {
+    let root = input_dir.canonicalize()?;
+    if !root.is_dir() {
+        return Err(CliError::new(ErrorKind::Arguments, format!("Not a directory: {}", input_dir.display())).into());
+    }
+    let server = tiny_http::Server::http(listen)
+        .map_err(|e| CliError::new(ErrorKind::Io, format!("Failed to listen on {}: {}", listen, e)))?;
+    println!("Serving {} at http://{}/", root.display(), listen);
+
+    // Decoding and resizing take a while, so requests are answered on the
+    // rayon thread pool.
+    let root = std::sync::Arc::new(root);
+    for request in server.incoming_requests() {
+        let root = root.clone();
+        rayon::spawn(move || {
+            let (status, content_type, body) = match request.method() {
+                tiny_http::Method::Get | tiny_http::Method::Head => serve_request(&root, request.url(), quality),
+                _ => (405, "text/plain; charset=utf-8", b"Method not allowed".to_vec()),
+            };
+            eprintln!("{} {} {}", request.method(), request.url(), status);
+            let header = tiny_http::Header::from_bytes("Content-Type", content_type).expect("Valid header");
+            let response = tiny_http::Response::from_data(body)
+                .with_status_code(status)
+                .with_header(header);
+            // The client may have gone away, which is no reason to stop serving.
+            let _ = request.respond(response);
+        });
+    }
+    Ok(())
+}
+
+/// Answers a `serve` request: an index page for a directory, or a preview of a
+/// QOIR file.
+pub fn serve_request(root: &Path, url: &str, quality: u8) -> ServeResponse {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let not_found = (404, "text/plain; charset=utf-8", b"Not found".to_vec());
+    // Symbolic links may point outside the served directory.
+    let Some(full) = url_path(path)
+        .and_then(|relative| root.join(relative).canonicalize().ok())
+        .filter(|full| full.starts_with(root))
+    else {
+        return not_found;
+    };
+
+    let response: Result<ServeResponse, Box<dyn std::error::Error>> = if full.is_dir() {
+        index_page(root, &full)
+            .map(|html| (200, "text/html; charset=utf-8", html.into_bytes()))
+            .map_err(Into::into)
+    } else if full.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("qoir")) {
+        match PreviewQuery::parse(query) {
+            Ok(query) => preview_response(&full, &query, quality),
+            Err(message) => return (400, "text/plain; charset=utf-8", message.into_bytes()),
+        }
+    } else {
+        return not_found;
+    };
+    response.unwrap_or_else(|e| (500, "text/plain; charset=utf-8", e.to_string().into_bytes()))
+}
+
+/// Decodes a QOIR file, shrinks it to fit the query's bounds and encodes it in
+/// the query's format. Images are never enlarged.
+pub fn preview_response(
+    path: &Path,
+    query: &PreviewQuery,
+    quality: u8,
+) -> Result<ServeResponse, Box<dyn std::error::Error>> {
+    let data = std::fs::read(path)?;
+    if query.format == PreviewFormat::Qoir && query.max_width.is_none() && query.max_height.is_none() {
+        return Ok((200, "image/x-qoir", data));
+    }
+
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGBANonPremul,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&data, options)?;
+    let (width, height) = (decoded.image.width, decoded.image.height);
+    let max_width = query.max_width.map_or(width, |max| max.min(width));
+    let max_height = query.max_height.map_or(height, |max| max.min(height));
+    let resized;
+    let image = if (max_width, max_height) != (width, height) {
+        resized = decoded
+            .image
+            .resize(max_width, max_height, ResizeMode::Fit, ResizeFilter::CatmullRom)?;
+        resized.as_image()
+    } else {
+        decoded.image.clone()
+    };
+
+    Ok(match query.format {
+        PreviewFormat::Png => {
+            let mut png = std::io::Cursor::new(Vec::new());
+            to_dynamic_image(&image)?.write_to(&mut png, ImageFormat::Png)?;
+            (200, "image/png", png.into_inner())
+        }
+        PreviewFormat::Jpeg => (200, "image/jpeg", jpeg_data(&to_dynamic_image(&image)?, quality)?),
+        PreviewFormat::Qoir => (200, "image/x-qoir", encode_to_vec(image, EncodeOptions::default())?),
+    })
+}
+
+/// Lists the subdirectories and QOIR files of `dir` as an HTML page of
+/// thumbnails.
+pub fn index_page(root: &Path, dir: &Path) -> std::io::Result<String> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        if path.is_dir() {
+            dirs.push(name.to_string());
+        } else if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("qoir")) {
+            files.push(name.to_string());
+        }
+    }
+    dirs.sort();
+    files.sort();
+
+    // Links are absolute, so they work whether or not the URL ends in a slash.
+    let relative = dir.strip_prefix(root).unwrap_or(Path::new(""));
+    let mut base = String::from("/");
+    for component in relative.components() {
+        base.push_str(&percent_encode(&component.as_os_str().to_string_lossy()));
+        base.push('/');
+    }
+    let title = html_escape(&base);
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>\n\
+         body {{ font-family: sans-serif; background: #1e1e1e; color: #ddd; }}\n\
+         a {{ color: #8cf; }}\n\
+         figure {{ display: inline-block; margin: 8px; width: 256px; text-align: center; vertical-align: top; }}\n\
+         img {{ max-width: 256px; max-height: 256px; }}\n\
+         figcaption {{ font-size: small; overflow-wrap: anywhere; }}\n\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n<ul>\n"
+    );
+    if !relative.as_os_str().is_empty() {
+        html.push_str("<li><a href=\"..\">..</a></li>\n");
+    }
+    for name in &dirs {
+        let url = format!("{}{}/", base, percent_encode(name));
+        html.push_str(&format!("<li><a href=\"{}\">{}/</a></li>\n", html_escape(&url), html_escape(name)));
+    }
+    html.push_str("</ul>\n");
+    for name in &files {
+        let url = html_escape(&format!("{}{}", base, percent_encode(name)));
+        html.push_str(&format!(
+            "<figure><a href=\"{url}?format=png\"><img src=\"{url}?w=256&amp;h=256&amp;format=jpeg\" loading=\"lazy\" alt=\"\"></a>\
+             <figcaption>{}</figcaption></figure>\n",
+            html_escape(name)
+        ));
+    }
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+/// Maps the path of a URL to a path relative to the served directory, or
+/// `None` if it has `..` or other components that could leave it.
+pub fn url_path(path: &str) -> Option<PathBuf> {
+    let decoded = percent_decode(path)?;
+    let mut relative = PathBuf::new();
+    for part in decoded.split('/').filter(|part| !part.is_empty()) {
+        match Path::new(part).components().collect::<Vec<_>>()[..] {
+            [Component::Normal(name)] => relative.push(name),
+            _ => return None,
+        }
+    }
+    Some(relative)
+}
+
+pub fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+pub fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
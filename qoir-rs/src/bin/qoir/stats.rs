@@ -0,0 +1,140 @@
+use qoir_rs::{read_custom_metadata, read_metadata, inspect};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::path::Path;
+use std::collections::BTreeMap;
+use crate::errors::BatchError;
+use crate::io::{collect_files, format_bytes};
+use crate::batch::in_thread_pool;
+
+/// The output of `stats`.
+#[derive(Serialize)]
+pub struct StatsReport {
+    count: usize,
+    total_bytes: u64,
+    average_bytes: u64,
+    /// The number of files of each size, keyed by `WIDTHxHEIGHT`.
+    resolutions: BTreeMap<String, usize>,
+    /// The number of files at each lossiness level, 0 being lossless.
+    lossiness: BTreeMap<u8, usize>,
+    /// The percentage of files that have each kind of metadata.
+    metadata: MetadataPresence,
+    /// Files with a `.qoir` extension that could not be parsed.
+    unreadable: usize,
+}
+
+#[derive(Serialize, Default)]
+pub struct MetadataPresence {
+    cicp: f64,
+    icc: f64,
+    exif: f64,
+    xmp: f64,
+    custom: f64,
+}
+
+/// What `stats` learns from one file.
+pub struct FileStats {
+    len: u64,
+    width: u32,
+    height: u32,
+    lossiness: u8,
+    /// Whether the file has CICP, ICC, EXIF, XMP and custom metadata.
+    metadata: [bool; 5],
+}
+
+pub fn stats_command(dir: &Path, json: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    collect_files(dir, &mut files)?;
+    files.retain(|path| path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("qoir")));
+    files.sort();
+
+    let results: Vec<Result<FileStats, BatchError>> = in_thread_pool(|| files.par_iter().map(|path| file_stats(path)).collect());
+
+    let mut report = StatsReport {
+        count: 0,
+        total_bytes: 0,
+        average_bytes: 0,
+        resolutions: BTreeMap::new(),
+        lossiness: BTreeMap::new(),
+        metadata: MetadataPresence::default(),
+        unreadable: 0,
+    };
+    let mut with_metadata = [0usize; 5];
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(stats) => {
+                report.count += 1;
+                report.total_bytes += stats.len;
+                *report.resolutions.entry(format!("{}x{}", stats.width, stats.height)).or_default() += 1;
+                *report.lossiness.entry(stats.lossiness).or_default() += 1;
+                for (count, present) in with_metadata.iter_mut().zip(stats.metadata) {
+                    *count += present as usize;
+                }
+            }
+            Err(e) => {
+                eprintln!("Warning: {}: {}", path.display(), e);
+                report.unreadable += 1;
+            }
+        }
+    }
+    if report.count > 0 {
+        report.average_bytes = report.total_bytes / report.count as u64;
+        let percent = |n: usize| n as f64 * 100.0 / report.count as f64;
+        let [cicp, icc, exif, xmp, custom] = with_metadata;
+        report.metadata = MetadataPresence {
+            cicp: percent(cicp),
+            icc: percent(icc),
+            exif: percent(exif),
+            xmp: percent(xmp),
+            custom: percent(custom),
+        };
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    println!("Files: {}", report.count);
+    if report.unreadable > 0 {
+        println!("Unreadable: {}", report.unreadable);
+    }
+    println!("Total Size: {}", format_bytes(report.total_bytes as usize));
+    println!("Average Size: {}", format_bytes(report.average_bytes as usize));
+    println!("Resolutions:");
+    let mut resolutions: Vec<_> = report.resolutions.iter().collect();
+    resolutions.sort_by(|a, b| b.1.cmp(a.1));
+    for (resolution, count) in resolutions {
+        println!("  {:>12}  {}", resolution, count);
+    }
+    println!("Lossiness:");
+    for (level, count) in &report.lossiness {
+        println!("  {:>12}  {}", level, count);
+    }
+    println!("Metadata:");
+    let metadata = &report.metadata;
+    for (name, percent) in [("CICP", metadata.cicp), ("ICC", metadata.icc), ("EXIF", metadata.exif), ("XMP", metadata.xmp), ("Custom", metadata.custom)] {
+        println!("  {:>12}  {:.1}%", name, percent);
+    }
+    Ok(())
+}
+
+/// Reads the header and metadata of a QOIR file, without decoding its pixels.
+pub fn file_stats(path: &Path) -> Result<FileStats, BatchError> {
+    let data = std::fs::read(path)?;
+    let layout = inspect(&data)?;
+    let metadata = read_metadata(&data)?;
+    let custom = !read_custom_metadata(&data)?.is_empty();
+    Ok(FileStats {
+        len: data.len() as u64,
+        width: layout.width,
+        height: layout.height,
+        lossiness: layout.lossiness,
+        metadata: [
+            metadata.cic_profile.is_some(),
+            metadata.icc_profile.is_some(),
+            metadata.exif.is_some(),
+            metadata.xmp.is_some(),
+            custom,
+        ],
+    })
+}
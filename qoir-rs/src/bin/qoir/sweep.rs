@@ -0,0 +1,76 @@
+use image::RgbaImage;
+use qoir_rs::{decode_from_memory, encode_to_memory, DecodeOptions, EncodeOptions, Image, PixelFormat};
+use rayon::prelude::*;
+use std::path::Path;
+use std::time::Instant;
+use crate::io::{format_bytes, load_rgba};
+use crate::batch::in_thread_pool;
+use crate::compare::{psnr, ssim};
+
+/// Encodes `input` at lossiness 0 to 7, with and without dithering, decodes
+/// each result back and prints one row per setting.
+pub fn sweep_command(input: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let original = load_rgba(input)?;
+    let (width, height) = original.dimensions();
+    let image = Image {
+        pixels: original.as_raw(),
+        width,
+        height,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: width as usize * 4,
+    };
+    let pixel_count = (width as f64 * height as f64).max(1.0);
+
+    println!(
+        "{:>9}  {:>6}  {:>12}  {:>7}  {:>9}  {:>7}  {:>10}",
+        "Lossiness", "Dither", "Size", "Bits/px", "PSNR (dB)", "SSIM", "Encode"
+    );
+    // Encode times are only comparable to each other with --jobs 1, as the
+    // settings are tried in parallel.
+    let settings: Vec<(u8, bool)> = encode_settings().collect();
+    let rows: Vec<Result<String, qoir_rs::Error>> = in_thread_pool(|| {
+        settings
+            .par_iter()
+            .map(|&(lossiness, dither)| {
+                let options = EncodeOptions {
+                    lossiness,
+                    dither,
+                    ..Default::default()
+                };
+                let start = Instant::now();
+                let encoded = encode_to_memory(image.clone(), options)?;
+                let encode_time = start.elapsed();
+
+                let decoded = decode_from_memory(encoded.data, DecodeOptions::default())?;
+                let pixels = decoded.image.to_pixel_format(PixelFormat::RGBANonPremul)?;
+                let roundtrip =
+                    RgbaImage::from_raw(width, height, pixels).ok_or(qoir_rs::Error::InvalidParameter)?;
+                let psnr = psnr(&original, &roundtrip);
+
+                Ok(format!(
+                    "{:>9}  {:>6}  {:>12}  {:>7.3}  {:>9}  {:>7.5}  {:>10}",
+                    lossiness,
+                    if dither { "yes" } else { "no" },
+                    format_bytes(encoded.data.len()),
+                    encoded.data.len() as f64 * 8.0 / pixel_count,
+                    if psnr.is_infinite() { "inf".to_string() } else { format!("{:.3}", psnr) },
+                    ssim(&original, &roundtrip),
+                    format!("{:.2?}", encode_time)
+                ))
+            })
+            .collect()
+    });
+    for row in rows {
+        println!("{}", row?);
+    }
+
+    Ok(())
+}
+
+/// Draws `input` in the terminal, scaled down to fit its width and height.
+/// Every distinct lossiness and dither combination, from lossless to the
+/// lossiest. Dithering has no effect on lossless encoding.
+pub fn encode_settings() -> impl Iterator<Item = (u8, bool)> {
+    std::iter::once((0, false))
+        .chain((1..=7).flat_map(|lossiness| [(lossiness, false), (lossiness, true)]))
+}
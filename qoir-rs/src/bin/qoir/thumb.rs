@@ -0,0 +1,97 @@
+use image::RgbaImage;
+use qoir_rs::{
+    decode_basic_metadata, decode_from_memory, encode, extract_thumbnail, DecodeOptions, EncodeOptions, Image, PixelFormat, ResizeFilter, ResizeMode
+};
+use std::path::{Path, PathBuf};
+use crate::errors::{CliError, ErrorKind};
+use crate::io::{format_bytes, load_rgba, rgba_image};
+use crate::view::fit_within;
+
+pub fn thumb_command(
+    input: &Path,
+    output: &Path,
+    max: u32,
+    filter: ResizeFilter,
+    options: EncodeOptions,
+    also: &[PathBuf],
+) -> Result<(), Box<dyn std::error::Error>> {
+    if max == 0 {
+        return Err(CliError::new(ErrorKind::Arguments, "--max must be at least 1").into());
+    }
+
+    for path in also {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !["jpg", "jpeg", "png"].contains(&ext.to_lowercase().as_str()) {
+            return Err(CliError::new(ErrorKind::Arguments, format!("Unsupported output format: {}", ext)).into());
+        }
+    }
+
+    let full = load_thumbnail_source(input, max)?;
+    let (width, height) = full.dimensions();
+    let thumb = if width > max || height > max {
+        let resized = rgba_image(&full).resize(max, max, ResizeMode::Fit, filter)?;
+        RgbaImage::from_raw(resized.width, resized.height, resized.pixels).ok_or("Failed to resize")?
+    } else {
+        full
+    };
+    let (width, height) = thumb.dimensions();
+
+    let image = Image {
+        pixels: thumb.as_raw(),
+        width,
+        height,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: (width * 4) as usize,
+    };
+    let encoded = encode(image, options, output)?;
+    println!(
+        "Thumbnail saved to: {} ({}x{}, {})",
+        output.display(),
+        width,
+        height,
+        format_bytes(encoded.data.len())
+    );
+
+    for path in also {
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        match ext.to_lowercase().as_str() {
+            // JPEG has no alpha channel.
+            "jpg" | "jpeg" => image::DynamicImage::ImageRgba8(thumb.clone())
+                .to_rgb8()
+                .save_with_format(path, image::ImageFormat::Jpeg)?,
+            _ => thumb.save_with_format(path, image::ImageFormat::Png)?,
+        }
+        println!("Thumbnail saved to: {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Loads the image to scale down to at most `max` by `max` pixels. A QOIR file's
+/// embedded thumbnail is decoded instead of the image when it is at least as
+/// large as the result, so the image's own pixels are never decoded.
+pub fn load_thumbnail_source(input: &Path, max: u32) -> Result<RgbaImage, Box<dyn std::error::Error>> {
+    let ext = input.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if !ext.eq_ignore_ascii_case("qoir") {
+        return load_rgba(input);
+    }
+
+    let data = std::fs::read(input)?;
+    let (width, height, _) = decode_basic_metadata(&data)?;
+    let (thumb_width, thumb_height) = fit_within((width, height), (max, max));
+    let mut source = &data[..];
+    if let Some(thumbnail) = extract_thumbnail(&data)? {
+        let (width, height, _) = decode_basic_metadata(thumbnail)?;
+        if width >= thumb_width && height >= thumb_height {
+            source = thumbnail;
+        }
+    }
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGBANonPremul,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(source, options)?;
+    let image = &decoded.image;
+    let pixels = image.to_pixel_format(PixelFormat::RGBANonPremul)?;
+    RgbaImage::from_raw(image.width, image.height, pixels).ok_or_else(|| "Invalid pixel buffer".into())
+}
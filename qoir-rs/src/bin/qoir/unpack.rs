@@ -0,0 +1,47 @@
+use qoir_rs::DecodeOptions;
+use qoir_rs::bundle::Bundle;
+use std::path::{Component, Path};
+use crate::errors::{CliError, ErrorKind};
+use crate::io::{IMAGE_EXTENSIONS, save_image, to_dynamic_image};
+
+pub fn unpack_command(
+    input: &Path,
+    output_dir: &Path,
+    names: &[String],
+    extension: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let extension = extension.trim_start_matches('.').to_lowercase();
+    if extension != "qoir" && !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(CliError::new(ErrorKind::Arguments, format!("Unsupported output format: {}", extension)).into());
+    }
+
+    let bundle = Bundle::open(input)?;
+    let names: Vec<&str> = if names.is_empty() {
+        bundle.entries().iter().map(|entry| entry.name.as_str()).collect()
+    } else {
+        names.iter().map(String::as_str).collect()
+    };
+    if let Some(missing) = names.iter().find(|name| !bundle.contains(name)) {
+        return Err(CliError::new(ErrorKind::Arguments, format!("{} has no image named {}", input.display(), missing)).into());
+    }
+    std::fs::create_dir_all(output_dir)?;
+
+    for name in &names {
+        // Names come from the bundle, so keep them from escaping the output directory.
+        let mut components = Path::new(name).components();
+        if !matches!((components.next(), components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(format!("{} has an image with an unsafe name: {}", input.display(), name).into());
+        }
+
+        let path = output_dir.join(format!("{}.{}", name, extension));
+        if extension == "qoir" {
+            std::fs::write(&path, bundle.get_data(name)?)?;
+        } else {
+            let decoded = bundle.get(name, DecodeOptions::default())?;
+            save_image(&to_dynamic_image(&decoded.image)?, &path, 90)?;
+        }
+    }
+
+    println!("Unpacked {} images to: {}", names.len(), output_dir.display());
+    Ok(())
+}
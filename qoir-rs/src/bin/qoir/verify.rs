@@ -0,0 +1,42 @@
+use qoir_rs::{verify, PixelFormat};
+use rayon::prelude::*;
+use std::path::PathBuf;
+use crate::args::LimitArgs;
+use crate::errors::{BatchError, CliError, ErrorKind, json_errors, print_error_record};
+use crate::io::read_input;
+use crate::batch::in_thread_pool;
+
+pub fn verify_command(files: &[PathBuf], fast: bool, limits: &LimitArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let results: Vec<Result<(), BatchError>> = in_thread_pool(|| {
+        files
+            .par_iter()
+            .map(|path| {
+                let data = read_input(path)?;
+                limits.limits(path).check(&data, PixelFormat::RGBANonPremul)?;
+                Ok(verify(&data, fast)?)
+            })
+            .collect()
+    });
+
+    let mut failed = Vec::new();
+    for (path, result) in files.iter().zip(results) {
+        match result {
+            Ok(()) => println!("OK       {}", path.display()),
+            Err(e) => {
+                println!("CORRUPT  {}: {}", path.display(), e);
+                let kind = ErrorKind::of(e.as_ref());
+                if json_errors() {
+                    print_error_record(Some(path), kind, &e.to_string());
+                }
+                failed.push(kind);
+            }
+        }
+    }
+
+    println!("{} of {} files OK", files.len() - failed.len(), files.len());
+    if let Some(&first_kind) = failed.first() {
+        let kind = if failed.len() < files.len() { ErrorKind::PartialFailure } else { first_kind };
+        return Err(CliError::new(kind, format!("{} files failed verification", failed.len())).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::RgbaImage;
+use std::path::Path;
+use std::io::Write;
+use crate::args::ViewProtocol;
+use crate::io::load_rgba;
+
+pub fn view_command(
+    input: &Path,
+    protocol: ViewProtocol,
+    columns: Option<u32>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let protocol = match protocol {
+        ViewProtocol::Auto => detect_view_protocol(),
+        protocol => protocol,
+    };
+    let (rows, terminal_columns) = console::Term::stdout().size_checked().unwrap_or((24, 80));
+    let columns = columns.unwrap_or(terminal_columns as u32).max(1);
+    // Leave a line for the prompt after the image.
+    let rows = (rows as u32).saturating_sub(1).max(1);
+
+    // Terminals don't report their cell size, so pixel protocols assume
+    // 8x16 pixel cells; half blocks draw two pixels per cell.
+    let (max_width, max_height) = match protocol {
+        ViewProtocol::Blocks => (columns, rows * 2),
+        _ => (columns * 8, rows * 16),
+    };
+    let img = load_rgba(input)?;
+    let (width, height) = fit_within(img.dimensions(), (max_width, max_height));
+    let img = if (width, height) == img.dimensions() {
+        img
+    } else {
+        image::imageops::thumbnail(&img, width, height)
+    };
+
+    let mut out = std::io::stdout().lock();
+    match protocol {
+        ViewProtocol::Kitty => write_kitty(&mut out, &img)?,
+        ViewProtocol::Iterm => write_iterm(&mut out, &img)?,
+        ViewProtocol::Sixel => write_sixel(&mut out, &img)?,
+        ViewProtocol::Blocks | ViewProtocol::Auto => write_blocks(&mut out, &img)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Picks a graphics protocol from the variables terminals set, falling back to
+/// half blocks. `TERM` and `LC_TERMINAL` survive SSH, unlike most of the others.
+pub fn detect_view_protocol() -> ViewProtocol {
+    let var = |name| std::env::var(name).unwrap_or_default();
+    let (term, term_program) = (var("TERM"), var("TERM_PROGRAM"));
+    if term == "xterm-kitty" || term == "xterm-ghostty" || std::env::var_os("KITTY_WINDOW_ID").is_some() {
+        ViewProtocol::Kitty
+    } else if term_program == "iTerm.app" || term_program == "WezTerm" || var("LC_TERMINAL") == "iTerm2" {
+        ViewProtocol::Iterm
+    } else if term.contains("sixel") || term.starts_with("foot") || term == "mlterm" {
+        ViewProtocol::Sixel
+    } else {
+        ViewProtocol::Blocks
+    }
+}
+
+/// The largest size with the aspect ratio of `size` that fits in `max`,
+/// never larger than `size` itself.
+pub fn fit_within((width, height): (u32, u32), (max_width, max_height): (u32, u32)) -> (u32, u32) {
+    let scale = (max_width as f64 / width.max(1) as f64)
+        .min(max_height as f64 / height.max(1) as f64)
+        .min(1.0);
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// Sends the pixels with the kitty graphics protocol, in base64 chunks of at
+/// most 4096 bytes.
+pub fn write_kitty(out: &mut impl Write, img: &RgbaImage) -> std::io::Result<()> {
+    let data = BASE64.encode(img.as_raw());
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        if i == 0 {
+            write!(out, "\x1b_Ga=T,f=32,s={},v={},m={};", img.width(), img.height(), more)?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+    writeln!(out)
+}
+
+/// Sends the image as an iTerm2 inline PNG.
+pub fn write_iterm(out: &mut impl Write, img: &RgbaImage) -> Result<(), Box<dyn std::error::Error>> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    img.write_to(&mut png, image::ImageOutputFormat::Png)?;
+    let png = png.into_inner();
+    write!(out, "\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:", png.len())?;
+    out.write_all(BASE64.encode(&png).as_bytes())?;
+    writeln!(out, "\x07")?;
+    Ok(())
+}
+
+/// Sends the image as sixels, with transparent pixels blended onto black.
+pub fn write_sixel(out: &mut impl Write, img: &RgbaImage) -> Result<(), Box<dyn std::error::Error>> {
+    let rgb: Vec<u8> = img.pixels().flat_map(|pixel| over_black(pixel.0)).collect();
+    let sixel = icy_sixel::sixel_string(
+        &rgb,
+        img.width() as i32,
+        img.height() as i32,
+        icy_sixel::PixelFormat::RGB888,
+        icy_sixel::DiffusionMethod::Stucki,
+        icy_sixel::MethodForLargest::Auto,
+        icy_sixel::MethodForRep::Auto,
+        icy_sixel::Quality::HIGH,
+    )
+    .map_err(|e| format!("Sixel encoding failed: {:?}", e))?;
+    writeln!(out, "{}", sixel)?;
+    Ok(())
+}
+
+/// Draws two pixels per cell with the upper half block character, its
+/// foreground the upper pixel and its background the lower one.
+pub fn write_blocks(out: &mut impl Write, img: &RgbaImage) -> std::io::Result<()> {
+    for y in (0..img.height()).step_by(2) {
+        for x in 0..img.width() {
+            let [r, g, b] = over_black(img.get_pixel(x, y).0);
+            write!(out, "\x1b[38;2;{};{};{}m", r, g, b)?;
+            if y + 1 < img.height() {
+                let [r, g, b] = over_black(img.get_pixel(x, y + 1).0);
+                write!(out, "\x1b[48;2;{};{};{}m", r, g, b)?;
+            }
+            out.write_all("\u{2580}".as_bytes())?;
+        }
+        writeln!(out, "\x1b[0m")?;
+    }
+    Ok(())
+}
+
+/// Blends a non-premultiplied RGBA pixel onto black.
+pub fn over_black([r, g, b, a]: [u8; 4]) -> [u8; 3] {
+    let blend = |c: u8| ((c as u32 * a as u32 + 127) / 255) as u8;
+    [blend(r), blend(g), blend(b)]
+}
@@ -0,0 +1,126 @@
+use indicatif::ProgressBar;
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode};
+use qoir_rs::EncodeOptions;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use crate::io::{collect_files, format_bytes, lock};
+use crate::batch::{BatchOutcome, conversion_failed, convert_to_qoir, in_thread_pool, is_up_to_date, progress_bar, qoir_output_path};
+
+pub fn watch_command(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: EncodeOptions,
+    debounce: Duration,
+    strip_metadata: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let input_dir = input_dir.canonicalize()?;
+    std::fs::create_dir_all(output_dir)?;
+    let output_dir = output_dir.canonicalize()?;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut debouncer = new_debouncer(debounce, tx)?;
+    debouncer
+        .watcher()
+        .watch(&input_dir, RecursiveMode::Recursive)?;
+
+    // Catch up on files that arrived while nothing was watching.
+    let mut existing = Vec::new();
+    collect_files(&input_dir, &mut existing)?;
+    let failed = Mutex::new(HashMap::new());
+    let progress = progress_bar(existing.len() as u64);
+    in_thread_pool(|| {
+        existing.par_iter().for_each(|input| {
+            watch_convert(input, &input_dir, &output_dir, &options, strip_metadata, &failed, &progress);
+            progress.inc(1);
+        })
+    });
+    progress.finish_and_clear();
+
+    let watching = format!("Watching {} (Ctrl-C to stop)", input_dir.display());
+    let spinner = ProgressBar::new_spinner().with_message(watching.clone());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    if spinner.is_hidden() {
+        println!("{}", watching);
+    }
+    let (mut converted, mut failures) = (0, 0);
+    for result in rx {
+        match result {
+            Ok(events) => {
+                for event in events {
+                    let outcome = watch_convert(
+                        &event.path,
+                        &input_dir,
+                        &output_dir,
+                        &options,
+                        strip_metadata,
+                        &failed,
+                        &spinner,
+                    );
+                    match outcome {
+                        BatchOutcome::Converted { .. } => converted += 1,
+                        BatchOutcome::Failed(..) => failures += 1,
+                        BatchOutcome::Planned | BatchOutcome::Skipped => continue,
+                    }
+                    spinner.set_message(format!(
+                        "{}: {} converted, {} failed",
+                        watching, converted, failures
+                    ));
+                }
+            }
+            Err(e) => spinner.suspend(|| eprintln!("Watch error: {}", e)),
+        }
+    }
+    Ok(())
+}
+
+/// Converts a file found by `watch` if it is an image that has no up-to-date
+/// QOIR file yet, logging the outcome.
+///
+/// Reading a file can itself trigger events, so files that failed are only
+/// tried again once they are modified.
+pub fn watch_convert(
+    input: &Path,
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &EncodeOptions,
+    strip_metadata: bool,
+    failed: &Mutex<HashMap<PathBuf, SystemTime>>,
+    progress: &ProgressBar,
+) -> BatchOutcome {
+    let is_image = input.is_file()
+        && !input.starts_with(output_dir)
+        && image::ImageFormat::from_path(input).is_ok();
+    if !is_image {
+        return BatchOutcome::Skipped;
+    }
+
+    let output = qoir_output_path(input, input_dir, output_dir);
+    let modified = input.metadata().and_then(|m| m.modified()).ok();
+    let failed_before = modified.is_some() && lock(failed).get(input) == modified.as_ref();
+    if failed_before || is_up_to_date(input, &output) {
+        return BatchOutcome::Skipped;
+    }
+    match convert_to_qoir(input, &output, options, strip_metadata) {
+        Ok((input_len, output_len)) => {
+            lock(failed).remove(input);
+            progress.suspend(|| {
+                println!(
+                    "{} -> {} ({})",
+                    input.display(),
+                    output.display(),
+                    format_bytes(output_len as usize)
+                )
+            });
+            BatchOutcome::Converted { input_len, output_len }
+        }
+        Err(e) => {
+            if let Some(modified) = modified {
+                lock(failed).insert(input.to_path_buf(), modified);
+            }
+            conversion_failed(input, &e, progress)
+        }
+    }
+}
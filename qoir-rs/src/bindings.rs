@@ -5,6 +5,84 @@
 
 include!(concat!(env!("OUT_DIR"), "/qoir_bindings.rs"));
 
+/// The AVX2 build of qoir linked in by the `runtime-simd` feature.
+#[cfg(qoir_avx2)]
+mod avx2 {
+    use super::*;
+
+    cpufeatures::new!(cpuid_avx2, "avx2");
+
+    pub(super) fn is_supported() -> bool {
+        cpuid_avx2::get()
+    }
+
+    unsafe extern "C" {
+        pub(super) fn qoir_rs_avx2_decode(
+            src_ptr: *const u8,
+            src_len: usize,
+            options: *const qoir_decode_options,
+        ) -> qoir_decode_result;
+
+        pub(super) fn qoir_rs_avx2_encode(
+            src_pixbuf: *const qoir_pixel_buffer,
+            options: *const qoir_encode_options,
+        ) -> qoir_encode_result;
+    }
+}
+
+/// Whether the AVX2 build of qoir is linked in and the CPU supports it.
+pub(crate) fn uses_avx2() -> bool {
+    #[cfg(qoir_avx2)]
+    {
+        avx2::is_supported()
+    }
+    #[cfg(not(qoir_avx2))]
+    {
+        false
+    }
+}
+
+/// Calls `qoir_decode`, or its AVX2 build when the crate has one and the CPU
+/// supports it.
+pub(crate) unsafe fn dispatch_qoir_decode(
+    src_ptr: *const u8,
+    src_len: usize,
+    options: *const qoir_decode_options,
+) -> qoir_decode_result {
+    #[cfg(qoir_avx2)]
+    if avx2::is_supported() {
+        return unsafe { avx2::qoir_rs_avx2_decode(src_ptr, src_len, options) };
+    }
+    unsafe { qoir_decode(src_ptr, src_len, options) }
+}
+
+/// Calls `qoir_encode`, or its AVX2 build when the crate has one and the CPU
+/// supports it.
+pub(crate) unsafe fn dispatch_qoir_encode(
+    src_pixbuf: *const qoir_pixel_buffer,
+    options: *const qoir_encode_options,
+) -> qoir_encode_result {
+    #[cfg(qoir_avx2)]
+    if avx2::is_supported() {
+        return unsafe { avx2::qoir_rs_avx2_encode(src_pixbuf, options) };
+    }
+    unsafe { qoir_encode(src_pixbuf, options) }
+}
+
+/// Releases memory handed back by the C library (the `owned_memory` of a
+/// decode or encode result).
+pub(crate) unsafe fn qoir_free(ptr: *mut core::ffi::c_void) {
+    #[cfg(not(target_arch = "wasm32"))]
+    unsafe {
+        libc::free(ptr)
+    };
+
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        crate::wasm_libc::free(ptr)
+    };
+}
+
 impl qoir_pixel_configuration {
     pub fn zero() -> Self {
         Self {
@@ -19,7 +97,7 @@ impl qoir_pixel_buffer {
     pub fn zero() -> Self {
         Self {
             pixcfg: qoir_pixel_configuration::zero(),
-            data: std::ptr::null_mut(),
+            data: core::ptr::null_mut(),
             stride_in_bytes: 0,
         }
     }
@@ -36,13 +114,24 @@ impl qoir_rectangle {
     }
 }
 
+impl From<crate::Rectangle> for qoir_rectangle {
+    fn from(rect: crate::Rectangle) -> Self {
+        Self {
+            x0: rect.x0,
+            y0: rect.y0,
+            x1: rect.x1,
+            y1: rect.y1,
+        }
+    }
+}
+
 impl Default for qoir_decode_options {
     fn default() -> Self {
         Self {
             contextual_free_func: None,
             contextual_malloc_func: None,
-            memory_func_context: std::ptr::null_mut(),
-            decbuf: std::ptr::null_mut(),
+            memory_func_context: core::ptr::null_mut(),
+            decbuf: core::ptr::null_mut(),
             pixbuf: qoir_pixel_buffer_struct::zero(),
             pixfmt: QOIR_PIXEL_FORMAT__RGBA_NONPREMUL,
             dst_clip_rectangle: qoir_rectangle::zero(),
@@ -60,16 +149,16 @@ impl Default for qoir_encode_options {
         Self {
             contextual_free_func: None,
             contextual_malloc_func: None,
-            memory_func_context: std::ptr::null_mut(),
-            encbuf: std::ptr::null_mut(),
+            memory_func_context: core::ptr::null_mut(),
+            encbuf: core::ptr::null_mut(),
             metadata_cicp_len: 0,
-            metadata_cicp_ptr: std::ptr::null_mut(),
+            metadata_cicp_ptr: core::ptr::null_mut(),
             metadata_iccp_len: 0,
-            metadata_iccp_ptr: std::ptr::null_mut(),
+            metadata_iccp_ptr: core::ptr::null_mut(),
             metadata_exif_len: 0,
-            metadata_exif_ptr: std::ptr::null_mut(),
+            metadata_exif_ptr: core::ptr::null_mut(),
             metadata_xmp_len: 0,
-            metadata_xmp_ptr: std::ptr::null_mut(),
+            metadata_xmp_ptr: core::ptr::null_mut(),
             lossiness: 0,
             dither: false,
         }
@@ -0,0 +1,58 @@
+/// How the crate was built, for applications to report which code paths are
+/// active. See [`build_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// The version of this crate.
+    pub crate_version: &'static str,
+    /// Whether the C library is linked (`c-backend`).
+    pub c_backend: bool,
+    /// Whether the pure-Rust codec is built in (`rust-backend`).
+    pub rust_backend: bool,
+    /// Whether the vendored C library was compiled with its SIMD code paths.
+    /// Always `false` for a system `qoir` or without the C library.
+    pub simd_enabled: bool,
+    /// Whether the vendored C library was compiled with its large look-up
+    /// tables. Always `false` for a system `qoir` or without the C library.
+    pub large_luts: bool,
+    /// Whether decoding and encoding use the AVX2 build of the C library
+    /// linked in by `runtime-simd`, which depends on the CPU.
+    pub avx2: bool,
+    /// The git revision of the vendored C library, or the pkg-config version
+    /// of a system `qoir`. `None` without the C library, or when the crate
+    /// was built from sources that are not a git checkout.
+    pub c_library_rev: Option<&'static str>,
+}
+
+/// Returns how the crate was built.
+///
+/// The SIMD and look-up table settings are reported by the build script that
+/// compiles the C library, so they describe the code that was actually built.
+///
+/// # Examples
+///
+/// ```
+/// let info = qoir_rs::build_info();
+/// println!("qoir-rs {} (SIMD: {})", info.crate_version, info.simd_enabled);
+/// ```
+pub fn build_info() -> BuildInfo {
+    BuildInfo {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        c_backend: cfg!(feature = "c-backend"),
+        rust_backend: cfg!(feature = "rust-backend"),
+        simd_enabled: cfg!(qoir_simd),
+        large_luts: cfg!(qoir_large_luts),
+        avx2: avx2(),
+        c_library_rev: option_env!("QOIR_RS_C_LIBRARY_REV"),
+    }
+}
+
+fn avx2() -> bool {
+    #[cfg(feature = "c-backend")]
+    {
+        crate::bindings::uses_avx2()
+    }
+    #[cfg(not(feature = "c-backend"))]
+    {
+        false
+    }
+}
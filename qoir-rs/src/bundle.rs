@@ -0,0 +1,308 @@
+//! Bundles: many QOIR images packed into one file, with an index of their
+//! names for extracting any of them without reading the others.
+//!
+//! A bundle file uses the chunk layout of a QOIR file. It starts with an empty
+//! `QBDL` chunk and holds each image, unchanged, in a `QIMG` chunk. After the
+//! images comes a `QIDX` chunk with one entry per image: the name's length as
+//! a little-endian `u16`, the UTF-8 name, and the byte offset and length of
+//! the image as little-endian `u64`s. The file ends with a `QEND` chunk whose
+//! 8-byte payload is the offset of the `QIDX` chunk, so that opening a bundle
+//! only reads its end and its index.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::bundle::{Bundle, BundleWriter};
+//! use qoir_rs::DecodeOptions;
+//!
+//! let file = std::fs::File::create("previews.qoirb").expect("Failed to create");
+//! let mut writer = BundleWriter::new(std::io::BufWriter::new(file)).expect("Failed to write");
+//! for name in ["IMG_0041", "IMG_0042"] {
+//!     let data = std::fs::read(format!("{}.qoir", name)).expect("Failed to read QOIR file");
+//!     writer.add(name, &data).expect("Failed to add");
+//! }
+//! writer.finish().expect("Failed to finish");
+//!
+//! let bundle = Bundle::open("previews.qoirb").expect("Failed to open");
+//! let preview = bundle.get("IMG_0042", DecodeOptions::default()).expect("Failed to decode");
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use crate::container::{CHUNK_HEADER_LEN, Header, next_chunk, write_chunk};
+use crate::{DecodeOptions, DecodedImage, Error, decode_from_memory};
+
+const END_PAYLOAD_LEN: usize = 8;
+const END_LEN: usize = CHUNK_HEADER_LEN + END_PAYLOAD_LEN;
+
+fn invalid_data() -> Error {
+    Error::DecodingFailed("#qoir-bundle: invalid data".to_string())
+}
+
+/// Where one image of a bundle is stored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BundleEntry {
+    /// The name the image was added with.
+    pub name: String,
+    /// The byte offset of the image's QOIR data in the bundle file.
+    pub offset: u64,
+    /// The length of the image's QOIR data in bytes.
+    pub len: u64,
+}
+
+/// Writes a bundle, one image at a time.
+///
+/// The images are written as they are added; only the index is kept in memory
+/// until [`finish`](Self::finish).
+pub struct BundleWriter<W: Write> {
+    writer: W,
+    position: u64,
+    entries: Vec<BundleEntry>,
+    names: HashMap<String, usize>,
+}
+
+impl<W: Write> BundleWriter<W> {
+    /// Starts a bundle in `writer`.
+    ///
+    /// # Returns
+    ///
+    /// The `BundleWriter`, or `Error::IoError` if writing fails.
+    pub fn new(writer: W) -> Result<Self, Error> {
+        let mut bundle = BundleWriter {
+            writer,
+            position: 0,
+            entries: Vec::new(),
+            names: HashMap::new(),
+        };
+        let mut header = Vec::with_capacity(CHUNK_HEADER_LEN);
+        write_chunk(&mut header, *b"QBDL", &[]);
+        bundle.write(&header)?;
+        Ok(bundle)
+    }
+
+    /// Adds an encoded QOIR image under `name`.
+    ///
+    /// # Returns
+    ///
+    /// `Error::InvalidParameter` if the name is already taken or longer than
+    /// 65535 bytes, an error if `data` is not a QOIR image, or
+    /// `Error::IoError` if writing fails.
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<(), Error> {
+        if name.len() > u16::MAX as usize || self.names.contains_key(name) {
+            return Err(Error::InvalidParameter);
+        }
+        Header::parse(data)?;
+
+        let mut chunk_header = Vec::with_capacity(CHUNK_HEADER_LEN);
+        chunk_header.extend_from_slice(b"QIMG");
+        chunk_header.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        self.write(&chunk_header)?;
+        let offset = self.position;
+        self.write(data)?;
+
+        self.names.insert(name.to_string(), self.entries.len());
+        self.entries.push(BundleEntry {
+            name: name.to_string(),
+            offset,
+            len: data.len() as u64,
+        });
+        Ok(())
+    }
+
+    /// The number of images added so far.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether an image named `name` was added.
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Whether no image was added yet.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Writes the index and the end of the bundle.
+    ///
+    /// # Returns
+    ///
+    /// The writer, flushed, or `Error::IoError` if writing fails.
+    pub fn finish(mut self) -> Result<W, Error> {
+        let mut index = Vec::new();
+        for entry in &self.entries {
+            index.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            index.extend_from_slice(entry.name.as_bytes());
+            index.extend_from_slice(&entry.offset.to_le_bytes());
+            index.extend_from_slice(&entry.len.to_le_bytes());
+        }
+
+        let index_offset = self.position;
+        let mut tail = Vec::with_capacity(CHUNK_HEADER_LEN + index.len() + END_LEN);
+        write_chunk(&mut tail, *b"QIDX", &index);
+        write_chunk(&mut tail, *b"QEND", &index_offset.to_le_bytes());
+        self.write(&tail)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(data)?;
+        self.position += data.len() as u64;
+        Ok(())
+    }
+}
+
+/// An open bundle file.
+///
+/// Opening reads the index only. Each image is then read and decoded with a
+/// single seek, and a `Bundle` can be shared between threads.
+#[derive(Debug)]
+pub struct Bundle {
+    file: Mutex<File>,
+    entries: Vec<BundleEntry>,
+    names: HashMap<String, usize>,
+}
+
+impl Bundle {
+    /// Opens a bundle file and reads its index.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Bundle`, `Error::FileNotFound` if the file
+    /// does not exist, `Error::IoError` if it cannot be read, or an `Error` if
+    /// it is not a valid bundle.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut file = File::open(path).map_err(|e| Error::from(e).with_path(path))?;
+        let file_len = file.seek(SeekFrom::End(0)).map_err(|e| Error::from(e).with_path(path))?;
+
+        if file_len < (CHUNK_HEADER_LEN + END_LEN) as u64 {
+            return Err(invalid_data());
+        }
+        let mut header = [0; CHUNK_HEADER_LEN];
+        read_at(&mut file, 0, &mut header)?;
+        if header[..4] != *b"QBDL" {
+            return Err(invalid_data());
+        }
+
+        let mut end = [0; END_LEN];
+        read_at(&mut file, file_len - END_LEN as u64, &mut end)?;
+        let (tag, payload, _) = next_chunk(&end)?;
+        if tag != *b"QEND" || payload.len() != END_PAYLOAD_LEN {
+            return Err(invalid_data());
+        }
+        let mut index_offset = [0; 8];
+        index_offset.copy_from_slice(payload);
+        let index_offset = u64::from_le_bytes(index_offset);
+
+        let index_len = (file_len - END_LEN as u64)
+            .checked_sub(index_offset)
+            .filter(|&len| len >= CHUNK_HEADER_LEN as u64)
+            .ok_or_else(invalid_data)?;
+        let mut index = vec![0; usize::try_from(index_len).map_err(|_| invalid_data())?];
+        read_at(&mut file, index_offset, &mut index)?;
+        let (tag, payload, rest) = next_chunk(&index)?;
+        if tag != *b"QIDX" || !rest.is_empty() {
+            return Err(invalid_data());
+        }
+
+        let mut entries = Vec::new();
+        let mut names = HashMap::new();
+        let mut rest = payload;
+        while !rest.is_empty() {
+            let (entry, remaining) = parse_entry(rest).ok_or_else(invalid_data)?;
+            rest = remaining;
+            if entry
+                .offset
+                .checked_add(entry.len)
+                .is_none_or(|end| end > index_offset)
+            {
+                return Err(invalid_data());
+            }
+            if names.insert(entry.name.clone(), entries.len()).is_some() {
+                return Err(invalid_data());
+            }
+            entries.push(entry);
+        }
+
+        Ok(Bundle {
+            file: Mutex::new(file),
+            entries,
+            names,
+        })
+    }
+
+    /// The number of images in the bundle.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the bundle holds no images.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The images, in the order they were added.
+    pub fn entries(&self) -> &[BundleEntry] {
+        &self.entries
+    }
+
+    /// Whether the bundle holds an image named `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.contains_key(name)
+    }
+
+    /// Reads the QOIR data of the image named `name`, without decoding it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the QOIR data, `Error::FileNotFound` if the
+    /// bundle holds no such image, or `Error::IoError` if reading fails.
+    pub fn get_data(&self, name: &str) -> Result<Vec<u8>, Error> {
+        let entry = &self.entries[*self.names.get(name).ok_or(Error::FileNotFound)?];
+        let mut data = vec![0; usize::try_from(entry.len).map_err(|_| invalid_data())?];
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        read_at(&mut file, entry.offset, &mut data)?;
+        Ok(data)
+    }
+
+    /// Reads and decodes the image named `name`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: The name the image was added with.
+    /// * `options`: `DecodeOptions` to control the decoding process.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DecodedImage`, `Error::FileNotFound` if the
+    /// bundle holds no such image, or an `Error` if reading or decoding fails.
+    pub fn get<'a>(&self, name: &str, options: DecodeOptions) -> Result<DecodedImage<'a>, Error> {
+        decode_from_memory(&self.get_data(name)?, options)
+    }
+}
+
+fn read_at(file: &mut File, offset: u64, buf: &mut [u8]) -> Result<(), Error> {
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(file.read_exact(buf)?)
+}
+
+/// Splits the index entry at the start of `data` from the bytes after it.
+fn parse_entry(data: &[u8]) -> Option<(BundleEntry, &[u8])> {
+    let name_len = u16::from_le_bytes([*data.first()?, *data.get(1)?]) as usize;
+    let (name, rest) = data.get(2..)?.split_at_checked(name_len)?;
+    let (offset, rest) = rest.split_first_chunk::<8>()?;
+    let (len, rest) = rest.split_first_chunk::<8>()?;
+    let entry = BundleEntry {
+        name: core::str::from_utf8(name).ok()?.to_string(),
+        offset: u64::from_le_bytes(*offset),
+        len: u64::from_le_bytes(*len),
+    };
+    Some((entry, rest))
+}
@@ -0,0 +1,246 @@
+//! A cache of decoded images with a memory budget, for viewers that show the
+//! same images again and again.
+//!
+//! Cached images are `OwnedDecodedImage`s behind an `Arc`, so they can be
+//! kept by the caller after they are evicted, and have no lifetime tied to
+//! the encoded data. When the cached images exceed the budget, the least
+//! recently used ones are dropped.
+//!
+//! ```no_run
+//! use qoir_rs::{DecodeCache, DecodeOptions};
+//!
+//! let cache = DecodeCache::new(256 * 1024 * 1024);
+//! let path = "IMG_0042.qoir";
+//! let image = cache
+//!     .get_or_decode(path, || std::fs::read(path).unwrap(), DecodeOptions::default())
+//!     .expect("Failed to decode");
+//! println!("{}x{}", image.width, image.height);
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use crate::{DecodeOptions, Error, OwnedDecodedImage, decode_from_memory};
+
+/// A thread-safe least-recently-used cache of decoded images, limited to a
+/// number of bytes.
+///
+/// Keys are chosen by the caller, such as file paths, or computed from the
+/// encoded data with [`content_key`]. A key should identify the decode
+/// options too if the same data is decoded in several ways.
+#[derive(Debug)]
+pub struct DecodeCache<K = u64> {
+    budget_bytes: usize,
+    state: Mutex<CacheState<K>>,
+}
+
+#[derive(Debug)]
+struct CacheState<K> {
+    entries: HashMap<K, CacheEntry>,
+    /// The keys ordered from the least to the most recently used.
+    order: BTreeMap<u64, K>,
+    next_use: u64,
+    bytes: usize,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    image: Arc<OwnedDecodedImage>,
+    last_use: u64,
+    bytes: usize,
+}
+
+impl<K: Eq + Hash + Clone> DecodeCache<K> {
+    /// Creates an empty cache that holds at most `budget_bytes` of pixels and
+    /// metadata.
+    pub fn new(budget_bytes: usize) -> Self {
+        DecodeCache {
+            budget_bytes,
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                next_use: 0,
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// The number of bytes the cache may hold.
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// The number of bytes the cached images take.
+    pub fn size_bytes(&self) -> usize {
+        self.lock().bytes
+    }
+
+    /// The number of cached images.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Whether the cache holds no images.
+    pub fn is_empty(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    /// Returns the image cached under `key`, marking it as recently used.
+    pub fn get(&self, key: &K) -> Option<Arc<OwnedDecodedImage>> {
+        let mut state = self.lock();
+        let use_id = state.next_use;
+        let entry = state.entries.get_mut(key)?;
+        let previous_use = std::mem::replace(&mut entry.last_use, use_id);
+        let image = entry.image.clone();
+        let key = state.order.remove(&previous_use)?;
+        state.order.insert(use_id, key);
+        state.next_use += 1;
+        Some(image)
+    }
+
+    /// Caches `image` under `key`, replacing any image cached under it, and
+    /// evicts the least recently used images if the budget is exceeded.
+    ///
+    /// An image larger than the whole budget is not cached, but is still
+    /// returned.
+    pub fn insert(&self, key: K, image: OwnedDecodedImage) -> Arc<OwnedDecodedImage> {
+        let image = Arc::new(image);
+        let bytes = image_bytes(&image);
+        let mut state = self.lock();
+        state.remove(&key);
+        if bytes > self.budget_bytes {
+            return image;
+        }
+
+        while state.bytes + bytes > self.budget_bytes {
+            let Some((_, oldest)) = state.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = state.entries.remove(&oldest) {
+                state.bytes -= entry.bytes;
+            }
+        }
+
+        let use_id = state.next_use;
+        state.next_use += 1;
+        state.order.insert(use_id, key.clone());
+        state.entries.insert(
+            key,
+            CacheEntry {
+                image: image.clone(),
+                last_use: use_id,
+                bytes,
+            },
+        );
+        state.bytes += bytes;
+        image
+    }
+
+    /// Returns the image cached under `key`, or decodes the data returned by
+    /// `data` and caches the result.
+    ///
+    /// The lock is not held while decoding, so several threads missing the
+    /// same key at once may each decode it; the last one to finish is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `key`: The key to look up and to cache the decoded image under.
+    /// * `data`: Returns the QOIR data to decode. It is only called on a miss.
+    /// * `options`: `DecodeOptions` to control the decoding process.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the cached or decoded image, or an `Error` if
+    /// decoding fails. Failures are not cached.
+    pub fn get_or_decode<D: AsRef<[u8]>>(
+        &self,
+        key: K,
+        data: impl FnOnce() -> D,
+        options: DecodeOptions,
+    ) -> Result<Arc<OwnedDecodedImage>, Error> {
+        if let Some(image) = self.get(&key) {
+            return Ok(image);
+        }
+        let decoded = decode_from_memory(data().as_ref(), options)?.into_owned();
+        Ok(self.insert(key, decoded))
+    }
+
+    /// Removes the image cached under `key`, returning it.
+    pub fn remove(&self, key: &K) -> Option<Arc<OwnedDecodedImage>> {
+        self.lock().remove(key)
+    }
+
+    /// Removes every cached image.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.order.clear();
+        state.bytes = 0;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState<K>> {
+        // The state is consistent between statements, so a panic elsewhere
+        // while the lock was held leaves it usable.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl DecodeCache<u64> {
+    /// Returns the image decoded from `data` with `options`, decoding and
+    /// caching it under its [`content_key`] on a miss.
+    pub fn get_or_decode_content(
+        &self,
+        data: &[u8],
+        options: DecodeOptions,
+    ) -> Result<Arc<OwnedDecodedImage>, Error> {
+        let key = content_key(data, &options);
+        self.get_or_decode(key, || data, options)
+    }
+}
+
+impl<K: Eq + Hash> CacheState<K> {
+    fn remove(&mut self, key: &K) -> Option<Arc<OwnedDecodedImage>> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.last_use);
+        self.bytes -= entry.bytes;
+        Some(entry.image)
+    }
+}
+
+/// A 64-bit hash of QOIR data and the options it is decoded with, to use as a
+/// [`DecodeCache`] key.
+///
+/// The hash may change between Rust versions, so it must not be stored.
+pub fn content_key(data: &[u8], options: &DecodeOptions) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    options.pixel_format.hash(&mut hasher);
+    options.src_clip_rect.hash(&mut hasher);
+    options.dst_clip_rect.hash(&mut hasher);
+    options.offset_x.hash(&mut hasher);
+    options.offset_y.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn image_bytes(image: &OwnedDecodedImage) -> usize {
+    let metadata = [
+        &image.cic_profile,
+        &image.icc_profile,
+        &image.exif,
+        &image.xmp,
+    ];
+    let custom: usize = image
+        .custom_metadata
+        .iter()
+        .map(|(key, value)| key.len() + value.len())
+        .sum();
+    image.pixels.len()
+        + custom
+        + metadata
+            .iter()
+            .filter_map(|m| m.as_ref())
+            .map(Vec::len)
+            .sum::<usize>()
+}
@@ -0,0 +1,354 @@
+//! A C ABI for linking the crate from other languages, enabled with the `capi`
+//! feature.
+//!
+//! Build the crate as a `cdylib` or `staticlib` (for example with
+//! `cargo rustc --lib --features capi --crate-type staticlib`) and include
+//! `include/qoir_rs.h`. Compared to calling `qoir.c` directly, callers get the
+//! crate's argument validation, file handling and metadata parsing.
+//!
+//! Every function returns a `qoir_rs_status`, where zero means success. Images
+//! and buffers handed out by the library own their memory through a handle,
+//! which must be released with [`qoir_rs_free`].
+
+use std::ffi::{CStr, c_char};
+
+use crate::{DecodeOptions, DecodedImage, EncodeOptions, EncodedBuffer, Error, Image, PixelFormat};
+
+// The status codes returned by every function, mirroring `Error`.
+pub const QOIR_RS_STATUS_OK: i32 = 0;
+pub const QOIR_RS_STATUS_INVALID_PARAMETER: i32 = 1;
+pub const QOIR_RS_STATUS_DECODING_FAILED: i32 = 2;
+pub const QOIR_RS_STATUS_ENCODING_FAILED: i32 = 3;
+pub const QOIR_RS_STATUS_FILE_NOT_FOUND: i32 = 4;
+pub const QOIR_RS_STATUS_IO_ERROR: i32 = 5;
+pub const QOIR_RS_STATUS_INTERNAL_ERROR: i32 = 6;
+
+/// Owns the memory behind a `qoir_rs_image` or `qoir_rs_buffer`.
+pub enum QoirRsHandle {
+    Image(DecodedImage<'static>),
+    Buffer(EncodedBuffer<'static>),
+}
+
+/// The width, height and pixel format of an encoded image.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QoirRsMetadata {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: u32,
+}
+
+/// A decoded image. The pixels stay valid until `handle` is freed.
+#[repr(C)]
+#[derive(Debug)]
+pub struct QoirRsImage {
+    pub handle: *mut QoirRsHandle,
+    pub pixels: *const u8,
+    pub pixels_len: usize,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: u32,
+    pub stride_in_bytes: usize,
+}
+
+/// Encoded QOIR bytes. The data stays valid until `handle` is freed.
+#[repr(C)]
+#[derive(Debug)]
+pub struct QoirRsBuffer {
+    pub handle: *mut QoirRsHandle,
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// Options for [`qoir_rs_encode`] and [`qoir_rs_encode_file`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QoirRsEncodeOptions {
+    pub lossiness: u8,
+    pub dither: bool,
+}
+
+fn status(error: Error) -> i32 {
+    match error {
+        Error::InvalidParameter => QOIR_RS_STATUS_INVALID_PARAMETER,
+        Error::DecodingFailed(_) => QOIR_RS_STATUS_DECODING_FAILED,
+        Error::EncodingFailed(_) => QOIR_RS_STATUS_ENCODING_FAILED,
+        Error::FileNotFound => QOIR_RS_STATUS_FILE_NOT_FOUND,
+        Error::IoError { .. } => QOIR_RS_STATUS_IO_ERROR,
+        Error::Internal(_) => QOIR_RS_STATUS_INTERNAL_ERROR,
+    }
+}
+
+/// Runs the body of a function and converts its result into the status code
+/// reporting it. A panic is caught and reported as an internal error, since
+/// unwinding into the caller's frames would abort the process.
+fn check(body: impl FnOnce() -> Result<(), Error>) -> i32 {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(body)) {
+        Ok(Ok(())) => QOIR_RS_STATUS_OK,
+        Ok(Err(error)) => status(error),
+        Err(_) => QOIR_RS_STATUS_INTERNAL_ERROR,
+    }
+}
+
+unsafe fn slice<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], Error> {
+    if ptr.is_null() {
+        return if len == 0 {
+            Ok(&[])
+        } else {
+            Err(Error::InvalidParameter)
+        };
+    }
+    Ok(unsafe { core::slice::from_raw_parts(ptr, len) })
+}
+
+unsafe fn path<'a>(path: *const c_char) -> Result<&'a str, Error> {
+    if path.is_null() {
+        return Err(Error::InvalidParameter);
+    }
+    unsafe { CStr::from_ptr(path) }
+        .to_str()
+        .map_err(|_| Error::InvalidParameter)
+}
+
+fn pixel_format(value: u32) -> Result<PixelFormat, Error> {
+    match PixelFormat::from(value) {
+        PixelFormat::Invalid => Err(Error::InvalidParameter),
+        pixel_format => Ok(pixel_format),
+    }
+}
+
+fn write_image(decoded: DecodedImage<'static>, out: &mut QoirRsImage) {
+    let image = &decoded.image;
+    *out = QoirRsImage {
+        handle: core::ptr::null_mut(),
+        pixels: image.pixels.as_ptr(),
+        pixels_len: image.pixels.len(),
+        width: image.width,
+        height: image.height,
+        pixel_format: image.pixel_format as u32,
+        stride_in_bytes: image.stride_in_bytes,
+    };
+    out.handle = Box::into_raw(Box::new(QoirRsHandle::Image(decoded)));
+}
+
+fn write_buffer(encoded: EncodedBuffer<'static>, out: &mut QoirRsBuffer) {
+    *out = QoirRsBuffer {
+        handle: core::ptr::null_mut(),
+        data: encoded.data.as_ptr(),
+        len: encoded.data.len(),
+    };
+    out.handle = Box::into_raw(Box::new(QoirRsHandle::Buffer(encoded)));
+}
+
+unsafe fn image<'a>(
+    pixels: *const u8,
+    pixels_len: usize,
+    width: u32,
+    height: u32,
+    pixel_format_value: u32,
+    stride_in_bytes: usize,
+) -> Result<Image<'a>, Error> {
+    let pixels = unsafe { slice(pixels, pixels_len)? };
+    let pixel_format = pixel_format(pixel_format_value)?;
+    let row_len = (width as usize)
+        .checked_mul(pixel_format.bytes_per_pixel())
+        .ok_or(Error::InvalidParameter)?;
+    let len = match (height as usize).checked_sub(1) {
+        Some(rows) => stride_in_bytes
+            .checked_mul(rows)
+            .and_then(|len| len.checked_add(row_len))
+            .ok_or(Error::InvalidParameter)?,
+        None => 0,
+    };
+    if stride_in_bytes < row_len || pixels.len() < len {
+        return Err(Error::InvalidParameter);
+    }
+
+    Ok(Image {
+        pixels,
+        width,
+        height,
+        pixel_format,
+        stride_in_bytes,
+    })
+}
+
+unsafe fn encode_options(options: *const QoirRsEncodeOptions) -> EncodeOptions {
+    let options = unsafe { options.as_ref() }.copied().unwrap_or_default();
+    EncodeOptions {
+        lossiness: options.lossiness,
+        dither: options.dither,
+        ..Default::default()
+    }
+}
+
+/// Reads the width, height and pixel format of QOIR data without decoding it.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qoir_rs_decode_metadata(
+    data: *const u8,
+    len: usize,
+    out: *mut QoirRsMetadata,
+) -> i32 {
+    check(|| {
+        let out = unsafe { out.as_mut() }.ok_or(Error::InvalidParameter)?;
+        let (width, height, pixel_format) =
+            crate::decode_basic_metadata(unsafe { slice(data, len)? })?;
+        *out = QoirRsMetadata {
+            width,
+            height,
+            pixel_format: pixel_format as u32,
+        };
+        Ok(())
+    })
+}
+
+/// Decodes QOIR data into `pixel_format`.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qoir_rs_decode(
+    data: *const u8,
+    len: usize,
+    pixel_format: u32,
+    out: *mut QoirRsImage,
+) -> i32 {
+    check(|| {
+        let out = unsafe { out.as_mut() }.ok_or(Error::InvalidParameter)?;
+        let options = DecodeOptions {
+            pixel_format: self::pixel_format(pixel_format)?,
+            ..Default::default()
+        };
+        write_image(
+            crate::decode_from_memory(unsafe { slice(data, len)? }, options)?,
+            out,
+        );
+        Ok(())
+    })
+}
+
+/// Decodes the QOIR file at the UTF-8 path `path` into `pixel_format`.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qoir_rs_decode_file(
+    path: *const c_char,
+    pixel_format: u32,
+    out: *mut QoirRsImage,
+) -> i32 {
+    check(|| {
+        let out = unsafe { out.as_mut() }.ok_or(Error::InvalidParameter)?;
+        let options = DecodeOptions {
+            pixel_format: self::pixel_format(pixel_format)?,
+            ..Default::default()
+        };
+        write_image(crate::decode(unsafe { self::path(path)? }, options)?, out);
+        Ok(())
+    })
+}
+
+/// Encodes pixels into QOIR data. `options` may be null for lossless encoding.
+///
+/// # Safety
+///
+/// `pixels` must point to `pixels_len` readable bytes, `options` must be null
+/// or valid for reads, and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qoir_rs_encode(
+    pixels: *const u8,
+    pixels_len: usize,
+    width: u32,
+    height: u32,
+    pixel_format: u32,
+    stride_in_bytes: usize,
+    options: *const QoirRsEncodeOptions,
+    out: *mut QoirRsBuffer,
+) -> i32 {
+    check(|| {
+        let out = unsafe { out.as_mut() }.ok_or(Error::InvalidParameter)?;
+        let image = unsafe {
+            image(
+                pixels,
+                pixels_len,
+                width,
+                height,
+                pixel_format,
+                stride_in_bytes,
+            )?
+        };
+        let options = unsafe { encode_options(options) };
+        write_buffer(crate::encode_to_memory(image, options)?, out);
+        Ok(())
+    })
+}
+
+/// Encodes pixels into a QOIR file at the UTF-8 path `path`. `options` may be
+/// null for lossless encoding.
+///
+/// # Safety
+///
+/// `pixels` must point to `pixels_len` readable bytes, `options` must be null
+/// or valid for reads, and `path` must be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qoir_rs_encode_file(
+    pixels: *const u8,
+    pixels_len: usize,
+    width: u32,
+    height: u32,
+    pixel_format: u32,
+    stride_in_bytes: usize,
+    options: *const QoirRsEncodeOptions,
+    path: *const c_char,
+) -> i32 {
+    check(|| {
+        let image = unsafe {
+            image(
+                pixels,
+                pixels_len,
+                width,
+                height,
+                pixel_format,
+                stride_in_bytes,
+            )?
+        };
+        let options = unsafe { encode_options(options) };
+        crate::encode(image, options, unsafe { self::path(path)? })?;
+        Ok(())
+    })
+}
+
+/// Releases an image or buffer handle. Passing null does nothing.
+///
+/// # Safety
+///
+/// `handle` must be null or a handle returned by this library that has not
+/// been freed yet.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn qoir_rs_free(handle: *mut QoirRsHandle) {
+    if !handle.is_null() {
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+/// Returns a static, NUL-terminated description of a status code.
+#[unsafe(no_mangle)]
+pub extern "C" fn qoir_rs_status_message(status: i32) -> *const c_char {
+    let message: &CStr = match status {
+        QOIR_RS_STATUS_OK => c"ok",
+        QOIR_RS_STATUS_INVALID_PARAMETER => c"invalid parameter",
+        QOIR_RS_STATUS_DECODING_FAILED => c"decoding failed",
+        QOIR_RS_STATUS_ENCODING_FAILED => c"encoding failed",
+        QOIR_RS_STATUS_FILE_NOT_FOUND => c"file not found",
+        QOIR_RS_STATUS_IO_ERROR => c"I/O error",
+        QOIR_RS_STATUS_INTERNAL_ERROR => c"internal error",
+        _ => c"unknown status",
+    };
+    message.as_ptr()
+}
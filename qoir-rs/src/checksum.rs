@@ -0,0 +1,36 @@
+//! CRC-32C checksums of the pixel data, stored in a `QSUM` chunk when
+//! `EncodeOptions::embed_checksum` is set.
+
+/// The Castagnoli polynomial, bit-reversed.
+const POLYNOMIAL: u32 = 0x82F6_3B78;
+
+const TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                crc >> 1 ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The payload of a `QSUM` chunk: the CRC-32C of the `QPIX` payload as a
+/// little-endian `u32`.
+pub(crate) const CHECKSUM_LEN: usize = 4;
+
+/// Computes the CRC-32C (Castagnoli) checksum of `data`.
+pub(crate) fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ crc >> 8
+    })
+}
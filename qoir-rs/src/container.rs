@@ -0,0 +1,251 @@
+//! Parsing and writing of the QOIR chunk structure.
+//!
+//! A QOIR file is a sequence of chunks, each a 4-byte tag followed by a
+//! little-endian `u64` payload length and the payload itself. The file starts
+//! with a `QOIR` header chunk, holds the tiles in a `QPIX` chunk and ends with
+//! an empty `QEND` chunk. Metadata lives in optional `CICP`, `ICCP`, `EXIF` and
+//! `XMP ` chunks.
+
+use alloc::{string::ToString, vec::Vec};
+
+use crate::{Error, PixelFormat};
+
+pub(crate) const CHUNK_HEADER_LEN: usize = 12;
+const QOIR_PAYLOAD_LEN: usize = 8;
+
+/// The width and height of a full tile, in pixels.
+pub(crate) const TILE_SIZE: u32 = 64;
+
+pub(crate) fn invalid_data() -> Error {
+    Error::DecodingFailed("#qoir: invalid data".to_string())
+}
+
+pub(crate) fn unsupported_pixfmt() -> Error {
+    Error::DecodingFailed("#qoir: unsupported pixfmt".to_string())
+}
+
+/// The contents of the `QOIR` header chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    /// One of `BGRX`, `BGRANonPremul` or `BGRAPremul`.
+    pub(crate) pixel_format: PixelFormat,
+    /// The number of low bits dropped from each color channel, from 0 to 7.
+    pub(crate) lossiness: u8,
+}
+
+impl Header {
+    pub(crate) fn parse(data: &[u8]) -> Result<Self, Error> {
+        let (tag, payload, _) = next_chunk(data)?;
+        if tag != *b"QOIR" || payload.len() != QOIR_PAYLOAD_LEN {
+            return Err(invalid_data());
+        }
+
+        let word0 = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        let word1 = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+
+        let pixel_format = match word0 >> 24 {
+            0x01 => PixelFormat::BGRX,
+            0x02 => PixelFormat::BGRANonPremul,
+            0x03 => PixelFormat::BGRAPremul,
+            _ => return Err(unsupported_pixfmt()),
+        };
+        let lossiness = (word1 >> 24) as u8;
+        if lossiness > 7 {
+            return Err(invalid_data());
+        }
+
+        Ok(Header {
+            width: word0 & 0x00FF_FFFF,
+            height: word1 & 0x00FF_FFFF,
+            pixel_format,
+            lossiness,
+        })
+    }
+
+    /// Appends the `QOIR` chunk for this header to `dst`.
+    #[cfg(feature = "rust-backend")]
+    pub(crate) fn write(&self, dst: &mut Vec<u8>) {
+        let pixfmt: u32 = match self.pixel_format {
+            PixelFormat::BGRANonPremul => 0x02,
+            PixelFormat::BGRAPremul => 0x03,
+            _ => 0x01,
+        };
+        let word0 = self.width | pixfmt << 24;
+        let word1 = self.height | (self.lossiness as u32) << 24;
+
+        let mut payload = [0; QOIR_PAYLOAD_LEN];
+        payload[..4].copy_from_slice(&word0.to_le_bytes());
+        payload[4..].copy_from_slice(&word1.to_le_bytes());
+        write_chunk(dst, *b"QOIR", &payload);
+    }
+}
+
+/// A parsed QOIR file, borrowing its payloads from the input.
+pub(crate) struct Container<'a> {
+    pub(crate) header: Header,
+    pub(crate) cicp: Option<&'a [u8]>,
+    pub(crate) iccp: Option<&'a [u8]>,
+    pub(crate) exif: Option<&'a [u8]>,
+    pub(crate) xmp: Option<&'a [u8]>,
+    /// The payload of the `APPD` chunk of application key/value pairs.
+    pub(crate) custom: Option<&'a [u8]>,
+    /// The payload of the `QSUM` chunk holding the pixel data's checksum.
+    pub(crate) checksum: Option<&'a [u8]>,
+    /// The payload of the `QPIX` chunk.
+    pub(crate) tiles: &'a [u8],
+}
+
+impl<'a> Container<'a> {
+    pub(crate) fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        Self::parse_chunks(data, false)
+    }
+
+    /// Parses as much of a truncated or corrupt file as possible. Only the
+    /// header must be intact: a `QPIX` chunk that runs past the end of the
+    /// data is cut short, and anything after the last readable chunk is
+    /// ignored.
+    #[cfg(feature = "rust-backend")]
+    pub(crate) fn parse_tolerant(data: &'a [u8]) -> Result<Self, Error> {
+        Self::parse_chunks(data, true)
+    }
+
+    fn parse_chunks(data: &'a [u8], tolerant: bool) -> Result<Self, Error> {
+        let header = Header::parse(data)?;
+        let mut rest = &data[CHUNK_HEADER_LEN + QOIR_PAYLOAD_LEN..];
+
+        let mut container = Container {
+            header,
+            cicp: None,
+            iccp: None,
+            exif: None,
+            xmp: None,
+            custom: None,
+            checksum: None,
+            tiles: &[],
+        };
+        let mut found_tiles = false;
+
+        loop {
+            let (tag, payload, remaining) = match next_chunk(rest) {
+                Ok(chunk) => chunk,
+                Err(_) if tolerant => {
+                    if rest.len() >= CHUNK_HEADER_LEN && rest.starts_with(b"QPIX") {
+                        container.tiles = &rest[CHUNK_HEADER_LEN..];
+                    }
+                    return Ok(container);
+                }
+                Err(err) => return Err(err),
+            };
+            rest = remaining;
+
+            match &tag {
+                b"CICP" => container.cicp = Some(payload),
+                b"ICCP" => container.iccp = Some(payload),
+                b"EXIF" => container.exif = Some(payload),
+                b"XMP " => container.xmp = Some(payload),
+                b"APPD" => container.custom = Some(payload),
+                b"QSUM" => container.checksum = Some(payload),
+                b"QPIX" => {
+                    container.tiles = payload;
+                    found_tiles = true;
+                }
+                b"QEND" => break,
+                // Unknown chunks are skipped.
+                _ => {}
+            }
+        }
+
+        if !found_tiles && !tolerant {
+            return Err(invalid_data());
+        }
+        Ok(container)
+    }
+}
+
+/// Copies QOIR image data, inserting a chunk before the first chunk tagged
+/// `before`, for encoders that cannot write the chunk themselves.
+#[cfg(feature = "c-backend")]
+pub(crate) fn insert_chunk(
+    data: &[u8],
+    before: [u8; 4],
+    tag: [u8; 4],
+    payload: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut dst = Vec::with_capacity(data.len() + CHUNK_HEADER_LEN + payload.len());
+    let mut rest = data;
+    loop {
+        let (chunk_tag, _, remaining) = next_chunk(rest)?;
+        if chunk_tag == before {
+            write_chunk(&mut dst, tag, payload);
+            dst.extend_from_slice(rest);
+            return Ok(dst);
+        }
+        dst.extend_from_slice(&rest[..rest.len() - remaining.len()]);
+        rest = remaining;
+    }
+}
+
+/// Appends a chunk with the given tag and payload to `dst`.
+pub(crate) fn write_chunk(dst: &mut Vec<u8>, tag: [u8; 4], payload: &[u8]) {
+    dst.extend_from_slice(&tag);
+    dst.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    dst.extend_from_slice(payload);
+}
+
+/// Splits the tile at the start of `data` (the remaining `QPIX` payload) into
+/// its format, its payload and the bytes that follow it.
+///
+/// Each tile starts with a little-endian `u32` holding the payload length in
+/// its low 24 bits and the tile format in its high 8 bits.
+pub(crate) fn next_tile(data: &[u8]) -> Result<(u8, &[u8], &[u8]), Error> {
+    if data.len() < 4 {
+        return Err(invalid_data());
+    }
+
+    let word = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let len = (word & 0x00FF_FFFF) as usize;
+    let rest = &data[4..];
+    if len > rest.len() {
+        return Err(invalid_data());
+    }
+    Ok(((word >> 24) as u8, &rest[..len], &rest[len..]))
+}
+
+/// Appends a tile of `pixels` opaque black pixels, as RUNL opcodes, to stand
+/// in for a tile that was not read.
+#[cfg(feature = "object-store")]
+pub(crate) fn write_blank_tile(dst: &mut Vec<u8>, pixels: usize) {
+    const TILE_FORMAT_OPCODES: u32 = 1;
+    const OP_RUNL: u8 = 0xD7;
+
+    let runs = pixels.div_ceil(256);
+    dst.extend_from_slice(&((TILE_FORMAT_OPCODES << 24) | (2 * runs) as u32).to_le_bytes());
+    for run in 0..runs {
+        let len = (pixels - run * 256).min(256);
+        dst.extend_from_slice(&[OP_RUNL, (len - 1) as u8]);
+    }
+}
+
+/// A chunk's tag and payload, followed by the bytes after the chunk.
+type Chunk<'a> = ([u8; 4], &'a [u8], &'a [u8]);
+
+/// Splits the chunk at the start of `data` into its tag, its payload and the
+/// bytes that follow it.
+pub(crate) fn next_chunk(data: &[u8]) -> Result<Chunk<'_>, Error> {
+    if data.len() < CHUNK_HEADER_LEN {
+        return Err(invalid_data());
+    }
+
+    let tag = [data[0], data[1], data[2], data[3]];
+    let mut len = [0; 8];
+    len.copy_from_slice(&data[4..CHUNK_HEADER_LEN]);
+    let len = usize::try_from(u64::from_le_bytes(len)).map_err(|_| invalid_data())?;
+
+    let rest = &data[CHUNK_HEADER_LEN..];
+    if len > rest.len() {
+        return Err(invalid_data());
+    }
+    Ok((tag, &rest[..len], &rest[len..]))
+}
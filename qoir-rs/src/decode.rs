@@ -1,13 +1,35 @@
 use crate::{
-    DecodeOptions, DecodedImage, DecodedResult, Error, Image, PixelFormat, Rectangle,
+    DecodeOptions, DecodedImage, Error, Image, MetadataSelection, OwnedDecodedImage, PixelFormat,
+};
+#[cfg(feature = "std")]
+use crate::Rectangle;
+#[cfg(feature = "c-backend")]
+use crate::{Timings, timings::Stopwatch};
+#[cfg(feature = "c-backend")]
+use crate::{
+    DecodedResult,
     bindings::{
-        qoir_decode, qoir_decode_options, qoir_decode_pixel_configuration, qoir_decode_result,
+        dispatch_qoir_decode, qoir_decode_options, qoir_decode_pixel_configuration,
+        qoir_decode_result,
     },
 };
-use std::{io::Read, path::Path, sync::Arc};
+#[cfg(feature = "c-backend")]
+use alloc::sync::Arc;
+#[cfg(any(feature = "std", feature = "c-backend"))]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
 
 /// Decodes QOIR image data from a byte slice.
 ///
+/// This uses the C library unless the crate is built with only the
+/// `rust-backend` feature. Tolerant decoding, see
+/// `DecodeOptions::tolerant`, always uses the Rust backend, and fails with
+/// `Error::InvalidParameter` when it is not built in.
+///
 /// # Arguments
 ///
 /// * `data`: A slice of bytes containing the QOIR encoded image data.
@@ -37,32 +59,182 @@ pub fn decode_from_memory<'a>(
     data: &'_ [u8],
     options: DecodeOptions,
 ) -> Result<DecodedImage<'a>, Error> {
+    #[cfg(feature = "metrics")]
+    let start = std::time::Instant::now();
+
+    options.limits.check(data, options.pixel_format)?;
+    #[cfg(feature = "std")]
+    let result = match options.fit_within {
+        Some(size) => decode_fitted(data, options, size),
+        None => decode_unscaled(data, options),
+    };
+    #[cfg(not(feature = "std"))]
+    let result = if options.fit_within.is_some() {
+        Err(Error::InvalidParameter)
+    } else {
+        decode_unscaled(data, options)
+    };
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_decode(
+        data.len(),
+        result.as_ref().map(|decoded| decoded.image.pixels.len()),
+        start.elapsed(),
+    );
+    result
+}
+
+/// Decodes with the backend `decode_from_memory` picks, ignoring
+/// `DecodeOptions::fit_within`.
+fn decode_unscaled<'a>(data: &[u8], options: DecodeOptions) -> Result<DecodedImage<'a>, Error> {
+    let metadata = options.metadata;
+    let result = {
+        #[cfg(all(feature = "c-backend", feature = "rust-backend"))]
+        if options.tolerant {
+            crate::rust_backend::decode_from_memory(data, options)
+        } else {
+            c_decode_from_memory(data, options)
+        }
+
+        #[cfg(all(feature = "c-backend", not(feature = "rust-backend")))]
+        if options.tolerant {
+            // Only the Rust backend can recover from corrupt data.
+            Err(Error::InvalidParameter)
+        } else {
+            c_decode_from_memory(data, options)
+        }
+
+        #[cfg(not(feature = "c-backend"))]
+        {
+            crate::rust_backend::decode_from_memory(data, options)
+        }
+    };
+    result.map(|decoded| decoded.select_metadata(metadata))
+}
+
+/// Decodes the largest image that fits within `max_width` by `max_height`,
+/// for `DecodeOptions::fit_within`.
+///
+/// Only the header is read to size the output. An embedded thumbnail at
+/// least that large is scaled down instead of the image, so the image's
+/// pixels are never decoded; otherwise only the tiles under `src_clip_rect`
+/// are, and that region is scaled.
+#[cfg(feature = "std")]
+fn decode_fitted<'a>(
+    data: &[u8],
+    options: DecodeOptions,
+    (max_width, max_height): (u32, u32),
+) -> Result<DecodedImage<'a>, Error> {
+    if max_width == 0
+        || max_height == 0
+        || options.dst_clip_rect.is_some()
+        || options.offset_x != 0
+        || options.offset_y != 0
+    {
+        return Err(Error::InvalidParameter);
+    }
+    let (width, height, _) = decode_basic_metadata(data)?;
+    let region = match options.src_clip_rect {
+        Some(rect) => Rectangle {
+            x0: rect.x0.clamp(0, width as i32),
+            y0: rect.y0.clamp(0, height as i32),
+            x1: rect.x1.clamp(0, width as i32),
+            y1: rect.y1.clamp(0, height as i32),
+        },
+        None => Rectangle {
+            x0: 0,
+            y0: 0,
+            x1: width as i32,
+            y1: height as i32,
+        },
+    };
+    if region.x0 >= region.x1 || region.y0 >= region.y1 {
+        return Err(Error::InvalidParameter);
+    }
+    let region_size = ((region.x1 - region.x0) as u32, (region.y1 - region.y0) as u32);
+    let size = if region_size.0 <= max_width && region_size.1 <= max_height {
+        region_size
+    } else {
+        let resampling = crate::resize::Resampling::new(
+            region_size.0,
+            region_size.1,
+            max_width,
+            max_height,
+            crate::ResizeMode::Fit,
+            crate::ResizeFilter::Lanczos3,
+        );
+        (resampling.width, resampling.height)
+    };
+
+    let thumbnail = if options.src_clip_rect.is_none() && !options.tolerant && size != (width, height) {
+        crate::extract_thumbnail(data)?
+    } else {
+        None
+    };
+    if let Some(thumbnail) = thumbnail {
+        let (thumbnail_width, thumbnail_height, _) = decode_basic_metadata(thumbnail)?;
+        if thumbnail_width >= size.0 && thumbnail_height >= size.1 {
+            let thumbnail_options = DecodeOptions {
+                pixel_format: options.pixel_format,
+                collect_timings: options.collect_timings,
+                metadata: MetadataSelection::NONE,
+                ..Default::default()
+            };
+            let decoded = decode_unscaled(thumbnail, thumbnail_options)?
+                .with_metadata_of(data, options.metadata)?;
+            let region = Rectangle {
+                x0: 0,
+                y0: 0,
+                x1: thumbnail_width as i32,
+                y1: thumbnail_height as i32,
+            };
+            return decoded.scaled(region, size);
+        }
+    }
+    decode_unscaled(data, options)?.scaled(region, size)
+}
+
+#[cfg(feature = "c-backend")]
+fn c_decode_from_memory<'a>(
+    data: &'_ [u8],
+    options: DecodeOptions,
+) -> Result<DecodedImage<'a>, Error> {
+    let collect_timings = options.collect_timings;
+    let mut clock = Stopwatch::start(collect_timings);
+    // The C library skips unknown chunks, so the pairs are read separately,
+    // walking the chunks like the Rust backend's header parse does.
+    let custom_metadata = crate::read_custom_metadata(data)?;
+    let header_parse = clock.lap();
     let options = qoir_decode_options {
         pixfmt: options.pixel_format as u32,
         offset_x: options.offset_x,
         offset_y: options.offset_y,
         use_src_clip_rectangle: options.src_clip_rect.is_some(),
         use_dst_clip_rectangle: options.dst_clip_rect.is_some(),
-        src_clip_rectangle: options.src_clip_rect.unwrap_or(Rectangle::zero()),
-        dst_clip_rectangle: options.dst_clip_rect.unwrap_or(Rectangle::zero()),
+        src_clip_rectangle: options.src_clip_rect.unwrap_or_default().into(),
+        dst_clip_rectangle: options.dst_clip_rect.unwrap_or_default().into(),
         ..Default::default()
     };
     let decoded = unsafe {
-        qoir_decode(
+        dispatch_qoir_decode(
             data.as_ptr(),
             data.len(),
             &options as *const qoir_decode_options,
         )
     };
 
-    if !decoded.status_message.is_null() {
-        let error_message = (unsafe { std::ffi::CStr::from_ptr(decoded.status_message) })
-            .to_string_lossy()
-            .into_owned();
-        return Err(Error::DecodingFailed(error_message));
+    if let Some(message) = crate::ffi::error_message(decoded.status_message, "decode") {
+        return Err(Error::DecodingFailed(message));
     }
 
-    Ok(DecodedImage::new(decoded))
+    let mut image = DecodedImage::new(decoded)?;
+    image.custom_metadata = custom_metadata;
+    image.timings = collect_timings.then(|| Timings {
+        header_parse,
+        tile_decode: clock.lap(),
+        ..Default::default()
+    });
+    Ok(image)
 }
 
 /// Decodes a QOIR image from a reader.
@@ -93,19 +265,111 @@ pub fn decode_from_memory<'a>(
 ///     }
 /// }
 /// ```
+#[cfg(feature = "std")]
 pub fn decode_from_reader<'a>(
     reader: impl Read,
     options: DecodeOptions,
 ) -> Result<DecodedImage<'a>, Error> {
+    let mut clock = crate::timings::Stopwatch::start(options.collect_timings);
     let mut data = Vec::new();
     let mut reader = std::io::BufReader::new(reader);
-    reader.read_to_end(&mut data).map_err(|_| Error::IoError)?;
-    decode_from_memory(&data, options)
+    reader.read_to_end(&mut data)?;
+    let io = clock.lap();
+    let mut decoded = decode_from_memory(&data, options)?;
+    if let Some(timings) = &mut decoded.timings {
+        timings.io = io;
+    }
+    Ok(decoded)
 }
 
+/// Decodes a QOIR image from a reader that can seek, reading only what the
+/// decode needs.
+///
+/// Unlike `decode_from_reader`, this reads the header first and checks it
+/// against `DecodeOptions::limits` before reading any more of the file. The
+/// metadata that `DecodeOptions::metadata` leaves out and unknown chunks are
+/// skipped with `seek`, and nothing after the `QEND` chunk is read.
+///
+/// # Arguments
+///
+/// * `reader`: An object implementing `std::io::Read` and `std::io::Seek`,
+///   positioned at the start of the QOIR data.
+/// * `options`: `DecodeOptions` to control the decoding process.
+///
+/// # Returns
+///
+/// A `Result` containing the `DecodedImage` or an `Error` if reading or decoding fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::{decode_from_seek, DecodeLimits, DecodeOptions, MetadataSelection};
+/// use std::fs::File;
+///
+/// let file = File::open("input.qoir").expect("Failed to open file");
+/// let options = DecodeOptions {
+///     limits: DecodeLimits { max_pixels: Some(50_000_000), ..Default::default() },
+///     metadata: MetadataSelection { icc_profile: true, ..MetadataSelection::NONE },
+///     ..Default::default()
+/// };
+/// let decoded_image = decode_from_seek(file, options).expect("Failed to decode");
+/// ```
+#[cfg(feature = "std")]
+pub fn decode_from_seek<'a>(
+    mut reader: impl Read + Seek,
+    options: DecodeOptions,
+) -> Result<DecodedImage<'a>, Error> {
+    use crate::container::{CHUNK_HEADER_LEN, invalid_data};
+
+    let mut clock = crate::timings::Stopwatch::start(options.collect_timings);
+    let mut data = Vec::new();
+    loop {
+        let mut header = [0; CHUNK_HEADER_LEN];
+        reader.read_exact(&mut header).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => invalid_data(),
+            _ => e.into(),
+        })?;
+        let tag = [header[0], header[1], header[2], header[3]];
+        let mut len = [0; 8];
+        len.copy_from_slice(&header[4..]);
+        let len = u64::from_le_bytes(len);
+        if data.is_empty() != (tag == *b"QOIR") {
+            return Err(invalid_data());
+        }
+
+        if !options.metadata.keeps(&tag) {
+            let len = i64::try_from(len).map_err(|_| invalid_data())?;
+            reader.seek(SeekFrom::Current(len))?;
+            continue;
+        }
+
+        data.extend_from_slice(&header);
+        // The payload is read without reserving its claimed length first, so
+        // a forged length can't allocate more than the file holds.
+        let read = (&mut reader).take(len).read_to_end(&mut data)?;
+        if read as u64 != len {
+            return Err(invalid_data());
+        }
+        match &tag {
+            b"QOIR" => options.limits.check(&data, options.pixel_format)?,
+            b"QEND" => break,
+            _ => {}
+        }
+    }
+    let io = clock.lap();
+
+    let mut decoded = decode_from_memory(&data, options)?;
+    if let Some(timings) = &mut decoded.timings {
+        timings.io = io;
+    }
+    Ok(decoded)
+}
 
 /// Decodes a QOIR image from a file path.
 ///
+/// The file is read with `decode_from_seek`, so an image over the limits is
+/// rejected before its pixels are read.
+///
 /// # Arguments
 ///
 /// * `path`: A path to the QOIR image file.
@@ -129,15 +393,143 @@ pub fn decode_from_reader<'a>(
 ///     }
 /// }
 /// ```
+#[cfg(feature = "std")]
 pub fn decode<'a>(
     path: impl AsRef<Path>,
     options: DecodeOptions,
 ) -> Result<DecodedImage<'a>, Error> {
     let path = path.as_ref();
-    let file = std::fs::File::open(path).map_err(|_| Error::FileNotFound)?;
-    decode_from_reader(file, options)
+    let file = std::fs::File::open(path).map_err(|e| Error::from(e).with_path(path))?;
+    decode_from_seek(std::io::BufReader::new(file), options).map_err(|e| e.with_path(path))
+}
+
+/// Decodes every QOIR image in a stream of images written one after another,
+/// such as previews appended to a file or sent over a socket or pipe.
+///
+/// Each image is read up to its `QEND` chunk, using the lengths in its chunk
+/// headers, so the stream needs no other framing. Images are read as they are
+/// iterated, not ahead of time.
+///
+/// # Arguments
+///
+/// * `reader`: An object implementing `std::io::Read` from which the QOIR
+///   images will be read.
+/// * `options`: `DecodeOptions` to decode every image with. The limits are
+///   checked against each image's header before the rest of it is read.
+///
+/// # Returns
+///
+/// An iterator over the decoded images, which ends when the stream ends
+/// between two images. An image that fails to decode is returned as an
+/// `Error` and the iteration continues with the next one. Errors reading the
+/// stream, a stream that ends inside an image, data that is not a QOIR image
+/// and an image over the limits are returned as an `Error` that ends the
+/// iteration, since the start of the next image can't be found.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::{decode_all, DecodeOptions};
+/// use std::net::TcpStream;
+///
+/// let stream = TcpStream::connect("127.0.0.1:9000").expect("Failed to connect");
+/// for decoded in decode_all(stream, DecodeOptions::default()) {
+///     let decoded = decoded.expect("Failed to decode");
+///     println!("Image decoded: {}x{}", decoded.width, decoded.height);
+/// }
+/// ```
+#[cfg(feature = "std")]
+pub fn decode_all<R: Read>(reader: R, options: DecodeOptions) -> DecodeAll<R> {
+    DecodeAll {
+        reader,
+        options,
+        done: false,
+    }
 }
 
+/// An iterator over the images in a stream, created by [`decode_all`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct DecodeAll<R> {
+    reader: R,
+    options: DecodeOptions,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> DecodeAll<R> {
+    /// Reads the chunks of the next image, up to and including its `QEND`
+    /// chunk, or returns `None` if the stream ends before it starts.
+    fn read_image(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        use crate::container::{CHUNK_HEADER_LEN, invalid_data};
+
+        let mut data = Vec::new();
+        loop {
+            let start = data.len();
+            let read = (&mut self.reader)
+                .take(CHUNK_HEADER_LEN as u64)
+                .read_to_end(&mut data)?;
+            if read == 0 && start == 0 {
+                return Ok(None);
+            }
+            if read < CHUNK_HEADER_LEN {
+                return Err(invalid_data());
+            }
+
+            let header = &data[start..];
+            let tag = [header[0], header[1], header[2], header[3]];
+            let mut len = [0; 8];
+            len.copy_from_slice(&header[4..]);
+            let len = u64::from_le_bytes(len);
+            if (start == 0) != (tag == *b"QOIR") {
+                return Err(invalid_data());
+            }
+
+            // The payload is read without reserving its claimed length first,
+            // so a forged length can't allocate more than the stream holds.
+            let read = (&mut self.reader).take(len).read_to_end(&mut data)?;
+            if read as u64 != len {
+                return Err(invalid_data());
+            }
+            if start == 0 {
+                self.options
+                    .limits
+                    .check(&data, self.options.pixel_format)?;
+            }
+            if tag == *b"QEND" {
+                return Ok(Some(data));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for DecodeAll<R> {
+    type Item = Result<OwnedDecodedImage, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_image() {
+            Ok(Some(data)) => Some(
+                decode_from_memory(&data, self.options.clone()).map(DecodedImage::into_owned),
+            ),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> core::iter::FusedIterator for DecodeAll<R> {}
+
 /// Decodes basic metadata (width, height, pixel format) from QOIR image data.
 ///
 /// This function is faster than full decoding if only metadata is needed.
@@ -166,13 +558,23 @@ pub fn decode<'a>(
 /// }
 /// ```
 pub fn decode_basic_metadata(data: &[u8]) -> Result<(u32, u32, PixelFormat), Error> {
+    #[cfg(feature = "c-backend")]
+    {
+        c_decode_basic_metadata(data)
+    }
+
+    #[cfg(not(feature = "c-backend"))]
+    {
+        crate::rust_backend::decode_basic_metadata(data)
+    }
+}
+
+#[cfg(feature = "c-backend")]
+fn c_decode_basic_metadata(data: &[u8]) -> Result<(u32, u32, PixelFormat), Error> {
     let decoded = unsafe { qoir_decode_pixel_configuration(data.as_ptr(), data.len()) };
 
-    if !decoded.status_message.is_null() {
-        let error_message = (unsafe { std::ffi::CStr::from_ptr(decoded.status_message) })
-            .to_string_lossy()
-            .into_owned();
-        return Err(Error::DecodingFailed(error_message));
+    if let Some(message) = crate::ffi::error_message(decoded.status_message, "decode") {
+        return Err(Error::DecodingFailed(message));
     }
 
     let pixel_format = PixelFormat::from(decoded.dst_pixcfg.pixfmt);
@@ -183,65 +585,210 @@ pub fn decode_basic_metadata(data: &[u8]) -> Result<(u32, u32, PixelFormat), Err
 }
 
 impl DecodedImage<'_> {
-    /// Creates a new `DecodedImage` from the raw `qoir_decode_result`.
-    ///
-    /// This is an internal function.
-    pub(crate) fn new(data: qoir_decode_result) -> Self {
-        let result = Arc::new(DecodedResult::new(data));
+    /// Copies the pixels and metadata into an `OwnedDecodedImage`, releasing the
+    /// decoder's buffer.
+    pub fn into_owned(self) -> OwnedDecodedImage {
+        OwnedDecodedImage {
+            pixels: self.image.pixels.to_vec(),
+            width: self.image.width,
+            height: self.image.height,
+            pixel_format: self.image.pixel_format,
+            stride_in_bytes: self.image.stride_in_bytes,
+            cic_profile: self.cic_profile.map(<[u8]>::to_vec),
+            icc_profile: self.icc_profile.map(<[u8]>::to_vec),
+            exif: self.exif.map(<[u8]>::to_vec),
+            xmp: self.xmp.map(<[u8]>::to_vec),
+            custom_metadata: self.custom_metadata,
+        }
+    }
+}
+
+impl<'a> DecodedImage<'a> {
+    /// Leaves out the metadata that `selection` doesn't select.
+    fn select_metadata(mut self, selection: MetadataSelection) -> Self {
+        if !selection.cic_profile {
+            self.cic_profile = None;
+        }
+        if !selection.icc_profile {
+            self.icc_profile = None;
+        }
+        if !selection.exif {
+            self.exif = None;
+        }
+        if !selection.xmp {
+            self.xmp = None;
+        }
+        if !selection.custom {
+            self.custom_metadata.clear();
+        }
+        self
+    }
 
+    /// Crops the image to `region` and scales it to `width` by `height`, for
+    /// `DecodeOptions::fit_within`.
+    #[cfg(feature = "std")]
+    fn scaled(self, region: Rectangle, (width, height): (u32, u32)) -> Result<Self, Error> {
+        let image = &self.image;
+        let bpp = image.pixel_format.bytes_per_pixel();
+        let start = region.y0 as usize * image.stride_in_bytes + region.x0 as usize * bpp;
+        let source = Image {
+            pixels: &image.pixels[start..],
+            width: (region.x1 - region.x0) as u32,
+            height: (region.y1 - region.y0) as u32,
+            pixel_format: image.pixel_format,
+            stride_in_bytes: image.stride_in_bytes,
+        };
+        if (source.width, source.height) == (width, height)
+            && (image.width, image.height) == (width, height)
+        {
+            return Ok(self);
+        }
+
+        let mut clock = crate::timings::Stopwatch::start(self.timings.is_some());
+        let resized = if (source.width, source.height) == (width, height) {
+            let row_len = width as usize * bpp;
+            let mut pixels = Vec::with_capacity(row_len * height as usize);
+            for y in 0..height as usize {
+                let row = &source.pixels[y * source.stride_in_bytes..];
+                pixels.extend_from_slice(&row[..row_len]);
+            }
+            crate::ImageBuf {
+                pixels,
+                width,
+                height,
+                pixel_format: source.pixel_format,
+                stride_in_bytes: row_len,
+            }
+        } else {
+            source.resize(
+                width,
+                height,
+                crate::ResizeMode::Exact,
+                crate::ResizeFilter::Lanczos3,
+            )?
+        };
+        // The pixels never move once they are on the heap, so the slice stays
+        // valid for as long as the `Arc` below is alive.
         let pixels = unsafe {
-            // NOTE: Verify this
-            std::slice::from_raw_parts(
-                result.result.dst_pixbuf.data as *const u8,
-                    result.result.dst_pixbuf.pixcfg.height_in_pixels as usize
-                    * result.result.dst_pixbuf.stride_in_bytes,
-            )
+            core::slice::from_raw_parts(resized.pixels.as_ptr(), resized.pixels.len())
         };
+        let mut timings = self.timings;
+        if let Some(timings) = &mut timings {
+            timings.format_convert += clock.lap();
+        }
+        Ok(DecodedImage {
+            result: alloc::sync::Arc::new(crate::DecodedResult::Resized {
+                source: self.result,
+                pixels: resized.pixels,
+            }),
+            image: Image {
+                pixels,
+                width: resized.width,
+                height: resized.height,
+                pixel_format: resized.pixel_format,
+                stride_in_bytes: resized.stride_in_bytes,
+            },
+            timings,
+            ..self
+        })
+    }
 
-        let pixel_format = PixelFormat::from(result.result.dst_pixbuf.pixcfg.pixfmt);
-        let width = result.result.dst_pixbuf.pixcfg.width_in_pixels;
-        let height = result.result.dst_pixbuf.pixcfg.height_in_pixels;
-        let stride_in_bytes = result.result.dst_pixbuf.stride_in_bytes;
+    /// Gives a decoded thumbnail copies of the metadata of the image in
+    /// `data` that `selection` selects.
+    #[cfg(feature = "std")]
+    fn with_metadata_of(self, data: &[u8], selection: MetadataSelection) -> Result<Self, Error> {
+        let metadata = crate::read_metadata(data)?;
+        let custom_metadata = if selection.custom {
+            crate::read_custom_metadata(data)?
+        } else {
+            Vec::new()
+        };
+        let copies = [
+            metadata.cic_profile,
+            metadata.icc_profile,
+            metadata.exif,
+            metadata.xmp,
+        ]
+        .map(|chunk| chunk.map(<[u8]>::to_vec));
+        // The copies never move once they are on the heap, so the slices stay
+        // valid for as long as the `Arc` below is alive.
+        let extend = |copy: &Option<Vec<u8>>| {
+            copy.as_ref()
+                .map(|copy| unsafe { core::slice::from_raw_parts(copy.as_ptr(), copy.len()) })
+        };
+        let [cic_profile, icc_profile, exif, xmp] = [0, 1, 2, 3].map(|i| extend(&copies[i]));
+        let decoded = DecodedImage {
+            result: alloc::sync::Arc::new(crate::DecodedResult::Thumbnail {
+                source: self.result,
+                metadata: copies,
+            }),
+            cic_profile,
+            icc_profile,
+            exif,
+            xmp,
+            custom_metadata,
+            ..self
+        };
+        Ok(decoded.select_metadata(selection))
+    }
+}
+
+impl OwnedDecodedImage {
+    /// Borrows the pixels as an `Image`, for example to encode them again.
+    pub fn as_image(&self) -> Image<'_> {
+        Image {
+            pixels: &self.pixels,
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pixel_format,
+            stride_in_bytes: self.stride_in_bytes,
+        }
+    }
+}
 
-        let cic_profile = if !result.result.metadata_cicp_ptr.is_null() {
+#[cfg(feature = "c-backend")]
+impl DecodedImage<'_> {
+    /// Creates a new `DecodedImage` from the raw `qoir_decode_result`.
+    ///
+    /// This is an internal function. It returns `Error::Internal`, after
+    /// freeing `data`, if the pixel buffer is inconsistent.
+    pub(crate) fn new(data: qoir_decode_result) -> Result<Self, Error> {
+        // Take ownership first, so the memory is freed if the checks fail.
+        let result = Arc::new(DecodedResult::Ffi(data));
+        let pixels = unsafe { crate::ffi::pixels(&data.dst_pixbuf)? };
+
+        let pixel_format = PixelFormat::from(data.dst_pixbuf.pixcfg.pixfmt);
+        let width = data.dst_pixbuf.pixcfg.width_in_pixels;
+        let height = data.dst_pixbuf.pixcfg.height_in_pixels;
+        let stride_in_bytes = data.dst_pixbuf.stride_in_bytes;
+
+        let cic_profile = if !data.metadata_cicp_ptr.is_null() {
             Some(unsafe {
-                std::slice::from_raw_parts(
-                    result.result.metadata_cicp_ptr,
-                    result.result.metadata_cicp_len,
-                )
+                core::slice::from_raw_parts(data.metadata_cicp_ptr, data.metadata_cicp_len)
             })
         } else {
             None
         };
 
-        let icc_profile = if !result.result.metadata_iccp_ptr.is_null() {
+        let icc_profile = if !data.metadata_iccp_ptr.is_null() {
             Some(unsafe {
-                std::slice::from_raw_parts(
-                    result.result.metadata_iccp_ptr,
-                    result.result.metadata_iccp_len,
-                )
+                core::slice::from_raw_parts(data.metadata_iccp_ptr, data.metadata_iccp_len)
             })
         } else {
             None
         };
 
-        let exif = if !result.result.metadata_exif_ptr.is_null() {
+        let exif = if !data.metadata_exif_ptr.is_null() {
             Some(unsafe {
-                std::slice::from_raw_parts(
-                    result.result.metadata_exif_ptr,
-                    result.result.metadata_exif_len,
-                )
+                core::slice::from_raw_parts(data.metadata_exif_ptr, data.metadata_exif_len)
             })
         } else {
             None
         };
 
-        let xmp = if !result.result.metadata_xmp_ptr.is_null() {
+        let xmp = if !data.metadata_xmp_ptr.is_null() {
             Some(unsafe {
-                std::slice::from_raw_parts(
-                    result.result.metadata_xmp_ptr,
-                    result.result.metadata_xmp_len,
-                )
+                core::slice::from_raw_parts(data.metadata_xmp_ptr, data.metadata_xmp_len)
             })
         } else {
             None
@@ -255,13 +802,18 @@ impl DecodedImage<'_> {
             stride_in_bytes,
         };
 
-        Self {
+        Ok(Self {
+            // The slices above point into this allocation, which is only freed
+            // once the last clone of the image is dropped.
             result,
             image,
             cic_profile,
             icc_profile,
             exif,
             xmp,
-        }
+            custom_metadata: Vec::new(),
+            missing_regions: Vec::new(),
+            timings: None,
+        })
     }
 }
@@ -0,0 +1,240 @@
+//! Deltas between successive frames that store only the tiles that changed,
+//! for screen captures and bursts where consecutive frames are nearly
+//! identical.
+//!
+//! A delta uses the chunk layout of a QOIR file. It starts with a `QDLT`
+//! chunk holding the frame width and height as little-endian `u32`s and a
+//! bitmap of the 64x64 tiles, one bit per tile in row-major order starting
+//! from the low bit of the first byte, where set bits mark the tiles that
+//! changed. A `QIMG` chunk follows unless no tile changed. It holds a
+//! complete QOIR image one tile wide, with the changed tiles stacked top to
+//! bottom in bitmap order. The delta ends with an empty `QEND` chunk.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::delta::{apply_delta, encode_delta};
+//! use qoir_rs::{EncodeOptions, ImageBuf};
+//!
+//! # fn run(frames: &[ImageBuf]) -> Result<(), qoir_rs::Error> {
+//! let delta = encode_delta(&frames[0].as_image(), &frames[1].as_image(), EncodeOptions::default())?;
+//!
+//! let mut frame = frames[0].clone();
+//! apply_delta(&mut frame, &delta)?;
+//! assert_eq!(frame, frames[1]);
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::{string::ToString, vec, vec::Vec};
+
+use crate::container::{Container, TILE_SIZE, next_chunk, write_chunk};
+use crate::{
+    DecodeOptions, EncodeOptions, Error, Image, ImageBuf, PixelFormat, decode_from_memory,
+    encode_to_memory,
+};
+
+const SIZE_LEN: usize = 8;
+
+fn invalid_data() -> Error {
+    Error::DecodingFailed("#qoir-delta: invalid data".to_string())
+}
+
+/// Encodes the tiles of `next` that differ from `prev`.
+///
+/// The changed tiles are encoded with the lossiness and dithering of
+/// `options`; its metadata is ignored. With lossy options, decoding a chain
+/// of deltas accumulates error unless each delta is taken against the frame
+/// the receiver reconstructed, rather than the original.
+///
+/// # Arguments
+///
+/// * `prev`: The frame the delta will be applied to.
+/// * `next`: The frame the delta reproduces.
+/// * `options`: `EncodeOptions` for the changed tiles.
+///
+/// # Returns
+///
+/// A `Result` containing the delta, `Error::InvalidParameter` if the frames
+/// differ in size or pixel format or their pixel buffers are too short, or an
+/// `Error` if encoding fails.
+pub fn encode_delta(prev: &Image, next: &Image, options: EncodeOptions) -> Result<Vec<u8>, Error> {
+    if (prev.width, prev.height, prev.pixel_format) != (next.width, next.height, next.pixel_format)
+        || next.pixel_format == PixelFormat::Invalid
+    {
+        return Err(Error::InvalidParameter);
+    }
+    let grid = TileGrid::new(next.width, next.height, next.pixel_format);
+    let mut bitmap = vec![0; grid.count().div_ceil(8)];
+    let mut changed = Vec::new();
+    for index in 0..grid.count() {
+        for row in 0..grid.tile_height(index) {
+            if grid.row(prev, index, row)? != grid.row(next, index, row)? {
+                bitmap[index / 8] |= 1 << (index % 8);
+                changed.push(index);
+                break;
+            }
+        }
+    }
+
+    let mut payload = Vec::with_capacity(SIZE_LEN + bitmap.len());
+    payload.extend_from_slice(&next.width.to_le_bytes());
+    payload.extend_from_slice(&next.height.to_le_bytes());
+    payload.extend_from_slice(&bitmap);
+    let mut delta = Vec::new();
+    write_chunk(&mut delta, *b"QDLT", &payload);
+
+    if !changed.is_empty() {
+        let stride = grid.strip_width() as usize * grid.bytes_per_pixel;
+        let mut strip = vec![0; stride * grid.strip_slot_height() as usize * changed.len()];
+        for (slot, &index) in changed.iter().enumerate() {
+            for row in 0..grid.tile_height(index) {
+                let src = grid.row(next, index, row)?;
+                let start = (slot * grid.strip_slot_height() as usize + row) * stride;
+                strip[start..start + src.len()].copy_from_slice(src);
+            }
+        }
+        let strip = Image {
+            pixels: &strip,
+            width: grid.strip_width(),
+            height: grid.strip_slot_height() * changed.len() as u32,
+            pixel_format: next.pixel_format,
+            stride_in_bytes: stride,
+        };
+        let options = EncodeOptions {
+            lossiness: options.lossiness,
+            dither: options.dither,
+            ..Default::default()
+        };
+        write_chunk(&mut delta, *b"QIMG", encode_to_memory(strip, options)?.data);
+    }
+    write_chunk(&mut delta, *b"QEND", &[]);
+    Ok(delta)
+}
+
+/// Applies a delta made by [`encode_delta`] to `base`, in place.
+///
+/// `base` may use any pixel format; the changed tiles are converted to it.
+///
+/// # Returns
+///
+/// `Ok(())`, `Error::InvalidParameter` if `base` differs in size from the
+/// delta's frames or its pixel buffer is too short, or an `Error` if the
+/// delta is invalid.
+pub fn apply_delta(base: &mut ImageBuf, delta: &[u8]) -> Result<(), Error> {
+    let (tag, payload, mut rest) = next_chunk(delta)?;
+    if tag != *b"QDLT" || payload.len() < SIZE_LEN {
+        return Err(invalid_data());
+    }
+    let width = u32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+    let height = u32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    let bitmap = &payload[SIZE_LEN..];
+    let grid = TileGrid::new(width, height, base.pixel_format);
+    if bitmap.len() != grid.count().div_ceil(8) {
+        return Err(invalid_data());
+    }
+    if (base.width, base.height) != (width, height) || base.pixel_format == PixelFormat::Invalid {
+        return Err(Error::InvalidParameter);
+    }
+    let row_len = width as usize * grid.bytes_per_pixel;
+    if height > 0 && (height as usize - 1) * base.stride_in_bytes + row_len > base.pixels.len() {
+        return Err(Error::InvalidParameter);
+    }
+    let changed: Vec<usize> = (0..grid.count())
+        .filter(|index| bitmap[index / 8] & (1 << (index % 8)) != 0)
+        .collect();
+
+    // Every chunk is checked before `base` is touched, so that an invalid
+    // delta leaves it unchanged.
+    let mut strip = None;
+    if !changed.is_empty() {
+        let (tag, data, remaining) = next_chunk(rest)?;
+        rest = remaining;
+        if tag != *b"QIMG" {
+            return Err(invalid_data());
+        }
+        let header = Container::parse(data)?.header;
+        let strip_height = grid.strip_slot_height() as u64 * changed.len() as u64;
+        if (header.width, header.height as u64) != (grid.strip_width(), strip_height) {
+            return Err(invalid_data());
+        }
+        strip = Some(data);
+    }
+    let (tag, _, rest) = next_chunk(rest)?;
+    if tag != *b"QEND" || !rest.is_empty() {
+        return Err(invalid_data());
+    }
+
+    if let Some(data) = strip {
+        let options = DecodeOptions {
+            pixel_format: base.pixel_format,
+            ..Default::default()
+        };
+        let strip = decode_from_memory(data, options)?;
+        let stride = strip.image.stride_in_bytes;
+        for (slot, &index) in changed.iter().enumerate() {
+            for row in 0..grid.tile_height(index) {
+                let (start, len) = grid.row_range(base.stride_in_bytes, index, row);
+                let src = (slot * grid.strip_slot_height() as usize + row) * stride;
+                base.pixels[start..start + len]
+                    .copy_from_slice(&strip.image.pixels[src..src + len]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The tiles of a frame and where their pixels are.
+struct TileGrid {
+    width: u32,
+    height: u32,
+    tiles_x: usize,
+    bytes_per_pixel: usize,
+}
+
+impl TileGrid {
+    fn new(width: u32, height: u32, pixel_format: PixelFormat) -> Self {
+        TileGrid {
+            width,
+            height,
+            tiles_x: width.div_ceil(TILE_SIZE) as usize,
+            bytes_per_pixel: pixel_format.bytes_per_pixel(),
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.tiles_x * self.height.div_ceil(TILE_SIZE) as usize
+    }
+
+    fn strip_width(&self) -> u32 {
+        self.width.min(TILE_SIZE)
+    }
+
+    /// The height each tile takes in the strip of changed tiles.
+    fn strip_slot_height(&self) -> u32 {
+        self.height.min(TILE_SIZE)
+    }
+
+    fn tile_height(&self, index: usize) -> usize {
+        let y0 = (index / self.tiles_x) as u32 * TILE_SIZE;
+        (self.height - y0).min(TILE_SIZE) as usize
+    }
+
+    /// The byte offset and length of one row of a tile in a frame with the
+    /// given stride.
+    fn row_range(&self, stride_in_bytes: usize, index: usize, row: usize) -> (usize, usize) {
+        let x0 = (index % self.tiles_x) as u32 * TILE_SIZE;
+        let y = (index / self.tiles_x) * TILE_SIZE as usize + row;
+        let start = y * stride_in_bytes + x0 as usize * self.bytes_per_pixel;
+        let len = (self.width - x0).min(TILE_SIZE) as usize * self.bytes_per_pixel;
+        (start, len)
+    }
+
+    fn row<'a>(&self, image: &Image<'a>, index: usize, row: usize) -> Result<&'a [u8], Error> {
+        let (start, len) = self.row_range(image.stride_in_bytes, index, row);
+        image
+            .pixels
+            .get(start..start + len)
+            .ok_or(Error::InvalidParameter)
+    }
+}
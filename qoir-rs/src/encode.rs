@@ -1,15 +1,26 @@
-use std::{io::Write, path::Path, sync::Arc};
+use alloc::{sync::Arc, vec::Vec};
+#[cfg(feature = "std")]
+use std::{io::Write, path::Path};
 
+use crate::{EncodeOptions, EncodedBuffer, EncodedResult, Error, Image};
+#[cfg(feature = "c-backend")]
+use crate::{Timings, timings::Stopwatch};
+#[cfg(feature = "c-backend")]
 use crate::{
-    EncodeOptions, EncodedBuffer, EncodedResult, Error, Image,
     bindings::{
-        qoir_encode, qoir_encode_options, qoir_encode_result, qoir_pixel_buffer,
-        qoir_pixel_buffer_struct, qoir_pixel_configuration,
+        dispatch_qoir_encode, qoir_encode_options, qoir_pixel_buffer, qoir_pixel_buffer_struct,
+        qoir_pixel_configuration,
     },
+    checksum::crc32c,
+    container::{Container, insert_chunk},
 };
 
 /// Encodes an `Image` into QOIR format in memory.
 ///
+/// This uses the C library unless the crate is built with only the
+/// `rust-backend` feature. If `options.quantize` is set, the colors are
+/// first reduced with [`quantize`](crate::quantize()).
+///
 /// # Arguments
 ///
 /// * `image`: The `Image` to encode.
@@ -46,29 +57,104 @@ pub fn encode_to_memory<'a>(
     image: Image<'_>,
     options: EncodeOptions,
 ) -> Result<EncodedBuffer<'a>, Error> {
+    #[cfg(feature = "metrics")]
+    let (start, bytes_in) = (std::time::Instant::now(), image.pixels.len());
+
+    let result = {
+        #[cfg(feature = "c-backend")]
+        {
+            c_encode_to_memory(image, options)
+        }
+
+        #[cfg(not(feature = "c-backend"))]
+        {
+            crate::rust_backend::encode_to_memory(image, options)
+        }
+    };
+
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_encode(
+        bytes_in,
+        result.as_ref().map(|encoded| encoded.data.len()),
+        start.elapsed(),
+    );
+    result
+}
+
+/// Encodes an `Image` into QOIR format, returning the encoded data as a `Vec`.
+///
+/// This is `encode_to_memory` followed by [`EncodedBuffer::into_vec`]. Both
+/// backends encode into a `Vec`, the C library through an allocator the crate
+/// gives it, which is returned as it is rather than copied as `.data.to_vec()`
+/// would.
+///
+/// # Arguments
+///
+/// * `image`: The `Image` to encode.
+/// * `options`: `EncodeOptions` to control the encoding process.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded data or an `Error` if encoding fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::{encode_to_vec, EncodeOptions, Image, PixelFormat};
+///
+/// let pixels = vec![0u8; 64 * 64 * 4];
+/// let image = Image::from_raw(&pixels, 64, 64, PixelFormat::RGBANonPremul).expect("Too few pixels");
+/// let data: Vec<u8> = encode_to_vec(image, EncodeOptions::default()).expect("Failed to encode");
+/// ```
+pub fn encode_to_vec(image: Image<'_>, options: EncodeOptions) -> Result<Vec<u8>, Error> {
+    encode_to_memory(image, options).map(EncodedBuffer::into_vec)
+}
+
+#[cfg(feature = "c-backend")]
+fn c_encode_to_memory<'a>(
+    image: Image<'_>,
+    options: EncodeOptions,
+) -> Result<EncodedBuffer<'a>, Error> {
+    let collect_timings = options.collect_timings;
+    let mut clock = Stopwatch::start(collect_timings);
+    let thumbnail =
+        crate::thumbnail::encode_thumbnail(&image, options.embed_thumbnail, c_encode_to_memory)?;
+    let quantized = match &options.quantize {
+        Some(quantize) => Some(crate::quantize(&image, quantize)?),
+        None => None,
+    };
+    let image = quantized.as_ref().map_or(image, |buf| buf.as_image());
+    let format_convert = clock.lap();
+    let custom = crate::metadata::write_custom(&options.custom_metadata)?;
+    let embed_checksum = options.embed_checksum;
+
+    let mut allocator = VecAllocator::default();
     let options = qoir_encode_options {
         metadata_cicp_ptr: options
             .cicp_profile
             .as_deref()
-            .map_or(std::ptr::null(), |s| s.as_ptr()),
+            .map_or(core::ptr::null(), |s| s.as_ptr()),
         metadata_cicp_len: options.cicp_profile.as_deref().map_or(0, |s| s.len()),
         metadata_iccp_ptr: options
             .icc_profile
             .as_deref()
-            .map_or(std::ptr::null(), |s| s.as_ptr()),
+            .map_or(core::ptr::null(), |s| s.as_ptr()),
         metadata_iccp_len: options.icc_profile.as_deref().map_or(0, |s| s.len()),
         metadata_exif_ptr: options
             .exif
             .as_deref()
-            .map_or(std::ptr::null(), |s| s.as_ptr()),
+            .map_or(core::ptr::null(), |s| s.as_ptr()),
         metadata_exif_len: options.exif.as_deref().map_or(0, |s| s.len()),
         metadata_xmp_ptr: options
             .xmp
             .as_deref()
-            .map_or(std::ptr::null(), |s| s.as_ptr()),
+            .map_or(core::ptr::null(), |s| s.as_ptr()),
         metadata_xmp_len: options.xmp.as_deref().map_or(0, |s| s.len()),
         lossiness: options.lossiness as u32,
         dither: options.dither,
+        contextual_malloc_func: Some(VecAllocator::malloc),
+        contextual_free_func: Some(VecAllocator::free),
+        memory_func_context: (&raw mut allocator).cast(),
         ..Default::default()
     };
 
@@ -83,20 +169,122 @@ pub fn encode_to_memory<'a>(
     };
 
     let result = unsafe {
-        qoir_encode(
+        dispatch_qoir_encode(
             &pix_buff as *const qoir_pixel_buffer_struct,
             &options as *const qoir_encode_options,
         )
     };
 
-    if !result.status_message.is_null() {
-        let error_message = (unsafe { std::ffi::CStr::from_ptr(result.status_message) })
-            .to_string_lossy()
-            .into_owned();
-        return Err(Error::EncodingFailed(error_message));
+    if let Some(message) = crate::ffi::error_message(result.status_message, "encode") {
+        return Err(Error::EncodingFailed(message));
+    }
+
+    let mut data = allocator.take(result.owned_memory, result.dst_ptr, result.dst_len)?;
+    let tile_encode = clock.lap();
+    // The C library has no extension chunks, so they are spliced in.
+    if !custom.is_empty() {
+        data = insert_chunk(&data, *b"QPIX", *b"APPD", &custom)?;
+    }
+    if let Some(thumbnail) = &thumbnail {
+        data = insert_chunk(&data, *b"QPIX", *b"THMB", thumbnail)?;
     }
+    if embed_checksum {
+        let checksum = crc32c(Container::parse(&data)?.tiles).to_le_bytes();
+        data = insert_chunk(&data, *b"QEND", *b"QSUM", &checksum)?;
+    }
+    let mut encoded = EncodedBuffer::from_vec(data);
+    encoded.timings = collect_timings.then(|| Timings {
+        tile_encode,
+        chunk_write: clock.lap(),
+        format_convert,
+        ..Default::default()
+    });
+    Ok(encoded)
+}
 
-    Ok(EncodedBuffer::new(result))
+/// The allocator the C encoder is given, so that the buffer it encodes into is
+/// a `Vec` that can be returned without copying.
+///
+/// Each allocation is a `Vec` with room to align the pointer handed to C as
+/// `malloc` would. Allocations the encoder does not free, such as its output,
+/// are freed when the allocator is dropped unless taken out with `take`.
+#[cfg(feature = "c-backend")]
+#[derive(Default)]
+struct VecAllocator {
+    /// The live allocations, as the pointer handed to C and its `Vec`.
+    live: Vec<(*mut u8, Vec<u8>)>,
+}
+
+#[cfg(feature = "c-backend")]
+impl VecAllocator {
+    /// The alignment of `malloc` on 64-bit platforms.
+    const ALIGN: usize = 16;
+
+    unsafe extern "C" fn malloc(
+        context: *mut core::ffi::c_void,
+        len: usize,
+    ) -> *mut core::ffi::c_void {
+        // SAFETY: the context is the `VecAllocator` that outlives the call to
+        // the encoder.
+        let allocator = unsafe { &mut *context.cast::<VecAllocator>() };
+        let mut buffer = Vec::<u8>::new();
+        let Some(capacity) = len.checked_add(Self::ALIGN - 1) else {
+            return core::ptr::null_mut();
+        };
+        if buffer.try_reserve_exact(capacity).is_err() {
+            return core::ptr::null_mut();
+        }
+        let offset = buffer.as_ptr().align_offset(Self::ALIGN);
+        // SAFETY: the offset is less than `ALIGN`, within the capacity.
+        let ptr = unsafe { buffer.as_mut_ptr().add(offset) };
+        allocator.live.push((ptr, buffer));
+        ptr.cast()
+    }
+
+    unsafe extern "C" fn free(context: *mut core::ffi::c_void, ptr: *mut core::ffi::c_void) {
+        // SAFETY: as for `malloc`.
+        let allocator = unsafe { &mut *context.cast::<VecAllocator>() };
+        if let Some(index) = allocator.live.iter().position(|(p, _)| *p == ptr.cast()) {
+            allocator.live.swap_remove(index);
+        }
+    }
+
+    /// Takes the allocation `owned` out of the allocator, as a `Vec` of the
+    /// `len` bytes at `ptr` within it.
+    fn take(
+        &mut self,
+        owned: *mut core::ffi::c_void,
+        ptr: *const u8,
+        len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        if len == 0 {
+            return Ok(Vec::new());
+        }
+        let index = self
+            .live
+            .iter()
+            .position(|(p, _)| *p == owned.cast())
+            .ok_or_else(|| crate::ffi::internal("memory it did not allocate"))?;
+        let (_, mut buffer) = self.live.swap_remove(index);
+        let base = buffer.as_mut_ptr();
+        let start = (ptr as usize).wrapping_sub(base as usize);
+        if start
+            .checked_add(len)
+            .is_none_or(|end| end > buffer.capacity())
+        {
+            return Err(crate::ffi::internal("encoded data outside its buffer"));
+        }
+        // SAFETY: the encoder wrote the `len` bytes at `ptr`, which lie within
+        // the buffer's capacity. They are moved to its start only if `malloc`
+        // had to align the pointer, which the global allocator rarely needs.
+        unsafe {
+            if start != 0 {
+                core::ptr::copy(ptr, base, len);
+            }
+            buffer.set_len(len);
+        }
+        Ok(buffer)
+    }
 }
 
 /// Encodes an `Image` into QOIR format and writes it to a `Write` implementor.
@@ -137,16 +325,21 @@ pub fn encode_to_memory<'a>(
 ///     }
 /// }
 /// ```
+#[cfg(feature = "std")]
 pub fn encode_to_writer<'a>(
     image: Image<'_>,
     options: EncodeOptions,
     writer: impl Write,
 ) -> Result<EncodedBuffer<'a>, Error> {
-    let encoded_buffer = encode_to_memory(image, options)?;
+    let mut encoded_buffer = encode_to_memory(image, options)?;
+    let mut clock = crate::timings::Stopwatch::start(encoded_buffer.timings.is_some());
     let mut writer = std::io::BufWriter::new(writer);
-    writer
-        .write_all(encoded_buffer.data)
-        .map_err(|_| Error::IoError)?;
+    writer.write_all(encoded_buffer.data)?;
+    // Dropping a `BufWriter` ignores errors, so flush it explicitly
+    writer.flush()?;
+    if let Some(timings) = &mut encoded_buffer.timings {
+        timings.io = clock.lap();
+    }
     Ok(encoded_buffer)
 }
 
@@ -186,28 +379,44 @@ pub fn encode_to_writer<'a>(
 ///     }
 /// }
 /// ```
+#[cfg(feature = "std")]
 pub fn encode<'a>(
     image: Image<'_>,
     options: EncodeOptions,
     path: impl AsRef<Path>,
 ) -> Result<EncodedBuffer<'a>, Error> {
-    let file = std::fs::File::create(path).map_err(|_| Error::IoError)?;
-    encode_to_writer(image, options, file)
+    let path = path.as_ref();
+    let file = std::fs::File::create(path).map_err(|e| Error::from(e).with_path(path))?;
+    encode_to_writer(image, options, file).map_err(|e| e.with_path(path))
 }
 
 impl EncodedBuffer<'_> {
-    /// Creates a new `EncodedBuffer` from the raw `qoir_encode_result`.
-    ///
-    /// This is an internal function.
-    pub(crate) fn new(buffer: qoir_encode_result) -> Self {
-        let buffer = EncodedResult::new(buffer);
-        let data = unsafe {
-            std::slice::from_raw_parts(buffer.result.dst_ptr as *const u8, buffer.result.dst_len)
-        };
+    /// Creates a new `EncodedBuffer` that keeps an encoded `Vec` alive.
+    pub(crate) fn from_vec(buffer: Vec<u8>) -> Self {
+        // The bytes never move once they are on the heap, so the slice stays
+        // valid for as long as the `Arc` below is alive.
+        let data = unsafe { core::slice::from_raw_parts(buffer.as_ptr(), buffer.len()) };
 
         EncodedBuffer {
-            result: Arc::new(buffer),
+            result: Arc::new(EncodedResult::Owned(buffer)),
             data,
+            timings: None,
+        }
+    }
+
+    /// Moves the encoded data into a `Vec`.
+    ///
+    /// Both backends encode into a `Vec`, the C library through an allocator
+    /// the crate gives it, so that `Vec` is returned without copying unless
+    /// the buffer has been cloned.
+    pub fn into_vec(self) -> Vec<u8> {
+        let data = self.data;
+        match Arc::try_unwrap(self.result) {
+            Ok(mut result) => match &mut result {
+                EncodedResult::Owned(buffer) => core::mem::take(buffer),
+            },
+            // Another clone keeps the memory alive while it is copied.
+            Err(_shared) => data.to_vec(),
         }
     }
 }
@@ -0,0 +1,241 @@
+//! Writing EXIF data for [`EncodeOptions::exif`](crate::EncodeOptions::exif).
+//!
+//! Images rendered or composited from scratch have no EXIF to copy over, but
+//! viewers still want their orientation and capture time. [`ExifBuilder`]
+//! serializes the few tags such pipelines set into the TIFF structure that the
+//! `EXIF` chunk holds.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::Error;
+
+const TAG_MAKE: u16 = 0x010F;
+const TAG_MODEL: u16 = 0x0110;
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_SOFTWARE: u16 = 0x0131;
+const TAG_DATE_TIME: u16 = 0x0132;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_EXIF_VERSION: u16 = 0x9000;
+const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+/// The length of the TIFF header, after which the first directory starts.
+const TIFF_HEADER_LEN: usize = 8;
+
+/// Builds the EXIF data of an image, in the little-endian TIFF layout that
+/// `EncodeOptions::exif` expects.
+///
+/// # Examples
+///
+/// ```
+/// use qoir_rs::{EncodeOptions, ExifBuilder};
+///
+/// let exif = ExifBuilder::new()
+///     .orientation(6)
+///     .date_time("2024:05:17 14:03:59")
+///     .software("render-farm 2.1")
+///     .build()
+///     .expect("Invalid EXIF");
+/// let options = EncodeOptions {
+///     exif: Some(exif),
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExifBuilder {
+    orientation: Option<u16>,
+    date_time: Option<String>,
+    make: Option<String>,
+    model: Option<String>,
+    software: Option<String>,
+}
+
+impl ExifBuilder {
+    /// Creates a builder without any tags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the orientation, from 1 (upright) to 8, as numbered by the EXIF
+    /// `Orientation` tag.
+    pub fn orientation(mut self, orientation: u16) -> Self {
+        self.orientation = Some(orientation);
+        self
+    }
+
+    /// Sets when the image was taken or created, as `YYYY:MM:DD HH:MM:SS`.
+    /// It is written both as the `DateTime` and the `DateTimeOriginal` tag.
+    pub fn date_time(mut self, date_time: impl Into<String>) -> Self {
+        self.date_time = Some(date_time.into());
+        self
+    }
+
+    /// Sets the manufacturer of the camera.
+    pub fn make(mut self, make: impl Into<String>) -> Self {
+        self.make = Some(make.into());
+        self
+    }
+
+    /// Sets the model of the camera.
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Sets the name and version of the software that created the image.
+    pub fn software(mut self, software: impl Into<String>) -> Self {
+        self.software = Some(software.into());
+        self
+    }
+
+    /// Serializes the tags that were set.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the EXIF data, or `Error::InvalidParameter` if
+    /// the orientation is not between 1 and 8, the date and time is not in
+    /// the `YYYY:MM:DD HH:MM:SS` format, or a string is not ASCII or contains
+    /// a NUL character.
+    pub fn build(&self) -> Result<Vec<u8>, Error> {
+        if self.orientation.is_some_and(|o| !(1..=8).contains(&o)) {
+            return Err(Error::InvalidParameter);
+        }
+        if self
+            .date_time
+            .as_deref()
+            .is_some_and(|date_time| !is_exif_date_time(date_time))
+        {
+            return Err(Error::InvalidParameter);
+        }
+
+        let mut exif_ifd = Vec::new();
+        exif_ifd.push((TAG_EXIF_VERSION, Value::Undefined(b"0232")));
+        if let Some(date_time) = &self.date_time {
+            exif_ifd.push((TAG_DATE_TIME_ORIGINAL, ascii(date_time)?));
+        }
+
+        let mut ifd0 = Vec::new();
+        if let Some(make) = &self.make {
+            ifd0.push((TAG_MAKE, ascii(make)?));
+        }
+        if let Some(model) = &self.model {
+            ifd0.push((TAG_MODEL, ascii(model)?));
+        }
+        if let Some(orientation) = self.orientation {
+            ifd0.push((TAG_ORIENTATION, Value::Short(orientation)));
+        }
+        if let Some(software) = &self.software {
+            ifd0.push((TAG_SOFTWARE, ascii(software)?));
+        }
+        if let Some(date_time) = &self.date_time {
+            ifd0.push((TAG_DATE_TIME, ascii(date_time)?));
+        }
+        // The Exif directory goes right after the first one, which grows by
+        // the 12 bytes of the entry pointing at it.
+        let exif_offset = TIFF_HEADER_LEN + ifd_len(&ifd0) + 12;
+        ifd0.push((TAG_EXIF_IFD, Value::Long(offset(exif_offset)?)));
+
+        let mut data = Vec::with_capacity(exif_offset + ifd_len(&exif_ifd));
+        data.extend_from_slice(b"II*\0");
+        data.extend_from_slice(&(TIFF_HEADER_LEN as u32).to_le_bytes());
+        write_ifd(&mut data, &ifd0)?;
+        debug_assert_eq!(data.len(), exif_offset);
+        write_ifd(&mut data, &exif_ifd)?;
+        Ok(data)
+    }
+}
+
+/// The value of a TIFF directory entry.
+enum Value<'a> {
+    /// A NUL-terminated string.
+    Ascii(&'a [u8]),
+    Short(u16),
+    Long(u32),
+    Undefined(&'a [u8]),
+}
+
+impl Value<'_> {
+    /// The TIFF field type and the number of values.
+    fn type_and_count(&self) -> (u16, usize) {
+        match self {
+            Value::Ascii(s) => (2, s.len() + 1),
+            Value::Short(_) => (3, 1),
+            Value::Long(_) => (4, 1),
+            Value::Undefined(bytes) => (7, bytes.len()),
+        }
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self {
+            Value::Ascii(s) => [s, &b"\0"[..]].concat(),
+            Value::Short(n) => n.to_le_bytes().to_vec(),
+            Value::Long(n) => n.to_le_bytes().to_vec(),
+            Value::Undefined(bytes) => bytes.to_vec(),
+        }
+    }
+}
+
+fn ascii(s: &str) -> Result<Value<'_>, Error> {
+    if !s.is_ascii() || s.contains('\0') {
+        return Err(Error::InvalidParameter);
+    }
+    Ok(Value::Ascii(s.as_bytes()))
+}
+
+fn is_exif_date_time(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() == 19
+        && b.iter().enumerate().all(|(i, &c)| match i {
+            4 | 7 => c == b':',
+            10 => c == b' ',
+            13 | 16 => c == b':',
+            _ => c.is_ascii_digit(),
+        })
+}
+
+fn offset(offset: usize) -> Result<u32, Error> {
+    u32::try_from(offset).map_err(|_| Error::InvalidParameter)
+}
+
+/// The length of a directory, with the values that don't fit in its entries
+/// each padded to an even length.
+fn ifd_len(entries: &[(u16, Value)]) -> usize {
+    entries
+        .iter()
+        .map(|(_, value)| value.bytes().len())
+        .filter(|&len| len > 4)
+        .map(|len| len + len % 2)
+        .sum::<usize>()
+        + 2
+        + 12 * entries.len()
+        + 4
+}
+
+/// Appends a directory without a next one, followed by the values that
+/// don't fit in its entries. The entries must be sorted by tag.
+fn write_ifd(data: &mut Vec<u8>, entries: &[(u16, Value)]) -> Result<(), Error> {
+    let mut values_offset = data.len() + 2 + 12 * entries.len() + 4;
+    let mut values = Vec::new();
+    data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (tag, value) in entries {
+        let (field_type, count) = value.type_and_count();
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&field_type.to_le_bytes());
+        data.extend_from_slice(&offset(count)?.to_le_bytes());
+        let mut bytes = value.bytes();
+        if bytes.len() <= 4 {
+            bytes.resize(4, 0);
+            data.extend_from_slice(&bytes);
+        } else {
+            data.extend_from_slice(&offset(values_offset)?.to_le_bytes());
+            if bytes.len() % 2 == 1 {
+                bytes.push(0);
+            }
+            values_offset += bytes.len();
+            values.extend_from_slice(&bytes);
+        }
+    }
+    // There is no next directory.
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&values);
+    Ok(())
+}
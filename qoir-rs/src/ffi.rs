@@ -0,0 +1,85 @@
+//! Checks on the results of C library calls, so that a failure or a bug there
+//! becomes an `Error` rather than undefined behavior.
+
+use alloc::string::String;
+use core::ffi::{CStr, c_char};
+
+use crate::bindings::qoir_pixel_buffer;
+use crate::{Error, PixelFormat};
+
+/// Reads the status message of a C library call, logging it with the `log`
+/// feature.
+///
+/// The C library follows the Wuffs convention, where a message starting with
+/// `@` is a note that leaves the result usable. Anything else, such as a `#`
+/// error, fails the call.
+///
+/// # Arguments
+///
+/// * `status_message`: The `status_message` field of the call's result.
+/// * `operation`: What the call was doing, such as `"decode"`, for the log.
+///
+/// # Returns
+///
+/// The message if the call failed, or `None` if it succeeded.
+pub(crate) fn error_message(status_message: *const c_char, operation: &str) -> Option<String> {
+    if status_message.is_null() {
+        return None;
+    }
+    let message = (unsafe { CStr::from_ptr(status_message) })
+        .to_string_lossy()
+        .into_owned();
+    if message.starts_with('@') {
+        #[cfg(feature = "log")]
+        log::warn!(target: "qoir_rs", "C library {}: {}", operation, message);
+        #[cfg(not(feature = "log"))]
+        let _ = operation;
+        None
+    } else {
+        #[cfg(feature = "log")]
+        log::debug!(target: "qoir_rs", "C library {} failed: {}", operation, message);
+        Some(message)
+    }
+}
+
+/// Makes a slice over the pixels of a pixel buffer the C library returned.
+///
+/// The buffer is checked first: a null pointer, zero dimensions with pixel
+/// data, an unknown pixel format or a stride too short for a row all return
+/// `Error::Internal`.
+///
+/// # Safety
+///
+/// If the checks pass, `data` must point to `height * stride` readable bytes
+/// that stay valid for `'a`.
+pub(crate) unsafe fn pixels<'a>(pixbuf: &qoir_pixel_buffer) -> Result<&'a [u8], Error> {
+    let width = pixbuf.pixcfg.width_in_pixels as usize;
+    let height = pixbuf.pixcfg.height_in_pixels as usize;
+    let stride = pixbuf.stride_in_bytes;
+    let len = height
+        .checked_mul(stride)
+        .ok_or_else(|| internal("pixel buffer size overflows"))?;
+
+    if width == 0 || height == 0 {
+        return if len == 0 {
+            Ok(&[])
+        } else {
+            Err(internal("empty image with pixel data"))
+        };
+    }
+    let pixel_format = PixelFormat::from(pixbuf.pixcfg.pixfmt);
+    if pixel_format == PixelFormat::Invalid {
+        return Err(internal("unknown pixel format"));
+    }
+    if width.checked_mul(pixel_format.bytes_per_pixel()).is_none_or(|row| stride < row) {
+        return Err(internal("stride shorter than a row"));
+    }
+    if pixbuf.data.is_null() {
+        return Err(internal("null pixel buffer"));
+    }
+    Ok(unsafe { core::slice::from_raw_parts(pixbuf.data, len) })
+}
+
+pub(crate) fn internal(message: &str) -> Error {
+    Error::Internal(alloc::format!("#qoir: C library returned {}", message))
+}
@@ -0,0 +1,86 @@
+//! Serving encoded images over HTTP, enabled with the `http` feature.
+//!
+//! An [`EncodedBuffer`] can be turned into a [`Bytes`] that keeps the encoder's
+//! buffer alive instead of copying it, and from there into a response body
+//! for hyper or any other server built on the `http` crate. With the `axum`
+//! feature, an `EncodedBuffer` can also be returned directly from an axum
+//! handler.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::{EncodeOptions, Image, encode_to_memory};
+//!
+//! # fn handler(image: Image<'_>) -> http::Response<http_body_util::Full<bytes::Bytes>> {
+//! let encoded = encode_to_memory(image, EncodeOptions::default()).expect("Failed to encode");
+//! encoded.into_http_response()
+//! # }
+//! ```
+
+use bytes::Bytes;
+use http_body_util::Full;
+
+use crate::EncodedBuffer;
+
+/// The media type used for QOIR images.
+///
+/// QOIR has no registered type, so this is an unregistered `x-` subtype.
+pub const CONTENT_TYPE: &str = "image/x-qoir";
+
+/// Keeps an `EncodedBuffer`'s memory alive for as long as a `Bytes` points
+/// into it.
+struct Owner(EncodedBuffer<'static>);
+
+impl AsRef<[u8]> for Owner {
+    fn as_ref(&self) -> &[u8] {
+        self.0.data
+    }
+}
+
+impl EncodedBuffer<'static> {
+    /// Converts the encoded data into `Bytes` without copying it.
+    pub fn into_bytes(self) -> Bytes {
+        Bytes::from_owner(Owner(self))
+    }
+
+    /// Converts the encoded data into a response body without copying it.
+    pub fn into_http_body(self) -> Full<Bytes> {
+        Full::new(self.into_bytes())
+    }
+
+    /// Builds a `200 OK` response with the encoded data as its body and its
+    /// `Content-Type` and `Content-Length` headers set.
+    pub fn into_http_response(self) -> http::Response<Full<Bytes>> {
+        let headers = self.http_headers();
+        let mut response = http::Response::new(self.into_http_body());
+        *response.headers_mut() = headers;
+        response
+    }
+}
+
+impl EncodedBuffer<'_> {
+    /// The `Content-Type` and `Content-Length` headers for the encoded data.
+    pub fn http_headers(&self) -> http::HeaderMap {
+        headers(self.data.len())
+    }
+}
+
+/// The `Content-Type` and `Content-Length` headers for `len` bytes of QOIR
+/// data, such as a file being streamed.
+pub fn headers(len: usize) -> http::HeaderMap {
+    let mut headers = http::HeaderMap::with_capacity(2);
+    headers.insert(
+        http::header::CONTENT_TYPE,
+        http::HeaderValue::from_static(CONTENT_TYPE),
+    );
+    headers.insert(http::header::CONTENT_LENGTH, http::HeaderValue::from(len));
+    headers
+}
+
+#[cfg(feature = "axum")]
+impl axum_core::response::IntoResponse for EncodedBuffer<'static> {
+    fn into_response(self) -> axum_core::response::Response {
+        let headers = self.http_headers();
+        (headers, axum_core::body::Body::from(self.into_bytes())).into_response()
+    }
+}
@@ -0,0 +1,136 @@
+use alloc::vec::Vec;
+
+use crate::container::{Container, TILE_SIZE, invalid_data, next_tile};
+use crate::{Error, PixelFormat, Rectangle};
+
+/// How the pixels of a tile are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TileCodec {
+    /// Uncompressed BGRA bytes.
+    Literals,
+    /// QOIR opcodes.
+    Opcodes,
+    /// LZ4 compressed BGRA bytes.
+    Lz4Literals,
+    /// LZ4 compressed QOIR opcodes.
+    Lz4Opcodes,
+}
+
+impl TileCodec {
+    pub(crate) fn from_format(format: u8) -> Option<Self> {
+        match format {
+            0 => Some(TileCodec::Literals),
+            1 => Some(TileCodec::Opcodes),
+            2 => Some(TileCodec::Lz4Literals),
+            3 => Some(TileCodec::Lz4Opcodes),
+            _ => None,
+        }
+    }
+}
+
+/// Where a tile sits in the image and in the encoded data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TileInfo {
+    /// The pixels covered by the tile.
+    pub rect: Rectangle,
+    /// The byte offset of the tile's payload in the encoded data.
+    pub offset: usize,
+    /// The length of the tile's payload in bytes, excluding its 4-byte header.
+    pub compressed_len: usize,
+    /// How the tile's pixels are stored.
+    pub codec: TileCodec,
+}
+
+/// The layout of a QOIR image, as returned by [`inspect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QoirLayout {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// The pixel format the image was encoded with: `BGRX`, `BGRANonPremul`
+    /// or `BGRAPremul`.
+    pub pixel_format: PixelFormat,
+    /// The number of low bits dropped from each color channel, from 0 to 7.
+    pub lossiness: u8,
+    /// The width and height of a full tile. Tiles on the right and bottom
+    /// edges may be smaller.
+    pub tile_size: u32,
+    /// The total payload length of the CICP, ICC, EXIF, XMP and `APPD` chunks.
+    pub metadata_len: usize,
+    /// The tiles in the order they are stored: left to right, then top to
+    /// bottom.
+    pub tiles: Vec<TileInfo>,
+}
+
+/// Reads the layout of QOIR image data without decoding any pixels.
+///
+/// This walks the chunks and tile headers only, which makes it cheap enough
+/// to find out which tiles bloat a file, or where to fetch a tile from.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+///
+/// # Returns
+///
+/// A `Result` containing the `QoirLayout` or an `Error` if the data is not a
+/// valid QOIR image.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::inspect;
+///
+/// let qoir_data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+/// let layout = inspect(&qoir_data).expect("Failed to inspect");
+/// let largest = layout.tiles.iter().max_by_key(|tile| tile.compressed_len);
+/// println!("Largest tile: {:?}", largest);
+/// ```
+pub fn inspect(data: &[u8]) -> Result<QoirLayout, Error> {
+    let container = Container::parse(data)?;
+    let header = container.header;
+
+    let metadata_len = [
+        container.cicp,
+        container.iccp,
+        container.exif,
+        container.xmp,
+        container.custom,
+    ]
+    .iter()
+    .flatten()
+    .map(|payload| payload.len())
+    .sum();
+
+    let mut tiles = Vec::new();
+    let mut rest = container.tiles;
+    for ty in (0..header.height).step_by(TILE_SIZE as usize) {
+        for tx in (0..header.width).step_by(TILE_SIZE as usize) {
+            let (format, payload, remaining) = next_tile(rest)?;
+            rest = remaining;
+
+            tiles.push(TileInfo {
+                rect: Rectangle {
+                    x0: tx as i32,
+                    y0: ty as i32,
+                    x1: (tx + TILE_SIZE).min(header.width) as i32,
+                    y1: (ty + TILE_SIZE).min(header.height) as i32,
+                },
+                offset: payload.as_ptr() as usize - data.as_ptr() as usize,
+                compressed_len: payload.len(),
+                codec: TileCodec::from_format(format).ok_or_else(invalid_data)?,
+            });
+        }
+    }
+
+    Ok(QoirLayout {
+        width: header.width,
+        height: header.height,
+        pixel_format: header.pixel_format,
+        lossiness: header.lossiness,
+        tile_size: TILE_SIZE,
+        metadata_len,
+        tiles,
+    })
+}
@@ -0,0 +1,177 @@
+//! Conversions to and from the `rgb` and `imgref` crates, enabled with the
+//! `rgb` feature.
+//!
+//! Many Rust imaging crates, such as `ravif`, `resize` and `dssim`, take
+//! images as an `ImgRef` of `rgb` pixels. Those are plain bytes in the same
+//! order as the matching [`PixelFormat`], so the conversions here reuse the
+//! pixel buffer instead of copying it whenever the formats agree.
+
+use alloc::vec::Vec;
+use imgref::{ImgRef, ImgVec};
+use rgb::alt::{BGR8, BGRA8};
+use rgb::{RGB8, RGBA8};
+
+use crate::{Error, Image, ImageBuf, PixelFormat};
+
+/// An `rgb` pixel type with the same layout as one of the pixel formats.
+///
+/// `RGBA8` and `BGRA8` hold non-premultiplied alpha, as the crates that use
+/// them expect.
+pub trait RgbPixel: bytemuck::Pod {
+    /// The pixel format with this layout.
+    const PIXEL_FORMAT: PixelFormat;
+}
+
+impl RgbPixel for RGB8 {
+    const PIXEL_FORMAT: PixelFormat = PixelFormat::RGB;
+}
+
+impl RgbPixel for RGBA8 {
+    const PIXEL_FORMAT: PixelFormat = PixelFormat::RGBANonPremul;
+}
+
+impl RgbPixel for BGR8 {
+    const PIXEL_FORMAT: PixelFormat = PixelFormat::BGR;
+}
+
+impl RgbPixel for BGRA8 {
+    const PIXEL_FORMAT: PixelFormat = PixelFormat::BGRANonPremul;
+}
+
+impl<'data> Image<'data> {
+    /// Borrows the pixels of an `ImgRef` as an `Image`, without copying.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use imgref::ImgVec;
+    /// use qoir_rs::{encode, EncodeOptions, Image};
+    /// use rgb::RGBA8;
+    ///
+    /// let img = ImgVec::new(vec![RGBA8::new(255, 0, 0, 255); 64 * 64], 64, 64);
+    /// let image = Image::from_imgref(img.as_ref());
+    /// encode(image, EncodeOptions::default(), "red.qoir").expect("Failed to encode");
+    /// ```
+    pub fn from_imgref<P: RgbPixel>(img: ImgRef<'data, P>) -> Self {
+        let pixels: &[u8] = bytemuck::cast_slice(img.buf());
+        let bpp = P::PIXEL_FORMAT.bytes_per_pixel();
+        // The buffer may end right after the last pixel of the last row.
+        let len = if img.height() == 0 {
+            0
+        } else {
+            (img.stride() * (img.height() - 1) + img.width()) * bpp
+        };
+        Image {
+            pixels: &pixels[..len.min(pixels.len())],
+            width: img.width() as u32,
+            height: img.height() as u32,
+            pixel_format: P::PIXEL_FORMAT,
+            stride_in_bytes: img.stride() * bpp,
+        }
+    }
+
+    /// Borrows the pixels as an `ImgRef`, without copying.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ImgRef`, or `Error::InvalidParameter` if
+    /// the pixel format does not match `P`, the stride is not a whole number
+    /// of pixels or the pixel buffer is too small. Use `to_imgvec` to convert
+    /// from other formats.
+    pub fn as_imgref<P: RgbPixel>(&self) -> Result<ImgRef<'data, P>, Error> {
+        let bpp = P::PIXEL_FORMAT.bytes_per_pixel();
+        if self.pixel_format != P::PIXEL_FORMAT || !self.stride_in_bytes.is_multiple_of(bpp) {
+            return Err(Error::InvalidParameter);
+        }
+        self.check_buffer()?;
+
+        let pixels = &self.pixels[..self.pixels.len() / bpp * bpp];
+        // `ImgRef` needs a stride of at least one row, even for empty images.
+        let stride = (self.stride_in_bytes / bpp).max(self.width as usize).max(1);
+        Ok(ImgRef::new_stride(
+            bytemuck::cast_slice(pixels),
+            self.width as usize,
+            self.height as usize,
+            stride,
+        ))
+    }
+
+    /// Copies the pixels into a tightly packed `ImgVec`, converting them
+    /// from the image's pixel format as needed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ImgVec`, or `Error::InvalidParameter` if
+    /// the pixel format is `Invalid` or the pixel buffer is too small.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use qoir_rs::{decode, DecodeOptions};
+    /// use rgb::RGBA8;
+    ///
+    /// let decoded = decode("input.qoir", DecodeOptions::default()).expect("Failed to decode");
+    /// let img = decoded.image.to_imgvec::<RGBA8>().expect("Failed to convert");
+    /// ```
+    pub fn to_imgvec<P: RgbPixel>(&self) -> Result<ImgVec<P>, Error> {
+        let pixels = self.to_pixel_format(P::PIXEL_FORMAT)?;
+        Ok(ImgVec::new_stride(
+            cast_pixels(pixels),
+            self.width as usize,
+            self.height as usize,
+            (self.width as usize).max(1),
+        ))
+    }
+}
+
+impl ImageBuf {
+    /// Converts the image into an `ImgVec`. The pixel buffer is reused when
+    /// the pixel format matches `P` and the stride is a whole number of
+    /// pixels; otherwise the pixels are converted as in
+    /// [`Image::to_imgvec`].
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ImgVec`, or `Error::InvalidParameter` if
+    /// the pixel format is `Invalid` or the pixel buffer is too small.
+    pub fn into_imgvec<P: RgbPixel>(self) -> Result<ImgVec<P>, Error> {
+        let bpp = P::PIXEL_FORMAT.bytes_per_pixel();
+        if self.pixel_format != P::PIXEL_FORMAT
+            || !self.stride_in_bytes.is_multiple_of(bpp)
+            || !self.pixels.len().is_multiple_of(bpp)
+        {
+            return self.as_image().to_imgvec();
+        }
+        self.as_image().check_buffer()?;
+
+        let stride = (self.stride_in_bytes / bpp).max(self.width as usize).max(1);
+        Ok(ImgVec::new_stride(
+            cast_pixels(self.pixels),
+            self.width as usize,
+            self.height as usize,
+            stride,
+        ))
+    }
+}
+
+impl<P: RgbPixel> From<ImgVec<P>> for ImageBuf {
+    /// Takes over the pixel buffer of an `ImgVec` without copying.
+    fn from(img: ImgVec<P>) -> Self {
+        let (width, height, stride) = (img.width(), img.height(), img.stride());
+        let bpp = P::PIXEL_FORMAT.bytes_per_pixel();
+        ImageBuf {
+            pixels: bytemuck::allocation::cast_vec(img.into_buf()),
+            width: width as u32,
+            height: height as u32,
+            pixel_format: P::PIXEL_FORMAT,
+            stride_in_bytes: stride * bpp,
+        }
+    }
+}
+
+/// Reinterprets bytes as pixels, reusing the allocation when its capacity is
+/// a whole number of pixels and copying otherwise.
+fn cast_pixels<P: RgbPixel>(bytes: Vec<u8>) -> Vec<P> {
+    bytemuck::allocation::try_cast_vec(bytes)
+        .unwrap_or_else(|(_, bytes)| bytemuck::allocation::pod_collect_to_vec(&bytes))
+}
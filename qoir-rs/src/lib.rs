@@ -11,7 +11,7 @@
 //! - Access to image metadata (width, height, pixel format).
 //! - Support for various pixel formats.
 //! - Control over decoding options like clipping and offset.
-//! - Control over encoding options like lossiness and dithering.
+//! - Control over encoding options like lossiness, dithering and color quantization.
 //!
 //! ## Getting Started
 //!
@@ -93,14 +93,264 @@
 //! ```
 //!
 //! For more detailed examples, see the documentation for the specific functions and structs.
+//!
+//! ## `no_std` support
+//!
+//! The in-memory functions (`decode_from_memory`, `decode_basic_metadata` and
+//! `encode_to_memory`) only need `alloc`. Disable the default `std` feature to
+//! build for `no_std` targets; the file and reader/writer functions are then
+//! unavailable.
+//!
+//! ## Backends
+//!
+//! By default the crate compiles and links the C `qoir` library
+//! (`c-backend`). The `rust-backend` feature adds a pure-Rust decoder and
+//! encoder in the `rust_backend` module. Building with only `rust-backend`
+//! removes the need for a C toolchain; the top-level functions then use the
+//! Rust implementation. With `lz4-flex`, the Rust backend compresses tiles
+//! with the `lz4_flex` crate. With `rayon`, it encodes and decodes bands of
+//! tiles in parallel on a pool configured with [`ThreadPoolBuilder`]. With
+//! `gpu`, `rust_backend::gpu` decodes images straight into wgpu textures.
+//!
+//! The `system-qoir` feature links an installed `libqoir` located with
+//! pkg-config instead of the vendored C sources, and `runtime-simd` adds an
+//! AVX2 build of the C library that is picked at run time on CPUs supporting
+//! it. [`build_info`] reports which of these were built in.
+//!
+//! ## Metrics
+//!
+//! The `metrics` feature reports counts, byte totals, durations and failures
+//! of every decode and encode through the `metrics` facade. See the `metrics`
+//! module for the names.
+//!
+//! ## Logging
+//!
+//! The `log` feature logs the status messages of the C library through the
+//! `log` facade, under the `qoir_rs` target: failures at debug level, with the
+//! exact message before it becomes an `Error`, and non-fatal notes at warn
+//! level. `tracing` subscribers receive them through `tracing-log`.
+//!
+//! ## QOI
+//!
+//! The `qoi` feature adds the `qoi` module, which reads and writes plain QOI
+//! images using the same `Image` and `PixelFormat` types.
+//!
+//! ## Caching
+//!
+//! `DecodeCache` holds decoded images up to a memory budget, evicting the
+//! least recently used ones, for viewers that decode the same files again.
+//!
+//! ## Animations
+//!
+//! The `anim` module stores a sequence of QOIR frames, such as a burst of
+//! previews, in one file with shared metadata and a duration per frame.
+//!
+//! ## Frame deltas
+//!
+//! The `delta` module encodes only the tiles that changed between two frames
+//! and applies such a delta to the previous frame in place.
+//!
+//! ## Pyramids
+//!
+//! The `pyramid` module, which requires `std`, stores an image together with
+//! its half, quarter and smaller sizes, so that a viewer can decode just the
+//! resolution and region it shows.
+//!
+//! ## Smart previews
+//!
+//! The `preview` module, which requires `std`, stores a few renditions of an
+//! image, such as a lossy preview and the lossless original, in one file and
+//! picks the one that suits a display width.
+//!
+//! ## RAW previews
+//!
+//! The `raw` feature adds the `raw` module, which encodes the JPEG preview
+//! embedded in a camera RAW file as QOIR, carrying its EXIF over. Files
+//! without one are developed if their sensor data is uncompressed.
+//!
+//! ## TurboJPEG
+//!
+//! The `turbojpeg` feature decodes and encodes JPEGs with the system
+//! libjpeg-turbo and adds `transcode_jpeg_to_qoir`, the fast path from camera
+//! JPEGs to QOIR.
+//!
+//! ## Bundles
+//!
+//! The `bundle` module, which requires `std`, packs many QOIR images into one
+//! file with an index of their names, so that any of them can be read with a
+//! single seek.
+//!
+//! ## Windows
+//!
+//! The `windows` feature adds the `windows` module, which decodes straight
+//! into the top-down BGRA layout of a Windows bitmap and describes it with a
+//! `BITMAPINFOHEADER`, for GDI and Direct2D viewers.
+//!
+//! ## macOS and iOS
+//!
+//! The `apple` feature adds the `apple` module, which lays decoded pixels out
+//! the way Core Graphics draws them fastest. On Apple platforms,
+//! `DecodedImage::to_cgimage` turns them into a `CGImage` for AppKit and
+//! UIKit.
+//!
+//! ## HTTP
+//!
+//! With the `http` feature, an `EncodedBuffer` converts into a `Bytes` or an
+//! HTTP response without copying the encoded data, and the `http` module has
+//! the QOIR `Content-Type`. The `axum` feature lets handlers return an
+//! `EncodedBuffer` directly.
+
+#![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(not(any(feature = "c-backend", feature = "rust-backend")))]
+compile_error!("enable at least one of the `c-backend` and `rust-backend` features");
+
+extern crate alloc;
+
+#[cfg(feature = "c-backend")]
 mod bindings;
+#[cfg(feature = "c-backend")]
+mod ffi;
 
 mod types;
 pub use types::*;
 
+mod container;
+
+mod checksum;
+
 mod decode;
 pub use decode::*;
 
+mod timings;
+pub use timings::Timings;
+
+mod build_info;
+pub use build_info::*;
+
 mod encode;
 pub use encode::*;
+
+mod inspect;
+pub use inspect::*;
+
+mod metadata;
+pub use metadata::*;
+
+mod exif_builder;
+pub use exif_builder::*;
+
+mod thumbnail;
+pub use thumbnail::*;
+
+mod verify;
+pub use verify::*;
+
+#[cfg(feature = "rayon")]
+mod thread_pool;
+#[cfg(feature = "rayon")]
+pub use thread_pool::*;
+
+#[cfg(feature = "metrics")]
+pub mod metrics;
+
+mod pixel;
+
+mod typed;
+pub use typed::*;
+
+mod phash;
+pub use phash::*;
+
+mod quantize;
+pub use quantize::*;
+
+mod transform;
+
+#[cfg(feature = "std")]
+mod resize;
+#[cfg(feature = "std")]
+pub use resize::*;
+
+#[cfg(feature = "std")]
+mod linear;
+#[cfg(feature = "std")]
+pub use linear::*;
+
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+pub use stats::*;
+
+#[cfg(feature = "std")]
+mod cache;
+#[cfg(feature = "std")]
+pub use cache::*;
+
+#[cfg(feature = "rust-backend")]
+pub mod rust_backend;
+#[cfg(feature = "rust-backend")]
+pub use rust_backend::{decode_progressive, decode_streaming};
+
+#[cfg(all(feature = "std", feature = "rust-backend"))]
+mod pool;
+#[cfg(all(feature = "std", feature = "rust-backend"))]
+pub use pool::*;
+
+#[cfg(feature = "bumpalo")]
+mod arena;
+#[cfg(feature = "bumpalo")]
+pub use arena::*;
+
+#[cfg(feature = "qoi")]
+pub mod qoi;
+
+pub mod anim;
+
+pub mod delta;
+
+#[cfg(feature = "std")]
+pub mod pyramid;
+
+#[cfg(feature = "std")]
+pub mod preview;
+
+#[cfg(feature = "std")]
+pub mod bundle;
+
+#[cfg(feature = "raw")]
+pub mod raw;
+
+#[cfg(feature = "turbojpeg")]
+pub mod turbojpeg;
+#[cfg(feature = "turbojpeg")]
+pub use turbojpeg::transcode_jpeg_to_qoir;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+#[cfg(feature = "object-store")]
+pub mod object_store;
+
+#[cfg(all(feature = "uring", target_os = "linux"))]
+pub mod uring;
+
+#[cfg(feature = "windows")]
+pub mod windows;
+
+#[cfg(feature = "apple")]
+pub mod apple;
+
+#[cfg(feature = "rgb")]
+mod interop;
+#[cfg(feature = "rgb")]
+pub use interop::*;
+
+#[cfg(all(target_arch = "wasm32", feature = "c-backend"))]
+mod wasm_libc;
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "capi")]
+pub mod capi;
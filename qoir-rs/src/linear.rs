@@ -0,0 +1,229 @@
+//! Conversions between sRGB-encoded 8-bit pixels and linear light.
+//!
+//! Pixels are stored gamma-encoded, so averaging them, as resizing and
+//! compositing do, darkens fine detail and the edges between light and dark
+//! areas. Converting to linear light first and back afterwards avoids that.
+//! Both directions go through lookup tables that are built on first use.
+
+use alloc::{vec, vec::Vec};
+use std::sync::OnceLock;
+
+use crate::pixel::{convert, to_bgra};
+use crate::resize::Resampling;
+use crate::{Error, Image, ImageBuf, PixelFormat, ResizeFilter, ResizeMode};
+
+/// The number of entries in the linear to sRGB table. It is the smallest
+/// power of two for which every 8-bit value survives a round trip.
+const TO_SRGB_LEN: usize = 4096;
+
+fn to_linear_table() -> &'static [f32; 256] {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        core::array::from_fn(|i| {
+            let c = i as f32 / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        })
+    })
+}
+
+fn to_srgb_table() -> &'static [u8; TO_SRGB_LEN] {
+    static TABLE: OnceLock<[u8; TO_SRGB_LEN]> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        core::array::from_fn(|i| {
+            let l = i as f32 / (TO_SRGB_LEN - 1) as f32;
+            let c = if l <= 0.003_130_8 {
+                l * 12.92
+            } else {
+                1.055 * l.powf(1.0 / 2.4) - 0.055
+            };
+            (c * 255.0).round() as u8
+        })
+    })
+}
+
+/// Converts an sRGB-encoded channel to linear light, from 0.0 to 1.0.
+pub fn srgb_to_linear(value: u8) -> f32 {
+    to_linear_table()[value as usize]
+}
+
+/// Converts a linear light value to an sRGB-encoded channel. Values outside
+/// 0.0 to 1.0 are clamped.
+pub fn linear_to_srgb(value: f32) -> u8 {
+    let index = (value.clamp(0.0, 1.0) * (TO_SRGB_LEN - 1) as f32 + 0.5) as usize;
+    to_srgb_table()[index]
+}
+
+/// An image in linear light, with four `f32` channels per pixel.
+///
+/// The channels are in RGBA order from 0.0 to 1.0, with the color channels
+/// premultiplied by alpha so that they can be averaged and composited
+/// directly. Rows are tightly packed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinearImage {
+    /// The pixels, row by row.
+    pub pixels: Vec<[f32; 4]>,
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+}
+
+impl Image<'_> {
+    /// Converts the image to linear light.
+    ///
+    /// Alpha is not gamma-encoded, so it is only scaled to 0.0 to 1.0.
+    /// Formats without alpha are opaque.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `LinearImage`, or `Error::InvalidParameter`
+    /// if the pixel format is `Invalid` or the pixel buffer is too small.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use qoir_rs::{decode, DecodeOptions, ResizeFilter, ResizeMode};
+    ///
+    /// let decoded = decode("input.qoir", DecodeOptions::default()).expect("Failed to decode");
+    /// let preview = decoded
+    ///     .image
+    ///     .to_linear_f32()
+    ///     .and_then(|linear| linear.resize(512, 512, ResizeMode::Fit, ResizeFilter::Lanczos3))
+    ///     .and_then(|linear| linear.to_srgb_u8(decoded.image.pixel_format))
+    ///     .expect("Failed to resize");
+    /// ```
+    pub fn to_linear_f32(&self) -> Result<LinearImage, Error> {
+        if self.pixel_format == PixelFormat::Invalid {
+            return Err(Error::InvalidParameter);
+        }
+        self.check_buffer()?;
+
+        let has_alpha = self.pixel_format.has_alpha();
+        let premultiplied = matches!(
+            self.pixel_format,
+            PixelFormat::BGRAPremul | PixelFormat::RGBAPremul
+        );
+        let bpp = self.pixel_format.bytes_per_pixel();
+        let row_len = self.width as usize * bpp;
+        let mut pixels = Vec::with_capacity(self.width as usize * self.height as usize);
+        for y in 0..self.height as usize {
+            let row = &self.pixels[y * self.stride_in_bytes..][..row_len];
+            for src in row.chunks_exact(bpp) {
+                let mut bgra = to_bgra(src, self.pixel_format);
+                if !has_alpha {
+                    bgra[3] = 0xFF;
+                }
+                if premultiplied {
+                    convert(
+                        bgra,
+                        PixelFormat::BGRAPremul,
+                        PixelFormat::BGRANonPremul,
+                        &mut bgra,
+                    );
+                }
+                let [b, g, r, a] = bgra;
+                let a = a as f32 / 255.0;
+                pixels.push([
+                    srgb_to_linear(r) * a,
+                    srgb_to_linear(g) * a,
+                    srgb_to_linear(b) * a,
+                    a,
+                ]);
+            }
+        }
+
+        Ok(LinearImage {
+            pixels,
+            width: self.width,
+            height: self.height,
+        })
+    }
+}
+
+impl LinearImage {
+    /// Converts the image back to sRGB-encoded 8-bit pixels.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ImageBuf`, tightly packed in
+    /// `pixel_format`, or `Error::InvalidParameter` if the pixel format is
+    /// `Invalid` or there are fewer pixels than the dimensions need.
+    pub fn to_srgb_u8(&self, pixel_format: PixelFormat) -> Result<ImageBuf, Error> {
+        let len = self.width as usize * self.height as usize;
+        if pixel_format == PixelFormat::Invalid || self.pixels.len() < len {
+            return Err(Error::InvalidParameter);
+        }
+
+        let bpp = pixel_format.bytes_per_pixel();
+        let mut pixels = vec![0; len * bpp];
+        for (dst, &[r, g, b, a]) in pixels.chunks_exact_mut(bpp).zip(&self.pixels) {
+            let a = a.clamp(0.0, 1.0);
+            let unpremultiply = if a > 0.0 { 1.0 / a } else { 0.0 };
+            let bgra = [
+                linear_to_srgb(b * unpremultiply),
+                linear_to_srgb(g * unpremultiply),
+                linear_to_srgb(r * unpremultiply),
+                (a * 255.0).round() as u8,
+            ];
+            convert(bgra, PixelFormat::BGRANonPremul, pixel_format, dst);
+        }
+
+        Ok(ImageBuf {
+            pixels,
+            width: self.width,
+            height: self.height,
+            pixel_format,
+            stride_in_bytes: self.width as usize * bpp,
+        })
+    }
+
+    /// Resamples the image to another size in linear light. See
+    /// [`Image::resize`].
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the resized `LinearImage`, or
+    /// `Error::InvalidParameter` if a dimension is zero or there are fewer
+    /// pixels than the dimensions need.
+    pub fn resize(
+        &self,
+        width: u32,
+        height: u32,
+        mode: ResizeMode,
+        filter: ResizeFilter,
+    ) -> Result<LinearImage, Error> {
+        let src_width = self.width as usize;
+        if width == 0
+            || height == 0
+            || self.width == 0
+            || self.height == 0
+            || self.pixels.len() < src_width * self.height as usize
+        {
+            return Err(Error::InvalidParameter);
+        }
+
+        let resampling = Resampling::new(self.width, self.height, width, height, mode, filter);
+        let mut pixels = Vec::with_capacity(resampling.width as usize * resampling.height as usize);
+        resampling.run(
+            |y, row| row.copy_from_slice(&self.pixels[y * src_width..][..src_width]),
+            // Ringing filters can overshoot, so keep the channels within
+            // what the alpha allows.
+            |_, row| {
+                pixels.extend(row.iter().map(|&[r, g, b, a]| {
+                    let a = a.clamp(0.0, 1.0);
+                    [r.clamp(0.0, a), g.clamp(0.0, a), b.clamp(0.0, a), a]
+                }))
+            },
+        );
+
+        Ok(LinearImage {
+            pixels,
+            width: resampling.width,
+            height: resampling.height,
+        })
+    }
+}
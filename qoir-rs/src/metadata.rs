@@ -0,0 +1,198 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::Error;
+use crate::container::{Container, invalid_data, next_chunk, write_chunk};
+
+/// The metadata of a QOIR image, borrowed from the encoded data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QoirMetadata<'a> {
+    /// The CICP profile (`CICP` chunk), if present.
+    pub cic_profile: Option<&'a [u8]>,
+    /// The ICC profile (`ICCP` chunk), if present.
+    pub icc_profile: Option<&'a [u8]>,
+    /// The EXIF data (`EXIF` chunk), if present.
+    pub exif: Option<&'a [u8]>,
+    /// The XMP data (`XMP ` chunk), if present.
+    pub xmp: Option<&'a [u8]>,
+}
+
+/// What [`rewrite_metadata`] does with one kind of metadata.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MetadataChange<'a> {
+    /// Keeps the existing chunk, if any.
+    #[default]
+    Keep,
+    /// Removes the chunk.
+    Remove,
+    /// Replaces the chunk, or adds it if there was none.
+    Set(&'a [u8]),
+}
+
+impl<'a> MetadataChange<'a> {
+    fn apply(self, current: Option<&'a [u8]>) -> Option<&'a [u8]> {
+        match self {
+            MetadataChange::Keep => current,
+            MetadataChange::Remove => None,
+            MetadataChange::Set(payload) => Some(payload),
+        }
+    }
+}
+
+/// The changes made by [`rewrite_metadata`]. Every field defaults to
+/// [`MetadataChange::Keep`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MetadataEdit<'a> {
+    pub cic_profile: MetadataChange<'a>,
+    pub icc_profile: MetadataChange<'a>,
+    pub exif: MetadataChange<'a>,
+    pub xmp: MetadataChange<'a>,
+}
+
+/// Reads the metadata chunks of QOIR image data without decoding any pixels.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+///
+/// # Returns
+///
+/// A `Result` containing the `QoirMetadata` or an `Error` if the data is not a
+/// valid QOIR image.
+pub fn read_metadata(data: &[u8]) -> Result<QoirMetadata<'_>, Error> {
+    let container = Container::parse(data)?;
+    Ok(QoirMetadata {
+        cic_profile: container.cicp,
+        icc_profile: container.iccp,
+        exif: container.exif,
+        xmp: container.xmp,
+    })
+}
+
+/// Adds, replaces or removes metadata chunks of QOIR image data.
+///
+/// The pixels are copied as they are, without being decoded and encoded
+/// again, so this is lossless and about as fast as copying the file. Unknown
+/// chunks are kept.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+/// * `edit`: The changes to make to each kind of metadata.
+///
+/// # Returns
+///
+/// A `Result` containing the rewritten QOIR data or an `Error` if the data is
+/// not a valid QOIR image.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::{rewrite_metadata, MetadataChange, MetadataEdit};
+///
+/// let qoir_data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+/// let icc_profile = std::fs::read("sRGB.icc").expect("Failed to read ICC profile");
+/// let edit = MetadataEdit {
+///     icc_profile: MetadataChange::Set(&icc_profile),
+///     exif: MetadataChange::Remove,
+///     ..Default::default()
+/// };
+/// let rewritten = rewrite_metadata(&qoir_data, &edit).expect("Failed to rewrite");
+/// std::fs::write("output.qoir", rewritten).expect("Failed to write QOIR file");
+/// ```
+pub fn rewrite_metadata(data: &[u8], edit: &MetadataEdit) -> Result<Vec<u8>, Error> {
+    let container = Container::parse(data)?;
+    let metadata = [
+        (*b"CICP", edit.cic_profile.apply(container.cicp)),
+        (*b"ICCP", edit.icc_profile.apply(container.iccp)),
+        (*b"EXIF", edit.exif.apply(container.exif)),
+        (*b"XMP ", edit.xmp.apply(container.xmp)),
+    ];
+
+    let mut dst = Vec::with_capacity(data.len());
+    let mut rest = data;
+    loop {
+        let (tag, _, remaining) = next_chunk(rest)?;
+        let chunk = &rest[..rest.len() - remaining.len()];
+        rest = remaining;
+
+        match &tag {
+            // The metadata goes between the header and the pixels, in the
+            // order the encoder writes it.
+            b"CICP" | b"ICCP" | b"EXIF" | b"XMP " => continue,
+            b"QPIX" => {
+                for (tag, payload) in metadata {
+                    if let Some(payload) = payload {
+                        write_chunk(&mut dst, tag, payload);
+                    }
+                }
+            }
+            _ => {}
+        }
+        dst.extend_from_slice(chunk);
+        if tag == *b"QEND" {
+            break;
+        }
+    }
+    Ok(dst)
+}
+
+/// Reads the application key/value pairs stored with
+/// [`EncodeOptions::custom_metadata`](crate::EncodeOptions::custom_metadata)
+/// without decoding any pixels.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+///
+/// # Returns
+///
+/// A `Result` containing the pairs, empty if there are none, or an `Error` if
+/// the data is not a valid QOIR image.
+pub fn read_custom_metadata(data: &[u8]) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    let container = Container::parse(data)?;
+    container.custom.map_or(Ok(Vec::new()), parse_custom)
+}
+
+/// Serializes key/value pairs into the payload of an `APPD` chunk: for each
+/// pair, the key's length as a little-endian `u16`, the UTF-8 key, the
+/// value's length as a little-endian `u32` and the value.
+///
+/// Returns `Error::InvalidParameter` if a key or value is too long.
+pub(crate) fn write_custom(pairs: &[(String, Vec<u8>)]) -> Result<Vec<u8>, Error> {
+    let mut payload = Vec::new();
+    for (key, value) in pairs {
+        let key_len = u16::try_from(key.len()).map_err(|_| Error::InvalidParameter)?;
+        let value_len = u32::try_from(value.len()).map_err(|_| Error::InvalidParameter)?;
+        payload.extend_from_slice(&key_len.to_le_bytes());
+        payload.extend_from_slice(key.as_bytes());
+        payload.extend_from_slice(&value_len.to_le_bytes());
+        payload.extend_from_slice(value);
+    }
+    Ok(payload)
+}
+
+/// Parses the payload of an `APPD` chunk written by [`write_custom`].
+pub(crate) fn parse_custom(payload: &[u8]) -> Result<Vec<(String, Vec<u8>)>, Error> {
+    fn take<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+        let (head, tail) = rest.split_at_checked(len).ok_or_else(invalid_data)?;
+        *rest = tail;
+        Ok(head)
+    }
+
+    let mut pairs = Vec::new();
+    let mut rest = payload;
+    while !rest.is_empty() {
+        let key_len = take(&mut rest, 2)?;
+        let key = take(
+            &mut rest,
+            u16::from_le_bytes([key_len[0], key_len[1]]) as usize,
+        )?;
+        let key = String::from_utf8(key.to_vec()).map_err(|_| invalid_data())?;
+        let value_len = take(&mut rest, 4)?;
+        let value_len =
+            u32::from_le_bytes([value_len[0], value_len[1], value_len[2], value_len[3]]);
+        let value = take(&mut rest, value_len as usize)?;
+        pairs.push((key, value.to_vec()));
+    }
+    Ok(pairs)
+}
@@ -0,0 +1,121 @@
+//! Process-wide counters and histograms, enabled with the `metrics` feature.
+//!
+//! [`decode_from_memory`](crate::decode_from_memory) and
+//! [`encode_to_memory`](crate::encode_to_memory), and so every function built
+//! on them, report to the recorder installed with the
+//! [`metrics`](https://docs.rs/metrics) facade. Without a recorder, reporting
+//! does nothing. Pair it with an exporter such as
+//! `metrics-exporter-prometheus`:
+//!
+//! ```ignore
+//! metrics_exporter_prometheus::PrometheusBuilder::new().install()?;
+//! qoir_rs::metrics::describe();
+//! ```
+//!
+//! Failures are counted with an `error` label holding the kind of
+//! [`Error`](crate::Error), such as `decoding_failed`.
+
+use core::time::Duration;
+
+use metrics::{Unit, counter, describe_counter, describe_histogram, histogram};
+
+use crate::Error;
+
+/// Counter of images decoded successfully.
+pub const IMAGES_DECODED: &str = "qoir_images_decoded_total";
+/// Counter of QOIR bytes passed to the decoder, including failed calls.
+pub const DECODE_BYTES_IN: &str = "qoir_decode_bytes_in_total";
+/// Counter of pixel bytes produced by the decoder.
+pub const DECODE_BYTES_OUT: &str = "qoir_decode_bytes_out_total";
+/// Histogram of decode durations, in seconds.
+pub const DECODE_DURATION: &str = "qoir_decode_duration_seconds";
+/// Counter of failed decodes, labelled with `error`.
+pub const DECODE_FAILURES: &str = "qoir_decode_failures_total";
+
+/// Counter of images encoded successfully.
+pub const IMAGES_ENCODED: &str = "qoir_images_encoded_total";
+/// Counter of pixel bytes passed to the encoder, including failed calls.
+pub const ENCODE_BYTES_IN: &str = "qoir_encode_bytes_in_total";
+/// Counter of QOIR bytes produced by the encoder.
+pub const ENCODE_BYTES_OUT: &str = "qoir_encode_bytes_out_total";
+/// Histogram of encode durations, in seconds.
+pub const ENCODE_DURATION: &str = "qoir_encode_duration_seconds";
+/// Counter of failed encodes, labelled with `error`.
+pub const ENCODE_FAILURES: &str = "qoir_encode_failures_total";
+
+/// Registers the units and descriptions of every metric with the installed
+/// recorder. Call it once after installing the recorder.
+pub fn describe() {
+    describe_counter!(IMAGES_DECODED, Unit::Count, "Images decoded");
+    describe_counter!(
+        DECODE_BYTES_IN,
+        Unit::Bytes,
+        "QOIR bytes read by the decoder"
+    );
+    describe_counter!(
+        DECODE_BYTES_OUT,
+        Unit::Bytes,
+        "Pixel bytes written by the decoder"
+    );
+    describe_histogram!(
+        DECODE_DURATION,
+        Unit::Seconds,
+        "Time spent decoding an image"
+    );
+    describe_counter!(DECODE_FAILURES, Unit::Count, "Images that failed to decode");
+    describe_counter!(IMAGES_ENCODED, Unit::Count, "Images encoded");
+    describe_counter!(
+        ENCODE_BYTES_IN,
+        Unit::Bytes,
+        "Pixel bytes read by the encoder"
+    );
+    describe_counter!(
+        ENCODE_BYTES_OUT,
+        Unit::Bytes,
+        "QOIR bytes written by the encoder"
+    );
+    describe_histogram!(
+        ENCODE_DURATION,
+        Unit::Seconds,
+        "Time spent encoding an image"
+    );
+    describe_counter!(ENCODE_FAILURES, Unit::Count, "Images that failed to encode");
+}
+
+/// The value of the `error` label for `error`.
+fn error_label(error: &Error) -> &'static str {
+    match error {
+        Error::InvalidParameter => "invalid_parameter",
+        Error::DecodingFailed(_) => "decoding_failed",
+        Error::EncodingFailed(_) => "encoding_failed",
+        Error::FileNotFound => "file_not_found",
+        Error::IoError { .. } => "io_error",
+        Error::Internal(_) => "internal",
+    }
+}
+
+/// Records one decode call, which read `bytes_in` bytes and produced `result`.
+pub(crate) fn record_decode(bytes_in: usize, result: Result<usize, &Error>, elapsed: Duration) {
+    counter!(DECODE_BYTES_IN).increment(bytes_in as u64);
+    histogram!(DECODE_DURATION).record(elapsed);
+    match result {
+        Ok(bytes_out) => {
+            counter!(IMAGES_DECODED).increment(1);
+            counter!(DECODE_BYTES_OUT).increment(bytes_out as u64);
+        }
+        Err(error) => counter!(DECODE_FAILURES, "error" => error_label(error)).increment(1),
+    }
+}
+
+/// Records one encode call, which read `bytes_in` bytes and produced `result`.
+pub(crate) fn record_encode(bytes_in: usize, result: Result<usize, &Error>, elapsed: Duration) {
+    counter!(ENCODE_BYTES_IN).increment(bytes_in as u64);
+    histogram!(ENCODE_DURATION).record(elapsed);
+    match result {
+        Ok(bytes_out) => {
+            counter!(IMAGES_ENCODED).increment(1);
+            counter!(ENCODE_BYTES_OUT).increment(bytes_out as u64);
+        }
+        Err(error) => counter!(ENCODE_FAILURES, "error" => error_label(error)).increment(1),
+    }
+}
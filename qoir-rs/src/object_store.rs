@@ -0,0 +1,230 @@
+//! Decoding images held in object storage, enabled with the `object-store`
+//! feature.
+//!
+//! [`decode_from_object_store`] reads a QOIR image from any store the
+//! `object_store` crate supports, such as S3, GCS, Azure Blob Storage or a
+//! local directory, with range requests instead of downloading the whole
+//! object. Metadata that `DecodeOptions::metadata` leaves out is never
+//! fetched, and with `DecodeOptions::src_clip_rect` only the tiles up to the
+//! bottom of the clip rectangle are.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use object_store::{local::LocalFileSystem, path::Path};
+//! use qoir_rs::{DecodeOptions, Rectangle, object_store::decode_from_object_store};
+//!
+//! # async fn run() -> Result<(), qoir_rs::Error> {
+//! let store = LocalFileSystem::new_with_prefix("/mnt/archive").expect("Failed to open store");
+//! let options = DecodeOptions {
+//!     src_clip_rect: Some(Rectangle { x0: 0, y0: 0, x1: 512, y1: 512 }),
+//!     ..Default::default()
+//! };
+//! let decoded = decode_from_object_store(&store, &Path::from("2024/IMG_0042.qoir"), options).await?;
+//! println!("Image decoded: {}x{}", decoded.image.width, decoded.image.height);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::ops::Range;
+
+use bytes::Bytes;
+use object_store::ObjectStore;
+use object_store::path::Path;
+
+use crate::container::{CHUNK_HEADER_LEN, Header, TILE_SIZE, invalid_data, write_blank_tile};
+use crate::timings::Stopwatch;
+use crate::{DecodeOptions, DecodedImage, Error, decode_from_memory};
+
+/// How much to fetch past the end of each read, so that consecutive small
+/// chunks and tiles don't each cost a request.
+const READ_AHEAD: u64 = 256 * 1024;
+
+/// Decodes a QOIR image from an object store, fetching only the byte ranges
+/// the decode needs.
+///
+/// Like `decode_from_seek`, this reads the header first and checks it against
+/// `DecodeOptions::limits` before fetching any more of the object, and skips
+/// metadata that `DecodeOptions::metadata` leaves out and unknown chunks.
+///
+/// With `DecodeOptions::src_clip_rect` set, the tiles below the clip
+/// rectangle are not fetched. Their pixels are left opaque black, so they
+/// must not be read from the decoded image; the pixels inside the clip
+/// rectangle are decoded as usual. The image's `QSUM` checksum is dropped in
+/// that case, as it no longer matches.
+///
+/// # Arguments
+///
+/// * `store`: The object store holding the image.
+/// * `location`: The path of the image within the store.
+/// * `options`: `DecodeOptions` to control the decoding process.
+///
+/// # Returns
+///
+/// A `Result` containing the `DecodedImage` or an `Error` if fetching or
+/// decoding fails. A missing object is reported as `Error::FileNotFound`.
+pub async fn decode_from_object_store<'a>(
+    store: &dyn ObjectStore,
+    location: &Path,
+    options: DecodeOptions,
+) -> Result<DecodedImage<'a>, Error> {
+    let mut clock = Stopwatch::start(options.collect_timings);
+    let size = store
+        .head(location)
+        .await
+        .map_err(|e| store_error(e, location))?
+        .size;
+    let mut reader = RangeReader {
+        store,
+        location,
+        size,
+        window: Bytes::new(),
+        window_start: 0,
+    };
+
+    let mut data = Vec::new();
+    let mut offset = 0;
+    let mut partial = false;
+    loop {
+        let header = reader
+            .read(offset..offset + CHUNK_HEADER_LEN as u64)
+            .await?;
+        offset += CHUNK_HEADER_LEN as u64;
+        let tag = [header[0], header[1], header[2], header[3]];
+        let mut len = [0; 8];
+        len.copy_from_slice(&header[4..]);
+        let len = u64::from_le_bytes(len);
+        let end = offset.checked_add(len).ok_or_else(invalid_data)?;
+        if data.is_empty() != (tag == *b"QOIR") {
+            return Err(invalid_data());
+        }
+        if !options.metadata.keeps(&tag) || (partial && tag == *b"QSUM") {
+            offset = end;
+            continue;
+        }
+
+        data.extend_from_slice(&header);
+        if tag == *b"QPIX" {
+            let header = Header::parse(&data)?;
+            partial = read_tiles(&mut reader, offset..end, &header, &options, &mut data).await?;
+        } else {
+            data.extend_from_slice(&reader.read(offset..end).await?);
+        }
+        offset = end;
+        match &tag {
+            b"QOIR" => options.limits.check(&data, options.pixel_format)?,
+            b"QEND" => break,
+            _ => {}
+        }
+    }
+    let io = clock.lap();
+
+    let mut decoded = decode_from_memory(&data, options)?;
+    if let Some(timings) = &mut decoded.timings {
+        timings.io = io;
+    }
+    Ok(decoded)
+}
+
+/// Appends the `QPIX` payload at `range` to `data`, whose last bytes are the
+/// chunk's header.
+///
+/// The tiles below `DecodeOptions::src_clip_rect` are replaced with blank
+/// ones instead of being fetched, and the chunk's length is updated to match.
+/// Returns whether any tiles were replaced.
+async fn read_tiles(
+    reader: &mut RangeReader<'_>,
+    range: Range<u64>,
+    header: &Header,
+    options: &DecodeOptions,
+    data: &mut Vec<u8>,
+) -> Result<bool, Error> {
+    let Some(clip) = options.src_clip_rect else {
+        data.extend_from_slice(&reader.read(range).await?);
+        return Ok(false);
+    };
+
+    let tiles_x = header.width.div_ceil(TILE_SIZE) as usize;
+    let tile_rows = header.height.div_ceil(TILE_SIZE);
+    let needed_rows = (clip.y1.max(0) as u32).div_ceil(TILE_SIZE).min(tile_rows);
+    if needed_rows == tile_rows {
+        data.extend_from_slice(&reader.read(range).await?);
+        return Ok(false);
+    }
+
+    let len_pos = data.len() - 8;
+    let start = data.len();
+    let mut offset = range.start;
+    for _ in 0..tiles_x * needed_rows as usize {
+        let tile_header = reader.read(offset..offset + 4).await?;
+        let word = u32::from_le_bytes([
+            tile_header[0],
+            tile_header[1],
+            tile_header[2],
+            tile_header[3],
+        ]);
+        let end = offset + 4 + u64::from(word & 0x00FF_FFFF);
+        if end > range.end {
+            return Err(invalid_data());
+        }
+        data.extend_from_slice(&reader.read(offset..end).await?);
+        offset = end;
+    }
+    for row in needed_rows..tile_rows {
+        let height = (header.height - row * TILE_SIZE).min(TILE_SIZE) as usize;
+        for column in 0..tiles_x as u32 {
+            let width = (header.width - column * TILE_SIZE).min(TILE_SIZE) as usize;
+            write_blank_tile(data, width * height);
+        }
+    }
+    let len = (data.len() - start) as u64;
+    data[len_pos..start].copy_from_slice(&len.to_le_bytes());
+    Ok(true)
+}
+
+/// Reads byte ranges of an object, fetching a little past each one so that
+/// nearby reads are served from memory.
+struct RangeReader<'s> {
+    store: &'s dyn ObjectStore,
+    location: &'s Path,
+    size: u64,
+    /// The bytes fetched last, starting at `window_start`.
+    window: Bytes,
+    window_start: u64,
+}
+
+impl RangeReader<'_> {
+    async fn read(&mut self, range: Range<u64>) -> Result<Bytes, Error> {
+        if range.end > self.size {
+            return Err(invalid_data());
+        }
+        let window_end = self.window_start + self.window.len() as u64;
+        if range.start < self.window_start || range.end > window_end {
+            let fetch_end = range.end.max(range.start + READ_AHEAD).min(self.size);
+            self.window = self
+                .store
+                .get_range(self.location, range.start..fetch_end)
+                .await
+                .map_err(|e| store_error(e, self.location))?;
+            self.window_start = range.start;
+            if (self.window.len() as u64) < range.end - range.start {
+                return Err(invalid_data());
+            }
+        }
+        let start = (range.start - self.window_start) as usize;
+        Ok(self
+            .window
+            .slice(start..start + (range.end - range.start) as usize))
+    }
+}
+
+/// Converts an object store error, noting the object it happened on.
+fn store_error(error: object_store::Error, location: &Path) -> Error {
+    match Error::from(std::io::Error::from(error)) {
+        Error::IoError { source, .. } => Error::IoError {
+            source,
+            path: Some(location.as_ref().into()),
+        },
+        error => error,
+    }
+}
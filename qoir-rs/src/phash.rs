@@ -0,0 +1,79 @@
+//! Perceptual hashes for finding duplicate and near-duplicate images.
+
+use crate::pixel::to_bgra;
+use crate::{Error, Image, PixelFormat};
+
+/// The size of the luma grid the hash compares, one column wider than the
+/// hash so that each row gives 8 differences.
+const GRID_WIDTH: usize = 9;
+const GRID_HEIGHT: usize = 8;
+
+/// Computes a 64-bit perceptual hash of an image.
+///
+/// This is a difference hash (dHash): the image is averaged down to a 9x8
+/// grid of luma values, and each bit records whether a cell is darker than
+/// its right neighbour, row by row from the most significant bit. Resizing,
+/// recompressing, converting the pixel format and small color shifts change
+/// few bits, so similar images have hashes a small [`hamming_distance`]
+/// apart. Premultiplied pixels are hashed as they are stored, as if
+/// composited over black.
+///
+/// The pixels are read in place, honouring the stride, so a decoded QOIR
+/// image can be hashed without converting it.
+///
+/// # Returns
+///
+/// A `Result` containing the hash, or `Error::InvalidParameter` if the pixel
+/// format is `Invalid` or the pixel buffer is too small. Empty images hash
+/// to 0.
+pub fn phash(image: &Image) -> Result<u64, Error> {
+    if image.pixel_format == PixelFormat::Invalid {
+        return Err(Error::InvalidParameter);
+    }
+    image.check_buffer()?;
+    let (width, height) = (image.width as usize, image.height as usize);
+    if width == 0 || height == 0 {
+        return Ok(0);
+    }
+
+    // The cells split the image as evenly as possible, and are at least one
+    // pixel wide and tall, overlapping in images smaller than the grid.
+    let span = |cell: usize, cells: usize, len: usize| {
+        let start = (cell * len / cells).min(len - 1);
+        start..((cell + 1) * len / cells).max(start + 1)
+    };
+    let bpp = image.pixel_format.bytes_per_pixel();
+    let mut grid = [[0u64; GRID_WIDTH]; GRID_HEIGHT];
+    for (cy, cells) in grid.iter_mut().enumerate() {
+        let rows = span(cy, GRID_HEIGHT, height);
+        let row_count = rows.len() as u64;
+        for (cx, cell) in cells.iter_mut().enumerate() {
+            let columns = span(cx, GRID_WIDTH, width);
+            let mut sum = 0;
+            for y in rows.clone() {
+                let row = &image.pixels[y * image.stride_in_bytes..];
+                for pixel in row[columns.start * bpp..columns.end * bpp].chunks_exact(bpp) {
+                    let [b, g, r, _] = to_bgra(pixel, image.pixel_format);
+                    sum += 299 * r as u64 + 587 * g as u64 + 114 * b as u64;
+                }
+            }
+            *cell = sum / (row_count * columns.len() as u64);
+        }
+    }
+
+    let mut hash = 0;
+    for cells in &grid {
+        for pair in cells.windows(2) {
+            hash = hash << 1 | (pair[0] < pair[1]) as u64;
+        }
+    }
+    Ok(hash)
+}
+
+/// The number of bits that differ between two hashes from [`phash`].
+///
+/// Identical images give 0. Resized or recompressed copies of an image
+/// typically give less than 10, and unrelated images around 32.
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
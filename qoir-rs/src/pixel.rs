@@ -0,0 +1,193 @@
+//! Conversions between the pixel formats, shared by the pure-Rust codecs.
+
+use alloc::{vec, vec::Vec};
+
+use crate::{Error, Image, PixelFormat};
+
+impl<'data> Image<'data> {
+    /// Wraps tightly packed raw pixels, such as a framebuffer or camera dump,
+    /// checking that the buffer holds `height` rows of `width` pixels.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixels`: The raw pixel data.
+    /// * `width`: Width of the image in pixels.
+    /// * `height`: Height of the image in pixels.
+    /// * `pixel_format`: Pixel format of the data.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Image`, or `Error::InvalidParameter` if the
+    /// pixel format is `Invalid` or `pixels` is too small.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use qoir_rs::{Image, PixelFormat};
+    ///
+    /// let pixels = vec![0u8; 4 * 3 * 2];
+    /// let image = Image::from_raw(&pixels, 4, 2, PixelFormat::RGB).expect("Too few pixels");
+    /// assert_eq!(image.stride_in_bytes, 12);
+    /// ```
+    pub fn from_raw(
+        pixels: &'data [u8],
+        width: u32,
+        height: u32,
+        pixel_format: PixelFormat,
+    ) -> Result<Self, Error> {
+        let stride_in_bytes = width as usize * pixel_format.bytes_per_pixel();
+        Self::from_raw_with_stride(pixels, width, height, pixel_format, stride_in_bytes)
+    }
+
+    /// Like `from_raw`, for rows that are `stride_in_bytes` apart, such as
+    /// rows padded for alignment.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Image`, or `Error::InvalidParameter` if the
+    /// pixel format is `Invalid`, the stride is shorter than a row or `pixels`
+    /// is too small.
+    pub fn from_raw_with_stride(
+        pixels: &'data [u8],
+        width: u32,
+        height: u32,
+        pixel_format: PixelFormat,
+        stride_in_bytes: usize,
+    ) -> Result<Self, Error> {
+        if pixel_format == PixelFormat::Invalid {
+            return Err(Error::InvalidParameter);
+        }
+        let image = Image {
+            pixels,
+            width,
+            height,
+            pixel_format,
+            stride_in_bytes,
+        };
+        image.check_buffer()?;
+        Ok(image)
+    }
+
+    /// Copies the pixels into a tightly packed buffer in another pixel format,
+    /// reordering the channels and converting premultiplied alpha as needed.
+    ///
+    /// Formats without alpha are read as opaque, and the X byte is written as
+    /// 0xFF.
+    ///
+    /// # Arguments
+    ///
+    /// * `pixel_format`: The pixel format of the returned buffer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the converted pixels, `width * height *
+    /// pixel_format.bytes_per_pixel()` bytes long, or `Error::InvalidParameter`
+    /// if either format is `Invalid` or the pixel buffer is too small.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use qoir_rs::{decode, DecodeOptions, PixelFormat};
+    ///
+    /// let decoded = decode("input.qoir", DecodeOptions::default()).expect("Failed to decode");
+    /// let rgb = decoded.image.to_pixel_format(PixelFormat::RGB).expect("Failed to convert");
+    /// ```
+    pub fn to_pixel_format(&self, pixel_format: PixelFormat) -> Result<Vec<u8>, Error> {
+        let src_format = self.pixel_format;
+        if src_format == PixelFormat::Invalid || pixel_format == PixelFormat::Invalid {
+            return Err(Error::InvalidParameter);
+        }
+        self.check_buffer()?;
+        let src_bpp = src_format.bytes_per_pixel();
+        let row_len = self.width as usize * src_bpp;
+
+        // `convert` takes the source as one of the formats QOIR stores.
+        let stored_format = match src_format {
+            PixelFormat::BGRAPremul | PixelFormat::RGBAPremul => PixelFormat::BGRAPremul,
+            PixelFormat::BGRANonPremul | PixelFormat::RGBANonPremul => PixelFormat::BGRANonPremul,
+            _ => PixelFormat::BGRX,
+        };
+        let dst_bpp = pixel_format.bytes_per_pixel();
+        let mut dst = vec![0; self.width as usize * self.height as usize * dst_bpp];
+        if dst.is_empty() {
+            return Ok(dst);
+        }
+        let rows = self.pixels.chunks(self.stride_in_bytes);
+        for (src_row, dst_row) in rows.zip(dst.chunks_exact_mut(self.width as usize * dst_bpp)) {
+            let src_pixels = src_row[..row_len].chunks_exact(src_bpp);
+            for (src, dst) in src_pixels.zip(dst_row.chunks_exact_mut(dst_bpp)) {
+                convert(to_bgra(src, src_format), stored_format, pixel_format, dst);
+            }
+        }
+        Ok(dst)
+    }
+
+    /// Checks that the stride and pixel buffer are large enough for the
+    /// image's dimensions.
+    pub(crate) fn check_buffer(&self) -> Result<(), Error> {
+        let row_len = self.width as usize * self.pixel_format.bytes_per_pixel();
+        if self.height > 0
+            && (self.stride_in_bytes < row_len
+                || self.pixels.len() < self.stride_in_bytes * (self.height as usize - 1) + row_len)
+        {
+            return Err(Error::InvalidParameter);
+        }
+        Ok(())
+    }
+}
+
+/// Reads one pixel stored as `format` into BGRA order.
+pub(crate) fn to_bgra(src: &[u8], format: PixelFormat) -> [u8; 4] {
+    match format {
+        PixelFormat::BGRANonPremul | PixelFormat::BGRAPremul => [src[0], src[1], src[2], src[3]],
+        PixelFormat::RGBANonPremul | PixelFormat::RGBAPremul => [src[2], src[1], src[0], src[3]],
+        PixelFormat::BGRX | PixelFormat::BGR => [src[0], src[1], src[2], 0xFF],
+        PixelFormat::RGBX | PixelFormat::RGB => [src[2], src[1], src[0], 0xFF],
+        PixelFormat::Invalid => [0; 4],
+    }
+}
+
+/// Converts one BGRA pixel stored as `src_format` into `dst`.
+pub(crate) fn convert(
+    bgra: [u8; 4],
+    src_format: PixelFormat,
+    dst_format: PixelFormat,
+    dst: &mut [u8],
+) {
+    let [b, g, r, a] = match src_format {
+        PixelFormat::BGRX => [bgra[0], bgra[1], bgra[2], 0xFF],
+        _ => bgra,
+    };
+    let src_premul = src_format == PixelFormat::BGRAPremul;
+    let dst_premul = matches!(
+        dst_format,
+        PixelFormat::BGRAPremul | PixelFormat::RGBAPremul
+    );
+
+    let [b, g, r] = match (src_premul, dst_premul) {
+        (false, true) => [b, g, r].map(|c| premultiply(c, a)),
+        (true, false) => [b, g, r].map(|c| unpremultiply(c, a)),
+        _ => [b, g, r],
+    };
+
+    match dst_format {
+        PixelFormat::BGRX => dst.copy_from_slice(&[b, g, r, 0xFF]),
+        PixelFormat::BGRANonPremul | PixelFormat::BGRAPremul => dst.copy_from_slice(&[b, g, r, a]),
+        PixelFormat::BGR => dst.copy_from_slice(&[b, g, r]),
+        PixelFormat::RGBX => dst.copy_from_slice(&[r, g, b, 0xFF]),
+        PixelFormat::RGBANonPremul | PixelFormat::RGBAPremul => dst.copy_from_slice(&[r, g, b, a]),
+        PixelFormat::RGB => dst.copy_from_slice(&[r, g, b]),
+        PixelFormat::Invalid => {}
+    }
+}
+
+fn premultiply(channel: u8, alpha: u8) -> u8 {
+    ((channel as u32 * alpha as u32 + 127) / 255) as u8
+}
+
+fn unpremultiply(channel: u8, alpha: u8) -> u8 {
+    if alpha == 0 {
+        return 0;
+    }
+    ((channel as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8
+}
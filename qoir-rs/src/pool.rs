@@ -0,0 +1,275 @@
+//! Reusing pixel and encoded-data buffers between decodes and encodes.
+//!
+//! A viewer scrubbing through frames decodes image after image of the same
+//! size, and allocating and zeroing a fresh pixel buffer for each one can cost
+//! as much as the decode itself. A [`BufferPool`] hands the buffers of
+//! finished images back to the next decode or encode instead.
+//!
+//! ```no_run
+//! use qoir_rs::{BufferPool, DecodeOptions};
+//!
+//! let pool = BufferPool::new(512 * 1024 * 1024);
+//! for path in ["frame-0001.qoir", "frame-0002.qoir"] {
+//!     let data = std::fs::read(path).expect("Failed to read QOIR file");
+//!     let frame = pool.decode(&data, DecodeOptions::default()).expect("Failed to decode");
+//!     println!("{}x{}", frame.width, frame.height);
+//!     // The frame's pixels go back to the pool when it is dropped.
+//! }
+//! ```
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{DecodeOptions, EncodeOptions, Error, Image, ImageBuf, Timings, rust_backend};
+
+/// A thread-safe pool of byte buffers, limited to a number of bytes, that
+/// [`decode`](BufferPool::decode) and [`encode`](BufferPool::encode) take
+/// their output buffers from.
+///
+/// The images and encoded data they return borrow the pool, and put their
+/// buffers back into it when they are dropped. When the pooled buffers would
+/// exceed the budget, the oldest ones are freed. Decoding and encoding are
+/// done by the Rust backend.
+#[derive(Debug)]
+pub struct BufferPool {
+    budget_bytes: usize,
+    state: Mutex<PoolState>,
+}
+
+#[derive(Debug, Default)]
+struct PoolState {
+    /// The pooled buffers, from the least to the most recently returned.
+    buffers: Vec<Vec<u8>>,
+    /// The total capacity of the pooled buffers.
+    bytes: usize,
+}
+
+impl BufferPool {
+    /// Creates an empty pool that holds at most `budget_bytes` of buffers.
+    pub fn new(budget_bytes: usize) -> Self {
+        BufferPool {
+            budget_bytes,
+            state: Mutex::new(PoolState::default()),
+        }
+    }
+
+    /// The number of bytes the pool may hold.
+    pub fn budget_bytes(&self) -> usize {
+        self.budget_bytes
+    }
+
+    /// The number of bytes the pooled buffers take.
+    pub fn size_bytes(&self) -> usize {
+        self.lock().bytes
+    }
+
+    /// Frees every pooled buffer.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.buffers.clear();
+        state.bytes = 0;
+    }
+
+    /// Decodes QOIR image data into a pooled pixel buffer.
+    ///
+    /// A buffer that already holds an image is not zeroed again unless the
+    /// clip rectangles or offsets leave some of its pixels undrawn, which are
+    /// zeroed as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: A slice of bytes containing the QOIR encoded image data.
+    /// * `options`: `DecodeOptions` to control the decoding process.
+    ///   `fit_within` and `metadata` are ignored; only the pixels are
+    ///   returned.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PooledImage` or an `Error` if decoding fails.
+    pub fn decode(&self, data: &[u8], options: DecodeOptions) -> Result<PooledImage<'_>, Error> {
+        let (width, height, _) = rust_backend::decode_basic_metadata(data)?;
+        let len = (width as usize)
+            .saturating_mul(height as usize)
+            .saturating_mul(options.pixel_format.bytes_per_pixel());
+        let mut pixels = self.take(Some(len));
+        let result = rust_backend::decode_into(data, &options, &mut pixels);
+        let (width, height, stride_in_bytes, timings) = match result {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                self.give(pixels);
+                return Err(err);
+            }
+        };
+        Ok(PooledImage {
+            pool: self,
+            image: ImageBuf {
+                pixels,
+                width,
+                height,
+                pixel_format: options.pixel_format,
+                stride_in_bytes,
+            },
+            timings: options.collect_timings.then_some(timings),
+        })
+    }
+
+    /// Encodes an `Image` into a pooled buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `image`: The `Image` to encode.
+    /// * `options`: `EncodeOptions` to control the encoding process.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PooledBuffer` or an `Error` if encoding
+    /// fails.
+    pub fn encode(
+        &self,
+        image: Image<'_>,
+        options: EncodeOptions,
+    ) -> Result<PooledBuffer<'_>, Error> {
+        let mut data = self.take(None);
+        match rust_backend::encode_into(image, &options, &mut data) {
+            Ok(timings) => Ok(PooledBuffer {
+                pool: self,
+                data,
+                timings: options.collect_timings.then_some(timings),
+            }),
+            Err(err) => {
+                self.give(data);
+                Err(err)
+            }
+        }
+    }
+
+    /// Takes the smallest pooled buffer that holds `len` bytes, or a new one
+    /// if none does. Without a length, takes the largest pooled buffer.
+    fn take(&self, len: Option<usize>) -> Vec<u8> {
+        let mut state = self.lock();
+        let best = match len {
+            Some(len) => state
+                .buffers
+                .iter()
+                .enumerate()
+                .filter(|(_, buffer)| buffer.capacity() >= len)
+                .min_by_key(|(_, buffer)| buffer.capacity()),
+            None => state
+                .buffers
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, buffer)| buffer.capacity()),
+        };
+        let Some(index) = best.map(|(index, _)| index) else {
+            return Vec::new();
+        };
+        let buffer = state.buffers.remove(index);
+        state.bytes -= buffer.capacity();
+        buffer
+    }
+
+    /// Puts a buffer back into the pool, freeing the oldest buffers if the
+    /// budget is exceeded. A buffer larger than the whole budget is freed.
+    fn give(&self, buffer: Vec<u8>) {
+        let bytes = buffer.capacity();
+        if bytes == 0 || bytes > self.budget_bytes {
+            return;
+        }
+        let mut state = self.lock();
+        while state.bytes + bytes > self.budget_bytes {
+            let oldest = state.buffers.remove(0);
+            state.bytes -= oldest.capacity();
+        }
+        state.buffers.push(buffer);
+        state.bytes += bytes;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PoolState> {
+        // The state is consistent between statements, so a panic elsewhere
+        // while the lock was held leaves it usable.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// An image decoded by [`BufferPool::decode`], whose pixels go back to the
+/// pool when it is dropped. It dereferences to an `ImageBuf`.
+#[derive(Debug)]
+pub struct PooledImage<'p> {
+    pool: &'p BufferPool,
+    image: ImageBuf,
+    timings: Option<Timings>,
+}
+
+impl PooledImage<'_> {
+    /// How long each stage of the decode took, if
+    /// `DecodeOptions::collect_timings` was set.
+    pub fn timings(&self) -> Option<Timings> {
+        self.timings
+    }
+
+    /// Takes the image out of the pool, so that its pixels are not returned
+    /// to it.
+    pub fn into_inner(mut self) -> ImageBuf {
+        ImageBuf {
+            pixels: std::mem::take(&mut self.image.pixels),
+            ..self.image
+        }
+    }
+}
+
+impl Deref for PooledImage<'_> {
+    type Target = ImageBuf;
+
+    fn deref(&self) -> &ImageBuf {
+        &self.image
+    }
+}
+
+impl DerefMut for PooledImage<'_> {
+    fn deref_mut(&mut self) -> &mut ImageBuf {
+        &mut self.image
+    }
+}
+
+impl Drop for PooledImage<'_> {
+    fn drop(&mut self) {
+        self.pool.give(std::mem::take(&mut self.image.pixels));
+    }
+}
+
+/// QOIR data encoded by [`BufferPool::encode`], which goes back to the pool
+/// when it is dropped. It dereferences to the encoded bytes.
+#[derive(Debug)]
+pub struct PooledBuffer<'p> {
+    pool: &'p BufferPool,
+    data: Vec<u8>,
+    timings: Option<Timings>,
+}
+
+impl PooledBuffer<'_> {
+    /// How long each stage of the encode took, if
+    /// `EncodeOptions::collect_timings` was set.
+    pub fn timings(&self) -> Option<Timings> {
+        self.timings
+    }
+
+    /// Takes the encoded data out of the pool, so that its buffer is not
+    /// returned to it.
+    pub fn into_vec(mut self) -> Vec<u8> {
+        std::mem::take(&mut self.data)
+    }
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        self.pool.give(std::mem::take(&mut self.data));
+    }
+}
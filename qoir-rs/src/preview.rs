@@ -0,0 +1,260 @@
+//! Smart previews: one file holding a few renditions of the same image, such
+//! as a 2048-pixel lossy preview for browsing and the full-size lossless
+//! image for editing, so that previews need no sidecar files.
+//!
+//! A smart preview file uses the chunk layout of a QOIR file. It starts with
+//! a `QSPV` header chunk holding the rendition count as a little-endian
+//! `u32`, followed by an index with the byte offset and length of each
+//! rendition as little-endian `u64`s. One `QREN` chunk per rendition follows,
+//! from the largest down, each holding a complete QOIR image, and the file
+//! ends with an empty `QEND` chunk. The index lets a reader fetch just the
+//! rendition it needs.
+//!
+//! Every rendition carries the color profiles of the image, so that it can be
+//! shown on its own. The EXIF and XMP are only stored in the largest one.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::preview::{encode_smart_preview, Rendition, SmartPreview};
+//! use qoir_rs::{decode, DecodeOptions, EncodeOptions};
+//!
+//! let decoded = decode("IMG_0042.qoir", DecodeOptions::default()).expect("Failed to decode");
+//! let renditions = [
+//!     Rendition { max_size: None, lossiness: 0, dither: false },
+//!     Rendition { max_size: Some(2048), lossiness: 2, dither: true },
+//! ];
+//! let data = encode_smart_preview(decoded.image, &renditions, EncodeOptions::default())
+//!     .expect("Failed to encode");
+//!
+//! let preview = SmartPreview::new(&data).expect("Failed to parse");
+//! let thumbnail = preview
+//!     .decode(preview.best_for(400), DecodeOptions::default())
+//!     .expect("Failed to decode");
+//! ```
+
+use crate::container::{CHUNK_HEADER_LEN, Header, next_chunk, write_chunk};
+use crate::{
+    DecodeOptions, DecodedImage, EncodeOptions, Error, Image, QoirMetadata, ResizeFilter,
+    ResizeMode, decode_from_memory, encode_to_memory, read_metadata,
+};
+
+const HEADER_LEN: usize = 4;
+const INDEX_ENTRY_LEN: usize = 16;
+
+fn invalid_data() -> Error {
+    Error::DecodingFailed("#qoir-preview: invalid data".to_string())
+}
+
+/// The size and compression of one rendition to encode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Rendition {
+    /// The longest side of the rendition in pixels, or `None` for the full
+    /// size. Images are never upscaled.
+    pub max_size: Option<u32>,
+    /// Lossiness level, from 0 (lossless) to 7.
+    pub lossiness: u8,
+    /// Whether to dither the lossy encoding.
+    pub dither: bool,
+}
+
+/// Encodes `image` at each of `renditions` into a smart preview file.
+///
+/// Smaller renditions are resized from the full image with a Lanczos filter,
+/// keeping its aspect ratio. The renditions are stored from the largest
+/// down, and lossless before lossy at equal sizes.
+///
+/// # Arguments
+///
+/// * `image`: The full-size `Image`.
+/// * `renditions`: The renditions to store, at least one.
+/// * `options`: `EncodeOptions` holding the metadata and any quantization.
+///   Its lossiness and dithering are replaced by those of each rendition.
+///
+/// # Returns
+///
+/// A `Result` containing the smart preview file, `Error::InvalidParameter` if
+/// `renditions` is empty or has a `max_size` of 0, or an `Error` if resizing
+/// or encoding fails.
+pub fn encode_smart_preview(
+    image: Image<'_>,
+    renditions: &[Rendition],
+    options: EncodeOptions,
+) -> Result<Vec<u8>, Error> {
+    if renditions.is_empty() || renditions.iter().any(|r| r.max_size == Some(0)) {
+        return Err(Error::InvalidParameter);
+    }
+    let size = |rendition: &Rendition| match rendition.max_size {
+        Some(max) if image.width.max(image.height) > max => {
+            let longest = image.width.max(image.height) as u64;
+            let scale = |side: u32| ((side as u64 * max as u64 + longest / 2) / longest).max(1);
+            (scale(image.width) as u32, scale(image.height) as u32)
+        }
+        _ => (image.width, image.height),
+    };
+    let mut renditions = renditions.to_vec();
+    renditions.sort_by_key(|r| {
+        let (w, h) = size(r);
+        (core::cmp::Reverse(w as u64 * h as u64), r.lossiness)
+    });
+
+    let mut encoded = Vec::with_capacity(renditions.len());
+    for (n, rendition) in renditions.iter().enumerate() {
+        let (exif, xmp) = match n {
+            0 => (options.exif.clone(), options.xmp.clone()),
+            _ => (None, None),
+        };
+        let rendition_options = EncodeOptions {
+            exif,
+            xmp,
+            lossiness: rendition.lossiness,
+            dither: rendition.dither,
+            ..options.clone()
+        };
+        let (w, h) = size(rendition);
+        let data = if (w, h) == (image.width, image.height) {
+            encode_to_memory(image.clone(), rendition_options)?
+                .data
+                .to_vec()
+        } else {
+            let resized = image.resize(w, h, ResizeMode::Exact, ResizeFilter::Lanczos3)?;
+            encode_to_memory(resized.as_image(), rendition_options)?
+                .data
+                .to_vec()
+        };
+        encoded.push(data);
+    }
+
+    let header_len = HEADER_LEN + INDEX_ENTRY_LEN * encoded.len();
+    let mut header = Vec::with_capacity(header_len);
+    header.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+    // Each rendition's QOIR image starts after its own chunk header.
+    let mut offset = 2 * CHUNK_HEADER_LEN + header_len;
+    for data in &encoded {
+        header.extend_from_slice(&(offset as u64).to_le_bytes());
+        header.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        offset += CHUNK_HEADER_LEN + data.len();
+    }
+
+    let mut dst = Vec::with_capacity(offset + CHUNK_HEADER_LEN);
+    write_chunk(&mut dst, *b"QSPV", &header);
+    for data in &encoded {
+        write_chunk(&mut dst, *b"QREN", data);
+    }
+    write_chunk(&mut dst, *b"QEND", &[]);
+    Ok(dst)
+}
+
+/// One rendition of a smart preview.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PreviewRendition {
+    /// Width of the rendition in pixels.
+    pub width: u32,
+    /// Height of the rendition in pixels.
+    pub height: u32,
+    /// The lossiness the rendition was encoded with, from 0 to 7.
+    pub lossiness: u8,
+    /// The byte offset of the rendition's QOIR image in the file.
+    pub offset: usize,
+    /// The length of the rendition's QOIR image in bytes.
+    pub len: usize,
+}
+
+/// Reads the renditions of a smart preview file.
+///
+/// Parsing reads the index and the header of each rendition; the pixels are
+/// decoded on request.
+#[derive(Debug, Clone)]
+pub struct SmartPreview<'a> {
+    data: &'a [u8],
+    renditions: Vec<PreviewRendition>,
+}
+
+impl<'a> SmartPreview<'a> {
+    /// Parses a smart preview file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `SmartPreview` or an `Error` if the data is
+    /// not a valid smart preview.
+    pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+        let (tag, header, _) = next_chunk(data)?;
+        if tag != *b"QSPV" || header.len() < HEADER_LEN {
+            return Err(invalid_data());
+        }
+        let count = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        if count == 0 || header.len() != HEADER_LEN + INDEX_ENTRY_LEN * count {
+            return Err(invalid_data());
+        }
+
+        let mut renditions = Vec::with_capacity(count);
+        for entry in header[HEADER_LEN..].chunks_exact(INDEX_ENTRY_LEN) {
+            let mut offset = [0; 8];
+            let mut len = [0; 8];
+            offset.copy_from_slice(&entry[..8]);
+            len.copy_from_slice(&entry[8..]);
+            let offset = usize::try_from(u64::from_le_bytes(offset)).map_err(|_| invalid_data())?;
+            let len = usize::try_from(u64::from_le_bytes(len)).map_err(|_| invalid_data())?;
+            let rendition_data = offset
+                .checked_add(len)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(invalid_data)?;
+
+            let rendition_header = Header::parse(rendition_data)?;
+            renditions.push(PreviewRendition {
+                width: rendition_header.width,
+                height: rendition_header.height,
+                lossiness: rendition_header.lossiness,
+                offset,
+                len,
+            });
+        }
+
+        Ok(SmartPreview { data, renditions })
+    }
+
+    /// The renditions, from the largest down.
+    pub fn renditions(&self) -> &[PreviewRendition] {
+        &self.renditions
+    }
+
+    /// The QOIR image of rendition `n`, without decoding it.
+    pub fn rendition_data(&self, n: usize) -> Option<&'a [u8]> {
+        let rendition = self.renditions.get(n)?;
+        Some(&self.data[rendition.offset..rendition.offset + rendition.len])
+    }
+
+    /// The metadata stored with the largest rendition.
+    pub fn metadata(&self) -> Result<QoirMetadata<'a>, Error> {
+        read_metadata(self.rendition_data(0).ok_or_else(invalid_data)?)
+    }
+
+    /// The narrowest rendition that is at least `width` pixels wide, or the
+    /// largest one if none is that wide.
+    ///
+    /// Between renditions of the same width, the one with the smallest
+    /// encoded size is picked, as it is the quickest to read.
+    pub fn best_for(&self, width: u32) -> usize {
+        (0..self.renditions.len())
+            .filter(|&n| self.renditions[n].width >= width)
+            .min_by_key(|&n| (self.renditions[n].width, self.renditions[n].len))
+            .unwrap_or(0)
+    }
+
+    /// Decodes rendition `n`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n`: The rendition to decode; 0 is the largest.
+    /// * `options`: `DecodeOptions` to control the decoding process. Clip
+    ///   rectangles and offsets are in the rendition's coordinates.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DecodedImage`, `Error::InvalidParameter` if
+    /// there is no such rendition, or an `Error` if decoding fails.
+    pub fn decode(&self, n: usize, options: DecodeOptions) -> Result<DecodedImage<'a>, Error> {
+        let data = self.rendition_data(n).ok_or(Error::InvalidParameter)?;
+        decode_from_memory(data, options)
+    }
+}
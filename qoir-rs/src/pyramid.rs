@@ -0,0 +1,341 @@
+//! Multi-resolution pyramids: one file holding an image at full, half,
+//! quarter and smaller sizes, like a tiled pyramidal TIFF.
+//!
+//! A pyramid file uses the chunk layout of a QOIR file. It starts with a
+//! `QPYR` header chunk holding the full width, height and level count as
+//! little-endian `u32`s, followed by an index with the byte offset and length
+//! of each level as little-endian `u64`s. One `QLVL` chunk per level follows,
+//! from the full size down, each holding a complete QOIR image, and the file
+//! ends with an empty `QEND` chunk. The index lets a reader fetch just the
+//! level it needs, for example with an HTTP range request.
+//!
+//! Level `n` is the full image scaled by `1 / 2^n`, rounding up. Levels are
+//! added until the smallest fits within a single 64x64 tile. The metadata is
+//! only stored in the full-size level.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::pyramid::{encode_pyramid, PyramidReader};
+//! use qoir_rs::{decode, DecodeOptions, EncodeOptions, PixelFormat, Rectangle};
+//!
+//! let decoded = decode("huge.qoir", DecodeOptions::default()).expect("Failed to decode");
+//! let data = encode_pyramid(decoded.image, EncodeOptions::default()).expect("Failed to encode");
+//!
+//! let reader = PyramidReader::new(&data).expect("Failed to parse");
+//! let overview = reader
+//!     .decode_level(reader.level_count() - 1, DecodeOptions::default())
+//!     .expect("Failed to decode");
+//! let region = Rectangle { x0: 4096, y0: 2048, x1: 8192, y1: 4096 };
+//! let view = reader
+//!     .decode_region_at_zoom(region, 0.25, PixelFormat::RGBANonPremul)
+//!     .expect("Failed to decode");
+//! ```
+
+use alloc::{string::ToString, vec::Vec};
+
+use crate::container::{CHUNK_HEADER_LEN, Header, TILE_SIZE, next_chunk, write_chunk};
+use crate::{
+    DecodeOptions, DecodedImage, EncodeOptions, Error, Image, ImageBuf, PixelFormat, QoirMetadata,
+    Rectangle, ResizeFilter, ResizeMode, decode_from_memory, encode_to_vec, read_metadata,
+};
+
+const HEADER_LEN: usize = 12;
+const INDEX_ENTRY_LEN: usize = 16;
+
+fn invalid_data() -> Error {
+    Error::DecodingFailed("#qoir-pyramid: invalid data".to_string())
+}
+
+/// The size of level `level` of an image of `width` by `height` pixels.
+fn level_size(width: u32, height: u32, level: usize) -> (u32, u32) {
+    let scale = |side: u32| side.div_ceil(1 << level.min(31)).max(1);
+    (scale(width), scale(height))
+}
+
+/// Encodes `image` and its downscaled levels into a pyramid file.
+///
+/// Each level is a box-filtered half of the one before it, encoded with the
+/// lossiness and dithering of `options`. The metadata of `options` goes into
+/// the full-size level only.
+///
+/// # Arguments
+///
+/// * `image`: The full-size `Image`.
+/// * `options`: `EncodeOptions` to control the encoding of every level.
+///
+/// # Returns
+///
+/// A `Result` containing the pyramid file or an `Error` if resizing or
+/// encoding fails.
+pub fn encode_pyramid(image: Image<'_>, options: EncodeOptions) -> Result<Vec<u8>, Error> {
+    let (width, height) = (image.width, image.height);
+    let level_options = EncodeOptions {
+        lossiness: options.lossiness,
+        dither: options.dither,
+        ..Default::default()
+    };
+
+    let mut levels = alloc::vec![encode_to_vec(image.clone(), options)?];
+    let mut previous: Option<ImageBuf> = None;
+    loop {
+        let (w, h) = level_size(width, height, levels.len() - 1);
+        if w <= TILE_SIZE && h <= TILE_SIZE {
+            break;
+        }
+        let (w, h) = level_size(width, height, levels.len());
+        let source = previous.as_ref().map_or(image.clone(), ImageBuf::as_image);
+        let level = source.resize(w, h, ResizeMode::Exact, ResizeFilter::Box)?;
+        levels.push(encode_to_vec(level.as_image(), level_options.clone())?);
+        previous = Some(level);
+    }
+
+    let header_len = HEADER_LEN + INDEX_ENTRY_LEN * levels.len();
+    let mut header = Vec::with_capacity(header_len);
+    header.extend_from_slice(&width.to_le_bytes());
+    header.extend_from_slice(&height.to_le_bytes());
+    header.extend_from_slice(&(levels.len() as u32).to_le_bytes());
+    // Each level's QOIR image starts after its own chunk header.
+    let mut offset = 2 * CHUNK_HEADER_LEN + header_len;
+    for level in &levels {
+        header.extend_from_slice(&(offset as u64).to_le_bytes());
+        header.extend_from_slice(&(level.len() as u64).to_le_bytes());
+        offset += CHUNK_HEADER_LEN + level.len();
+    }
+
+    let mut dst = Vec::with_capacity(offset + CHUNK_HEADER_LEN);
+    write_chunk(&mut dst, *b"QPYR", &header);
+    for level in &levels {
+        write_chunk(&mut dst, *b"QLVL", level);
+    }
+    write_chunk(&mut dst, *b"QEND", &[]);
+    Ok(dst)
+}
+
+/// One level of a pyramid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PyramidLevel {
+    /// Width of the level in pixels.
+    pub width: u32,
+    /// Height of the level in pixels.
+    pub height: u32,
+    /// The byte offset of the level's QOIR image in the pyramid file.
+    pub offset: usize,
+    /// The length of the level's QOIR image in bytes.
+    pub len: usize,
+}
+
+/// Reads the levels of a pyramid file.
+///
+/// Parsing reads the index and the header of each level; the pixels are
+/// decoded on request.
+#[derive(Debug, Clone)]
+pub struct PyramidReader<'a> {
+    data: &'a [u8],
+    width: u32,
+    height: u32,
+    levels: Vec<PyramidLevel>,
+}
+
+impl<'a> PyramidReader<'a> {
+    /// Parses a pyramid file.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `PyramidReader` or an `Error` if the data is
+    /// not a valid pyramid.
+    pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+        let (tag, header, _) = next_chunk(data)?;
+        if tag != *b"QPYR" || header.len() < HEADER_LEN {
+            return Err(invalid_data());
+        }
+        let word =
+            |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let (width, height, level_count) = (word(0), word(4), word(8) as usize);
+        if level_count == 0 || header.len() != HEADER_LEN + INDEX_ENTRY_LEN * level_count {
+            return Err(invalid_data());
+        }
+
+        let mut levels = Vec::with_capacity(level_count);
+        for (n, entry) in header[HEADER_LEN..]
+            .chunks_exact(INDEX_ENTRY_LEN)
+            .enumerate()
+        {
+            let mut offset = [0; 8];
+            let mut len = [0; 8];
+            offset.copy_from_slice(&entry[..8]);
+            len.copy_from_slice(&entry[8..]);
+            let offset = usize::try_from(u64::from_le_bytes(offset)).map_err(|_| invalid_data())?;
+            let len = usize::try_from(u64::from_le_bytes(len)).map_err(|_| invalid_data())?;
+            let level_data = offset
+                .checked_add(len)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(invalid_data)?;
+
+            let level_header = Header::parse(level_data)?;
+            let (w, h) = level_size(width, height, n);
+            if (level_header.width, level_header.height) != (w, h) {
+                return Err(invalid_data());
+            }
+            levels.push(PyramidLevel {
+                width: w,
+                height: h,
+                offset,
+                len,
+            });
+        }
+
+        Ok(PyramidReader {
+            data,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// Width of the full-size level in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the full-size level in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of levels, including the full-size one.
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// The levels, from the full size down.
+    pub fn levels(&self) -> &[PyramidLevel] {
+        &self.levels
+    }
+
+    /// The QOIR image of level `n`, without decoding it.
+    pub fn level_data(&self, n: usize) -> Option<&'a [u8]> {
+        let level = self.levels.get(n)?;
+        Some(&self.data[level.offset..level.offset + level.len])
+    }
+
+    /// The metadata stored with the full-size level.
+    pub fn metadata(&self) -> Result<QoirMetadata<'a>, Error> {
+        read_metadata(self.level_data(0).ok_or_else(invalid_data)?)
+    }
+
+    /// The smallest level that still has at least `zoom` times the full
+    /// resolution, or the smallest level if none is that small.
+    ///
+    /// A zoom of 1 or more is served by the full-size level.
+    pub fn level_for_zoom(&self, zoom: f64) -> usize {
+        let mut n = 0;
+        while n + 1 < self.levels.len() && zoom <= 1.0 / (2u64 << n) as f64 {
+            n += 1;
+        }
+        n
+    }
+
+    /// Decodes level `n`.
+    ///
+    /// # Arguments
+    ///
+    /// * `n`: The level to decode; 0 is the full size.
+    /// * `options`: `DecodeOptions` to control the decoding process. Clip
+    ///   rectangles and offsets are in the level's coordinates.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `DecodedImage`, `Error::InvalidParameter` if
+    /// there is no such level, or an `Error` if decoding fails.
+    pub fn decode_level(
+        &self,
+        n: usize,
+        options: DecodeOptions,
+    ) -> Result<DecodedImage<'a>, Error> {
+        let data = self.level_data(n).ok_or(Error::InvalidParameter)?;
+        decode_from_memory(data, options)
+    }
+
+    /// Decodes a region of the image at a zoom level, from the smallest level
+    /// that has enough resolution for it.
+    ///
+    /// The result has the resolution of the level picked by
+    /// [`level_for_zoom`](Self::level_for_zoom): the region's size divided
+    /// by `2^level`, rounded outwards. Scaling it to exactly `zoom` is left
+    /// to the caller.
+    ///
+    /// # Arguments
+    ///
+    /// * `region`: The region to decode, in full-size coordinates. It is
+    ///   clipped to the image.
+    /// * `zoom`: The requested scale, such as 0.25 for a quarter of the full
+    ///   resolution.
+    /// * `pixel_format`: The pixel format to decode into.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the region as a tightly packed `ImageBuf`,
+    /// `Error::InvalidParameter` if the zoom is not positive or the region
+    /// lies outside the image, or an `Error` if decoding fails.
+    pub fn decode_region_at_zoom(
+        &self,
+        region: Rectangle,
+        zoom: f64,
+        pixel_format: PixelFormat,
+    ) -> Result<ImageBuf, Error> {
+        if zoom.is_nan() || zoom <= 0.0 || pixel_format == PixelFormat::Invalid {
+            return Err(Error::InvalidParameter);
+        }
+        let n = self.level_for_zoom(zoom);
+        let level = self.levels[n];
+
+        let scale = 1i64 << n;
+        let down = |v: i32, max: u32| (v as i64).div_euclid(scale).clamp(0, max as i64) as i32;
+        let up = |v: i32, max: u32| {
+            (v as i64 + scale - 1)
+                .div_euclid(scale)
+                .clamp(0, max as i64) as i32
+        };
+        let rect = Rectangle {
+            x0: down(region.x0, level.width),
+            y0: down(region.y0, level.height),
+            x1: up(region.x1, level.width),
+            y1: up(region.y1, level.height),
+        };
+        if rect.x0 >= rect.x1 || rect.y0 >= rect.y1 {
+            return Err(Error::InvalidParameter);
+        }
+
+        // Move the region to the top-left corner of the decoded pixels, then
+        // copy it out of the level-sized buffer.
+        let options = DecodeOptions {
+            pixel_format,
+            src_clip_rect: Some(rect),
+            dst_clip_rect: None,
+            offset_x: -rect.x0,
+            offset_y: -rect.y0,
+            ..Default::default()
+        };
+        let decoded = self.decode_level(n, options)?;
+        let image = &decoded.image;
+
+        let (width, height) = ((rect.x1 - rect.x0) as u32, (rect.y1 - rect.y0) as u32);
+        let row_len = width as usize * pixel_format.bytes_per_pixel();
+        let mut pixels = Vec::with_capacity(row_len * height as usize);
+        for row in image
+            .pixels
+            .chunks(image.stride_in_bytes)
+            .take(height as usize)
+        {
+            pixels.extend_from_slice(&row[..row_len]);
+        }
+        Ok(ImageBuf {
+            pixels,
+            width,
+            height,
+            pixel_format,
+            stride_in_bytes: row_len,
+        })
+    }
+}
@@ -0,0 +1,365 @@
+//! Encoding and decoding of [QOI](https://qoiformat.org/) images, enabled
+//! with the `qoi` feature.
+//!
+//! QOI is the simpler format QOIR grew out of. This pure-Rust implementation
+//! uses the same `Image` and `PixelFormat` types as the rest of the crate, so
+//! pipelines holding `.qoi` files can handle both formats with one crate.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::{qoi, PixelFormat};
+//!
+//! let qoi_data = std::fs::read("input.qoi").expect("Failed to read QOI file");
+//! let decoded = qoi::decode_from_memory(&qoi_data, PixelFormat::RGBANonPremul)
+//!     .expect("Failed to decode");
+//! let qoi_data = qoi::encode_to_memory(decoded.as_image()).expect("Failed to encode");
+//! ```
+//!
+//! Existing QOI files can be migrated to QOIR, and back, without going through
+//! the generic `image` crate path:
+//!
+//! ```no_run
+//! use qoir_rs::{qoi, EncodeOptions};
+//!
+//! let qoi_data = std::fs::read("input.qoi").expect("Failed to read QOI file");
+//! let qoir = qoi::transcode_qoi_to_qoir(&qoi_data, EncodeOptions::default())
+//!     .expect("Failed to transcode");
+//! std::fs::write("output.qoir", qoir.data).expect("Failed to write QOIR file");
+//! ```
+
+use alloc::{string::ToString, vec, vec::Vec};
+
+use crate::pixel::{convert, to_bgra};
+use crate::{
+    DecodeOptions, EncodeOptions, EncodedBuffer, Error, Image, OwnedDecodedImage, PixelFormat,
+};
+
+const MAGIC: &[u8; 4] = b"qoif";
+const HEADER_LEN: usize = 14;
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+/// The reference implementation refuses images with more pixels than this.
+const MAX_PIXELS: u64 = 400_000_000;
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const MASK_2: u8 = 0xC0;
+
+/// The contents of a QOI header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QoiHeader {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// 3 for RGB images, 4 for RGBA images.
+    pub channels: u8,
+    /// 0 for sRGB with linear alpha, 1 for all channels linear.
+    pub colorspace: u8,
+}
+
+impl QoiHeader {
+    /// The pixel format matching the image's channels: `RGB` or
+    /// `RGBANonPremul`.
+    pub fn pixel_format(&self) -> PixelFormat {
+        if self.channels == 4 {
+            PixelFormat::RGBANonPremul
+        } else {
+            PixelFormat::RGB
+        }
+    }
+}
+
+fn invalid_data() -> Error {
+    Error::DecodingFailed("#qoi: invalid data".to_string())
+}
+
+/// Reads the header of QOI image data.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOI encoded image data.
+///
+/// # Returns
+///
+/// A `Result` containing the `QoiHeader` or an `Error` if the header is invalid.
+pub fn decode_header(data: &[u8]) -> Result<QoiHeader, Error> {
+    if data.len() < HEADER_LEN || &data[..4] != MAGIC {
+        return Err(invalid_data());
+    }
+
+    let header = QoiHeader {
+        width: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        height: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+        channels: data[12],
+        colorspace: data[13],
+    };
+    if header.width == 0
+        || header.height == 0
+        || !(3..=4).contains(&header.channels)
+        || header.colorspace > 1
+        || header.width as u64 * header.height as u64 > MAX_PIXELS
+    {
+        return Err(invalid_data());
+    }
+    Ok(header)
+}
+
+/// Decodes QOI image data into `pixel_format`.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOI encoded image data.
+/// * `pixel_format`: The pixel format to decode into.
+///
+/// # Returns
+///
+/// A `Result` containing the `OwnedDecodedImage` or an `Error` if decoding fails.
+pub fn decode_from_memory(
+    data: &[u8],
+    pixel_format: PixelFormat,
+) -> Result<OwnedDecodedImage, Error> {
+    let header = decode_header(data)?;
+    if pixel_format == PixelFormat::Invalid {
+        return Err(Error::DecodingFailed(
+            "#qoi: unsupported pixfmt".to_string(),
+        ));
+    }
+
+    let bpp = pixel_format.bytes_per_pixel();
+    let stride_in_bytes = header.width as usize * bpp;
+    let mut pixels = vec![0; stride_in_bytes * header.height as usize];
+
+    let mut index = [[0u8; 4]; 64];
+    let mut pixel = [0x00, 0x00, 0x00, 0xFF];
+    let mut run = 0;
+    let mut pos = HEADER_LEN;
+    let end = data.len().saturating_sub(END_MARKER.len()).max(HEADER_LEN);
+
+    for dst in pixels.chunks_exact_mut(bpp) {
+        if run > 0 {
+            run -= 1;
+        } else {
+            let op = *data[..end].get(pos).ok_or_else(invalid_data)?;
+            let len = match op {
+                OP_RGB => 4,
+                OP_RGBA => 5,
+                _ if op & MASK_2 == OP_LUMA => 2,
+                _ => 1,
+            };
+            let bytes = data[..end].get(pos..pos + len).ok_or_else(invalid_data)?;
+            pos += len;
+
+            match op {
+                OP_RGB => pixel[..3].copy_from_slice(&bytes[1..4]),
+                OP_RGBA => pixel.copy_from_slice(&bytes[1..5]),
+                _ => match op & MASK_2 {
+                    OP_INDEX => pixel = index[op as usize],
+                    OP_DIFF => {
+                        pixel[0] = pixel[0].wrapping_add((op >> 4) & 0x03).wrapping_sub(2);
+                        pixel[1] = pixel[1].wrapping_add((op >> 2) & 0x03).wrapping_sub(2);
+                        pixel[2] = pixel[2].wrapping_add(op & 0x03).wrapping_sub(2);
+                    }
+                    OP_LUMA => {
+                        let dg = (op & 0x3F).wrapping_sub(32);
+                        pixel[0] =
+                            pixel[0].wrapping_add(dg.wrapping_sub(8).wrapping_add(bytes[1] >> 4));
+                        pixel[1] = pixel[1].wrapping_add(dg);
+                        pixel[2] =
+                            pixel[2].wrapping_add(dg.wrapping_sub(8).wrapping_add(bytes[1] & 0x0F));
+                    }
+                    _ => run = (op & 0x3F) as usize,
+                },
+            }
+            index[hash(pixel)] = pixel;
+        }
+
+        let [r, g, b, a] = pixel;
+        convert([b, g, r, a], PixelFormat::BGRANonPremul, pixel_format, dst);
+    }
+
+    Ok(OwnedDecodedImage {
+        pixels,
+        width: header.width,
+        height: header.height,
+        pixel_format,
+        stride_in_bytes,
+        cic_profile: None,
+        icc_profile: None,
+        exif: None,
+        xmp: None,
+        custom_metadata: Vec::new(),
+    })
+}
+
+/// Encodes an `Image` into QOI format in memory.
+///
+/// Images with an alpha channel are stored as RGBA, with premultiplied alpha
+/// undone, and all others as RGB. The colorspace is always sRGB.
+///
+/// # Arguments
+///
+/// * `image`: The `Image` to encode.
+///
+/// # Returns
+///
+/// A `Result` containing the encoded bytes or an `Error` if encoding fails.
+pub fn encode_to_memory(image: Image<'_>) -> Result<Vec<u8>, Error> {
+    let src_format = image.pixel_format;
+    let bpp = src_format.bytes_per_pixel();
+    let row_len = image.width as usize * bpp;
+    if src_format == PixelFormat::Invalid
+        || image.width == 0
+        || image.height == 0
+        || image.width as u64 * image.height as u64 > MAX_PIXELS
+        || image.stride_in_bytes < row_len
+        || image.pixels.len() < image.stride_in_bytes * (image.height as usize - 1) + row_len
+    {
+        return Err(Error::InvalidParameter);
+    }
+
+    let channels: u8 = match src_format {
+        PixelFormat::BGRANonPremul
+        | PixelFormat::BGRAPremul
+        | PixelFormat::RGBANonPremul
+        | PixelFormat::RGBAPremul => 4,
+        _ => 3,
+    };
+    let src_bgra_format = match src_format {
+        PixelFormat::BGRAPremul | PixelFormat::RGBAPremul => PixelFormat::BGRAPremul,
+        _ => PixelFormat::BGRANonPremul,
+    };
+
+    let mut data = Vec::with_capacity(HEADER_LEN + image.width as usize * image.height as usize);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&image.width.to_be_bytes());
+    data.extend_from_slice(&image.height.to_be_bytes());
+    data.extend_from_slice(&[channels, 0]);
+
+    let mut index = [[0u8; 4]; 64];
+    let mut prev = [0x00, 0x00, 0x00, 0xFF];
+    let mut run = 0u8;
+
+    for row in image
+        .pixels
+        .chunks(image.stride_in_bytes)
+        .take(image.height as usize)
+    {
+        for src in row[..row_len].chunks_exact(bpp) {
+            let mut pixel = [0; 4];
+            convert(
+                to_bgra(src, src_format),
+                src_bgra_format,
+                PixelFormat::RGBANonPremul,
+                &mut pixel,
+            );
+
+            if pixel == prev {
+                run += 1;
+                if run == 62 {
+                    data.push(OP_RUN | (run - 1));
+                    run = 0;
+                }
+                continue;
+            }
+            if run > 0 {
+                data.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+
+            let hash = hash(pixel);
+            if index[hash] == pixel {
+                data.push(OP_INDEX | hash as u8);
+            } else if pixel[3] != prev[3] {
+                data.push(OP_RGBA);
+                data.extend_from_slice(&pixel);
+            } else {
+                let [dr, dg, db] = [0, 1, 2].map(|i| pixel[i].wrapping_sub(prev[i]) as i8);
+                let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+                if [dr, dg, db].iter().all(|d| (-2..2).contains(d)) {
+                    data.push(
+                        OP_DIFF | ((dr + 2) as u8) << 4 | ((dg + 2) as u8) << 2 | (db + 2) as u8,
+                    );
+                } else if (-32..32).contains(&dg)
+                    && (-8..8).contains(&dr_dg)
+                    && (-8..8).contains(&db_dg)
+                {
+                    data.push(OP_LUMA | (dg + 32) as u8);
+                    data.push(((dr_dg + 8) as u8) << 4 | (db_dg + 8) as u8);
+                } else {
+                    data.push(OP_RGB);
+                    data.extend_from_slice(&pixel[..3]);
+                }
+            }
+            index[hash] = pixel;
+            prev = pixel;
+        }
+    }
+
+    if run > 0 {
+        data.push(OP_RUN | (run - 1));
+    }
+    data.extend_from_slice(&END_MARKER);
+    Ok(data)
+}
+
+/// Transcodes QOI image data into QOIR.
+///
+/// The pixels are decoded in the QOI file's own channel layout and handed
+/// straight to the QOIR encoder, so with the default (lossless) `options` the
+/// QOIR image holds exactly the same pixels.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOI encoded image data.
+/// * `options`: The `EncodeOptions` for the QOIR image.
+///
+/// # Returns
+///
+/// A `Result` containing the `EncodedBuffer` or an `Error` if transcoding fails.
+pub fn transcode_qoi_to_qoir<'a>(
+    data: &[u8],
+    options: EncodeOptions,
+) -> Result<EncodedBuffer<'a>, Error> {
+    let header = decode_header(data)?;
+    let decoded = decode_from_memory(data, header.pixel_format())?;
+    crate::encode_to_memory(decoded.as_image(), options)
+}
+
+/// Transcodes QOIR image data into QOI.
+///
+/// Opaque QOIR images become RGB QOI images and all others RGBA. Pixels are
+/// preserved exactly, except that premultiplied alpha is undone because QOI
+/// only stores non-premultiplied colors. QOIR metadata has no QOI equivalent
+/// and is dropped.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+///
+/// # Returns
+///
+/// A `Result` containing the QOI bytes or an `Error` if transcoding fails.
+pub fn transcode_qoir_to_qoi(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let (_, _, pixel_format) = crate::decode_basic_metadata(data)?;
+    let pixel_format = match pixel_format {
+        PixelFormat::BGRANonPremul => PixelFormat::RGBANonPremul,
+        PixelFormat::BGRAPremul => PixelFormat::RGBAPremul,
+        _ => PixelFormat::RGB,
+    };
+    let options = DecodeOptions {
+        pixel_format,
+        ..Default::default()
+    };
+    let decoded = crate::decode_from_memory(data, options)?;
+    encode_to_memory(decoded.image)
+}
+
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
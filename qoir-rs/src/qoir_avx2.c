@@ -0,0 +1,17 @@
+// A second copy of qoir, compiled with AVX2 enabled. Its functions are made
+// static so that it can be linked next to the baseline copy in qoir.c, and it
+// is reached through the renamed entry points below.
+#define QOIR_CONFIG__STATIC_FUNCTIONS
+#define QOIR_IMPLEMENTATION
+#include "qoir.h"
+
+qoir_decode_result qoir_rs_avx2_decode(const uint8_t* src_ptr,
+                                       const size_t src_len,
+                                       const qoir_decode_options* options) {
+  return qoir_decode(src_ptr, src_len, options);
+}
+
+qoir_encode_result qoir_rs_avx2_encode(const qoir_pixel_buffer* src_pixbuf,
+                                       const qoir_encode_options* options) {
+  return qoir_encode(src_pixbuf, options);
+}
@@ -0,0 +1,205 @@
+//! Color quantization, which maps an image to a small palette so that it
+//! compresses better.
+
+use alloc::{vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{Error, Image, ImageBuf, PixelFormat, QuantizeOptions};
+
+/// The number of entries in the nearest-color cache, as a power of two.
+const CACHE_BITS: u32 = 12;
+
+/// A color, packed with its first stored channel in the low byte, and the
+/// number of pixels that have it.
+#[derive(Clone, Copy)]
+struct Entry {
+    color: u32,
+    count: u64,
+}
+
+/// Reduces an image to at most `options.max_colors` colors with median cut.
+///
+/// The colors are split into boxes along their widest channel at the
+/// population median until there are `max_colors` boxes, and each box is
+/// replaced by its mean color. Every pixel is then mapped to the nearest
+/// palette color, optionally with Floyd-Steinberg dithering. The alpha
+/// channel is quantized along with the color channels, and premultiplied
+/// pixels stay premultiplied. `RGBX` and `BGRX` padding bytes are copied.
+///
+/// [`crate::encode_to_memory`] calls this when `EncodeOptions::quantize` is
+/// set, so it is rarely needed directly.
+///
+/// # Returns
+///
+/// A `Result` containing a copy of the image in the same pixel format, or
+/// `Error::InvalidParameter` if `max_colors` is 0, the pixel format is
+/// `Invalid` or the pixel buffer is too small.
+pub fn quantize(image: &Image, options: &QuantizeOptions) -> Result<ImageBuf, Error> {
+    if options.max_colors == 0 || image.pixel_format == PixelFormat::Invalid {
+        return Err(Error::InvalidParameter);
+    }
+    image.check_buffer()?;
+
+    let bpp = image.pixel_format.bytes_per_pixel();
+    let channels = if image.pixel_format.has_alpha() { 4 } else { 3 };
+    let row_len = image.width as usize * bpp;
+    let mut out = ImageBuf {
+        pixels: Vec::with_capacity(row_len * image.height as usize),
+        width: image.width,
+        height: image.height,
+        pixel_format: image.pixel_format,
+        stride_in_bytes: row_len,
+    };
+    for y in 0..image.height as usize {
+        let start = y * image.stride_in_bytes;
+        out.pixels
+            .extend_from_slice(&image.pixels[start..start + row_len]);
+    }
+    if row_len == 0 {
+        return Ok(out);
+    }
+
+    let pack = |pixel: &[u8]| {
+        let mut bytes = [0; 4];
+        bytes[..channels].copy_from_slice(&pixel[..channels]);
+        u32::from_le_bytes(bytes)
+    };
+    let mut colors: Vec<u32> = out.pixels.chunks_exact(bpp).map(pack).collect();
+    colors.sort_unstable();
+    let mut entries: Vec<Entry> = Vec::new();
+    for color in colors {
+        match entries.last_mut() {
+            Some(last) if last.color == color => last.count += 1,
+            _ => entries.push(Entry { color, count: 1 }),
+        }
+    }
+    if entries.len() <= options.max_colors as usize {
+        return Ok(out);
+    }
+
+    let palette = median_cut(&mut entries, options.max_colors as usize, channels);
+    let mut cache = vec![(0u32, u32::MAX); 1 << CACHE_BITS];
+    let mut nearest = |color: [u8; 4]| {
+        let key = u32::from_le_bytes(color);
+        let slot = (key.wrapping_mul(0x9E37_79B1) >> (32 - CACHE_BITS)) as usize;
+        if cache[slot].0 != key || cache[slot].1 == u32::MAX {
+            cache[slot] = (key, nearest_index(&palette, color, channels));
+        }
+        palette[cache[slot].1 as usize]
+    };
+
+    if !options.dither {
+        for pixel in out.pixels.chunks_exact_mut(bpp) {
+            let color = nearest(pack(pixel).to_le_bytes());
+            pixel[..channels].copy_from_slice(&color[..channels]);
+        }
+        return Ok(out);
+    }
+
+    // The errors are kept in sixteenths, with a pixel of padding either side.
+    let width = image.width as usize;
+    let mut errors = vec![[0i32; 4]; width + 2];
+    let mut next_errors = vec![[0i32; 4]; width + 2];
+    for row in out.pixels.chunks_exact_mut(row_len) {
+        for (x, pixel) in row.chunks_exact_mut(bpp).enumerate() {
+            let mut target = [0u8; 4];
+            for c in 0..channels {
+                let value = pixel[c] as i32 + (errors[x + 1][c] + 8).div_euclid(16);
+                target[c] = value.clamp(0, 255) as u8;
+            }
+            let color = nearest(target);
+            for c in 0..channels {
+                let error = target[c] as i32 - color[c] as i32;
+                errors[x + 2][c] += error * 7;
+                next_errors[x][c] += error * 3;
+                next_errors[x + 1][c] += error * 5;
+                next_errors[x + 2][c] += error;
+            }
+            pixel[..channels].copy_from_slice(&color[..channels]);
+        }
+        core::mem::swap(&mut errors, &mut next_errors);
+        next_errors.fill([0; 4]);
+    }
+    Ok(out)
+}
+
+/// Splits `entries` into at most `max_colors` boxes and returns their mean
+/// colors, weighted by pixel count.
+fn median_cut(entries: &mut [Entry], max_colors: usize, channels: usize) -> Vec<[u8; 4]> {
+    let channel = |color: u32, c: usize| (color >> (c * 8)) as u8;
+    // The channel with the widest range in a box, and that range.
+    let widest = |entries: &[Entry]| {
+        (0..channels)
+            .map(|c| {
+                let (min, max) = entries.iter().fold((255, 0), |(min, max), entry| {
+                    let value = channel(entry.color, c);
+                    (value.min(min), value.max(max))
+                });
+                (c, max - min)
+            })
+            .max_by_key(|&(_, range)| range)
+            .unwrap_or((0, 0))
+    };
+
+    // Each box has its entries, the channel to split it along, and a score.
+    // The box to split next is the one whose widest range covers the most
+    // pixels, so that large areas of similar colors get the most entries.
+    let make_box = |entries: &[Entry], range: Range<usize>| {
+        let (c, width) = widest(&entries[range.clone()]);
+        let count: u64 = entries[range.clone()].iter().map(|e| e.count).sum();
+        (range, c, width as u64 * count)
+    };
+    let mut boxes = vec![make_box(entries, 0..entries.len())];
+    while boxes.len() < max_colors {
+        let Some(i) = (0..boxes.len())
+            .filter(|&i| boxes[i].2 > 0)
+            .max_by_key(|&i| boxes[i].2)
+        else {
+            break;
+        };
+        let (range, c, _) = boxes[i].clone();
+        let slice = &mut entries[range.clone()];
+        slice.sort_unstable_by_key(|entry| channel(entry.color, c));
+        let half = slice.iter().map(|e| e.count).sum::<u64>() / 2;
+        let mut seen = 0;
+        let median = slice
+            .iter()
+            .position(|entry| {
+                seen += entry.count;
+                seen > half
+            })
+            .unwrap_or(0);
+        let split = range.start + median.clamp(1, slice.len() - 1);
+        boxes[i] = make_box(entries, range.start..split);
+        boxes.push(make_box(entries, split..range.end));
+    }
+
+    boxes
+        .into_iter()
+        .map(|(range, _, _)| {
+            let entries = &entries[range];
+            let count: u64 = entries.iter().map(|e| e.count).sum();
+            let mut color = [0u8; 4];
+            for (c, value) in color.iter_mut().enumerate().take(channels) {
+                let sum: u64 = entries
+                    .iter()
+                    .map(|e| channel(e.color, c) as u64 * e.count)
+                    .sum();
+                *value = ((sum + count / 2) / count) as u8;
+            }
+            color
+        })
+        .collect()
+}
+
+/// The index of the palette color closest to `color`.
+fn nearest_index(palette: &[[u8; 4]], color: [u8; 4], channels: usize) -> u32 {
+    let distance = |entry: &[u8; 4]| -> u32 {
+        (0..channels)
+            .map(|c| (entry[c] as i32 - color[c] as i32).pow(2) as u32)
+            .sum()
+    };
+    (0..palette.len())
+        .min_by_key(|&i| distance(&palette[i]))
+        .unwrap_or(0) as u32
+}
@@ -0,0 +1,613 @@
+//! QOIR previews of camera RAW files, enabled with the `raw` feature.
+//!
+//! Cameras store a JPEG preview, usually at screen size or larger, inside
+//! every RAW file. [`encode_raw_preview`] pulls out the largest one, resizes
+//! it if asked to and encodes it as QOIR, carrying the EXIF of the RAW file
+//! over, which is the ingest path of a photo culling application.
+//!
+//! TIFF-based RAW files, such as Nikon NEF, Sony ARW, Canon CR2, Adobe DNG,
+//! Olympus ORF and Pentax PEF, are searched for JPEGs in every image
+//! directory. Canon CR3 files are searched for their `PRVW` preview.
+//!
+//! Files without a preview that can be decoded are developed instead, when
+//! they hold uncompressed sensor data, such as an uncompressed DNG: a 2x2
+//! color filter array is demosaiced at half size, or linear RGB is used as
+//! is, after applying the black and white levels and the as-shot white
+//! balance. Compressed sensor data is not developed, so those files without
+//! a preview are an error.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::raw::{encode_raw_preview, PreviewOptions};
+//!
+//! let options = PreviewOptions {
+//!     max_size: Some(2048),
+//!     ..Default::default()
+//! };
+//! let encoded = encode_raw_preview("DSC_0042.NEF", options).expect("Failed to encode");
+//! std::fs::write("DSC_0042.qoir", encoded.data).expect("Failed to write");
+//! ```
+
+use std::path::Path;
+
+use exif::{Context, Field, In, Tag};
+
+use crate::{
+    EncodeOptions, EncodedBuffer, Error, Image, PixelFormat, ResizeFilter, ResizeMode,
+    encode_to_memory,
+};
+
+/// The most image directories to visit in a TIFF-based RAW file, so that
+/// directories pointing at each other cannot loop forever.
+const MAX_IFDS: usize = 64;
+
+/// The UUID of the CR3 box holding the `PRVW` preview.
+const CR3_PREVIEW_UUID: [u8; 16] = [
+    0xea, 0xf4, 0x2b, 0x5e, 0x1c, 0x98, 0x4b, 0x88, 0xb9, 0xfb, 0xb7, 0xdc, 0x40, 0x6e, 0x4d, 0x16,
+];
+/// The UUID of the CR3 box, inside `moov`, holding the `CMT` metadata boxes.
+const CR3_METADATA_UUID: [u8; 16] = [
+    0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b, 0x6a, 0x48,
+];
+
+fn no_preview() -> Error {
+    Error::DecodingFailed("#qoir-raw: no embedded preview or uncompressed sensor data".to_string())
+}
+
+/// How to turn the preview of a RAW file into a QOIR image.
+#[derive(Debug, Clone)]
+pub struct PreviewOptions {
+    /// The longest side of the encoded image in pixels, or `None` to keep
+    /// the size of the embedded preview. Previews are never upscaled.
+    pub max_size: Option<u32>,
+    /// The filter used when the preview is resized.
+    pub filter: ResizeFilter,
+    /// Whether to copy the EXIF of the RAW file into the QOIR image. EXIF
+    /// already set in `encode_options` takes precedence.
+    pub keep_exif: bool,
+    /// The options to encode with, such as the lossiness.
+    pub encode_options: EncodeOptions,
+}
+
+impl Default for PreviewOptions {
+    fn default() -> Self {
+        PreviewOptions {
+            max_size: None,
+            filter: ResizeFilter::default(),
+            keep_exif: true,
+            encode_options: EncodeOptions::default(),
+        }
+    }
+}
+
+/// Encodes the embedded preview of a camera RAW file as QOIR.
+///
+/// # Arguments
+///
+/// * `path`: The path to the RAW file.
+/// * `options`: `PreviewOptions` controlling the size, metadata and encoding.
+///
+/// # Returns
+///
+/// A `Result` containing the `EncodedBuffer`, `Error::FileNotFound` or
+/// `Error::IoError` if the file cannot be read, or `Error::DecodingFailed`
+/// if it holds neither a preview that can be decoded nor uncompressed sensor
+/// data.
+pub fn encode_raw_preview<'a>(
+    path: impl AsRef<Path>,
+    options: PreviewOptions,
+) -> Result<EncodedBuffer<'a>, Error> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|e| Error::from(e).with_path(path))?;
+    encode_raw_preview_from_memory(&data, options)
+}
+
+/// Like [`encode_raw_preview`], for a RAW file already in memory.
+pub fn encode_raw_preview_from_memory<'a>(
+    data: &[u8],
+    options: PreviewOptions,
+) -> Result<EncodedBuffer<'a>, Error> {
+    if options.max_size == Some(0) {
+        return Err(Error::InvalidParameter);
+    }
+    let mut candidates = preview_candidates(data);
+    // Try the largest preview first, falling back to smaller ones if it is,
+    // for example, a lossless JPEG that cannot be decoded, and to developing
+    // the sensor data if there is none.
+    candidates.sort_by_key(|jpeg| core::cmp::Reverse(jpeg.len()));
+    let (jpeg, preview) = candidates
+        .into_iter()
+        .find_map(|jpeg| {
+            let image = image::load_from_memory_with_format(jpeg, image::ImageFormat::Jpeg);
+            Some((Some(jpeg), image.ok()?.into_rgb8()))
+        })
+        .or_else(|| Some((None, Tiff::new(data)?.develop()?)))
+        .ok_or_else(no_preview)?;
+
+    let mut encode_options = options.encode_options;
+    if options.keep_exif && encode_options.exif.is_none() {
+        encode_options.exif =
+            raw_exif(data).or_else(|| jpeg.and_then(jpeg_exif).map(<[u8]>::to_vec));
+    }
+
+    let (width, height) = preview.dimensions();
+    let image = Image::from_raw(preview.as_raw(), width, height, PixelFormat::RGB)?;
+    match options.max_size {
+        Some(max) if width.max(height) > max => {
+            let resized = image.resize(max, max, ResizeMode::Fit, options.filter)?;
+            encode_to_memory(resized.as_image(), encode_options)
+        }
+        _ => encode_to_memory(image, encode_options),
+    }
+}
+
+/// Finds the largest JPEG preview embedded in a RAW file, without decoding
+/// it.
+///
+/// # Returns
+///
+/// A `Result` containing the JPEG data, or `Error::DecodingFailed` if there
+/// is none. The JPEG is not checked beyond its start-of-image marker.
+pub fn extract_raw_preview(data: &[u8]) -> Result<&[u8], Error> {
+    preview_candidates(data)
+        .into_iter()
+        .max_by_key(|jpeg| jpeg.len())
+        .ok_or_else(no_preview)
+}
+
+fn preview_candidates(data: &[u8]) -> Vec<&[u8]> {
+    let candidates = match Tiff::new(data) {
+        Some(tiff) => tiff.jpegs(),
+        None => cr3_box(data, &CR3_PREVIEW_UUID)
+            .and_then(cr3_prvw)
+            .into_iter()
+            .collect(),
+    };
+    candidates
+        .into_iter()
+        .filter(|jpeg| jpeg.starts_with(&[0xFF, 0xD8]))
+        .collect()
+}
+
+/// A TIFF file, just enough of it to find the JPEGs it points at.
+struct Tiff<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+/// One entry of a TIFF image directory.
+struct IfdEntry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    /// Where the value, or the offset of the value, is stored.
+    pos: usize,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        let little_endian = match data.get(..4)? {
+            // Olympus and Panasonic use their own magic numbers.
+            b"II*\0" | b"IIRO" | b"IIRS" | b"IIU\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(Tiff {
+            data,
+            little_endian,
+        })
+    }
+
+    fn u16_at(&self, pos: usize) -> Option<u16> {
+        let bytes = self.data.get(pos..pos + 2)?.try_into().ok()?;
+        Some(match self.little_endian {
+            true => u16::from_le_bytes(bytes),
+            false => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32_at(&self, pos: usize) -> Option<u32> {
+        let bytes = self.data.get(pos..pos + 4)?.try_into().ok()?;
+        Some(match self.little_endian {
+            true => u32::from_le_bytes(bytes),
+            false => u32::from_be_bytes(bytes),
+        })
+    }
+
+    /// The entries of the directory at `offset` and the offset of the next
+    /// directory, 0 for the last one.
+    fn ifd(&self, offset: usize) -> Option<(Vec<IfdEntry>, usize)> {
+        let count = self.u16_at(offset)? as usize;
+        let entries = (0..count)
+            .map(|i| {
+                let pos = offset + 2 + i * 12;
+                Some(IfdEntry {
+                    tag: self.u16_at(pos)?,
+                    kind: self.u16_at(pos + 2)?,
+                    count: self.u32_at(pos + 4)?,
+                    pos: pos + 8,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let next = self.u32_at(offset + 2 + count * 12).unwrap_or(0);
+        Some((entries, next as usize))
+    }
+
+    /// The values of a `SHORT` or `LONG` entry.
+    fn values(&self, entry: &IfdEntry) -> Vec<usize> {
+        let size = if entry.kind == 3 { 2 } else { 4 };
+        let count = (entry.count as usize).min(MAX_IFDS);
+        let start = match count * size {
+            len if len <= 4 => entry.pos,
+            _ => self.u32_at(entry.pos).unwrap_or(0) as usize,
+        };
+        (0..count)
+            .map_while(|i| match size {
+                2 => self.u16_at(start + i * 2).map(usize::from),
+                _ => self.u32_at(start + i * 4).map(|v| v as usize),
+            })
+            .collect()
+    }
+
+    /// The first value of the `SHORT` or `LONG` entry for `tag`.
+    fn value(&self, entries: &[IfdEntry], tag: u16) -> Option<usize> {
+        let entry = entries.iter().find(|entry| entry.tag == tag)?;
+        self.values(entry).first().copied()
+    }
+
+    /// The values of a `SHORT`, `LONG` or `RATIONAL` entry as numbers.
+    fn numbers(&self, entry: &IfdEntry) -> Vec<f32> {
+        if entry.kind != 5 {
+            return self.values(entry).into_iter().map(|v| v as f32).collect();
+        }
+        let count = (entry.count as usize).min(MAX_IFDS);
+        let start = self.u32_at(entry.pos).unwrap_or(0) as usize;
+        (0..count)
+            .map_while(|i| {
+                let num = self.u32_at(start + i * 8)?;
+                let denom = self.u32_at(start + i * 8 + 4)?;
+                (denom != 0).then(|| num as f32 / denom as f32)
+            })
+            .collect()
+    }
+
+    /// Every image directory: the chain starting at the header, and the
+    /// directories given by `SubIFDs`.
+    fn directories(&self) -> Vec<Vec<IfdEntry>> {
+        let mut pending = Vec::from_iter(self.u32_at(4).map(|offset| offset as usize));
+        let mut visited = Vec::new();
+        let mut directories = Vec::new();
+        while let Some(offset) = pending.pop() {
+            if offset == 0 || visited.contains(&offset) || visited.len() == MAX_IFDS {
+                continue;
+            }
+            visited.push(offset);
+            let Some((entries, next)) = self.ifd(offset) else {
+                continue;
+            };
+            pending.push(next);
+            if let Some(sub_ifds) = entries.iter().find(|entry| entry.tag == 0x014A) {
+                pending.extend(self.values(sub_ifds));
+            }
+            directories.push(entries);
+        }
+        directories
+    }
+
+    /// The JPEGs of every image directory: those given by
+    /// `JPEGInterchangeFormat`, and reduced-resolution images stored as a
+    /// single JPEG strip.
+    fn jpegs(&self) -> Vec<&'a [u8]> {
+        let mut jpegs = Vec::new();
+        for entries in self.directories() {
+            let value = |tag| self.value(&entries, tag);
+            // JPEGInterchangeFormat and JPEGInterchangeFormatLength
+            let mut add = |start: Option<usize>, len: Option<usize>| {
+                let (Some(start), Some(len)) = (start, len) else {
+                    return;
+                };
+                if let Some(jpeg) = self.data.get(start..start.saturating_add(len)) {
+                    jpegs.push(jpeg);
+                }
+            };
+            add(value(0x0201), value(0x0202));
+            // A reduced-resolution NewSubfileType with old- or new-style JPEG
+            // compression.
+            if value(0x00FE) == Some(1) && matches!(value(0x0103), Some(6 | 7)) {
+                add(value(0x0111), value(0x0117));
+            }
+        }
+        jpegs
+    }
+
+    /// Develops the first full-resolution directory holding uncompressed
+    /// sensor data, either a 2x2 color filter array or linear RGB.
+    fn develop(&self) -> Option<image::RgbImage> {
+        self.directories().iter().find_map(|entries| {
+            let value = |tag| self.value(entries, tag);
+            // Compression and PhotometricInterpretation: uncompressed CFA or
+            // LinearRaw data.
+            let linear = match value(0x0106)? {
+                32803 => false,
+                34892 => true,
+                _ => return None,
+            };
+            if value(0x00FE).unwrap_or(0) & 1 != 0 || value(0x0103) != Some(1) {
+                return None;
+            }
+            Sensor::new(self, entries, linear)?.develop()
+        })
+    }
+}
+
+/// The uncompressed sensor data of a TIFF image directory.
+struct Sensor {
+    width: usize,
+    height: usize,
+    /// The samples of each pixel: 1 for a color filter array, 3 for linear
+    /// RGB.
+    samples: usize,
+    data: Vec<u16>,
+    black: f32,
+    white: f32,
+    /// The color, 0 for red, 1 for green and 2 for blue, of each position of
+    /// the 2x2 color filter array, in row order.
+    cfa: [u8; 4],
+    /// The multipliers bringing the channels to a neutral white.
+    balance: [f32; 3],
+}
+
+impl Sensor {
+    fn new(tiff: &Tiff, entries: &[IfdEntry], linear: bool) -> Option<Self> {
+        let entry = |tag| entries.iter().find(|entry: &&IfdEntry| entry.tag == tag);
+        let value = |tag| tiff.value(entries, tag);
+        let width = value(0x0100)?;
+        let height = value(0x0101)?;
+        let bits = value(0x0102)?;
+        let samples = value(0x0115).unwrap_or(1);
+        if samples != if linear { 3 } else { 1 } || !matches!(bits, 8 | 16) {
+            return None;
+        }
+
+        // StripOffsets and StripByteCounts. The strips must hold every
+        // sample, which also bounds the size of the image by that of the file.
+        let offsets = tiff.values(entry(0x0111)?);
+        let counts = tiff.values(entry(0x0117)?);
+        let len = width.checked_mul(height)?.checked_mul(samples)?;
+        let size = len.checked_mul(bits / 8)?;
+        let mut bytes = Vec::new();
+        for (&offset, &count) in offsets.iter().zip(&counts) {
+            if bytes.len() >= size {
+                break;
+            }
+            bytes.extend_from_slice(tiff.data.get(offset..offset.checked_add(count)?)?);
+        }
+        let data: Vec<u16> = match bits {
+            8 => bytes.get(..size)?.iter().map(|&b| u16::from(b)).collect(),
+            _ => bytes
+                .get(..size)?
+                .chunks_exact(2)
+                .map(|b| match tiff.little_endian {
+                    true => u16::from_le_bytes([b[0], b[1]]),
+                    false => u16::from_be_bytes([b[0], b[1]]),
+                })
+                .collect(),
+        };
+
+        // CFARepeatPatternDim and CFAPattern, only 2x2 patterns of red, green
+        // and blue.
+        let mut cfa = [0, 1, 1, 2];
+        if !linear {
+            let dim = tiff.values(entry(0x828D)?);
+            let pattern = entry(0x828E).filter(|entry| entry.kind == 1 && entry.count == 4)?;
+            cfa.copy_from_slice(tiff.data.get(pattern.pos..pattern.pos + 4)?);
+            if dim != [2, 2] || cfa.iter().any(|&color| color > 2) {
+                return None;
+            }
+        }
+
+        // BlackLevel, WhiteLevel and AsShotNeutral.
+        let number = |tag| tiff.numbers(entry(tag)?).first().copied();
+        let black = number(0xC61A).unwrap_or(0.0);
+        let white = number(0xC61D).unwrap_or(((1u32 << bits) - 1) as f32);
+        let neutral = entry(0xC628).map(|entry| tiff.numbers(entry));
+        let balance = match neutral.as_deref() {
+            Some(&[r, g, b]) if r > 0.0 && g > 0.0 && b > 0.0 => [g / r, 1.0, g / b],
+            _ => [1.0; 3],
+        };
+        (white > black).then_some(Sensor {
+            width,
+            height,
+            samples,
+            data,
+            black,
+            white,
+            cfa,
+            balance,
+        })
+    }
+
+    /// Develops the sensor data into sRGB. A color filter array is
+    /// demosaiced by turning each 2x2 block into one pixel, halving the size
+    /// of the image, which is plenty for a preview.
+    fn develop(&self) -> Option<image::RgbImage> {
+        let scale = 1.0 / (self.white - self.black);
+        let level = |sample: u16| (f32::from(sample) - self.black).max(0.0) * scale;
+        let (width, height) = match self.samples {
+            3 => (self.width, self.height),
+            _ => (self.width / 2, self.height / 2),
+        };
+        let mut image =
+            image::RgbImage::new(u32::try_from(width).ok()?, u32::try_from(height).ok()?);
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let (x, y) = (x as usize, y as usize);
+            let mut rgb = [0.0; 3];
+            if self.samples == 3 {
+                let start = (y * self.width + x) * 3;
+                for (channel, &sample) in rgb.iter_mut().zip(&self.data[start..start + 3]) {
+                    *channel = level(sample);
+                }
+            } else {
+                let mut counts = [0.0; 3];
+                for (i, &color) in self.cfa.iter().enumerate() {
+                    let sample = self.data[(y * 2 + i / 2) * self.width + x * 2 + i % 2];
+                    rgb[color as usize] += level(sample);
+                    counts[color as usize] += 1.0;
+                }
+                for (channel, count) in rgb.iter_mut().zip(counts) {
+                    if count > 0.0 {
+                        *channel /= count;
+                    }
+                }
+            }
+            for ((out, channel), balance) in pixel.0.iter_mut().zip(rgb).zip(self.balance) {
+                *out = crate::linear_to_srgb(channel * balance);
+            }
+        }
+        Some(image)
+    }
+}
+
+/// The boxes of an ISO base media file, as type and payload.
+fn boxes(mut data: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    core::iter::from_fn(move || {
+        let size = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+        let kind: [u8; 4] = data.get(4..8)?.try_into().ok()?;
+        let (header, size) = match size {
+            0 => (8, data.len()),
+            1 => (
+                16,
+                u64::from_be_bytes(data.get(8..16)?.try_into().ok()?) as usize,
+            ),
+            _ => (8, size),
+        };
+        let payload = data.get(header..size)?;
+        data = &data[size..];
+        Some((kind, payload))
+    })
+}
+
+/// The payload, after the UUID, of a `uuid` box of a CR3 file, at the top
+/// level or in `moov`.
+fn cr3_box<'a>(data: &'a [u8], uuid: &[u8; 16]) -> Option<&'a [u8]> {
+    let (_, ftyp) = boxes(data).next().filter(|(kind, _)| kind == b"ftyp")?;
+    if !ftyp.starts_with(b"crx ") {
+        return None;
+    }
+    let top = boxes(data);
+    let moov = boxes(data)
+        .filter(|(kind, _)| kind == b"moov")
+        .flat_map(|(_, payload)| boxes(payload));
+    top.chain(moov)
+        .filter(|(kind, _)| kind == b"uuid")
+        .find_map(|(_, payload)| payload.strip_prefix(uuid))
+}
+
+/// The JPEG in the `PRVW` box of a CR3 preview box.
+fn cr3_prvw(preview: &[u8]) -> Option<&[u8]> {
+    // The preview box starts with 8 unknown bytes. `PRVW` holds 4 unknown
+    // bytes, a `u16` 1, the width and height as `u16`s, another `u16` 1 and
+    // the JPEG length as a `u32`.
+    let (_, prvw) = boxes(preview.get(8..)?).find(|(kind, _)| kind == b"PRVW")?;
+    let len = u32::from_be_bytes(prvw.get(12..16)?.try_into().ok()?) as usize;
+    prvw.get(16..16 + len)
+}
+
+/// The EXIF of a RAW file, rebuilt from its descriptive tags and its EXIF
+/// and GPS directories.
+fn raw_exif(data: &[u8]) -> Option<Vec<u8>> {
+    let Some(metadata) = cr3_box(data, &CR3_METADATA_UUID) else {
+        return Tiff::new(data).and_then(|_| rebuild_exif(&[(data, None)]));
+    };
+    // `CMT1` holds the TIFF directory, `CMT2` the EXIF one and `CMT4` the GPS
+    // one, each as a TIFF file of its own.
+    let mut sources = Vec::new();
+    for (kind, payload) in boxes(metadata) {
+        match &kind {
+            b"CMT1" => sources.push((payload, None)),
+            b"CMT2" => sources.push((payload, Some(Context::Exif))),
+            b"CMT4" => sources.push((payload, Some(Context::Gps))),
+            _ => {}
+        }
+    }
+    rebuild_exif(&sources)
+}
+
+/// Writes the fields of the first image of each TIFF file in `sources` as
+/// EXIF. A context, if given, replaces that of every field of its file.
+///
+/// Of the TIFF tags, only the descriptive ones are kept, as the others
+/// describe the RAW data. The maker note is dropped, as its offsets point
+/// into the RAW file.
+fn rebuild_exif(sources: &[(&[u8], Option<Context>)]) -> Option<Vec<u8>> {
+    const DESCRIPTIVE_TAGS: &[Tag] = &[
+        Tag::ImageDescription,
+        Tag::Make,
+        Tag::Model,
+        Tag::Orientation,
+        Tag::XResolution,
+        Tag::YResolution,
+        Tag::ResolutionUnit,
+        Tag::Software,
+        Tag::DateTime,
+        Tag::Artist,
+        Tag::Copyright,
+    ];
+
+    let mut fields = Vec::new();
+    let mut little_endian = true;
+    for &(data, context) in sources {
+        let Ok(exif) = exif::Reader::new().read_raw(data.to_vec()) else {
+            continue;
+        };
+        little_endian = exif.little_endian();
+        for field in exif.fields().filter(|field| field.ifd_num == In::PRIMARY) {
+            let tag = match context {
+                Some(context) => Tag(context, field.tag.number()),
+                None => field.tag,
+            };
+            let keep = match tag.context() {
+                Context::Tiff => DESCRIPTIVE_TAGS.contains(&tag),
+                _ => tag != Tag::MakerNote,
+            };
+            if keep {
+                fields.push(Field {
+                    tag,
+                    ..field.clone()
+                });
+            }
+        }
+    }
+    if fields.is_empty() {
+        return None;
+    }
+
+    let mut writer = exif::experimental::Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut rebuilt = std::io::Cursor::new(Vec::new());
+    writer.write(&mut rebuilt, little_endian).ok()?;
+    Some(rebuilt.into_inner())
+}
+
+/// The EXIF in the `APP1` segment of a JPEG.
+fn jpeg_exif(jpeg: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    loop {
+        let &[0xFF, marker, hi, lo] = jpeg.get(pos..pos + 4)? else {
+            return None;
+        };
+        // The image data follows start-of-scan, so there is no more metadata.
+        if marker == 0xDA {
+            return None;
+        }
+        let len = u16::from_be_bytes([hi, lo]) as usize;
+        let segment = jpeg.get(pos + 4..pos + 2 + len)?;
+        if let Some(exif) = segment.strip_prefix(b"Exif\0\0").filter(|_| marker == 0xE1) {
+            return Some(exif);
+        }
+        pos += 2 + len;
+    }
+}
@@ -0,0 +1,362 @@
+//! Resampling of images to other dimensions.
+//!
+//! Images are resized with a separable filter: each row is resampled to the
+//! new width, then each column to the new height. The work is done on
+//! premultiplied floating-point channels, so transparent pixels don't bleed
+//! their color into their neighbours. The filters need the floating-point
+//! functions of `std`, which is why this module requires that feature.
+
+use alloc::{vec, vec::Vec};
+use core::f32::consts::PI;
+
+use crate::pixel::{convert, to_bgra};
+use crate::{Error, Image, ImageBuf, PixelFormat};
+
+/// The filter used to compute each resized pixel from the source pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ResizeFilter {
+    /// Picks the closest source pixel. Fast, but blocky and aliased.
+    Nearest,
+    /// Averages the source pixels each resized pixel covers. Good for
+    /// downscaling by large factors.
+    Box,
+    /// Interpolates linearly between neighbouring pixels.
+    Bilinear,
+    /// A cubic filter that keeps edges sharper than `Bilinear`.
+    CatmullRom,
+    /// A windowed sinc filter over three lobes. The sharpest, and the slowest.
+    #[default]
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    /// How far the filter reaches, in source pixels at a scale of 1.
+    fn support(self) -> f32 {
+        match self {
+            ResizeFilter::Nearest | ResizeFilter::Box => 0.5,
+            ResizeFilter::Bilinear => 1.0,
+            ResizeFilter::CatmullRom => 2.0,
+            ResizeFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Nearest | ResizeFilter::Box => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Bilinear => (1.0 - x).max(0.0),
+            ResizeFilter::CatmullRom => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * PI;
+        x.sin() / x
+    }
+}
+
+/// How an image is fitted into the requested width and height.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ResizeMode {
+    /// Scales to exactly the requested size, changing the aspect ratio if the
+    /// requested one differs.
+    Exact,
+    /// Scales, keeping the aspect ratio, to the largest size that fits within
+    /// the requested one. One side may come out smaller than requested.
+    #[default]
+    Fit,
+    /// Scales, keeping the aspect ratio, to the smallest size that covers the
+    /// requested one, then crops the overflow equally from both sides.
+    Fill,
+}
+
+impl Image<'_> {
+    /// Resamples the image to another size.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: The requested width in pixels.
+    /// * `height`: The requested height in pixels.
+    /// * `mode`: How the image is fitted into `width` and `height`.
+    /// * `filter`: The filter used to compute the resized pixels.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the resized `ImageBuf`, tightly packed in the
+    /// same pixel format, or `Error::InvalidParameter` if a dimension is zero,
+    /// the pixel format is `Invalid` or the pixel buffer is too small.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use qoir_rs::{decode, DecodeOptions, ResizeFilter, ResizeMode};
+    ///
+    /// let decoded = decode("input.qoir", DecodeOptions::default()).expect("Failed to decode");
+    /// let preview = decoded
+    ///     .image
+    ///     .resize(512, 512, ResizeMode::Fit, ResizeFilter::Lanczos3)
+    ///     .expect("Failed to resize");
+    /// ```
+    pub fn resize(
+        &self,
+        width: u32,
+        height: u32,
+        mode: ResizeMode,
+        filter: ResizeFilter,
+    ) -> Result<ImageBuf, Error> {
+        if width == 0
+            || height == 0
+            || self.width == 0
+            || self.height == 0
+            || self.pixel_format == PixelFormat::Invalid
+        {
+            return Err(Error::InvalidParameter);
+        }
+        self.check_buffer()?;
+
+        let resampling = Resampling::new(self.width, self.height, width, height, mode, filter);
+        let has_alpha = self.pixel_format.has_alpha();
+        let premultiplied = matches!(
+            self.pixel_format,
+            PixelFormat::BGRAPremul | PixelFormat::RGBAPremul
+        );
+        let stored_format = if premultiplied {
+            PixelFormat::BGRAPremul
+        } else if has_alpha {
+            PixelFormat::BGRANonPremul
+        } else {
+            PixelFormat::BGRX
+        };
+        let bpp = self.pixel_format.bytes_per_pixel();
+        let stride_in_bytes = resampling.width as usize * bpp;
+        let mut pixels = vec![0; stride_in_bytes * resampling.height as usize];
+
+        resampling.run(
+            |y, src_row| {
+                let row = &self.pixels[y * self.stride_in_bytes..];
+                for (dst, src) in src_row.iter_mut().zip(row.chunks_exact(bpp)) {
+                    let [b, g, r, a] = to_bgra(src, self.pixel_format).map(f32::from);
+                    let a = if has_alpha { a } else { 255.0 };
+                    *dst = if premultiplied {
+                        [b, g, r, a]
+                    } else {
+                        let scale = a / 255.0;
+                        [b * scale, g * scale, r * scale, a]
+                    };
+                }
+            },
+            |y, row| {
+                let dst_row = &mut pixels[y * stride_in_bytes..][..stride_in_bytes];
+                for (dst, &[b, g, r, a]) in dst_row.chunks_exact_mut(bpp).zip(row) {
+                    let a = a.clamp(0.0, 255.0);
+                    let unpremultiply = match a {
+                        _ if premultiplied => 1.0,
+                        0.0 => 0.0,
+                        _ => 255.0 / a,
+                    };
+                    // Ringing filters can overshoot, so keep the channels
+                    // within what the alpha allows.
+                    let max = if premultiplied { a } else { 255.0 };
+                    let channel = |c: f32| (c * unpremultiply).round().clamp(0.0, max) as u8;
+                    let bgra = [channel(b), channel(g), channel(r), a.round() as u8];
+                    convert(bgra, stored_format, self.pixel_format, dst);
+                }
+            },
+        );
+
+        Ok(ImageBuf {
+            pixels,
+            width: resampling.width,
+            height: resampling.height,
+            pixel_format: self.pixel_format,
+            stride_in_bytes,
+        })
+    }
+}
+
+/// How a resize maps the source onto the output, shared by the resizing of
+/// 8-bit and linear images.
+pub(crate) struct Resampling {
+    src_width: u32,
+    src_height: u32,
+    /// The region of the source that is resampled: x, y, width and height.
+    src_rect: (f64, f64, f64, f64),
+    /// The output width, which `ResizeMode::Fit` may make smaller than
+    /// requested.
+    pub(crate) width: u32,
+    /// The output height.
+    pub(crate) height: u32,
+    filter: ResizeFilter,
+}
+
+impl Resampling {
+    /// Fits a `src_width` x `src_height` image into `width` x `height`. All
+    /// four must be non-zero.
+    pub(crate) fn new(
+        src_width: u32,
+        src_height: u32,
+        width: u32,
+        height: u32,
+        mode: ResizeMode,
+        filter: ResizeFilter,
+    ) -> Self {
+        let (src_w, src_h) = (src_width as f64, src_height as f64);
+        let (dst_w, dst_h) = (width as f64, height as f64);
+        let (src_rect, width, height) = match mode {
+            ResizeMode::Exact => ((0.0, 0.0, src_w, src_h), width, height),
+            ResizeMode::Fit => {
+                let scale = (dst_w / src_w).min(dst_h / src_h);
+                let width = ((src_w * scale).round() as u32).clamp(1, width);
+                let height = ((src_h * scale).round() as u32).clamp(1, height);
+                ((0.0, 0.0, src_w, src_h), width, height)
+            }
+            ResizeMode::Fill => {
+                let scale = (dst_w / src_w).max(dst_h / src_h);
+                let (crop_w, crop_h) = (dst_w / scale, dst_h / scale);
+                let rect = (
+                    (src_w - crop_w) / 2.0,
+                    (src_h - crop_h) / 2.0,
+                    crop_w,
+                    crop_h,
+                );
+                (rect, width, height)
+            }
+        };
+        Resampling {
+            src_width,
+            src_height,
+            src_rect,
+            width,
+            height,
+            filter,
+        }
+    }
+
+    /// Resamples premultiplied pixels. `read_row` fills its slice with the
+    /// source row it is given, and `write_row` receives each output row.
+    pub(crate) fn run(
+        &self,
+        mut read_row: impl FnMut(usize, &mut [[f32; 4]]),
+        mut write_row: impl FnMut(usize, &[[f32; 4]]),
+    ) {
+        let (x0, y0, rect_w, rect_h) = self.src_rect;
+        let width = self.width as usize;
+        let columns = contributions(x0, rect_w, self.src_width, self.width, self.filter);
+        let rows = contributions(y0, rect_h, self.src_height, self.height, self.filter);
+
+        // Resample the rows that any output row reads to the new width.
+        let first_row = rows.iter().map(|c| c.start).min().unwrap_or(0);
+        let last_row = rows
+            .iter()
+            .map(|c| c.start + c.weights.len())
+            .max()
+            .unwrap_or(0);
+        let mut src_row = vec![[0f32; 4]; self.src_width as usize];
+        let mut wide = vec![[0f32; 4]; width * (last_row - first_row)];
+        for (y, wide_row) in (first_row..last_row).zip(wide.chunks_exact_mut(width)) {
+            read_row(y, &mut src_row);
+            for (dst, contribution) in wide_row.iter_mut().zip(&columns) {
+                *dst = contribution.apply(|x| src_row[x]);
+            }
+        }
+
+        // Then resample the columns to the new height.
+        let mut row = vec![[0f32; 4]; width];
+        for (y, contribution) in rows.iter().enumerate() {
+            for (x, dst) in row.iter_mut().enumerate() {
+                *dst = contribution.apply(|y| wide[(y - first_row) * width + x]);
+            }
+            write_row(y, &row);
+        }
+    }
+}
+
+/// The source pixels an output pixel is computed from, and their weights.
+struct Contribution {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+impl Contribution {
+    fn apply(&self, pixel: impl Fn(usize) -> [f32; 4]) -> [f32; 4] {
+        let mut sum = [0f32; 4];
+        for (i, &weight) in self.weights.iter().enumerate() {
+            let pixel = pixel(self.start + i);
+            for (sum, channel) in sum.iter_mut().zip(pixel) {
+                *sum += channel * weight;
+            }
+        }
+        sum
+    }
+}
+
+/// Computes, for each of `dst_len` output pixels, which of the `src_len`
+/// source pixels it reads, when the source span from `offset` to `offset +
+/// len` is stretched over the output.
+fn contributions(
+    offset: f64,
+    len: f64,
+    src_len: u32,
+    dst_len: u32,
+    filter: ResizeFilter,
+) -> Vec<Contribution> {
+    let scale = len / dst_len as f64;
+    // When downscaling, the filter is widened to cover every source pixel.
+    let filter_scale = scale.max(1.0) as f32;
+    let support = filter.support() * filter_scale;
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (offset + (i as f64 + 0.5) * scale) as f32;
+            if filter == ResizeFilter::Nearest {
+                let nearest = (center as usize).min(src_len as usize - 1);
+                return Contribution {
+                    start: nearest,
+                    weights: vec![1.0],
+                };
+            }
+
+            let start = ((center - support).floor().max(0.0) as usize).min(src_len as usize - 1);
+            let end = ((center + support).ceil() as usize).clamp(start + 1, src_len as usize);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|x| filter.weight((x as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            let total: f32 = weights.iter().sum();
+            if total.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|weight| *weight /= total);
+            } else {
+                // The filter missed every pixel center; fall back to the
+                // closest pixel.
+                weights.iter_mut().for_each(|weight| *weight = 0.0);
+                let nearest = ((center as usize).max(start) - start).min(weights.len() - 1);
+                weights[nearest] = 1.0;
+            }
+            Contribution { start, weights }
+        })
+        .collect()
+}
@@ -0,0 +1,237 @@
+//! The Rust encoder.
+
+use alloc::{vec, vec::Vec};
+
+use super::tile;
+use crate::checksum::crc32c;
+use crate::container::{Header, TILE_SIZE, write_chunk};
+use crate::metadata::write_custom;
+use crate::pixel::to_bgra;
+use crate::thumbnail::encode_thumbnail;
+use crate::timings::Stopwatch;
+use crate::{EncodeOptions, EncodedBuffer, Error, Image, PixelFormat, Timings};
+
+/// The largest width or height a QOIR header can hold.
+const MAX_DIMENSION: u32 = 0x00FF_FFFF;
+
+/// A 4x4 ordered dithering matrix, with thresholds from 0 to 15.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Encodes an `Image` into QOIR format in memory using the Rust backend.
+///
+/// This behaves like [`crate::encode_to_memory`]. Images with an alpha channel
+/// keep their premultiplication; all others are stored as `BGRX`. Lossy
+/// encoding drops the low `lossiness` bits of the color channels, rounding to
+/// the nearest representable value or, with `dither`, using ordered dithering.
+/// Alpha is always stored losslessly.
+///
+/// # Arguments
+///
+/// * `image`: The `Image` to encode.
+/// * `options`: `EncodeOptions` to control the encoding process.
+///
+/// # Returns
+///
+/// A `Result` containing the `EncodedBuffer` or an `Error` if encoding fails.
+pub fn encode_to_memory<'a>(
+    image: Image<'_>,
+    options: EncodeOptions,
+) -> Result<EncodedBuffer<'a>, Error> {
+    let mut data = Vec::new();
+    let timings = encode_into(image, &options, &mut data)?;
+    let mut encoded = EncodedBuffer::from_vec(data);
+    encoded.timings = options.collect_timings.then_some(timings);
+    Ok(encoded)
+}
+
+/// Encodes an `Image` into `data`, replacing its contents but reusing its
+/// allocation, and returns the time spent on each stage.
+pub(crate) fn encode_into(
+    image: Image<'_>,
+    options: &EncodeOptions,
+    data: &mut Vec<u8>,
+) -> Result<Timings, Error> {
+    let src_format = image.pixel_format;
+    if src_format == PixelFormat::Invalid {
+        return Err(Error::EncodingFailed("#qoir: unsupported pixfmt".into()));
+    }
+    if image.width > MAX_DIMENSION || image.height > MAX_DIMENSION {
+        return Err(Error::EncodingFailed(
+            "#qoir: unsupported pixbuf dimensions".into(),
+        ));
+    }
+    if options.lossiness > 7 {
+        return Err(Error::InvalidParameter);
+    }
+
+    let row_len = image.width as usize * src_format.bytes_per_pixel();
+    if image.height > 0
+        && (image.stride_in_bytes < row_len
+            || image.pixels.len() < image.stride_in_bytes * (image.height as usize - 1) + row_len)
+    {
+        return Err(Error::InvalidParameter);
+    }
+    let mut clock = Stopwatch::start(options.collect_timings);
+    let thumbnail = encode_thumbnail(&image, options.embed_thumbnail, encode_to_memory)?;
+    let quantized = match &options.quantize {
+        Some(quantize) => Some(crate::quantize(&image, quantize)?),
+        None => None,
+    };
+    let image = quantized.as_ref().map_or(image, |buf| buf.as_image());
+    let mut timings = Timings {
+        format_convert: clock.lap(),
+        ..Default::default()
+    };
+
+    let header = Header {
+        width: image.width,
+        height: image.height,
+        pixel_format: match src_format {
+            PixelFormat::BGRANonPremul | PixelFormat::RGBANonPremul => PixelFormat::BGRANonPremul,
+            PixelFormat::BGRAPremul | PixelFormat::RGBAPremul => PixelFormat::BGRAPremul,
+            _ => PixelFormat::BGRX,
+        },
+        lossiness: options.lossiness,
+    };
+
+    let custom = write_custom(&options.custom_metadata)?;
+    let tiles = encode_tiles(&image, options, &mut timings);
+    clock.lap();
+
+    data.clear();
+    data.reserve(tiles.len() + 64);
+    header.write(data);
+    let metadata = [
+        (b"CICP", &options.cicp_profile),
+        (b"ICCP", &options.icc_profile),
+        (b"EXIF", &options.exif),
+        (b"XMP ", &options.xmp),
+    ];
+    for (tag, payload) in metadata {
+        if let Some(payload) = payload {
+            write_chunk(data, *tag, payload);
+        }
+    }
+    if !custom.is_empty() {
+        write_chunk(data, *b"APPD", &custom);
+    }
+    if let Some(thumbnail) = &thumbnail {
+        write_chunk(data, *b"THMB", thumbnail);
+    }
+    write_chunk(data, *b"QPIX", &tiles);
+    if options.embed_checksum {
+        write_chunk(data, *b"QSUM", &crc32c(&tiles).to_le_bytes());
+    }
+    write_chunk(data, *b"QEND", &[]);
+    timings.chunk_write = clock.lap();
+    Ok(timings)
+}
+
+/// Encodes all tiles, in row-major order, adding the time spent to `timings`.
+fn encode_tiles(image: &Image<'_>, options: &EncodeOptions, timings: &mut Timings) -> Vec<u8> {
+    let bands = (0..image.height).step_by(TILE_SIZE as usize);
+
+    // Each band of tiles is encoded on its own, then the bands are joined in
+    // order.
+    #[cfg(feature = "rayon")]
+    if bands.len() > 1 {
+        use rayon::prelude::*;
+
+        let bands: Vec<u32> = bands.collect();
+        let encoded: Vec<(Vec<u8>, Timings)> = crate::thread_pool::install(|| {
+            bands
+                .into_par_iter()
+                .map_init(Scratch::new, |scratch, ty| {
+                    let mut band = Vec::new();
+                    let mut band_timings = Timings::default();
+                    encode_band(image, options, ty, &mut band, scratch, &mut band_timings);
+                    (band, band_timings)
+                })
+                .collect()
+        });
+        let mut tiles = Vec::with_capacity(encoded.iter().map(|(band, _)| band.len()).sum());
+        for (band, band_timings) in encoded {
+            tiles.extend_from_slice(&band);
+            *timings += band_timings;
+        }
+        return tiles;
+    }
+
+    let mut tiles = Vec::new();
+    let mut scratch = Scratch::new();
+    for ty in bands {
+        encode_band(image, options, ty, &mut tiles, &mut scratch, timings);
+    }
+    tiles
+}
+
+/// Buffers reused from one tile to the next.
+struct Scratch {
+    tile_pixels: Vec<u8>,
+    ops: Vec<u8>,
+    compressed: Vec<u8>,
+}
+
+impl Scratch {
+    fn new() -> Self {
+        Self {
+            tile_pixels: vec![0; (TILE_SIZE * TILE_SIZE * 4) as usize],
+            ops: Vec::new(),
+            compressed: Vec::new(),
+        }
+    }
+}
+
+/// Encodes the row of tiles whose top edge is at `ty`, appending them to `dst`.
+fn encode_band(
+    image: &Image<'_>,
+    options: &EncodeOptions,
+    ty: u32,
+    dst: &mut Vec<u8>,
+    scratch: &mut Scratch,
+    timings: &mut Timings,
+) {
+    let src_format = image.pixel_format;
+    for tx in (0..image.width).step_by(TILE_SIZE as usize) {
+        let mut clock = Stopwatch::start(options.collect_timings);
+        let tile_width = (image.width - tx).min(TILE_SIZE) as usize;
+        let tile_height = (image.height - ty).min(TILE_SIZE) as usize;
+        let tile_pixels = &mut scratch.tile_pixels[..tile_width * tile_height * 4];
+
+        for (y, row) in tile_pixels.chunks_exact_mut(tile_width * 4).enumerate() {
+            let y = ty as usize + y;
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let x = tx as usize + x;
+                let bpp = src_format.bytes_per_pixel();
+                let offset = y * image.stride_in_bytes + x * bpp;
+                let mut bgra = to_bgra(&image.pixels[offset..offset + bpp], src_format);
+                if options.lossiness > 0 {
+                    let threshold = options.dither.then(|| BAYER[y % 4][x % 4]);
+                    for channel in &mut bgra[..3] {
+                        *channel = quantize(*channel, options.lossiness, threshold);
+                    }
+                }
+                pixel.copy_from_slice(&bgra);
+            }
+        }
+
+        timings.format_convert += clock.lap();
+
+        tile::encode_tile(tile_pixels, dst, &mut scratch.ops, &mut scratch.compressed);
+        timings.tile_encode += clock.lap();
+    }
+}
+
+/// Reduces an 8-bit value to `8 - lossiness` bits.
+///
+/// Without a dithering threshold this picks the closest value after
+/// dequantization. With one, the value is rounded up or down depending on the
+/// threshold, so that neighbouring pixels average out to the original color.
+fn quantize(value: u8, lossiness: u8, threshold: Option<u8>) -> u8 {
+    let max = (1u32 << (8 - lossiness)) - 1;
+    let bias = match threshold {
+        Some(threshold) => (threshold as u32 * 2 + 1) * 255 / 32,
+        None => 127,
+    };
+    ((value as u32 * max + bias) / 255) as u8
+}
@@ -0,0 +1,409 @@
+//! Decoding straight into a GPU texture with a wgpu compute shader, enabled
+//! with the `gpu` feature.
+//!
+//! The work is split between the CPU and the GPU. The CPU walks the tiles and
+//! undoes their LZ4 compression, which is byte-serial and branchy, then
+//! uploads the literal and opcode streams. A compute shader decodes one tile
+//! per invocation, dequantizes lossy images, swizzles BGRA to RGBA and writes
+//! the pixels to an `Rgba8Unorm` texture. The decoded image never goes
+//! through system memory, so a viewer can show a 100 MP image without copying
+//! 400 MB of pixels to the GPU.
+//!
+//! The shader cannot report errors. Literal tiles and LZ4 streams are checked
+//! on the CPU, but a corrupt opcode stream leaves wrong or black pixels
+//! instead of failing. Use [`crate::verify`] first on files that may be
+//! damaged.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::rust_backend::gpu::GpuDecoder;
+//!
+//! # fn run(device: &wgpu::Device, queue: &wgpu::Queue) {
+//! let decoder = GpuDecoder::new(device, queue);
+//! let qoir_data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+//! let image = decoder.decode(&qoir_data).expect("Failed to decode");
+//! let view = image.texture.create_view(&wgpu::TextureViewDescriptor::default());
+//! # }
+//! ```
+
+use alloc::vec::Vec;
+
+use wgpu::util::DeviceExt;
+
+use super::tile::{
+    MAX_OP_LEN, TILE_FORMAT_LITERALS, TILE_FORMAT_LZ4_LITERALS, TILE_FORMAT_LZ4_OPCODES,
+    TILE_FORMAT_OPCODES,
+};
+use super::{dequantize_table, lz4, unsupported_pixbuf_dimensions};
+use crate::container::{self, Container, TILE_SIZE, invalid_data};
+use crate::{Error, PixelFormat};
+
+const TILE_KIND_LITERALS: u32 = 0;
+const TILE_KIND_OPCODES: u32 = 1;
+
+/// The number of tiles each workgroup decodes, which must match the shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// An image decoded into a GPU texture.
+#[derive(Debug)]
+pub struct GpuImage {
+    /// The decoded pixels, as an `Rgba8Unorm` texture of the image's size.
+    ///
+    /// It can be bound as a sampled or storage texture and copied from.
+    pub texture: wgpu::Texture,
+    /// The image width in pixels.
+    pub width: u32,
+    /// The image height in pixels.
+    pub height: u32,
+    /// Whether the color channels are premultiplied by alpha, which is the
+    /// case for images encoded as `BGRAPremul`. Images encoded as `BGRX` are
+    /// opaque.
+    pub premultiplied: bool,
+}
+
+/// Decodes QOIR images into textures on a wgpu device.
+///
+/// Creating a decoder compiles the shader, so one decoder should be kept and
+/// reused for every image.
+#[derive(Debug)]
+pub struct GpuDecoder {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+/// Tiles whose streams are uploaded and decoded together.
+#[derive(Default)]
+struct Batch {
+    /// The index of the first tile in the image.
+    first_tile: u32,
+    /// Each tile's stream offset, stream length and kind.
+    tiles: Vec<[u32; 3]>,
+    /// The tiles' streams, each padded to a multiple of 4 bytes.
+    stream: Vec<u8>,
+}
+
+impl GpuDecoder {
+    /// Whether devices from `adapter` can run the decoder, which needs compute
+    /// shaders and writable `Rgba8Unorm` storage textures. WebGL2 and some
+    /// older OpenGL drivers have neither.
+    pub fn is_supported(adapter: &wgpu::Adapter) -> bool {
+        let compute = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+        let storage = adapter
+            .get_texture_format_features(wgpu::TextureFormat::Rgba8Unorm)
+            .allowed_usages
+            .contains(wgpu::TextureUsages::STORAGE_BINDING);
+        compute && storage
+    }
+
+    /// Creates a decoder for `device`, submitting its work to `queue`.
+    ///
+    /// The device must come from an adapter for which
+    /// [`is_supported`](Self::is_supported) returns `true`.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Self {
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("qoir decode"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("qoir decode"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+                storage(3),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: wgpu::TextureFormat::Rgba8Unorm,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("qoir decode"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("qoir decode"),
+            layout: Some(&pipeline_layout),
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        GpuDecoder {
+            device: device.clone(),
+            queue: queue.clone(),
+            pipeline,
+            layout,
+        }
+    }
+
+    /// Decodes QOIR image data into a new texture.
+    ///
+    /// The tiles are decompressed on the calling thread and the shader work
+    /// is submitted to the queue before returning, without waiting for it.
+    /// Later commands that use the texture run after the decode finishes.
+    ///
+    /// Large images are split into several dispatches so that no buffer
+    /// exceeds the device's limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: A slice of bytes containing the QOIR encoded image data.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `GpuImage`, or an `Error` if the data is
+    /// invalid or the image is larger than the device's textures can be.
+    pub fn decode(&self, data: &[u8]) -> Result<GpuImage, Error> {
+        let container = Container::parse(data)?;
+        let header = container.header;
+        let limits = self.device.limits();
+        let max_dimension = limits.max_texture_dimension_2d;
+        if header.width == 0
+            || header.height == 0
+            || header.width > max_dimension
+            || header.height > max_dimension
+        {
+            return Err(unsupported_pixbuf_dimensions());
+        }
+
+        let texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("qoir image"),
+            size: wgpu::Extent3d {
+                width: header.width,
+                height: header.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsages::STORAGE_BINDING
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let dequantize: Vec<u8> = dequantize_table(header.lossiness)
+            .iter()
+            .flat_map(|&value| u32::from(value).to_le_bytes())
+            .collect();
+        let dequantize = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("qoir dequantize"),
+                contents: &dequantize,
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+        let max_stream_len =
+            (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size) as usize;
+        let max_tiles =
+            limits.max_compute_workgroups_per_dimension as usize * WORKGROUP_SIZE as usize;
+        let tiles_x = header.width.div_ceil(TILE_SIZE);
+        let params = [
+            header.width,
+            header.height,
+            tiles_x,
+            (header.pixel_format == PixelFormat::BGRX) as u32,
+        ];
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("qoir decode"),
+            });
+        let mut batch = Batch::default();
+        let mut band = Batch::default();
+        let mut scratch = Vec::new();
+        let mut rest = container.tiles;
+        for ty in (0..header.height).step_by(TILE_SIZE as usize) {
+            band.tiles.clear();
+            band.stream.clear();
+            let tile_height = (header.height - ty).min(TILE_SIZE) as usize;
+            for tx in (0..header.width).step_by(TILE_SIZE as usize) {
+                let (format, payload, remaining) = container::next_tile(rest)?;
+                rest = remaining;
+                let pixels = (header.width - tx).min(TILE_SIZE) as usize * tile_height;
+                push_tile(&mut band, format, payload, pixels, &mut scratch)?;
+            }
+
+            if band.stream.len() > max_stream_len {
+                return Err(unsupported_pixbuf_dimensions());
+            }
+            if batch.stream.len() + band.stream.len() > max_stream_len
+                || batch.tiles.len() + band.tiles.len() > max_tiles
+            {
+                self.dispatch(&mut encoder, &batch, params, &dequantize, &view);
+                batch.first_tile += batch.tiles.len() as u32;
+                batch.tiles.clear();
+                batch.stream.clear();
+            }
+            let base = batch.stream.len() as u32;
+            batch.tiles.extend(
+                band.tiles
+                    .iter()
+                    .map(|&[offset, len, kind]| [base + offset, len, kind]),
+            );
+            batch.stream.extend_from_slice(&band.stream);
+        }
+
+        if !rest.is_empty() {
+            return Err(invalid_data());
+        }
+        self.dispatch(&mut encoder, &batch, params, &dequantize, &view);
+        self.queue.submit([encoder.finish()]);
+
+        Ok(GpuImage {
+            texture,
+            width: header.width,
+            height: header.height,
+            premultiplied: header.pixel_format == PixelFormat::BGRAPremul,
+        })
+    }
+
+    /// Records a compute pass that decodes the tiles of `batch`.
+    fn dispatch(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        batch: &Batch,
+        [width, height, tiles_x, opaque]: [u32; 4],
+        dequantize: &wgpu::Buffer,
+        view: &wgpu::TextureView,
+    ) {
+        if batch.tiles.is_empty() {
+            return;
+        }
+        let tile_count = batch.tiles.len() as u32;
+        let params = [width, height, tiles_x, batch.first_tile, tile_count, opaque];
+        let buffer = |label, contents: &[u8], usage| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage,
+                })
+        };
+        let params = buffer(
+            "qoir params",
+            &to_bytes(&params),
+            wgpu::BufferUsages::UNIFORM,
+        );
+        let tiles = buffer(
+            "qoir tiles",
+            &to_bytes(batch.tiles.as_flattened()),
+            wgpu::BufferUsages::STORAGE,
+        );
+        let stream = buffer("qoir stream", &batch.stream, wgpu::BufferUsages::STORAGE);
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("qoir decode"),
+            layout: &self.layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: tiles.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: stream.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: dequantize.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+            ],
+        });
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("qoir decode"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(tile_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+}
+
+/// Appends one tile of `pixels` pixels to `batch`, decompressing it if needed.
+fn push_tile(
+    batch: &mut Batch,
+    format: u8,
+    payload: &[u8],
+    pixels: usize,
+    scratch: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let max_len = pixels * MAX_OP_LEN;
+    let (kind, stream) = match format {
+        TILE_FORMAT_LITERALS => (TILE_KIND_LITERALS, payload),
+        TILE_FORMAT_OPCODES => (TILE_KIND_OPCODES, payload),
+        TILE_FORMAT_LZ4_LITERALS => {
+            lz4::decode_block(payload, scratch, max_len).ok_or_else(invalid_data)?;
+            (TILE_KIND_LITERALS, &scratch[..])
+        }
+        TILE_FORMAT_LZ4_OPCODES => {
+            lz4::decode_block(payload, scratch, max_len).ok_or_else(invalid_data)?;
+            (TILE_KIND_OPCODES, &scratch[..])
+        }
+        _ => return Err(invalid_data()),
+    };
+    if kind == TILE_KIND_LITERALS && stream.len() != pixels * 4 {
+        return Err(invalid_data());
+    }
+
+    batch
+        .tiles
+        .push([batch.stream.len() as u32, stream.len() as u32, kind]);
+    batch.stream.extend_from_slice(stream);
+    batch
+        .stream
+        .resize(batch.stream.len().next_multiple_of(4), 0);
+    Ok(())
+}
+
+fn to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
@@ -0,0 +1,151 @@
+// Decodes QOIR tiles into an rgba8unorm storage texture, one tile per
+// invocation. The CPU has already undone the LZ4 compression, so each tile's
+// stream holds either literal BGRA pixels or opcodes, starting at a 4-byte
+// aligned offset. See `tile.rs` for the opcodes.
+
+struct Params {
+    width: u32,
+    height: u32,
+    tiles_x: u32,
+    // The index of the first tile of this batch in the whole image.
+    first_tile: u32,
+    tile_count: u32,
+    // Whether the alpha channel is ignored (BGRX images).
+    opaque: u32,
+}
+
+struct Tile {
+    offset: u32,
+    len: u32,
+    // 0 for literals, 1 for opcodes.
+    kind: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> tiles: array<Tile>;
+@group(0) @binding(2) var<storage, read> stream: array<u32>;
+@group(0) @binding(3) var<storage, read> dequantize: array<u32, 256>;
+@group(0) @binding(4) var output: texture_storage_2d<rgba8unorm, write>;
+
+const OP_RUNL: u32 = 0xD7u;
+const OP_BGRA2: u32 = 0xDFu;
+const OP_BGRA4: u32 = 0xE7u;
+const OP_BGRA8: u32 = 0xEFu;
+const OP_BGR8: u32 = 0xF7u;
+const OP_A8: u32 = 0xFFu;
+
+fn byte_at(pos: u32) -> u32 {
+    return (stream[pos >> 2u] >> ((pos & 3u) * 8u)) & 0xFFu;
+}
+
+// Adds per-channel deltas, modulo 256, to a pixel packed as B | G << 8 |
+// R << 16 | A << 24.
+fn add(pixel: u32, delta: vec4<u32>) -> u32 {
+    let b = (pixel + delta.x) & 0xFFu;
+    let g = ((pixel >> 8u) + delta.y) & 0xFFu;
+    let r = ((pixel >> 16u) + delta.z) & 0xFFu;
+    let a = ((pixel >> 24u) + delta.w) & 0xFFu;
+    return b | (g << 8u) | (r << 16u) | (a << 24u);
+}
+
+fn store(x0: u32, y0: u32, tile_width: u32, n: u32, pixel: u32) {
+    let b = dequantize[pixel & 0xFFu];
+    let g = dequantize[(pixel >> 8u) & 0xFFu];
+    let r = dequantize[(pixel >> 16u) & 0xFFu];
+    var a = pixel >> 24u;
+    if (params.opaque != 0u) {
+        a = 0xFFu;
+    }
+    let position = vec2<i32>(i32(x0 + n % tile_width), i32(y0 + n / tile_width));
+    textureStore(output, position, vec4<f32>(f32(r), f32(g), f32(b), f32(a)) / 255.0);
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.tile_count) {
+        return;
+    }
+    let tile = tiles[id.x];
+    let index = params.first_tile + id.x;
+    let x0 = (index % params.tiles_x) * 64u;
+    let y0 = (index / params.tiles_x) * 64u;
+    let tile_width = min(64u, params.width - x0);
+    let count = tile_width * min(64u, params.height - y0);
+
+    if (tile.kind == 0u) {
+        for (var n = 0u; n < count; n++) {
+            store(x0, y0, tile_width, n, stream[(tile.offset >> 2u) + n]);
+        }
+        return;
+    }
+
+    var cache: array<u32, 64>;
+    var cache_pos = 0u;
+    var pixel = 0xFF000000u;
+    var pos = tile.offset;
+    let end = tile.offset + tile.len;
+    var n = 0u;
+
+    while (pos < end && n < count) {
+        let op = byte_at(pos);
+        var run = 1u;
+        var append = true;
+
+        if ((op & 3u) == 0u) {
+            pixel = cache[op >> 2u];
+            append = false;
+            pos += 1u;
+        } else if ((op & 3u) == 1u) {
+            let delta = vec4<u32>((op >> 2u) & 3u, (op >> 4u) & 3u, (op >> 6u) & 3u, 0u);
+            pixel = add(pixel, delta + vec4<u32>(254u, 254u, 254u, 0u));
+            pos += 1u;
+        } else if ((op & 3u) == 2u) {
+            let bits = byte_at(pos + 1u);
+            let dg = (op >> 2u) + 224u;
+            pixel = add(pixel, vec4<u32>(dg + (bits & 0x0Fu) + 248u, dg, dg + (bits >> 4u) + 248u, 0u));
+            pos += 2u;
+        } else if ((op & 7u) == 3u) {
+            let bits = (op | (byte_at(pos + 1u) << 8u) | (byte_at(pos + 2u) << 16u)) >> 3u;
+            let delta = vec4<u32>(bits & 0x7Fu, (bits >> 7u) & 0x7Fu, (bits >> 14u) & 0x7Fu, 0u);
+            pixel = add(pixel, delta + vec4<u32>(192u, 192u, 192u, 0u));
+            pos += 3u;
+        } else if (op == OP_RUNL) {
+            run = byte_at(pos + 1u) + 1u;
+            append = false;
+            pos += 2u;
+        } else if (op == OP_BGRA2) {
+            let bits = byte_at(pos + 1u);
+            let delta = vec4<u32>(bits & 3u, (bits >> 2u) & 3u, (bits >> 4u) & 3u, bits >> 6u);
+            pixel = add(pixel, delta + vec4<u32>(254u, 254u, 254u, 254u));
+            pos += 2u;
+        } else if (op == OP_BGRA4) {
+            let bits = byte_at(pos + 1u) | (byte_at(pos + 2u) << 8u);
+            let delta = vec4<u32>(bits & 0x0Fu, (bits >> 4u) & 0x0Fu, (bits >> 8u) & 0x0Fu, bits >> 12u);
+            pixel = add(pixel, delta + vec4<u32>(248u, 248u, 248u, 248u));
+            pos += 3u;
+        } else if (op == OP_BGRA8) {
+            let delta = vec4<u32>(byte_at(pos + 1u), byte_at(pos + 2u), byte_at(pos + 3u), byte_at(pos + 4u));
+            pixel = add(pixel, delta);
+            pos += 5u;
+        } else if (op == OP_BGR8) {
+            pixel = add(pixel, vec4<u32>(byte_at(pos + 1u), byte_at(pos + 2u), byte_at(pos + 3u), 0u));
+            pos += 4u;
+        } else if (op == OP_A8) {
+            pixel = add(pixel, vec4<u32>(0u, 0u, 0u, byte_at(pos + 1u)));
+            pos += 2u;
+        } else {
+            run = (op >> 3u) + 1u;
+            append = false;
+            pos += 1u;
+        }
+
+        if (append) {
+            cache[cache_pos] = pixel;
+            cache_pos = (cache_pos + 1u) & 63u;
+        }
+        for (var i = 0u; i < run && n < count; i++) {
+            store(x0, y0, tile_width, n, pixel);
+            n++;
+        }
+    }
+}
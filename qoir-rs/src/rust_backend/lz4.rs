@@ -0,0 +1,137 @@
+//! Encoder and decoder for the LZ4 block format, used to compress individual
+//! tiles.
+
+use alloc::vec::Vec;
+
+const MIN_MATCH: usize = 4;
+/// The last bytes of a block are always literals.
+const LAST_LITERALS: usize = 5;
+/// A match cannot start within this many bytes of the end of a block.
+const MF_LIMIT: usize = 12;
+const MAX_OFFSET: usize = 0xFFFF;
+const HASH_BITS: u32 = 12;
+
+/// Compresses `src` into `dst` as a single LZ4 block, replacing its contents.
+///
+/// This is a simple greedy compressor: each position is looked up in a hash
+/// table of the most recent position with the same four bytes.
+pub(crate) fn encode_block(src: &[u8], dst: &mut Vec<u8>) {
+    dst.clear();
+    let mut table = [usize::MAX; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut pos = 0;
+
+    while pos + MF_LIMIT <= src.len() {
+        let seq = read_u32(src, pos);
+        let hash = (seq.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+        let candidate = table[hash];
+        table[hash] = pos;
+
+        if candidate == usize::MAX
+            || pos - candidate > MAX_OFFSET
+            || read_u32(src, candidate) != seq
+        {
+            pos += 1;
+            continue;
+        }
+
+        let match_limit = src.len() - LAST_LITERALS;
+        let mut len = MIN_MATCH;
+        while pos + len < match_limit && src[candidate + len] == src[pos + len] {
+            len += 1;
+        }
+
+        write_sequence(dst, &src[anchor..pos], Some((pos - candidate, len)));
+        pos += len;
+        anchor = pos;
+    }
+
+    write_sequence(dst, &src[anchor..], None);
+}
+
+fn write_sequence(dst: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    dst.push(((literals.len().min(15) as u8) << 4) | match_len.min(15) as u8);
+    write_length(dst, literals.len());
+    dst.extend_from_slice(literals);
+
+    if let Some((offset, _)) = matched {
+        dst.extend_from_slice(&(offset as u16).to_le_bytes());
+        write_length(dst, match_len);
+    }
+}
+
+fn write_length(dst: &mut Vec<u8>, len: usize) {
+    if len < 15 {
+        return;
+    }
+    let mut rest = len - 15;
+    while rest >= 255 {
+        dst.push(0xFF);
+        rest -= 255;
+    }
+    dst.push(rest as u8);
+}
+
+fn read_u32(src: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([src[pos], src[pos + 1], src[pos + 2], src[pos + 3]])
+}
+
+/// Decompresses an LZ4 block into `dst`, replacing its contents.
+///
+/// Returns `None` if the block is malformed or would decompress to more than
+/// `max_len` bytes.
+pub(crate) fn decode_block(src: &[u8], dst: &mut Vec<u8>, max_len: usize) -> Option<()> {
+    dst.clear();
+    let mut pos = 0;
+
+    loop {
+        let token = *src.get(pos)?;
+        pos += 1;
+
+        let literal_len = read_length(src, &mut pos, (token >> 4) as usize)?;
+        let literals = src.get(pos..pos.checked_add(literal_len)?)?;
+        if dst.len() + literal_len > max_len {
+            return None;
+        }
+        dst.extend_from_slice(literals);
+        pos += literal_len;
+
+        // The last sequence has literals only.
+        if pos == src.len() {
+            return Some(());
+        }
+
+        let offset = u16::from_le_bytes([*src.get(pos)?, *src.get(pos + 1)?]) as usize;
+        pos += 2;
+        if offset == 0 || offset > dst.len() {
+            return None;
+        }
+
+        let match_len = read_length(src, &mut pos, (token & 0x0F) as usize)? + 4;
+        if dst.len() + match_len > max_len {
+            return None;
+        }
+
+        // Matches may overlap the bytes they produce, so copy one at a time.
+        let start = dst.len() - offset;
+        for i in start..start + match_len {
+            dst.push(dst[i]);
+        }
+    }
+}
+
+fn read_length(src: &[u8], pos: &mut usize, nibble: usize) -> Option<usize> {
+    let mut len = nibble;
+    if nibble == 0x0F {
+        loop {
+            let byte = *src.get(*pos)?;
+            *pos += 1;
+            len = len.checked_add(byte as usize)?;
+            if byte != 0xFF {
+                break;
+            }
+        }
+    }
+    Some(len)
+}
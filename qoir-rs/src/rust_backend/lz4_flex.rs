@@ -0,0 +1,26 @@
+//! LZ4 block compression backed by the `lz4_flex` crate, used in place of the
+//! built-in implementation when the `lz4-flex` feature is enabled.
+
+use alloc::vec::Vec;
+
+use lz4_flex::block::{compress_into, decompress_into, get_maximum_output_size};
+
+/// Compresses `src` into `dst` as a single LZ4 block, replacing its contents.
+pub(crate) fn encode_block(src: &[u8], dst: &mut Vec<u8>) {
+    dst.clear();
+    dst.resize(get_maximum_output_size(src.len()), 0);
+    let len = compress_into(src, dst).expect("dst holds the largest possible block");
+    dst.truncate(len);
+}
+
+/// Decompresses an LZ4 block into `dst`, replacing its contents.
+///
+/// Returns `None` if the block is malformed or would decompress to more than
+/// `max_len` bytes.
+pub(crate) fn decode_block(src: &[u8], dst: &mut Vec<u8>, max_len: usize) -> Option<()> {
+    dst.clear();
+    dst.resize(max_len, 0);
+    let len = decompress_into(src, dst).ok()?;
+    dst.truncate(len);
+    Some(())
+}
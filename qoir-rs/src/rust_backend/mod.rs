@@ -0,0 +1,931 @@
+//! A pure-Rust implementation of QOIR, enabled with the `rust-backend` feature.
+//!
+//! It needs no C toolchain, which makes it usable where `qoir.c` cannot be
+//! built and lets the codec be fuzzed or run under Miri. When the
+//! `c-backend` feature is disabled the crate's top-level functions forward
+//! here. With both features enabled the C library stays the default and the
+//! functions in this module can be called directly, for example to compare
+//! the two.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::{rust_backend, DecodeOptions};
+//!
+//! let qoir_data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+//! let decoded_image = rust_backend::decode_from_memory(&qoir_data, DecodeOptions::default())
+//!     .expect("Failed to decode");
+//! println!("Image decoded: {}x{}", decoded_image.image.width, decoded_image.image.height);
+//! ```
+
+mod encode;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(not(feature = "lz4-flex"))]
+mod lz4;
+#[cfg(feature = "lz4-flex")]
+#[path = "lz4_flex.rs"]
+mod lz4;
+mod tile;
+
+#[cfg(feature = "std")]
+pub(crate) use encode::encode_into;
+pub use encode::encode_to_memory;
+pub(crate) use tile::decode_tile;
+
+use alloc::{
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use core::ops::{ControlFlow, Range};
+
+use crate::container::{self, Container, Header, TILE_SIZE, invalid_data, unsupported_pixfmt};
+use crate::metadata::parse_custom;
+use crate::pixel::convert;
+use crate::timings::Stopwatch;
+use crate::{
+    DecodeOptions, DecodedImage, DecodedResult, Error, Image, PixelFormat, Rectangle, Timings,
+};
+
+fn out_of_memory() -> Error {
+    Error::DecodingFailed("#qoir: out of memory".to_string())
+}
+
+fn unsupported_pixbuf_dimensions() -> Error {
+    Error::DecodingFailed("#qoir: unsupported pixbuf dimensions".to_string())
+}
+
+/// Buffers allocated by the Rust decoder, kept alive by a `DecodedImage`.
+pub(crate) struct DecodedBuffers {
+    pixels: Vec<u8>,
+    cicp: Option<Vec<u8>>,
+    iccp: Option<Vec<u8>>,
+    exif: Option<Vec<u8>>,
+    xmp: Option<Vec<u8>>,
+}
+
+/// Decodes QOIR image data from a byte slice using the Rust backend.
+///
+/// This behaves like [`crate::decode_from_memory`], including the handling of
+/// clip rectangles and offsets: the destination buffer has the same size as
+/// the image, and pixels outside the clipped area are left zeroed. It also
+/// implements `DecodeOptions::tolerant`.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+/// * `options`: `DecodeOptions` to control the decoding process.
+///
+/// # Returns
+///
+/// A `Result` containing the `DecodedImage` or an `Error` if decoding fails.
+pub fn decode_from_memory<'a>(
+    data: &'_ [u8],
+    options: DecodeOptions,
+) -> Result<DecodedImage<'a>, Error> {
+    decode(data, options, None)?.ok_or_else(invalid_data)
+}
+
+/// Decodes QOIR image data band by band, calling `on_band` each time a row of
+/// tiles has been drawn, so that a viewer can paint the image as it decodes
+/// instead of waiting for all of it.
+///
+/// The bands are decoded from the top down on the calling thread. For
+/// coarse-to-fine painting, show a small pyramid level or smart preview
+/// rendition first.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+/// * `options`: `DecodeOptions` to control the decoding process, as for
+///   [`decode_from_memory`].
+/// * `on_band`: Called with the rectangle that was just drawn, in destination
+///   coordinates, and the destination image so far; rows that are still to be
+///   decoded are zeroed. Bands that are clipped away are skipped. Return
+///   `ControlFlow::Break(())` to stop decoding.
+///
+/// # Returns
+///
+/// A `Result` containing the `DecodedImage`, `None` if `on_band` stopped the
+/// decoding, or an `Error` if decoding fails.
+///
+/// # Examples
+///
+/// ```no_run
+/// use core::ops::ControlFlow;
+/// use qoir_rs::{decode_progressive, DecodeOptions};
+///
+/// let qoir_data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+/// let decoded = decode_progressive(&qoir_data, DecodeOptions::default(), |rect, image| {
+///     println!("Rows {}..{} of {} are ready", rect.y0, rect.y1, image.height);
+///     ControlFlow::Continue(())
+/// })
+/// .expect("Failed to decode");
+/// ```
+pub fn decode_progressive<'a>(
+    data: &'_ [u8],
+    options: DecodeOptions,
+    mut on_band: impl FnMut(Rectangle, &Image) -> ControlFlow<()>,
+) -> Result<Option<DecodedImage<'a>>, Error> {
+    decode(data, options, Some(&mut on_band))
+}
+
+/// Decodes QOIR image data tile by tile, handing each tile's pixels to
+/// `on_tile` without ever allocating the whole image. Memory use stays at a
+/// few tiles however large the image is, so callers can write the tiles
+/// straight to a file or socket, or feed them to a downscaler.
+///
+/// The tiles are decoded from the top down and left to right, on the calling
+/// thread. Clip rectangles and offsets are applied as for
+/// [`decode_from_memory`]: only the drawn part of each tile is passed on, and
+/// tiles that are clipped away are skipped. In tolerant mode, tiles that
+/// cannot be decoded are passed on filled with `DecodeOptions::fill_color`.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+/// * `options`: `DecodeOptions` to control the decoding process.
+///   `fit_within` and `collect_timings` are ignored.
+/// * `on_tile`: Called with the rectangle that was just decoded, in
+///   destination coordinates, and its pixels in `options.pixel_format`,
+///   tightly packed. Return `ControlFlow::Break(())` to stop decoding.
+///
+/// # Returns
+///
+/// `Ok(())` once every tile has been passed on or `on_tile` stopped the
+/// decoding, or an `Error` if decoding fails. Tiles before the one that
+/// failed will already have been passed on.
+///
+/// # Examples
+///
+/// ```no_run
+/// use core::ops::ControlFlow;
+/// use qoir_rs::{decode_streaming, DecodeOptions};
+///
+/// let qoir_data = std::fs::read("panorama.qoir").expect("Failed to read QOIR file");
+/// let mut bytes = 0;
+/// decode_streaming(&qoir_data, DecodeOptions::default(), |rect, pixels| {
+///     println!("Tile at {}, {} is ready", rect.x0, rect.y0);
+///     bytes += pixels.len();
+///     ControlFlow::Continue(())
+/// })
+/// .expect("Failed to decode");
+/// ```
+pub fn decode_streaming(
+    data: &[u8],
+    options: DecodeOptions,
+    mut on_tile: impl FnMut(Rectangle, &[u8]) -> ControlFlow<()>,
+) -> Result<(), Error> {
+    let container = if options.tolerant {
+        Container::parse_tolerant(data)?
+    } else {
+        Container::parse(data)?
+    };
+    let header = container.header;
+    if options.pixel_format == PixelFormat::Invalid {
+        return Err(unsupported_pixfmt());
+    }
+    let ctx = BandContext::new(header, &options);
+    let bpp = options.pixel_format.bytes_per_pixel();
+
+    let mut tile_pixels = vec![0; (TILE_SIZE * TILE_SIZE * 4) as usize];
+    let mut scratch = Vec::new();
+    let mut out = vec![0; (TILE_SIZE * TILE_SIZE) as usize * bpp];
+    let mut timings = Timings::default();
+    let mut rest = container.tiles;
+    for ty in (0..header.height).step_by(TILE_SIZE as usize) {
+        for tx in (0..header.width).step_by(TILE_SIZE as usize) {
+            let tile_data = match container::next_tile(rest) {
+                Ok((format, payload, remaining)) => {
+                    rest = remaining;
+                    Some((format, payload))
+                }
+                Err(_) if options.tolerant => {
+                    rest = &[];
+                    None
+                }
+                Err(err) => return Err(err),
+            };
+            let tile = tile_rect(header, tx, ty);
+            let visible = intersect(tile, ctx.draw);
+            if visible.x0 == visible.x1 || visible.y0 == visible.y1 {
+                continue;
+            }
+
+            let stride_in_bytes = (visible.x1 - visible.x0) as usize * bpp;
+            let out = &mut out[..stride_in_bytes * (visible.y1 - visible.y0) as usize];
+            let mut target = Target {
+                data: out,
+                x: visible.x0,
+                y: visible.y0,
+                stride_in_bytes,
+            };
+            draw_tile(
+                &ctx,
+                tile,
+                visible,
+                tile_data,
+                &mut target,
+                &mut tile_pixels,
+                &mut scratch,
+                &mut timings,
+            )?;
+            let rect = Rectangle {
+                x0: visible.x0 + options.offset_x,
+                y0: visible.y0 + options.offset_y,
+                x1: visible.x1 + options.offset_x,
+                y1: visible.y1 + options.offset_y,
+            };
+            if on_tile(rect, target.data).is_break() {
+                return Ok(());
+            }
+        }
+    }
+
+    if !rest.is_empty() && !options.tolerant {
+        return Err(invalid_data());
+    }
+    Ok(())
+}
+
+/// Called after each band is drawn, when decoding progressively.
+type OnBand<'c> = &'c mut dyn FnMut(Rectangle, &Image) -> ControlFlow<()>;
+
+fn decode<'a>(
+    data: &'_ [u8],
+    options: DecodeOptions,
+    on_band: Option<OnBand>,
+) -> Result<Option<DecodedImage<'a>>, Error> {
+    let mut clock = Stopwatch::start(options.collect_timings);
+    let container = if options.tolerant {
+        Container::parse_tolerant(data)?
+    } else {
+        Container::parse(data)?
+    };
+    let header = container.header;
+    let custom_metadata = match container.custom.map_or(Ok(Vec::new()), parse_custom) {
+        Err(_) if options.tolerant => Vec::new(),
+        result => result?,
+    };
+    let mut timings = Timings {
+        header_parse: clock.lap(),
+        ..Default::default()
+    };
+
+    let mut pixels = Vec::new();
+    let Some((stride_in_bytes, missing_regions)) =
+        decode_pixels(&container, &options, &mut pixels, on_band, &mut timings)?
+    else {
+        return Ok(None);
+    };
+
+    let buffers = DecodedBuffers {
+        pixels,
+        cicp: container.cicp.map(<[u8]>::to_vec),
+        iccp: container.iccp.map(<[u8]>::to_vec),
+        exif: container.exif.map(<[u8]>::to_vec),
+        xmp: container.xmp.map(<[u8]>::to_vec),
+    };
+    let mut decoded = DecodedImage::from_buffers(
+        buffers,
+        header.width,
+        header.height,
+        options.pixel_format,
+        stride_in_bytes,
+        custom_metadata,
+    );
+    decoded.missing_regions = missing_regions;
+    decoded.timings = options.collect_timings.then_some(timings);
+    Ok(Some(decoded))
+}
+
+/// Decodes QOIR image data into `pixels`, reusing its allocation, for
+/// `BufferPool`.
+///
+/// # Returns
+///
+/// A `Result` containing the width, height and stride in bytes of the
+/// decoded image and the time spent on it, or an `Error` if decoding fails.
+#[cfg(feature = "std")]
+pub(crate) fn decode_into(
+    data: &[u8],
+    options: &DecodeOptions,
+    pixels: &mut Vec<u8>,
+) -> Result<(u32, u32, usize, Timings), Error> {
+    let mut clock = Stopwatch::start(options.collect_timings);
+    let container = if options.tolerant {
+        Container::parse_tolerant(data)?
+    } else {
+        Container::parse(data)?
+    };
+    let mut timings = Timings {
+        header_parse: clock.lap(),
+        ..Default::default()
+    };
+    let (stride_in_bytes, _) =
+        decode_pixels(&container, options, pixels, None, &mut timings)?.ok_or_else(invalid_data)?;
+    let header = container.header;
+    Ok((header.width, header.height, stride_in_bytes, timings))
+}
+
+/// Decodes QOIR image data into a zeroed buffer of the length passed to
+/// `alloc`, for `decode_batch_into_arena`. The buffer is only allocated once
+/// the header has been checked.
+#[cfg(feature = "bumpalo")]
+pub(crate) fn decode_into_buffer<'b>(
+    data: &[u8],
+    options: &DecodeOptions,
+    alloc: impl FnOnce(usize) -> Result<&'b mut [u8], Error>,
+) -> Result<Image<'b>, Error> {
+    let container = if options.tolerant {
+        Container::parse_tolerant(data)?
+    } else {
+        Container::parse(data)?
+    };
+    let (stride_in_bytes, len) = pixbuf_layout(&container, options)?;
+    let header = container.header;
+    let pixels = alloc(len)?;
+    decode_tiles(
+        &container,
+        options,
+        &mut Pixbuf {
+            data: &mut *pixels,
+            width: header.width,
+            height: header.height,
+            pixel_format: options.pixel_format,
+            stride_in_bytes,
+        },
+        None,
+        &mut Timings::default(),
+    )?;
+    Ok(Image {
+        pixels,
+        width: header.width,
+        height: header.height,
+        pixel_format: options.pixel_format,
+        stride_in_bytes,
+    })
+}
+
+/// Decodes the tiles of `container` into `pixels`, resizing it to the image.
+///
+/// Its existing contents are only cleared when the clip rectangles or offsets
+/// leave pixels undrawn, so a reused buffer is not zeroed for nothing.
+///
+/// # Returns
+///
+/// A `Result` containing the stride in bytes and the regions that could not
+/// be decoded in tolerant mode, `None` if `on_band` stopped the decoding, or
+/// an `Error` if decoding fails.
+fn decode_pixels(
+    container: &Container,
+    options: &DecodeOptions,
+    pixels: &mut Vec<u8>,
+    on_band: Option<OnBand>,
+    timings: &mut Timings,
+) -> Result<Option<(usize, Vec<Rectangle>)>, Error> {
+    let header = container.header;
+    let (stride_in_bytes, len) = pixbuf_layout(container, options)?;
+    let draws_everything = options.src_clip_rect.is_none()
+        && options.dst_clip_rect.is_none()
+        && options.offset_x == 0
+        && options.offset_y == 0;
+    if !draws_everything {
+        pixels.clear();
+    }
+    pixels.truncate(len);
+    pixels
+        .try_reserve_exact(len - pixels.len())
+        .map_err(|_| out_of_memory())?;
+    pixels.resize(len, 0);
+
+    let missing_regions = decode_tiles(
+        container,
+        options,
+        &mut Pixbuf {
+            data: pixels,
+            width: header.width,
+            height: header.height,
+            pixel_format: options.pixel_format,
+            stride_in_bytes,
+        },
+        on_band,
+        timings,
+    )?;
+    Ok(missing_regions.map(|regions| (stride_in_bytes, regions)))
+}
+
+/// The stride and length in bytes of the image in `container` decoded to
+/// `options.pixel_format`.
+fn pixbuf_layout(container: &Container, options: &DecodeOptions) -> Result<(usize, usize), Error> {
+    let header = container.header;
+    if options.pixel_format == PixelFormat::Invalid {
+        return Err(unsupported_pixfmt());
+    }
+
+    let stride_in_bytes = (header.width as usize)
+        .checked_mul(options.pixel_format.bytes_per_pixel())
+        .ok_or_else(unsupported_pixbuf_dimensions)?;
+    let len = stride_in_bytes
+        .checked_mul(header.height as usize)
+        .ok_or_else(unsupported_pixbuf_dimensions)?;
+
+    // Every tile takes at least its 4-byte header, which rules out most
+    // forged dimensions before the pixel buffer is allocated. Truncated files
+    // fail this check too, so tolerant decoding lets them through only when
+    // the limits bound the buffer instead.
+    let tiles = header.width.div_ceil(TILE_SIZE) as u64 * header.height.div_ceil(TILE_SIZE) as u64;
+    if tiles * 4 > container.tiles.len() as u64 {
+        if !options.tolerant {
+            return Err(invalid_data());
+        }
+        if !options.limits.bounds_memory() {
+            return Err(Error::DecodingFailed(
+                "#qoir: truncated data needs a pixel or memory limit in tolerant mode".to_string(),
+            ));
+        }
+        options
+            .limits
+            .check_dimensions(header.width, header.height, options.pixel_format)?;
+    }
+    Ok((stride_in_bytes, len))
+}
+
+/// Decodes basic metadata (width, height, pixel format) from QOIR image data
+/// using the Rust backend.
+///
+/// Only the header chunk is read. The pixel format is the one the image was
+/// encoded with: `BGRX`, `BGRANonPremul` or `BGRAPremul`.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+///
+/// # Returns
+///
+/// A `Result` containing a tuple `(width, height, PixelFormat)` or an `Error` if metadata decoding fails.
+pub fn decode_basic_metadata(data: &[u8]) -> Result<(u32, u32, PixelFormat), Error> {
+    let header = Header::parse(data)?;
+    Ok((header.width, header.height, header.pixel_format))
+}
+
+impl DecodedImage<'_> {
+    /// Creates a new `DecodedImage` that keeps the Rust decoder's buffers alive.
+    fn from_buffers(
+        buffers: DecodedBuffers,
+        width: u32,
+        height: u32,
+        pixel_format: PixelFormat,
+        stride_in_bytes: usize,
+        custom_metadata: Vec<(String, Vec<u8>)>,
+    ) -> Self {
+        // The buffers never move once they are on the heap, so the slices stay
+        // valid for as long as the `Arc` below is alive.
+        let pixels =
+            unsafe { core::slice::from_raw_parts(buffers.pixels.as_ptr(), buffers.pixels.len()) };
+        let extend = |buf: &Option<Vec<u8>>| {
+            buf.as_ref()
+                .map(|buf| unsafe { core::slice::from_raw_parts(buf.as_ptr(), buf.len()) })
+        };
+        let cic_profile = extend(&buffers.cicp);
+        let icc_profile = extend(&buffers.iccp);
+        let exif = extend(&buffers.exif);
+        let xmp = extend(&buffers.xmp);
+
+        Self {
+            result: Arc::new(DecodedResult::Owned(buffers)),
+            image: Image {
+                pixels,
+                width,
+                height,
+                pixel_format,
+                stride_in_bytes,
+            },
+            cic_profile,
+            icc_profile,
+            exif,
+            xmp,
+            custom_metadata,
+            missing_regions: Vec::new(),
+            timings: None,
+        }
+    }
+}
+
+struct Pixbuf<'a> {
+    data: &'a mut [u8],
+    width: u32,
+    height: u32,
+    pixel_format: PixelFormat,
+    stride_in_bytes: usize,
+}
+
+/// Decodes the tiles into `dst`, returning the regions that could not be
+/// decoded in tolerant mode, or `None` if `on_band` stopped the decoding. The
+/// time spent is added to `timings`.
+fn decode_tiles(
+    container: &Container,
+    options: &DecodeOptions,
+    dst: &mut Pixbuf,
+    on_band: Option<OnBand>,
+    timings: &mut Timings,
+) -> Result<Option<Vec<Rectangle>>, Error> {
+    let header = container.header;
+    let ctx = BandContext::new(header, options);
+    let draw = ctx.draw;
+    let stride = dst.stride_in_bytes;
+
+    // Each band of tiles draws to its own rows of the destination, so the
+    // bands can be decoded independently once the tile headers are walked.
+    let mut bands = Vec::new();
+    let mut rest = container.tiles;
+    let mut dst_y = 0;
+    for ty in (0..header.height).step_by(TILE_SIZE as usize) {
+        let mut tiles = Vec::new();
+        for _ in (0..header.width).step_by(TILE_SIZE as usize) {
+            // In tolerant mode, a truncated tile leaves it and every tile
+            // after it missing.
+            let tile = match container::next_tile(rest) {
+                Ok((format, payload, remaining)) => {
+                    rest = remaining;
+                    Some((format, payload))
+                }
+                Err(_) if options.tolerant => {
+                    rest = &[];
+                    None
+                }
+                Err(err) => return Err(err),
+            };
+            tiles.push(tile);
+        }
+
+        let band = Rectangle {
+            x0: 0,
+            y0: ty as i32,
+            x1: header.width as i32,
+            y1: (ty + TILE_SIZE).min(header.height) as i32,
+        };
+        let visible = intersect(band, draw);
+        let (first_row, rows) = if visible.x0 < visible.x1 && visible.y0 < visible.y1 {
+            (visible.y0 + options.offset_y, visible.y1 - visible.y0)
+        } else {
+            (dst_y, 0)
+        };
+        let start = first_row as usize * dst.stride_in_bytes;
+        let len = rows as usize * dst.stride_in_bytes;
+        dst_y = first_row + rows;
+
+        bands.push(Band {
+            y: ty,
+            tiles,
+            visible,
+            dst: start..start + len,
+            first_row,
+        });
+    }
+
+    if !rest.is_empty() && !options.tolerant {
+        return Err(invalid_data());
+    }
+
+    let mut tile_pixels = vec![0; (TILE_SIZE * TILE_SIZE * 4) as usize];
+    let mut scratch = Vec::new();
+    let mut missing = Vec::new();
+
+    if let Some(on_band) = on_band {
+        for band in &bands {
+            let band_dst = &mut dst.data[band.dst.clone()];
+            let regions = decode_band(
+                &ctx,
+                band,
+                band_dst,
+                stride,
+                &mut tile_pixels,
+                &mut scratch,
+                timings,
+            )?;
+            for region in regions {
+                add_region(&mut missing, region);
+            }
+            if band.dst.is_empty() {
+                continue;
+            }
+            let rect = Rectangle {
+                x0: band.visible.x0 + options.offset_x,
+                y0: band.visible.y0 + options.offset_y,
+                x1: band.visible.x1 + options.offset_x,
+                y1: band.visible.y1 + options.offset_y,
+            };
+            let image = Image {
+                pixels: dst.data,
+                width: dst.width,
+                height: dst.height,
+                pixel_format: dst.pixel_format,
+                stride_in_bytes: dst.stride_in_bytes,
+            };
+            if on_band(rect, &image).is_break() {
+                return Ok(None);
+            }
+        }
+        return Ok(Some(missing));
+    }
+
+    // The bands' rows are in order and do not overlap, so the destination
+    // can be split between them.
+    let mut jobs = Vec::with_capacity(bands.len());
+    let mut dst_rest = &mut *dst.data;
+    let mut dst_start = 0;
+    for band in &bands {
+        let remaining = core::mem::take(&mut dst_rest);
+        let (band_dst, remaining) =
+            remaining[band.dst.start - dst_start..].split_at_mut(band.dst.len());
+        dst_rest = remaining;
+        dst_start = band.dst.end;
+        jobs.push((band, band_dst));
+    }
+
+    #[cfg(feature = "rayon")]
+    if jobs.len() > 1 {
+        use rayon::prelude::*;
+
+        let band_results = crate::thread_pool::install(|| {
+            jobs.into_par_iter()
+                .map_init(
+                    || (vec![0; (TILE_SIZE * TILE_SIZE * 4) as usize], Vec::new()),
+                    |(tile_pixels, scratch), (band, band_dst)| {
+                        let mut band_timings = Timings::default();
+                        let regions = decode_band(
+                            &ctx,
+                            band,
+                            band_dst,
+                            stride,
+                            tile_pixels,
+                            scratch,
+                            &mut band_timings,
+                        )?;
+                        Ok((regions, band_timings))
+                    },
+                )
+                .collect::<Result<Vec<_>, Error>>()
+        })?;
+        for (regions, band_timings) in band_results {
+            for region in regions {
+                add_region(&mut missing, region);
+            }
+            *timings += band_timings;
+        }
+        return Ok(Some(missing));
+    }
+
+    for (band, band_dst) in jobs {
+        let regions = decode_band(
+            &ctx,
+            band,
+            band_dst,
+            stride,
+            &mut tile_pixels,
+            &mut scratch,
+            timings,
+        )?;
+        for region in regions {
+            add_region(&mut missing, region);
+        }
+    }
+    Ok(Some(missing))
+}
+
+/// Appends `rect` to `regions`, merging it into the last region when the two
+/// are side by side or one above the other.
+fn add_region(regions: &mut Vec<Rectangle>, rect: Rectangle) {
+    match regions.last_mut() {
+        Some(last) if (last.y0, last.y1) == (rect.y0, rect.y1) && last.x1 == rect.x0 => {
+            last.x1 = rect.x1;
+        }
+        Some(last) if (last.x0, last.x1) == (rect.x0, rect.x1) && last.y1 == rect.y0 => {
+            last.y1 = rect.y1;
+        }
+        _ => regions.push(rect),
+    }
+}
+
+/// What every band needs to know to draw its tiles.
+struct BandContext {
+    header: Header,
+    /// The part of the source image that is drawn, in source coordinates.
+    draw: Rectangle,
+    offset_x: i32,
+    offset_y: i32,
+    dequantize: [u8; 256],
+    pixel_format: PixelFormat,
+    /// In tolerant mode, the BGRA color to draw in place of tiles that
+    /// cannot be decoded.
+    fill: Option<[u8; 4]>,
+    collect_timings: bool,
+}
+
+impl BandContext {
+    /// Works out what to draw for `options`, into a destination the size of
+    /// the image.
+    fn new(header: Header, options: &DecodeOptions) -> Self {
+        let full = Rectangle {
+            x0: 0,
+            y0: 0,
+            x1: header.width as i32,
+            y1: header.height as i32,
+        };
+        let src_rect = match options.src_clip_rect {
+            Some(rect) => intersect(full, rect),
+            None => full,
+        };
+        let dst_rect = match options.dst_clip_rect {
+            Some(rect) => intersect(full, rect),
+            None => full,
+        };
+        let dst_rect = Rectangle {
+            x0: dst_rect.x0.saturating_sub(options.offset_x),
+            y0: dst_rect.y0.saturating_sub(options.offset_y),
+            x1: dst_rect.x1.saturating_sub(options.offset_x),
+            y1: dst_rect.y1.saturating_sub(options.offset_y),
+        };
+
+        BandContext {
+            header,
+            draw: intersect(src_rect, dst_rect),
+            offset_x: options.offset_x,
+            offset_y: options.offset_y,
+            dequantize: dequantize_table(header.lossiness),
+            pixel_format: options.pixel_format,
+            fill: options.tolerant.then(|| {
+                let [r, g, b, a] = options.fill_color;
+                [b, g, r, a]
+            }),
+            collect_timings: options.collect_timings,
+        }
+    }
+}
+
+/// Where a tile is drawn: a buffer, the source coordinates of its first
+/// pixel, and its stride.
+struct Target<'d> {
+    data: &'d mut [u8],
+    x: i32,
+    y: i32,
+    stride_in_bytes: usize,
+}
+
+/// A row of tiles and the destination rows it draws to.
+struct Band<'a> {
+    /// The source row of the band's top edge.
+    y: u32,
+    /// Each tile's format and payload, or `None` if the data ended first.
+    tiles: Vec<Option<(u8, &'a [u8])>>,
+    /// The part of the band that is drawn, in source coordinates.
+    visible: Rectangle,
+    /// The bytes of the destination that the band draws to.
+    dst: Range<usize>,
+    /// The destination row that `dst` starts at.
+    first_row: i32,
+}
+
+fn decode_band(
+    ctx: &BandContext,
+    band: &Band,
+    dst: &mut [u8],
+    stride_in_bytes: usize,
+    tile_pixels: &mut [u8],
+    scratch: &mut Vec<u8>,
+    timings: &mut Timings,
+) -> Result<Vec<Rectangle>, Error> {
+    let header = ctx.header;
+    let mut target = Target {
+        data: dst,
+        x: -ctx.offset_x,
+        y: band.first_row - ctx.offset_y,
+        stride_in_bytes,
+    };
+    let mut missing = Vec::new();
+
+    for (tx, tile_data) in (0..header.width)
+        .step_by(TILE_SIZE as usize)
+        .zip(&band.tiles)
+    {
+        let tile = tile_rect(header, tx, band.y);
+        let visible = intersect(tile, ctx.draw);
+        let drawn = draw_tile(
+            ctx,
+            tile,
+            visible,
+            *tile_data,
+            &mut target,
+            tile_pixels,
+            scratch,
+            timings,
+        )?;
+        if !drawn && visible.x0 < visible.x1 && visible.y0 < visible.y1 {
+            add_region(&mut missing, visible);
+        }
+    }
+    Ok(missing)
+}
+
+/// The rectangle of the tile whose top-left corner is at `x`, `y`.
+fn tile_rect(header: Header, x: u32, y: u32) -> Rectangle {
+    Rectangle {
+        x0: x as i32,
+        y0: y as i32,
+        x1: (x + TILE_SIZE).min(header.width) as i32,
+        y1: (y + TILE_SIZE).min(header.height) as i32,
+    }
+}
+
+/// Decodes a tile and draws its `visible` part to `target`, returning
+/// `false` if it could not be decoded and was filled in tolerant mode.
+#[allow(clippy::too_many_arguments)]
+fn draw_tile(
+    ctx: &BandContext,
+    tile: Rectangle,
+    visible: Rectangle,
+    tile_data: Option<(u8, &[u8])>,
+    target: &mut Target,
+    tile_pixels: &mut [u8],
+    scratch: &mut Vec<u8>,
+    timings: &mut Timings,
+) -> Result<bool, Error> {
+    let bpp = ctx.pixel_format.bytes_per_pixel();
+    let tile_width = (tile.x1 - tile.x0) as usize;
+    let tile_len = tile_width * (tile.y1 - tile.y0) as usize * 4;
+    let tile_pixels = &mut tile_pixels[..tile_len];
+    let mut clock = Stopwatch::start(ctx.collect_timings);
+    let result = match tile_data {
+        Some((format, payload)) => tile::decode_tile(format, payload, tile_pixels, scratch),
+        None => Err(invalid_data()),
+    };
+    timings.tile_decode += clock.lap();
+    if let Err(err) = result {
+        let Some(fill) = ctx.fill else {
+            return Err(err);
+        };
+        for y in visible.y0..visible.y1 {
+            let dst_row = (y - target.y) as usize * target.stride_in_bytes;
+            for x in visible.x0..visible.x1 {
+                let offset = dst_row + (x - target.x) as usize * bpp;
+                convert(
+                    fill,
+                    PixelFormat::BGRANonPremul,
+                    ctx.pixel_format,
+                    &mut target.data[offset..offset + bpp],
+                );
+            }
+        }
+        timings.format_convert += clock.lap();
+        return Ok(false);
+    }
+
+    for y in visible.y0..visible.y1 {
+        let row = (y - tile.y0) as usize * tile_width;
+        let dst_row = (y - target.y) as usize * target.stride_in_bytes;
+        for x in visible.x0..visible.x1 {
+            let src = (row + (x - tile.x0) as usize) * 4;
+            let mut pixel = [0; 4];
+            pixel.copy_from_slice(&tile_pixels[src..src + 4]);
+            for channel in &mut pixel[..3] {
+                *channel = ctx.dequantize[*channel as usize];
+            }
+
+            let offset = dst_row + (x - target.x) as usize * bpp;
+            convert(
+                pixel,
+                ctx.header.pixel_format,
+                ctx.pixel_format,
+                &mut target.data[offset..offset + bpp],
+            );
+        }
+    }
+    timings.format_convert += clock.lap();
+    Ok(true)
+}
+
+fn intersect(a: Rectangle, b: Rectangle) -> Rectangle {
+    let x0 = a.x0.max(b.x0);
+    let y0 = a.y0.max(b.y0);
+    Rectangle {
+        x0,
+        y0,
+        x1: a.x1.min(b.x1).max(x0),
+        y1: a.y1.min(b.y1).max(y0),
+    }
+}
+
+/// Maps the reduced-precision values of a lossy image back to 8 bits,
+/// spreading them evenly over the full range.
+fn dequantize_table(lossiness: u8) -> [u8; 256] {
+    let mut table = [0; 256];
+    let max = (1u32 << (8 - lossiness)) - 1;
+    for (value, entry) in table.iter_mut().enumerate() {
+        let value = value as u32 & max;
+        *entry = ((value * 255 + max / 2) / max) as u8;
+    }
+    table
+}
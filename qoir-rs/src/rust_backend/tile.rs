@@ -0,0 +1,343 @@
+//! Encoding and decoding of individual tiles.
+//!
+//! Images are split into tiles of up to `TILE_SIZE` x `TILE_SIZE` pixels, each
+//! prefixed by a little-endian `u32` holding the payload length in its low 24
+//! bits and the tile format in its high 8 bits. A tile's pixels are stored
+//! either as literal BGRA bytes or as a stream of opcodes, optionally LZ4
+//! compressed.
+//!
+//! The opcodes are QOI's, rearranged. Each one either repeats the previous
+//! pixel, recalls one of the last 64 distinct pixels, or adds a delta to the
+//! previous pixel (wrapping modulo 256):
+//!
+//! | Bits       | Op    | Bytes | Meaning                                           |
+//! |------------|-------|-------|---------------------------------------------------|
+//! | `xxxxxx00` | INDEX | 1     | pixel from the color cache                        |
+//! | `xxxxxx01` | BGR2  | 1     | 2-bit B, G, R deltas, biased by 2                 |
+//! | `xxxxxx10` | LUMA  | 2     | 6-bit G delta, 4-bit R-G and B-G, QOI style       |
+//! | `xxxxx011` | BGR7  | 3     | 7-bit B, G, R deltas, biased by 64                |
+//! | `xxxxx111` | RUNS  | 1     | repeat the previous pixel 1 to 26 times           |
+//! | `11010111` | RUNL  | 2     | repeat the previous pixel 1 to 256 times          |
+//! | `11011111` | BGRA2 | 2     | 2-bit B, G, R, A deltas, biased by 2              |
+//! | `11100111` | BGRA4 | 3     | 4-bit B, G, R, A deltas, biased by 8              |
+//! | `11101111` | BGRA8 | 5     | 8-bit B, G, R, A deltas                           |
+//! | `11110111` | BGR8  | 4     | 8-bit B, G, R deltas                              |
+//! | `11111111` | A8    | 2     | 8-bit A delta                                     |
+//!
+//! Every op other than INDEX and the runs appends its result to the color
+//! cache, a ring buffer that is reset at the start of each tile along with
+//! the previous pixel (opaque black).
+
+use alloc::vec::Vec;
+
+use super::lz4;
+use crate::Error;
+use crate::container::invalid_data;
+
+pub(super) const TILE_FORMAT_LITERALS: u8 = 0;
+pub(super) const TILE_FORMAT_OPCODES: u8 = 1;
+pub(super) const TILE_FORMAT_LZ4_LITERALS: u8 = 2;
+pub(super) const TILE_FORMAT_LZ4_OPCODES: u8 = 3;
+
+const OP_RUNL: u8 = 0xD7;
+const OP_BGRA2: u8 = 0xDF;
+const OP_BGRA4: u8 = 0xE7;
+const OP_BGRA8: u8 = 0xEF;
+const OP_BGR8: u8 = 0xF7;
+const OP_A8: u8 = 0xFF;
+
+/// The longest any op can be, per pixel, which bounds the size of a tile.
+pub(super) const MAX_OP_LEN: usize = 5;
+
+/// Encodes one tile of `pixels.len() / 4` BGRA pixels and appends it, with its
+/// header, to `dst`.
+///
+/// The tile is stored as opcodes unless literals would be shorter, and is LZ4
+/// compressed when that saves space. `ops` and `compressed` are scratch buffers
+/// that can be reused between calls.
+pub(crate) fn encode_tile(
+    pixels: &[u8],
+    dst: &mut Vec<u8>,
+    ops: &mut Vec<u8>,
+    compressed: &mut Vec<u8>,
+) {
+    encode_opcodes(pixels, ops);
+    let (format, payload) = if ops.len() < pixels.len() {
+        (TILE_FORMAT_OPCODES, &ops[..])
+    } else {
+        (TILE_FORMAT_LITERALS, pixels)
+    };
+
+    lz4::encode_block(payload, compressed);
+    let (format, payload) = if compressed.len() >= payload.len() {
+        (format, payload)
+    } else if format == TILE_FORMAT_OPCODES {
+        (TILE_FORMAT_LZ4_OPCODES, &compressed[..])
+    } else {
+        (TILE_FORMAT_LZ4_LITERALS, &compressed[..])
+    };
+
+    let word = payload.len() as u32 | (format as u32) << 24;
+    dst.extend_from_slice(&word.to_le_bytes());
+    dst.extend_from_slice(payload);
+}
+
+fn encode_opcodes(src: &[u8], dst: &mut Vec<u8>) {
+    dst.clear();
+    let mut cache = [[0u8; 4]; 64];
+    let mut cache_pos = 0;
+    let mut prev = [0x00, 0x00, 0x00, 0xFF];
+    let mut run = 0;
+
+    for pixel in src.chunks_exact(4) {
+        let pixel = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        if pixel == prev {
+            run += 1;
+            if run == 256 {
+                encode_run(dst, run);
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            encode_run(dst, run);
+            run = 0;
+        }
+
+        if let Some(slot) = cache.iter().position(|&cached| cached == pixel) {
+            dst.push((slot as u8) << 2);
+            prev = pixel;
+            continue;
+        }
+
+        let [db, dg, dr, da] = [0, 1, 2, 3].map(|i| pixel[i].wrapping_sub(prev[i]) as i8);
+        if da == 0 {
+            let (dr_dg, db_dg) = (dr.wrapping_sub(dg), db.wrapping_sub(dg));
+            if [db, dg, dr].iter().all(|d| (-2..2).contains(d)) {
+                dst.push(0x01 | bias(db, 2) << 2 | bias(dg, 2) << 4 | bias(dr, 2) << 6);
+            } else if (-32..32).contains(&dg)
+                && (-8..8).contains(&dr_dg)
+                && (-8..8).contains(&db_dg)
+            {
+                dst.push(bias(dg, 32) << 2 | 0x02);
+                dst.push(bias(dr_dg, 8) << 4 | bias(db_dg, 8));
+            } else if [db, dg, dr].iter().all(|d| (-64..64).contains(d)) {
+                let bits = (bias(db, 64) as u32
+                    | (bias(dg, 64) as u32) << 7
+                    | (bias(dr, 64) as u32) << 14)
+                    << 3
+                    | 0x03;
+                dst.extend_from_slice(&bits.to_le_bytes()[..3]);
+            } else {
+                dst.extend_from_slice(&[OP_BGR8, db as u8, dg as u8, dr as u8]);
+            }
+        } else if [db, dg, dr, da].iter().all(|d| (-8..8).contains(d)) {
+            let bits = bias(db, 8) as u16
+                | (bias(dg, 8) as u16) << 4
+                | (bias(dr, 8) as u16) << 8
+                | (bias(da, 8) as u16) << 12;
+            dst.push(OP_BGRA4);
+            dst.extend_from_slice(&bits.to_le_bytes());
+        } else {
+            dst.extend_from_slice(&[OP_BGRA8, db as u8, dg as u8, dr as u8, da as u8]);
+        }
+
+        cache[cache_pos] = pixel;
+        cache_pos = (cache_pos + 1) % cache.len();
+        prev = pixel;
+    }
+
+    if run > 0 {
+        encode_run(dst, run);
+    }
+}
+
+/// Encodes a run of 1 to 256 repeats of the previous pixel.
+fn encode_run(dst: &mut Vec<u8>, run: usize) {
+    if run <= 26 {
+        dst.push(((run - 1) as u8) << 3 | 0x07);
+    } else {
+        dst.extend_from_slice(&[OP_RUNL, (run - 1) as u8]);
+    }
+}
+
+/// Converts a signed delta into the unsigned field that stores it.
+fn bias(delta: i8, bias: i8) -> u8 {
+    delta.wrapping_add(bias) as u8
+}
+
+/// Decodes one tile of `dst.len() / 4` pixels into `dst` as BGRA bytes.
+///
+/// `scratch` holds decompressed payloads and can be reused between calls.
+pub(crate) fn decode_tile(
+    format: u8,
+    payload: &[u8],
+    dst: &mut [u8],
+    scratch: &mut Vec<u8>,
+) -> Result<(), Error> {
+    let max_len = (dst.len() / 4) * MAX_OP_LEN;
+
+    match format {
+        TILE_FORMAT_LITERALS => decode_literals(payload, dst),
+        TILE_FORMAT_OPCODES => decode_opcodes(payload, dst),
+        TILE_FORMAT_LZ4_LITERALS => {
+            lz4::decode_block(payload, scratch, max_len).ok_or_else(invalid_data)?;
+            decode_literals(scratch, dst)
+        }
+        TILE_FORMAT_LZ4_OPCODES => {
+            lz4::decode_block(payload, scratch, max_len).ok_or_else(invalid_data)?;
+            decode_opcodes(scratch, dst)
+        }
+        _ => Err(invalid_data()),
+    }
+}
+
+fn decode_literals(src: &[u8], dst: &mut [u8]) -> Result<(), Error> {
+    if src.len() != dst.len() {
+        return Err(invalid_data());
+    }
+    dst.copy_from_slice(src);
+    Ok(())
+}
+
+fn decode_opcodes(src: &[u8], dst: &mut [u8]) -> Result<(), Error> {
+    let mut cache = [[0u8; 4]; 64];
+    let mut cache_pos = 0;
+    let mut pixel = [0x00, 0x00, 0x00, 0xFF];
+    let mut pixels = dst.chunks_exact_mut(4);
+    let mut pos = 0;
+
+    while pos < src.len() {
+        let op = src[pos];
+        let len = op_len(op);
+        let bytes = src.get(pos..pos + len).ok_or_else(invalid_data)?;
+        pos += len;
+
+        let run = match op {
+            _ if op & 0x03 == 0x00 => {
+                pixel = cache[(op >> 2) as usize];
+                let out = pixels.next().ok_or_else(invalid_data)?;
+                out.copy_from_slice(&pixel);
+                continue;
+            }
+            _ if op & 0x03 == 0x01 => {
+                add(
+                    &mut pixel,
+                    [
+                        ((op >> 2) & 0x03).wrapping_sub(2),
+                        ((op >> 4) & 0x03).wrapping_sub(2),
+                        ((op >> 6) & 0x03).wrapping_sub(2),
+                        0,
+                    ],
+                );
+                None
+            }
+            _ if op & 0x03 == 0x02 => {
+                let dg = (op >> 2).wrapping_sub(32);
+                add(
+                    &mut pixel,
+                    [
+                        dg.wrapping_add(bytes[1] & 0x0F).wrapping_sub(8),
+                        dg,
+                        dg.wrapping_add(bytes[1] >> 4).wrapping_sub(8),
+                        0,
+                    ],
+                );
+                None
+            }
+            _ if op & 0x07 == 0x03 => {
+                let bits = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) >> 3;
+                add(
+                    &mut pixel,
+                    [
+                        ((bits & 0x7F) as u8).wrapping_sub(64),
+                        (((bits >> 7) & 0x7F) as u8).wrapping_sub(64),
+                        (((bits >> 14) & 0x7F) as u8).wrapping_sub(64),
+                        0,
+                    ],
+                );
+                None
+            }
+            OP_RUNL => Some(bytes[1] as usize + 1),
+            OP_BGRA2 => {
+                let bits = bytes[1];
+                add(
+                    &mut pixel,
+                    [
+                        (bits & 0x03).wrapping_sub(2),
+                        ((bits >> 2) & 0x03).wrapping_sub(2),
+                        ((bits >> 4) & 0x03).wrapping_sub(2),
+                        ((bits >> 6) & 0x03).wrapping_sub(2),
+                    ],
+                );
+                None
+            }
+            OP_BGRA4 => {
+                let bits = u16::from_le_bytes([bytes[1], bytes[2]]);
+                add(
+                    &mut pixel,
+                    [
+                        ((bits & 0x0F) as u8).wrapping_sub(8),
+                        (((bits >> 4) & 0x0F) as u8).wrapping_sub(8),
+                        (((bits >> 8) & 0x0F) as u8).wrapping_sub(8),
+                        (((bits >> 12) & 0x0F) as u8).wrapping_sub(8),
+                    ],
+                );
+                None
+            }
+            OP_BGRA8 => {
+                add(&mut pixel, [bytes[1], bytes[2], bytes[3], bytes[4]]);
+                None
+            }
+            OP_BGR8 => {
+                add(&mut pixel, [bytes[1], bytes[2], bytes[3], 0]);
+                None
+            }
+            OP_A8 => {
+                add(&mut pixel, [0, 0, 0, bytes[1]]);
+                None
+            }
+            _ => Some((op >> 3) as usize + 1),
+        };
+
+        match run {
+            Some(run) => {
+                for _ in 0..run {
+                    let out = pixels.next().ok_or_else(invalid_data)?;
+                    out.copy_from_slice(&pixel);
+                }
+            }
+            None => {
+                cache[cache_pos] = pixel;
+                cache_pos = (cache_pos + 1) % cache.len();
+                let out = pixels.next().ok_or_else(invalid_data)?;
+                out.copy_from_slice(&pixel);
+            }
+        }
+    }
+
+    if pixels.next().is_some() {
+        return Err(invalid_data());
+    }
+    Ok(())
+}
+
+/// The length in bytes of the op starting with `op`.
+fn op_len(op: u8) -> usize {
+    match op {
+        _ if op & 0x03 == 0x02 => 2,
+        _ if op & 0x03 != 0x03 => 1,
+        _ if op & 0x07 == 0x03 => 3,
+        OP_RUNL | OP_BGRA2 | OP_A8 => 2,
+        OP_BGRA4 => 3,
+        OP_BGRA8 => 5,
+        OP_BGR8 => 4,
+        _ => 1,
+    }
+}
+
+fn add(pixel: &mut [u8; 4], delta: [u8; 4]) {
+    for (channel, delta) in pixel.iter_mut().zip(delta) {
+        *channel = channel.wrapping_add(delta);
+    }
+}
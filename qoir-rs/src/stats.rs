@@ -0,0 +1,248 @@
+//! Image statistics for choosing encode settings, such as whether an image
+//! needs its alpha channel or how many colors it uses.
+
+use crate::{Error, Image, PixelFormat};
+
+/// The number of HyperLogLog registers the color count estimate uses, as a
+/// power of two. 4096 registers give a typical error of 1.6%.
+const HLL_BITS: u32 = 12;
+
+/// Statistics computed by [`stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageStats {
+    /// The number of pixels.
+    pub pixel_count: u64,
+    /// The number of pixels with each value, per channel, in R, G, B, A
+    /// order. Premultiplied colors are counted as stored. Formats without
+    /// alpha count every pixel as 255.
+    pub histograms: [[u64; 256]; 4],
+    /// The mean luma, from 0 to 255, with the BT.601 weights applied to the
+    /// stored color values.
+    pub mean_luma: f64,
+    /// Whether every pixel has an alpha of 255, in which case the image can
+    /// be encoded without its alpha channel.
+    pub is_fully_opaque: bool,
+    /// An estimate of the number of distinct RGBA colors, within a few
+    /// percent for large counts and usually exact for small ones.
+    pub unique_colors_estimate: u64,
+}
+
+/// Computes the histograms, mean luma, opacity and an estimate of the number
+/// of colors of an image, in one pass over its pixels.
+///
+/// The pixels are read in place, honouring the stride. With the `simd`
+/// feature on x86-64, the luma and lowest alpha of 4-byte pixels are computed
+/// four pixels at a time with SSE2, leaving only the histograms and the color
+/// estimate per pixel. Runs of identical pixels, common in screenshots, are
+/// only hashed once for the color estimate.
+///
+/// # Returns
+///
+/// A `Result` containing the `ImageStats`, or `Error::InvalidParameter` if the
+/// pixel format is `Invalid` or the pixel buffer is too small.
+pub fn stats(image: &Image) -> Result<ImageStats, Error> {
+    // The offsets of R, G, B and A in a pixel, if it has alpha.
+    let (offsets, alpha) = match image.pixel_format {
+        PixelFormat::RGBANonPremul | PixelFormat::RGBAPremul => ([0, 1, 2], Some(3)),
+        PixelFormat::BGRANonPremul | PixelFormat::BGRAPremul => ([2, 1, 0], Some(3)),
+        PixelFormat::RGBX | PixelFormat::RGB => ([0, 1, 2], None),
+        PixelFormat::BGRX | PixelFormat::BGR => ([2, 1, 0], None),
+        PixelFormat::Invalid => return Err(Error::InvalidParameter),
+    };
+    image.check_buffer()?;
+
+    let bpp = image.pixel_format.bytes_per_pixel();
+    let row_len = image.width as usize * bpp;
+    let mut histograms = [[0u64; 256]; 4];
+    let mut luma_sum = 0u64;
+    let mut min_alpha = 0xFF;
+    let mut registers = [0u8; 1 << HLL_BITS];
+    let mut previous = None;
+    // Counts a pixel in the histograms and the color estimate.
+    let mut count = |pixel: &[u8]| {
+        let [r, g, b] = offsets.map(|offset| pixel[offset]);
+        let a = alpha.map_or(0xFF, |offset| pixel[offset]);
+        histograms[0][r as usize] += 1;
+        histograms[1][g as usize] += 1;
+        histograms[2][b as usize] += 1;
+        histograms[3][a as usize] += 1;
+
+        let color = u32::from_le_bytes([r, g, b, a]);
+        if previous != Some(color) {
+            previous = Some(color);
+            let hash = mix(color);
+            let register = (hash >> (64 - HLL_BITS)) as usize;
+            let rank = ((hash << HLL_BITS) | 1 << (HLL_BITS - 1)).leading_zeros() as u8 + 1;
+            registers[register] = registers[register].max(rank);
+        }
+        [r, g, b, a]
+    };
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    let mut simd = (bpp == 4).then(|| sse2::LumaAlpha::new(offsets, alpha));
+    for y in 0..image.height as usize {
+        let start = y * image.stride_in_bytes;
+        #[allow(unused_mut)]
+        let mut row = &image.pixels[start..start + row_len];
+
+        #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+        if let Some(simd) = &mut simd {
+            let (groups, rest) = row.split_at(row.len() / 16 * 16);
+            for group in groups.chunks_exact(16) {
+                simd.add(group);
+                for pixel in group.chunks_exact(4) {
+                    count(pixel);
+                }
+            }
+            row = rest;
+        }
+        for pixel in row.chunks_exact(bpp) {
+            let [r, g, b, a] = count(pixel);
+            luma_sum += 299 * r as u64 + 587 * g as u64 + 114 * b as u64;
+            min_alpha = min_alpha.min(a);
+        }
+    }
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    if let Some(simd) = simd {
+        let (simd_luma_sum, simd_min_alpha) = simd.finish();
+        luma_sum += simd_luma_sum;
+        min_alpha = min_alpha.min(simd_min_alpha);
+    }
+
+    let pixel_count = image.width as u64 * image.height as u64;
+    Ok(ImageStats {
+        pixel_count,
+        histograms,
+        mean_luma: if pixel_count == 0 {
+            0.0
+        } else {
+            luma_sum as f64 / 1000.0 / pixel_count as f64
+        },
+        is_fully_opaque: min_alpha == 0xFF,
+        unique_colors_estimate: if pixel_count == 0 {
+            0
+        } else {
+            estimate_cardinality(&registers).min(pixel_count)
+        },
+    })
+}
+
+/// Spreads the bits of a color over a 64-bit hash (the SplitMix64 finalizer).
+fn mix(color: u32) -> u64 {
+    let mut x = color as u64;
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// The HyperLogLog estimate for `registers`, using linear counting while
+/// many registers are still empty.
+fn estimate_cardinality(registers: &[u8]) -> u64 {
+    let m = registers.len() as f64;
+    let sum: f64 = registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+    let estimate = 0.7213 / (1.0 + 1.079 / m) * m * m / sum;
+    let zeros = registers.iter().filter(|&&r| r == 0).count();
+    if estimate <= 2.5 * m && zeros > 0 {
+        (m * (m / zeros as f64).ln()).round() as u64
+    } else {
+        estimate.round() as u64
+    }
+}
+
+/// The SSE2 part of [`stats`]. SSE2 is part of x86-64, so it needs no run-time
+/// detection.
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use core::arch::x86_64::*;
+
+    /// How many groups of four pixels are summed before the 32-bit sums are
+    /// added to the total. Each group adds at most 2 × 255 × 886 to a lane,
+    /// so 1024 groups stay far below `i32::MAX`.
+    const FLUSH_INTERVAL: u32 = 1024;
+
+    /// Sums the luma of 4-byte pixels and tracks their lowest alpha.
+    pub(super) struct LumaAlpha {
+        /// The luma weight of each byte of two pixels, zero for alpha or X.
+        weights: __m128i,
+        /// 0xFF in every byte that isn't alpha, so that only alpha lowers
+        /// `min`.
+        non_alpha: __m128i,
+        sums: __m128i,
+        min: __m128i,
+        pending: u32,
+        total: u64,
+    }
+
+    impl LumaAlpha {
+        /// Takes the offsets of R, G and B in a pixel, and that of alpha if it
+        /// has one.
+        pub(super) fn new(offsets: [usize; 3], alpha: Option<usize>) -> Self {
+            let mut weights = [0i16; 8];
+            for (offset, weight) in offsets.into_iter().zip([299, 587, 114]) {
+                weights[offset] = weight;
+                weights[offset + 4] = weight;
+            }
+            let mut non_alpha = [0xFFu8; 16];
+            if let Some(offset) = alpha {
+                for pixel in 0..4 {
+                    non_alpha[pixel * 4 + offset] = 0;
+                }
+            }
+            // SAFETY: SSE2 is always available on x86-64, both arrays are 16
+            // bytes, and unaligned loads allow any address.
+            unsafe {
+                LumaAlpha {
+                    weights: _mm_loadu_si128(weights.as_ptr().cast()),
+                    non_alpha: _mm_loadu_si128(non_alpha.as_ptr().cast()),
+                    sums: _mm_setzero_si128(),
+                    min: _mm_set1_epi8(-1),
+                    pending: 0,
+                    total: 0,
+                }
+            }
+        }
+
+        /// Adds four pixels, the 16 bytes of `group`.
+        pub(super) fn add(&mut self, group: &[u8]) {
+            assert_eq!(group.len(), 16);
+            // SAFETY: SSE2 is always available on x86-64, `group` holds the 16
+            // bytes loaded, and unaligned loads allow any address.
+            unsafe {
+                let pixels = _mm_loadu_si128(group.as_ptr().cast());
+                let zero = _mm_setzero_si128();
+                let low = _mm_madd_epi16(_mm_unpacklo_epi8(pixels, zero), self.weights);
+                let high = _mm_madd_epi16(_mm_unpackhi_epi8(pixels, zero), self.weights);
+                self.sums = _mm_add_epi32(self.sums, _mm_add_epi32(low, high));
+                self.min = _mm_min_epu8(self.min, _mm_or_si128(pixels, self.non_alpha));
+            }
+
+            self.pending += 1;
+            if self.pending == FLUSH_INTERVAL {
+                self.flush();
+            }
+        }
+
+        fn flush(&mut self) {
+            let mut lanes = [0i32; 4];
+            // SAFETY: SSE2 is always available on x86-64, `lanes` is 16 bytes,
+            // and unaligned stores allow any address.
+            unsafe {
+                _mm_storeu_si128(lanes.as_mut_ptr().cast(), self.sums);
+                self.sums = _mm_setzero_si128();
+            }
+            self.total += lanes.iter().map(|&lane| lane as u64).sum::<u64>();
+            self.pending = 0;
+        }
+
+        /// Returns the luma sum, with the same weights as the scalar code,
+        /// and the lowest alpha of the pixels added.
+        pub(super) fn finish(mut self) -> (u64, u8) {
+            self.flush();
+            let mut bytes = [0u8; 16];
+            // SAFETY: SSE2 is always available on x86-64, `bytes` is 16 bytes,
+            // and unaligned stores allow any address.
+            unsafe { _mm_storeu_si128(bytes.as_mut_ptr().cast(), self.min) };
+            (self.total, bytes.into_iter().min().unwrap_or(0xFF))
+        }
+    }
+}
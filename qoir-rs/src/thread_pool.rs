@@ -0,0 +1,94 @@
+//! Configuration of the threads used by the parallel encode and decode paths,
+//! enabled with the `rayon` feature.
+//!
+//! By default, parallel work runs on rayon's global pool, which has one thread
+//! per CPU. Servers that need to cap the crate's CPU usage can install a
+//! process-wide pool instead, either built here or shared with the rest of the
+//! application:
+//!
+//! ```no_run
+//! use qoir_rs::ThreadPoolBuilder;
+//!
+//! ThreadPoolBuilder::new()
+//!     .num_threads(2)
+//!     .build_global()
+//!     .expect("Failed to build the thread pool");
+//! ```
+//!
+//! Only the Rust backend splits images into bands of tiles that are encoded or
+//! decoded in parallel. The C library always runs on the calling thread.
+
+use std::sync::{Arc, RwLock};
+
+pub use rayon::{ThreadPool, ThreadPoolBuildError};
+
+static THREAD_POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+
+/// Builds the thread pool used by parallel encode and decode paths.
+#[derive(Debug, Clone, Default)]
+pub struct ThreadPoolBuilder {
+    num_threads: usize,
+    thread_name_prefix: Option<String>,
+}
+
+impl ThreadPoolBuilder {
+    /// Creates a builder for a pool with one thread per CPU.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of threads. Zero, the default, means one per CPU.
+    pub fn num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = num_threads;
+        self
+    }
+
+    /// Names the threads `<prefix>-<index>`, which helps telling them apart in
+    /// profilers and debuggers.
+    pub fn thread_name_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.thread_name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Builds the pool without installing it.
+    pub fn build(self) -> Result<Arc<ThreadPool>, ThreadPoolBuildError> {
+        let mut builder = rayon::ThreadPoolBuilder::new().num_threads(self.num_threads);
+        if let Some(prefix) = self.thread_name_prefix {
+            builder = builder.thread_name(move |index| format!("{prefix}-{index}"));
+        }
+        builder.build().map(Arc::new)
+    }
+
+    /// Builds the pool and installs it as the process-wide default, replacing
+    /// any pool installed before.
+    pub fn build_global(self) -> Result<(), ThreadPoolBuildError> {
+        set_thread_pool(Some(self.build()?));
+        Ok(())
+    }
+}
+
+/// Installs `pool` as the process-wide pool for parallel encode and decode
+/// paths. `None` goes back to rayon's global pool.
+///
+/// Calls already running keep the pool they started with.
+pub fn set_thread_pool(pool: Option<Arc<ThreadPool>>) {
+    *THREAD_POOL.write().unwrap_or_else(|e| e.into_inner()) = pool;
+}
+
+/// Returns the pool installed with [`set_thread_pool`] or
+/// [`ThreadPoolBuilder::build_global`], if any.
+pub fn thread_pool() -> Option<Arc<ThreadPool>> {
+    THREAD_POOL
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Runs `op` in the installed pool, or in rayon's global pool if there is none.
+#[cfg(feature = "rust-backend")]
+pub(crate) fn install<R: Send>(op: impl FnOnce() -> R + Send) -> R {
+    match thread_pool() {
+        Some(pool) => pool.install(op),
+        None => op(),
+    }
+}
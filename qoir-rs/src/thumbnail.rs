@@ -0,0 +1,92 @@
+//! Thumbnails stored with [`EncodeOptions::embed_thumbnail`].
+//!
+//! The thumbnail is a complete QOIR image in the payload of a `THMB` chunk,
+//! written between the metadata and the `QPIX` chunk. Readers that only want
+//! the thumbnail can stop before the pixels of the image.
+
+use alloc::vec::Vec;
+
+use crate::container::{Header, next_chunk};
+use crate::{EncodeOptions, EncodedBuffer, Error, Image, ThumbnailSpec};
+
+/// Reads the thumbnail stored with
+/// [`EncodeOptions::embed_thumbnail`] without decoding the image.
+///
+/// Only the chunks before the pixels are read, so the start of a file, up to
+/// the end of its thumbnail, is enough. This makes it cheap to show a gallery
+/// of files on network storage.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+///
+/// # Returns
+///
+/// A `Result` containing the thumbnail's QOIR data, to be decoded like any
+/// other QOIR image, `None` if the image has no thumbnail, or an `Error` if
+/// the data is not a valid QOIR image.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::{decode_from_memory, extract_thumbnail, DecodeOptions};
+///
+/// let qoir_data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+/// if let Some(thumbnail) = extract_thumbnail(&qoir_data).expect("Not a QOIR file") {
+///     let decoded = decode_from_memory(thumbnail, DecodeOptions::default()).expect("Failed to decode");
+///     println!("Thumbnail: {}x{}", decoded.image.width, decoded.image.height);
+/// }
+/// ```
+pub fn extract_thumbnail(data: &[u8]) -> Result<Option<&[u8]>, Error> {
+    Header::parse(data)?;
+    let (_, _, mut rest) = next_chunk(data)?;
+    loop {
+        let (tag, payload, remaining) = next_chunk(rest)?;
+        match &tag {
+            b"THMB" => return Ok(Some(payload)),
+            b"QPIX" | b"QEND" => return Ok(None),
+            _ => rest = remaining,
+        }
+    }
+}
+
+/// Encodes the thumbnail of `image` that `spec` asks for, with the backend's
+/// `encode` function. Without the `std` feature there is no resizer, and no
+/// thumbnail is made.
+pub(crate) fn encode_thumbnail<'a>(
+    image: &Image<'_>,
+    spec: Option<ThumbnailSpec>,
+    encode: impl FnOnce(Image<'_>, EncodeOptions) -> Result<EncodedBuffer<'a>, Error>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let Some(spec) = spec else {
+        return Ok(None);
+    };
+    if spec.max_dim == 0 || spec.lossiness > 7 {
+        return Err(Error::InvalidParameter);
+    }
+    let options = EncodeOptions {
+        lossiness: spec.lossiness,
+        ..Default::default()
+    };
+
+    #[cfg(feature = "std")]
+    {
+        use crate::{ResizeFilter, ResizeMode};
+
+        if image.width > spec.max_dim || image.height > spec.max_dim {
+            let resized = image.resize(
+                spec.max_dim,
+                spec.max_dim,
+                ResizeMode::Fit,
+                ResizeFilter::Box,
+            )?;
+            return Ok(Some(encode(resized.as_image(), options)?.into_vec()));
+        }
+        Ok(Some(encode(image.clone(), options)?.into_vec()))
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        let _ = (image, options, encode);
+        Ok(None)
+    }
+}
@@ -0,0 +1,81 @@
+use core::ops::AddAssign;
+use core::time::Duration;
+
+/// The time a decode or encode spent in each of its stages, collected when
+/// `DecodeOptions::collect_timings` or `EncodeOptions::collect_timings` is
+/// set.
+///
+/// A decode fills in `header_parse` and `tile_decode`, and an encode
+/// `tile_encode` and `chunk_write`; the other pair stays zero. The C library
+/// decodes or encodes the tiles and converts their pixels in one call, which
+/// is counted as `tile_decode` or `tile_encode`; a C decode's `header_parse`
+/// is the walk over the chunks that reads the custom metadata first. With
+/// the `rayon` feature, the tile and conversion times add up the time spent
+/// on every thread, so they can exceed the wall-clock time. Without the
+/// `std` feature there is no clock, and every stage is zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timings {
+    /// Reading the input or writing the output, for the reader, writer and
+    /// file functions.
+    pub io: Duration,
+    /// Parsing the chunks and the header.
+    pub header_parse: Duration,
+    /// Decoding the tiles.
+    pub tile_decode: Duration,
+    /// Encoding the tiles.
+    pub tile_encode: Duration,
+    /// Writing the header, metadata and other chunks around the tiles, and
+    /// splicing in the chunks the C library cannot write.
+    pub chunk_write: Duration,
+    /// Converting between the stored pixel format and the requested one, and
+    /// scaling the image down for `DecodeOptions::fit_within`.
+    pub format_convert: Duration,
+}
+
+impl AddAssign for Timings {
+    fn add_assign(&mut self, other: Self) {
+        self.io += other.io;
+        self.header_parse += other.header_parse;
+        self.tile_decode += other.tile_decode;
+        self.tile_encode += other.tile_encode;
+        self.chunk_write += other.chunk_write;
+        self.format_convert += other.format_convert;
+    }
+}
+
+/// Measures consecutive stages, if timings are being collected.
+#[derive(Clone, Copy)]
+pub(crate) struct Stopwatch {
+    #[cfg(feature = "std")]
+    start: Option<std::time::Instant>,
+}
+
+impl Stopwatch {
+    /// Starts measuring the first stage, or does nothing unless `enabled`.
+    pub(crate) fn start(enabled: bool) -> Self {
+        #[cfg(feature = "std")]
+        {
+            Stopwatch {
+                start: enabled.then(std::time::Instant::now),
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        {
+            let _ = enabled;
+            Stopwatch {}
+        }
+    }
+
+    /// Ends the current stage and starts the next one, returning how long the
+    /// current one took.
+    pub(crate) fn lap(&mut self) -> Duration {
+        #[cfg(feature = "std")]
+        if let Some(start) = &mut self.start {
+            let now = std::time::Instant::now();
+            let elapsed = now - *start;
+            *start = now;
+            return elapsed;
+        }
+        Duration::ZERO
+    }
+}
@@ -0,0 +1,138 @@
+//! Lossless rotations and flips of images, for example to apply an EXIF
+//! orientation after decoding.
+
+use alloc::vec;
+
+use crate::{Error, Image, ImageBuf, PixelFormat};
+
+/// The side of the square blocks that rotations copy at a time, so that the
+/// rows being read and written both stay in the cache.
+const BLOCK_SIZE: usize = 32;
+
+#[derive(Clone, Copy)]
+enum Transform {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipH,
+    FlipV,
+}
+
+impl Image<'_> {
+    /// Rotates the image 90 degrees clockwise.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the rotated `ImageBuf`, tightly packed in the
+    /// same pixel format, or `Error::InvalidParameter` if the pixel format is
+    /// `Invalid` or the pixel buffer is too small.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use qoir_rs::{decode, DecodeOptions};
+    ///
+    /// let decoded = decode("input.qoir", DecodeOptions::default()).expect("Failed to decode");
+    /// let portrait = decoded.image.rotate90().expect("Failed to rotate");
+    /// ```
+    pub fn rotate90(&self) -> Result<ImageBuf, Error> {
+        self.transform(Transform::Rotate90)
+    }
+
+    /// Rotates the image 180 degrees. See [`Image::rotate90`].
+    pub fn rotate180(&self) -> Result<ImageBuf, Error> {
+        self.transform(Transform::Rotate180)
+    }
+
+    /// Rotates the image 90 degrees counter-clockwise. See
+    /// [`Image::rotate90`].
+    pub fn rotate270(&self) -> Result<ImageBuf, Error> {
+        self.transform(Transform::Rotate270)
+    }
+
+    /// Mirrors the image left to right. See [`Image::rotate90`].
+    pub fn flip_h(&self) -> Result<ImageBuf, Error> {
+        self.transform(Transform::FlipH)
+    }
+
+    /// Mirrors the image top to bottom. See [`Image::rotate90`].
+    pub fn flip_v(&self) -> Result<ImageBuf, Error> {
+        self.transform(Transform::FlipV)
+    }
+
+    fn transform(&self, transform: Transform) -> Result<ImageBuf, Error> {
+        if self.pixel_format == PixelFormat::Invalid {
+            return Err(Error::InvalidParameter);
+        }
+        self.check_buffer()?;
+
+        let (width, height) = match transform {
+            Transform::Rotate90 | Transform::Rotate270 => (self.height, self.width),
+            _ => (self.width, self.height),
+        };
+        let bpp = self.pixel_format.bytes_per_pixel();
+        let stride_in_bytes = width as usize * bpp;
+        let mut out = ImageBuf {
+            pixels: vec![0; stride_in_bytes * height as usize],
+            width,
+            height,
+            pixel_format: self.pixel_format,
+            stride_in_bytes,
+        };
+        // A fixed pixel size lets the compiler turn each copy into a single
+        // load and store.
+        match bpp {
+            3 => transform_pixels::<3>(self, transform, &mut out),
+            _ => transform_pixels::<4>(self, transform, &mut out),
+        }
+        Ok(out)
+    }
+}
+
+fn transform_pixels<const BPP: usize>(src: &Image, transform: Transform, dst: &mut ImageBuf) {
+    let (width, height) = (src.width as usize, src.height as usize);
+    let row_len = width * BPP;
+    if row_len == 0 || height == 0 {
+        return;
+    }
+    let src_row = |y: usize| &src.pixels[y * src.stride_in_bytes..][..row_len];
+
+    match transform {
+        Transform::FlipV => {
+            for (y, dst_row) in dst.pixels.chunks_exact_mut(row_len).enumerate() {
+                dst_row.copy_from_slice(src_row(height - 1 - y));
+            }
+        }
+        Transform::FlipH | Transform::Rotate180 => {
+            for (y, dst_row) in dst.pixels.chunks_exact_mut(row_len).enumerate() {
+                let y = match transform {
+                    Transform::Rotate180 => height - 1 - y,
+                    _ => y,
+                };
+                let pixels = src_row(y).chunks_exact(BPP).rev();
+                for (dst_pixel, src_pixel) in dst_row.chunks_exact_mut(BPP).zip(pixels) {
+                    dst_pixel.copy_from_slice(src_pixel);
+                }
+            }
+        }
+        Transform::Rotate90 | Transform::Rotate270 => {
+            let dst_stride = dst.stride_in_bytes;
+            for block_y in (0..height).step_by(BLOCK_SIZE) {
+                for block_x in (0..width).step_by(BLOCK_SIZE) {
+                    for y in block_y..(block_y + BLOCK_SIZE).min(height) {
+                        let row = src_row(y);
+                        for x in block_x..(block_x + BLOCK_SIZE).min(width) {
+                            let (dst_x, dst_y) = match transform {
+                                Transform::Rotate90 => (height - 1 - y, x),
+                                _ => (y, width - 1 - x),
+                            };
+                            let offset = dst_y * dst_stride + dst_x * BPP;
+                            dst.pixels[offset..offset + BPP]
+                                .copy_from_slice(&row[x * BPP..x * BPP + BPP]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
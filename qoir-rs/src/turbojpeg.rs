@@ -0,0 +1,303 @@
+//! JPEG decoding and encoding with libjpeg-turbo, enabled with the
+//! `turbojpeg` feature.
+//!
+//! Camera JPEGs are the most common source of QOIR previews, and the SIMD
+//! decoder of libjpeg-turbo reads them several times faster than the
+//! pure-Rust one of the `image` crate. The feature links the system
+//! `libturbojpeg`, version 2.0 or later.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::{transcode_jpeg_to_qoir, EncodeOptions};
+//!
+//! let jpeg = std::fs::read("IMG_0042.JPG").expect("Failed to read JPEG file");
+//! let encoded = transcode_jpeg_to_qoir(&jpeg, EncodeOptions::default()).expect("Failed to transcode");
+//! std::fs::write("IMG_0042.qoir", encoded.data).expect("Failed to write");
+//! ```
+
+use alloc::{format, vec, vec::Vec};
+use core::ffi::{CStr, c_char, c_int, c_uchar, c_ulong, c_void};
+
+use crate::{EncodeOptions, EncodedBuffer, Error, Image, ImageBuf, PixelFormat, encode_to_memory};
+
+type TjHandle = *mut c_void;
+
+/// `TJSAMP_420`: 4:2:0 chroma subsampling.
+const TJSAMP_420: c_int = 2;
+/// `TJFLAG_NOREALLOC`: write into the given buffer instead of allocating.
+const TJFLAG_NOREALLOC: c_int = 1024;
+
+#[link(name = "turbojpeg")]
+unsafe extern "C" {
+    fn tjInitDecompress() -> TjHandle;
+    fn tjInitCompress() -> TjHandle;
+    fn tjDestroy(handle: TjHandle) -> c_int;
+    fn tjGetErrorStr2(handle: TjHandle) -> *mut c_char;
+    fn tjDecompressHeader3(
+        handle: TjHandle,
+        jpeg_buf: *const c_uchar,
+        jpeg_size: c_ulong,
+        width: *mut c_int,
+        height: *mut c_int,
+        jpeg_subsamp: *mut c_int,
+        jpeg_colorspace: *mut c_int,
+    ) -> c_int;
+    fn tjDecompress2(
+        handle: TjHandle,
+        jpeg_buf: *const c_uchar,
+        jpeg_size: c_ulong,
+        dst_buf: *mut c_uchar,
+        width: c_int,
+        pitch: c_int,
+        height: c_int,
+        pixel_format: c_int,
+        flags: c_int,
+    ) -> c_int;
+    fn tjBufSize(width: c_int, height: c_int, jpeg_subsamp: c_int) -> c_ulong;
+    fn tjCompress2(
+        handle: TjHandle,
+        src_buf: *const c_uchar,
+        width: c_int,
+        pitch: c_int,
+        height: c_int,
+        pixel_format: c_int,
+        jpeg_buf: *mut *mut c_uchar,
+        jpeg_size: *mut c_ulong,
+        jpeg_subsamp: c_int,
+        jpeg_qual: c_int,
+        flags: c_int,
+    ) -> c_int;
+}
+
+/// A TurboJPEG instance, destroyed when dropped.
+struct Handle(TjHandle);
+
+impl Handle {
+    fn new(handle: TjHandle) -> Option<Self> {
+        (!handle.is_null()).then_some(Handle(handle))
+    }
+
+    /// The message of the last error of this instance.
+    fn error(&self) -> String {
+        // SAFETY: The handle is valid, and the message is a NUL-terminated
+        // string owned by it.
+        let message = unsafe { CStr::from_ptr(tjGetErrorStr2(self.0)) };
+        format!("#qoir-turbojpeg: {}", message.to_string_lossy())
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        // SAFETY: The handle is valid and not used again.
+        unsafe { tjDestroy(self.0) };
+    }
+}
+
+/// The TurboJPEG `TJPF` value with the same layout as `pixel_format`. JPEGs
+/// are opaque, so premultiplied alpha needs no conversion.
+fn tj_pixel_format(pixel_format: PixelFormat) -> Option<c_int> {
+    Some(match pixel_format {
+        PixelFormat::RGB => 0,
+        PixelFormat::BGR => 1,
+        PixelFormat::RGBX => 2,
+        PixelFormat::BGRX => 3,
+        PixelFormat::RGBANonPremul | PixelFormat::RGBAPremul => 7,
+        PixelFormat::BGRANonPremul | PixelFormat::BGRAPremul => 8,
+        PixelFormat::Invalid => return None,
+    })
+}
+
+/// Decodes a JPEG with libjpeg-turbo.
+///
+/// # Arguments
+///
+/// * `jpeg`: The JPEG data.
+/// * `pixel_format`: The pixel format to decode into. Alpha is opaque.
+///
+/// # Returns
+///
+/// A `Result` containing the tightly packed `ImageBuf`,
+/// `Error::InvalidParameter` if the pixel format is `Invalid`, or
+/// `Error::DecodingFailed` if libjpeg-turbo cannot decode the data, for
+/// example because it is a CMYK JPEG.
+pub fn decode_jpeg(jpeg: &[u8], pixel_format: PixelFormat) -> Result<ImageBuf, Error> {
+    let tj_format = tj_pixel_format(pixel_format).ok_or(Error::InvalidParameter)?;
+    // SAFETY: Creating an instance has no preconditions.
+    let handle = Handle::new(unsafe { tjInitDecompress() })
+        .ok_or_else(|| Error::DecodingFailed("#qoir-turbojpeg: out of memory".into()))?;
+
+    let (mut width, mut height, mut subsamp, mut colorspace) = (0, 0, 0, 0);
+    // SAFETY: `jpeg` is valid for its length, and the outputs are valid
+    // pointers.
+    let status = unsafe {
+        tjDecompressHeader3(
+            handle.0,
+            jpeg.as_ptr(),
+            jpeg.len() as c_ulong,
+            &mut width,
+            &mut height,
+            &mut subsamp,
+            &mut colorspace,
+        )
+    };
+    if status != 0 {
+        return Err(Error::DecodingFailed(handle.error()));
+    }
+
+    let stride = width as usize * pixel_format.bytes_per_pixel();
+    let mut pixels = vec![0; stride * height as usize];
+    // SAFETY: `pixels` holds `height` rows of `stride` bytes.
+    let status = unsafe {
+        tjDecompress2(
+            handle.0,
+            jpeg.as_ptr(),
+            jpeg.len() as c_ulong,
+            pixels.as_mut_ptr(),
+            width,
+            stride as c_int,
+            height,
+            tj_format,
+            0,
+        )
+    };
+    if status != 0 {
+        return Err(Error::DecodingFailed(handle.error()));
+    }
+
+    Ok(ImageBuf {
+        pixels,
+        width: width as u32,
+        height: height as u32,
+        pixel_format,
+        stride_in_bytes: stride,
+    })
+}
+
+/// Encodes an image as a JPEG with libjpeg-turbo, with 4:2:0 chroma
+/// subsampling. Alpha is dropped.
+///
+/// # Arguments
+///
+/// * `image`: The `Image` to encode.
+/// * `quality`: The JPEG quality, from 1 to 100.
+///
+/// # Returns
+///
+/// A `Result` containing the JPEG data, `Error::InvalidParameter` if the
+/// quality, pixel format, dimensions or pixel buffer are invalid, or
+/// `Error::EncodingFailed` if libjpeg-turbo fails.
+pub fn encode_jpeg(image: &Image, quality: u8) -> Result<Vec<u8>, Error> {
+    let tj_format = tj_pixel_format(image.pixel_format).ok_or(Error::InvalidParameter)?;
+    let (Ok(width), Ok(height), Ok(pitch)) = (
+        c_int::try_from(image.width),
+        c_int::try_from(image.height),
+        c_int::try_from(image.stride_in_bytes),
+    ) else {
+        return Err(Error::InvalidParameter);
+    };
+    if !(1..=100).contains(&quality) || width == 0 || height == 0 {
+        return Err(Error::InvalidParameter);
+    }
+    image.check_buffer()?;
+    // SAFETY: Creating an instance has no preconditions.
+    let handle = Handle::new(unsafe { tjInitCompress() })
+        .ok_or_else(|| Error::EncodingFailed("#qoir-turbojpeg: out of memory".into()))?;
+
+    // Encode into a buffer of the worst-case size, so that libjpeg-turbo
+    // never allocates memory that would have to be freed with `tjFree`.
+    // SAFETY: Computing a size has no preconditions.
+    let mut jpeg_size = unsafe { tjBufSize(width, height, TJSAMP_420) };
+    let mut jpeg = vec![0; jpeg_size as usize];
+    let mut jpeg_ptr = jpeg.as_mut_ptr();
+    // SAFETY: The pixels hold `height` rows of `pitch` bytes, as checked
+    // above, and `jpeg` holds `jpeg_size` bytes.
+    let status = unsafe {
+        tjCompress2(
+            handle.0,
+            image.pixels.as_ptr(),
+            width,
+            pitch,
+            height,
+            tj_format,
+            &mut jpeg_ptr,
+            &mut jpeg_size,
+            TJSAMP_420,
+            quality as c_int,
+            TJFLAG_NOREALLOC,
+        )
+    };
+    if status != 0 {
+        return Err(Error::EncodingFailed(handle.error()));
+    }
+    jpeg.truncate(jpeg_size as usize);
+    Ok(jpeg)
+}
+
+/// Transcodes a JPEG to QOIR, decoding it with libjpeg-turbo.
+///
+/// The ICC profile, EXIF and XMP of the JPEG are copied over, unless
+/// `options` already holds them.
+///
+/// # Returns
+///
+/// A `Result` containing the `EncodedBuffer`, or an `Error` if decoding or
+/// encoding fails.
+pub fn transcode_jpeg_to_qoir<'a>(
+    jpeg: &[u8],
+    mut options: EncodeOptions,
+) -> Result<EncodedBuffer<'a>, Error> {
+    let image = decode_jpeg(jpeg, PixelFormat::RGB)?;
+    let (icc_profile, exif, xmp) = jpeg_metadata(jpeg);
+    options.icc_profile = options.icc_profile.or(icc_profile);
+    options.exif = options.exif.or(exif);
+    options.xmp = options.xmp.or(xmp);
+    encode_to_memory(image.as_image(), options)
+}
+
+/// The ICC profile, EXIF and XMP of a JPEG.
+type JpegMetadata = (Option<Vec<u8>>, Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// Reads the metadata in the `APP1` and `APP2` segments of a JPEG. An ICC
+/// profile split over several segments is joined back up.
+fn jpeg_metadata(jpeg: &[u8]) -> JpegMetadata {
+    let mut icc_chunks = Vec::new();
+    let (mut exif, mut xmp) = (None, None);
+    let mut pos = 2;
+    while let Some(&[0xFF, marker, hi, lo]) = jpeg.get(pos..pos + 4) {
+        // The image data follows start-of-scan, so there is no more metadata.
+        if marker == 0xDA {
+            break;
+        }
+        let len = u16::from_be_bytes([hi, lo]) as usize;
+        let Some(segment) = jpeg.get(pos + 4..pos + 2 + len) else {
+            break;
+        };
+        match marker {
+            0xE1 => {
+                if let Some(data) = segment.strip_prefix(b"Exif\0\0") {
+                    exif.get_or_insert_with(|| data.to_vec());
+                } else if let Some(data) = segment.strip_prefix(b"http://ns.adobe.com/xap/1.0/\0") {
+                    xmp.get_or_insert_with(|| data.to_vec());
+                }
+            }
+            0xE2 => {
+                // The sequence number and chunk count follow the signature.
+                if let Some(&[seq, _, ref data @ ..]) = segment.strip_prefix(b"ICC_PROFILE\0") {
+                    icc_chunks.push((seq, data));
+                }
+            }
+            _ => {}
+        }
+        pos += 2 + len;
+    }
+    icc_chunks.sort_by_key(|&(seq, _)| seq);
+    let icc_profile = (!icc_chunks.is_empty()).then(|| {
+        icc_chunks
+            .iter()
+            .flat_map(|(_, data)| *data)
+            .copied()
+            .collect()
+    });
+    (icc_profile, exif, xmp)
+}
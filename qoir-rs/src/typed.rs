@@ -0,0 +1,204 @@
+//! Images whose pixel format is known at compile time.
+//!
+//! An [`Image`] carries its pixel format and stride as run-time values, so
+//! code reading it has to get the channel order and bytes per pixel right by
+//! hand. A [`TypedImage`] fixes the format in its type instead, and hands out
+//! rows and pixels as structs with named channels.
+
+use core::marker::PhantomData;
+
+use crate::{Error, Image, PixelFormat};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A pixel struct with the same layout as one of the pixel formats.
+///
+/// The trait is sealed: every implementor is a `#[repr(C)]` struct of `u8`
+/// channels, so a row of pixel bytes can be viewed as a slice of them.
+pub trait PixelLayout: sealed::Sealed + Copy + 'static {
+    /// The pixel format with this layout.
+    const PIXEL_FORMAT: PixelFormat;
+    /// The size of one pixel in bytes.
+    const BYTES_PER_PIXEL: usize = core::mem::size_of::<Self>();
+}
+
+macro_rules! pixel_layouts {
+    ($($(#[$doc:meta])* $name:ident { $($channel:ident),+ } => $format:ident,)+) => {
+        $(
+            $(#[$doc])*
+            #[repr(C)]
+            #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+            pub struct $name {
+                $(pub $channel: u8,)+
+            }
+
+            impl sealed::Sealed for $name {}
+
+            impl PixelLayout for $name {
+                const PIXEL_FORMAT: PixelFormat = PixelFormat::$format;
+            }
+        )+
+    };
+}
+
+pixel_layouts! {
+    /// A `PixelFormat::RGBANonPremul` pixel.
+    Rgba8 { r, g, b, a } => RGBANonPremul,
+    /// A `PixelFormat::RGBAPremul` pixel.
+    Rgba8Premul { r, g, b, a } => RGBAPremul,
+    /// A `PixelFormat::RGBX` pixel. The X byte is ignored.
+    Rgbx8 { r, g, b, x } => RGBX,
+    /// A `PixelFormat::RGB` pixel.
+    Rgb8 { r, g, b } => RGB,
+    /// A `PixelFormat::BGRANonPremul` pixel.
+    Bgra8 { b, g, r, a } => BGRANonPremul,
+    /// A `PixelFormat::BGRAPremul` pixel.
+    Bgra8Premul { b, g, r, a } => BGRAPremul,
+    /// A `PixelFormat::BGRX` pixel. The X byte is ignored.
+    Bgrx8 { b, g, r, x } => BGRX,
+    /// A `PixelFormat::BGR` pixel.
+    Bgr8 { b, g, r } => BGR,
+}
+
+/// Views the bytes of whole pixels as a slice of `P`.
+fn cast<P: PixelLayout>(bytes: &[u8]) -> &[P] {
+    // `P` is a `#[repr(C)]` struct of `u8`s, so it has an alignment of one
+    // and every byte pattern is a valid pixel.
+    unsafe {
+        core::slice::from_raw_parts(bytes.as_ptr().cast::<P>(), bytes.len() / P::BYTES_PER_PIXEL)
+    }
+}
+
+/// A borrowed image whose pixel format is the type parameter `P`, such as
+/// `TypedImage<Rgba8>`.
+///
+/// Its buffer is checked to hold every row when it is created, so reading
+/// rows and pixels cannot go out of bounds.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::{decode, DecodeOptions, Rgba8, TypedImage};
+///
+/// let decoded = decode("input.qoir", DecodeOptions::default()).expect("Failed to decode");
+/// let image: TypedImage<Rgba8> = decoded.image.as_typed().expect("Not RGBA");
+/// let opaque = image.rows().flatten().filter(|pixel| pixel.a == 0xFF).count();
+/// println!("{} opaque pixels", opaque);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct TypedImage<'data, P: PixelLayout> {
+    pixels: &'data [u8],
+    width: u32,
+    height: u32,
+    stride_in_bytes: usize,
+    layout: PhantomData<P>,
+}
+
+impl<'data, P: PixelLayout> TypedImage<'data, P> {
+    /// Wraps tightly packed pixels.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TypedImage`, or `Error::InvalidParameter`
+    /// if `pixels` holds fewer than `width * height` pixels.
+    pub fn new(pixels: &'data [P], width: u32, height: u32) -> Result<Self, Error> {
+        // A slice of `P` is a slice of its bytes, with no padding.
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                pixels.as_ptr().cast::<u8>(),
+                pixels.len() * P::BYTES_PER_PIXEL,
+            )
+        };
+        Image::from_raw(bytes, width, height, P::PIXEL_FORMAT)?.as_typed()
+    }
+
+    /// Width of the image in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Stride (or row size) in bytes for the pixel data.
+    pub fn stride_in_bytes(&self) -> usize {
+        self.stride_in_bytes
+    }
+
+    /// The pixels of row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `y` is not less than the height.
+    pub fn row(&self, y: u32) -> &'data [P] {
+        assert!(y < self.height, "row {} is out of bounds", y);
+        let start = y as usize * self.stride_in_bytes;
+        cast(&self.pixels[start..start + self.width as usize * P::BYTES_PER_PIXEL])
+    }
+
+    /// The rows of the image, from the top down.
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = &'data [P]> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    /// The pixel at column `x` of row `y`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `y` is out of bounds.
+    pub fn pixel(&self, x: u32, y: u32) -> P {
+        self.row(y)[x as usize]
+    }
+
+    /// The same pixels as an `Image`, for example to encode them.
+    pub fn as_image(&self) -> Image<'data> {
+        Image {
+            pixels: self.pixels,
+            width: self.width,
+            height: self.height,
+            pixel_format: P::PIXEL_FORMAT,
+            stride_in_bytes: self.stride_in_bytes,
+        }
+    }
+}
+
+impl<'data> Image<'data> {
+    /// Borrows the pixels as a `TypedImage`, without copying.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `TypedImage`, or `Error::InvalidParameter`
+    /// if the pixel format does not match `P` or the pixel buffer is too
+    /// small. Use `to_pixel_format` to convert from other formats.
+    pub fn as_typed<P: PixelLayout>(&self) -> Result<TypedImage<'data, P>, Error> {
+        if self.pixel_format != P::PIXEL_FORMAT {
+            return Err(Error::InvalidParameter);
+        }
+        self.check_buffer()?;
+        Ok(TypedImage {
+            pixels: self.pixels,
+            width: self.width,
+            height: self.height,
+            stride_in_bytes: self.stride_in_bytes,
+            layout: PhantomData,
+        })
+    }
+}
+
+impl<'data, P: PixelLayout> TryFrom<Image<'data>> for TypedImage<'data, P> {
+    type Error = Error;
+
+    fn try_from(image: Image<'data>) -> Result<Self, Error> {
+        image.as_typed()
+    }
+}
+
+impl<'data, P: PixelLayout> From<TypedImage<'data, P>> for Image<'data> {
+    fn from(image: TypedImage<'data, P>) -> Self {
+        image.as_image()
+    }
+}
@@ -1,6 +1,7 @@
-use std::sync::Arc;
+use alloc::{string::String, sync::Arc, vec::Vec};
 
-use crate::bindings::{qoir_decode_result, qoir_encode_result, qoir_pixel_format, qoir_rectangle};
+#[cfg(feature = "c-backend")]
+use crate::bindings::{qoir_decode_result, qoir_free};
 
 /// Represents errors that can occur during QOIR encoding or decoding.
 #[derive(Debug, Clone, thiserror::Error)]
@@ -8,74 +9,146 @@ pub enum Error {
     /// An invalid parameter was provided to a function.
     #[error("Invalid parameter")]
     InvalidParameter,
-    /// Decoding of QOIR data failed. Contains a message from the C library.
+    /// Decoding of QOIR data failed. Contains a message from the decoder.
     #[error("Decoding failed: {0}")]
     DecodingFailed(String),
-    /// Encoding to QOIR data failed. Contains a message from the C library.
+    /// Encoding to QOIR data failed. Contains a message from the encoder.
     #[error("Encoding failed: {0}")]
     EncodingFailed(String),
     /// The specified file could not be found.
     #[error("File not found")]
     FileNotFound,
-    /// An I/O error occurred during file reading or writing.
-    #[error("I/O error occurred")]
-    IoError,
+    /// A backend returned a result that breaks its own guarantees, such as a
+    /// null pixel buffer. This is a bug in the backend rather than bad input.
+    #[error("Internal error: {0}")]
+    Internal(String),
+    /// An I/O error occurred during file reading or writing. Holds the error
+    /// reported by the OS and, when known, the file it happened on.
+    #[cfg(feature = "std")]
+    #[error("I/O error{}: {source}", path_context(.path))]
+    IoError {
+        source: Arc<std::io::Error>,
+        path: Option<std::path::PathBuf>,
+    },
 }
 
-/// A rectangle, defined by its top-left (x0, y0) and bottom-right (x1, y1) coordinates.
-/// The low bounds are inclusive, high bounds are exclusive.
-pub type Rectangle = qoir_rectangle;
-
-// This is the memory allocated for all the fields in this struct
-// allocated in one place by the C library to avoid fragmentation.
-pub(crate) struct DecodedResult {
-    pub(crate) result: qoir_decode_result,
+#[cfg(feature = "std")]
+fn path_context(path: &Option<std::path::PathBuf>) -> String {
+    match path {
+        Some(path) => alloc::format!(" on {}", path.display()),
+        None => String::new(),
+    }
 }
 
-unsafe impl Send for DecodedResult {}
-unsafe impl Sync for DecodedResult {}
-
-impl DecodedResult {
-    pub fn new(result: qoir_decode_result) -> Self {
-        DecodedResult { result }
+#[cfg(feature = "std")]
+impl Error {
+    /// Records that the error happened on the file at `path`.
+    ///
+    /// Only `Error::IoError` carries a path, so other errors are returned
+    /// unchanged. A path that is already set is kept.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use qoir_rs::Error;
+    ///
+    /// let data = std::fs::read("input.qoir").map_err(|e| Error::from(e).with_path("input.qoir"))?;
+    /// # Ok::<(), Error>(())
+    /// ```
+    pub fn with_path(self, path: impl AsRef<std::path::Path>) -> Self {
+        match self {
+            Error::IoError { source, path: None } => Error::IoError {
+                source,
+                path: Some(path.as_ref().to_path_buf()),
+            },
+            error => error,
+        }
     }
 }
 
-impl Drop for DecodedResult {
-    fn drop(&mut self) {
-        unsafe {
-            if !self.result.owned_memory.is_null() {
-                libc::free(self.result.owned_memory);
-            }
+/// Converts an I/O error, keeping it as the source. A missing file becomes
+/// `Error::FileNotFound`.
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => Error::FileNotFound,
+            _ => Error::IoError {
+                source: Arc::new(error),
+                path: None,
+            },
         }
     }
 }
 
-pub(crate) struct EncodedResult {
-    pub(crate) result: qoir_encode_result,
+/// A rectangle, defined by its top-left (x0, y0) and bottom-right (x1, y1) coordinates.
+/// The low bounds are inclusive, high bounds are exclusive.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Rectangle {
+    pub x0: i32,
+    pub y0: i32,
+    pub x1: i32,
+    pub y1: i32,
 }
 
-unsafe impl Send for EncodedResult {}
-unsafe impl Sync for EncodedResult {}
-
-impl EncodedResult {
-    pub fn new(result: qoir_encode_result) -> Self {
-        EncodedResult { result }
-    }
+/// The memory backing a `DecodedImage`, owned by whichever backend produced it.
+pub(crate) enum DecodedResult {
+    // This is the memory allocated for all the fields in this struct
+    // allocated in one place by the C library to avoid fragmentation.
+    #[cfg(feature = "c-backend")]
+    Ffi(qoir_decode_result),
+    #[cfg(feature = "rust-backend")]
+    Owned(#[allow(dead_code)] crate::rust_backend::DecodedBuffers),
+    /// Pixels scaled by `DecodeOptions::fit_within`. The metadata still
+    /// points into the decoder's memory, which is kept alive with them.
+    #[cfg(feature = "std")]
+    Resized {
+        #[allow(dead_code)]
+        source: Arc<DecodedResult>,
+        #[allow(dead_code)]
+        pixels: Vec<u8>,
+    },
+    /// An embedded thumbnail decoded for `DecodeOptions::fit_within`, with
+    /// copies of the image's metadata, since the thumbnail has none.
+    #[cfg(feature = "std")]
+    Thumbnail {
+        #[allow(dead_code)]
+        source: Arc<DecodedResult>,
+        #[allow(dead_code)]
+        metadata: [Option<Vec<u8>>; 4],
+    },
 }
 
-impl Drop for EncodedResult {
+unsafe impl Send for DecodedResult {}
+unsafe impl Sync for DecodedResult {}
+
+impl Drop for DecodedResult {
     fn drop(&mut self) {
-        unsafe {
-            if !self.result.owned_memory.is_null() {
-                libc::free(self.result.owned_memory);
-            }
+        match self {
+            #[cfg(feature = "c-backend")]
+            DecodedResult::Ffi(result) => unsafe {
+                if !result.owned_memory.is_null() {
+                    qoir_free(result.owned_memory);
+                }
+            },
+            #[cfg(feature = "rust-backend")]
+            DecodedResult::Owned(_) => {}
+            #[cfg(feature = "std")]
+            DecodedResult::Resized { .. } | DecodedResult::Thumbnail { .. } => {}
         }
     }
 }
 
+/// The memory backing an `EncodedBuffer`. Both backends encode into a `Vec`,
+/// the C library through the allocator it is given.
+pub(crate) enum EncodedResult {
+    Owned(#[allow(dead_code)] Vec<u8>),
+}
+
 /// Represents the different pixel formats supported by QOIR.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub enum PixelFormat {
     /// Invalid pixel format.
     Invalid = 0x00,
@@ -99,9 +172,31 @@ pub enum PixelFormat {
     // MaskForColorModel = 0x0C,        // Internal C library detail
 }
 
+impl PixelFormat {
+    /// The number of bytes used to store one pixel in this format.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Invalid => 0,
+            PixelFormat::BGR | PixelFormat::RGB => 3,
+            _ => 4,
+        }
+    }
+
+    /// Whether the format has an alpha channel, premultiplied or not.
+    pub fn has_alpha(self) -> bool {
+        matches!(
+            self,
+            PixelFormat::BGRANonPremul
+                | PixelFormat::BGRAPremul
+                | PixelFormat::RGBANonPremul
+                | PixelFormat::RGBAPremul
+        )
+    }
+}
+
 #[allow(non_snake_case, unused_variables)]
-impl From<qoir_pixel_format> for PixelFormat {
-    fn from(value: qoir_pixel_format) -> Self {
+impl From<u32> for PixelFormat {
+    fn from(value: u32) -> Self {
         match value {
             0x00 => PixelFormat::Invalid,
             0x01 => PixelFormat::BGRX,
@@ -136,8 +231,38 @@ pub struct Image<'data> {
     pub stride_in_bytes: usize,
 }
 
+/// An uncompressed image that owns its pixels, such as the result of
+/// `Image::resize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBuf {
+    /// Raw pixel data.
+    pub pixels: Vec<u8>,
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Pixel format of the image data.
+    pub pixel_format: PixelFormat,
+    /// Stride (or row size) in bytes for the pixel data.
+    pub stride_in_bytes: usize,
+}
+
+impl ImageBuf {
+    /// Borrows the pixels as an `Image`, for example to encode them.
+    pub fn as_image(&self) -> Image<'_> {
+        Image {
+            pixels: &self.pixels,
+            width: self.width,
+            height: self.height,
+            pixel_format: self.pixel_format,
+            stride_in_bytes: self.stride_in_bytes,
+        }
+    }
+}
+
 /// Options for controlling the QOIR decoding process.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct DecodeOptions {
     /// If non-zero, this is the pixel format to use when dynamically allocating
     /// the pixel buffer to decode into. Defaults to `PixelFormat::RGBANonPremul`.
@@ -152,6 +277,48 @@ pub struct DecodeOptions {
     /// The Y offset (in destination coordinate space) to place the top-left
     /// corner of the decoded source image. The Y axis grows down.
     pub offset_y: i32,
+    /// Whether to decode as much as possible of a truncated or corrupt file
+    /// instead of failing. Tiles that are missing or fail to decode are
+    /// filled with `fill_color` and listed in
+    /// [`DecodedImage::missing_regions`]; the header must still be intact.
+    ///
+    /// A file too short to hold every tile its header claims could be a
+    /// forged header asking for gigabytes of pixels, so it is only decoded
+    /// when `limits` sets `max_pixels` or `max_memory`. Otherwise the decode
+    /// fails with `Error::DecodingFailed`.
+    ///
+    /// Tolerant decoding is done by the Rust backend, so this needs the
+    /// `rust-backend` feature. Without it, decoding with this option set
+    /// fails with `Error::InvalidParameter`.
+    pub tolerant: bool,
+    /// The color of the regions that could not be decoded in tolerant mode,
+    /// as non-premultiplied RGBA.
+    pub fill_color: [u8; 4],
+    /// Limits on the size of the image, checked against its header before
+    /// any pixels are decoded. There are none by default.
+    pub limits: DecodeLimits,
+    /// Whether to measure how long each stage of the decode takes, reported
+    /// in [`DecodedImage::timings`]. Defaults to `false`.
+    pub collect_timings: bool,
+    /// A width and height to scale the decoded image down to fit within,
+    /// keeping its aspect ratio, with `ResizeFilter::Lanczos3`. Images that
+    /// already fit are left as they are; none are scaled up. Defaults to
+    /// `None`.
+    ///
+    /// The output size is worked out from the header. If the file has an
+    /// embedded thumbnail (see [`EncodeOptions::embed_thumbnail`]) at least
+    /// that large, the thumbnail is scaled down instead, and the pixels of
+    /// the image itself are never decoded. Otherwise the image is decoded and
+    /// scaled.
+    ///
+    /// With `src_clip_rect`, only the tiles under the rectangle are decoded,
+    /// and the output is that region, scaled to fit; thumbnails are not used.
+    /// Combining this with `dst_clip_rect` or the offsets fails with
+    /// `Error::InvalidParameter`, and so does decoding with this option set
+    /// without the `std` feature, which the resizer needs.
+    pub fit_within: Option<(u32, u32)>,
+    /// Which metadata to return with the image. Defaults to all of it.
+    pub metadata: MetadataSelection,
 }
 
 impl Default for DecodeOptions {
@@ -162,10 +329,138 @@ impl Default for DecodeOptions {
             dst_clip_rect: None,
             offset_x: 0,
             offset_y: 0,
+            tolerant: false,
+            fill_color: [0; 4],
+            limits: DecodeLimits::default(),
+            collect_timings: false,
+            fit_within: None,
+            metadata: MetadataSelection::default(),
+        }
+    }
+}
+
+/// Limits on the images a decoder accepts, so that untrusted files can't make
+/// it allocate more memory than expected. A QOIR header can claim an image of
+/// up to 16777215x16777215 pixels in a few bytes. `None` means no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct DecodeLimits {
+    /// The largest number of pixels, width times height.
+    pub max_pixels: Option<u64>,
+    /// The largest width or height, in pixels.
+    pub max_dimension: Option<u32>,
+    /// The largest pixel buffer to decode into, in bytes.
+    pub max_memory: Option<u64>,
+}
+
+impl DecodeLimits {
+    /// Checks the header of QOIR data against the limits.
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: The QOIR data. Only its header is read.
+    /// * `pixel_format`: The pixel format the image will be decoded into,
+    ///   which sets the size of the pixel buffer.
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())` if the image is within every limit, or
+    /// `Error::DecodingFailed` if it exceeds one or the header is invalid.
+    pub fn check(&self, data: &[u8], pixel_format: PixelFormat) -> Result<(), Error> {
+        if *self == DecodeLimits::default() {
+            return Ok(());
+        }
+        let (width, height, _) = crate::decode_basic_metadata(data)?;
+        self.check_dimensions(width, height, pixel_format)
+    }
+
+    /// Whether the limits bound the size of the pixel buffer.
+    #[cfg(feature = "rust-backend")]
+    pub(crate) fn bounds_memory(&self) -> bool {
+        self.max_pixels.is_some() || self.max_memory.is_some()
+    }
+
+    /// Checks the dimensions of an image against the limits.
+    pub(crate) fn check_dimensions(
+        &self,
+        width: u32,
+        height: u32,
+        pixel_format: PixelFormat,
+    ) -> Result<(), Error> {
+        let pixels = width as u64 * height as u64;
+        let exceeds = |limit: Option<u64>, value: u64| limit.is_some_and(|max| value > max);
+        if exceeds(self.max_dimension.map(u64::from), width.max(height) as u64) {
+            return Err(Error::DecodingFailed("#qoir: image exceeds the dimension limit".into()));
+        }
+        if exceeds(self.max_pixels, pixels) {
+            return Err(Error::DecodingFailed("#qoir: image exceeds the pixel limit".into()));
+        }
+        if exceeds(self.max_memory, pixels * pixel_format.bytes_per_pixel() as u64) {
+            return Err(Error::DecodingFailed("#qoir: image exceeds the memory limit".into()));
+        }
+        Ok(())
+    }
+}
+
+/// The metadata a decoder returns, by kind. Metadata that is not selected is
+/// left out of the `DecodedImage`, and `decode_from_seek` skips over it
+/// without reading it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct MetadataSelection {
+    /// Whether to return the CICP profile.
+    pub cic_profile: bool,
+    /// Whether to return the ICC profile.
+    pub icc_profile: bool,
+    /// Whether to return the EXIF data.
+    pub exif: bool,
+    /// Whether to return the XMP data.
+    pub xmp: bool,
+    /// Whether to return the application-defined key/value pairs.
+    pub custom: bool,
+}
+
+impl MetadataSelection {
+    /// Selects every kind of metadata. This is the default.
+    pub const ALL: Self = MetadataSelection {
+        cic_profile: true,
+        icc_profile: true,
+        exif: true,
+        xmp: true,
+        custom: true,
+    };
+
+    /// Selects no metadata, for callers that only want the pixels.
+    pub const NONE: Self = MetadataSelection {
+        cic_profile: false,
+        icc_profile: false,
+        exif: false,
+        xmp: false,
+        custom: false,
+    };
+
+    /// Whether a decoder keeps the chunk tagged `tag`. The chunks holding the
+    /// image are always kept, and unknown chunks never are.
+    #[cfg(feature = "std")]
+    pub(crate) fn keeps(&self, tag: &[u8; 4]) -> bool {
+        match tag {
+            b"QOIR" | b"QPIX" | b"QSUM" | b"QEND" => true,
+            b"CICP" => self.cic_profile,
+            b"ICCP" => self.icc_profile,
+            b"EXIF" => self.exif,
+            b"XMP " => self.xmp,
+            b"APPD" => self.custom,
+            _ => false,
         }
     }
 }
 
+impl Default for MetadataSelection {
+    fn default() -> Self {
+        MetadataSelection::ALL
+    }
+}
+
 /// Represents a decoded QOIR image.
 ///
 /// This struct holds the decoded image data (`image`) and any embedded metadata.
@@ -189,10 +484,51 @@ pub struct DecodedImage<'a> {
     pub exif: Option<&'a [u8]>,
     /// Optional embedded XMP (Extensible Metadata Platform) data.
     pub xmp: Option<&'a [u8]>,
+    /// Application-defined key/value pairs, in the order they were stored.
+    /// See [`EncodeOptions::custom_metadata`].
+    pub custom_metadata: Vec<(String, Vec<u8>)>,
+    /// The parts of the decoded area, in source coordinates, that could not
+    /// be decoded and were filled with `DecodeOptions::fill_color`. Always
+    /// empty unless `DecodeOptions::tolerant` is set.
+    pub missing_regions: Vec<Rectangle>,
+    /// How long each stage of the decode took, if
+    /// `DecodeOptions::collect_timings` was set.
+    pub timings: Option<crate::Timings>,
+}
+
+/// A decoded QOIR image that owns its pixels and metadata.
+///
+/// Unlike `DecodedImage` it has no lifetime parameter, which makes it easier
+/// to store, send between threads or hand to other languages. Create one with
+/// `DecodedImage::into_owned`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedDecodedImage {
+    /// Raw pixel data.
+    pub pixels: Vec<u8>,
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Pixel format of the image data.
+    pub pixel_format: PixelFormat,
+    /// Stride (or row size) in bytes for the pixel data.
+    pub stride_in_bytes: usize,
+
+    /// Optional embedded CICP (Coding-Independent Code Points) profile data.
+    pub cic_profile: Option<Vec<u8>>,
+    /// Optional embedded ICC (International Color Consortium) profile data.
+    pub icc_profile: Option<Vec<u8>>,
+    /// Optional embedded EXIF (Exchangeable image file format) data.
+    pub exif: Option<Vec<u8>>,
+    /// Optional embedded XMP (Extensible Metadata Platform) data.
+    pub xmp: Option<Vec<u8>>,
+    /// Application-defined key/value pairs, in the order they were stored.
+    pub custom_metadata: Vec<(String, Vec<u8>)>,
 }
 
 /// Options for controlling the QOIR encoding process.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct EncodeOptions {
     /// Optional CICP (Coding-Independent Code Points) profile data to embed.
     pub cicp_profile: Option<Vec<u8>>,
@@ -202,6 +538,10 @@ pub struct EncodeOptions {
     pub exif: Option<Vec<u8>>,
     /// Optional XMP (Extensible Metadata Platform) data to embed.
     pub xmp: Option<Vec<u8>>,
+    /// Application-defined key/value pairs to embed, such as ratings, pick
+    /// flags or pipeline provenance. They are stored in an `APPD` chunk,
+    /// which other QOIR readers skip. Keys may repeat. Defaults to empty.
+    pub custom_metadata: Vec<(String, Vec<u8>)>,
 
     /// Lossiness level for encoding. Ranges from 0 (lossless) to 7 (very lossy).
     /// Defaults to 0 (lossless).
@@ -210,6 +550,71 @@ pub struct EncodeOptions {
     /// Whether to dither the lossy encoding. This option has no effect if `lossiness` is zero.
     /// Defaults to `false`.
     pub dither: bool,
+
+    /// Whether to store a CRC-32C checksum of the pixel data after it, so that
+    /// [`verify_integrity`](crate::verify_integrity) can detect bit rot
+    /// without an external checksum file. The checksum does not cover the
+    /// metadata, which can be edited without invalidating it. Defaults to
+    /// `false`.
+    pub embed_checksum: bool,
+
+    /// Reduces the number of colors before encoding, see [`QuantizeOptions`].
+    /// Defaults to `None` (no quantization).
+    pub quantize: Option<QuantizeOptions>,
+
+    /// Whether to measure how long each stage of the encode takes, reported
+    /// in [`EncodedBuffer::timings`]. Defaults to `false`.
+    pub collect_timings: bool,
+
+    /// Stores a small copy of the image, see [`ThumbnailSpec`]. Defaults to
+    /// `None` (no thumbnail).
+    pub embed_thumbnail: Option<ThumbnailSpec>,
+}
+
+/// Options for reducing the number of colors of an image before encoding it.
+///
+/// Screenshots, UI captures and other synthetic images often use a few
+/// hundred colors, plus a long tail of anti-aliasing shades. Mapping them to
+/// a small palette gives the encoder long runs and repeated colors, which
+/// typically makes such images several times smaller. Images that already
+/// have at most `max_colors` colors are encoded unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct QuantizeOptions {
+    /// The largest number of colors to keep, counting each alpha value of a
+    /// color separately. Must be at least 1.
+    pub max_colors: u16,
+    /// Whether to diffuse the quantization error over neighbouring pixels
+    /// (Floyd-Steinberg dithering). This smooths gradients, but adds noise
+    /// that costs some of the size saving.
+    pub dither: bool,
+}
+
+/// A thumbnail to store alongside the image, read back with
+/// [`extract_thumbnail`](crate::extract_thumbnail()) without decoding the
+/// image itself.
+///
+/// The thumbnail is the image scaled down with `ResizeFilter::Box` to fit
+/// within `max_dim` by `max_dim` pixels, or the image itself if it is that
+/// small already, encoded as QOIR in a `THMB` chunk before the pixels. Other
+/// QOIR readers skip the chunk. Making thumbnails needs the `std` feature;
+/// without it no thumbnail is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct ThumbnailSpec {
+    /// The largest width or height of the thumbnail. Must be at least 1.
+    pub max_dim: u32,
+    /// The lossiness to encode the thumbnail with, from 0 to 7.
+    pub lossiness: u8,
+}
+
+impl Default for ThumbnailSpec {
+    fn default() -> Self {
+        ThumbnailSpec {
+            max_dim: 256,
+            lossiness: 0,
+        }
+    }
 }
 
 /// Represents an encoded QOIR image buffer.
@@ -226,4 +631,8 @@ pub struct EncodedBuffer<'a> {
 
     /// The raw QOIR encoded byte data.
     pub data: &'a [u8],
+
+    /// How long each stage of the encode took, if
+    /// `EncodeOptions::collect_timings` was set.
+    pub timings: Option<crate::Timings>,
 }
@@ -0,0 +1,335 @@
+//! Batched file I/O with io_uring, enabled with the `uring` feature on Linux.
+//!
+//! Reading a file and then decoding it, one file after another, leaves the
+//! disk idle while the CPU decodes and the CPU idle while the disk reads.
+//! [`decode_files`] keeps several reads queued in the kernel and decodes each
+//! file as soon as it has arrived, while the next ones are still being read.
+//! [`encode_files`] does the same for writes, encoding the next image while
+//! the previous ones are being written.
+//!
+//! If io_uring is not available, because the kernel is too old or a seccomp
+//! filter blocks it as in many containers, the files are read and written
+//! with `std::fs` one at a time instead.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::{DecodeOptions, uring::decode_files};
+//!
+//! let paths = ["IMG_0001.qoir", "IMG_0002.qoir", "IMG_0003.qoir"];
+//! decode_files(&paths, DecodeOptions::default(), |index, result| match result {
+//!     Ok(decoded) => println!("{}: {}x{}", paths[index], decoded.image.width, decoded.image.height),
+//!     Err(e) => eprintln!("{}: {}", paths[index], e),
+//! });
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use io_uring::{IoUring, opcode, types};
+
+use crate::{DecodeOptions, DecodedImage, EncodeOptions, Error, Image, decode_from_memory};
+
+/// The number of files read or written at once.
+const QUEUE_DEPTH: usize = 8;
+
+/// Reads and decodes QOIR files, keeping several reads in flight while
+/// decoding the files that have been read.
+///
+/// The files are decoded on the calling thread, in the order their reads
+/// finish, which need not be the order of `paths`.
+///
+/// # Arguments
+///
+/// * `paths`: The QOIR files to decode.
+/// * `options`: `DecodeOptions` to control the decoding process, used for
+///   every file.
+/// * `on_image`: Called once for each file with its index in `paths` and the
+///   `DecodedImage`, or an `Error` if reading or decoding it failed.
+pub fn decode_files<P: AsRef<Path>>(
+    paths: &[P],
+    options: DecodeOptions,
+    mut on_image: impl FnMut(usize, Result<DecodedImage<'_>, Error>),
+) {
+    let Ok(mut ring) = Ring::new() else {
+        for (index, path) in paths.iter().enumerate() {
+            on_image(index, crate::decode(path, options.clone()));
+        }
+        return;
+    };
+
+    let mut next = 0;
+    start_reads(&mut ring, paths, &mut next, &mut on_image);
+    while !ring.is_idle() {
+        let finished = ring.wait();
+        // The next reads are queued before decoding, so the disk stays busy.
+        // A failed submit is reported by the next wait.
+        start_reads(&mut ring, paths, &mut next, &mut on_image);
+        let _ = ring.ring.submit();
+
+        for (transfer, result) in finished {
+            let result = match result {
+                Ok(()) => decode_from_memory(&transfer.buf, options.clone()),
+                Err(e) => Err(Error::from(e).with_path(&transfer.path)),
+            };
+            on_image(transfer.index, result);
+        }
+    }
+}
+
+/// Queues reads of the files from `paths[*next]` on while the ring has room.
+fn start_reads<P: AsRef<Path>>(
+    ring: &mut Ring,
+    paths: &[P],
+    next: &mut usize,
+    on_image: &mut impl FnMut(usize, Result<DecodedImage<'_>, Error>),
+) {
+    while ring.has_room() && *next < paths.len() {
+        let path = paths[*next].as_ref();
+        if let Err(e) = ring.start_read(*next, path) {
+            on_image(*next, Err(Error::from(e).with_path(path)));
+        }
+        *next += 1;
+    }
+}
+
+/// Encodes images and writes them to files, encoding each image while the
+/// previous ones are still being written.
+///
+/// # Arguments
+///
+/// * `images`: The images to encode, each with the path to write it to.
+/// * `options`: `EncodeOptions` to control the encoding process, used for
+///   every image.
+///
+/// # Returns
+///
+/// A `Vec` with a `Result` for each image, in the order of `images`, holding
+/// an `Error` if encoding or writing that image failed.
+pub fn encode_files<'i, P: AsRef<Path>>(
+    images: impl IntoIterator<Item = (Image<'i>, P)>,
+    options: EncodeOptions,
+) -> Vec<Result<(), Error>> {
+    let mut ring = Ring::new().ok();
+    let mut results = Vec::new();
+    for (index, (image, path)) in images.into_iter().enumerate() {
+        let path = path.as_ref();
+        results.push(Ok(()));
+        let data = match crate::encode_to_vec(image, options.clone()) {
+            Ok(data) => data,
+            Err(e) => {
+                results[index] = Err(e);
+                continue;
+            }
+        };
+        let Some(ring) = &mut ring else {
+            results[index] = std::fs::write(path, data).map_err(|e| Error::from(e).with_path(path));
+            continue;
+        };
+
+        while !ring.has_room() {
+            record_writes(ring.wait(), &mut results);
+        }
+        if let Err(e) = ring.start_write(index, path, data) {
+            results[index] = Err(Error::from(e).with_path(path));
+        }
+    }
+    if let Some(ring) = &mut ring {
+        while !ring.is_idle() {
+            record_writes(ring.wait(), &mut results);
+        }
+    }
+    results
+}
+
+fn record_writes(finished: Vec<(Transfer, io::Result<()>)>, results: &mut [Result<(), Error>]) {
+    for (transfer, result) in finished {
+        if let Err(e) = result {
+            results[transfer.index] = Err(Error::from(e).with_path(&transfer.path));
+        }
+    }
+}
+
+/// A file being read into or written from `buf`.
+struct Transfer {
+    /// The position of the file in the caller's list.
+    index: usize,
+    path: PathBuf,
+    file: File,
+    /// For a read, a buffer with room for the whole file whose length is the
+    /// number of bytes read so far. For a write, the data to write.
+    buf: Vec<u8>,
+    /// The number of bytes read or written so far.
+    done: usize,
+    /// The number of bytes to read or write.
+    len: usize,
+    write: bool,
+}
+
+/// An io_uring with a slot for each file in flight. Each slot has at most one
+/// request queued, so the submission queue never overflows.
+struct Ring {
+    ring: IoUring,
+    slots: Vec<Option<Transfer>>,
+}
+
+impl Ring {
+    fn new() -> io::Result<Self> {
+        Ok(Ring {
+            ring: IoUring::new(QUEUE_DEPTH as u32)?,
+            slots: (0..QUEUE_DEPTH).map(|_| None).collect(),
+        })
+    }
+
+    fn has_room(&self) -> bool {
+        self.slots.iter().any(Option::is_none)
+    }
+
+    fn is_idle(&self) -> bool {
+        self.slots.iter().all(Option::is_none)
+    }
+
+    /// Opens the file at `path` and queues a read of all of it.
+    fn start_read(&mut self, index: usize, path: &Path) -> io::Result<()> {
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len()).map_err(io::Error::other)?;
+        let mut buf = Vec::new();
+        buf.try_reserve_exact(len).map_err(io::Error::other)?;
+        self.start(Transfer {
+            index,
+            path: path.to_path_buf(),
+            file,
+            buf,
+            done: 0,
+            len,
+            write: false,
+        })
+    }
+
+    /// Creates the file at `path` and queues a write of `data` to it.
+    fn start_write(&mut self, index: usize, path: &Path, data: Vec<u8>) -> io::Result<()> {
+        let file = File::create(path)?;
+        self.start(Transfer {
+            index,
+            path: path.to_path_buf(),
+            file,
+            len: data.len(),
+            buf: data,
+            done: 0,
+            write: true,
+        })
+    }
+
+    fn start(&mut self, transfer: Transfer) -> io::Result<()> {
+        let slot = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .ok_or_else(|| io::Error::other("io_uring queue is full"))?;
+        self.slots[slot] = Some(transfer);
+        let result = self.queue(slot);
+        if result.is_err() {
+            self.slots[slot] = None;
+        }
+        result
+    }
+
+    /// Queues a request for the rest of the transfer in `slot`.
+    fn queue(&mut self, slot: usize) -> io::Result<()> {
+        let transfer = self.slots[slot].as_mut().expect("slot is in use");
+        let fd = types::Fd(transfer.file.as_raw_fd());
+        let remaining = (transfer.len - transfer.done).min(u32::MAX as usize) as u32;
+        let offset = transfer.done as u64;
+        let entry = if transfer.write {
+            let buf = transfer.buf[transfer.done..].as_ptr();
+            opcode::Write::new(fd, buf, remaining)
+                .offset(offset)
+                .build()
+        } else {
+            let buf = transfer.buf.spare_capacity_mut().as_mut_ptr().cast::<u8>();
+            opcode::Read::new(fd, buf, remaining).offset(offset).build()
+        };
+        // The buffer stays in its slot, and is not touched, until the request
+        // completes.
+        unsafe { self.ring.submission().push(&entry.user_data(slot as u64)) }
+            .map_err(io::Error::other)
+    }
+
+    /// Waits for at least one request to complete, queues the rest of any
+    /// transfers that were only partly done, and returns the transfers that
+    /// finished or failed.
+    fn wait(&mut self) -> Vec<(Transfer, io::Result<()>)> {
+        let mut finished = Vec::new();
+        if let Err(e) = self.ring.submit_and_wait(1) {
+            if e.kind() == io::ErrorKind::Interrupted {
+                return finished;
+            }
+            // The ring is unusable, so every transfer in it fails. The kernel
+            // may still be using their buffers, so those are leaked.
+            for mut transfer in self.slots.iter_mut().filter_map(Option::take) {
+                std::mem::forget(std::mem::take(&mut transfer.buf));
+                finished.push((transfer, Err(io::Error::new(e.kind(), e.to_string()))));
+            }
+            return finished;
+        }
+
+        let completions: Vec<_> = self
+            .ring
+            .completion()
+            .map(|cqe| (cqe.user_data() as usize, cqe.result()))
+            .collect();
+        for (slot, result) in completions {
+            let transfer = self.slots[slot].as_mut().expect("slot is in use");
+            let result = match result {
+                n if n < 0 => Err(io::Error::from_raw_os_error(-n)),
+                n => {
+                    transfer.done += n as usize;
+                    if !transfer.write {
+                        // The kernel has filled the buffer up to `done`.
+                        unsafe { transfer.buf.set_len(transfer.done) };
+                    }
+                    if transfer.done >= transfer.len {
+                        Ok(())
+                    } else if n == 0 {
+                        Err(io::ErrorKind::UnexpectedEof.into())
+                    } else {
+                        match self.queue(slot) {
+                            Ok(()) => continue,
+                            Err(e) => Err(e),
+                        }
+                    }
+                }
+            };
+            let transfer = self.slots[slot].take().expect("slot is in use");
+            finished.push((transfer, result));
+        }
+        finished
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // The kernel may still write into the buffers of requests in flight,
+        // so they are only freed once it is done with them.
+        while !self.is_idle() {
+            if self.ring.submit_and_wait(1).is_err() {
+                // The requests can't be waited for, so their buffers are
+                // leaked rather than freed while in use.
+                for mut transfer in self.slots.iter_mut().filter_map(Option::take) {
+                    std::mem::forget(std::mem::take(&mut transfer.buf));
+                }
+                return;
+            }
+            let completions: Vec<_> = self
+                .ring
+                .completion()
+                .map(|cqe| cqe.user_data() as usize)
+                .collect();
+            for slot in completions {
+                self.slots[slot] = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use alloc::{string::ToString, vec::Vec};
+
+use crate::checksum::{CHECKSUM_LEN, crc32c};
+use crate::container::{
+    CHUNK_HEADER_LEN, Container, Header, TILE_SIZE, invalid_data, next_chunk, next_tile,
+};
+use crate::{DecodeOptions, Error, TileCodec};
+
+/// A problem found by [`verify`], with where it was found.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{error} at byte {offset}")]
+pub struct VerifyError {
+    /// The byte offset of the chunk or tile that could not be read.
+    pub offset: usize,
+    /// What went wrong.
+    pub error: Error,
+}
+
+fn at(offset: usize) -> impl FnOnce(Error) -> VerifyError {
+    move |error| VerifyError { offset, error }
+}
+
+/// A problem found by [`verify_integrity`].
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum IntegrityError {
+    /// The data is not a valid QOIR image.
+    #[error("{0}")]
+    Invalid(Error),
+    /// The image was encoded without `EncodeOptions::embed_checksum`.
+    #[error("No checksum")]
+    MissingChecksum,
+    /// The pixel data does not match its checksum.
+    #[error("Checksum mismatch: stored {stored:08x}, computed {computed:08x}")]
+    Mismatch {
+        /// The checksum stored in the file.
+        stored: u32,
+        /// The checksum of the pixel data as read.
+        computed: u32,
+    },
+}
+
+/// Reads the checksum from the payload of a `QSUM` chunk.
+fn stored_checksum(payload: &[u8]) -> Result<u32, Error> {
+    let bytes: [u8; CHECKSUM_LEN] = payload.try_into().map_err(|_| invalid_data())?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Checks the pixel data of a QOIR image against the checksum stored with
+/// `EncodeOptions::embed_checksum`, for detecting bit rot in archives.
+///
+/// Only the chunk structure is parsed and the pixel data checksummed; nothing
+/// is decompressed, so this is much faster than decoding. [`verify`] also
+/// checks the checksum when there is one.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+///
+/// # Returns
+///
+/// `Ok(())` if the pixel data matches its checksum, or an `IntegrityError`
+/// if it does not, the image has no checksum, or the data is not a valid
+/// QOIR image.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::{verify_integrity, IntegrityError};
+///
+/// let qoir_data = std::fs::read("archive.qoir").expect("Failed to read QOIR file");
+/// match verify_integrity(&qoir_data) {
+///     Ok(()) => {}
+///     Err(IntegrityError::MissingChecksum) => eprintln!("Not checksummed"),
+///     Err(e) => eprintln!("Corrupt: {}", e),
+/// }
+/// ```
+pub fn verify_integrity(data: &[u8]) -> Result<(), IntegrityError> {
+    let container = Container::parse(data).map_err(IntegrityError::Invalid)?;
+    let payload = container.checksum.ok_or(IntegrityError::MissingChecksum)?;
+    let stored = stored_checksum(payload).map_err(IntegrityError::Invalid)?;
+    let computed = crc32c(container.tiles);
+    if stored != computed {
+        return Err(IntegrityError::Mismatch { stored, computed });
+    }
+    Ok(())
+}
+
+/// Checks that QOIR image data is intact, for example when scrubbing an
+/// archive.
+///
+/// This always checks the chunk structure and every tile header, which finds
+/// truncated files without decompressing anything, and the checksum of the
+/// pixel data if the image has one. Unless `fast` is set, it also decodes all
+/// pixels.
+///
+/// # Arguments
+///
+/// * `data`: A slice of bytes containing the QOIR encoded image data.
+/// * `fast`: Whether to skip decoding the pixels.
+///
+/// # Returns
+///
+/// `Ok(())` if the data is intact, or a `VerifyError` holding the first
+/// problem found and its byte offset. When decoding fails, the offset is the
+/// failing tile's if the `rust-backend` feature is enabled, and otherwise that
+/// of the `QPIX` chunk.
+///
+/// # Examples
+///
+/// ```no_run
+/// use qoir_rs::verify;
+///
+/// let qoir_data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+/// if let Err(e) = verify(&qoir_data, false) {
+///     eprintln!("Corrupt: {} at byte {}", e.error, e.offset);
+/// }
+/// ```
+pub fn verify(data: &[u8], fast: bool) -> Result<(), VerifyError> {
+    let header = Header::parse(data).map_err(at(0))?;
+
+    let mut rest = data;
+    let mut qpix = None;
+    let mut qsum = None;
+    loop {
+        let offset = data.len() - rest.len();
+        let (tag, payload, remaining) = next_chunk(rest).map_err(at(offset))?;
+        rest = remaining;
+
+        match &tag {
+            b"QPIX" => qpix = Some((offset, payload)),
+            b"QSUM" => qsum = Some((offset, payload)),
+            b"QEND" => break,
+            _ => {}
+        }
+    }
+    let Some((qpix_offset, tiles)) = qpix else {
+        return Err(at(data.len() - rest.len())(invalid_data()));
+    };
+
+    let tiles_offset = qpix_offset + CHUNK_HEADER_LEN;
+    let mut rest = tiles;
+    let mut tile_list = Vec::new();
+    for ty in (0..header.height).step_by(TILE_SIZE as usize) {
+        for tx in (0..header.width).step_by(TILE_SIZE as usize) {
+            let offset = tiles_offset + tiles.len() - rest.len();
+            let (format, payload, remaining) = next_tile(rest).map_err(at(offset))?;
+            rest = remaining;
+            if TileCodec::from_format(format).is_none() {
+                return Err(at(offset)(invalid_data()));
+            }
+            tile_list.push((offset, tx, ty, format, payload));
+        }
+    }
+    if !rest.is_empty() {
+        return Err(at(tiles_offset + tiles.len() - rest.len())(invalid_data()));
+    }
+
+    if let Some((offset, payload)) = qsum {
+        let stored = stored_checksum(payload).map_err(at(offset))?;
+        if stored != crc32c(tiles) {
+            let error = Error::DecodingFailed("#qoir: checksum mismatch".to_string());
+            return Err(VerifyError { offset, error });
+        }
+    }
+
+    if fast {
+        return Ok(());
+    }
+    let Err(error) = crate::decode_from_memory(data, DecodeOptions::default()) else {
+        return Ok(());
+    };
+
+    // Find the tile that failed to decode.
+    #[cfg(feature = "rust-backend")]
+    {
+        let mut pixels = Vec::new();
+        let mut scratch = Vec::new();
+        for (offset, tx, ty, format, payload) in tile_list {
+            let tile_width = (header.width - tx).min(TILE_SIZE) as usize;
+            let tile_height = (header.height - ty).min(TILE_SIZE) as usize;
+            pixels.resize(tile_width * tile_height * 4, 0);
+            if let Err(error) =
+                crate::rust_backend::decode_tile(format, payload, &mut pixels, &mut scratch)
+            {
+                return Err(VerifyError { offset, error });
+            }
+        }
+    }
+
+    Err(VerifyError {
+        offset: qpix_offset,
+        error,
+    })
+}
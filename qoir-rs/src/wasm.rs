@@ -0,0 +1,208 @@
+//! WebAssembly bindings, enabled with the `wasm` feature.
+//!
+//! These wrap the in-memory encode and decode functions with `wasm-bindgen`
+//! so that QOIR files can be previewed from JavaScript. Pixels cross the
+//! boundary as tightly packed, non-premultiplied RGBA, which is the layout
+//! used by the Canvas API's `ImageData`.
+//!
+//! ```js
+//! import init, { decodeToImageData, encodeFromImageData, EncodeOptions } from "./pkg/qoir_rs.js";
+//!
+//! await init();
+//! const bytes = new Uint8Array(await (await fetch("photo.qoir")).arrayBuffer());
+//! const context = canvas.getContext("2d");
+//! context.putImageData(decodeToImageData(bytes), 0, 0);
+//!
+//! const options = new EncodeOptions();
+//! options.lossiness = 2;
+//! const qoir = encodeFromImageData(context.getImageData(0, 0, canvas.width, canvas.height), options);
+//! ```
+
+use std::borrow::Cow;
+
+use js_sys::Uint8Array;
+use wasm_bindgen::{Clamped, JsError, JsValue, prelude::wasm_bindgen};
+use web_sys::ImageData;
+
+use crate::{DecodeOptions, EncodeOptions, Error, Image, PixelFormat};
+
+const BYTES_PER_PIXEL: usize = 4;
+
+fn to_js_error(error: Error) -> JsValue {
+    JsError::from(error).into()
+}
+
+/// Decodes QOIR bytes into non-premultiplied RGBA, which is what `ImageData`
+/// holds whatever alpha format the file was stored in.
+fn decode_rgba(bytes: &[u8]) -> Result<crate::DecodedImage<'static>, JsValue> {
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGBANonPremul,
+        ..Default::default()
+    };
+    crate::decode_from_memory(bytes, options).map_err(to_js_error)
+}
+
+/// The pixels of `image` without row padding, borrowed if it has none.
+fn packed_pixels<'a>(image: &Image<'a>) -> Cow<'a, [u8]> {
+    // The C library is free to pad rows, `ImageData` is not.
+    let row_len = image.width as usize * BYTES_PER_PIXEL;
+    if image.stride_in_bytes == row_len {
+        Cow::Borrowed(&image.pixels[..row_len * image.height as usize])
+    } else {
+        image
+            .pixels
+            .chunks(image.stride_in_bytes)
+            .take(image.height as usize)
+            .flat_map(|row| &row[..row_len])
+            .copied()
+            .collect()
+    }
+}
+
+/// Options for `encodeFromImageData`.
+#[wasm_bindgen(js_name = EncodeOptions)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WebEncodeOptions {
+    /// The number of low bits to drop from each color channel, from 0
+    /// (lossless) to 7.
+    pub lossiness: u8,
+    /// Whether to dither when `lossiness` is non-zero.
+    pub dither: bool,
+}
+
+#[wasm_bindgen(js_class = EncodeOptions)]
+impl WebEncodeOptions {
+    /// Creates lossless options.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// A decoded image with tightly packed, non-premultiplied RGBA pixels.
+#[wasm_bindgen]
+pub struct DecodedRgba {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl DecodedRgba {
+    /// Width of the image in pixels.
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image in pixels.
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// A copy of the RGBA pixel data.
+    #[wasm_bindgen(getter)]
+    pub fn pixels(&self) -> Uint8Array {
+        Uint8Array::from(self.pixels.as_slice())
+    }
+
+    /// Converts the pixels into an `ImageData` ready for `putImageData`.
+    #[wasm_bindgen(js_name = toImageData)]
+    pub fn to_image_data(&self) -> Result<ImageData, JsValue> {
+        ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(self.pixels.as_slice()),
+            self.width,
+            self.height,
+        )
+    }
+}
+
+/// Decodes QOIR bytes into RGBA pixels.
+#[wasm_bindgen]
+pub fn decode(bytes: &[u8]) -> Result<DecodedRgba, JsValue> {
+    let decoded = decode_rgba(bytes)?;
+    let image = &decoded.image;
+
+    Ok(DecodedRgba {
+        width: image.width,
+        height: image.height,
+        pixels: packed_pixels(image).into_owned(),
+    })
+}
+
+/// Decodes QOIR bytes straight into an `ImageData` ready for `putImageData`.
+///
+/// Unlike `decode(bytes).toImageData()`, the pixels are only copied once, into
+/// the JavaScript heap.
+#[wasm_bindgen(js_name = decodeToImageData)]
+pub fn decode_to_image_data(bytes: &[u8]) -> Result<ImageData, JsValue> {
+    let decoded = decode_rgba(bytes)?;
+    let image = &decoded.image;
+    ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(&packed_pixels(image)),
+        image.width,
+        image.height,
+    )
+}
+
+/// Encodes tightly packed, non-premultiplied RGBA pixels into QOIR bytes.
+#[wasm_bindgen]
+pub fn encode(
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    lossiness: u8,
+    dither: bool,
+) -> Result<Uint8Array, JsValue> {
+    let stride_in_bytes = width as usize * BYTES_PER_PIXEL;
+    if pixels.len() < stride_in_bytes * height as usize {
+        return Err(to_js_error(Error::InvalidParameter));
+    }
+
+    let image = Image {
+        pixels,
+        width,
+        height,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes,
+    };
+    let options = EncodeOptions {
+        lossiness,
+        dither,
+        ..Default::default()
+    };
+    let encoded = crate::encode_to_memory(image, options).map_err(to_js_error)?;
+
+    Ok(Uint8Array::from(encoded.data))
+}
+
+/// Encodes the contents of an `ImageData` into QOIR bytes.
+///
+/// `ImageData` holds non-premultiplied RGBA, which is what the pixels are
+/// encoded as. `options` may be omitted for lossless encoding.
+#[wasm_bindgen(js_name = encodeFromImageData)]
+pub fn encode_from_image_data(
+    image_data: &ImageData,
+    options: Option<WebEncodeOptions>,
+) -> Result<Uint8Array, JsValue> {
+    let options = options.unwrap_or_default();
+    encode_image_data(image_data, options.lossiness, options.dither)
+}
+
+/// Encodes the contents of an `ImageData` into QOIR bytes.
+#[wasm_bindgen(js_name = encodeImageData)]
+pub fn encode_image_data(
+    image_data: &ImageData,
+    lossiness: u8,
+    dither: bool,
+) -> Result<Uint8Array, JsValue> {
+    let Clamped(pixels) = image_data.data();
+    encode(
+        &pixels,
+        image_data.width(),
+        image_data.height(),
+        lossiness,
+        dither,
+    )
+}
@@ -0,0 +1,48 @@
+//! Minimal `malloc`/`free` for `wasm32-unknown-unknown`.
+//!
+//! There is no libc on that target, so the allocation calls made by `qoir.c`
+//! would otherwise become unresolved `env` imports. These forward to the Rust
+//! global allocator instead, storing the requested size in a small header so
+//! that `free` can rebuild the layout.
+
+use alloc::alloc::{Layout, alloc, dealloc};
+use core::{ffi::c_void, ptr};
+
+// Large enough to keep the returned pointer aligned for any C type.
+const HEADER_SIZE: usize = 16;
+
+fn layout_for(size: usize) -> Option<Layout> {
+    let total = size.checked_add(HEADER_SIZE)?;
+    Layout::from_size_align(total, HEADER_SIZE).ok()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn malloc(size: usize) -> *mut c_void {
+    let Some(layout) = layout_for(size) else {
+        return ptr::null_mut();
+    };
+
+    unsafe {
+        let base = alloc(layout);
+        if base.is_null() {
+            return ptr::null_mut();
+        }
+        (base as *mut usize).write(size);
+        base.add(HEADER_SIZE) as *mut c_void
+    }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn free(ptr: *mut c_void) {
+    if ptr.is_null() {
+        return;
+    }
+
+    unsafe {
+        let base = (ptr as *mut u8).sub(HEADER_SIZE);
+        let size = (base as *const usize).read();
+        // The layout was valid when the block was handed out by `malloc`.
+        let layout = Layout::from_size_align_unchecked(size + HEADER_SIZE, HEADER_SIZE);
+        dealloc(base, layout);
+    }
+}
@@ -0,0 +1,172 @@
+//! Helpers for showing QOIR images with Win32 GDI and Direct2D, enabled with
+//! the `windows` feature.
+//!
+//! Windows bitmaps are BGRA with each row padded to a multiple of 4 bytes,
+//! which is exactly what the decoder produces for `PixelFormat::BGRAPremul`,
+//! so the decoded pixels can be handed to `CreateDIBSection`,
+//! `StretchDIBits` or `ID2D1RenderTarget::CreateBitmap` without another pass.
+//! [`BitmapInfoHeader`] has the layout of a Win32 `BITMAPINFOHEADER` and
+//! describes the pixels as a top-down 32-bit bitmap.
+//!
+//! No Windows crates are needed, so the module builds on every platform.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use qoir_rs::windows::decode_dib;
+//!
+//! let data = std::fs::read("input.qoir").expect("Failed to read QOIR file");
+//! let dib = decode_dib(&data).expect("Failed to decode");
+//! // For example, with the `windows` crate:
+//! // SetDIBitsToDevice(hdc, 0, 0, width, height, 0, 0, 0, height,
+//! //     dib.pixels().as_ptr().cast(), (&dib.header as *const _).cast(), DIB_RGB_COLORS);
+//! println!("{}x{}", dib.header.width, -dib.header.height);
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::{DecodeOptions, DecodedImage, Error, Image, PixelFormat, decode_from_memory};
+
+/// The `BI_RGB` compression value: uncompressed pixels.
+const BI_RGB: u32 = 0;
+
+/// The size of a `BITMAPINFOHEADER` in bytes.
+pub const BITMAP_INFO_HEADER_LEN: usize = 40;
+
+/// A Win32 `BITMAPINFOHEADER`, describing a 32-bit top-down bitmap.
+///
+/// It is `#[repr(C)]` with the same fields as the Win32 structure, so a
+/// pointer to it can be passed wherever a `BITMAPINFO` with no color table
+/// is expected.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitmapInfoHeader {
+    /// `biSize`: the size of the structure, 40.
+    pub size: u32,
+    /// `biWidth`: the width in pixels.
+    pub width: i32,
+    /// `biHeight`: the height in pixels, negative for a top-down bitmap.
+    pub height: i32,
+    /// `biPlanes`: always 1.
+    pub planes: u16,
+    /// `biBitCount`: bits per pixel, 32.
+    pub bit_count: u16,
+    /// `biCompression`: `BI_RGB`.
+    pub compression: u32,
+    /// `biSizeImage`: the size of the pixels in bytes.
+    pub size_image: u32,
+    /// `biXPelsPerMeter`: the horizontal resolution, 0 if unknown.
+    pub x_pels_per_meter: i32,
+    /// `biYPelsPerMeter`: the vertical resolution, 0 if unknown.
+    pub y_pels_per_meter: i32,
+    /// `biClrUsed`: 0, as there is no color table.
+    pub clr_used: u32,
+    /// `biClrImportant`: 0.
+    pub clr_important: u32,
+}
+
+impl BitmapInfoHeader {
+    /// Describes `image` as a top-down 32-bit bitmap.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the header, or `Error::InvalidParameter` if the
+    /// image is not `BGRAPremul`, `BGRANonPremul` or `BGRX`, has padding
+    /// between rows, or is too large for a bitmap.
+    pub fn for_image(image: &Image) -> Result<Self, Error> {
+        let is_bgra = matches!(
+            image.pixel_format,
+            PixelFormat::BGRAPremul | PixelFormat::BGRANonPremul | PixelFormat::BGRX
+        );
+        if !is_bgra || image.stride_in_bytes != image.width as usize * 4 {
+            return Err(Error::InvalidParameter);
+        }
+        image.check_buffer()?;
+
+        let width = i32::try_from(image.width).map_err(|_| Error::InvalidParameter)?;
+        let height = i32::try_from(image.height).map_err(|_| Error::InvalidParameter)?;
+        let size_image = u32::try_from(image.stride_in_bytes * image.height as usize)
+            .map_err(|_| Error::InvalidParameter)?;
+        Ok(BitmapInfoHeader {
+            size: BITMAP_INFO_HEADER_LEN as u32,
+            width,
+            height: -height,
+            planes: 1,
+            bit_count: 32,
+            compression: BI_RGB,
+            size_image,
+            x_pels_per_meter: 0,
+            y_pels_per_meter: 0,
+            clr_used: 0,
+            clr_important: 0,
+        })
+    }
+
+    /// The header as little-endian bytes, as stored in `.bmp` files and
+    /// `CF_DIB` clipboard data.
+    pub fn to_bytes(&self) -> [u8; BITMAP_INFO_HEADER_LEN] {
+        let mut bytes = [0; BITMAP_INFO_HEADER_LEN];
+        let fields: [&[u8]; 11] = [
+            &self.size.to_le_bytes(),
+            &self.width.to_le_bytes(),
+            &self.height.to_le_bytes(),
+            &self.planes.to_le_bytes(),
+            &self.bit_count.to_le_bytes(),
+            &self.compression.to_le_bytes(),
+            &self.size_image.to_le_bytes(),
+            &self.x_pels_per_meter.to_le_bytes(),
+            &self.y_pels_per_meter.to_le_bytes(),
+            &self.clr_used.to_le_bytes(),
+            &self.clr_important.to_le_bytes(),
+        ];
+        let mut offset = 0;
+        for field in fields {
+            bytes[offset..offset + field.len()].copy_from_slice(field);
+            offset += field.len();
+        }
+        bytes
+    }
+}
+
+/// A decoded image ready to be shown as a Windows bitmap.
+#[derive(Clone)]
+pub struct Dib<'a> {
+    /// The decoded image, in `PixelFormat::BGRAPremul`.
+    pub image: DecodedImage<'a>,
+    /// The bitmap header describing `image`.
+    pub header: BitmapInfoHeader,
+}
+
+impl Dib<'_> {
+    /// The pixels, top row first, to pass along with `header`.
+    pub fn pixels(&self) -> &[u8] {
+        self.image.image.pixels
+    }
+
+    /// The header followed by the pixels, which is the `CF_DIB` clipboard
+    /// format.
+    pub fn to_packed(&self) -> Vec<u8> {
+        let mut packed = Vec::with_capacity(BITMAP_INFO_HEADER_LEN + self.pixels().len());
+        packed.extend_from_slice(&self.header.to_bytes());
+        packed.extend_from_slice(self.pixels());
+        packed
+    }
+}
+
+/// Decodes QOIR image data into premultiplied BGRA, the layout GDI's
+/// `AlphaBlend` and Direct2D's `DXGI_FORMAT_B8G8R8A8_UNORM` with
+/// `D2D1_ALPHA_MODE_PREMULTIPLIED` expect.
+///
+/// # Returns
+///
+/// A `Result` containing the `Dib` or an `Error` if decoding fails or the
+/// image is too large for a bitmap.
+pub fn decode_dib<'a>(data: &[u8]) -> Result<Dib<'a>, Error> {
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGRAPremul,
+        ..Default::default()
+    };
+    let image = decode_from_memory(data, options)?;
+    let header = BitmapInfoHeader::for_image(&image.image)?;
+    Ok(Dib { image, header })
+}
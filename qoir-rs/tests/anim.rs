@@ -0,0 +1,76 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::anim::{AnimationDecoder, AnimationEncoder};
+use qoir_rs::{DecodeOptions, EncodeOptions, Error, decode_from_memory};
+
+#[test]
+fn test_animation_round_trip() {
+    let first = read_test_file("at-mouquins.qoir");
+    let second = read_test_file("at-mouquins.lossy-flat-2.qoir");
+    let options = EncodeOptions {
+        exif: Some(b"exif".to_vec()),
+        ..Default::default()
+    };
+
+    let mut encoder = AnimationEncoder::new(options);
+    encoder.add_encoded_frame(&first, 40).unwrap();
+    let decoded = decode_from_memory(&second, DecodeOptions::default()).unwrap();
+    encoder.add_frame(decoded.image.clone(), 60).unwrap();
+    assert_eq!(encoder.frame_count(), 2);
+    let data = encoder.finish().expect("Failed to finish");
+
+    let decoder = AnimationDecoder::new(&data).expect("Failed to parse");
+    assert_eq!(decoder.frame_count(), 2);
+    assert_eq!(
+        (decoder.width(), decoder.height()),
+        (decoded.image.width, decoded.image.height)
+    );
+    assert_eq!(decoder.total_duration_ms(), 100);
+    assert_eq!(decoder.metadata().exif, Some(&b"exif"[..]));
+    assert_eq!(decoder.frames()[0].data, &first[..]);
+
+    let expected = decode_from_memory(&first, DecodeOptions::default()).unwrap();
+    let frame = decoder.decode_frame(0, DecodeOptions::default()).unwrap();
+    assert_eq!(frame.image.pixels, expected.image.pixels);
+    assert_eq!(frame.exif, Some(&b"exif"[..]));
+    let frame = decoder.decode_frame(1, DecodeOptions::default()).unwrap();
+    assert_eq!(frame.image.pixels, decoded.image.pixels);
+    assert!(matches!(
+        decoder.decode_frame(2, DecodeOptions::default()),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_animation_frames_must_match_in_size() {
+    let mut encoder = AnimationEncoder::new(EncodeOptions::default());
+    encoder
+        .add_encoded_frame(&read_test_file("ramp-64x64.rgba.qoir"), 100)
+        .unwrap();
+    assert!(matches!(
+        encoder.add_encoded_frame(&read_test_file("ramp-32x32.rgb.qoir"), 100),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        AnimationEncoder::new(EncodeOptions::default()).finish(),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_animation_invalid_data() {
+    let frame = read_test_file("ramp-64x64.rgba.qoir");
+    let mut encoder = AnimationEncoder::new(EncodeOptions::default());
+    encoder.add_encoded_frame(&frame, 100).unwrap();
+    let data = encoder.finish().unwrap();
+
+    assert!(AnimationDecoder::new(&data[..data.len() - 1]).is_err());
+    // A plain QOIR image is not an animation.
+    assert!(AnimationDecoder::new(&frame).is_err());
+    assert!(
+        AnimationEncoder::new(EncodeOptions::default())
+            .add_encoded_frame(b"not qoir", 100)
+            .is_err()
+    );
+}
@@ -0,0 +1,98 @@
+#![cfg(feature = "apple")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::apple::{
+    ALPHA_NONE_SKIP_FIRST, ALPHA_PREMULTIPLIED_FIRST, BYTE_ORDER_32_LITTLE,
+    BYTES_PER_ROW_ALIGNMENT, CgBitmap,
+};
+use qoir_rs::{DecodeOptions, Error, Image, PixelFormat, decode_from_memory};
+
+#[test]
+fn test_cg_bitmap_from_decoded() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGRAPremul,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&data, options).expect("Failed to decode");
+    let image = &decoded.image;
+
+    let bitmap = CgBitmap::from_image(image).expect("Failed to convert");
+    assert_eq!((bitmap.width, bitmap.height), (image.width, image.height));
+    assert_eq!(bitmap.bytes_per_row % BYTES_PER_ROW_ALIGNMENT, 0);
+    assert!(bitmap.bytes_per_row >= image.width as usize * 4);
+    assert_eq!(
+        bitmap.bitmap_info,
+        BYTE_ORDER_32_LITTLE | ALPHA_PREMULTIPLIED_FIRST
+    );
+    assert_eq!(
+        bitmap.pixels.len(),
+        bitmap.bytes_per_row * image.height as usize
+    );
+    let row_len = image.width as usize * 4;
+    for y in 0..image.height as usize {
+        assert_eq!(
+            bitmap.pixels[y * bitmap.bytes_per_row..][..row_len],
+            image.pixels[y * image.stride_in_bytes..][..row_len]
+        );
+    }
+
+    // Non-premultiplied input ends up the same.
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGBANonPremul,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&data, options).unwrap();
+    assert_eq!(CgBitmap::from_image(&decoded.image).unwrap(), bitmap);
+}
+
+#[test]
+fn test_cg_bitmap_formats() {
+    // One translucent red pixel and one opaque green one.
+    let pixels = [0xFF, 0x00, 0x00, 0x80, 0x00, 0xFF, 0x00, 0xFF];
+    let image = Image {
+        pixels: &pixels,
+        width: 2,
+        height: 1,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 8,
+    };
+    let bitmap = CgBitmap::from_image(&image).unwrap();
+    assert_eq!(bitmap.bytes_per_row, BYTES_PER_ROW_ALIGNMENT);
+    assert_eq!(
+        bitmap.pixels[..8],
+        [0x00, 0x00, 0x80, 0x80, 0x00, 0xFF, 0x00, 0xFF]
+    );
+    assert!(bitmap.pixels[8..].iter().all(|&b| b == 0));
+
+    let rgb = [0x10, 0x20, 0x30];
+    let opaque = Image {
+        pixels: &rgb,
+        width: 1,
+        height: 1,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: 3,
+    };
+    let bitmap = CgBitmap::from_image(&opaque).unwrap();
+    assert_eq!(
+        bitmap.bitmap_info,
+        BYTE_ORDER_32_LITTLE | ALPHA_NONE_SKIP_FIRST
+    );
+    assert_eq!(bitmap.pixels[..4], [0x30, 0x20, 0x10, 0xFF]);
+
+    let invalid = Image {
+        pixel_format: PixelFormat::Invalid,
+        ..image.clone()
+    };
+    assert!(matches!(
+        CgBitmap::from_image(&invalid),
+        Err(Error::InvalidParameter)
+    ));
+    let short = Image { height: 2, ..image };
+    assert!(matches!(
+        CgBitmap::from_image(&short),
+        Err(Error::InvalidParameter)
+    ));
+}
@@ -0,0 +1,34 @@
+#![cfg(feature = "bumpalo")]
+
+mod common;
+
+use common::read_test_file;
+use bumpalo::Bump;
+use qoir_rs::{DecodeOptions, Error, PixelFormat, decode_batch_into_arena, decode_from_memory};
+
+#[test]
+fn test_decode_batch_into_arena() {
+    let inputs = vec![
+        read_test_file("ramp-32x32.rgb.qoir"),
+        b"not a qoir file".to_vec(),
+        read_test_file("ramp-64x64.rgba.qoir"),
+    ];
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGRANonPremul,
+        ..Default::default()
+    };
+    let mut arena = Bump::new();
+    {
+        let images = decode_batch_into_arena(&inputs, options.clone(), &arena);
+        assert_eq!(images.len(), 3);
+        assert!(matches!(images[1], Err(Error::DecodingFailed(_))));
+        for index in [0, 2] {
+            let image = images[index].as_ref().expect("Failed to decode");
+            let expected = decode_from_memory(&inputs[index], options.clone()).unwrap();
+            assert_eq!(image.pixels, expected.image.pixels);
+            assert_eq!(image.pixel_format, PixelFormat::BGRANonPremul);
+        }
+    }
+    assert!(arena.allocated_bytes() >= (32 * 32 + 64 * 64) * 4);
+    arena.reset();
+}
@@ -0,0 +1,13 @@
+use qoir_rs::build_info;
+
+#[test]
+fn test_build_info() {
+    let info = build_info();
+    assert_eq!(info.crate_version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.c_backend, cfg!(feature = "c-backend"));
+    assert_eq!(info.rust_backend, cfg!(feature = "rust-backend"));
+    if !info.c_backend {
+        assert!(!info.simd_enabled && !info.large_luts && !info.avx2);
+        assert_eq!(info.c_library_rev, None);
+    }
+}
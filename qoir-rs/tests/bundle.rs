@@ -0,0 +1,81 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::bundle::{Bundle, BundleWriter};
+use qoir_rs::{DecodeOptions, Error, decode_from_memory};
+use std::fs;
+
+const TEST_OUTPUT_DIR: &str = "tests/output";
+
+fn write_bundle(name: &str, images: &[(&str, &[u8])]) -> String {
+    fs::create_dir_all(TEST_OUTPUT_DIR).expect("Failed to create output directory");
+    let path = format!("{}/{}", TEST_OUTPUT_DIR, name);
+    let mut writer = BundleWriter::new(fs::File::create(&path).unwrap()).unwrap();
+    for (name, data) in images {
+        writer.add(name, data).expect("Failed to add");
+    }
+    writer.finish().expect("Failed to finish");
+    path
+}
+
+#[test]
+fn test_bundle_round_trip() {
+    let ramp = read_test_file("ramp-32x32.rgb.qoir");
+    let hibiscus = read_test_file("hibiscus.regular.qoir");
+    let path = write_bundle(
+        "round_trip.qoirb",
+        &[("IMG_0041", &ramp), ("IMG_0042", &hibiscus)],
+    );
+
+    let bundle = Bundle::open(&path).expect("Failed to open");
+    assert_eq!(bundle.len(), 2);
+    let names: Vec<&str> = bundle.entries().iter().map(|e| e.name.as_str()).collect();
+    assert_eq!(names, ["IMG_0041", "IMG_0042"]);
+    assert!(bundle.contains("IMG_0042"));
+    assert!(!bundle.contains("IMG_0043"));
+
+    assert_eq!(bundle.get_data("IMG_0041").unwrap(), ramp);
+    let decoded = bundle.get("IMG_0042", DecodeOptions::default()).unwrap();
+    let expected = decode_from_memory(&hibiscus, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.image.pixels, expected.image.pixels);
+    assert!(matches!(
+        bundle.get("IMG_0043", DecodeOptions::default()),
+        Err(Error::FileNotFound)
+    ));
+
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn test_bundle_writer_rejects_bad_input() {
+    let ramp = read_test_file("ramp-32x32.rgb.qoir");
+    let mut writer = BundleWriter::new(Vec::new()).unwrap();
+    writer.add("ramp", &ramp).unwrap();
+    assert!(matches!(
+        writer.add("ramp", &ramp),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(writer.add("not qoir", b"not qoir").is_err());
+    assert_eq!(writer.len(), 1);
+}
+
+#[test]
+fn test_bundle_invalid_data() {
+    let ramp = read_test_file("ramp-32x32.rgb.qoir");
+    let path = write_bundle("truncated.qoirb", &[("ramp", &ramp)]);
+    let data = fs::read(&path).unwrap();
+    fs::write(&path, &data[..data.len() - 1]).unwrap();
+    assert!(Bundle::open(&path).is_err());
+
+    // A plain QOIR image is not a bundle.
+    fs::write(&path, &ramp).unwrap();
+    assert!(Bundle::open(&path).is_err());
+    assert!(matches!(
+        Bundle::open(format!("{}/missing.qoirb", TEST_OUTPUT_DIR)),
+        Err(Error::FileNotFound)
+    ));
+
+    let _ = fs::remove_file(path);
+}
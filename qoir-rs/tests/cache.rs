@@ -0,0 +1,91 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{DecodeCache, DecodeOptions, PixelFormat, content_key, decode_from_memory};
+use std::sync::Arc;
+
+#[test]
+fn test_decode_cache_hits_and_misses() {
+    let data = read_test_file("ramp-32x32.rgb.qoir");
+    let cache = DecodeCache::new(1 << 20);
+    let mut decodes = 0;
+    let mut load = || {
+        decodes += 1;
+        data.clone()
+    };
+
+    let first = cache
+        .get_or_decode("ramp", &mut load, DecodeOptions::default())
+        .unwrap();
+    let second = cache
+        .get_or_decode("ramp", &mut load, DecodeOptions::default())
+        .unwrap();
+    assert_eq!(decodes, 1);
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(cache.len(), 1);
+    assert_eq!(cache.size_bytes(), 32 * 32 * 4);
+
+    let expected = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    assert_eq!(first.pixels, expected.image.pixels);
+
+    assert!(cache.remove(&"ramp").is_some());
+    assert!(cache.get(&"ramp").is_none());
+    assert!(cache.is_empty());
+    assert_eq!(cache.size_bytes(), 0);
+}
+
+#[test]
+fn test_decode_cache_evicts_least_recently_used() {
+    let data = read_test_file("ramp-32x32.rgb.qoir");
+    let image_bytes = 32 * 32 * 4;
+    let cache = DecodeCache::new(2 * image_bytes);
+    let options = DecodeOptions::default;
+
+    cache.get_or_decode(1, || &data, options()).unwrap();
+    cache.get_or_decode(2, || &data, options()).unwrap();
+    // Using 1 makes 2 the least recently used.
+    assert!(cache.get(&1).is_some());
+    cache.get_or_decode(3, || &data, options()).unwrap();
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache.get(&1).is_some());
+    assert!(cache.get(&2).is_none());
+    assert!(cache.get(&3).is_some());
+    assert!(cache.size_bytes() <= cache.budget_bytes());
+
+    // An image larger than the whole budget is returned but not cached.
+    let small = DecodeCache::new(16);
+    let image = small.get_or_decode(1, || &data, options()).unwrap();
+    assert_eq!(image.width, 32);
+    assert!(small.is_empty());
+
+    cache.clear();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_decode_cache_by_content() {
+    let data = read_test_file("ramp-32x32.rgb.qoir");
+    let cache = DecodeCache::new(1 << 20);
+    let rgb = DecodeOptions {
+        pixel_format: PixelFormat::RGB,
+        ..Default::default()
+    };
+    assert_ne!(
+        content_key(&data, &rgb),
+        content_key(&data, &DecodeOptions::default())
+    );
+
+    let a = cache.get_or_decode_content(&data, rgb.clone()).unwrap();
+    let b = cache.get_or_decode_content(&data, rgb).unwrap();
+    assert!(Arc::ptr_eq(&a, &b));
+    assert_eq!(a.pixel_format, PixelFormat::RGB);
+    assert!(
+        cache
+            .get_or_decode_content(b"not qoir", DecodeOptions::default())
+            .is_err()
+    );
+    assert_eq!(cache.len(), 1);
+}
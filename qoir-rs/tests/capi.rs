@@ -0,0 +1,156 @@
+#![cfg(feature = "capi")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::capi::*;
+use std::{ffi::CStr, fs, ptr};
+
+fn empty_image() -> QoirRsImage {
+    QoirRsImage {
+        handle: ptr::null_mut(),
+        pixels: ptr::null(),
+        pixels_len: 0,
+        width: 0,
+        height: 0,
+        pixel_format: 0,
+        stride_in_bytes: 0,
+    }
+}
+
+fn empty_buffer() -> QoirRsBuffer {
+    QoirRsBuffer {
+        handle: ptr::null_mut(),
+        data: ptr::null(),
+        len: 0,
+    }
+}
+
+#[test]
+fn test_capi_decode_metadata() {
+    let data = read_test_file("at-mouquins.qoir");
+    let mut metadata = QoirRsMetadata::default();
+    let status = unsafe { qoir_rs_decode_metadata(data.as_ptr(), data.len(), &mut metadata) };
+
+    assert_eq!(status, QOIR_RS_STATUS_OK);
+    assert_eq!((metadata.width, metadata.height), (193, 256));
+    assert_eq!(metadata.pixel_format, 0x01);
+}
+
+#[test]
+fn test_capi_round_trip() {
+    let data = read_test_file("at-mouquins.qoir");
+    let mut image = empty_image();
+    let status = unsafe { qoir_rs_decode(data.as_ptr(), data.len(), 0x22, &mut image) };
+    assert_eq!(status, QOIR_RS_STATUS_OK);
+    assert!(!image.handle.is_null());
+    assert_eq!(image.pixels_len, 193 * 256 * 4);
+
+    let mut buffer = empty_buffer();
+    let status = unsafe {
+        qoir_rs_encode(
+            image.pixels,
+            image.pixels_len,
+            image.width,
+            image.height,
+            image.pixel_format,
+            image.stride_in_bytes,
+            ptr::null(),
+            &mut buffer,
+        )
+    };
+    assert_eq!(status, QOIR_RS_STATUS_OK);
+
+    let mut decoded = empty_image();
+    let status = unsafe { qoir_rs_decode(buffer.data, buffer.len, 0x22, &mut decoded) };
+    assert_eq!(status, QOIR_RS_STATUS_OK);
+    let original = unsafe { std::slice::from_raw_parts(image.pixels, image.pixels_len) };
+    let round_tripped = unsafe { std::slice::from_raw_parts(decoded.pixels, decoded.pixels_len) };
+    assert!(original == round_tripped, "Round trip changed pixels");
+
+    unsafe {
+        qoir_rs_free(image.handle);
+        qoir_rs_free(buffer.handle);
+        qoir_rs_free(decoded.handle);
+        qoir_rs_free(ptr::null_mut());
+    }
+}
+
+#[test]
+fn test_capi_file_round_trip() {
+    let pixels: Vec<u8> = (0..32 * 32 * 3).map(|i| (i % 256) as u8).collect();
+    let path = std::env::temp_dir().join("qoir_rs_capi_test.qoir");
+    let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+    let options = QoirRsEncodeOptions {
+        lossiness: 0,
+        dither: false,
+    };
+
+    let status = unsafe {
+        qoir_rs_encode_file(
+            pixels.as_ptr(),
+            pixels.len(),
+            32,
+            32,
+            0x31,
+            32 * 3,
+            &options,
+            c_path.as_ptr(),
+        )
+    };
+    assert_eq!(status, QOIR_RS_STATUS_OK);
+
+    let mut image = empty_image();
+    let status = unsafe { qoir_rs_decode_file(c_path.as_ptr(), 0x31, &mut image) };
+    assert_eq!(status, QOIR_RS_STATUS_OK);
+    let decoded = unsafe { std::slice::from_raw_parts(image.pixels, image.pixels_len) };
+    assert_eq!(decoded, &pixels[..]);
+
+    unsafe { qoir_rs_free(image.handle) };
+    let _ = fs::remove_file(path);
+}
+
+#[test]
+fn test_capi_invalid_arguments() {
+    let data = read_test_file("at-mouquins.qoir");
+    let mut image = empty_image();
+
+    let status = unsafe { qoir_rs_decode(ptr::null(), 10, 0x22, &mut image) };
+    assert_eq!(status, QOIR_RS_STATUS_INVALID_PARAMETER);
+
+    let status = unsafe { qoir_rs_decode(data.as_ptr(), data.len(), 0x22, ptr::null_mut()) };
+    assert_eq!(status, QOIR_RS_STATUS_INVALID_PARAMETER);
+
+    let status = unsafe { qoir_rs_decode(data.as_ptr(), data.len(), 0x42, &mut image) };
+    assert_eq!(status, QOIR_RS_STATUS_INVALID_PARAMETER);
+
+    let status = unsafe { qoir_rs_decode(data.as_ptr(), 10, 0x22, &mut image) };
+    assert_eq!(status, QOIR_RS_STATUS_DECODING_FAILED);
+    assert!(image.handle.is_null());
+
+    let missing = c"../data/does-not-exist.qoir";
+    let status = unsafe { qoir_rs_decode_file(missing.as_ptr(), 0x22, &mut image) };
+    assert_eq!(status, QOIR_RS_STATUS_FILE_NOT_FOUND);
+
+    // The stride is too small for the width.
+    let pixels = [0u8; 64];
+    let mut buffer = empty_buffer();
+    let status = unsafe {
+        qoir_rs_encode(
+            pixels.as_ptr(),
+            pixels.len(),
+            4,
+            4,
+            0x22,
+            8,
+            ptr::null(),
+            &mut buffer,
+        )
+    };
+    assert_eq!(status, QOIR_RS_STATUS_INVALID_PARAMETER);
+
+    let message = unsafe { CStr::from_ptr(qoir_rs_status_message(status)) };
+    assert_eq!(message.to_str().unwrap(), "invalid parameter");
+    let message = unsafe { CStr::from_ptr(qoir_rs_status_message(QOIR_RS_STATUS_INTERNAL_ERROR)) };
+    assert_eq!(message.to_str().unwrap(), "internal error");
+}
@@ -0,0 +1,13 @@
+//! Helpers shared by the integration tests.
+
+// Each test file is its own crate and uses only some of these.
+#![allow(dead_code)]
+
+use std::fs;
+
+pub const TEST_DATA_DIR: &str = "../data";
+
+pub fn read_test_file(name: &str) -> Vec<u8> {
+    let file_path = format!("{}/{}", TEST_DATA_DIR, name);
+    fs::read(&file_path).unwrap_or_else(|_| panic!("Failed to read {}", file_path))
+}
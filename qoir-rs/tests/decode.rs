@@ -1,7 +1,13 @@
-use qoir_rs::{decode, decode_from_memory, decode_from_reader, DecodeOptions};
+#![cfg(feature = "std")]
+
+use qoir_rs::{
+    decode, decode_all, decode_from_memory, decode_from_reader, decode_from_seek, DecodeLimits, DecodeOptions, Error,
+    encode_to_vec, EncodeOptions, Image, MetadataSelection, OwnedDecodedImage, PixelFormat,
+};
 use std::fs::{self, File};
-use std::io::BufReader;
+use std::io::{BufReader, Cursor};
 use std::path::Path;
+use std::time::Duration;
 
 const TEST_DATA_DIR: &str = "../data";
 const TEST_OUTPUT_DIR: &str = "tests/output";
@@ -76,6 +82,20 @@ fn test_decode_from_reader_valid_qoir() {
     }
 }
 
+#[test]
+fn test_decode_from_reader_collects_timings() {
+    let file_path = get_test_file_path("hibiscus.regular.qoir");
+    let options = DecodeOptions {
+        collect_timings: true,
+        ..Default::default()
+    };
+    let decoded = decode_from_reader(BufReader::new(File::open(&file_path).unwrap()), options)
+        .expect("Failed to decode");
+    let timings = decoded.timings.expect("Timings were not collected");
+    assert!(timings.io > Duration::ZERO);
+    assert!(timings.tile_decode > Duration::ZERO);
+}
+
 #[test]
 fn test_decode_from_memory_invalid_data() {
     let invalid_data: &[u8] = &[0, 1, 2, 3, 4, 5];
@@ -84,6 +104,28 @@ fn test_decode_from_memory_invalid_data() {
     assert!(result.is_err(), "Decoding invalid data should fail");
 }
 
+/// Found by the `decode_from_memory` fuzz target: the header claims a
+/// 4194320x1296 image, but only a few bytes of tiles follow.
+const FORGED_DIMENSIONS: &[u8] = &[
+    0x51, 0x4F, 0x49, 0x52, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x40, 0x01,
+    0x10, 0x05, 0x00, 0x00, 0x51, 0x50, 0x49, 0x58, 0x13, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x0F, 0x00, 0x00, 0x03, 0x4F, 0x86, 0x97, 0x8E, 0x88, 0x02, 0x00, 0xFF, 0xE5, 0x50, 0x8E, 0x88,
+    0x88, 0x8E, 0x88, 0x51, 0x45, 0x4E, 0x44, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
+#[test]
+fn test_decode_from_memory_forged_dimensions() {
+    let result = decode_from_memory(FORGED_DIMENSIONS, DecodeOptions::default());
+    assert!(result.is_err(), "Decoding forged dimensions should fail");
+
+    // Real tiles under the largest dimensions the header can hold.
+    let mut data = fs::read(get_test_file_path("ramp-64x64.rgba.qoir")).unwrap();
+    data[12..15].copy_from_slice(&[0xFF; 3]);
+    data[16..19].copy_from_slice(&[0xFF; 3]);
+    let result = decode_from_memory(&data, DecodeOptions::default());
+    assert!(result.is_err(), "Decoding forged dimensions should fail");
+}
+
 #[test]
 fn test_decode_from_path_non_existent_file() {
     ensure_output_dir(); // Ensure parent dir for path exists if needed by OS
@@ -92,3 +134,170 @@ fn test_decode_from_path_non_existent_file() {
     let result = decode(path, options);
     assert!(result.is_err(), "Decoding non-existent file should fail");
 }
+
+#[test]
+fn test_decode_into_owned() {
+    let file_path = get_test_file_path("at-mouquins.qoir");
+    let data = fs::read(&file_path).unwrap_or_else(|_| panic!("Failed to read {}", file_path));
+
+    let owned: OwnedDecodedImage = {
+        let decoded_image = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+        decoded_image.into_owned()
+    };
+    let decoded_image = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+
+    assert_eq!(owned.width, decoded_image.image.width);
+    assert_eq!(owned.height, decoded_image.image.height);
+    assert_eq!(owned.pixel_format, decoded_image.image.pixel_format);
+    assert_eq!(owned.pixels, decoded_image.image.pixels);
+    assert_eq!(owned.as_image().pixels, decoded_image.image.pixels);
+    assert_eq!(owned.icc_profile.as_deref(), decoded_image.icc_profile);
+}
+
+#[test]
+fn test_decode_limits() {
+    let file_path = get_test_file_path("hibiscus.regular.qoir");
+    let data = fs::read(&file_path).unwrap_or_else(|_| panic!("Failed to read {}", file_path));
+    let decode_with = |limits: DecodeLimits| {
+        decode_from_memory(&data, DecodeOptions { limits, ..Default::default() })
+    };
+
+    // hibiscus.regular.qoir is 312x442
+    let exceeded = [
+        DecodeLimits { max_pixels: Some(312 * 442 - 1), ..Default::default() },
+        DecodeLimits { max_dimension: Some(441), ..Default::default() },
+        DecodeLimits { max_memory: Some(312 * 442 * 4 - 1), ..Default::default() },
+    ];
+    for limits in exceeded {
+        assert!(
+            matches!(decode_with(limits), Err(Error::DecodingFailed(_))),
+            "{:?} should reject the image",
+            limits
+        );
+    }
+
+    let decoded = decode_with(DecodeLimits {
+        max_pixels: Some(312 * 442),
+        max_dimension: Some(442),
+        max_memory: Some(312 * 442 * 4),
+    })
+    .expect("Image within the limits should decode");
+    assert_eq!((decoded.image.width, decoded.image.height), (312, 442));
+}
+
+#[test]
+fn test_decode_io_error_keeps_source_and_path() {
+    // A directory opens on some platforms but can never be read as a file.
+    let result = decode("tests", DecodeOptions::default());
+    match result {
+        Err(Error::IoError { path, .. }) => assert_eq!(path.as_deref(), Some(Path::new("tests"))),
+        other => panic!("Expected an I/O error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_error_from_io_error() {
+    let denied = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied");
+    let error = Error::from(denied).with_path("locked.qoir");
+    match &error {
+        Error::IoError { source, path } => {
+            assert_eq!(source.kind(), std::io::ErrorKind::PermissionDenied);
+            assert_eq!(path.as_deref(), Some(Path::new("locked.qoir")));
+        }
+        other => panic!("Expected an I/O error, got {:?}", other),
+    }
+    assert_eq!(error.to_string(), "I/O error on locked.qoir: denied");
+    assert!(std::error::Error::source(&error).is_some());
+
+    let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+    assert!(matches!(Error::from(missing).with_path("missing.qoir"), Error::FileNotFound));
+}
+
+#[test]
+fn test_decode_all() {
+    let first = fs::read(get_test_file_path("ramp-64x64.rgba.qoir")).unwrap();
+    let second = fs::read(get_test_file_path("hibiscus.regular.qoir")).unwrap();
+    let stream = [first.as_slice(), &second, &first].concat();
+
+    let decoded: Vec<OwnedDecodedImage> = decode_all(stream.as_slice(), DecodeOptions::default())
+        .collect::<Result<_, _>>()
+        .expect("Failed to decode stream");
+    let expected = |data: &[u8]| decode_from_memory(data, DecodeOptions::default()).unwrap().into_owned();
+    assert_eq!(decoded, [expected(&first), expected(&second), expected(&first)]);
+
+    assert_eq!(decode_all(std::io::empty(), DecodeOptions::default()).count(), 0);
+}
+
+#[test]
+fn test_decode_all_stops_at_truncated_image() {
+    let data = fs::read(get_test_file_path("ramp-64x64.rgba.qoir")).unwrap();
+    let stream = [data.as_slice(), &data[..data.len() - 1]].concat();
+
+    let mut images = decode_all(stream.as_slice(), DecodeOptions::default());
+    assert!(images.next().unwrap().is_ok());
+    assert!(matches!(images.next(), Some(Err(Error::DecodingFailed(_)))));
+    assert!(images.next().is_none());
+
+    let garbage = b"not a QOIR image at all";
+    let mut images = decode_all(&garbage[..], DecodeOptions::default());
+    assert!(matches!(images.next(), Some(Err(Error::DecodingFailed(_)))));
+    assert!(images.next().is_none());
+}
+
+#[test]
+fn test_decode_from_seek() {
+    let data = fs::read(get_test_file_path("hibiscus.regular.qoir")).unwrap();
+    let expected = decode_from_memory(&data, DecodeOptions::default()).unwrap().into_owned();
+
+    // Trailing bytes after the `QEND` chunk are never read.
+    let padded = [data.as_slice(), b"trailing"].concat();
+    let mut cursor = Cursor::new(padded);
+    let decoded = decode_from_seek(&mut cursor, DecodeOptions::default()).expect("Failed to decode");
+    assert_eq!(decoded.into_owned(), expected);
+    assert_eq!(cursor.position(), data.len() as u64);
+
+}
+
+#[test]
+fn test_decode_from_seek_skips_metadata() {
+    let pixels = vec![0x80; 16 * 16 * 4];
+    let image = Image::from_raw(&pixels, 16, 16, PixelFormat::RGBANonPremul).unwrap();
+    let encode_options = EncodeOptions {
+        icc_profile: Some(b"icc".to_vec()),
+        exif: Some(b"exif".to_vec()),
+        custom_metadata: vec![("rating".to_string(), b"4".to_vec())],
+        ..Default::default()
+    };
+    let data = encode_to_vec(image, encode_options).unwrap();
+
+    let all = decode_from_seek(Cursor::new(&data), DecodeOptions::default()).unwrap();
+    assert_eq!(all.icc_profile, Some(&b"icc"[..]));
+    assert_eq!(all.exif, Some(&b"exif"[..]));
+    assert_eq!(all.custom_metadata.len(), 1);
+
+    let metadata = MetadataSelection { icc_profile: true, ..MetadataSelection::NONE };
+    let options = DecodeOptions { metadata, ..Default::default() };
+    for decoded in [
+        decode_from_seek(Cursor::new(&data), options.clone()).unwrap(),
+        decode_from_memory(&data, options).unwrap(),
+    ] {
+        assert_eq!(decoded.icc_profile, Some(&b"icc"[..]));
+        assert!(decoded.exif.is_none() && decoded.custom_metadata.is_empty());
+        assert_eq!(decoded.image.pixels, all.image.pixels);
+    }
+}
+
+#[test]
+fn test_decode_from_seek_checks_limits_before_pixels() {
+    let data = fs::read(get_test_file_path("ramp-64x64.rgba.qoir")).unwrap();
+    let options = DecodeOptions {
+        limits: DecodeLimits { max_dimension: Some(32), ..Default::default() },
+        ..Default::default()
+    };
+    let mut cursor = Cursor::new(&data);
+    assert!(matches!(decode_from_seek(&mut cursor, options), Err(Error::DecodingFailed(_))));
+    assert_eq!(cursor.position(), 20, "Read past the header");
+
+    let truncated = Cursor::new(&data[..data.len() - 1]);
+    assert!(matches!(decode_from_seek(truncated, DecodeOptions::default()), Err(Error::DecodingFailed(_))));
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::delta::{apply_delta, encode_delta};
+use qoir_rs::{DecodeOptions, EncodeOptions, Error, ImageBuf, PixelFormat, decode_from_memory};
+
+fn hibiscus() -> ImageBuf {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    ImageBuf {
+        pixels: decoded.image.pixels.to_vec(),
+        width: decoded.image.width,
+        height: decoded.image.height,
+        pixel_format: decoded.image.pixel_format,
+        stride_in_bytes: decoded.image.stride_in_bytes,
+    }
+}
+
+#[test]
+fn test_delta_round_trip() {
+    let prev = hibiscus();
+    let mut next = prev.clone();
+    // Change one pixel in the second tile row and one in the last tile.
+    let last = next.pixels.len() - 4;
+    next.pixels[70 * next.stride_in_bytes + 8] ^= 0xFF;
+    next.pixels[last] ^= 0xFF;
+
+    let delta = encode_delta(&prev.as_image(), &next.as_image(), EncodeOptions::default())
+        .expect("Failed to encode");
+    let full = qoir_rs::encode_to_memory(next.as_image(), EncodeOptions::default()).unwrap();
+    assert!(delta.len() < full.data.len() / 2);
+
+    let mut frame = prev.clone();
+    apply_delta(&mut frame, &delta).expect("Failed to apply");
+    assert_eq!(frame, next);
+
+    // The delta can be applied to a frame in another pixel format. The
+    // changed byte was the red channel of the third pixel.
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGR,
+        ..Default::default()
+    };
+    let data = qoir_rs::encode_to_memory(prev.as_image(), EncodeOptions::default()).unwrap();
+    let bgr = decode_from_memory(data.data, options).unwrap();
+    let mut frame = ImageBuf {
+        pixels: bgr.image.pixels.to_vec(),
+        width: bgr.image.width,
+        height: bgr.image.height,
+        pixel_format: PixelFormat::BGR,
+        stride_in_bytes: bgr.image.stride_in_bytes,
+    };
+    apply_delta(&mut frame, &delta).unwrap();
+    let changed = 70 * frame.stride_in_bytes + 2 * 3 + 2;
+    assert_eq!(
+        frame.pixels[changed],
+        next.pixels[70 * next.stride_in_bytes + 8]
+    );
+}
+
+#[test]
+fn test_delta_of_identical_frames() {
+    let prev = hibiscus();
+    let delta = encode_delta(&prev.as_image(), &prev.as_image(), EncodeOptions::default()).unwrap();
+    assert!(delta.len() < 64);
+
+    let mut frame = prev.clone();
+    apply_delta(&mut frame, &delta).unwrap();
+    assert_eq!(frame, prev);
+}
+
+#[test]
+fn test_delta_invalid_input() {
+    let prev = hibiscus();
+    let mut next = prev.clone();
+    next.pixels[0] ^= 0xFF;
+    let delta = encode_delta(&prev.as_image(), &next.as_image(), EncodeOptions::default()).unwrap();
+
+    let mut small = prev.clone();
+    small.width -= 1;
+    assert!(matches!(
+        encode_delta(
+            &small.as_image(),
+            &next.as_image(),
+            EncodeOptions::default()
+        ),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        apply_delta(&mut small, &delta),
+        Err(Error::InvalidParameter)
+    ));
+
+    // A truncated delta fails without changing the frame.
+    let mut frame = prev.clone();
+    assert!(apply_delta(&mut frame, &delta[..delta.len() - 1]).is_err());
+    assert_eq!(frame, prev);
+    assert!(apply_delta(&mut frame, b"not a delta").is_err());
+}
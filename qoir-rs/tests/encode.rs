@@ -1,6 +1,9 @@
+#![cfg(feature = "std")]
+
 use qoir_rs::{
     encode,
     encode_to_memory,
+    encode_to_vec,
     DecodeOptions,
     EncodeOptions,
     Image,
@@ -68,6 +71,19 @@ fn test_encode_to_memory_basic() {
     );
 }
 
+#[test]
+fn test_encode_to_vec() {
+    let image = create_dummy_image(64, 64, PixelFormat::RGBANonPremul);
+    let encoded = encode_to_memory(image.clone(), EncodeOptions::default()).unwrap();
+    let vec = encode_to_vec(image, EncodeOptions::default()).unwrap();
+    assert_eq!(vec, encoded.data);
+
+    // A buffer that is still shared is copied out.
+    let shared = encoded.clone();
+    assert_eq!(encoded.into_vec(), vec);
+    assert_eq!(shared.into_vec(), vec);
+}
+
 #[test]
 fn test_encode_to_path_basic() {
     ensure_output_dir();
@@ -0,0 +1,63 @@
+mod common;
+
+use common::read_test_file;
+use exif::{In, Reader, Tag, Value};
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, ExifBuilder, decode_from_memory, encode_to_vec,
+};
+
+#[test]
+fn test_exif_builder() {
+    let exif = ExifBuilder::new()
+        .orientation(6)
+        .date_time("2024:05:17 14:03:59")
+        .make("Acme")
+        .model("Render 3000")
+        .software("qoir-rs tests")
+        .build()
+        .expect("Failed to build EXIF");
+
+    let parsed = Reader::new()
+        .read_raw(exif.clone())
+        .expect("Failed to parse EXIF");
+    let orientation = parsed.get_field(Tag::Orientation, In::PRIMARY).unwrap();
+    assert_eq!(orientation.value.get_uint(0), Some(6));
+    let ascii = |tag| match &parsed.get_field(tag, In::PRIMARY).unwrap().value {
+        Value::Ascii(strings) => String::from_utf8(strings[0].clone()).unwrap(),
+        value => panic!("{:?} is not ASCII", value),
+    };
+    assert_eq!(ascii(Tag::Make), "Acme");
+    assert_eq!(ascii(Tag::Model), "Render 3000");
+    assert_eq!(ascii(Tag::Software), "qoir-rs tests");
+    assert_eq!(ascii(Tag::DateTime), "2024:05:17 14:03:59");
+    assert_eq!(ascii(Tag::DateTimeOriginal), "2024:05:17 14:03:59");
+
+    // The blob is stored as the image's EXIF chunk unchanged.
+    let decoded = decode_from_memory(
+        &read_test_file("ramp-64x64.rgba.qoir"),
+        DecodeOptions::default(),
+    )
+    .expect("Failed to decode");
+    let options = EncodeOptions {
+        exif: Some(exif.clone()),
+        ..Default::default()
+    };
+    let encoded = encode_to_vec(decoded.image.clone(), options).expect("Failed to encode");
+    let decoded = decode_from_memory(&encoded, DecodeOptions::default()).expect("Failed to decode");
+    assert_eq!(decoded.exif, Some(&exif[..]));
+}
+
+#[test]
+fn test_exif_builder_rejects_invalid_tags() {
+    let invalid = |builder: ExifBuilder| matches!(builder.build(), Err(Error::InvalidParameter));
+    assert!(invalid(ExifBuilder::new().orientation(0)));
+    assert!(invalid(ExifBuilder::new().orientation(9)));
+    assert!(invalid(ExifBuilder::new().date_time("2024-05-17T14:03:59")));
+    assert!(invalid(ExifBuilder::new().make("Caméra")));
+    assert!(invalid(ExifBuilder::new().software("a\0b")));
+
+    // Without any tags, the data still has the Exif directory and its version.
+    let exif = ExifBuilder::new().build().expect("Failed to build EXIF");
+    let parsed = Reader::new().read_raw(exif).expect("Failed to parse EXIF");
+    assert!(parsed.get_field(Tag::ExifVersion, In::PRIMARY).is_some());
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "gpu")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::rust_backend::gpu::{GpuDecoder, GpuImage};
+use qoir_rs::{DecodeOptions, PixelFormat, rust_backend};
+
+const QOIR_FILES: [&str; 6] = [
+    "at-mouquins.qoir",
+    "at-mouquins.lossy-flat-4.qoir",
+    "hibiscus.regular.qoir",
+    "ramp-64x64.rgba.qoir",
+    "ramp-100x50.rgba.qoir",
+    "ramp-32x32.rgb.qoir",
+];
+
+/// Returns a device on an adapter that can run the decoder, or `None` on
+/// machines without one, where the GPU tests are skipped.
+fn device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let instance = wgpu::Instance::default();
+    let adapter = pollster::block_on(instance.request_adapter(&Default::default())).ok()?;
+    if !GpuDecoder::is_supported(&adapter) {
+        return None;
+    }
+    pollster::block_on(adapter.request_device(&Default::default())).ok()
+}
+
+fn read_pixels(device: &wgpu::Device, queue: &wgpu::Queue, image: &GpuImage) -> Vec<u8> {
+    let row_len = image.width as usize * 4;
+    let padded_row_len = row_len.next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as usize);
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: (padded_row_len * image.height as usize) as u64,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = device.create_command_encoder(&Default::default());
+    encoder.copy_texture_to_buffer(
+        image.texture.as_image_copy(),
+        wgpu::TexelCopyBufferInfo {
+            buffer: &buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_len as u32),
+                rows_per_image: None,
+            },
+        },
+        image.texture.size(),
+    );
+    queue.submit([encoder.finish()]);
+
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::PollType::wait_indefinitely()).unwrap();
+    let mapped = buffer.slice(..).get_mapped_range();
+    mapped
+        .chunks(padded_row_len)
+        .flat_map(|row| &row[..row_len])
+        .copied()
+        .collect()
+}
+
+#[test]
+fn test_gpu_decode_matches_rust_backend() {
+    let Some((device, queue)) = device() else {
+        eprintln!("No supported GPU adapter, skipping");
+        return;
+    };
+    let decoder = GpuDecoder::new(&device, &queue);
+
+    for name in QOIR_FILES {
+        let data = read_test_file(name);
+        let image = decoder.decode(&data).expect("Failed to decode");
+        let options = DecodeOptions {
+            pixel_format: PixelFormat::RGBANonPremul,
+            ..Default::default()
+        };
+        let expected = rust_backend::decode_from_memory(&data, options).unwrap();
+        assert_eq!(
+            (image.width, image.height),
+            (expected.image.width, expected.image.height)
+        );
+        assert!(!image.premultiplied);
+        assert_eq!(
+            read_pixels(&device, &queue, &image),
+            expected.image.pixels,
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_gpu_decode_invalid_data() {
+    let Some((device, queue)) = device() else {
+        eprintln!("No supported GPU adapter, skipping");
+        return;
+    };
+    let decoder = GpuDecoder::new(&device, &queue);
+
+    let data = read_test_file("hibiscus.regular.qoir");
+    assert!(decoder.decode(&data[..data.len() / 2]).is_err());
+    assert!(decoder.decode(b"not qoir").is_err());
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "http")]
+
+use http_body_util::BodyExt;
+use qoir_rs::{EncodeOptions, Image, PixelFormat, decode_from_memory, encode_to_memory};
+
+fn encoded() -> qoir_rs::EncodedBuffer<'static> {
+    let pixels: Vec<u8> = (0..32 * 32 * 4).map(|i| (i % 256) as u8).collect();
+    let image = Image {
+        pixels: &pixels,
+        width: 32,
+        height: 32,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 32 * 4,
+    };
+    encode_to_memory(image, EncodeOptions::default()).expect("Failed to encode")
+}
+
+#[test]
+fn test_into_bytes_does_not_copy() {
+    let encoded = encoded();
+    let (ptr, len) = (encoded.data.as_ptr(), encoded.data.len());
+    let bytes = encoded.into_bytes();
+    assert_eq!((bytes.as_ptr(), bytes.len()), (ptr, len));
+    assert!(decode_from_memory(&bytes, Default::default()).is_ok());
+}
+
+#[test]
+fn test_http_response() {
+    let encoded = encoded();
+    let data = encoded.data.to_vec();
+    let response = encoded.into_http_response();
+    assert_eq!(response.status(), http::StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        qoir_rs::http::CONTENT_TYPE
+    );
+    assert_eq!(
+        response.headers()["content-length"],
+        data.len().to_string().as_str()
+    );
+    let body = pollster::block_on(response.into_body().collect()).unwrap();
+    assert_eq!(body.to_bytes(), data);
+}
+
+#[cfg(feature = "axum")]
+#[test]
+fn test_axum_into_response() {
+    use axum_core::response::IntoResponse;
+
+    let encoded = encoded();
+    let data = encoded.data.to_vec();
+    let response = encoded.into_response();
+    assert_eq!(
+        response.headers()["content-type"],
+        qoir_rs::http::CONTENT_TYPE
+    );
+    let body = pollster::block_on(response.into_body().collect()).unwrap();
+    assert_eq!(body.to_bytes(), data);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{PixelFormat, Rectangle, TileCodec, inspect};
+
+#[test]
+fn test_inspect_tiles() {
+    let data = read_test_file("ramp-100x50.rgba.qoir");
+    let layout = inspect(&data).expect("Failed to inspect");
+
+    assert_eq!((layout.width, layout.height), (100, 50));
+    assert_eq!(layout.pixel_format, PixelFormat::BGRANonPremul);
+    assert_eq!(layout.lossiness, 0);
+    assert_eq!(layout.tile_size, 64);
+    assert_eq!(layout.metadata_len, 0);
+
+    let rects: Vec<Rectangle> = layout.tiles.iter().map(|tile| tile.rect).collect();
+    assert_eq!(
+        rects,
+        [
+            Rectangle {
+                x0: 0,
+                y0: 0,
+                x1: 64,
+                y1: 50
+            },
+            Rectangle {
+                x0: 64,
+                y0: 0,
+                x1: 100,
+                y1: 50
+            },
+        ]
+    );
+
+    // Each tile's payload follows its 4-byte header, which records its length
+    // and codec.
+    for tile in &layout.tiles {
+        let header = &data[tile.offset - 4..tile.offset];
+        let word = u32::from_le_bytes(header.try_into().unwrap());
+        assert_eq!((word & 0x00FF_FFFF) as usize, tile.compressed_len);
+        assert!(tile.offset + tile.compressed_len <= data.len());
+    }
+}
+
+#[test]
+fn test_inspect_covers_image() {
+    let data = read_test_file("at-mouquins.qoir");
+    let layout = inspect(&data).unwrap();
+
+    assert_eq!(layout.tiles.len(), 4 * 4);
+    let area: i32 = layout
+        .tiles
+        .iter()
+        .map(|tile| (tile.rect.x1 - tile.rect.x0) * (tile.rect.y1 - tile.rect.y0))
+        .sum();
+    assert_eq!(area, 193 * 256);
+
+    // Tiles are stored back to back.
+    for pair in layout.tiles.windows(2) {
+        assert_eq!(pair[0].offset + pair[0].compressed_len + 4, pair[1].offset);
+    }
+    assert!(
+        layout
+            .tiles
+            .iter()
+            .all(|tile| tile.codec == TileCodec::Opcodes || tile.codec == TileCodec::Lz4Opcodes)
+    );
+}
+
+#[test]
+fn test_inspect_invalid_data() {
+    assert!(inspect(&[0u8; 10]).is_err());
+
+    let data = read_test_file("at-mouquins.qoir");
+    let layout = inspect(&data).unwrap();
+    let last = layout.tiles.last().unwrap();
+    assert!(inspect(&data[..last.offset]).is_err());
+}
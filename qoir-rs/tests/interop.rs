@@ -0,0 +1,94 @@
+#![cfg(feature = "rgb")]
+
+mod common;
+
+use common::read_test_file;
+use imgref::{Img, ImgVec};
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, Image, ImageBuf, PixelFormat, decode_from_memory,
+    encode_to_memory,
+};
+use rgb::alt::BGRA8;
+use rgb::{RGB8, RGBA8};
+
+#[test]
+fn test_imgref_round_trip() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let image = &decoded.image;
+
+    let img = image.as_imgref::<RGBA8>().expect("Failed to borrow");
+    assert_eq!(
+        (img.width(), img.height()),
+        (image.width as usize, image.height as usize)
+    );
+    assert_eq!(img.stride() * 4, image.stride_in_bytes);
+    assert_eq!(img.buf().as_ptr().cast::<u8>(), image.pixels.as_ptr());
+    let first = &image.pixels[..4];
+    assert_eq!(
+        img[(0usize, 0usize)],
+        RGBA8::new(first[0], first[1], first[2], first[3])
+    );
+
+    let back = Image::from_imgref(img);
+    assert_eq!(back.pixel_format, PixelFormat::RGBANonPremul);
+    assert_eq!(back.stride_in_bytes, image.stride_in_bytes);
+    let encoded = encode_to_memory(back, EncodeOptions::default()).expect("Failed to encode");
+    let round_trip = decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert_eq!(round_trip.image.pixels, image.pixels);
+
+    // Other pixel types need a conversion.
+    assert!(matches!(
+        image.as_imgref::<RGB8>(),
+        Err(Error::InvalidParameter)
+    ));
+    let rgb = image.to_imgvec::<RGB8>().expect("Failed to convert");
+    assert_eq!(rgb.buf()[0], RGB8::new(first[0], first[1], first[2]));
+    assert_eq!(rgb.buf().len(), img.width() * img.height());
+}
+
+#[test]
+fn test_imgvec_into_image_buf() {
+    let pixels = vec![
+        BGRA8 {
+            b: 1,
+            g: 2,
+            r: 3,
+            a: 4,
+        },
+        BGRA8 {
+            b: 5,
+            g: 6,
+            r: 7,
+            a: 8,
+        },
+        BGRA8::default(),
+    ];
+    let ptr = pixels.as_ptr().cast::<u8>();
+    // Two pixels wide with one pixel of padding.
+    let img = Img::new_stride(pixels, 2, 1, 3);
+    let buf = ImageBuf::from(img);
+    assert_eq!(buf.pixel_format, PixelFormat::BGRANonPremul);
+    assert_eq!((buf.width, buf.height, buf.stride_in_bytes), (2, 1, 12));
+    assert_eq!(buf.pixels.as_ptr(), ptr);
+    assert_eq!(buf.pixels[..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+
+    let img: ImgVec<BGRA8> = buf.clone().into_imgvec().unwrap();
+    assert_eq!((img.width(), img.stride()), (2, 3));
+    assert_eq!(
+        img.buf()[1],
+        BGRA8 {
+            b: 5,
+            g: 6,
+            r: 7,
+            a: 8
+        }
+    );
+
+    let converted: ImgVec<RGBA8> = buf.into_imgvec().unwrap();
+    assert_eq!((converted.width(), converted.stride()), (2, 2));
+    assert_eq!(
+        converted.buf(),
+        &[RGBA8::new(3, 2, 1, 4), RGBA8::new(7, 6, 5, 8)]
+    );
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, Error, Image, LinearImage, PixelFormat, ResizeFilter, ResizeMode,
+    decode_from_memory, linear_to_srgb, srgb_to_linear,
+};
+
+#[test]
+fn test_srgb_linear_round_trip() {
+    assert_eq!(srgb_to_linear(0), 0.0);
+    assert_eq!(srgb_to_linear(255), 1.0);
+    assert!((srgb_to_linear(128) - 0.2158).abs() < 0.0001);
+    for value in 0..=255 {
+        assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+    }
+    assert_eq!(linear_to_srgb(-1.0), 0);
+    assert_eq!(linear_to_srgb(2.0), 255);
+    assert_eq!(linear_to_srgb(f32::NAN), 0);
+}
+
+#[test]
+fn test_linear_image_round_trip() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    for pixel_format in [PixelFormat::RGBANonPremul, PixelFormat::BGR] {
+        let options = DecodeOptions {
+            pixel_format,
+            ..Default::default()
+        };
+        let decoded = decode_from_memory(&data, options).expect("Failed to decode");
+        let linear = decoded.image.to_linear_f32().expect("Failed to convert");
+        assert_eq!(
+            (linear.width, linear.height),
+            (decoded.image.width, decoded.image.height)
+        );
+        let srgb = linear.to_srgb_u8(pixel_format).expect("Failed to convert");
+        assert_eq!(srgb.pixels, decoded.image.pixels);
+    }
+
+    // Transparent pixels have no color once premultiplied.
+    let pixels = [0x80, 0x40, 0x20, 0x00, 0xFF, 0xFF, 0xFF, 0x80];
+    let image = Image {
+        pixels: &pixels,
+        width: 2,
+        height: 1,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 8,
+    };
+    let linear = image.to_linear_f32().unwrap();
+    assert_eq!(linear.pixels[0], [0.0; 4]);
+    assert!((linear.pixels[1][0] - 0.502).abs() < 0.001);
+    let premul = linear.to_srgb_u8(PixelFormat::RGBAPremul).unwrap();
+    assert_eq!(premul.pixels, [0, 0, 0, 0, 0x80, 0x80, 0x80, 0x80]);
+}
+
+#[test]
+fn test_linear_resize() {
+    // Black and white stripes average to a light grey in linear light, not
+    // the mid grey that averaging the encoded values gives.
+    let pixels = [0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF];
+    let image = Image {
+        pixels: &pixels,
+        width: 2,
+        height: 1,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: 6,
+    };
+    let gamma = image
+        .resize(1, 1, ResizeMode::Exact, ResizeFilter::Box)
+        .unwrap();
+    assert_eq!(gamma.pixels, [0x80; 3]);
+    let linear = image
+        .to_linear_f32()
+        .unwrap()
+        .resize(1, 1, ResizeMode::Exact, ResizeFilter::Box)
+        .unwrap();
+    assert_eq!(
+        linear.to_srgb_u8(PixelFormat::RGB).unwrap().pixels,
+        [188; 3]
+    );
+
+    let fitted = image
+        .to_linear_f32()
+        .unwrap()
+        .resize(10, 10, ResizeMode::Fit, ResizeFilter::Lanczos3)
+        .unwrap();
+    assert_eq!((fitted.width, fitted.height), (10, 5));
+    assert_eq!(fitted.pixels.len(), 50);
+
+    let empty = LinearImage {
+        pixels: vec![[0.0; 4]; 3],
+        width: 2,
+        height: 2,
+    };
+    assert!(matches!(
+        empty.resize(1, 1, ResizeMode::Exact, ResizeFilter::Box),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        empty.to_srgb_u8(PixelFormat::RGB),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(
+        image
+            .to_linear_f32()
+            .unwrap()
+            .resize(0, 1, ResizeMode::Fit, ResizeFilter::Box)
+            .is_err()
+    );
+}
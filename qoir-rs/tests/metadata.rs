@@ -0,0 +1,140 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, MetadataChange, MetadataEdit, QoirMetadata,
+    decode_from_memory, read_custom_metadata, read_metadata, rewrite_metadata,
+};
+
+fn with_metadata(data: &[u8], options: EncodeOptions) -> Vec<u8> {
+    let decoded = decode_from_memory(data, DecodeOptions::default()).expect("Failed to decode");
+    qoir_rs::encode_to_memory(decoded.image.clone(), options)
+        .expect("Failed to encode")
+        .data
+        .to_vec()
+}
+
+#[test]
+fn test_read_metadata() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    assert_eq!(read_metadata(&data).unwrap(), QoirMetadata::default());
+
+    let data = with_metadata(
+        &data,
+        EncodeOptions {
+            exif: Some(b"exif".to_vec()),
+            xmp: Some(b"<xmp/>".to_vec()),
+            ..Default::default()
+        },
+    );
+    let metadata = read_metadata(&data).expect("Failed to read metadata");
+    assert_eq!(metadata.exif, Some(&b"exif"[..]));
+    assert_eq!(metadata.xmp, Some(&b"<xmp/>"[..]));
+    assert_eq!(metadata.icc_profile, None);
+}
+
+#[test]
+fn test_rewrite_metadata() {
+    let data = with_metadata(
+        &read_test_file("hibiscus.regular.qoir"),
+        EncodeOptions {
+            cicp_profile: Some(vec![1, 13, 0, 1]),
+            exif: Some(b"exif".to_vec()),
+            xmp: Some(b"<xmp/>".to_vec()),
+            ..Default::default()
+        },
+    );
+    let edit = MetadataEdit {
+        icc_profile: MetadataChange::Set(b"icc"),
+        exif: MetadataChange::Set(b"new exif"),
+        xmp: MetadataChange::Remove,
+        ..Default::default()
+    };
+    let rewritten = rewrite_metadata(&data, &edit).expect("Failed to rewrite metadata");
+
+    let original = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let decoded =
+        decode_from_memory(&rewritten, DecodeOptions::default()).expect("Failed to decode");
+    assert_eq!(decoded.image.pixels, original.image.pixels);
+    assert_eq!(decoded.cic_profile, Some(&[1, 13, 0, 1][..]));
+    assert_eq!(decoded.icc_profile, Some(&b"icc"[..]));
+    assert_eq!(decoded.exif, Some(&b"new exif"[..]));
+    assert_eq!(decoded.xmp, None);
+
+    // Keeping everything gives back the same bytes.
+    assert_eq!(
+        rewrite_metadata(&data, &MetadataEdit::default()).unwrap(),
+        data
+    );
+}
+
+#[test]
+fn test_rewrite_metadata_invalid_data() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    assert!(rewrite_metadata(&data[..data.len() - 1], &MetadataEdit::default()).is_err());
+    assert!(read_metadata(&data[..20]).is_err());
+}
+
+#[test]
+fn test_custom_metadata() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    assert!(decoded.custom_metadata.is_empty());
+    assert!(read_custom_metadata(&data).unwrap().is_empty());
+
+    let pairs = vec![
+        ("rating".to_string(), b"4".to_vec()),
+        ("pick".to_string(), Vec::new()),
+        ("rating".to_string(), b"5".to_vec()),
+        ("pipeline/κ".to_string(), vec![0, 255, 1]),
+    ];
+    let data = with_metadata(
+        &data,
+        EncodeOptions {
+            xmp: Some(b"<xmp/>".to_vec()),
+            custom_metadata: pairs.clone(),
+            ..Default::default()
+        },
+    );
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    assert_eq!(decoded.custom_metadata, pairs);
+    assert_eq!(decoded.xmp, Some(&b"<xmp/>"[..]));
+    assert_eq!(read_custom_metadata(&data).unwrap(), pairs);
+    assert_eq!(decoded.into_owned().custom_metadata, pairs);
+
+    // Rewriting the other metadata keeps the pairs.
+    let edit = MetadataEdit {
+        xmp: MetadataChange::Remove,
+        ..Default::default()
+    };
+    let rewritten = rewrite_metadata(&data, &edit).unwrap();
+    assert_eq!(read_custom_metadata(&rewritten).unwrap(), pairs);
+    assert_eq!(read_metadata(&rewritten).unwrap().xmp, None);
+}
+
+#[test]
+fn test_custom_metadata_invalid() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let options = EncodeOptions {
+        custom_metadata: vec![("k".repeat(70_000), Vec::new())],
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    assert!(matches!(
+        qoir_rs::encode_to_memory(decoded.image.clone(), options),
+        Err(Error::InvalidParameter)
+    ));
+
+    // A pair whose key runs past the end of the chunk.
+    let mut data = with_metadata(
+        &data,
+        EncodeOptions {
+            custom_metadata: vec![("key".to_string(), b"value".to_vec())],
+            ..Default::default()
+        },
+    );
+    let chunk = data.windows(4).position(|tag| tag == b"APPD").unwrap();
+    data[chunk + 12] = 0xFF;
+    assert!(read_custom_metadata(&data).is_err());
+    assert!(decode_from_memory(&data, DecodeOptions::default()).is_err());
+}
@@ -0,0 +1,128 @@
+#![cfg(feature = "metrics")]
+
+use metrics::{
+    Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+};
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Image, PixelFormat, decode_from_memory, encode_to_memory,
+    metrics as qoir_metrics,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Keeps every counter and histogram by its name and labels.
+#[derive(Default)]
+struct TestRecorder {
+    counters: Mutex<HashMap<String, Arc<AtomicU64>>>,
+    histograms: Mutex<HashMap<String, Arc<Samples>>>,
+}
+
+#[derive(Default)]
+struct Samples(Mutex<Vec<f64>>);
+
+impl HistogramFn for Samples {
+    fn record(&self, value: f64) {
+        self.0.lock().unwrap().push(value);
+    }
+}
+
+fn key_string(key: &Key) -> String {
+    let mut name = key.name().to_string();
+    for label in key.labels() {
+        name.push_str(&format!(",{}={}", label.key(), label.value()));
+    }
+    name
+}
+
+impl TestRecorder {
+    fn counter(&self, name: &str) -> u64 {
+        self.counters
+            .lock()
+            .unwrap()
+            .get(name)
+            .map_or(0, |counter| counter.load(Ordering::Relaxed))
+    }
+
+    fn samples(&self, name: &str) -> usize {
+        self.histograms
+            .lock()
+            .unwrap()
+            .get(name)
+            .map_or(0, |samples| samples.0.lock().unwrap().len())
+    }
+}
+
+impl Recorder for TestRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        let mut counters = self.counters.lock().unwrap();
+        Counter::from_arc(counters.entry(key_string(key)).or_default().clone())
+    }
+
+    fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+        Gauge::noop()
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        let mut histograms = self.histograms.lock().unwrap();
+        Histogram::from_arc(histograms.entry(key_string(key)).or_default().clone())
+    }
+}
+
+#[test]
+fn test_metrics_record_encode_and_decode() {
+    let recorder = TestRecorder::default();
+    let pixels = vec![0x80; 100 * 50 * 4];
+    let image = Image {
+        pixels: &pixels,
+        width: 100,
+        height: 50,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 100 * 4,
+    };
+
+    metrics::with_local_recorder(&recorder, || {
+        qoir_metrics::describe();
+
+        let encoded = encode_to_memory(image, EncodeOptions::default()).expect("Failed to encode");
+        let options = DecodeOptions {
+            pixel_format: PixelFormat::RGB,
+            ..Default::default()
+        };
+        decode_from_memory(encoded.data, options.clone()).expect("Failed to decode");
+        assert!(decode_from_memory(&encoded.data[..10], options).is_err());
+
+        assert_eq!(recorder.counter(qoir_metrics::IMAGES_ENCODED), 1);
+        assert_eq!(
+            recorder.counter(qoir_metrics::ENCODE_BYTES_IN),
+            pixels.len() as u64
+        );
+        assert_eq!(
+            recorder.counter(qoir_metrics::ENCODE_BYTES_OUT),
+            encoded.data.len() as u64
+        );
+        assert_eq!(recorder.samples(qoir_metrics::ENCODE_DURATION), 1);
+
+        assert_eq!(recorder.counter(qoir_metrics::IMAGES_DECODED), 1);
+        assert_eq!(
+            recorder.counter(qoir_metrics::DECODE_BYTES_IN),
+            encoded.data.len() as u64 + 10
+        );
+        assert_eq!(
+            recorder.counter(qoir_metrics::DECODE_BYTES_OUT),
+            100 * 50 * 3
+        );
+        assert_eq!(recorder.samples(qoir_metrics::DECODE_DURATION), 2);
+        assert_eq!(
+            recorder.counter(&format!(
+                "{},error=decoding_failed",
+                qoir_metrics::DECODE_FAILURES
+            )),
+            1
+        );
+    });
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "object-store")]
+
+use object_store::{ObjectStore, PutPayload, memory::InMemory, path::Path};
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, Image, PixelFormat, Rectangle, decode_from_memory,
+    encode_to_vec, object_store::decode_from_object_store,
+};
+
+fn store_with_image() -> (InMemory, Path, Vec<u8>) {
+    let (width, height) = (100, 200);
+    let pixels: Vec<u8> = (0..width * height * 4)
+        .map(|i| (i * 7 % 251) as u8)
+        .collect();
+    let image = Image::from_raw(&pixels, width, height, PixelFormat::RGBANonPremul).unwrap();
+    let options = EncodeOptions {
+        embed_checksum: true,
+        ..Default::default()
+    };
+    let data = encode_to_vec(image, options).expect("Failed to encode");
+
+    let store = InMemory::new();
+    let location = Path::from("archive/image.qoir");
+    pollster::block_on(store.put(&location, PutPayload::from(data.clone()))).unwrap();
+    (store, location, data)
+}
+
+#[test]
+fn test_decode_from_object_store() {
+    let (store, location, data) = store_with_image();
+    let decoded = pollster::block_on(decode_from_object_store(
+        &store,
+        &location,
+        DecodeOptions::default(),
+    ))
+    .expect("Failed to decode");
+    let expected = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.image.pixels, expected.image.pixels);
+
+    let missing = Path::from("archive/missing.qoir");
+    let result = pollster::block_on(decode_from_object_store(
+        &store,
+        &missing,
+        DecodeOptions::default(),
+    ));
+    assert!(matches!(result, Err(Error::FileNotFound)));
+}
+
+#[test]
+fn test_decode_from_object_store_clipped() {
+    let (store, location, data) = store_with_image();
+    let options = DecodeOptions {
+        src_clip_rect: Some(Rectangle {
+            x0: 10,
+            y0: 20,
+            x1: 90,
+            y1: 70,
+        }),
+        ..Default::default()
+    };
+    let decoded = pollster::block_on(decode_from_object_store(&store, &location, options.clone()))
+        .expect("Failed to decode");
+    let expected = decode_from_memory(&data, options).unwrap();
+
+    // Only the pixels inside the clip rectangle are decoded.
+    let stride = decoded.image.stride_in_bytes;
+    for y in 20..70 {
+        let row = y * stride + 10 * 4..y * stride + 90 * 4;
+        assert_eq!(
+            decoded.image.pixels[row.clone()],
+            expected.image.pixels[row]
+        );
+    }
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, Error, Image, PixelFormat, ResizeFilter, ResizeMode, decode_from_memory,
+    hamming_distance, phash,
+};
+
+fn hash_file(name: &str, pixel_format: PixelFormat) -> u64 {
+    let options = DecodeOptions {
+        pixel_format,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&read_test_file(name), options).expect("Failed to decode");
+    phash(&decoded.image).expect("Failed to hash")
+}
+
+#[test]
+fn test_phash_is_stable_across_formats_and_compression() {
+    let hash = hash_file("at-mouquins.qoir", PixelFormat::RGBANonPremul);
+    assert_eq!(hash_file("at-mouquins.qoir", PixelFormat::BGR), hash);
+    let lossy = hash_file("at-mouquins.lossy-naive-dither-6.qoir", PixelFormat::RGB);
+    assert!(hamming_distance(hash, lossy) <= 2);
+
+    let other = hash_file("hibiscus.regular.qoir", PixelFormat::RGBANonPremul);
+    assert!(hamming_distance(hash, other) > 10);
+}
+
+#[test]
+fn test_phash_of_resized_and_cropped_views() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let image = &decoded.image;
+    let hash = phash(image).unwrap();
+
+    let small = image
+        .resize(
+            image.width / 3,
+            image.height / 3,
+            ResizeMode::Exact,
+            ResizeFilter::Bilinear,
+        )
+        .unwrap();
+    assert!(hamming_distance(hash, phash(&small.as_image()).unwrap()) <= 6);
+
+    // The stride is honoured, so a view of the left half hashes like a copy.
+    let half = Image {
+        width: image.width / 2,
+        ..image.clone()
+    };
+    let copy = half
+        .resize(
+            half.width,
+            half.height,
+            ResizeMode::Exact,
+            ResizeFilter::Box,
+        )
+        .unwrap();
+    assert_eq!(phash(&half).unwrap(), phash(&copy.as_image()).unwrap());
+}
+
+#[test]
+fn test_phash_invalid_input() {
+    let pixels = [0u8; 16];
+    let image = Image {
+        pixels: &pixels,
+        width: 4,
+        height: 4,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 16,
+    };
+    assert!(matches!(phash(&image), Err(Error::InvalidParameter)));
+    let empty = Image {
+        width: 0,
+        height: 0,
+        ..image.clone()
+    };
+    assert_eq!(phash(&empty).unwrap(), 0);
+    assert_eq!(hamming_distance(0, u64::MAX), 64);
+}
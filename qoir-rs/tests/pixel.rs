@@ -0,0 +1,131 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{DecodeOptions, Error, Image, PixelFormat, decode_from_memory};
+
+fn image_with(
+    pixels: &[u8],
+    width: u32,
+    pixel_format: PixelFormat,
+    stride_in_bytes: usize,
+) -> Image<'_> {
+    Image {
+        pixels,
+        width,
+        height: (pixels.len() / stride_in_bytes) as u32,
+        pixel_format,
+        stride_in_bytes,
+    }
+}
+
+#[test]
+fn test_to_pixel_format_reorders_channels() {
+    let rgba = [10, 20, 30, 40, 50, 60, 70, 80];
+    let image = image_with(&rgba, 2, PixelFormat::RGBANonPremul, 8);
+
+    assert_eq!(
+        image.to_pixel_format(PixelFormat::BGRANonPremul).unwrap(),
+        [30, 20, 10, 40, 70, 60, 50, 80]
+    );
+    assert_eq!(
+        image.to_pixel_format(PixelFormat::RGB).unwrap(),
+        [10, 20, 30, 50, 60, 70]
+    );
+    assert_eq!(
+        image.to_pixel_format(PixelFormat::BGRX).unwrap(),
+        [30, 20, 10, 0xFF, 70, 60, 50, 0xFF]
+    );
+}
+
+#[test]
+fn test_to_pixel_format_premultiplies() {
+    let rgba = [255, 128, 0, 128];
+    let image = image_with(&rgba, 1, PixelFormat::RGBANonPremul, 4);
+    let premul = image.to_pixel_format(PixelFormat::RGBAPremul).unwrap();
+    assert_eq!(premul, [128, 64, 0, 128]);
+
+    let image = image_with(&premul, 1, PixelFormat::RGBAPremul, 4);
+    let unpremul = image.to_pixel_format(PixelFormat::RGBANonPremul).unwrap();
+    assert_eq!(unpremul, [255, 128, 0, 128]);
+}
+
+#[test]
+fn test_to_pixel_format_treats_x_as_opaque() {
+    let bgrx = [1, 2, 3, 0];
+    let image = image_with(&bgrx, 1, PixelFormat::BGRX, 4);
+    assert_eq!(
+        image.to_pixel_format(PixelFormat::RGBAPremul).unwrap(),
+        [3, 2, 1, 0xFF]
+    );
+}
+
+#[test]
+fn test_to_pixel_format_skips_stride_padding() {
+    let rgb = [1, 2, 3, 0xAA, 4, 5, 6, 0xAA];
+    let image = image_with(&rgb, 1, PixelFormat::RGB, 4);
+    assert_eq!(
+        image.to_pixel_format(PixelFormat::BGR).unwrap(),
+        [3, 2, 1, 6, 5, 4]
+    );
+}
+
+#[test]
+fn test_to_pixel_format_rejects_invalid() {
+    let rgba = [0; 8];
+    let image = image_with(&rgba, 2, PixelFormat::RGBANonPremul, 8);
+    assert!(matches!(
+        image.to_pixel_format(PixelFormat::Invalid),
+        Err(Error::InvalidParameter)
+    ));
+
+    let short = image_with(&rgba, 3, PixelFormat::RGBANonPremul, 8);
+    assert!(matches!(
+        short.to_pixel_format(PixelFormat::RGB),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_to_pixel_format_matches_decoding() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let rgba = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    for pixel_format in [
+        PixelFormat::BGRX,
+        PixelFormat::BGRAPremul,
+        PixelFormat::BGR,
+        PixelFormat::RGB,
+        PixelFormat::RGBX,
+        PixelFormat::RGBAPremul,
+    ] {
+        let options = DecodeOptions {
+            pixel_format,
+            ..Default::default()
+        };
+        let decoded = decode_from_memory(&data, options).expect("Failed to decode");
+        let converted = rgba.image.to_pixel_format(pixel_format).unwrap();
+        assert_eq!(converted, decoded.image.pixels, "{pixel_format:?}");
+    }
+}
+
+#[test]
+fn test_from_raw_checks_the_buffer() {
+    let pixels = [0; 24];
+    let image = Image::from_raw(&pixels, 4, 2, PixelFormat::RGB).unwrap();
+    assert_eq!(image.stride_in_bytes, 12);
+    assert!(matches!(
+        Image::from_raw(&pixels, 4, 3, PixelFormat::RGB),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        Image::from_raw(&pixels, 2, 2, PixelFormat::Invalid),
+        Err(Error::InvalidParameter)
+    ));
+
+    // The last row doesn't need its padding.
+    let image = Image::from_raw_with_stride(&pixels[..20], 2, 2, PixelFormat::BGRX, 12).unwrap();
+    assert_eq!(image.height, 2);
+    assert!(matches!(
+        Image::from_raw_with_stride(&pixels, 2, 2, PixelFormat::BGRX, 4),
+        Err(Error::InvalidParameter)
+    ));
+}
@@ -0,0 +1,60 @@
+#![cfg(all(feature = "rust-backend", feature = "std"))]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{BufferPool, DecodeOptions, EncodeOptions, Rectangle, decode_from_memory};
+
+#[test]
+fn test_pool_reuses_pixel_buffers() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let expected = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let pool = BufferPool::new(1 << 20);
+
+    let first = pool.decode(&data, DecodeOptions::default()).unwrap();
+    assert_eq!(first.pixels, expected.image.pixels);
+    let ptr = first.pixels.as_ptr();
+    drop(first);
+    assert_eq!(pool.size_bytes(), 64 * 64 * 4);
+
+    let second = pool.decode(&data, DecodeOptions::default()).unwrap();
+    assert_eq!(second.pixels.as_ptr(), ptr);
+    assert_eq!(second.pixels, expected.image.pixels);
+    assert_eq!(pool.size_bytes(), 0);
+    drop(second);
+
+    // A clipped decode into a reused buffer still zeroes the undrawn pixels.
+    let options = DecodeOptions {
+        src_clip_rect: Some(Rectangle {
+            x0: 0,
+            y0: 0,
+            x1: 32,
+            y1: 32,
+        }),
+        ..Default::default()
+    };
+    let clipped = pool.decode(&data, options.clone()).unwrap();
+    let expected = decode_from_memory(&data, options).unwrap();
+    assert_eq!(clipped.pixels, expected.image.pixels);
+}
+
+#[test]
+fn test_pool_encode_and_budget() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let pool = BufferPool::new(1 << 20);
+    let decoded = pool.decode(&data, DecodeOptions::default()).unwrap();
+    let encoded = pool
+        .encode(decoded.as_image(), EncodeOptions::default())
+        .unwrap();
+    let round_trip = decode_from_memory(&encoded, DecodeOptions::default()).unwrap();
+    assert_eq!(round_trip.image.pixels, decoded.pixels);
+    drop(encoded);
+    assert!(pool.size_bytes() > 0);
+    pool.clear();
+    assert_eq!(pool.size_bytes(), 0);
+
+    // Buffers larger than the budget are freed instead of pooled.
+    let small = BufferPool::new(1024);
+    drop(small.decode(&data, DecodeOptions::default()).unwrap());
+    assert_eq!(small.size_bytes(), 0);
+}
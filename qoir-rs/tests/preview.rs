@@ -0,0 +1,124 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::preview::{Rendition, SmartPreview, encode_smart_preview};
+use qoir_rs::{DecodeOptions, EncodeOptions, Error, decode_from_memory, read_metadata};
+
+fn hibiscus_preview(renditions: &[Rendition]) -> (Vec<u8>, Vec<u8>) {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let options = EncodeOptions {
+        icc_profile: Some(b"icc".to_vec()),
+        xmp: Some(b"<xmp/>".to_vec()),
+        ..Default::default()
+    };
+    let preview =
+        encode_smart_preview(decoded.image.clone(), renditions, options).expect("Failed to encode");
+    (decoded.image.pixels.to_vec(), preview)
+}
+
+#[test]
+fn test_smart_preview_renditions() {
+    // Given smallest first, stored largest first.
+    let renditions = [
+        Rendition {
+            max_size: Some(64),
+            lossiness: 3,
+            dither: true,
+        },
+        Rendition {
+            max_size: None,
+            lossiness: 0,
+            dither: false,
+        },
+        Rendition {
+            max_size: Some(200),
+            lossiness: 2,
+            dither: false,
+        },
+    ];
+    let (pixels, data) = hibiscus_preview(&renditions);
+    let preview = SmartPreview::new(&data).expect("Failed to parse");
+    let found = preview.renditions();
+    assert_eq!(found.len(), 3);
+    assert_eq!(found[0].lossiness, 0);
+    assert_eq!(found[1].width.max(found[1].height), 200);
+    assert_eq!(found[2].width.max(found[2].height), 64);
+    assert_eq!(found[1].lossiness, 2);
+
+    // The largest rendition is lossless and has all the metadata; the
+    // others keep the color profile.
+    let full = preview.decode(0, DecodeOptions::default()).unwrap();
+    assert_eq!(full.image.pixels, &pixels[..]);
+    assert_eq!(preview.metadata().unwrap().xmp, Some(&b"<xmp/>"[..]));
+    let small = read_metadata(preview.rendition_data(2).unwrap()).unwrap();
+    assert_eq!(small.icc_profile, Some(&b"icc"[..]));
+    assert_eq!(small.xmp, None);
+
+    let decoded = preview.decode(2, DecodeOptions::default()).unwrap();
+    assert_eq!(
+        (decoded.image.width, decoded.image.height),
+        (found[2].width, found[2].height)
+    );
+    assert!(matches!(
+        preview.decode(3, DecodeOptions::default()),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_smart_preview_best_for() {
+    let renditions = [
+        Rendition {
+            max_size: None,
+            lossiness: 0,
+            dither: false,
+        },
+        Rendition {
+            max_size: None,
+            lossiness: 4,
+            dither: false,
+        },
+        Rendition {
+            max_size: Some(100),
+            lossiness: 1,
+            dither: false,
+        },
+    ];
+    let (_, data) = hibiscus_preview(&renditions);
+    let preview = SmartPreview::new(&data).unwrap();
+    let found = preview.renditions();
+    assert_eq!(found[0].lossiness, 0);
+    assert_eq!(found[1].lossiness, 4);
+
+    assert_eq!(preview.best_for(1), 2);
+    assert_eq!(preview.best_for(found[2].width), 2);
+    // At full size, the smaller lossy encoding wins.
+    assert_eq!(preview.best_for(found[2].width + 1), 1);
+    assert_eq!(preview.best_for(u32::MAX), 0);
+}
+
+#[test]
+fn test_smart_preview_invalid_input() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    assert!(matches!(
+        encode_smart_preview(decoded.image.clone(), &[], EncodeOptions::default()),
+        Err(Error::InvalidParameter)
+    ));
+    let zero = Rendition {
+        max_size: Some(0),
+        ..Default::default()
+    };
+    assert!(matches!(
+        encode_smart_preview(decoded.image.clone(), &[zero], EncodeOptions::default()),
+        Err(Error::InvalidParameter)
+    ));
+
+    let (_, preview) = hibiscus_preview(&[Rendition::default()]);
+    assert!(SmartPreview::new(&data).is_err());
+    assert!(SmartPreview::new(&preview[..preview.len() / 2]).is_err());
+    assert!(SmartPreview::new(&preview).is_ok());
+}
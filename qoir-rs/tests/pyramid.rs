@@ -0,0 +1,108 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::pyramid::{PyramidReader, encode_pyramid};
+use qoir_rs::{DecodeOptions, EncodeOptions, Error, PixelFormat, Rectangle, decode_from_memory};
+
+fn hibiscus_pyramid() -> (Vec<u8>, Vec<u8>) {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let options = EncodeOptions {
+        xmp: Some(b"<xmp/>".to_vec()),
+        ..Default::default()
+    };
+    let pyramid = encode_pyramid(decoded.image.clone(), options).expect("Failed to encode");
+    (decoded.image.pixels.to_vec(), pyramid)
+}
+
+#[test]
+fn test_pyramid_levels() {
+    let (pixels, pyramid) = hibiscus_pyramid();
+    let reader = PyramidReader::new(&pyramid).expect("Failed to parse");
+    let (width, height) = (reader.width(), reader.height());
+
+    let levels = reader.levels();
+    assert!(levels.len() > 1);
+    for (n, level) in levels.iter().enumerate() {
+        assert_eq!(level.width, width.div_ceil(1 << n));
+        assert_eq!(level.height, height.div_ceil(1 << n));
+        let decoded = reader.decode_level(n, DecodeOptions::default()).unwrap();
+        assert_eq!(
+            (decoded.image.width, decoded.image.height),
+            (level.width, level.height)
+        );
+    }
+    let last = levels.last().unwrap();
+    assert!(last.width <= 64 && last.height <= 64);
+
+    // The full-size level is lossless and keeps the metadata.
+    let full = reader.decode_level(0, DecodeOptions::default()).unwrap();
+    assert_eq!(full.image.pixels, &pixels[..]);
+    assert_eq!(reader.metadata().unwrap().xmp, Some(&b"<xmp/>"[..]));
+    assert!(matches!(
+        reader.decode_level(levels.len(), DecodeOptions::default()),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_pyramid_region_at_zoom() {
+    let (pixels, pyramid) = hibiscus_pyramid();
+    let reader = PyramidReader::new(&pyramid).unwrap();
+    let width = reader.width() as usize;
+
+    assert_eq!(reader.level_for_zoom(1.0), 0);
+    assert_eq!(reader.level_for_zoom(0.6), 0);
+    assert_eq!(reader.level_for_zoom(0.5), 1);
+    assert_eq!(reader.level_for_zoom(0.3), 1);
+    assert_eq!(reader.level_for_zoom(1e-9), reader.level_count() - 1);
+
+    // At full resolution, the region matches the same pixels of the image.
+    let region = Rectangle {
+        x0: 10,
+        y0: 20,
+        x1: 50,
+        y1: 35,
+    };
+    let view = reader
+        .decode_region_at_zoom(region, 1.0, PixelFormat::RGBANonPremul)
+        .unwrap();
+    assert_eq!((view.width, view.height), (40, 15));
+    for y in 0..15 {
+        let src = ((20 + y) * width + 10) * 4;
+        assert_eq!(
+            &view.pixels[y * 160..(y + 1) * 160],
+            &pixels[src..src + 160]
+        );
+    }
+
+    let view = reader
+        .decode_region_at_zoom(region, 0.5, PixelFormat::RGB)
+        .unwrap();
+    assert_eq!((view.width, view.height), (20, 8));
+    assert_eq!(view.pixels.len(), 20 * 8 * 3);
+
+    let outside = Rectangle {
+        x0: -100,
+        y0: -100,
+        x1: -10,
+        y1: -10,
+    };
+    assert!(matches!(
+        reader.decode_region_at_zoom(outside, 1.0, PixelFormat::RGB),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        reader.decode_region_at_zoom(region, 0.0, PixelFormat::RGB),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_pyramid_invalid_data() {
+    let (_, pyramid) = hibiscus_pyramid();
+    assert!(PyramidReader::new(&pyramid[..pyramid.len() / 2]).is_err());
+    assert!(PyramidReader::new(&read_test_file("hibiscus.regular.qoir")).is_err());
+}
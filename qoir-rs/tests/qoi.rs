@@ -0,0 +1,161 @@
+#![cfg(feature = "qoi")]
+
+mod common;
+
+use common::read_test_file;
+use image::{ImageFormat, RgbaImage};
+use qoir_rs::{DecodeOptions, EncodeOptions, Error, Image, PixelFormat, decode_from_memory, qoi};
+use std::io::Cursor;
+
+/// An RGBA test image with some transparency, decoded from a QOIR file.
+fn test_image() -> (Vec<u8>, u32, u32) {
+    let decoded = decode_from_memory(
+        &read_test_file("hibiscus.regular.qoir"),
+        DecodeOptions::default(),
+    )
+    .expect("Failed to decode");
+    let mut pixels = decoded.image.pixels.to_vec();
+    for (i, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        pixel[3] = (i / 97 % 256) as u8;
+    }
+    (pixels, decoded.image.width, decoded.image.height)
+}
+
+#[test]
+fn test_qoi_round_trip() {
+    let (pixels, width, height) = test_image();
+    let image = Image {
+        pixels: &pixels,
+        width,
+        height,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: width as usize * 4,
+    };
+    let encoded = qoi::encode_to_memory(image).expect("Failed to encode");
+
+    let header = qoi::decode_header(&encoded).unwrap();
+    assert_eq!(
+        (header.width, header.height, header.channels),
+        (width, height, 4)
+    );
+
+    let decoded = qoi::decode_from_memory(&encoded, PixelFormat::RGBANonPremul).unwrap();
+    assert_eq!(decoded.pixels, pixels);
+}
+
+#[test]
+fn test_qoi_matches_image_crate() {
+    let (pixels, width, height) = test_image();
+
+    // Decode what the `image` crate encodes...
+    let reference = RgbaImage::from_raw(width, height, pixels.clone()).unwrap();
+    let mut reference_qoi = Cursor::new(Vec::new());
+    reference
+        .write_to(&mut reference_qoi, ImageFormat::Qoi)
+        .expect("Failed to encode with the image crate");
+    let decoded =
+        qoi::decode_from_memory(reference_qoi.get_ref(), PixelFormat::RGBANonPremul).unwrap();
+    assert_eq!(decoded.pixels, pixels);
+
+    // ...and the other way around.
+    let encoded = qoi::encode_to_memory(decoded.as_image()).unwrap();
+    let decoded = image::load_from_memory_with_format(&encoded, ImageFormat::Qoi).unwrap();
+    assert_eq!(decoded.to_rgba8().into_raw(), pixels);
+}
+
+#[test]
+fn test_qoi_rgb_and_pixel_formats() {
+    let pixels: Vec<u8> = (0..32 * 32 * 3).map(|i| (i % 256) as u8).collect();
+    let image = Image {
+        pixels: &pixels,
+        width: 32,
+        height: 32,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: 32 * 3,
+    };
+    let encoded = qoi::encode_to_memory(image).unwrap();
+    assert_eq!(
+        qoi::decode_header(&encoded).unwrap().pixel_format(),
+        PixelFormat::RGB
+    );
+
+    let bgr = qoi::decode_from_memory(&encoded, PixelFormat::BGR).unwrap();
+    for (bgr, rgb) in bgr.pixels.chunks_exact(3).zip(pixels.chunks_exact(3)) {
+        assert_eq!(bgr, [rgb[2], rgb[1], rgb[0]]);
+    }
+    let rgbx = qoi::decode_from_memory(&encoded, PixelFormat::RGBX).unwrap();
+    for (rgbx, rgb) in rgbx.pixels.chunks_exact(4).zip(pixels.chunks_exact(3)) {
+        assert_eq!(rgbx, [rgb[0], rgb[1], rgb[2], 0xFF]);
+    }
+}
+
+#[test]
+fn test_qoi_invalid_data() {
+    assert!(qoi::decode_from_memory(&[0u8; 10], PixelFormat::RGB).is_err());
+
+    let pixels: Vec<u8> = (0..16 * 16 * 4).map(|i| (i * 7 % 256) as u8).collect();
+    let image = Image {
+        pixels: &pixels,
+        width: 16,
+        height: 16,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 16 * 4,
+    };
+    let encoded = qoi::encode_to_memory(image.clone()).unwrap();
+    for len in 0..encoded.len() - 8 {
+        let result = qoi::decode_from_memory(&encoded[..len], PixelFormat::RGB);
+        assert!(
+            result.is_err(),
+            "Decoding {} truncated bytes should fail",
+            len
+        );
+    }
+
+    let image = Image {
+        height: 17,
+        ..image
+    };
+    assert!(matches!(
+        qoi::encode_to_memory(image),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_transcode_round_trip() {
+    let (pixels, width, height) = test_image();
+    let image = Image {
+        pixels: &pixels,
+        width,
+        height,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: width as usize * 4,
+    };
+    let qoi_data = qoi::encode_to_memory(image).unwrap();
+
+    let qoir = qoi::transcode_qoi_to_qoir(&qoi_data, EncodeOptions::default())
+        .expect("Failed to transcode to QOIR");
+    let decoded = decode_from_memory(qoir.data, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.image.pixels, &pixels[..]);
+
+    let transcoded = qoi::transcode_qoir_to_qoi(qoir.data).expect("Failed to transcode to QOI");
+    assert_eq!(transcoded, qoi_data);
+}
+
+#[test]
+fn test_transcode_opaque_to_rgb() {
+    let qoir = read_test_file("hibiscus.regular.qoir");
+    let qoi_data = qoi::transcode_qoir_to_qoi(&qoir).unwrap();
+    assert_eq!(qoi::decode_header(&qoi_data).unwrap().channels, 3);
+
+    let decoded = qoi::decode_from_memory(&qoi_data, PixelFormat::RGB).unwrap();
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGB,
+        ..Default::default()
+    };
+    let expected = decode_from_memory(&qoir, options).unwrap();
+    assert_eq!(decoded.pixels, expected.image.pixels);
+
+    assert!(qoi::transcode_qoir_to_qoi(&qoi_data).is_err());
+    assert!(qoi::transcode_qoi_to_qoir(&qoir, EncodeOptions::default()).is_err());
+}
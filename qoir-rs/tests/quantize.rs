@@ -0,0 +1,155 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, Image, PixelFormat, QuantizeOptions, decode_from_memory,
+    encode_to_memory, quantize,
+};
+use std::collections::HashSet;
+
+fn count_colors(pixels: &[u8], bpp: usize) -> usize {
+    pixels.chunks_exact(bpp).collect::<HashSet<_>>().len()
+}
+
+#[test]
+fn test_quantize_photo() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let image = &decoded.image;
+
+    for dither in [false, true] {
+        let options = QuantizeOptions {
+            max_colors: 16,
+            dither,
+        };
+        let quantized = quantize(image, &options).expect("Failed to quantize");
+        assert_eq!(quantized.pixel_format, image.pixel_format);
+        assert_eq!(
+            (quantized.width, quantized.height),
+            (image.width, image.height)
+        );
+        assert!(count_colors(&quantized.pixels, 4) <= 16);
+
+        let error: u64 = quantized
+            .pixels
+            .iter()
+            .zip(image.pixels)
+            .map(|(&a, &b)| a.abs_diff(b) as u64)
+            .sum();
+        assert!(error / (quantized.pixels.len() as u64) < 16);
+    }
+
+    // Encoding with quantization gives a smaller file with the same colors.
+    let options = EncodeOptions {
+        quantize: Some(QuantizeOptions {
+            max_colors: 16,
+            dither: false,
+        }),
+        ..Default::default()
+    };
+    let encoded = encode_to_memory(image.clone(), options).expect("Failed to encode");
+    let plain = encode_to_memory(image.clone(), EncodeOptions::default()).unwrap();
+    assert!(encoded.data.len() < plain.data.len() / 2);
+    let roundtrip = decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert!(count_colors(roundtrip.image.pixels, 4) <= 16);
+}
+
+#[test]
+fn test_quantize_synthetic_images() {
+    // A screenshot-like image: a flat background with a gradient bar and a
+    // padded stride.
+    let (width, height, stride) = (64, 16, 64 * 3 + 5);
+    let mut pixels = vec![0xEE; stride * height];
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = &mut pixels[y * stride + x * 3..][..3];
+            if y < 4 {
+                pixel.copy_from_slice(&[(x * 4) as u8, 0x40, 0x80]);
+            } else {
+                pixel.copy_from_slice(&[0xF0, 0xF0, 0xF0]);
+            }
+        }
+    }
+    let image = Image {
+        pixels: &pixels,
+        width: width as u32,
+        height: height as u32,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: stride,
+    };
+
+    // Images with few enough colors are only copied.
+    let options = QuantizeOptions {
+        max_colors: 65,
+        dither: true,
+    };
+    let copy = quantize(&image, &options).unwrap();
+    assert_eq!(copy.stride_in_bytes, width * 3);
+    for y in 0..height {
+        assert_eq!(
+            copy.pixels[y * width * 3..][..width * 3],
+            pixels[y * stride..][..width * 3]
+        );
+    }
+
+    // Dithering keeps the mean of the gradient, and the background stays
+    // flat.
+    let options = QuantizeOptions {
+        max_colors: 4,
+        dither: true,
+    };
+    let quantized = quantize(&image, &options).unwrap();
+    assert!(count_colors(&quantized.pixels, 3) <= 4);
+    let reds = |pixels: &[u8], stride: usize| -> u64 {
+        (0..width).map(|x| pixels[stride + x * 3] as u64).sum()
+    };
+    let mean = reds(&quantized.pixels, width * 3) as f64 / width as f64;
+    let expected = reds(&pixels, stride) as f64 / width as f64;
+    assert!((mean - expected).abs() < 8.0, "{mean} vs {expected}");
+    assert!(
+        quantized.pixels[width * 3 * 4..]
+            .chunks_exact(3)
+            .all(|pixel| pixel == [0xF0, 0xF0, 0xF0])
+    );
+}
+
+#[test]
+fn test_quantize_invalid_input() {
+    let pixels = [0u8; 12];
+    let image = Image {
+        pixels: &pixels,
+        width: 2,
+        height: 2,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 8,
+    };
+    let options = QuantizeOptions {
+        max_colors: 2,
+        dither: false,
+    };
+    assert!(matches!(
+        quantize(&image, &options),
+        Err(Error::InvalidParameter)
+    ));
+
+    let image = Image { height: 1, ..image };
+    let none = QuantizeOptions {
+        max_colors: 0,
+        ..options
+    };
+    assert!(matches!(
+        quantize(&image, &none),
+        Err(Error::InvalidParameter)
+    ));
+    let encode_options = EncodeOptions {
+        quantize: Some(none),
+        ..Default::default()
+    };
+    assert!(encode_to_memory(image.clone(), encode_options).is_err());
+
+    let invalid = Image {
+        pixel_format: PixelFormat::Invalid,
+        ..image
+    };
+    assert!(quantize(&invalid, &options).is_err());
+}
@@ -0,0 +1,298 @@
+#![cfg(feature = "raw")]
+
+mod common;
+
+use common::read_test_file;
+use exif::{Context, In, Reader, Tag, Value};
+use qoir_rs::raw::{
+    PreviewOptions, encode_raw_preview, encode_raw_preview_from_memory, extract_raw_preview,
+};
+use qoir_rs::{DecodeOptions, Error, PixelFormat, decode_from_memory};
+
+fn small_jpeg() -> Vec<u8> {
+    let thumbnail = image::RgbImage::from_pixel(16, 12, image::Rgb([200, 100, 50]));
+    let mut jpeg = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new(&mut jpeg)
+        .encode_image(&thumbnail)
+        .unwrap();
+    jpeg
+}
+
+/// The value of a TIFF entry.
+enum Entry {
+    /// A value of up to four bytes, stored in the entry.
+    Inline(u32),
+    /// A value stored after the directories, pointed at by the entry.
+    Data(Vec<u8>),
+    /// The offset of another directory.
+    Ifd(usize),
+    /// The offsets of other directories, stored after the directories.
+    Ifds(Vec<usize>),
+}
+
+/// A TIFF directory of `(tag, type, count, value)` entries.
+type Ifd = Vec<(u16, u16, u32, Entry)>;
+
+/// Builds a little-endian TIFF from unchained directories.
+fn tiff(ifds: &[Ifd]) -> Vec<u8> {
+    let mut ifd_offsets = Vec::new();
+    let mut offset = 8;
+    for entries in ifds {
+        ifd_offsets.push(offset as u32);
+        offset += 2 + 12 * entries.len() + 4;
+    }
+
+    let mut tiff = b"II*\0\x08\0\0\0".to_vec();
+    let mut data = Vec::new();
+    for entries in ifds {
+        tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, kind, count, value) in entries {
+            tiff.extend_from_slice(&tag.to_le_bytes());
+            tiff.extend_from_slice(&kind.to_le_bytes());
+            tiff.extend_from_slice(&count.to_le_bytes());
+            let mut store = |bytes: &[u8]| {
+                data.extend_from_slice(bytes);
+                (offset + data.len() - bytes.len()) as u32
+            };
+            let value = match value {
+                Entry::Inline(value) => *value,
+                Entry::Data(bytes) => store(bytes),
+                Entry::Ifd(n) => ifd_offsets[*n],
+                Entry::Ifds(ns) => {
+                    let offsets: Vec<u8> = ns
+                        .iter()
+                        .flat_map(|&n| ifd_offsets[n].to_le_bytes())
+                        .collect();
+                    store(&offsets)
+                }
+            };
+            tiff.extend_from_slice(&value.to_le_bytes());
+        }
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+    }
+    tiff.extend_from_slice(&data);
+    tiff
+}
+
+fn bmff_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut data = ((8 + payload.len()) as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(kind);
+    data.extend_from_slice(payload);
+    data
+}
+
+fn exif_field(exif: &[u8], tag: Tag) -> Option<Value> {
+    let exif = Reader::new().read_raw(exif.to_vec()).unwrap();
+    exif.get_field(tag, In::PRIMARY)
+        .map(|field| field.value.clone())
+}
+
+#[test]
+fn test_tiff_raw_preview() {
+    let preview = read_test_file("0.jpg");
+    let thumbnail = small_jpeg();
+    let exposure = [1u32.to_le_bytes(), 250u32.to_le_bytes()].concat();
+    // Like a NEF: a thumbnail in IFD0, the preview in a sub-directory and the
+    // raw data, which is not a JPEG, in another.
+    let raw = tiff(&[
+        vec![
+            (0x00FE, 4, 1, Entry::Inline(1)),
+            (0x010F, 2, 6, Entry::Data(b"Nikon\0".to_vec())),
+            (0x0112, 3, 1, Entry::Inline(6)),
+            (0x0201, 4, 1, Entry::Data(thumbnail.clone())),
+            (0x0202, 4, 1, Entry::Inline(thumbnail.len() as u32)),
+            (0x014A, 4, 2, Entry::Ifds(vec![1, 2])),
+            (0x8769, 4, 1, Entry::Ifd(3)),
+        ],
+        vec![
+            (0x0201, 4, 1, Entry::Data(preview.clone())),
+            (0x0202, 4, 1, Entry::Inline(preview.len() as u32)),
+        ],
+        vec![
+            (0x00FE, 4, 1, Entry::Inline(0)),
+            (0x0103, 3, 1, Entry::Inline(34713)),
+            (0x0111, 4, 1, Entry::Data(vec![0xFF, 0xD8, 0, 0])),
+            (0x0117, 4, 1, Entry::Inline(4)),
+        ],
+        vec![(0x829A, 5, 1, Entry::Data(exposure))],
+    ]);
+    assert_eq!(extract_raw_preview(&raw).unwrap(), preview);
+
+    let expected = image::load_from_memory(&preview).unwrap();
+    let encoded =
+        encode_raw_preview_from_memory(&raw, PreviewOptions::default()).expect("Failed to encode");
+    let decoded = decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert_eq!(
+        (decoded.image.width, decoded.image.height),
+        (expected.width(), expected.height())
+    );
+
+    let exif = decoded.exif.expect("Missing EXIF");
+    assert!(matches!(
+        exif_field(exif, Tag::Make),
+        Some(Value::Ascii(v)) if v == [b"Nikon"]
+    ));
+    assert!(matches!(
+        exif_field(exif, Tag::Orientation),
+        Some(Value::Short(v)) if v == [6]
+    ));
+    assert!(exif_field(exif, Tag::ExposureTime).is_some());
+    assert!(exif_field(exif, Tag(Context::Tiff, 0x00FE)).is_none());
+
+    let options = PreviewOptions {
+        max_size: Some(100),
+        keep_exif: false,
+        ..Default::default()
+    };
+    let encoded = encode_raw_preview_from_memory(&raw, options).unwrap();
+    let decoded = decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.image.width.max(decoded.image.height), 100);
+    assert!(decoded.exif.is_none());
+}
+
+#[test]
+fn test_cr3_raw_preview() {
+    let preview = small_jpeg();
+    let cmt1 = tiff(&[vec![(0x010F, 2, 6, Entry::Data(b"Canon\0".to_vec()))]]);
+    let exposure = [1u32.to_le_bytes(), 125u32.to_le_bytes()].concat();
+    let cmt2 = tiff(&[vec![(0x829A, 5, 1, Entry::Data(exposure))]]);
+    let metadata = [
+        &[
+            0x85, 0xc0, 0xb6, 0x87, 0x82, 0x0f, 0x11, 0xe0, 0x81, 0x11, 0xf4, 0xce, 0x46, 0x2b,
+            0x6a, 0x48,
+        ][..],
+        &bmff_box(b"CMT1", &cmt1),
+        &bmff_box(b"CMT2", &cmt2),
+    ]
+    .concat();
+    let prvw = [
+        &[0, 0, 0, 0, 0, 1, 0, 16, 0, 12, 0, 1][..],
+        &(preview.len() as u32).to_be_bytes(),
+        &preview,
+    ]
+    .concat();
+    let preview_box = [
+        &[
+            0xea, 0xf4, 0x2b, 0x5e, 0x1c, 0x98, 0x4b, 0x88, 0xb9, 0xfb, 0xb7, 0xdc, 0x40, 0x6e,
+            0x4d, 0x16,
+        ][..],
+        &[0; 8],
+        &bmff_box(b"PRVW", &prvw),
+    ]
+    .concat();
+    let raw = [
+        bmff_box(b"ftyp", b"crx \0\0\0\x01crx isom"),
+        bmff_box(b"moov", &bmff_box(b"uuid", &metadata)),
+        bmff_box(b"uuid", &preview_box),
+        bmff_box(b"mdat", &[0; 64]),
+    ]
+    .concat();
+    assert_eq!(extract_raw_preview(&raw).unwrap(), preview);
+
+    let encoded =
+        encode_raw_preview_from_memory(&raw, PreviewOptions::default()).expect("Failed to encode");
+    let decoded = decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert_eq!((decoded.image.width, decoded.image.height), (16, 12));
+    let exif = decoded.exif.expect("Missing EXIF");
+    assert!(matches!(
+        exif_field(exif, Tag::Make),
+        Some(Value::Ascii(v)) if v == [b"Canon"]
+    ));
+    assert!(matches!(
+        exif_field(exif, Tag::ExposureTime),
+        Some(Value::Rational(v)) if v[0].num == 1 && v[0].denom == 125
+    ));
+}
+
+#[test]
+fn test_raw_develop() {
+    // A 4x2 RGGB color filter array, red on the left and cyan on the right,
+    // with the red halved by an as-shot neutral of 0.5, 1, 1.
+    let samples: Vec<u8> = [500u16, 0, 0, 1000, 0, 0, 1000, 1000]
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect();
+    let neutral = [[1u32, 2], [1, 1], [1, 1]]
+        .iter()
+        .flatten()
+        .flat_map(|v| v.to_le_bytes())
+        .collect::<Vec<u8>>();
+    let cfa = |compression| {
+        vec![
+            (0x00FE, 4, 1, Entry::Inline(0)),
+            (0x0100, 3, 1, Entry::Inline(4)),
+            (0x0101, 3, 1, Entry::Inline(2)),
+            (0x0102, 3, 1, Entry::Inline(16)),
+            (0x0103, 3, 1, Entry::Inline(compression)),
+            (0x0106, 3, 1, Entry::Inline(32803)),
+            (0x0111, 4, 1, Entry::Data(samples.clone())),
+            (0x0117, 4, 1, Entry::Inline(samples.len() as u32)),
+            (0x828D, 3, 2, Entry::Inline(2 | 2 << 16)),
+            (0x828E, 1, 4, Entry::Inline(0x0201_0100)),
+            (0xC61D, 4, 1, Entry::Inline(1000)),
+            (0xC628, 5, 3, Entry::Data(neutral.clone())),
+        ]
+    };
+    let raw = tiff(&[
+        vec![
+            (0x010F, 2, 6, Entry::Data(b"Adobe\0".to_vec())),
+            (0x014A, 4, 1, Entry::Ifd(1)),
+        ],
+        cfa(1),
+    ]);
+    assert!(matches!(
+        extract_raw_preview(&raw),
+        Err(Error::DecodingFailed(_))
+    ));
+
+    let encoded =
+        encode_raw_preview_from_memory(&raw, PreviewOptions::default()).expect("Failed to encode");
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGBANonPremul,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(encoded.data, options).unwrap();
+    assert_eq!((decoded.image.width, decoded.image.height), (2, 1));
+    assert_eq!(
+        &decoded.image.pixels[..8],
+        [255, 0, 0, 255, 0, 255, 255, 255]
+    );
+    let exif = decoded.exif.expect("Missing EXIF");
+    assert!(matches!(
+        exif_field(exif, Tag::Make),
+        Some(Value::Ascii(v)) if v == [b"Adobe"]
+    ));
+
+    // Compressed sensor data is not developed.
+    let compressed = tiff(&[cfa(7)]);
+    assert!(matches!(
+        encode_raw_preview_from_memory(&compressed, PreviewOptions::default()),
+        Err(Error::DecodingFailed(_))
+    ));
+}
+
+#[test]
+fn test_raw_preview_errors() {
+    let jpeg_only = read_test_file("0.jpg");
+    assert!(matches!(
+        extract_raw_preview(&jpeg_only),
+        Err(Error::DecodingFailed(_))
+    ));
+    let no_preview = tiff(&[vec![(0x0112, 3, 1, Entry::Inline(1))]]);
+    assert!(matches!(
+        encode_raw_preview_from_memory(&no_preview, PreviewOptions::default()),
+        Err(Error::DecodingFailed(_))
+    ));
+    let options = PreviewOptions {
+        max_size: Some(0),
+        ..Default::default()
+    };
+    assert!(matches!(
+        encode_raw_preview_from_memory(&no_preview, options),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        encode_raw_preview("missing.NEF", PreviewOptions::default()),
+        Err(Error::FileNotFound)
+    ));
+}
@@ -0,0 +1,285 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, Image, PixelFormat, Rectangle, ResizeFilter, ResizeMode,
+    ThumbnailSpec, decode_from_memory, encode_to_vec, extract_thumbnail,
+};
+
+fn solid(pixel: &[u8], width: u32, height: u32) -> Vec<u8> {
+    pixel.repeat((width * height) as usize)
+}
+
+fn image_with(pixels: &[u8], width: u32, height: u32, pixel_format: PixelFormat) -> Image<'_> {
+    Image {
+        pixels,
+        width,
+        height,
+        pixel_format,
+        stride_in_bytes: width as usize * pixel_format.bytes_per_pixel(),
+    }
+}
+
+#[test]
+fn test_resize_modes() {
+    let pixels = solid(&[0, 0, 0, 0xFF], 40, 20);
+    let image = image_with(&pixels, 40, 20, PixelFormat::RGBANonPremul);
+
+    let cases = [
+        (ResizeMode::Exact, (10, 10), (10, 10)),
+        (ResizeMode::Fit, (10, 10), (10, 5)),
+        (ResizeMode::Fit, (100, 100), (100, 50)),
+        (ResizeMode::Fill, (10, 10), (10, 10)),
+    ];
+    for (mode, (width, height), expected) in cases {
+        let resized = image
+            .resize(width, height, mode, ResizeFilter::default())
+            .unwrap();
+        assert_eq!((resized.width, resized.height), expected, "{mode:?}");
+        assert_eq!(resized.stride_in_bytes, resized.width as usize * 4);
+        assert_eq!(
+            resized.pixels.len(),
+            resized.stride_in_bytes * resized.height as usize
+        );
+    }
+}
+
+#[test]
+fn test_resize_keeps_solid_colors() {
+    for (pixel, pixel_format) in [
+        (&[200, 100, 50, 0xFF][..], PixelFormat::RGBANonPremul),
+        (&[50, 100, 200, 0x80][..], PixelFormat::BGRANonPremul),
+        (&[10, 20, 30][..], PixelFormat::RGB),
+        (&[30, 20, 10, 0xFF][..], PixelFormat::BGRX),
+    ] {
+        let pixels = solid(pixel, 16, 16);
+        let image = image_with(&pixels, 16, 16, pixel_format);
+        for filter in [
+            ResizeFilter::Nearest,
+            ResizeFilter::Box,
+            ResizeFilter::Bilinear,
+            ResizeFilter::CatmullRom,
+            ResizeFilter::Lanczos3,
+        ] {
+            for (width, height) in [(5, 7), (33, 20)] {
+                let resized = image
+                    .resize(width, height, ResizeMode::Exact, filter)
+                    .unwrap();
+                assert_eq!(resized.pixel_format, pixel_format);
+                assert_eq!(
+                    resized.pixels,
+                    solid(pixel, width, height),
+                    "{pixel_format:?} {filter:?}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resize_ignores_transparent_colors() {
+    // Half the pixels are fully transparent red, which must not tint the
+    // opaque blue ones they are averaged with.
+    let pixels = [[0xFF, 0, 0, 0], [0, 0, 0xFF, 0xFF]].concat().repeat(8);
+    let image = image_with(&pixels, 4, 4, PixelFormat::RGBANonPremul);
+    let resized = image
+        .resize(1, 1, ResizeMode::Exact, ResizeFilter::Box)
+        .unwrap();
+    assert_eq!(resized.pixels, [0, 0, 0xFF, 0x80]);
+}
+
+#[test]
+fn test_resize_skips_stride_padding() {
+    let pixels = [1, 2, 3, 0xAA, 1, 2, 3, 0xAA];
+    let image = Image {
+        pixels: &pixels,
+        width: 1,
+        height: 2,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: 4,
+    };
+    let resized = image
+        .resize(2, 1, ResizeMode::Exact, ResizeFilter::Bilinear)
+        .unwrap();
+    assert_eq!(resized.pixels, [1, 2, 3, 1, 2, 3]);
+}
+
+#[test]
+fn test_resize_rejects_invalid() {
+    let pixels = [0; 16];
+    let image = image_with(&pixels, 2, 2, PixelFormat::RGBANonPremul);
+    assert!(matches!(
+        image.resize(0, 1, ResizeMode::Exact, ResizeFilter::default()),
+        Err(Error::InvalidParameter)
+    ));
+
+    let short = image_with(&pixels, 3, 2, PixelFormat::RGBANonPremul);
+    assert!(matches!(
+        short.resize(1, 1, ResizeMode::Exact, ResizeFilter::default()),
+        Err(Error::InvalidParameter)
+    ));
+
+    let invalid = image_with(&pixels, 2, 2, PixelFormat::Invalid);
+    assert!(matches!(
+        invalid.resize(1, 1, ResizeMode::Exact, ResizeFilter::default()),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_resize_decoded_image() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let half = decoded
+        .image
+        .resize(32, 32, ResizeMode::Fit, ResizeFilter::Box)
+        .unwrap();
+
+    // Box filtering by exactly half averages each 2x2 block.
+    let src = decoded.image.pixels;
+    let stride = decoded.image.stride_in_bytes;
+    for (y, x) in [(0, 0), (10, 20), (31, 31)] {
+        for c in 0..4 {
+            let at =
+                |dy: usize, dx: usize| src[(2 * y + dy) * stride + (2 * x + dx) * 4 + c] as u32;
+            let average = (at(0, 0) + at(0, 1) + at(1, 0) + at(1, 1)) as f32 / 4.0;
+            let resized = half.pixels[y * half.stride_in_bytes + x * 4 + c] as f32;
+            assert!((resized - average).abs() <= 1.0, "({x}, {y}) channel {c}");
+        }
+    }
+}
+
+#[test]
+fn test_decode_fit_within() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let fit = |fit_within| {
+        let options = DecodeOptions {
+            fit_within,
+            ..Default::default()
+        };
+        decode_from_memory(&data, options).expect("Failed to decode")
+    };
+
+    let full = fit(None);
+    let scaled = fit(Some((32, 16)));
+    assert_eq!((scaled.image.width, scaled.image.height), (16, 16));
+    let expected = full
+        .image
+        .resize(32, 16, ResizeMode::Fit, ResizeFilter::Lanczos3)
+        .unwrap();
+    assert_eq!(scaled.image.pixels, expected.pixels);
+
+    // Images are never scaled up.
+    let small = fit(Some((100, 100)));
+    assert_eq!((small.image.width, small.image.height), (64, 64));
+    assert_eq!(small.image.pixels, full.image.pixels);
+}
+
+#[test]
+fn test_decode_fit_within_uses_thumbnail() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let options = EncodeOptions {
+        embed_thumbnail: Some(ThumbnailSpec {
+            max_dim: 32,
+            lossiness: 0,
+        }),
+        exif: Some(b"II*\0".to_vec()),
+        ..Default::default()
+    };
+    let mut encoded = encode_to_vec(decoded.image.clone(), options).unwrap();
+    let fit = |data: &[u8], size| {
+        let options = DecodeOptions {
+            fit_within: Some(size),
+            ..Default::default()
+        };
+        decode_from_memory(data, options)
+    };
+
+    // A thumbnail as large as the output is scaled instead of the image, so
+    // corrupting the image's tiles doesn't matter.
+    let thumbnail = extract_thumbnail(&encoded).unwrap().unwrap();
+    let thumbnail = decode_from_memory(thumbnail, DecodeOptions::default()).unwrap();
+    let expected = thumbnail
+        .image
+        .resize(16, 16, ResizeMode::Exact, ResizeFilter::Lanczos3)
+        .unwrap();
+    let qpix = encoded.windows(4).rposition(|tag| tag == b"QPIX").unwrap();
+    encoded[qpix + 12 + 3] = 0xFF;
+    assert!(decode_from_memory(&encoded, DecodeOptions::default()).is_err());
+    let scaled = fit(&encoded, (16, 16)).expect("Failed to decode");
+    assert_eq!(scaled.image.pixels, expected.pixels);
+    assert_eq!(scaled.exif, Some(&b"II*\0"[..]));
+
+    // A smaller thumbnail isn't used.
+    assert!(fit(&encoded, (48, 48)).is_err());
+}
+
+#[test]
+fn test_decode_fit_within_clip() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let full = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let clip = Rectangle {
+        x0: 16,
+        y0: 0,
+        x1: 48,
+        y1: 64,
+    };
+    let options = DecodeOptions {
+        src_clip_rect: Some(clip),
+        fit_within: Some((16, 16)),
+        ..Default::default()
+    };
+    let scaled = decode_from_memory(&data, options).expect("Failed to decode");
+    assert_eq!((scaled.image.width, scaled.image.height), (8, 16));
+
+    let region = Image {
+        pixels: &full.image.pixels[16 * 4..],
+        width: 32,
+        height: 64,
+        ..full.image.clone()
+    };
+    let expected = region
+        .resize(8, 16, ResizeMode::Exact, ResizeFilter::Lanczos3)
+        .unwrap();
+    assert_eq!(scaled.image.pixels, expected.pixels);
+
+    // A region that already fits is cropped out as it is.
+    let options = DecodeOptions {
+        src_clip_rect: Some(clip),
+        fit_within: Some((100, 100)),
+        ..Default::default()
+    };
+    let cropped = decode_from_memory(&data, options).unwrap();
+    assert_eq!((cropped.image.width, cropped.image.height), (32, 64));
+    assert_eq!(cropped.image.pixels[..32 * 4], full.image.pixels[16 * 4..48 * 4]);
+}
+
+#[test]
+fn test_decode_fit_within_rejects_zero() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let options = DecodeOptions {
+        fit_within: Some((0, 32)),
+        ..Default::default()
+    };
+    assert!(matches!(
+        decode_from_memory(&data, options),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_decode_fit_within_rejects_offsets() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let options = DecodeOptions {
+        fit_within: Some((32, 32)),
+        offset_x: 8,
+        ..Default::default()
+    };
+    assert!(matches!(
+        decode_from_memory(&data, options),
+        Err(Error::InvalidParameter)
+    ));
+}
@@ -0,0 +1,673 @@
+#![cfg(feature = "rust-backend")]
+
+mod common;
+
+use common::read_test_file;
+use core::ops::ControlFlow;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, Image, PixelFormat, Rectangle, decode_progressive,
+    decode_streaming, rust_backend,
+};
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+const QOIR_FILES: [&str; 7] = [
+    "at-mouquins.qoir",
+    "at-mouquins.lossy-flat-4.qoir",
+    "at-mouquins.lossy-naive-dither-6.qoir",
+    "hibiscus.regular.qoir",
+    "ramp-64x64.rgba.qoir",
+    "ramp-100x50.rgba.qoir",
+    "ramp-32x32.rgb.qoir",
+];
+
+// The `ramp-*` files were encoded by the C library from the dummy images used
+// in `tests/encode.rs`, where every byte is its index modulo 256.
+fn ramp(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 256) as u8).collect()
+}
+
+fn decode_with_format(data: &[u8], pixel_format: PixelFormat) -> Vec<u8> {
+    let options = DecodeOptions {
+        pixel_format,
+        ..Default::default()
+    };
+    let decoded = rust_backend::decode_from_memory(data, options).expect("Failed to decode");
+    decoded.image.pixels.to_vec()
+}
+
+#[test]
+fn test_rust_decode_ramps() {
+    let cases = [
+        (
+            "ramp-64x64.rgba.qoir",
+            PixelFormat::RGBANonPremul,
+            64 * 64 * 4,
+        ),
+        (
+            "ramp-100x50.rgba.qoir",
+            PixelFormat::RGBANonPremul,
+            100 * 50 * 4,
+        ),
+        ("ramp-32x32.rgb.qoir", PixelFormat::RGB, 32 * 32 * 3),
+        ("ramp-16x16.bgr.qoir", PixelFormat::BGR, 16 * 16 * 3),
+    ];
+
+    for (file_name, pixel_format, len) in cases {
+        let pixels = decode_with_format(&read_test_file(file_name), pixel_format);
+        assert_eq!(pixels, ramp(len), "Pixel data mismatch for {}", file_name);
+    }
+}
+
+#[test]
+fn test_rust_decode_valid_qoir() {
+    for file_name in QOIR_FILES {
+        let data = read_test_file(file_name);
+        let result = rust_backend::decode_from_memory(&data, DecodeOptions::default());
+        assert!(
+            result.is_ok(),
+            "Failed to decode {}: {:?}",
+            file_name,
+            result.err()
+        );
+        let decoded_image = result.unwrap();
+
+        let (width, height, _) = rust_backend::decode_basic_metadata(&data).unwrap();
+        assert_eq!(decoded_image.image.width, width);
+        assert_eq!(decoded_image.image.height, height);
+        assert_eq!(decoded_image.image.pixel_format, PixelFormat::RGBANonPremul);
+        assert_eq!(
+            decoded_image.image.pixels.len(),
+            (width * height * 4) as usize
+        );
+    }
+}
+
+#[test]
+fn test_rust_decode_basic_metadata() {
+    let data = read_test_file("at-mouquins.qoir");
+    let (width, height, pixel_format) = rust_backend::decode_basic_metadata(&data).unwrap();
+    assert_eq!((width, height, pixel_format), (193, 256, PixelFormat::BGRX));
+
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let (width, height, pixel_format) = rust_backend::decode_basic_metadata(&data).unwrap();
+    assert_eq!(
+        (width, height, pixel_format),
+        (64, 64, PixelFormat::BGRANonPremul)
+    );
+}
+
+#[test]
+fn test_rust_decode_lossy_close_to_lossless() {
+    let lossless = decode_with_format(&read_test_file("at-mouquins.qoir"), PixelFormat::RGB);
+    let lossy = decode_with_format(
+        &read_test_file("at-mouquins.lossy-flat-2.qoir"),
+        PixelFormat::RGB,
+    );
+    assert_eq!(lossless.len(), lossy.len());
+
+    let total: u64 = lossless
+        .iter()
+        .zip(&lossy)
+        .map(|(a, b)| a.abs_diff(*b) as u64)
+        .sum();
+    let mean = total as f64 / lossless.len() as f64;
+    assert!(
+        mean < 4.0,
+        "Lossy image differs too much: mean error {}",
+        mean
+    );
+}
+
+#[test]
+fn test_rust_decode_with_clip_and_offset() {
+    let data = read_test_file("ramp-100x50.rgba.qoir");
+    let full = decode_with_format(&data, PixelFormat::RGBANonPremul);
+
+    let options = DecodeOptions {
+        src_clip_rect: Some(Rectangle {
+            x0: 10,
+            y0: 5,
+            x1: 80,
+            y1: 40,
+        }),
+        dst_clip_rect: Some(Rectangle {
+            x0: 0,
+            y0: 0,
+            x1: 60,
+            y1: 50,
+        }),
+        offset_x: -5,
+        offset_y: 3,
+        ..Default::default()
+    };
+    let decoded = rust_backend::decode_from_memory(&data, options).unwrap();
+    let stride = decoded.image.stride_in_bytes;
+
+    for y in 0..50 {
+        for x in 0..100 {
+            let (sx, sy) = (x + 5, y - 3);
+            let drawn = (10..80).contains(&sx) && (5..40).contains(&sy) && x < 60;
+            let dst = y as usize * stride + x as usize * 4;
+            let expected = if drawn {
+                let src = (sy as usize * 100 + sx as usize) * 4;
+                &full[src..src + 4]
+            } else {
+                &[0; 4][..]
+            };
+            assert_eq!(
+                &decoded.image.pixels[dst..dst + 4],
+                expected,
+                "at ({}, {})",
+                x,
+                y
+            );
+        }
+    }
+}
+
+#[test]
+fn test_rust_decode_invalid_data() {
+    let result = rust_backend::decode_from_memory(&[0u8; 10], DecodeOptions::default());
+    assert!(result.is_err(), "Decoding invalid data should fail");
+
+    // Every truncation of a valid file must be rejected without panicking.
+    let data = read_test_file("ramp-100x50.rgba.qoir");
+    for len in 0..data.len() {
+        let result = rust_backend::decode_from_memory(&data[..len], DecodeOptions::default());
+        assert!(
+            result.is_err(),
+            "Decoding {} truncated bytes should fail",
+            len
+        );
+    }
+}
+
+const PIXEL_FORMATS: [PixelFormat; 8] = [
+    PixelFormat::BGRX,
+    PixelFormat::BGRANonPremul,
+    PixelFormat::BGRAPremul,
+    PixelFormat::BGR,
+    PixelFormat::RGBX,
+    PixelFormat::RGBANonPremul,
+    PixelFormat::RGBAPremul,
+    PixelFormat::RGB,
+];
+
+fn packed_image(pixels: &[u8], width: u32, height: u32, pixel_format: PixelFormat) -> Image<'_> {
+    Image {
+        pixels,
+        width,
+        height,
+        pixel_format,
+        stride_in_bytes: width as usize * pixel_format.bytes_per_pixel(),
+    }
+}
+
+// `lz4-flex` compresses tiles with a different LZ4 encoder than the C library,
+// so its output is only checked by decoding it.
+#[cfg(not(feature = "lz4-flex"))]
+#[test]
+fn test_rust_encode_ramps_match_c_output() {
+    let cases = [
+        ("ramp-64x64.rgba.qoir", 64, 64, PixelFormat::RGBANonPremul),
+        ("ramp-100x50.rgba.qoir", 100, 50, PixelFormat::RGBANonPremul),
+        ("ramp-32x32.rgb.qoir", 32, 32, PixelFormat::RGB),
+        ("ramp-16x16.bgr.qoir", 16, 16, PixelFormat::BGR),
+    ];
+
+    for (file_name, width, height, pixel_format) in cases {
+        let pixels = ramp((width * height) as usize * pixel_format.bytes_per_pixel());
+        let image = packed_image(&pixels, width, height, pixel_format);
+        let encoded = rust_backend::encode_to_memory(image, EncodeOptions::default())
+            .expect("Failed to encode");
+        assert_eq!(
+            encoded.data,
+            &read_test_file(file_name)[..],
+            "Output differs for {}",
+            file_name
+        );
+    }
+}
+
+#[cfg(feature = "lz4-flex")]
+#[test]
+fn test_rust_encode_ramps_with_lz4_flex() {
+    for file_name in ["ramp-64x64.rgba.qoir", "ramp-100x50.rgba.qoir"] {
+        let c_encoded = read_test_file(file_name);
+        let original = decode_with_format(&c_encoded, PixelFormat::RGBANonPremul);
+        let (width, height, _) = rust_backend::decode_basic_metadata(&c_encoded).unwrap();
+
+        let image = packed_image(&original, width, height, PixelFormat::RGBANonPremul);
+        let encoded = rust_backend::encode_to_memory(image, EncodeOptions::default())
+            .expect("Failed to encode");
+        assert!(
+            decode_with_format(encoded.data, PixelFormat::RGBANonPremul) == original,
+            "Round trip changed pixels for {}",
+            file_name
+        );
+    }
+}
+
+#[test]
+fn test_rust_round_trip_lossless() {
+    let source = read_test_file("at-mouquins.qoir");
+    for pixel_format in PIXEL_FORMATS {
+        let original = decode_with_format(&source, pixel_format);
+        let image = packed_image(&original, 193, 256, pixel_format);
+        let encoded = rust_backend::encode_to_memory(image, EncodeOptions::default())
+            .expect("Failed to encode");
+
+        let decoded = decode_with_format(encoded.data, pixel_format);
+        assert!(
+            decoded == original,
+            "Round trip changed pixels for {:?}",
+            pixel_format
+        );
+    }
+}
+
+#[test]
+fn test_rust_round_trip_lossy() {
+    let original = decode_with_format(&read_test_file("at-mouquins.qoir"), PixelFormat::RGB);
+    for lossiness in 1..=7u8 {
+        for dither in [false, true] {
+            let options = EncodeOptions {
+                lossiness,
+                dither,
+                ..Default::default()
+            };
+            let image = packed_image(&original, 193, 256, PixelFormat::RGB);
+            let encoded = rust_backend::encode_to_memory(image, options).expect("Failed to encode");
+            let decoded = decode_with_format(encoded.data, PixelFormat::RGB);
+
+            // Rounding is off by at most half a step, dithering by a whole one.
+            let step = 255 / ((1u32 << (8 - lossiness)) - 1);
+            let limit = if dither { step } else { step.div_ceil(2) };
+            let max = original
+                .iter()
+                .zip(&decoded)
+                .map(|(a, b)| a.abs_diff(*b) as u32)
+                .max()
+                .unwrap();
+            assert!(
+                max <= limit,
+                "Lossiness {} (dither: {}) is off by {}",
+                lossiness,
+                dither,
+                max
+            );
+        }
+    }
+}
+
+#[test]
+fn test_rust_encode_metadata() {
+    let pixels = ramp(8 * 8 * 4);
+    let options = EncodeOptions {
+        cicp_profile: Some(vec![1, 13, 0, 1]),
+        icc_profile: Some(b"icc".to_vec()),
+        exif: None,
+        xmp: Some(b"<x:xmpmeta/>".to_vec()),
+        ..Default::default()
+    };
+    let image = packed_image(&pixels, 8, 8, PixelFormat::RGBAPremul);
+    let encoded = rust_backend::encode_to_memory(image, options).expect("Failed to encode");
+
+    let (_, _, pixel_format) = rust_backend::decode_basic_metadata(encoded.data).unwrap();
+    assert_eq!(pixel_format, PixelFormat::BGRAPremul);
+
+    let decoded = rust_backend::decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.cic_profile, Some(&[1, 13, 0, 1][..]));
+    assert_eq!(decoded.icc_profile, Some(&b"icc"[..]));
+    assert_eq!(decoded.exif, None);
+    assert_eq!(decoded.xmp, Some(&b"<x:xmpmeta/>"[..]));
+}
+
+#[test]
+fn test_rust_encode_invalid_parameters() {
+    let pixels = ramp(16 * 16 * 4);
+
+    let image = Image {
+        stride_in_bytes: 16 * 3,
+        ..packed_image(&pixels, 16, 16, PixelFormat::RGBANonPremul)
+    };
+    let result = rust_backend::encode_to_memory(image, EncodeOptions::default());
+    assert!(matches!(result, Err(Error::InvalidParameter)));
+
+    let image = packed_image(&pixels, 16, 17, PixelFormat::RGBANonPremul);
+    let result = rust_backend::encode_to_memory(image, EncodeOptions::default());
+    assert!(matches!(result, Err(Error::InvalidParameter)));
+
+    let image = packed_image(&pixels, 16, 16, PixelFormat::Invalid);
+    let result = rust_backend::encode_to_memory(image, EncodeOptions::default());
+    assert!(matches!(result, Err(Error::EncodingFailed(_))));
+
+    let image = packed_image(&pixels, 16, 16, PixelFormat::RGBANonPremul);
+    let options = EncodeOptions {
+        lossiness: 8,
+        ..Default::default()
+    };
+    let result = rust_backend::encode_to_memory(image, options);
+    assert!(matches!(result, Err(Error::InvalidParameter)));
+}
+
+// Without `std` there is no clock, so every stage stays zero.
+#[cfg(feature = "std")]
+#[test]
+fn test_rust_collect_timings() {
+    let data = read_test_file("at-mouquins.qoir");
+    let decoded = rust_backend::decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    assert!(decoded.timings.is_none());
+
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGR,
+        collect_timings: true,
+        ..Default::default()
+    };
+    let decoded = rust_backend::decode_from_memory(&data, options).unwrap();
+    let timings = decoded.timings.expect("Timings were not collected");
+    assert!(timings.tile_decode > Duration::ZERO);
+    assert!(timings.format_convert > Duration::ZERO);
+    assert_eq!(timings.io, Duration::ZERO);
+
+    let options = EncodeOptions {
+        collect_timings: true,
+        ..Default::default()
+    };
+    let encoded = rust_backend::encode_to_memory(decoded.image, options).unwrap();
+    let timings = encoded.timings.expect("Timings were not collected");
+    assert!(timings.tile_encode > Duration::ZERO);
+    assert!(timings.chunk_write > Duration::ZERO);
+    assert_eq!(timings.tile_decode, Duration::ZERO);
+    assert!(timings.format_convert > Duration::ZERO);
+}
+
+#[test]
+fn test_decode_progressive() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let full = rust_backend::decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let row_len = full.image.stride_in_bytes;
+
+    // Bands arrive from the top down, and the rows below the last one are
+    // still zeroed.
+    let mut bands = Vec::new();
+    let decoded = decode_progressive(&data, DecodeOptions::default(), |rect, image| {
+        let end = rect.y1 as usize * row_len;
+        assert_eq!(image.pixels[..end], full.image.pixels[..end]);
+        assert!(image.pixels[end..].iter().all(|&b| b == 0));
+        bands.push(rect);
+        ControlFlow::Continue(())
+    })
+    .expect("Failed to decode")
+    .expect("Decoding was stopped");
+    assert_eq!(decoded.image.pixels, full.image.pixels);
+    assert_eq!(bands.len(), full.image.height.div_ceil(64) as usize);
+    assert_eq!((bands[0].x0, bands[0].y0), (0, 0));
+    for pair in bands.windows(2) {
+        assert_eq!(pair[0].y1, pair[1].y0);
+    }
+    assert_eq!(bands.last().unwrap().y1, full.image.height as i32);
+}
+
+#[test]
+fn test_decode_progressive_clip_and_stop() {
+    let data = read_test_file("hibiscus.regular.qoir");
+
+    // Rectangles are in destination coordinates, and clipped bands are
+    // skipped.
+    let options = DecodeOptions {
+        src_clip_rect: Some(Rectangle {
+            x0: 10,
+            y0: 100,
+            x1: 50,
+            y1: 200,
+        }),
+        offset_x: -10,
+        offset_y: -100,
+        ..Default::default()
+    };
+    let mut bands = Vec::new();
+    decode_progressive(&data, options, |rect, _| {
+        bands.push(rect);
+        ControlFlow::Continue(())
+    })
+    .unwrap()
+    .unwrap();
+    assert_eq!(
+        bands,
+        [
+            Rectangle {
+                x0: 0,
+                y0: 0,
+                x1: 40,
+                y1: 28,
+            },
+            Rectangle {
+                x0: 0,
+                y0: 28,
+                x1: 40,
+                y1: 92,
+            },
+            Rectangle {
+                x0: 0,
+                y0: 92,
+                x1: 40,
+                y1: 100,
+            },
+        ]
+    );
+
+    let mut calls = 0;
+    let stopped = decode_progressive(&data, DecodeOptions::default(), |_, _| {
+        calls += 1;
+        ControlFlow::Break(())
+    })
+    .unwrap();
+    assert!(stopped.is_none());
+    assert_eq!(calls, 1);
+    assert!(
+        decode_progressive(&data[..100], DecodeOptions::default(), |_, _| {
+            ControlFlow::Continue(())
+        })
+        .is_err()
+    );
+}
+
+#[test]
+fn test_decode_streaming() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    for pixel_format in [PixelFormat::RGBANonPremul, PixelFormat::BGR] {
+        let options = DecodeOptions {
+            pixel_format,
+            ..Default::default()
+        };
+        let full = rust_backend::decode_from_memory(&data, options.clone()).unwrap();
+        let bpp = pixel_format.bytes_per_pixel();
+
+        // Putting the tiles back together gives the whole image.
+        let mut pixels = vec![0; full.image.pixels.len()];
+        let mut tiles = 0;
+        decode_streaming(&data, options, |rect, tile| {
+            let row_len = (rect.x1 - rect.x0) as usize * bpp;
+            assert!(rect.x1 - rect.x0 <= 64 && rect.y1 - rect.y0 <= 64);
+            assert_eq!(tile.len(), row_len * (rect.y1 - rect.y0) as usize);
+            for (y, row) in (rect.y0..rect.y1).zip(tile.chunks_exact(row_len)) {
+                let start = y as usize * full.image.stride_in_bytes + rect.x0 as usize * bpp;
+                pixels[start..start + row_len].copy_from_slice(row);
+            }
+            tiles += 1;
+            ControlFlow::Continue(())
+        })
+        .expect("Failed to decode");
+        assert_eq!(pixels, full.image.pixels);
+        let (width, height) = (full.image.width, full.image.height);
+        assert_eq!(tiles, width.div_ceil(64) * height.div_ceil(64));
+    }
+}
+
+#[test]
+fn test_decode_streaming_clip_and_stop() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let options = DecodeOptions {
+        src_clip_rect: Some(Rectangle {
+            x0: 10,
+            y0: 100,
+            x1: 80,
+            y1: 140,
+        }),
+        offset_x: -10,
+        offset_y: -100,
+        ..Default::default()
+    };
+    let mut rects = Vec::new();
+    decode_streaming(&data, options, |rect, _| {
+        rects.push(rect);
+        ControlFlow::Continue(())
+    })
+    .unwrap();
+    assert_eq!(
+        rects,
+        [
+            Rectangle {
+                x0: 0,
+                y0: 0,
+                x1: 54,
+                y1: 28,
+            },
+            Rectangle {
+                x0: 54,
+                y0: 0,
+                x1: 70,
+                y1: 28,
+            },
+            Rectangle {
+                x0: 0,
+                y0: 28,
+                x1: 54,
+                y1: 40,
+            },
+            Rectangle {
+                x0: 54,
+                y0: 28,
+                x1: 70,
+                y1: 40,
+            },
+        ]
+    );
+
+    let mut calls = 0;
+    decode_streaming(&data, DecodeOptions::default(), |_, _| {
+        calls += 1;
+        ControlFlow::Break(())
+    })
+    .unwrap();
+    assert_eq!(calls, 1);
+
+    let truncated = &data[..data.len() - 20];
+    let result = decode_streaming(truncated, DecodeOptions::default(), |_, _| {
+        ControlFlow::Continue(())
+    });
+    assert!(matches!(result, Err(Error::DecodingFailed(_))));
+}
+
+#[cfg(feature = "c-backend")]
+#[test]
+fn test_rust_encode_decodes_with_c_backend() {
+    let original = decode_with_format(
+        &read_test_file("hibiscus.regular.qoir"),
+        PixelFormat::RGBANonPremul,
+    );
+    let (width, height, _) =
+        rust_backend::decode_basic_metadata(&read_test_file("hibiscus.regular.qoir")).unwrap();
+
+    for (lossiness, dither) in [(0, false), (2, false), (4, true)] {
+        let options = EncodeOptions {
+            lossiness,
+            dither,
+            ..Default::default()
+        };
+        let image = packed_image(&original, width, height, PixelFormat::RGBANonPremul);
+        let encoded = rust_backend::encode_to_memory(image.clone(), options.clone()).unwrap();
+        let c_encoded = qoir_rs::encode_to_memory(image, options).unwrap();
+
+        let c = qoir_rs::decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+        let rust = decode_with_format(encoded.data, PixelFormat::RGBANonPremul);
+        assert!(
+            c.image.pixels == &rust[..],
+            "Backends disagree at lossiness {}",
+            lossiness
+        );
+
+        let c = qoir_rs::decode_from_memory(c_encoded.data, DecodeOptions::default()).unwrap();
+        let rust = decode_with_format(c_encoded.data, PixelFormat::RGBANonPremul);
+        assert!(
+            c.image.pixels == &rust[..],
+            "Backends disagree at lossiness {}",
+            lossiness
+        );
+    }
+}
+
+#[cfg(feature = "c-backend")]
+#[test]
+fn test_rust_decode_matches_c_backend() {
+    for file_name in QOIR_FILES {
+        let data = read_test_file(file_name);
+        for pixel_format in PIXEL_FORMATS {
+            let options = DecodeOptions {
+                pixel_format,
+                ..Default::default()
+            };
+            let c = qoir_rs::decode_from_memory(&data, options.clone()).unwrap();
+            let rust = rust_backend::decode_from_memory(&data, options).unwrap();
+
+            assert_eq!(c.image.width, rust.image.width);
+            assert_eq!(c.image.height, rust.image.height);
+            assert_eq!(c.image.pixel_format, rust.image.pixel_format);
+
+            let row_len = rust.image.width as usize * pixel_format.bytes_per_pixel();
+            let c_rows = c.image.pixels.chunks(c.image.stride_in_bytes);
+            let rust_rows = rust.image.pixels.chunks(rust.image.stride_in_bytes);
+            for (y, (c_row, rust_row)) in c_rows.zip(rust_rows).enumerate() {
+                assert_eq!(
+                    &c_row[..row_len],
+                    &rust_row[..row_len],
+                    "Row {} differs for {} as {:?}",
+                    y,
+                    file_name,
+                    pixel_format
+                );
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "c-backend", feature = "lz4-flex"))]
+#[test]
+fn test_lz4_flex_conforms_to_c_backend() {
+    for file_name in QOIR_FILES {
+        let data = read_test_file(file_name);
+        let (width, height, _) = rust_backend::decode_basic_metadata(&data).unwrap();
+        let original = decode_with_format(&data, PixelFormat::RGBANonPremul);
+        let image = packed_image(&original, width, height, PixelFormat::RGBANonPremul);
+
+        // Tiles compressed by `lz4_flex` decompress with the C library...
+        let encoded = rust_backend::encode_to_memory(image.clone(), EncodeOptions::default())
+            .expect("Failed to encode");
+        let c = qoir_rs::decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+        assert!(
+            c.image.pixels == &original[..],
+            "C decoding differs for {}",
+            file_name
+        );
+
+        // ...and tiles compressed by the C library decompress with `lz4_flex`.
+        let c_encoded = qoir_rs::encode_to_memory(image, EncodeOptions::default()).unwrap();
+        assert!(
+            decode_with_format(c_encoded.data, PixelFormat::RGBANonPremul) == original,
+            "Rust decoding differs for {}",
+            file_name
+        );
+    }
+}
@@ -0,0 +1,132 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{DecodeOptions, Error, Image, PixelFormat, decode_from_memory, stats};
+use std::collections::HashSet;
+
+#[test]
+fn test_stats_of_photo() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let image = &decoded.image;
+    let stats = stats(image).expect("Failed to compute stats");
+
+    let pixel_count = image.width as u64 * image.height as u64;
+    assert_eq!(stats.pixel_count, pixel_count);
+    for histogram in &stats.histograms {
+        assert_eq!(histogram.iter().sum::<u64>(), pixel_count);
+    }
+    assert!(stats.mean_luma > 0.0 && stats.mean_luma < 255.0);
+
+    let colors: HashSet<&[u8]> = image.pixels.chunks_exact(4).collect();
+    let exact = colors.len() as f64;
+    let estimate = stats.unique_colors_estimate as f64;
+    assert!(
+        (estimate - exact).abs() / exact < 0.05,
+        "{estimate} vs {exact}"
+    );
+
+    // The same pixels in another format give the same statistics.
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGRANonPremul,
+        ..Default::default()
+    };
+    let bgra = decode_from_memory(&data, options).unwrap();
+    assert_eq!(qoir_rs::stats(&bgra.image).unwrap(), stats);
+
+    // 4-byte pixels take the SIMD path where there is one, and 3-byte pixels
+    // don't, but they agree.
+    let opaque = |pixel_format| {
+        let options = DecodeOptions {
+            pixel_format,
+            ..Default::default()
+        };
+        let decoded = decode_from_memory(&data, options).unwrap();
+        qoir_rs::stats(&decoded.image).unwrap()
+    };
+    assert_eq!(opaque(PixelFormat::RGBX), opaque(PixelFormat::RGB));
+}
+
+#[test]
+fn test_stats_of_synthetic_images() {
+    // Four opaque colors, one per quadrant.
+    let mut pixels = Vec::new();
+    for y in 0..8 {
+        for x in 0..8 {
+            let color: [u8; 4] = match (x / 4, y / 4) {
+                (0, 0) => [255, 255, 255, 255],
+                (1, 0) => [0, 0, 0, 255],
+                (0, 1) => [255, 0, 0, 255],
+                _ => [0, 0, 255, 255],
+            };
+            pixels.extend_from_slice(&color);
+        }
+    }
+    let image = Image {
+        pixels: &pixels,
+        width: 8,
+        height: 8,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 32,
+    };
+    let opaque = stats(&image).unwrap();
+    assert!(opaque.is_fully_opaque);
+    assert_eq!(opaque.unique_colors_estimate, 4);
+    assert_eq!(opaque.histograms[0][255], 32);
+    let expected_luma = (255.0 + 0.0 + 0.299 * 255.0 + 0.114 * 255.0) / 4.0;
+    assert!((opaque.mean_luma - expected_luma).abs() < 1e-9);
+
+    let mut translucent = pixels.clone();
+    translucent[3] = 128;
+    let image = Image {
+        pixels: &translucent,
+        ..image
+    };
+    let translucent = stats(&image).unwrap();
+    assert!(!translucent.is_fully_opaque);
+    assert_eq!(translucent.histograms[3][128], 1);
+    assert_eq!(translucent.unique_colors_estimate, 5);
+
+    // Formats without alpha are always opaque, and the stride is honoured.
+    let rgb = [10u8, 20, 30, 0xEE, 10, 20, 30, 0xEE];
+    let image = Image {
+        pixels: &rgb,
+        width: 1,
+        height: 2,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: 4,
+    };
+    let stats = stats(&image).unwrap();
+    assert!(stats.is_fully_opaque);
+    assert_eq!(stats.unique_colors_estimate, 1);
+    assert_eq!(stats.histograms[2][30], 2);
+}
+
+#[test]
+fn test_stats_invalid_input() {
+    let pixels = [0u8; 12];
+    let image = Image {
+        pixels: &pixels,
+        width: 2,
+        height: 2,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 8,
+    };
+    assert!(matches!(stats(&image), Err(Error::InvalidParameter)));
+    let invalid = Image {
+        pixel_format: PixelFormat::Invalid,
+        ..image.clone()
+    };
+    assert!(matches!(stats(&invalid), Err(Error::InvalidParameter)));
+
+    let empty = Image {
+        width: 0,
+        height: 0,
+        ..image
+    };
+    let stats = stats(&empty).unwrap();
+    assert_eq!((stats.pixel_count, stats.unique_colors_estimate), (0, 0));
+    assert!(stats.is_fully_opaque);
+}
@@ -0,0 +1,73 @@
+#![cfg(all(feature = "rust-backend", feature = "rayon"))]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, PixelFormat, Rectangle, ThreadPoolBuilder, rust_backend,
+    set_thread_pool, thread_pool,
+};
+
+#[test]
+fn test_output_does_not_depend_on_thread_count() {
+    let data = read_test_file("at-mouquins.qoir");
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGBANonPremul,
+        src_clip_rect: Some(Rectangle {
+            x0: 30,
+            y0: 70,
+            x1: 500,
+            y1: 300,
+        }),
+        offset_x: -10,
+        offset_y: 5,
+        ..Default::default()
+    };
+
+    let run = |num_threads| {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .expect("Failed to build the thread pool");
+        pool.install(|| {
+            let decoded =
+                rust_backend::decode_from_memory(&data, options.clone()).expect("Failed to decode");
+            let encoded = rust_backend::encode_to_memory(
+                decoded.image.clone(),
+                EncodeOptions {
+                    lossiness: 2,
+                    dither: true,
+                    ..Default::default()
+                },
+            )
+            .expect("Failed to encode");
+            (decoded.image.pixels.to_vec(), encoded.data.to_vec())
+        })
+    };
+
+    let (pixels, encoded) = run(1);
+    for num_threads in [2, 3, 8] {
+        let (parallel_pixels, parallel_encoded) = run(num_threads);
+        assert_eq!(parallel_pixels, pixels, "{num_threads} threads");
+        assert_eq!(parallel_encoded, encoded, "{num_threads} threads");
+    }
+}
+
+#[test]
+fn test_set_thread_pool() {
+    ThreadPoolBuilder::new()
+        .num_threads(2)
+        .thread_name_prefix("qoir")
+        .build_global()
+        .expect("Failed to build the thread pool");
+    assert_eq!(
+        thread_pool().map(|pool| pool.current_num_threads()),
+        Some(2)
+    );
+
+    let data = read_test_file("hibiscus.regular.qoir");
+    rust_backend::decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+
+    set_thread_pool(None);
+    assert!(thread_pool().is_none());
+}
@@ -0,0 +1,57 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, MetadataEdit, ThumbnailSpec, decode_from_memory, encode_to_vec,
+    extract_thumbnail, rewrite_metadata,
+};
+
+fn with_thumbnail(data: &[u8], max_dim: u32) -> Vec<u8> {
+    let decoded = decode_from_memory(data, DecodeOptions::default()).expect("Failed to decode");
+    let options = EncodeOptions {
+        embed_thumbnail: Some(ThumbnailSpec {
+            max_dim,
+            lossiness: 0,
+        }),
+        ..Default::default()
+    };
+    encode_to_vec(decoded.image.clone(), options).expect("Failed to encode")
+}
+
+#[test]
+fn test_extract_thumbnail() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    assert_eq!(extract_thumbnail(&data).unwrap(), None);
+
+    let encoded = with_thumbnail(&data, 16);
+    let thumbnail = extract_thumbnail(&encoded)
+        .expect("Failed to read thumbnail")
+        .expect("No thumbnail");
+    let decoded = decode_from_memory(thumbnail, DecodeOptions::default())
+        .expect("Failed to decode thumbnail");
+    assert_eq!((decoded.image.width, decoded.image.height), (16, 16));
+
+    // The image itself is unchanged, and the pixels aren't needed to find the
+    // thumbnail.
+    let original = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let image = decode_from_memory(&encoded, DecodeOptions::default()).unwrap();
+    assert_eq!(image.image.pixels, original.image.pixels);
+    let end = thumbnail.as_ptr() as usize - encoded.as_ptr() as usize + thumbnail.len();
+    assert!(end < encoded.len());
+    assert_eq!(extract_thumbnail(&encoded[..end]).unwrap(), Some(thumbnail));
+}
+
+#[test]
+fn test_thumbnail_of_small_image() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let encoded = with_thumbnail(&data, 256);
+    let thumbnail = extract_thumbnail(&encoded).unwrap().expect("No thumbnail");
+    let decoded = decode_from_memory(thumbnail, DecodeOptions::default()).unwrap();
+    let original = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.image.pixels, original.image.pixels);
+
+    let rewritten = rewrite_metadata(&encoded, &MetadataEdit::default()).unwrap();
+    assert_eq!(extract_thumbnail(&rewritten).unwrap(), Some(thumbnail));
+}
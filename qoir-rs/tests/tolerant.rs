@@ -0,0 +1,143 @@
+#![cfg(feature = "rust-backend")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{DecodeLimits, DecodeOptions, Error, PixelFormat, Rectangle, decode_from_memory};
+
+fn tolerant(fill_color: [u8; 4]) -> DecodeOptions {
+    DecodeOptions {
+        tolerant: true,
+        fill_color,
+        limits: DecodeLimits {
+            max_memory: Some(256 << 20),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_tolerant_decode_truncated_file() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let full = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let truncated = &data[..data.len() / 2];
+    assert!(decode_from_memory(truncated, DecodeOptions::default()).is_err());
+
+    let fill = [0x10, 0x20, 0x30, 0xFF];
+    let decoded = decode_from_memory(truncated, tolerant(fill)).expect("Failed to decode");
+    let image = &decoded.image;
+    assert_eq!(
+        (image.width, image.height),
+        (full.image.width, full.image.height)
+    );
+
+    // The missing tiles run to the bottom-right corner, and every pixel is
+    // either decoded or filled.
+    let regions = &decoded.missing_regions;
+    assert!(!regions.is_empty());
+    let last = regions.last().unwrap();
+    assert_eq!(
+        (last.x1, last.y1),
+        (image.width as i32, image.height as i32)
+    );
+    let first_missing_row = regions.iter().map(|r| r.y0).min().unwrap() as usize;
+    assert!(first_missing_row > 0);
+    let row_len = image.stride_in_bytes;
+    assert_eq!(
+        image.pixels[..first_missing_row * row_len],
+        full.image.pixels[..first_missing_row * row_len]
+    );
+    for region in regions {
+        for y in region.y0..region.y1 {
+            for x in region.x0..region.x1 {
+                let offset = y as usize * row_len + x as usize * 4;
+                assert_eq!(image.pixels[offset..offset + 4], fill);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_tolerant_decode_corrupt_tile() {
+    let mut data = read_test_file("hibiscus.regular.qoir");
+    let bgr = DecodeOptions {
+        pixel_format: PixelFormat::BGR,
+        ..Default::default()
+    };
+    let full = decode_from_memory(&data, bgr.clone()).unwrap();
+
+    // Give the first tile an unknown format, keeping its length.
+    let qpix = data.windows(4).position(|tag| tag == b"QPIX").unwrap();
+    data[qpix + 12 + 3] = 0xFF;
+    assert!(decode_from_memory(&data, bgr).is_err());
+
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGR,
+        ..tolerant([0xFF, 0, 0, 0xFF])
+    };
+    let decoded = decode_from_memory(&data, options).expect("Failed to decode");
+    assert_eq!(decoded.missing_regions.len(), 1);
+    let region = decoded.missing_regions[0];
+    assert_eq!((region.x0, region.y0), (0, 0));
+    assert!(region.x1 < decoded.image.width as i32);
+    assert_eq!(decoded.image.pixels[..3], [0, 0, 0xFF]);
+
+    // Everything after the corrupt tile decodes as usual.
+    let start = region.y1 as usize * decoded.image.stride_in_bytes;
+    assert_eq!(decoded.image.pixels[start..], full.image.pixels[start..]);
+}
+
+#[test]
+fn test_tolerant_decode_intact_file() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let full = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let decoded = decode_from_memory(&data, tolerant([0xFF; 4])).unwrap();
+    assert!(decoded.missing_regions.is_empty());
+    assert_eq!(decoded.image.pixels, full.image.pixels);
+
+    // Only missing tiles inside the clip rectangle are filled and reported.
+    let crop = Rectangle {
+        x0: 0,
+        y0: 0,
+        x1: 8,
+        y1: 8,
+    };
+    let options = DecodeOptions {
+        src_clip_rect: Some(crop),
+        ..tolerant([0xFF; 4])
+    };
+    let truncated = &data[..data.len() / 2];
+    let decoded = decode_from_memory(truncated, options).unwrap();
+    assert!(decoded.missing_regions.is_empty());
+
+    // Without a header there is nothing to recover.
+    assert!(decode_from_memory(&data[..16], tolerant([0; 4])).is_err());
+}
+
+#[test]
+fn test_tolerant_decode_forged_header() {
+    // A header claiming 11468870x70 pixels, about 3 GB as RGBA, followed by
+    // far too little data for its tiles.
+    let mut data = read_test_file("ramp-64x64.rgba.qoir");
+    // The header chunk comes first, and its payload after the tag and length.
+    let header = 12;
+    let set_dimension = |data: &mut Vec<u8>, at: usize, value: u32| {
+        let word = u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        data[at..at + 4].copy_from_slice(&(word & 0xFF00_0000 | value).to_le_bytes());
+    };
+    set_dimension(&mut data, header, 11_468_870);
+    set_dimension(&mut data, header + 4, 70);
+    data.truncate(100);
+
+    // Without limits, nothing bounds the pixel buffer, so the file is refused.
+    let options = DecodeOptions {
+        tolerant: true,
+        ..Default::default()
+    };
+    assert!(matches!(decode_from_memory(&data, options), Err(Error::DecodingFailed(_))));
+
+    // With them, it is refused for exceeding the limit.
+    let result = decode_from_memory(&data, tolerant([0; 4]));
+    assert!(matches!(result, Err(Error::DecodingFailed(message)) if message.contains("memory limit")));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{DecodeOptions, Error, Image, PixelFormat, decode_from_memory};
+
+#[test]
+fn test_rotate_and_flip_small_image() {
+    // A 3x2 RGB image with a padded stride, each pixel holding its index:
+    //   0 1 2
+    //   3 4 5
+    let stride = 3 * 3 + 2;
+    let mut pixels = vec![0xEE; stride * 2];
+    for i in 0..6 {
+        let offset = (i / 3) * stride + (i % 3) * 3;
+        pixels[offset..offset + 3].fill(i as u8);
+    }
+    let image = Image {
+        pixels: &pixels,
+        width: 3,
+        height: 2,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: stride,
+    };
+    let indices = |pixels: &[u8]| -> Vec<u8> { pixels.chunks_exact(3).map(|p| p[0]).collect() };
+
+    let rotated = image.rotate90().unwrap();
+    assert_eq!((rotated.width, rotated.height), (2, 3));
+    assert_eq!(rotated.stride_in_bytes, 6);
+    assert_eq!(indices(&rotated.pixels), [3, 0, 4, 1, 5, 2]);
+
+    let rotated = image.rotate270().unwrap();
+    assert_eq!((rotated.width, rotated.height), (2, 3));
+    assert_eq!(indices(&rotated.pixels), [2, 5, 1, 4, 0, 3]);
+
+    let rotated = image.rotate180().unwrap();
+    assert_eq!((rotated.width, rotated.height), (3, 2));
+    assert_eq!(indices(&rotated.pixels), [5, 4, 3, 2, 1, 0]);
+
+    assert_eq!(indices(&image.flip_h().unwrap().pixels), [2, 1, 0, 5, 4, 3]);
+    assert_eq!(indices(&image.flip_v().unwrap().pixels), [3, 4, 5, 0, 1, 2]);
+}
+
+#[test]
+fn test_rotations_compose() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).expect("Failed to decode");
+    let image = &decoded.image;
+
+    let quarter = image.rotate90().unwrap();
+    assert_eq!((quarter.width, quarter.height), (image.height, image.width));
+    let half = quarter.as_image().rotate90().unwrap();
+    assert_eq!(half.pixels, image.rotate180().unwrap().pixels);
+    let back = quarter.as_image().rotate270().unwrap();
+    assert_eq!(back.pixels, image.pixels);
+
+    let mirrored = image.flip_h().unwrap().as_image().flip_v().unwrap();
+    assert_eq!(mirrored.pixels, half.pixels);
+    let flipped = image.flip_v().unwrap();
+    assert_eq!(flipped.as_image().flip_v().unwrap().pixels, image.pixels);
+}
+
+#[test]
+fn test_transform_invalid_input() {
+    let pixels = [0u8; 12];
+    let image = Image {
+        pixels: &pixels,
+        width: 2,
+        height: 2,
+        pixel_format: PixelFormat::RGBANonPremul,
+        stride_in_bytes: 8,
+    };
+    assert!(matches!(image.rotate90(), Err(Error::InvalidParameter)));
+    assert!(matches!(image.flip_h(), Err(Error::InvalidParameter)));
+
+    let invalid = Image {
+        pixel_format: PixelFormat::Invalid,
+        height: 1,
+        ..image
+    };
+    assert!(matches!(invalid.rotate180(), Err(Error::InvalidParameter)));
+
+    let empty = Image {
+        width: 0,
+        height: 3,
+        stride_in_bytes: 0,
+        pixels: &[],
+        pixel_format: PixelFormat::BGRX,
+    };
+    let rotated = empty.rotate270().unwrap();
+    assert_eq!((rotated.width, rotated.height), (3, 0));
+    assert!(rotated.pixels.is_empty());
+}
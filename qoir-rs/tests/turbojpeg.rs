@@ -0,0 +1,97 @@
+#![cfg(feature = "turbojpeg")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::turbojpeg::{decode_jpeg, encode_jpeg};
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, Error, Image, PixelFormat, decode_from_memory,
+    transcode_jpeg_to_qoir,
+};
+
+#[test]
+fn test_jpeg_round_trip() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::RGB,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&data, options).expect("Failed to decode");
+    let image = &decoded.image;
+
+    let jpeg = encode_jpeg(image, 95).expect("Failed to encode");
+    assert!(jpeg.starts_with(&[0xFF, 0xD8]));
+    for pixel_format in [PixelFormat::RGB, PixelFormat::BGRAPremul] {
+        let round_trip = decode_jpeg(&jpeg, pixel_format).expect("Failed to decode");
+        assert_eq!(
+            (round_trip.width, round_trip.height),
+            (image.width, image.height)
+        );
+        assert_eq!(round_trip.pixel_format, pixel_format);
+        assert_eq!(
+            round_trip.stride_in_bytes,
+            image.width as usize * pixel_format.bytes_per_pixel()
+        );
+    }
+
+    let rgb = decode_jpeg(&jpeg, PixelFormat::RGB).unwrap();
+    let total: u64 = rgb
+        .pixels
+        .iter()
+        .zip(image.to_pixel_format(PixelFormat::RGB).unwrap())
+        .map(|(&a, b)| a.abs_diff(b) as u64)
+        .sum();
+    assert!(total / (rgb.pixels.len() as u64) < 4);
+
+    assert!(matches!(
+        encode_jpeg(image, 0),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        decode_jpeg(&jpeg, PixelFormat::Invalid),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        decode_jpeg(&data, PixelFormat::RGB),
+        Err(Error::DecodingFailed(_))
+    ));
+    let empty = Image {
+        pixels: &[],
+        width: 0,
+        height: 0,
+        pixel_format: PixelFormat::RGB,
+        stride_in_bytes: 0,
+    };
+    assert!(matches!(
+        encode_jpeg(&empty, 90),
+        Err(Error::InvalidParameter)
+    ));
+}
+
+#[test]
+fn test_transcode_jpeg_to_qoir() {
+    let jpeg = read_test_file("0.jpg");
+    let expected = image::load_from_memory(&jpeg).unwrap();
+    let encoded =
+        transcode_jpeg_to_qoir(&jpeg, EncodeOptions::default()).expect("Failed to transcode");
+    let decoded = decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert_eq!(
+        (decoded.image.width, decoded.image.height),
+        (expected.width(), expected.height())
+    );
+
+    // The profile is copied from the `APP2` segment and starts with its size.
+    let icc = decoded.icc_profile.expect("Missing ICC profile");
+    assert_eq!(
+        u32::from_be_bytes(icc[..4].try_into().unwrap()) as usize,
+        icc.len()
+    );
+
+    let options = EncodeOptions {
+        icc_profile: Some(b"custom".to_vec()),
+        ..Default::default()
+    };
+    let encoded = transcode_jpeg_to_qoir(&jpeg, options).unwrap();
+    let decoded = decode_from_memory(encoded.data, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.icc_profile, Some(&b"custom"[..]));
+}
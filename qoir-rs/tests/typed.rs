@@ -0,0 +1,69 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    Bgr8, DecodeOptions, Error, Image, PixelFormat, PixelLayout, Rgba8, TypedImage,
+    decode_from_memory,
+};
+
+#[test]
+fn test_typed_image_matches_dynamic_image() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let image: TypedImage<Rgba8> = decoded.image.as_typed().unwrap();
+    assert_eq!((image.width(), image.height()), (312, 442));
+    assert_eq!(image.rows().len(), 442);
+    assert_eq!(Rgba8::BYTES_PER_PIXEL, 4);
+
+    let pixels = &decoded.image.pixels;
+    let offset = 100 * decoded.image.stride_in_bytes + 50 * 4;
+    let expected = Rgba8 {
+        r: pixels[offset],
+        g: pixels[offset + 1],
+        b: pixels[offset + 2],
+        a: pixels[offset + 3],
+    };
+    assert_eq!(image.pixel(50, 100), expected);
+    assert_eq!(image.row(100)[50], expected);
+
+    let round_trip = Image::from(image);
+    assert_eq!(round_trip.pixels, decoded.image.pixels);
+    assert_eq!(round_trip.pixel_format, PixelFormat::RGBANonPremul);
+}
+
+#[test]
+fn test_typed_image_from_pixels() {
+    let pixels = [
+        Bgr8 { b: 1, g: 2, r: 3 },
+        Bgr8 { b: 4, g: 5, r: 6 },
+        Bgr8 { b: 7, g: 8, r: 9 },
+        Bgr8 {
+            b: 10,
+            g: 11,
+            r: 12,
+        },
+    ];
+    let image = TypedImage::new(&pixels, 2, 2).unwrap();
+    assert_eq!(image.row(1), &pixels[2..]);
+    assert_eq!(image.stride_in_bytes(), 6);
+
+    let dynamic = image.as_image();
+    assert_eq!(dynamic.pixel_format, PixelFormat::BGR);
+    assert_eq!(dynamic.pixels, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+    let typed: TypedImage<Bgr8> = dynamic.try_into().unwrap();
+    assert_eq!(typed.pixel(1, 0), pixels[1]);
+}
+
+#[test]
+fn test_typed_image_rejects_mismatches() {
+    let pixels = [0u8; 12];
+    let image = Image::from_raw(&pixels, 2, 2, PixelFormat::RGB).unwrap();
+    assert!(matches!(
+        image.as_typed::<Rgba8>(),
+        Err(Error::InvalidParameter)
+    ));
+    assert!(matches!(
+        TypedImage::new(&[Rgba8::default(); 3], 2, 2),
+        Err(Error::InvalidParameter)
+    ));
+}
@@ -0,0 +1,50 @@
+#![cfg(all(feature = "uring", target_os = "linux"))]
+
+mod common;
+
+use common::{TEST_DATA_DIR, read_test_file};
+use qoir_rs::uring::{decode_files, encode_files};
+use qoir_rs::{DecodeOptions, EncodeOptions, Error, decode_from_memory};
+use std::fs;
+
+#[test]
+fn test_decode_files() {
+    let paths = [
+        format!("{}/ramp-32x32.rgb.qoir", TEST_DATA_DIR),
+        format!("{}/missing.qoir", TEST_DATA_DIR),
+        format!("{}/ramp-64x64.rgba.qoir", TEST_DATA_DIR),
+    ];
+    let mut seen = [false; 3];
+    decode_files(&paths, DecodeOptions::default(), |index, result| {
+        assert!(!seen[index]);
+        seen[index] = true;
+        if index == 1 {
+            assert!(matches!(result, Err(Error::FileNotFound)));
+            return;
+        }
+        let data = fs::read(&paths[index]).unwrap();
+        let expected = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+        assert_eq!(result.unwrap().image.pixels, expected.image.pixels);
+    });
+    assert_eq!(seen, [true; 3]);
+}
+
+#[test]
+fn test_encode_files() {
+    let data = read_test_file("ramp-64x64.rgba.qoir");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let dir = std::env::temp_dir().join(format!("qoir-rs-uring-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let outputs: Vec<_> = (0..12).map(|i| dir.join(format!("{}.qoir", i))).collect();
+    let jobs = outputs.iter().map(|path| (decoded.image.clone(), path));
+    let results = encode_files(jobs, EncodeOptions::default());
+    assert_eq!(results.len(), outputs.len());
+    for (result, path) in results.into_iter().zip(&outputs) {
+        result.expect("Failed to encode");
+        let written = fs::read(path).unwrap();
+        let round_trip = decode_from_memory(&written, DecodeOptions::default()).unwrap();
+        assert_eq!(round_trip.image.pixels, decoded.image.pixels);
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::read_test_file;
+use qoir_rs::{
+    DecodeOptions, EncodeOptions, IntegrityError, MetadataChange, MetadataEdit, decode_from_memory,
+    encode_to_memory, inspect, rewrite_metadata, verify, verify_integrity,
+};
+
+#[test]
+fn test_verify_intact_files() {
+    for name in [
+        "at-mouquins.qoir",
+        "hibiscus.regular.qoir",
+        "ramp-100x50.rgba.qoir",
+    ] {
+        let data = read_test_file(name);
+        verify(&data, true).unwrap_or_else(|e| panic!("{name}: {e}"));
+        verify(&data, false).unwrap_or_else(|e| panic!("{name}: {e}"));
+    }
+}
+
+#[test]
+fn test_verify_truncated_file() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let layout = inspect(&data).expect("Failed to inspect");
+    let tile = &layout.tiles[3];
+
+    // The file ends in the middle of the fourth tile, so the `QPIX` chunk at
+    // byte 20 runs past the end.
+    let error = verify(&data[..tile.offset + 1], true).unwrap_err();
+    assert_eq!(error.offset, 20);
+
+    let error = verify(&data[..10], true).unwrap_err();
+    assert_eq!(error.offset, 0);
+}
+
+#[test]
+fn test_verify_corrupt_tile() {
+    let data = read_test_file("at-mouquins.qoir");
+    let layout = inspect(&data).expect("Failed to inspect");
+    let tile = &layout.tiles[5];
+    let mut corrupt = data.clone();
+    corrupt[tile.offset..tile.offset + tile.compressed_len].fill(0xFF);
+
+    // The tile headers are intact, so only a full check notices.
+    verify(&corrupt, true).expect("Headers should be intact");
+    let error = verify(&corrupt, false).unwrap_err();
+    if cfg!(feature = "rust-backend") {
+        // The offset of the tile's header.
+        assert_eq!(error.offset, tile.offset - 4);
+    } else {
+        assert_eq!(error.offset, 20);
+    }
+
+    // An unknown tile format is found from the headers alone.
+    let mut corrupt = data;
+    corrupt[tile.offset - 1] = 0x7F;
+    assert_eq!(verify(&corrupt, true).unwrap_err().offset, tile.offset - 4);
+}
+
+fn with_checksum(data: &[u8]) -> Vec<u8> {
+    let decoded = decode_from_memory(data, DecodeOptions::default()).expect("Failed to decode");
+    let options = EncodeOptions {
+        embed_checksum: true,
+        ..Default::default()
+    };
+    encode_to_memory(decoded.image.clone(), options)
+        .expect("Failed to encode")
+        .data
+        .to_vec()
+}
+
+#[test]
+fn test_verify_integrity() {
+    let original = read_test_file("hibiscus.regular.qoir");
+    assert!(matches!(
+        verify_integrity(&original),
+        Err(IntegrityError::MissingChecksum)
+    ));
+
+    let data = with_checksum(&original);
+    verify_integrity(&data).expect("Checksum should match");
+    verify(&data, true).expect("Checksum should match");
+    let decoded = decode_from_memory(&data, DecodeOptions::default()).unwrap();
+    let expected = decode_from_memory(&original, DecodeOptions::default()).unwrap();
+    assert_eq!(decoded.image.pixels, expected.image.pixels);
+
+    // Editing the metadata keeps the checksum valid.
+    let edit = MetadataEdit {
+        xmp: MetadataChange::Set(b"<xmp/>"),
+        ..Default::default()
+    };
+    verify_integrity(&rewrite_metadata(&data, &edit).unwrap()).unwrap();
+}
+
+#[test]
+fn test_verify_integrity_detects_bit_rot() {
+    let data = with_checksum(&read_test_file("at-mouquins.qoir"));
+    let layout = inspect(&data).unwrap();
+    let tile = &layout.tiles[5];
+
+    // One flipped bit in a tile still decodes, but fails the checksum.
+    let mut corrupt = data.clone();
+    corrupt[tile.offset + tile.compressed_len / 2] ^= 0x01;
+    let Err(IntegrityError::Mismatch { stored, computed }) = verify_integrity(&corrupt) else {
+        panic!("Expected a checksum mismatch");
+    };
+    assert_ne!(stored, computed);
+    // `verify` reports the `QSUM` chunk, just before the final `QEND`.
+    let error = verify(&corrupt, true).unwrap_err();
+    assert_eq!(error.offset, data.len() - 12 - 16);
+
+    assert!(matches!(
+        verify_integrity(&data[..data.len() - 1]),
+        Err(IntegrityError::Invalid(_))
+    ));
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "windows")]
+
+mod common;
+
+use common::read_test_file;
+use qoir_rs::windows::{BITMAP_INFO_HEADER_LEN, BitmapInfoHeader, decode_dib};
+use qoir_rs::{DecodeOptions, Error, Image, PixelFormat, decode_from_memory};
+
+#[test]
+fn test_decode_dib() {
+    let data = read_test_file("hibiscus.regular.qoir");
+    let dib = decode_dib(&data).expect("Failed to decode");
+    let image = &dib.image.image;
+    assert_eq!(image.pixel_format, PixelFormat::BGRAPremul);
+    assert_eq!(image.stride_in_bytes % 4, 0);
+
+    let header = dib.header;
+    assert_eq!(header.size as usize, BITMAP_INFO_HEADER_LEN);
+    assert_eq!(
+        core::mem::size_of::<BitmapInfoHeader>(),
+        BITMAP_INFO_HEADER_LEN
+    );
+    assert_eq!(header.width, image.width as i32);
+    assert_eq!(header.height, -(image.height as i32));
+    assert_eq!((header.planes, header.bit_count), (1, 32));
+    assert_eq!(header.size_image as usize, dib.pixels().len());
+
+    let options = DecodeOptions {
+        pixel_format: PixelFormat::BGRAPremul,
+        ..Default::default()
+    };
+    let decoded = decode_from_memory(&data, options).unwrap();
+    assert_eq!(dib.pixels(), decoded.image.pixels);
+
+    let packed = dib.to_packed();
+    assert_eq!(packed[..BITMAP_INFO_HEADER_LEN], header.to_bytes());
+    assert_eq!(&packed[BITMAP_INFO_HEADER_LEN..], dib.pixels());
+}
+
+#[test]
+fn test_bitmap_info_header_bytes() {
+    let pixels = [0u8; 3 * 2 * 4];
+    let image = Image {
+        pixels: &pixels,
+        width: 3,
+        height: 2,
+        pixel_format: PixelFormat::BGRX,
+        stride_in_bytes: 12,
+    };
+    let header = BitmapInfoHeader::for_image(&image).unwrap();
+    let bytes = header.to_bytes();
+    assert_eq!(bytes[..4], 40u32.to_le_bytes());
+    assert_eq!(bytes[4..8], 3i32.to_le_bytes());
+    assert_eq!(bytes[8..12], (-2i32).to_le_bytes());
+    assert_eq!(bytes[12..16], [1, 0, 32, 0]);
+    assert_eq!(bytes[20..24], 24u32.to_le_bytes());
+    assert!(bytes[24..].iter().all(|&b| b == 0));
+
+    // Bitmaps are BGRA without padding between rows.
+    let rgba = Image {
+        pixel_format: PixelFormat::RGBANonPremul,
+        ..image.clone()
+    };
+    assert!(matches!(
+        BitmapInfoHeader::for_image(&rgba),
+        Err(Error::InvalidParameter)
+    ));
+    let padded = Image {
+        stride_in_bytes: 16,
+        ..image
+    };
+    assert!(matches!(
+        BitmapInfoHeader::for_image(&padded),
+        Err(Error::InvalidParameter)
+    ));
+}
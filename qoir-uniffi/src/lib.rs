@@ -0,0 +1,200 @@
+//! # qoir-uniffi
+//!
+//! [UniFFI](https://mozilla.github.io/uniffi-rs/) bindings for `qoir-rs`, so
+//! that Swift and Kotlin apps can decode and encode QOIR images natively.
+//!
+//! Every value crossing the boundary is owned: decoded images are returned as
+//! [`QoirImage`] records holding their own pixel buffer.
+//!
+//! Build the library, then generate the bindings from it:
+//!
+//! ```bash
+//! cargo build --release -p qoir-uniffi
+//! cargo run -p qoir-uniffi --features cli --bin uniffi-bindgen -- \
+//!     generate --library target/release/libqoir_uniffi.so --language swift --out-dir out
+//! ```
+
+use qoir_rs::{DecodeOptions, EncodeOptions, Error, Image, OwnedDecodedImage, PixelFormat};
+
+uniffi::setup_scaffolding!();
+
+/// Errors reported to foreign code, mirroring `qoir_rs::Error`.
+#[derive(Debug, thiserror::Error, uniffi::Error)]
+pub enum QoirError {
+    #[error("Invalid parameter")]
+    InvalidParameter,
+    #[error("Decoding failed: {message}")]
+    DecodingFailed { message: String },
+    #[error("Encoding failed: {message}")]
+    EncodingFailed { message: String },
+    #[error("File not found")]
+    FileNotFound,
+    #[error("I/O error: {message}")]
+    IoError { message: String },
+    #[error("Internal error: {message}")]
+    Internal { message: String },
+}
+
+impl From<Error> for QoirError {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidParameter => QoirError::InvalidParameter,
+            Error::DecodingFailed(message) => QoirError::DecodingFailed { message },
+            Error::EncodingFailed(message) => QoirError::EncodingFailed { message },
+            Error::Internal(message) => QoirError::Internal { message },
+            Error::FileNotFound => QoirError::FileNotFound,
+            error @ Error::IoError { .. } => QoirError::IoError {
+                message: error.to_string(),
+            },
+        }
+    }
+}
+
+/// The pixel formats that can be decoded into or encoded from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum QoirPixelFormat {
+    Bgrx,
+    BgraNonPremul,
+    BgraPremul,
+    Bgr,
+    Rgbx,
+    RgbaNonPremul,
+    RgbaPremul,
+    Rgb,
+}
+
+impl From<QoirPixelFormat> for PixelFormat {
+    fn from(pixel_format: QoirPixelFormat) -> Self {
+        match pixel_format {
+            QoirPixelFormat::Bgrx => PixelFormat::BGRX,
+            QoirPixelFormat::BgraNonPremul => PixelFormat::BGRANonPremul,
+            QoirPixelFormat::BgraPremul => PixelFormat::BGRAPremul,
+            QoirPixelFormat::Bgr => PixelFormat::BGR,
+            QoirPixelFormat::Rgbx => PixelFormat::RGBX,
+            QoirPixelFormat::RgbaNonPremul => PixelFormat::RGBANonPremul,
+            QoirPixelFormat::RgbaPremul => PixelFormat::RGBAPremul,
+            QoirPixelFormat::Rgb => PixelFormat::RGB,
+        }
+    }
+}
+
+impl TryFrom<PixelFormat> for QoirPixelFormat {
+    type Error = QoirError;
+
+    fn try_from(pixel_format: PixelFormat) -> Result<Self, Self::Error> {
+        Ok(match pixel_format {
+            PixelFormat::BGRX => QoirPixelFormat::Bgrx,
+            PixelFormat::BGRANonPremul => QoirPixelFormat::BgraNonPremul,
+            PixelFormat::BGRAPremul => QoirPixelFormat::BgraPremul,
+            PixelFormat::BGR => QoirPixelFormat::Bgr,
+            PixelFormat::RGBX => QoirPixelFormat::Rgbx,
+            PixelFormat::RGBANonPremul => QoirPixelFormat::RgbaNonPremul,
+            PixelFormat::RGBAPremul => QoirPixelFormat::RgbaPremul,
+            PixelFormat::RGB => QoirPixelFormat::Rgb,
+            PixelFormat::Invalid => return Err(QoirError::InvalidParameter),
+        })
+    }
+}
+
+/// The width, height and pixel format of an encoded image.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct QoirMetadata {
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: QoirPixelFormat,
+}
+
+/// An uncompressed image, with its rows `stride_in_bytes` apart.
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+pub struct QoirImage {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub pixel_format: QoirPixelFormat,
+    pub stride_in_bytes: u64,
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl TryFrom<OwnedDecodedImage> for QoirImage {
+    type Error = QoirError;
+
+    fn try_from(image: OwnedDecodedImage) -> Result<Self, Self::Error> {
+        Ok(QoirImage {
+            pixel_format: image.pixel_format.try_into()?,
+            pixels: image.pixels,
+            width: image.width,
+            height: image.height,
+            stride_in_bytes: image.stride_in_bytes as u64,
+            icc_profile: image.icc_profile,
+        })
+    }
+}
+
+/// Options for [`encode`].
+#[derive(Debug, Clone, Default, uniffi::Record)]
+pub struct QoirEncodeOptions {
+    /// From 0 (lossless) to 7 (very lossy).
+    #[uniffi(default = 0)]
+    pub lossiness: u8,
+    #[uniffi(default = false)]
+    pub dither: bool,
+}
+
+/// Reads the width, height and pixel format of QOIR data without decoding it.
+#[uniffi::export]
+pub fn decode_metadata(data: Vec<u8>) -> Result<QoirMetadata, QoirError> {
+    let (width, height, pixel_format) = qoir_rs::decode_basic_metadata(&data)?;
+    Ok(QoirMetadata {
+        width,
+        height,
+        pixel_format: pixel_format.try_into()?,
+    })
+}
+
+/// Decodes QOIR data into `pixel_format`.
+#[uniffi::export]
+pub fn decode(data: Vec<u8>, pixel_format: QoirPixelFormat) -> Result<QoirImage, QoirError> {
+    let options = DecodeOptions {
+        pixel_format: pixel_format.into(),
+        ..Default::default()
+    };
+    qoir_rs::decode_from_memory(&data, options)?
+        .into_owned()
+        .try_into()
+}
+
+/// Encodes an image into QOIR data.
+#[uniffi::export]
+pub fn encode(image: QoirImage, options: QoirEncodeOptions) -> Result<Vec<u8>, QoirError> {
+    let stride_in_bytes =
+        usize::try_from(image.stride_in_bytes).map_err(|_| QoirError::InvalidParameter)?;
+    let pixel_format = PixelFormat::from(image.pixel_format);
+    let row_len = image.width as usize * pixel_format.bytes_per_pixel();
+    let len = match (image.height as usize).checked_sub(1) {
+        Some(rows) => stride_in_bytes
+            .checked_mul(rows)
+            .and_then(|len| len.checked_add(row_len))
+            .ok_or(QoirError::InvalidParameter)?,
+        None => 0,
+    };
+    if stride_in_bytes < row_len || image.pixels.len() < len {
+        return Err(QoirError::InvalidParameter);
+    }
+
+    let options = EncodeOptions {
+        icc_profile: image.icc_profile.clone(),
+        lossiness: options.lossiness,
+        dither: options.dither,
+        ..Default::default()
+    };
+    Ok(qoir_rs::encode_to_vec(
+        Image {
+            pixels: &image.pixels,
+            width: image.width,
+            height: image.height,
+            pixel_format,
+            stride_in_bytes,
+        },
+        options,
+    )?)
+}
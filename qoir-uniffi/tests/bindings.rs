@@ -0,0 +1,41 @@
+use qoir_uniffi::{QoirEncodeOptions, QoirError, QoirPixelFormat, decode, decode_metadata, encode};
+use std::fs;
+
+const TEST_DATA_DIR: &str = "../data";
+
+fn read_test_file(name: &str) -> Vec<u8> {
+    let file_path = format!("{}/{}", TEST_DATA_DIR, name);
+    fs::read(&file_path).unwrap_or_else(|_| panic!("Failed to read {}", file_path))
+}
+
+#[test]
+fn test_decode_metadata() {
+    let metadata = decode_metadata(read_test_file("at-mouquins.qoir")).unwrap();
+    assert_eq!((metadata.width, metadata.height), (193, 256));
+    assert_eq!(metadata.pixel_format, QoirPixelFormat::Bgrx);
+}
+
+#[test]
+fn test_round_trip() {
+    let image = decode(
+        read_test_file("at-mouquins.qoir"),
+        QoirPixelFormat::RgbaNonPremul,
+    )
+    .unwrap();
+    assert_eq!(image.pixels.len(), 193 * 256 * 4);
+
+    let encoded = encode(image.clone(), QoirEncodeOptions::default()).unwrap();
+    let decoded = decode(encoded, QoirPixelFormat::RgbaNonPremul).unwrap();
+    assert_eq!(decoded, image);
+}
+
+#[test]
+fn test_invalid_input() {
+    let result = decode(vec![0; 10], QoirPixelFormat::Rgb);
+    assert!(matches!(result, Err(QoirError::DecodingFailed { .. })));
+
+    let mut image = decode(read_test_file("at-mouquins.qoir"), QoirPixelFormat::Rgb).unwrap();
+    image.pixels.truncate(100);
+    let result = encode(image, QoirEncodeOptions::default());
+    assert!(matches!(result, Err(QoirError::InvalidParameter)));
+}